@@ -0,0 +1,194 @@
+//! Benchmarks for the node hot paths.
+//!
+//! Run with `cargo bench`: criterion compares each run with the previous one,
+//! so regressions between releases show up as a change in the report.
+
+use aiblock_network::configurations::DbMode;
+use aiblock_network::interfaces::{BlockchainItem, BlockchainItemMeta, StoredSerializingBlock};
+use aiblock_network::utils::{create_valid_transaction, validate_pow_block};
+use aiblock_network::wallet::{FundStore, WalletDb};
+use bincode::{deserialize, serialize};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::collections::BTreeMap;
+use tokio::runtime::Runtime;
+use tw_chain::crypto::sign_ed25519 as sign;
+use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::primitives::block::Block;
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxOut};
+use tw_chain::utils::script_utils::tx_is_valid;
+use tw_chain::utils::transaction_utils::{construct_address, construct_tx_hash};
+
+const BLOCK_TX_COUNTS: &[usize] = &[100, 1_000];
+const FUND_STORE_SIZES: &[usize] = &[1_000, 10_000];
+const CONCURRENT_QUERIES: usize = 8;
+
+/// Transactions spending `count` outputs owned by the same key, with the spent outputs.
+fn spending_transactions(
+    count: usize,
+) -> (BTreeMap<String, Transaction>, BTreeMap<OutPoint, TxOut>) {
+    let (pk, sk) = sign::gen_keypair();
+    let address = construct_address(&pk);
+
+    let mut txs = BTreeMap::new();
+    let mut utxo_set = BTreeMap::new();
+    for i in 0..count {
+        let prev_hash = format!("{i:06}");
+        let (hash, tx) = create_valid_transaction(&prev_hash, 0, &address, &pk, &sk);
+        let tx_out = TxOut {
+            value: Asset::Token(TokenAmount(1)),
+            locktime: 0,
+            script_public_key: Some(address.clone()),
+        };
+        utxo_set.insert(OutPoint::new(prev_hash, 0), tx_out);
+        txs.insert(hash, tx);
+    }
+    (txs, utxo_set)
+}
+
+/// Wallet holding `count` payments of one token each.
+fn wallet_with_payments(runtime: &Runtime, count: usize) -> WalletDb {
+    runtime.block_on(async {
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (address, _) = wallet.generate_payment_address().await.unwrap();
+        let payments = (0..count)
+            .map(|i| {
+                let out_p = OutPoint::new(format!("{i:06}"), 0);
+                (out_p, Asset::token_u64(1), address.clone(), 0)
+            })
+            .collect();
+        wallet
+            .save_usable_payments_to_wallet(payments, 0)
+            .await
+            .unwrap();
+        wallet
+    })
+}
+
+fn bench_tx_validation(c: &mut Criterion) {
+    let (txs, utxo_set) = spending_transactions(1);
+    let tx = txs.values().next().unwrap();
+
+    c.bench_function("tx_validation", |b| {
+        b.iter(|| tx_is_valid(tx, 0, |out_p| utxo_set.get(out_p)))
+    });
+}
+
+fn bench_block_assembly(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("block_assembly");
+    for count in BLOCK_TX_COUNTS {
+        let (txs, _) = spending_transactions(*count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &txs, |b, txs| {
+            b.iter(|| {
+                let mut block = Block::new();
+                block.transactions = txs.values().map(construct_tx_hash).collect();
+                runtime.block_on(block.set_txs_merkle_root_and_hash());
+                block
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_pow_hashing(c: &mut Criterion) {
+    let header = Block::new().header;
+
+    c.bench_function("pow_block_hash", |b| b.iter(|| validate_pow_block(&header)));
+}
+
+fn bench_input_selection(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("wallet_input_selection");
+    for count in FUND_STORE_SIZES {
+        let wallet = wallet_with_payments(&runtime, *count);
+        let required = Asset::token_u64(*count as u64 / 2);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &required,
+            |b, required| {
+                b.iter(|| {
+                    runtime
+                        .block_on(wallet.fetch_inputs_for_payment(required.clone()))
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_concurrent_wallet_api(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let wallet = wallet_with_payments(&runtime, FUND_STORE_SIZES[0]);
+    let required = Asset::token_u64(FUND_STORE_SIZES[0] as u64 / 2);
+
+    // Balance queries and address generation running along a payment construction
+    c.bench_function("wallet_concurrent_api", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let queries = (0..CONCURRENT_QUERIES).map(|_| {
+                    let wallet = wallet.clone();
+                    tokio::task::spawn_blocking(move || wallet.get_fund_store())
+                });
+                let queries = futures::future::join_all(queries);
+                let mut generating = wallet.clone();
+                let payment = wallet.fetch_inputs_for_payment(required.clone());
+                let (queries, _, payment) =
+                    tokio::join!(queries, generating.generate_payment_address(), payment);
+                (queries.len(), payment.unwrap())
+            })
+        })
+    });
+}
+
+fn bench_db_serialization(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (txs, _) = spending_transactions(BLOCK_TX_COUNTS[0]);
+    let fund_store = wallet_with_payments(&runtime, FUND_STORE_SIZES[0])
+        .get_fund_store()
+        .unwrap();
+    let block = StoredSerializingBlock {
+        block: {
+            let mut block = Block::new();
+            block.transactions = txs.keys().cloned().collect();
+            block
+        },
+    };
+    let tx = txs.values().next().unwrap().clone();
+    let item = BlockchainItem {
+        version: 0,
+        item_meta: BlockchainItemMeta::Tx {
+            block_num: 0,
+            tx_num: 0,
+        },
+        key: construct_tx_hash(&tx).into_bytes(),
+        data: serialize(&tx).unwrap(),
+        data_json: Vec::new(),
+    };
+
+    let mut group = c.benchmark_group("db_serialization");
+    group.bench_function("fund_store", |b| {
+        b.iter(|| deserialize::<FundStore>(&serialize(&fund_store).unwrap()).unwrap())
+    });
+    group.bench_function("block", |b| {
+        b.iter(|| deserialize::<StoredSerializingBlock>(&serialize(&block).unwrap()).unwrap())
+    });
+    group.bench_function("transaction", |b| {
+        b.iter(|| deserialize::<Transaction>(&serialize(&tx).unwrap()).unwrap())
+    });
+    group.bench_function("blockchain_item", |b| {
+        b.iter(|| deserialize::<BlockchainItem>(&serialize(&item).unwrap()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_tx_validation,
+    bench_block_assembly,
+    bench_pow_hashing,
+    bench_input_selection,
+    bench_concurrent_wallet_api,
+    bench_db_serialization
+);
+criterion_main!(benches);
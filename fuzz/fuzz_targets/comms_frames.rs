@@ -0,0 +1,15 @@
+//! Feed arbitrary bytes to the decoder used on peer connections:
+//! length-delimited frames deserialized into `CommMessage`.
+#![no_main]
+
+use aiblock_network::comms_handler::node::{frame_codec_builder, get_messages_stream};
+use futures::executor::block_on;
+use futures::StreamExt;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::FramedRead;
+
+fuzz_target!(|data: &[u8]| {
+    let sock_in = FramedRead::new(data, frame_codec_builder().new_codec());
+    let (messages, _close_tx) = get_messages_stream(sock_in);
+    let _ = block_on(messages.collect::<Vec<_>>());
+});
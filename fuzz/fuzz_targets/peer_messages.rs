@@ -0,0 +1,17 @@
+//! Feed arbitrary bytes to the deserialization of the messages nodes receive from peers.
+#![no_main]
+
+use aiblock_network::interfaces::{
+    CommMessage, MempoolRequest, MineRequest, PreLaunchRequest, StorageRequest, UserRequest,
+};
+use bincode::deserialize;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserialize::<CommMessage>(data);
+    let _ = deserialize::<MempoolRequest>(data);
+    let _ = deserialize::<StorageRequest>(data);
+    let _ = deserialize::<MineRequest>(data);
+    let _ = deserialize::<UserRequest>(data);
+    let _ = deserialize::<PreLaunchRequest>(data);
+});
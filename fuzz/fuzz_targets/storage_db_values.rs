@@ -0,0 +1,16 @@
+//! Feed arbitrary bytes to the deserialization of blockchain items stored in
+//! the storage database and sent to peers catching up.
+#![no_main]
+
+use aiblock_network::interfaces::{BlockchainItem, BlockchainItemMeta, StoredSerializingBlock};
+use aiblock_network::utils::DeserializedBlockchainItem;
+use bincode::deserialize;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserialize::<BlockchainItemMeta>(data);
+    let _ = deserialize::<StoredSerializingBlock>(data);
+    if let Ok(item) = deserialize::<BlockchainItem>(data) {
+        let _ = DeserializedBlockchainItem::from_item(&item);
+    }
+});
@@ -0,0 +1,18 @@
+//! Feed arbitrary bytes to the deserialization of values stored in the wallet database.
+#![no_main]
+
+use aiblock_network::wallet::{
+    AddressStore, FundStore, LockedCoinbase, MasterKeyStore, TransactionStore,
+};
+use bincode::deserialize;
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeSet;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserialize::<FundStore>(data);
+    let _ = deserialize::<BTreeSet<String>>(data);
+    let _ = deserialize::<TransactionStore>(data);
+    let _ = deserialize::<MasterKeyStore>(data);
+    let _ = deserialize::<LockedCoinbase>(data);
+    let _ = deserialize::<AddressStore>(data);
+});
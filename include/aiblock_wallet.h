@@ -0,0 +1,67 @@
+/*
+ * C bindings for AIBlock wallet operations.
+ *
+ * Build the library with:
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * Strings returned by these functions are owned by the caller and must be
+ * released with aiblock_string_free. Functions return NULL or false on
+ * failure, with the reason available from aiblock_last_error.
+ */
+
+#ifndef AIBLOCK_WALLET_H
+#define AIBLOCK_WALLET_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque wallet handle */
+typedef struct AiblockWallet AiblockWallet;
+
+/* Open a wallet database, or an in-memory wallet if path is NULL */
+AiblockWallet *aiblock_wallet_open(const char *path, const char *passphrase);
+
+/* Release a wallet handle */
+void aiblock_wallet_free(AiblockWallet *wallet);
+
+/* Generate a new address, saving its keys to the wallet.
+ * Returned as a checked address of the wallet network. */
+char *aiblock_wallet_new_address(AiblockWallet *wallet);
+
+/* Check a string is an address that can be paid to, checking the checksum of checked addresses */
+bool aiblock_address_is_valid(const char *address);
+
+/* Record an output paid to an address of the wallet, so it can be spent */
+bool aiblock_wallet_add_output(AiblockWallet *wallet, const char *tx_hash, int32_t index,
+                               uint64_t amount, const char *address);
+
+/* Sign data with the key of an address, returning the hex encoded signature */
+char *aiblock_wallet_sign(AiblockWallet *wallet, const char *address, const uint8_t *data,
+                          size_t data_len);
+
+/* Sign an output of the wallet as a transaction input, returning the JSON encoded TxIn */
+char *aiblock_wallet_sign_input(AiblockWallet *wallet, const char *tx_hash, int32_t index);
+
+/* Construct a signed payment from the wallet funds, returning the JSON encoded transaction.
+ * Checked addresses of another network than the wallet are refused.
+ * The change goes to excess_address, or to a new address of the wallet if it is NULL. */
+char *aiblock_wallet_make_payment(AiblockWallet *wallet, const char *address, uint64_t amount,
+                                  const char *excess_address);
+
+/* Reason of the last failure on the calling thread, or NULL.
+ * Owned by the library and valid until the next call on this thread. */
+const char *aiblock_last_error(void);
+
+/* Release a string returned by this library */
+void aiblock_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AIBLOCK_WALLET_H */
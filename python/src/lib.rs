@@ -0,0 +1,253 @@
+//! Python bindings for AIBlock user nodes and wallets.
+//!
+//! Exposes a `Wallet` over a wallet database, or an in-memory wallet, and a
+//! `UserNode` running in the background against a live or local network, so
+//! payment scenarios and chain queries can be scripted from Python.
+//!
+//! Structured values such as transactions and unspent outputs are returned JSON
+//! encoded, in the format used by the HTTP API.
+
+use aiblock_network::configurations::{DbMode, ExtraNodeParams, UserNodeConfig};
+use aiblock_network::db_utils::CustomDbSpec;
+use aiblock_network::wallet::{WalletDb, WalletDbError};
+use aiblock_network::UserNodeHandle;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::runtime::{Builder, Runtime};
+use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::primitives::transaction::{OutPoint, TxOut};
+use tw_chain::utils::transaction_utils::construct_tx_core;
+
+/// Wallet database, or in-memory wallet
+#[pyclass]
+struct Wallet {
+    runtime: Arc<Runtime>,
+    wallet: WalletDb,
+}
+
+#[pymethods]
+impl Wallet {
+    /// Open the wallet database at `path`, or an in-memory wallet if not given
+    #[new]
+    #[pyo3(signature = (path = None, passphrase = None))]
+    fn new(path: Option<String>, passphrase: Option<String>) -> PyResult<Self> {
+        let (db_mode, custom_db_spec) = match path {
+            Some(db_path) => (
+                DbMode::Live,
+                Some(CustomDbSpec {
+                    db_path,
+                    suffix: String::new(),
+                }),
+            ),
+            None => (DbMode::InMemory, None),
+        };
+
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(runtime_err)?;
+        let wallet =
+            WalletDb::new(db_mode, None, passphrase, custom_db_spec).map_err(runtime_err)?;
+        Ok(Self {
+            runtime: Arc::new(runtime),
+            wallet,
+        })
+    }
+
+    /// Generate a new address, saving its keys to the wallet
+    ///
+    /// The address is returned as a checked address of the wallet network.
+    fn new_address(&mut self) -> PyResult<String> {
+        self.runtime
+            .block_on(self.wallet.generate_checked_payment_address())
+            .map_err(runtime_err)
+    }
+
+    /// Addresses held by the wallet
+    fn addresses(&self) -> PyResult<Vec<String>> {
+        self.wallet.get_known_addresses().map_err(runtime_err)
+    }
+
+    /// Token amount and item amounts by genesis hash held by the wallet
+    fn balance(&self) -> PyResult<(u64, BTreeMap<String, u64>)> {
+        let fund_store = self.wallet.get_fund_store().map_err(runtime_err)?;
+        let total = fund_store.running_total();
+        Ok((total.tokens.0, total.items.clone()))
+    }
+
+    /// JSON encoded list of the unspent outputs held, with their address
+    fn utxos(&self) -> PyResult<String> {
+        let fund_store = self.wallet.get_fund_store().map_err(runtime_err)?;
+        let utxos = fund_store
+            .transactions()
+            .iter()
+            .map(|(out_point, asset)| {
+                Ok(serde_json::json!({
+                    "out_point": out_point,
+                    "address": self.wallet.get_transaction_address(out_point)?,
+                    "asset": asset,
+                }))
+            })
+            .collect::<Result<Vec<_>, WalletDbError>>()
+            .map_err(runtime_err)?;
+        serde_json::to_string(&utxos).map_err(runtime_err)
+    }
+
+    /// Record a token output paid to an address of the wallet, so it can be spent
+    fn add_output(
+        &mut self,
+        tx_hash: String,
+        index: i32,
+        amount: u64,
+        address: String,
+    ) -> PyResult<()> {
+        let address = self.payment_address(&address)?;
+        let out_p = OutPoint::new(tx_hash, index);
+        let payments = vec![(out_p, Asset::Token(TokenAmount(amount)), address, 0)];
+        let saved = self
+            .runtime
+            .block_on(self.wallet.save_usable_payments_to_wallet(payments, 0))
+            .map_err(runtime_err)?;
+
+        match saved.is_empty() {
+            true => Err(PyValueError::new_err("Address not in wallet")),
+            false => Ok(()),
+        }
+    }
+
+    /// Construct a signed payment from the wallet funds, returning the JSON encoded transaction
+    ///
+    /// The spent outputs are marked as spent in the wallet. The change goes to
+    /// `excess_address`, or to a new address of the wallet if not given.
+    #[pyo3(signature = (address, amount, locktime = None, excess_address = None))]
+    fn make_payment(
+        &mut self,
+        address: String,
+        amount: u64,
+        locktime: Option<u64>,
+        excess_address: Option<String>,
+    ) -> PyResult<String> {
+        let address = self.payment_address(&address)?;
+        let excess_address = excess_address
+            .map(|a| self.payment_address(&a))
+            .transpose()?;
+        let amount = TokenAmount(amount);
+        let tx_out = TxOut::new_token_amount(address, amount, locktime);
+        let (tx_ins, tx_outs) = self
+            .runtime
+            .block_on(self.wallet.fetch_tx_ins_and_tx_outs_provided_excess(
+                Asset::Token(amount),
+                vec![tx_out],
+                excess_address,
+            ))
+            .map_err(runtime_err)?;
+
+        let tx = construct_tx_core(tx_ins, tx_outs, None);
+        serde_json::to_string(&tx).map_err(runtime_err)
+    }
+}
+
+impl Wallet {
+    /// Address paid to on chain, decoding a checked address of the wallet network
+    fn payment_address(&self, address: &str) -> PyResult<String> {
+        self.wallet
+            .decode_payment_address(address)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// User node running in the background
+#[pyclass]
+struct UserNode {
+    runtime: Arc<Runtime>,
+    handle: Option<UserNodeHandle>,
+}
+
+#[pymethods]
+impl UserNode {
+    /// Start a user node from its JSON encoded configuration
+    ///
+    /// Use `"user_db_mode": "InMemory"` for a wallet that is not persisted.
+    #[new]
+    fn new(config: &str) -> PyResult<Self> {
+        let config: UserNodeConfig =
+            serde_json::from_str(config).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        let runtime = Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(runtime_err)?;
+        let handle = runtime
+            .block_on(UserNodeHandle::start(config, ExtraNodeParams::default()))
+            .map_err(runtime_err)?;
+        Ok(Self {
+            runtime: Arc::new(runtime),
+            handle: Some(handle),
+        })
+    }
+
+    /// Address the node listens on
+    fn address(&self) -> PyResult<String> {
+        Ok(self.handle()?.address().to_string())
+    }
+
+    /// Wallet of the node, sharing its database
+    fn wallet(&self) -> PyResult<Wallet> {
+        Ok(Wallet {
+            runtime: self.runtime.clone(),
+            wallet: self.handle()?.wallet_db().clone(),
+        })
+    }
+
+    /// Whether the node is connected to its mempool node
+    fn is_connected(&self) -> PyResult<bool> {
+        let handle = self.handle()?;
+        Ok(self.runtime.block_on(handle.is_connected()))
+    }
+
+    /// Queue a payment to an address, sent once the node processes it
+    #[pyo3(signature = (address, amount, locktime = None))]
+    fn make_payment(&self, address: String, amount: u64, locktime: Option<u64>) -> PyResult<()> {
+        let handle = self.handle()?;
+        handle
+            .make_payment(address, TokenAmount(amount), locktime)
+            .map_err(runtime_err)
+    }
+
+    /// Queue an update of the wallet from the UTXO set of the mempool node
+    fn update_wallet_from_utxo_set(&self) -> PyResult<()> {
+        let handle = self.handle()?;
+        handle.update_wallet_from_utxo_set().map_err(runtime_err)
+    }
+
+    /// Stop the node and wait for it to disconnect
+    fn shutdown(&mut self, py: Python<'_>) {
+        if let Some(handle) = self.handle.take() {
+            let runtime = self.runtime.clone();
+            py.allow_threads(move || runtime.block_on(handle.shutdown()));
+        }
+    }
+}
+
+impl UserNode {
+    /// Handle of the node, if not shut down
+    fn handle(&self) -> PyResult<&UserNodeHandle> {
+        self.handle
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("User node is shut down"))
+    }
+}
+
+/// Convert an error to a Python `RuntimeError`
+fn runtime_err<E: std::fmt::Display>(error: E) -> PyErr {
+    PyRuntimeError::new_err(error.to_string())
+}
+
+#[pymodule]
+fn aiblock(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Wallet>()?;
+    m.add_class::<UserNode>()?;
+    Ok(())
+}
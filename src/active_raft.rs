@@ -1,7 +1,8 @@
+use crate::clock::Clock;
 use crate::db_utils::{SimpleDb, SimpleDbError};
 use crate::raft::{
     CommitReceiver, RaftCmd, RaftCmdSender, RaftCommit, RaftCommitData, RaftData,
-    RaftMessageWrapper, RaftMsgReceiver, RaftNode,
+    RaftMessageWrapper, RaftMsgReceiver, RaftNode, SharedRaftMetrics,
 };
 use std::collections::{HashMap, VecDeque};
 use std::future::Future;
@@ -10,6 +11,15 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 
+/// Standby members of a raft group
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RaftStandby {
+    /// Number of last peers joining as non-voting learners.
+    pub learners: usize,
+    /// Time without hearing from a voter before the leader promotes a standby in its place.
+    pub failover_timeout: Option<Duration>,
+}
+
 /// Provide RAFT loop and in/out channels to interact with it.
 ///
 pub struct ActiveRaft {
@@ -28,26 +38,48 @@ pub struct ActiveRaft {
     committed_rx: Arc<Mutex<(CommitReceiver, VecDeque<RaftCommit>)>>,
     /// Map to the address of the peers.
     peer_addr: HashMap<u64, SocketAddr>,
+    /// Number of voting peers.
+    voters_len: usize,
     /// Collection of the peer this node is responsible to connect to.
     raft_peers_to_connect: Vec<SocketAddr>,
     /// Collection of the peer expected to be connected.
     raft_peer_addrs: Vec<SocketAddr>,
+    /// Metrics updated by the running RaftNode.
+    metrics: SharedRaftMetrics,
 }
 
 impl ActiveRaft {
     /// Create ActiveRaft, need to spawn the raft loop to use raft.
+    ///
+    /// ### Arguments
+    ///
+    /// * `node_idx`              - Index of this node in `node_specs`
+    /// * `node_specs`            - Addresses of all the peers, standbys last
+    /// * `use_raft`              - Whether to run raft or commit proposals directly
+    /// * `tick_timeout_duration` - Duration between raft ticks
+    /// * `standby`               - Standby members and failover to them
+    /// * `raft_db`               - Raft persistent database
+    /// * `clock`                 - Clock used for raft ticks
     pub fn new(
         node_idx: usize,
         node_specs: &[SocketAddr],
         use_raft: bool,
         tick_timeout_duration: Duration,
+        standby: RaftStandby,
         raft_db: SimpleDb,
+        clock: Clock,
     ) -> Self {
-        let peers: Vec<u64> = (0..node_specs.len()).map(|idx| idx as u64 + 1).collect();
+        let mut peers: Vec<u64> = (0..node_specs.len()).map(|idx| idx as u64 + 1).collect();
         let peer_id = peers[node_idx];
+        let voters_len = peers.len().saturating_sub(standby.learners).max(1);
+        let learners = peers.split_off(voters_len);
+        let failover_ticks = standby.failover_timeout.map(|timeout| {
+            (timeout.as_millis() / tick_timeout_duration.as_millis().max(1)) as usize
+        });
 
         let peer_addr_vec: Vec<(u64, SocketAddr)> = peers
             .iter()
+            .chain(&learners)
             .zip(node_specs.iter())
             .map(|(idx, spec)| (*idx, *spec))
             .filter(|(idx, _)| use_raft || *idx == peer_id)
@@ -57,6 +89,7 @@ impl ActiveRaft {
             raft::Config {
                 id: peer_id,
                 peers,
+                learners,
                 max_size_per_msg: 4096,
                 max_inflight_msgs: 256,
                 tag: format!("[id={peer_id}]"),
@@ -64,6 +97,8 @@ impl ActiveRaft {
             },
             raft_db,
             tick_timeout_duration,
+            failover_ticks,
+            clock,
         );
 
         let peer_addr: HashMap<u64, SocketAddr> = peer_addr_vec.iter().cloned().collect();
@@ -89,8 +124,10 @@ impl ActiveRaft {
             msg_out_rx: Arc::new(Mutex::new(raft_channels.msg_out_rx)),
             committed_rx: Arc::new(Mutex::new((raft_channels.committed_rx, VecDeque::new()))),
             peer_addr,
+            voters_len: if use_raft { voters_len } else { 1 },
             raft_peers_to_connect,
             raft_peer_addrs,
+            metrics: raft_channels.metrics,
         }
     }
 
@@ -106,7 +143,17 @@ impl ActiveRaft {
 
     /// Returns a map to the addresses of this raft's peers
     pub fn peers_len(&self) -> usize {
-        self.peer_addr.len()
+        self.voters_len
+    }
+
+    /// Whether this peer is a standby mirroring the raft log without voting
+    pub fn is_standby(&self) -> bool {
+        self.metrics.lock().unwrap().is_learner
+    }
+
+    /// Metrics of the raft loop
+    pub fn metrics(&self) -> SharedRaftMetrics {
+        self.metrics.clone()
     }
 
     /// All the peers to connect to when using raft.
@@ -159,7 +206,7 @@ impl ActiveRaft {
             if let Some(commit) = committed_rx.1.pop_front() {
                 return Some(commit);
             } else if let Some(commits) = committed_rx.0.recv().await {
-                committed_rx.1.extend(commits.into_iter());
+                committed_rx.1.extend(commits);
             }
         }
     }
@@ -204,4 +251,24 @@ impl ActiveRaft {
                 .unwrap();
         }
     }
+
+    /// Propose to replace a voting peer with a standby, false if not a raft peer
+    ///
+    /// ### Arguments
+    ///
+    /// * `replaced` - Address of the voting peer to replace
+    pub fn failover(&self, replaced: SocketAddr) -> bool {
+        let replaced_id = self
+            .peer_addr
+            .iter()
+            .find(|(_, addr)| **addr == replaced)
+            .map(|(id, _)| *id);
+        match replaced_id {
+            Some(replaced_id) if self.use_raft => {
+                self.cmd_tx.send(RaftCmd::Failover { replaced_id }).unwrap();
+                true
+            }
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,143 @@
+//! Client certificate authentication for the API.
+//!
+//! When configured, API connections are accepted only from clients presenting a
+//! certificate signed by one of the configured CAs. Clients whose certificate is
+//! valid for one of the configured DNS names get the API role of that name, which
+//! then decides the routes they can call whatever API key they send. Other
+//! authenticated clients still need the API keys configured for the routes.
+
+use super::utils::ApiRole;
+use crate::comms_handler::tcp_tls::{
+    new_api_client_auth_acceptor, verify_is_valid_for_dns_names, TcpTlsStream, TlsCertificate,
+};
+use crate::comms_handler::{CommsError, Result};
+use crate::configurations::{ApiClientAuthSpec, TlsPrivateInfo};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, warn};
+use warp::hyper::server::conn::Http;
+use warp::hyper::service::{service_fn, Service};
+use warp::{Filter, Rejection, Reply};
+
+/// API roles of the client of a connection, from its certificate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientCertRoles(pub Vec<ApiRole>);
+
+/// Client certificate authentication of the API
+#[derive(Clone)]
+pub struct ApiClientAuth {
+    acceptor: TlsAcceptor,
+    identity_roles: Vec<(String, ApiRole)>,
+}
+
+impl fmt::Debug for ApiClientAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiClientAuth")
+            .field("identity_roles", &self.identity_roles)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ApiClientAuth {
+    /// Client authentication for the API server of a node
+    ///
+    /// ### Arguments
+    ///
+    /// * `info` - Certificate and private key of the API server.
+    /// * `spec` - Trusted CAs and roles of the client identities.
+    pub fn from_spec(info: &TlsPrivateInfo, spec: &ApiClientAuthSpec) -> Result<Self> {
+        let identity_roles = spec
+            .identity_roles
+            .iter()
+            .map(|(name, role)| match ApiRole::from_name(role) {
+                Some(role) => Ok((name.clone(), role)),
+                None => Err(CommsError::ConfigError("Invalid API client role")),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            acceptor: new_api_client_auth_acceptor(info, &spec.pem_ca_certificates)?,
+            identity_roles,
+        })
+    }
+
+    /// Roles of the identities a client certificate is valid for
+    ///
+    /// ### Arguments
+    ///
+    /// * `cert` - Certificate of the client.
+    pub fn client_roles(&self, cert: &TlsCertificate) -> ClientCertRoles {
+        let roles = self
+            .identity_roles
+            .iter()
+            .filter(|(name, _)| {
+                verify_is_valid_for_dns_names(cert, std::iter::once(name.as_str())).is_ok()
+            })
+            .map(|(_, role)| *role)
+            .collect();
+        ClientCertRoles(roles)
+    }
+}
+
+/// Serve API routes over TLS to clients authenticated with their certificate only
+///
+/// ### Arguments
+///
+/// * `routes`       - API routes to serve.
+/// * `bind_address` - Address to listen on.
+/// * `client_auth`  - Client authentication of the API.
+pub async fn serve_with_client_auth<F, R>(
+    routes: F,
+    bind_address: SocketAddr,
+    client_auth: ApiClientAuth,
+) where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let listener = match TcpListener::bind(bind_address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to listen for API connections on {bind_address}: {e:?}");
+            return;
+        }
+    };
+    let client_auth = Arc::new(client_auth);
+
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept API connection: {e:?}");
+                continue;
+            }
+        };
+        let client_auth = client_auth.clone();
+        let service = warp::service(routes.clone());
+
+        tokio::spawn(async move {
+            // The handshake fails for clients without a trusted certificate
+            let stream = match client_auth.acceptor.accept(stream).await {
+                Ok(stream) => TcpTlsStream::Server(stream, peer_addr),
+                Err(e) => {
+                    warn!("Rejected API connection from {peer_addr}: {e:?}");
+                    return;
+                }
+            };
+            let roles = stream
+                .peer_tls_certificate()
+                .map(|cert| client_auth.client_roles(&cert))
+                .unwrap_or_default();
+
+            let service = service_fn(move |mut request| {
+                request.extensions_mut().insert(roles.clone());
+                service.clone().call(request)
+            });
+            if let Err(e) = Http::new().serve_connection(stream, service).await {
+                debug!("API connection from {peer_addr} closed: {e:?}");
+            }
+        });
+    }
+}
@@ -14,6 +14,7 @@ pub enum ApiErrorType {
     BlankPassphrase,
     InvalidRequestBody,
     CannotParseAddress,
+    InvalidPaymentAddress(String),
     CannotAccessWallet,
     CannotAccessUserNode,
     CannotAccessMinerNode,
@@ -27,6 +28,8 @@ pub enum ApiErrorType {
     MethodNotFound,
     MethodNotAllowed,
     BadRequest,
+    UnknownWallet(String),
+    NodeWalletOnly,
 }
 
 impl ApiError {
@@ -56,6 +59,9 @@ impl std::fmt::Display for ApiErrorType {
             ApiErrorType::BlankPassphrase => write!(f, "New passphrase cannot be blank"),
             ApiErrorType::InvalidRequestBody => write!(f, "Invalid request body"),
             ApiErrorType::CannotParseAddress => write!(f, "Cannot parse address"),
+            ApiErrorType::InvalidPaymentAddress(message) => {
+                write!(f, "Invalid payment address: {message}")
+            }
             ApiErrorType::CannotAccessWallet => write!(f, "Cannot access wallet"),
             ApiErrorType::CannotAccessUserNode => write!(f, "Cannot access user node"),
             ApiErrorType::CannotAccessMinerNode => write!(f, "Cannot access miner node"),
@@ -71,6 +77,8 @@ impl std::fmt::Display for ApiErrorType {
             ApiErrorType::MethodNotFound => write!(f, "Method not found"),
             ApiErrorType::MethodNotAllowed => write!(f, "Method not allowed"),
             ApiErrorType::BadRequest => write!(f, "Bad request"),
+            ApiErrorType::UnknownWallet(name) => write!(f, "Unknown wallet: {name}"),
+            ApiErrorType::NodeWalletOnly => write!(f, "Route only uses the node wallet"),
         }
     }
 }
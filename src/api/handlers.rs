@@ -4,35 +4,48 @@ use crate::api::responses::{
     APICreateResponseContent, CallResponse, JsonReply,
 };
 use crate::api::utils::map_string_err;
-use crate::comms_handler::Node;
+use crate::block_stats::get_block_stats;
+use crate::comms_handler::{Node, PeerIpFilter};
 use crate::configurations::MempoolNodeSharedConfig;
 use crate::constants::LAST_BLOCK_HASH_KEY;
 use crate::db_utils::SimpleDb;
+use crate::denomination::{checked_sub, deserialize_token_amount, Denomination};
+use crate::exchange::add_deposit_account;
 use crate::interfaces::{
     node_type_as_str, AddressesWithOutPoints, BlockchainItem, BlockchainItemMeta,
-    BlockchainItemType, DebugData, DruidPool, MempoolApi, MineApiRequest, MineRequest, NodeType,
-    OutPointData, StoredSerializingBlock, UserApiRequest, UserRequest, UtxoFetchType,
+    BlockchainItemType, DebugData, DruidPool, MempoolApi, MineApiRequest, MineRequest, NetworkId,
+    NodeHealth, NodeType, OutPointData, StoredSerializingBlock, UserApiRequest, UserRequest,
+    UtxoFetchType,
 };
 use crate::mempool::MempoolError;
 use crate::miner::{BlockPoWReceived, CurrentBlockWithMutex};
-use crate::storage::{get_stored_value_from_db, indexed_block_hash_key};
+use crate::output_data::{load_anchored_data, tx_output_data};
+use crate::raft::SharedRaftMetrics;
+use crate::spend_script::{construct_condition_tx_in, SpendCondition, SpendWitness};
+use crate::storage::{compact_storage_db, get_stored_value_from_db, indexed_block_hash_key};
 use crate::threaded_call::{self, ThreadedCallSender};
-use crate::utils::{decode_pub_key, decode_signature, StringError};
-use crate::wallet::{AddressStore, AddressStoreHex, WalletDb, WalletDbError};
+use crate::trade::load_trades;
+use crate::utils::{decode_pub_key, decode_signature, reload_log_filter, StringError};
+use crate::wallet::history::DEFAULT_TX_HISTORY_PAGE_SIZE;
+use crate::wallet::{
+    AddressStore, AddressStoreHex, WalletBackup, WalletDb, WalletDbError, WalletManager,
+};
 use crate::Response;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::str;
 use std::sync::{Arc, Mutex};
 use tracing::{debug, error};
-use tw_chain::constants::{D_DISPLAY_PLACES, TOTAL_TOKENS};
+use tw_chain::constants::TOTAL_TOKENS;
 use tw_chain::crypto::sign_ed25519::PublicKey;
 use tw_chain::primitives::asset::{Asset, ItemAsset, TokenAmount};
 use tw_chain::primitives::druid::DdeValues;
 use tw_chain::primitives::transaction::{GenesisTxHashSpec, OutPoint, Transaction, TxIn, TxOut};
 use tw_chain::script::lang::Script;
 use tw_chain::utils::transaction_utils::{construct_address_for, construct_tx_hash};
+use wallet_core::PaymentAddress;
 use warp::hyper::StatusCode;
 
 pub type DbgPaths = Vec<&'static str>;
@@ -60,19 +73,47 @@ struct WalletInfo {
     locked_total_tokens: u64,
     available_total: f64,
     available_total_tokens: u64,
+    confirmed_total: f64,
+    confirmed_total_tokens: u64,
+    pending_incoming_total: f64,
+    pending_incoming_total_tokens: u64,
+    locked_outgoing_total: f64,
+    locked_outgoing_total_tokens: u64,
     item_total: BTreeMap<String, u64>, /* DRS tx hash - amount */
     addresses: AddressesWithOutPoints,
+    labels: BTreeMap<String, String>, /* Address - label */
+    watch_only_total: f64,
+    watch_only_total_tokens: u64,
+    watch_only_addresses: AddressesWithOutPoints,
 }
 
 /// Encapsulated payment received from client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncapsulatedPayment {
     pub address: String,
+    #[serde(deserialize_with = "deserialize_token_amount")]
     pub amount: TokenAmount,
     pub passphrase: String,
     pub locktime: Option<u64>,
 }
 
+/// Trade offer received from client: tokens for an item asset of the peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeOfferData {
+    pub address: String,
+    #[serde(deserialize_with = "deserialize_token_amount")]
+    pub amount: TokenAmount,
+    pub genesis_hash: String,
+    pub passphrase: String,
+}
+
+/// Range of block numbers received from client, both ends included
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRange {
+    pub start: u64,
+    pub end: u64,
+}
+
 /// Item asset creation structure received from client
 ///
 /// This structure is used to create a item asset on EITHER
@@ -107,6 +148,12 @@ pub enum CreateTxInScript {
         /// Optional address version field
         address_version: Option<u64>,
     },
+    Condition {
+        /// Condition the previous_out is locked to
+        condition: SpendCondition,
+        /// Signatures and preimages satisfying the condition
+        witness: SpendWitness,
+    },
 }
 
 /// Information needed for the creaion of TxIn.
@@ -129,6 +176,9 @@ pub struct CreateTransaction {
     pub version: usize,
     pub fees: Option<Vec<TxOut>>,
     pub druid_info: Option<DdeValues>,
+    /// Network the transaction is made for, refused by nodes of another network
+    #[serde(default)]
+    pub network_id: Option<NetworkId>,
 }
 /// Struct received from client to change passphrase
 ///
@@ -139,6 +189,73 @@ pub struct ChangePassphraseData {
     pub new_passphrase: String,
 }
 
+/// Struct received from client to export an encrypted wallet backup
+///
+/// Entries will be encrypted with TLS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBackupData {
+    pub passphrase: String,
+}
+
+/// Struct received from client to export the mnemonic phrase of the wallet seed
+///
+/// Entries will be encrypted with TLS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportMnemonicData {
+    pub passphrase: String,
+}
+
+/// Struct received from client to fetch a page of the transaction history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxHistoryData {
+    #[serde(default)]
+    pub page: usize,
+    pub page_size: Option<usize>,
+}
+
+/// Struct received from client to label a wallet address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressLabelData {
+    pub address: String,
+    /// Label to give, or None to clear it
+    pub label: Option<String>,
+}
+
+/// Struct received from client to watch addresses without their keys
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportWatchOnlyData {
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Hex encoded public keys, watched by their address
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+    pub address_version: Option<u64>,
+}
+
+/// Struct received from client to import an encrypted wallet backup
+///
+/// Entries will be encrypted with TLS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportBackupData {
+    pub passphrase: String,
+    pub backup: WalletBackup,
+}
+
+/// Struct received from client to back up the wallet to, or restore it from, a file of the node
+///
+/// Entries will be encrypted with TLS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletFileData {
+    pub path: String,
+    pub passphrase: String,
+}
+
+/// Whether to start or stop the dust consolidation, received from client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DustConsolidationData {
+    pub active: bool,
+}
+
 /// Struct received from client to construct address
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct AddressConstructData {
@@ -147,6 +264,27 @@ pub struct AddressConstructData {
     pub version: Option<u64>,
 }
 
+/// Struct received from client to generate a deposit address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositAddressData {
+    pub external_id: String,
+}
+
+/// Struct received from client to change tracing filter directives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFilterData {
+    pub directives: String,
+}
+
+/// Struct received from client to change the IP ranges of accepted peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerIpFilterData {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
 /// Struct received from client to fetch pending
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FetchPendingData {
@@ -172,10 +310,14 @@ pub async fn get_wallet_info(
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new(route, &call_id);
 
-    let mut fund_store = match wallet_db.get_fund_store_err() {
+    let mut fund_store = match wallet_db.get_fund_store() {
         Ok(fund) => fund,
         Err(_) => return r.into_err_internal(ApiErrorType::CannotAccessWallet),
     };
+    let balances = match wallet_db.get_fund_balances() {
+        Ok(balances) => balances,
+        Err(_) => return r.into_err_internal(ApiErrorType::CannotAccessWallet),
+    };
 
     let mut addresses = AddressesWithOutPoints::new();
     let txs;
@@ -195,8 +337,12 @@ pub async fn get_wallet_info(
     }
 
     for (out_point, asset) in txs {
+        let address = match wallet_db.get_transaction_address(&out_point) {
+            Ok(address) => address,
+            Err(_) => return r.into_err_internal(ApiErrorType::CannotAccessWallet),
+        };
         addresses
-            .entry(wallet_db.get_transaction_address(&out_point))
+            .entry(address)
             .or_default()
             .push(OutPointData::new(out_point.clone(), asset.clone()));
     }
@@ -206,15 +352,52 @@ pub async fn get_wallet_info(
         fund_store.filter_locked_coinbase(&locked_coinbase);
         fund_store.running_total()
     };
+    let denomination = Denomination::default();
+    let locked = checked_sub(total.tokens, available.tokens).unwrap_or_default();
+
+    let watch_only = match wallet_db.get_watch_only_store() {
+        Ok(watch_only) => watch_only,
+        Err(_) => return r.into_err_internal(ApiErrorType::CannotAccessWallet),
+    };
+    let mut watch_only_addresses: AddressesWithOutPoints = watch_only
+        .addresses()
+        .keys()
+        .map(|address| (address.clone(), Vec::new()))
+        .collect();
+    for (out_point, (asset, address)) in watch_only.outputs() {
+        watch_only_addresses
+            .entry(address.clone())
+            .or_default()
+            .push(OutPointData::new(out_point.clone(), asset.clone()));
+    }
+    let watch_only_total = watch_only.running_total().tokens;
+    let labels = match wallet_db.get_address_metadata() {
+        Ok(metadata) => metadata
+            .into_iter()
+            .filter_map(|(address, metadata)| Some((address, metadata.label?)))
+            .collect(),
+        Err(_) => return r.into_err_internal(ApiErrorType::CannotAccessWallet),
+    };
+
     let send_val = WalletInfo {
-        running_total: total.tokens.0 as f64 / D_DISPLAY_PLACES,
+        running_total: denomination.to_tokens_f64(total.tokens),
         running_total_tokens: total.tokens.0,
-        locked_total: (total.tokens.0 - available.tokens.0) as f64 / D_DISPLAY_PLACES,
-        locked_total_tokens: (total.tokens.0 - available.tokens.0),
-        available_total: available.tokens.0 as f64 / D_DISPLAY_PLACES,
+        locked_total: denomination.to_tokens_f64(locked),
+        locked_total_tokens: locked.0,
+        available_total: denomination.to_tokens_f64(available.tokens),
         available_total_tokens: available.tokens.0,
+        confirmed_total: denomination.to_tokens_f64(balances.confirmed.tokens),
+        confirmed_total_tokens: balances.confirmed.tokens.0,
+        pending_incoming_total: denomination.to_tokens_f64(balances.pending_incoming.tokens),
+        pending_incoming_total_tokens: balances.pending_incoming.tokens.0,
+        locked_outgoing_total: denomination.to_tokens_f64(balances.locked_outgoing.tokens),
+        locked_outgoing_total_tokens: balances.locked_outgoing.tokens.0,
         item_total: total.items,
         addresses,
+        labels,
+        watch_only_total: denomination.to_tokens_f64(watch_only_total),
+        watch_only_total_tokens: watch_only_total.0,
+        watch_only_addresses,
     };
 
     r.into_ok(
@@ -230,11 +413,17 @@ pub async fn get_export_keypairs(
     call_id: String,
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new(route, &call_id);
-    let known_addr = wallet_db.get_known_addresses();
+    let known_addr = match wallet_db.get_known_addresses() {
+        Ok(known_addr) => known_addr,
+        Err(e) => return wallet_db_error(e, r),
+    };
     let mut addresses = BTreeMap::new();
 
     for addr in known_addr {
-        addresses.insert(addr.clone(), wallet_db.get_address_store(&addr).into());
+        match wallet_db.get_address_store(&addr) {
+            Ok(store) => addresses.insert(addr.clone(), store.into()),
+            Err(e) => return wallet_db_error(e, r),
+        };
     }
 
     let response_data = Addresses { addresses };
@@ -245,20 +434,155 @@ pub async fn get_export_keypairs(
     )
 }
 
-/// Gets a newly generated payment address
+/// Gets the audit log of the inputs signed by the wallet
+pub async fn get_signing_audit_log(
+    wallet_db: WalletDb,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let entries = match wallet_db.get_signing_audit_log() {
+        Ok(entries) => entries,
+        Err(e) => return wallet_db_error(e, r),
+    };
+
+    r.into_ok(
+        "Signing audit log successfully retrieved",
+        json_serialize_embed(entries),
+    )
+}
+
+/// Post to get a page of the payments sent and received by the wallet, newest first
+pub async fn post_tx_history(
+    wallet_db: WalletDb,
+    info: TxHistoryData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let page_size = info.page_size.unwrap_or(DEFAULT_TX_HISTORY_PAGE_SIZE);
+    let history = match wallet_db.get_tx_history(info.page, page_size) {
+        Ok(history) => history,
+        Err(e) => return wallet_db_error(e, r),
+    };
+
+    r.into_ok(
+        "Transaction history successfully retrieved",
+        json_serialize_embed(history),
+    )
+}
+
+/// Gets the trades of the user node
+pub async fn get_trades(
+    wallet_db: WalletDb,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let trades = match load_trades(&wallet_db).await {
+        Ok(trades) => trades,
+        Err(e) => return wallet_db_error(e, r),
+    };
+    let trades: BTreeMap<_, _> = trades
+        .trades
+        .into_iter()
+        .map(|(id, trade)| {
+            let trade = serde_json::json!({
+                "role": trade.role,
+                "peer": trade.peer,
+                "state": trade.state,
+                "send_asset": trade.send_asset,
+                "receive_address": trade.receive_address,
+                "druid": trade.druid,
+                "created_at": trade.created_at,
+            });
+            (id, trade)
+        })
+        .collect();
+
+    r.into_ok(
+        "Trades successfully retrieved",
+        json_serialize_embed(trades),
+    )
+}
+
+/// Gets the names of the wallets selectable with the x-wallet-name header
+pub async fn get_wallets(
+    wallets: WalletManager,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    r.into_ok(
+        "Wallets successfully retrieved",
+        json_serialize_embed(wallets.names()),
+    )
+}
+
+/// Post to retrieve the data anchored by the given wallet transactions
+pub async fn post_anchored_data_user(
+    wallet_db: WalletDb,
+    tx_hashes: Vec<String>,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let mut anchored = match load_anchored_data(&wallet_db).await {
+        Ok(anchored) => anchored,
+        Err(e) => return wallet_db_error(e, r),
+    };
+    let anchored: BTreeMap<_, _> = tx_hashes
+        .into_iter()
+        .filter_map(|tx_hash| anchored.remove_entry(&tx_hash))
+        .collect();
+
+    r.into_ok(
+        "Anchored data successfully retrieved",
+        json_serialize_embed(anchored),
+    )
+}
+
+/// Gets a newly generated payment address, as a checked address of the node network
 pub async fn get_payment_address(
     mut wallet_db: WalletDb,
     route: &'static str,
     call_id: String,
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new(route, &call_id);
-    let (address, _) = wallet_db.generate_payment_address().await;
+    let address = match wallet_db.generate_checked_payment_address().await {
+        Ok(address) => address,
+        Err(e) => return wallet_db_error(e, r),
+    };
     r.into_ok(
         "New payment address generated",
         json_serialize_embed(address),
     )
 }
 
+/// Generates a deposit address tagged with a customer external id, returned as a checked address
+pub async fn post_deposit_address(
+    mut wallet_db: WalletDb,
+    deposit: DepositAddressData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let DepositAddressData { external_id } = deposit;
+
+    if external_id.is_empty() {
+        return r.into_err_bad_req(ApiErrorType::Generic("Blank external id".to_owned()));
+    }
+    let address = match add_deposit_account(&mut wallet_db, external_id).await {
+        Ok(address) => address,
+        Err(e) => return wallet_db_error(e, r),
+    };
+    let address = match wallet_db.encode_checked_address(&address) {
+        Ok(address) => address,
+        Err(e) => return wallet_db_error(e, r),
+    };
+    r.into_ok("Deposit address generated", json_serialize_embed(address))
+}
+
 /// Gets the latest block information
 pub async fn get_latest_block(
     db: Arc<Mutex<SimpleDb>>,
@@ -295,6 +619,7 @@ pub async fn get_debug_data(
                 node_api: debug_paths,
                 node_peers: [node_peers, aux_peers].concat(),
                 routes_pow,
+                event_queues: vec![node.event_queue_depth(), aux.event_queue_depth()],
             }
         }
         None => DebugData {
@@ -302,6 +627,7 @@ pub async fn get_debug_data(
             node_api: debug_paths,
             node_peers,
             routes_pow,
+            event_queues: vec![node.event_queue_depth()],
         },
     };
     r.into_ok(
@@ -310,6 +636,20 @@ pub async fn get_debug_data(
     )
 }
 
+/// Gets the metrics and health of the raft consensus
+pub async fn get_raft_metrics(
+    raft_metrics: SharedRaftMetrics,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let metrics = raft_metrics.lock().unwrap().clone();
+    r.into_ok(
+        "Raft metrics successfully retrieved",
+        json_serialize_embed(metrics),
+    )
+}
+
 /// Get to fetch information about the current mining block
 pub async fn get_current_mining_block(
     current_block: CurrentBlockWithMutex,
@@ -390,6 +730,68 @@ pub async fn get_issued_supply(
     )
 }
 
+/// GET the hashes of the transactions waiting in the mempool
+pub async fn get_mempool_transactions(
+    mut threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+
+    let tx_hashes = make_api_threaded_call(
+        &mut threaded_calls,
+        |c| c.get_tx_pool_hashes(),
+        "Cannot access Mempool Node",
+    )
+    .await
+    .map_err(|e| map_string_err(r.clone(), e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    r.into_ok(
+        "Mempool transactions successfully retrieved",
+        json_serialize_embed(tx_hashes),
+    )
+}
+
+/// POST the partition rounds of a range of blocks
+pub async fn post_partition_rounds(
+    mut threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    range: BlockRange,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+
+    let rounds = make_api_threaded_call(
+        &mut threaded_calls,
+        move |c| c.get_partition_rounds(range.start, range.end),
+        "Cannot access Mempool Node",
+    )
+    .await
+    .map_err(|e| map_string_err(r.clone(), e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    r.into_ok(
+        "Partition rounds successfully retrieved",
+        json_serialize_embed(rounds),
+    )
+}
+
+/// GET the health of the node
+pub async fn get_health(
+    node: Node,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+
+    let health = NodeHealth {
+        node_type: node_type_as_str(node.get_node_type()).to_owned(),
+        connected_peers: node.get_peer_list().await.len(),
+        event_queue: node.event_queue_depth(),
+    };
+
+    r.into_ok("Node is healthy", json_serialize_embed(health))
+}
+
 /// GET The total token supply in the system
 pub async fn get_total_supply(
     route: &'static str,
@@ -481,35 +883,201 @@ pub async fn post_import_keypairs(
         }
     }
 
-    match peer.get_node_type() {
-        NodeType::Miner => {
-            // Update running total from mempool node
-            if let Err(e) = peer.inject_next_event(
-                peer.local_address(),
-                MineRequest::MinerApi(MineApiRequest::RequestUTXOSet(UtxoFetchType::AnyOf(
-                    addresses,
-                ))),
-            ) {
-                error!("route:update_running_total error: {:?}", e);
-                return r.into_err_internal(ApiErrorType::CannotAccessMinerNode);
-            }
+    if let Err(e) = request_running_total_update(&peer, addresses) {
+        return r.into_err_internal(e);
+    }
+
+    r.into_ok("Key-pairs successfully imported", response_data)
+}
+
+/// Post to export the key-pairs of the connected wallet as an encrypted backup
+pub async fn post_export_backup(
+    db: WalletDb,
+    info: ExportBackupData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    if info.passphrase.is_empty() {
+        return r.into_err(StatusCode::UNAUTHORIZED, ApiErrorType::BlankPassphrase);
+    }
+
+    match db.export_key_backup(&info.passphrase) {
+        Ok(backup) => r.into_ok(
+            "Wallet backup successfully exported",
+            json_serialize_embed(backup),
+        ),
+        Err(e) => wallet_db_error(e, r),
+    }
+}
+
+/// Post to export the mnemonic phrase of the connected wallet seed, once the
+/// wallet passphrase is confirmed
+pub async fn post_export_mnemonic(
+    db: WalletDb,
+    info: ExportMnemonicData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    match db.export_mnemonic(info.passphrase).await {
+        Ok(mnemonic) => r.into_ok(
+            "Wallet mnemonic successfully exported",
+            json_serialize_embed(mnemonic),
+        ),
+        Err(WalletDbError::HdWalletError(e)) => {
+            r.into_err_bad_req(ApiErrorType::Generic(e.to_owned()))
         }
-        NodeType::User => {
-            // Update running total from mempool node
-            if let Err(e) = peer.inject_next_event(
-                peer.local_address(),
-                UserRequest::UserApi(UserApiRequest::UpdateWalletFromUtxoSet {
-                    address_list: UtxoFetchType::AnyOf(addresses),
-                }),
-            ) {
-                error!("route:update_running_total error: {:?}", e);
-                return r.into_err_internal(ApiErrorType::CannotAccessUserNode);
+        Err(e) => wallet_db_error(e, r),
+    }
+}
+
+/// Post to import the key-pairs of an encrypted backup to the connected wallet
+pub async fn post_import_backup(
+    peer: Node,
+    db: WalletDb,
+    info: ImportBackupData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let addresses = match db.import_key_backup(&info.backup, &info.passphrase).await {
+        Ok(addresses) => addresses,
+        Err(WalletDbError::PassphraseError) => {
+            return r.into_err(StatusCode::UNAUTHORIZED, ApiErrorType::InvalidPassphrase)
+        }
+        Err(e) => return r.into_err_bad_req(ApiErrorType::Generic(e.to_string())),
+    };
+
+    if let Err(e) = request_running_total_update(&peer, addresses.clone()) {
+        return r.into_err_internal(e);
+    }
+
+    r.into_ok(
+        "Wallet backup successfully imported",
+        json_serialize_embed(addresses),
+    )
+}
+
+/// Post to write an encrypted backup of the key-pairs and outputs of the connected
+/// wallet to a new file of the node
+pub async fn post_backup_wallet(
+    db: WalletDb,
+    info: WalletFileData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    if info.passphrase.is_empty() {
+        return r.into_err(StatusCode::UNAUTHORIZED, ApiErrorType::BlankPassphrase);
+    }
+
+    match db.export_backup(Path::new(&info.path), &info.passphrase) {
+        Ok(metadata) => r.into_ok(
+            "Wallet backup successfully written",
+            json_serialize_embed(metadata),
+        ),
+        Err(WalletDbError::IO(e)) => r.into_err_bad_req(ApiErrorType::Generic(e.to_string())),
+        Err(e) => wallet_db_error(e, r),
+    }
+}
+
+/// Post to restore the key-pairs and outputs of a backup file of the node to the
+/// connected wallet
+pub async fn post_restore_wallet(
+    peer: Node,
+    db: WalletDb,
+    info: WalletFileData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let path = Path::new(&info.path);
+    let addresses = match db.import_backup(path, &info.passphrase).await {
+        Ok(addresses) => addresses,
+        Err(WalletDbError::PassphraseError) => {
+            return r.into_err(StatusCode::UNAUTHORIZED, ApiErrorType::InvalidPassphrase)
+        }
+        Err(e) => return r.into_err_bad_req(ApiErrorType::Generic(e.to_string())),
+    };
+
+    if let Err(e) = request_running_total_update(&peer, addresses.clone()) {
+        return r.into_err_internal(e);
+    }
+
+    r.into_ok(
+        "Wallet successfully restored",
+        json_serialize_embed(addresses),
+    )
+}
+
+/// Post to set or clear the label of an address of the connected wallet
+pub async fn post_address_label(
+    db: WalletDb,
+    info: AddressLabelData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let AddressLabelData { address, label } = info;
+    match db.set_address_label(address.clone(), label).await {
+        Ok(()) => r.into_ok(
+            "Address label successfully set",
+            json_serialize_embed(address),
+        ),
+        Err(WalletDbError::MissingEntryError(_)) => r.into_err_bad_req(ApiErrorType::Generic(
+            "Address not held by the wallet".to_owned(),
+        )),
+        Err(WalletDbError::LabelError(e)) => {
+            r.into_err_bad_req(ApiErrorType::Generic(e.to_owned()))
+        }
+        Err(e) => wallet_db_error(e, r),
+    }
+}
+
+/// Post to watch addresses and public keys without their keys in the connected wallet
+pub async fn post_import_watch_only(
+    peer: Node,
+    db: WalletDb,
+    info: ImportWatchOnlyData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let mut public_keys = Vec::new();
+    for public_key in &info.public_keys {
+        let public_key = hex::decode(public_key).ok();
+        match public_key.and_then(|k| PublicKey::from_slice(&k)) {
+            Some(public_key) => public_keys.push(public_key),
+            None => {
+                return r.into_err_bad_req(ApiErrorType::Generic("Invalid public key".to_owned()))
             }
         }
-        _ => return r.into_err_internal(ApiErrorType::InternalError),
     }
 
-    r.into_ok("Key-pairs successfully imported", response_data)
+    let mut addresses = Vec::new();
+    for address in info.addresses {
+        if let Err(e) = db.import_watch_only_address(address.clone()).await {
+            return r.into_err_bad_req(ApiErrorType::Generic(e.to_string()));
+        }
+        addresses.push(address);
+    }
+    for public_key in public_keys {
+        let address = db.import_watch_only_public_key(public_key, info.address_version);
+        match address.await {
+            Ok(address) => addresses.push(address),
+            Err(e) => return r.into_err_bad_req(ApiErrorType::Generic(e.to_string())),
+        }
+    }
+
+    if let Err(e) = request_running_total_update(&peer, addresses.clone()) {
+        return r.into_err_internal(e);
+    }
+
+    r.into_ok(
+        "Watch-only addresses successfully imported",
+        json_serialize_embed(addresses),
+    )
 }
 
 ///Post make a new payment from the connected wallet
@@ -529,6 +1097,11 @@ pub async fn post_make_payment(
 
     let r = CallResponse::new(route, &call_id);
 
+    let network = peer.network_id().address_byte();
+    if let Err(e) = PaymentAddress::validate_for_network(&address, network) {
+        return r.into_err_bad_req(ApiErrorType::InvalidPaymentAddress(e.to_string()));
+    }
+
     let request = match db.test_passphrase(passphrase).await {
         Ok(_) => UserRequest::UserApi(UserApiRequest::MakePayment {
             address: address.clone(),
@@ -552,6 +1125,9 @@ pub async fn post_make_payment(
 }
 
 ///Post make a new payment from the connected wallet using an ip address
+///
+///The address paid to is received from the peer, and checked by the user node
+///before the payment is constructed
 pub async fn post_make_ip_payment(
     db: WalletDb,
     peer: Node,
@@ -597,6 +1173,49 @@ pub async fn post_make_ip_payment(
     )
 }
 
+/// Post an offer to trade tokens for an item asset of the user node at specified ip address
+pub async fn post_make_trade_offer(
+    db: WalletDb,
+    peer: Node,
+    trade_offer: TradeOfferData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let TradeOfferData {
+        address,
+        amount,
+        genesis_hash,
+        passphrase,
+    } = trade_offer;
+
+    let r = CallResponse::new(route, &call_id);
+
+    let trade_peer: SocketAddr = match address.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => {
+            return r.into_err_bad_req(ApiErrorType::CannotParseAddress);
+        }
+    };
+
+    let request = match db.test_passphrase(passphrase).await {
+        Ok(_) => UserRequest::UserApi(UserApiRequest::MakeTradeOffer {
+            trade_peer,
+            send_asset: Asset::Token(amount),
+            genesis_hash: Some(genesis_hash),
+        }),
+        Err(e) => {
+            return wallet_db_error(e, r);
+        }
+    };
+
+    if let Err(e) = peer.inject_next_event(peer.local_address(), request) {
+        error!("route:make_trade_offer error: {:?}", e);
+        return r.into_err_internal(ApiErrorType::CannotAccessUserNode);
+    }
+
+    r.into_ok("Trade offer processing", json_serialize_embed(address))
+}
+
 ///Post make a donation request from the user node at specified ip address
 pub async fn post_request_donation(
     peer: Node,
@@ -642,6 +1261,29 @@ pub async fn post_update_running_total(
     r.into_ok("Running total updated", json_serialize_embed("null"))
 }
 
+/// Post to start or stop the consolidation of the dust outputs of the wallet
+pub async fn post_dust_consolidation(
+    peer: Node,
+    data: DustConsolidationData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let DustConsolidationData { active } = data;
+    let request = UserRequest::UserApi(UserApiRequest::SetDustConsolidation { active });
+    let r = CallResponse::new(route, &call_id);
+
+    if let Err(e) = peer.inject_next_event(peer.local_address(), request) {
+        error!("route:dust_consolidation error: {:?}", e);
+        return r.into_err_internal(ApiErrorType::CannotAccessUserNode);
+    }
+
+    let reason = match active {
+        true => "Dust consolidation starting",
+        false => "Dust consolidation stopping",
+    };
+    r.into_ok(reason, json_serialize_embed(active))
+}
+
 /// Post to fetch the balance for given addresses in UTXO
 pub async fn post_fetch_utxo_balance(
     mut threaded_calls: ThreadedCallSender<dyn MempoolApi>,
@@ -778,7 +1420,7 @@ pub async fn post_create_item_asset(
         }
         false => r.into_err(
             StatusCode::INTERNAL_SERVER_ERROR,
-            ApiErrorType::Generic(mempool_resp.reason.to_owned()),
+            ApiErrorType::Generic(mempool_resp.reason.to_string()),
         ),
     }
 }
@@ -792,6 +1434,7 @@ pub async fn post_create_transactions(
 ) -> Result<JsonReply, JsonReply> {
     let r = CallResponse::new(route, &call_id);
 
+    let network_ids: Vec<NetworkId> = data.iter().filter_map(|tx| tx.network_id).collect();
     let transactions = data
         .into_iter()
         .map(to_transaction)
@@ -801,14 +1444,23 @@ pub async fn post_create_transactions(
     // Construct response
     let ctx_map = construct_ctx_map(&transactions);
 
-    // Send request to mempool node
+    // Send request to mempool node, unless a transaction is for another network
     let mempool_resp = make_api_threaded_call(
         &mut threaded_calls,
-        move |c| c.receive_transactions(transactions),
+        move |c| {
+            let network_id = c.get_network_id();
+            match network_ids.into_iter().find(|id| *id != network_id) {
+                Some(other) => Err(StringError(format!(
+                    "Transaction of network {other:?}, expected {network_id:?}"
+                ))),
+                None => Ok(c.receive_transactions(transactions)),
+            }
+        },
         "Cannot access Mempool Node",
     )
     .await
-    .map_err(|e| map_string_err(r.clone(), e, StatusCode::INTERNAL_SERVER_ERROR))?;
+    .map_err(|e| map_string_err(r.clone(), e, StatusCode::INTERNAL_SERVER_ERROR))?
+    .map_err(|e| map_string_err(r.clone(), e, StatusCode::BAD_REQUEST))?;
 
     // If the creation failed for some reason
     if !mempool_resp.success {
@@ -816,7 +1468,7 @@ pub async fn post_create_transactions(
             "route:post_create_transactions error: {:?}",
             mempool_resp.reason
         );
-        return r.into_err_internal(ApiErrorType::Generic(mempool_resp.reason.to_owned()));
+        return r.into_err_internal(ApiErrorType::Generic(mempool_resp.reason.to_string()));
     }
 
     r.into_ok("Transaction(s) processing", json_serialize_embed(ctx_map))
@@ -878,6 +1530,47 @@ pub async fn post_blocks_by_tx_hashes(
     )
 }
 
+/// Post to retrieve the data anchored by the given stored transactions
+pub async fn post_anchored_data(
+    db: Arc<Mutex<SimpleDb>>,
+    tx_hashes: Vec<String>,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let anchored: BTreeMap<_, _> = tx_hashes
+        .into_iter()
+        .filter_map(|tx_hash| {
+            let item = get_stored_value_from_db(db.clone(), tx_hash.clone())?;
+            if !matches!(item.item_meta, BlockchainItemMeta::Tx { .. }) {
+                return None;
+            }
+            let tx: Transaction = bincode::deserialize(&item.data).ok()?;
+            Some((tx_hash, tx_output_data(&tx)))
+        })
+        .collect();
+
+    r.into_ok(
+        "Anchored data successfully retrieved",
+        json_serialize_embed(anchored),
+    )
+}
+
+/// Post to retrieve the statistics of a range of stored blocks
+pub async fn post_block_stats(
+    db: Arc<Mutex<SimpleDb>>,
+    range: BlockRange,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let stats = get_block_stats(&db.lock().unwrap(), range.start, range.end);
+    r.into_ok(
+        "Block statistics successfully retrieved",
+        json_serialize_embed(stats),
+    )
+}
+
 //POST create a new payment address from a mempool node
 pub async fn post_payment_address_construction(
     data: AddressConstructData,
@@ -927,10 +1620,10 @@ pub async fn pause_nodes(
 
     if !res.success {
         debug!("route:pause_nodes error: {:?}", res.reason);
-        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_owned()));
+        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_string()));
     }
 
-    r.into_ok(res.reason, json_serialize_embed("null"))
+    r.into_ok(res.reason.as_str(), json_serialize_embed("null"))
 }
 
 //POST resume nodes in a coordinated manner
@@ -951,10 +1644,10 @@ pub async fn resume_nodes(
 
     if !res.success {
         debug!("route:resume_nodes error: {:?}", res.reason);
-        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_owned()));
+        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_string()));
     }
 
-    r.into_ok(res.reason, json_serialize_embed("null"))
+    r.into_ok(res.reason.as_str(), json_serialize_embed("null"))
 }
 
 //POST update a mempool node's config, sharing it to all other peers
@@ -976,14 +1669,169 @@ pub async fn update_shared_config(
 
     if !res.success {
         debug!("route:update_shared_config error: {:?}", res.reason);
-        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_owned()));
+        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_string()));
     }
 
-    r.into_ok(res.reason, json_serialize_embed("null"))
+    r.into_ok(res.reason.as_str(), json_serialize_embed("null"))
+}
+
+//POST change the tracing filter directives of the running node
+pub async fn post_log_filter(
+    log_filter: LogFilterData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let LogFilterData { directives } = log_filter;
+
+    match reload_log_filter(&directives) {
+        Ok(()) => r.into_ok("Log filter updated", json_serialize_embed(directives)),
+        Err(e) => {
+            debug!("route:log_filter error: {:?}", e);
+            r.into_err_bad_req(ApiErrorType::Generic(e.0))
+        }
+    }
+}
+
+//POST compact the wallet db
+pub async fn post_compact_wallet_db(
+    db: WalletDb,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    db.compact_db().await;
+    r.into_ok("Database compacted", json_serialize_embed("null"))
+}
+
+//POST compact the storage db
+pub async fn post_compact_storage_db(
+    db: Arc<Mutex<SimpleDb>>,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    compact_storage_db(db).await;
+    r.into_ok("Database compacted", json_serialize_embed("null"))
+}
+
+//POST compact the mempool db
+pub async fn post_compact_mempool_db(
+    mut threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let res = make_api_threaded_call(
+        &mut threaded_calls,
+        move |c| c.compact_db(),
+        "Cannot access Mempool Node",
+    )
+    .await
+    .map_err(|e| map_string_err(r.clone(), e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    r.into_ok(res.reason.as_str(), json_serialize_embed("null"))
+}
+
+//POST promote a standby mempool node in place of a failed one
+pub async fn post_failover(
+    mut threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    route: &'static str,
+    call_id: String,
+    replaced: SocketAddr,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let res = make_api_threaded_call(
+        &mut threaded_calls,
+        move |c| c.failover(replaced),
+        "Cannot access Mempool Node",
+    )
+    .await
+    .map_err(|e| map_string_err(r.clone(), e, StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    if !res.success {
+        debug!("route:failover error: {:?}", res.reason);
+        return r.into_err_internal(ApiErrorType::Generic(res.reason.to_string()));
+    }
+
+    r.into_ok(res.reason.as_str(), json_serialize_embed("null"))
+}
+
+//GET the IP ranges of the peers the node accepts and connects to
+pub async fn get_peer_ip_filter(
+    peer: Node,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let filter = peer.peer_ip_filter().await;
+    r.into_ok(
+        "Peer IP filter successfully retrieved",
+        json_serialize_embed(filter),
+    )
+}
+
+//POST change the IP ranges of the peers the node accepts and connects to
+pub async fn post_update_peer_ip_filter(
+    peer: Node,
+    filter: PeerIpFilterData,
+    route: &'static str,
+    call_id: String,
+) -> Result<JsonReply, JsonReply> {
+    let r = CallResponse::new(route, &call_id);
+    let filter = match PeerIpFilter::from_strs(&filter.allow, &filter.deny) {
+        Ok(filter) => filter,
+        Err(e) => return r.into_err_bad_req(ApiErrorType::Generic(e)),
+    };
+
+    match peer.set_peer_ip_filter(filter.clone()).await {
+        Ok(()) => r.into_ok("Peer IP filter updated", json_serialize_embed(filter)),
+        Err(e) => {
+            error!("route:update_peer_ip_filter error: {:?}", e);
+            r.into_err_internal(ApiErrorType::InternalError)
+        }
+    }
 }
 
 //======= Helpers =======//
 
+/// Request the node to update its running total for the given addresses from the mempool node
+///
+/// ### Arguments
+///
+/// * `peer`      - Miner or user node holding the wallet.
+/// * `addresses` - Addresses to update.
+fn request_running_total_update(peer: &Node, addresses: Vec<String>) -> Result<(), ApiErrorType> {
+    match peer.get_node_type() {
+        NodeType::Miner => {
+            // Update running total from mempool node
+            if let Err(e) = peer.inject_next_event(
+                peer.local_address(),
+                MineRequest::MinerApi(MineApiRequest::RequestUTXOSet(UtxoFetchType::AnyOf(
+                    addresses,
+                ))),
+            ) {
+                error!("route:update_running_total error: {:?}", e);
+                return Err(ApiErrorType::CannotAccessMinerNode);
+            }
+        }
+        NodeType::User => {
+            // Update running total from mempool node
+            if let Err(e) = peer.inject_next_event(
+                peer.local_address(),
+                UserRequest::UserApi(UserApiRequest::UpdateWalletFromUtxoSet {
+                    address_list: UtxoFetchType::AnyOf(addresses),
+                }),
+            ) {
+                error!("route:update_running_total error: {:?}", e);
+                return Err(ApiErrorType::CannotAccessUserNode);
+            }
+        }
+        _ => return Err(ApiErrorType::InternalError),
+    }
+    Ok(())
+}
+
 /// Filters through wallet errors which are internal vs errors caused by user input
 pub fn wallet_db_error(
     err: WalletDbError,
@@ -1010,6 +1858,7 @@ pub fn to_transaction(data: CreateTransaction) -> Result<Transaction, StringErro
         version,
         druid_info,
         fees,
+        network_id: _,
     } = data;
 
     let inputs = {
@@ -1017,27 +1866,30 @@ pub fn to_transaction(data: CreateTransaction) -> Result<Transaction, StringErro
         for i in inputs {
             let previous_out = with_opt_field(i.previous_out, "Invalid previous_out")?;
             let script_signature = with_opt_field(i.script_signature, "Invalid script_signature")?;
-            let tx_in = {
-                let CreateTxInScript::Pay2PkH {
+            let tx_in = match script_signature {
+                CreateTxInScript::Pay2PkH {
                     signable_data,
                     signature,
                     public_key,
                     address_version,
-                } = script_signature;
-
-                let signature =
-                    with_opt_field(decode_signature(&signature).ok(), "Invalid signature")?;
-                let public_key =
-                    with_opt_field(decode_pub_key(&public_key).ok(), "Invalid public_key")?;
-
-                TxIn {
-                    previous_out: Some(previous_out),
-                    script_signature: Script::pay2pkh(
-                        signable_data,
-                        signature,
-                        public_key,
-                        address_version,
-                    ),
+                } => {
+                    let signature =
+                        with_opt_field(decode_signature(&signature).ok(), "Invalid signature")?;
+                    let public_key =
+                        with_opt_field(decode_pub_key(&public_key).ok(), "Invalid public_key")?;
+
+                    TxIn {
+                        previous_out: Some(previous_out),
+                        script_signature: Script::pay2pkh(
+                            signable_data,
+                            signature,
+                            public_key,
+                            address_version,
+                        ),
+                    }
+                }
+                CreateTxInScript::Condition { condition, witness } => {
+                    construct_condition_tx_in(previous_out, &condition, &witness)
                 }
             };
 
@@ -1115,10 +1967,10 @@ pub fn get_json_reply_items_from_db(
 }
 
 /// Threaded call for API
-pub async fn make_api_threaded_call<'a, T: ?Sized, R: Send + Sized + Sync + 'static>(
+pub async fn make_api_threaded_call<T: ?Sized, R: Send + Sized + Sync + 'static>(
     tx: &mut ThreadedCallSender<T>,
     f: impl FnOnce(&mut T) -> R + Send + Sized + Sync + 'static,
-    tag: &'a str,
+    tag: &str,
 ) -> Result<R, StringError> {
     threaded_call::make_threaded_call(tx, f, tag).await
 }
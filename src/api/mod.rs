@@ -1,5 +1,6 @@
 //! This module provides API routes and handlers for API requests
 
+pub mod client_auth;
 pub mod errors;
 pub mod handlers;
 pub mod responses;
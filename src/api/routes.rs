@@ -1,15 +1,17 @@
 use crate::api::handlers::{self, DbgPaths};
 use crate::api::utils::{
-    auth_request, create_new_cache, handle_rejection, map_api_res_and_cache, warp_path,
-    with_node_component, ReplyCache, CACHE_LIVE_TIME,
+    auth_admin_request, auth_request, create_new_cache, handle_rejection, map_api_res_and_cache,
+    warp_path, with_node_component, with_node_wallet, with_wallet, ReplyCache, CACHE_LIVE_TIME,
 };
 use crate::comms_handler::Node;
 use crate::db_utils::SimpleDb;
 use crate::interfaces::MempoolApi;
 use crate::miner::CurrentBlockWithMutex;
+use crate::raft::SharedRaftMetrics;
 use crate::threaded_call::ThreadedCallSender;
 use crate::utils::{ApiKeys, RoutesPoWInfo};
-use crate::wallet::WalletDb;
+use crate::wallet::{WalletDb, WalletManager};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 
 use warp::{Filter, Rejection, Reply};
@@ -35,6 +37,7 @@ pub fn get_cors() -> warp::cors::Builder {
             "x-request-id",
             "x-nonce",
             "x-api-key",
+            "x-wallet-name",
         ])
         .allow_methods(vec!["GET"])
 }
@@ -42,7 +45,7 @@ pub fn get_cors() -> warp::cors::Builder {
 // GET wallet info
 pub fn wallet_info(
     dp: &mut DbgPaths,
-    db: WalletDb,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
@@ -51,7 +54,7 @@ pub fn wallet_info(
     warp_path(dp, route)
         .and(warp::get())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
+        .and(with_wallet(wallets.into(), route))
         .and(
             warp::path::param::<String>()
                 .map(Some)
@@ -72,7 +75,7 @@ pub fn wallet_info(
 // TODO: Requires password (will move to POST)
 pub fn export_keypairs(
     dp: &mut DbgPaths,
-    db: WalletDb,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
@@ -81,7 +84,7 @@ pub fn export_keypairs(
     warp_path(dp, route)
         .and(warp::get())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
+        .and(with_wallet(wallets.into(), route))
         .and(with_node_component(cache))
         .and_then(move |call_id: String, db, cache| {
             map_api_res_and_cache(
@@ -93,10 +96,132 @@ pub fn export_keypairs(
         .with(get_cors())
 }
 
+// GET audit log of the wallet signing operations
+pub fn signing_audit_log(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "signing_audit_log";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_wallet(wallets.into(), route))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_signing_audit_log(db, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
+// POST page of the transaction history of the wallet
+pub fn tx_history(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "tx_history";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_wallet(wallets.into(), route))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_tx_history(db, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// GET trades of the user node
+pub fn trades(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "trades";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_wallet(wallets.into(), route))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_trades(db, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
+// GET names of the wallets selectable by the user API
+pub fn wallet_names(
+    dp: &mut DbgPaths,
+    wallets: WalletManager,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "wallets";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(wallets))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, wallets, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_wallets(wallets, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
+// POST data anchored by the given wallet transactions
+pub fn anchored_data_user(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "anchored_data";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_wallet(wallets.into(), route))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_anchored_data_user(db, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
 // GET new payment address
 pub fn payment_address(
     dp: &mut DbgPaths,
-    db: WalletDb,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
@@ -105,7 +230,7 @@ pub fn payment_address(
     warp_path(dp, route)
         .and(warp::get())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
+        .and(with_wallet(wallets.into(), route))
         .and(with_node_component(cache))
         .and_then(move |call_id: String, db, cache| {
             map_api_res_and_cache(
@@ -117,6 +242,31 @@ pub fn payment_address(
         .with(get_cors())
 }
 
+// POST generate a deposit address for a customer
+pub fn deposit_address(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "deposit_address";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_wallet(wallets.into(), route))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_deposit_address(db, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
 // GET latest block
 pub fn latest_block(
     dp: &mut DbgPaths,
@@ -172,6 +322,30 @@ pub fn debug_data(
         .with(get_cors())
 }
 
+// GET raft consensus metrics and health
+pub fn raft_metrics(
+    dp: &mut DbgPaths,
+    raft_metrics: SharedRaftMetrics,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "raft_metrics";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(raft_metrics))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, metrics, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_raft_metrics(metrics, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
 // GET current block being mined
 pub fn current_mining_block(
     dp: &mut DbgPaths,
@@ -266,6 +440,79 @@ pub fn utxo_addresses(
         .with(get_cors())
 }
 
+// GET transactions waiting in the mempool
+pub fn mempool_transactions(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "mempool_transactions";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, tc, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_mempool_transactions(tc, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
+// POST partition rounds of a range of blocks
+pub fn partition_rounds(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "partition_rounds";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, tc, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_partition_rounds(tc, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// GET node health
+pub fn health(
+    dp: &mut DbgPaths,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "health";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(node))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, node, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_health(node, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
 // GET current config for node
 pub fn get_shared_config(
     dp: &mut DbgPaths,
@@ -311,6 +558,7 @@ pub fn post_cors() -> warp::cors::Builder {
             "x-request-id",
             "x-nonce",
             "x-api-key",
+            "x-wallet-name",
         ])
         .allow_methods(vec!["POST"])
 }
@@ -394,7 +642,7 @@ pub fn transactions_by_key(
 // TODO: Requires password
 pub fn import_keypairs(
     dp: &mut DbgPaths,
-    db: WalletDb,
+    wallets: impl Into<WalletManager>,
     node: Node,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
@@ -404,7 +652,7 @@ pub fn import_keypairs(
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
+        .and(with_node_wallet(wallets.into(), route))
         .and(with_node_component(node))
         .and(warp::body::json())
         .and(with_node_component(cache))
@@ -418,187 +666,420 @@ pub fn import_keypairs(
         .with(post_cors())
 }
 
-// POST make payment
-pub fn make_payment(
+// POST export encrypted wallet backup
+pub fn export_backup(
     dp: &mut DbgPaths,
-    db: WalletDb,
-    node: Node,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "make_payment";
+    let route = "export_backup";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
-        .and(with_node_component(node))
+        .and(with_wallet(wallets.into(), route))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, db, node, pi, cache| {
+        .and_then(move |call_id: String, db, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_make_payment(db, node, pi, route, call_id),
+                handlers::post_export_backup(db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-// POST make payment
-pub fn make_ip_payment(
+// POST export mnemonic phrase of the wallet seed
+pub fn export_mnemonic(
     dp: &mut DbgPaths,
-    db: WalletDb,
-    node: Node,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "make_ip_payment";
+    let route = "export_mnemonic";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
-        .and(with_node_component(node))
+        .and(with_wallet(wallets.into(), route))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, db, node, pi, cache| {
+        .and_then(move |call_id: String, db, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_make_ip_payment(db, node, pi, route, call_id),
+                handlers::post_export_mnemonic(db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-// POST request donation payment
-pub fn request_donation(
+// POST import encrypted wallet backup
+pub fn import_backup(
     dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
     node: Node,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "request_donation";
+    let route = "import_backup";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
+        .and(with_node_wallet(wallets.into(), route))
         .and(with_node_component(node))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, node, info, cache| {
+        .and_then(move |call_id: String, db, node, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_request_donation(node, info, route, call_id),
+                handlers::post_import_backup(node, db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-// POST update running total
-pub fn update_running_total(
+// POST write an encrypted backup of the wallet to a file of the node
+pub fn backup_wallet(
     dp: &mut DbgPaths,
-    node: Node,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "update_running_total";
+    let route = "backup_wallet";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(node))
+        .and(with_wallet(wallets.into(), route))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, node, info, cache| {
+        .and_then(move |call_id: String, db, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_update_running_total(node, info, route, call_id),
+                handlers::post_backup_wallet(db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-// POST fetch balance for addresses
-pub fn fetch_balance(
+// POST restore the wallet from a backup file of the node
+pub fn restore_wallet(
     dp: &mut DbgPaths,
-    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    wallets: impl Into<WalletManager>,
+    node: Node,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "fetch_balance";
+    let route = "restore_wallet";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(threaded_calls))
+        .and(with_node_wallet(wallets.into(), route))
+        .and(with_node_component(node))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, tc, info, cache| {
+        .and_then(move |call_id: String, db, node, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_fetch_utxo_balance(tc, info, route, call_id),
+                handlers::post_restore_wallet(node, db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-// POST fetch balance for addresses
-pub fn fetch_pending(
+// POST set the label of a wallet address
+pub fn address_label(
     dp: &mut DbgPaths,
-    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "fetch_pending";
+    let route = "address_label";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(threaded_calls))
+        .and(with_wallet(wallets.into(), route))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, tc, info, cache| {
+        .and_then(move |call_id: String, db, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_fetch_druid_pending(tc, info, route, call_id),
+                handlers::post_address_label(db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-// POST create item-based asset transaction
-pub fn create_item_asset(
+// POST import watch-only addresses and public keys
+pub fn import_watch_only(
     dp: &mut DbgPaths,
-    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    wallets: impl Into<WalletManager>,
+    node: Node,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    let route = "create_item_asset";
+    let route = "import_watch_only";
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(threaded_calls))
+        .and(with_node_wallet(wallets.into(), route))
+        .and(with_node_component(node))
         .and(warp::body::json())
         .and(with_node_component(cache))
-        .and_then(move |call_id: String, tc, info, cache| {
+        .and_then(move |call_id: String, db, node, info, cache| {
             map_api_res_and_cache(
                 call_id.clone(),
                 cache,
-                handlers::post_create_item_asset(tc, info, route, call_id),
+                handlers::post_import_watch_only(node, db, info, route, call_id),
             )
         })
         .with(post_cors())
 }
 
-/// POST create a item-based asset transaction on user
-pub fn create_item_asset_user(
+// POST make payment
+pub fn make_payment(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "make_payment";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_wallet(wallets.into(), route))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, node, pi, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_make_payment(db, node, pi, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST make payment
+pub fn make_ip_payment(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "make_ip_payment";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_wallet(wallets.into(), route))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, node, pi, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_make_ip_payment(db, node, pi, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST offer to trade tokens for an item asset of a peer
+pub fn make_trade_offer(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "make_trade_offer";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_wallet(wallets.into(), route))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, node, offer, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_make_trade_offer(db, node, offer, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST request donation payment
+pub fn request_donation(
+    dp: &mut DbgPaths,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "request_donation";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, node, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_request_donation(node, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST update running total
+pub fn update_running_total(
+    dp: &mut DbgPaths,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "update_running_total";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, node, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_update_running_total(node, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST start or stop the dust consolidation
+pub fn dust_consolidation(
+    dp: &mut DbgPaths,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "dust_consolidation";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, node, data, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_dust_consolidation(node, data, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST fetch balance for addresses
+pub fn fetch_balance(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "fetch_balance";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, tc, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_fetch_utxo_balance(tc, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST fetch balance for addresses
+pub fn fetch_pending(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "fetch_pending";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, tc, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_fetch_druid_pending(tc, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST create item-based asset transaction
+pub fn create_item_asset(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "create_item_asset";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, tc, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_create_item_asset(tc, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+/// POST create a item-based asset transaction on user
+pub fn create_item_asset_user(
     dp: &mut DbgPaths,
     node: Node,
     routes_pow: RoutesPoWInfo,
@@ -625,7 +1106,7 @@ pub fn create_item_asset_user(
 // POST change passphrase
 pub fn change_passphrase(
     dp: &mut DbgPaths,
-    db: WalletDb,
+    wallets: impl Into<WalletManager>,
     routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
     cache: ReplyCache,
@@ -634,7 +1115,7 @@ pub fn change_passphrase(
     warp_path(dp, route)
         .and(warp::post())
         .and(auth_request(routes_pow, api_keys))
-        .and(with_node_component(db))
+        .and(with_wallet(wallets.into(), route))
         .and(warp::body::json())
         .and(with_node_component(cache))
         .and_then(move |call_id: String, db, info, cache| {
@@ -697,6 +1178,56 @@ pub fn blocks_by_tx_hashes(
         .with(post_cors())
 }
 
+// POST statistics of a range of stored blocks
+pub fn block_stats(
+    dp: &mut DbgPaths,
+    db: Arc<Mutex<SimpleDb>>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    let route = "block_stats";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(db))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_block_stats(db, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST data anchored by the given stored transactions
+pub fn anchored_data(
+    dp: &mut DbgPaths,
+    db: Arc<Mutex<SimpleDb>>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    let route = "anchored_data";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_request(routes_pow, api_keys))
+        .and(with_node_component(db))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_anchored_data(db, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
 // POST construct payment address
 pub fn address_construction(
     dp: &mut DbgPaths,
@@ -794,6 +1325,177 @@ pub fn update_shared_config(
         .with(post_cors())
 }
 
+// POST change tracing filter directives at runtime
+pub fn log_filter(
+    dp: &mut DbgPaths,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "log_filter";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_log_filter(info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// GET the IP ranges of the peers the node accepts and connects to
+pub fn get_peer_ip_filter(
+    dp: &mut DbgPaths,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "get_peer_ip_filter";
+    warp_path(dp, route)
+        .and(warp::get())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(with_node_component(node))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, node, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::get_peer_ip_filter(node, route, call_id),
+            )
+        })
+        .with(get_cors())
+}
+
+// POST change the IP ranges of the peers the node accepts and connects to
+pub fn update_peer_ip_filter(
+    dp: &mut DbgPaths,
+    node: Node,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "update_peer_ip_filter";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(with_node_component(node))
+        .and(warp::body::json())
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, node, info, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_update_peer_ip_filter(node, info, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST compact the node db
+pub fn compact_wallet_db(
+    dp: &mut DbgPaths,
+    wallets: impl Into<WalletManager>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "compact_db";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(with_wallet(wallets.into(), route))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_compact_wallet_db(db, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST compact the node db
+pub fn compact_storage_db(
+    dp: &mut DbgPaths,
+    db: Arc<Mutex<SimpleDb>>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "compact_db";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(with_node_component(db))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, db, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_compact_storage_db(db, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST compact the node db
+pub fn compact_mempool_db(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "compact_db";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(with_node_component(cache))
+        .and_then(move |call_id: String, threaded_calls, cache| {
+            map_api_res_and_cache(
+                call_id.clone(),
+                cache,
+                handlers::post_compact_mempool_db(threaded_calls, route, call_id),
+            )
+        })
+        .with(post_cors())
+}
+
+// POST promote a standby mempool node in place of a failed one
+pub fn failover(
+    dp: &mut DbgPaths,
+    threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    cache: ReplyCache,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let route = "failover";
+    warp_path(dp, route)
+        .and(warp::post())
+        .and(auth_admin_request(routes_pow, api_keys))
+        .and(with_node_component(threaded_calls))
+        .and(with_node_component(cache))
+        .and(warp::body::json())
+        .and_then(
+            move |call_id: String, threaded_calls, cache, replaced: SocketAddr| {
+                map_api_res_and_cache(
+                    call_id.clone(),
+                    cache,
+                    handlers::post_failover(threaded_calls, route, call_id, replaced),
+                )
+            },
+        )
+        .with(post_cors())
+}
+
 //======= NODE ROUTES =======//
 //TODO: Nodes share similar routes; We need to find a way to reduce ambiguity
 
@@ -801,23 +1503,31 @@ pub fn update_shared_config(
 pub fn user_node_routes(
     api_keys: ApiKeys,
     routes_pow_info: RoutesPoWInfo,
-    db: WalletDb,
+    wallets: impl Into<WalletManager>,
     node: Node,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let mut dp_vec = DbgPaths::new();
     let dp = &mut dp_vec;
     let cache = create_new_cache(CACHE_LIVE_TIME);
+    let wallets: WalletManager = wallets.into();
 
     let routes = wallet_info(
         dp,
-        db.clone(),
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     )
+    .or(wallet_names(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(make_payment(
         dp,
-        db.clone(),
+        wallets.clone(),
         node.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
@@ -825,35 +1535,130 @@ pub fn user_node_routes(
     ))
     .or(make_ip_payment(
         dp,
-        db.clone(),
+        wallets.clone(),
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(request_donation(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(make_trade_offer(
+        dp,
+        wallets.clone(),
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(trades(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(anchored_data_user(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(export_keypairs(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(import_keypairs(
+        dp,
+        wallets.clone(),
         node.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
-    .or(request_donation(
+    .or(export_backup(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(export_mnemonic(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(import_backup(
+        dp,
+        wallets.clone(),
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(backup_wallet(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(restore_wallet(
+        dp,
+        wallets.clone(),
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(import_watch_only(
+        dp,
+        wallets.clone(),
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(address_label(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(signing_audit_log(
         dp,
-        node.clone(),
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
-    .or(export_keypairs(
+    .or(tx_history(
         dp,
-        db.clone(),
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
-    .or(import_keypairs(
+    .or(update_running_total(
         dp,
-        db.clone(),
         node.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
-    .or(update_running_total(
+    .or(dust_consolidation(
         dp,
         node.clone(),
         routes_pow_info.clone(),
@@ -869,14 +1674,21 @@ pub fn user_node_routes(
     ))
     .or(payment_address(
         dp,
-        db.clone(),
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(deposit_address(
+        dp,
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
     .or(change_passphrase(
         dp,
-        db,
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
@@ -887,6 +1699,33 @@ pub fn user_node_routes(
     //     api_keys.clone(),
     //     cache.clone(),
     // ))
+    .or(compact_wallet_db(
+        dp,
+        wallets,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(log_filter(
+        dp,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(get_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(update_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(debug_data(
         dp_vec,
         node,
@@ -904,6 +1743,7 @@ pub fn storage_node_routes(
     api_keys: ApiKeys,
     routes_pow_info: RoutesPoWInfo,
     db: Arc<Mutex<SimpleDb>>,
+    raft_metrics_info: SharedRaftMetrics,
     node: Node,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let mut dp_vec = DbgPaths::new();
@@ -917,13 +1757,13 @@ pub fn storage_node_routes(
         api_keys.clone(),
         cache.clone(),
     )
-    // .or(transactions_by_key(
-    //     dp,
-    //     db.clone(),
-    //     routes_pow_info.clone(),
-    //     api_keys.clone(),
-    //     cache.clone(),
-    // ))
+    .or(transactions_by_key(
+        dp,
+        db.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(latest_block(
         dp,
         db.clone(),
@@ -938,19 +1778,74 @@ pub fn storage_node_routes(
         api_keys.clone(),
         cache.clone(),
     ))
-    // .or(blocks_by_tx_hashes(
-    //     dp,
-    //     db,
-    //     routes_pow_info.clone(),
-    //     api_keys.clone(),
-    //     cache.clone(),
-    // ))
+    .or(blocks_by_tx_hashes(
+        dp,
+        db.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(anchored_data(
+        dp,
+        db.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(block_stats(
+        dp,
+        db.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     // .or(address_construction(
     //     dp,
     //     routes_pow_info.clone(),
     //     api_keys.clone(),
     //     cache.clone(),
     // ))
+    .or(raft_metrics(
+        dp,
+        raft_metrics_info,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(compact_storage_db(
+        dp,
+        db,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(log_filter(
+        dp,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(get_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(update_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(health(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(debug_data(
         dp_vec,
         node,
@@ -970,6 +1865,7 @@ pub fn mempool_node_routes(
     api_keys: ApiKeys,
     routes_pow_info: RoutesPoWInfo,
     threaded_calls: ThreadedCallSender<dyn MempoolApi>,
+    raft_metrics_info: SharedRaftMetrics,
     node: Node,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let mut dp_vec = DbgPaths::new();
@@ -1010,13 +1906,27 @@ pub fn mempool_node_routes(
         api_keys.clone(),
         cache.clone(),
     ))
-    // .or(utxo_addresses(
-    //     dp,
-    //     threaded_calls.clone(),
-    //     routes_pow_info.clone(),
-    //     api_keys.clone(),
-    //     cache.clone(),
-    // ))
+    .or(utxo_addresses(
+        dp,
+        threaded_calls.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(mempool_transactions(
+        dp,
+        threaded_calls.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(partition_rounds(
+        dp,
+        threaded_calls.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     // .or(address_construction(
     //     dp,
     //     routes_pow_info.clone(),
@@ -1051,6 +1961,54 @@ pub fn mempool_node_routes(
     //     api_keys.clone(),
     //     cache.clone(),
     // ))
+    .or(raft_metrics(
+        dp,
+        raft_metrics_info,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(compact_mempool_db(
+        dp,
+        threaded_calls.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(failover(
+        dp,
+        threaded_calls,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(log_filter(
+        dp,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(get_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(update_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(health(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(debug_data(
         dp_vec,
         node,
@@ -1097,6 +2055,28 @@ pub fn miner_node_routes(
         api_keys.clone(),
         cache.clone(),
     ))
+    .or(export_backup(
+        dp,
+        db.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(import_backup(
+        dp,
+        db.clone(),
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(signing_audit_log(
+        dp,
+        db.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(payment_address(
         dp,
         db.clone(),
@@ -1106,7 +2086,7 @@ pub fn miner_node_routes(
     ))
     .or(change_passphrase(
         dp,
-        db,
+        db.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
@@ -1124,6 +2104,33 @@ pub fn miner_node_routes(
     //     api_keys.clone(),
     //     cache.clone(),
     // ))
+    .or(compact_wallet_db(
+        dp,
+        db,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(log_filter(
+        dp,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(get_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(update_peer_ip_filter(
+        dp,
+        node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(debug_data(
         dp_vec,
         node,
@@ -1141,24 +2148,25 @@ pub fn miner_node_with_user_routes(
     api_keys: ApiKeys,
     routes_pow_info: RoutesPoWInfo,
     current_block: CurrentBlockWithMutex,
-    db: WalletDb, /* Shared WalletDb */
+    wallets: impl Into<WalletManager>, /* Shared WalletDb and named wallets */
     miner_node: Node,
     user_node: Node, /* Additional User `Node` */
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let mut dp_vec = DbgPaths::new();
     let dp = &mut dp_vec;
     let cache = create_new_cache(CACHE_LIVE_TIME);
+    let wallets: WalletManager = wallets.into();
 
     let routes = wallet_info(
         dp,
-        db.clone(),
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     )
     .or(make_payment(
         dp,
-        db.clone(),
+        wallets.clone(),
         user_node.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
@@ -1181,19 +2189,41 @@ pub fn miner_node_with_user_routes(
     // ))
     .or(export_keypairs(
         dp,
-        db.clone(),
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
     .or(import_keypairs(
         dp,
-        db.clone(),
+        wallets.clone(),
+        user_node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(export_backup(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(import_backup(
+        dp,
+        wallets.clone(),
         user_node.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
+    .or(signing_audit_log(
+        dp,
+        wallets.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(update_running_total(
         dp,
         user_node.clone(),
@@ -1210,14 +2240,14 @@ pub fn miner_node_with_user_routes(
     ))
     .or(payment_address(
         dp,
-        db.clone(),
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
     ))
     .or(change_passphrase(
         dp,
-        db,
+        wallets.clone(),
         routes_pow_info.clone(),
         api_keys.clone(),
         cache.clone(),
@@ -1235,6 +2265,33 @@ pub fn miner_node_with_user_routes(
     //     api_keys.clone(),
     //     cache.clone(),
     // ))
+    .or(compact_wallet_db(
+        dp,
+        wallets,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(log_filter(
+        dp,
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(get_peer_ip_filter(
+        dp,
+        miner_node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
+    .or(update_peer_ip_filter(
+        dp,
+        miner_node.clone(),
+        routes_pow_info.clone(),
+        api_keys.clone(),
+        cache.clone(),
+    ))
     .or(debug_data(
         dp_vec,
         miner_node,
@@ -1,29 +1,43 @@
+use crate::api::client_auth::ClientCertRoles;
 use crate::api::handlers::{
-    AddressConstructData, Addresses, ChangePassphraseData, CreateItemAssetDataMempool,
-    CreateItemAssetDataUser, CreateTransaction, CreateTxIn, CreateTxInScript, DbgPaths,
-    EncapsulatedPayment, FetchPendingData,
+    AddressConstructData, AddressLabelData, Addresses, BlockRange, ChangePassphraseData,
+    CreateItemAssetDataMempool, CreateItemAssetDataUser, CreateTransaction, CreateTxIn,
+    CreateTxInScript, DbgPaths, DepositAddressData, DustConsolidationData, EncapsulatedPayment,
+    ExportBackupData, ExportMnemonicData, FetchPendingData, ImportBackupData, ImportWatchOnlyData,
+    LogFilterData, PeerIpFilterData, TradeOfferData, TxHistoryData, WalletFileData,
 };
 use crate::api::routes;
-use crate::api::utils::{auth_request, create_new_cache, handle_rejection, CACHE_LIVE_TIME};
-use crate::comms_handler::{Event, Node, TcpTlsConfig};
+use crate::api::utils::{
+    auth_admin_request, auth_request, create_new_cache, handle_rejection, ApiRole, CACHE_LIVE_TIME,
+};
+use crate::block_stats::{put_block_stats, BlockStats};
+use crate::comms_handler::{Event, Node, PeerIpFilter, TcpTlsConfig};
 use crate::configurations::{DbMode, MempoolNodeSharedConfig};
-use crate::constants::FUND_KEY;
 use crate::db_utils::{new_db, SimpleDb};
+use crate::exchange::load_deposit_accounts;
 use crate::interfaces::{
-    BlockchainItemMeta, DruidDroplet, DruidPool, MempoolApi, MempoolApiRequest, NodeType, Response,
-    StoredSerializingBlock, UserApiRequest, UserRequest, UtxoFetchType,
+    BlockchainItemMeta, DruidDroplet, DruidPool, MempoolApi, MempoolApiRequest, NetworkId,
+    NodeType, Response, ResponseReason, StoredSerializingBlock, UserApiRequest, UserRequest,
+    UtxoFetchType,
 };
 use crate::mempool::MempoolError;
+use crate::output_data::save_anchored_data;
+use crate::partition_rounds::PartitionRound;
 use crate::storage::{put_named_last_block_to_block_chain, put_to_block_chain, DB_SPEC};
 use crate::test_utils::{generate_rb_transactions, RbReceiverData, RbSenderData};
 use crate::threaded_call::ThreadedCallChannel;
 use crate::tracked_utxo::TrackedUtxoSet;
+use crate::trade::{save_trades, Trades};
 use crate::utils::{
     apply_mining_tx, construct_valid_block_pow_hash, create_item_asset_tx_from_sig,
-    decode_secret_key, generate_pow_for_block, to_api_keys, to_route_pow_infos,
-    tracing_log_try_init, validate_pow_block, ApiKeys,
+    current_log_filter, decode_secret_key, generate_pow_for_block, set_log_filter_reload,
+    to_api_keys, to_route_pow_infos, tracing_log_try_init, validate_pow_block, ApiKeys,
+    StringError,
+};
+use crate::wallet::{
+    with_signing_trigger, AddressStore, AddressStoreHex, Argon2Params, WalletBackup, WalletDb,
+    WalletDbError, WalletManager,
 };
-use crate::wallet::{AddressStore, AddressStoreHex, WalletDb, WalletDbError};
 use crate::MempoolRequest;
 use bincode::serialize;
 use std::collections::BTreeMap;
@@ -39,7 +53,8 @@ use tw_chain::primitives::block::{Block, BlockHeader};
 use tw_chain::primitives::transaction::{GenesisTxHashSpec, OutPoint, Transaction, TxIn, TxOut};
 use tw_chain::script::lang::Script;
 use tw_chain::utils::transaction_utils::{
-    construct_tx_hash, construct_tx_in_signable_asset_hash, construct_tx_in_signable_hash,
+    construct_address_for, construct_tx_hash, construct_tx_in_signable_asset_hash,
+    construct_tx_in_signable_hash,
 };
 use warp::http::{HeaderMap, HeaderValue, StatusCode};
 use warp::Filter;
@@ -117,34 +132,42 @@ impl MempoolTest {
 }
 
 impl MempoolApi for MempoolTest {
+    fn compact_db(&mut self) -> Response {
+        Response {
+            success: true,
+            reason: ResponseReason::DatabaseCompacted,
+        }
+    }
+
     fn get_shared_config(&self) -> MempoolNodeSharedConfig {
         Default::default()
     }
 
     fn pause_nodes(&mut self, _b_num: u64) -> Response {
-        let reason: &'static str = "";
-
         Response {
             success: true,
-            reason,
+            reason: ResponseReason::AttemptCoordinatedNodePause,
         }
     }
 
     fn resume_nodes(&mut self) -> Response {
-        let reason: &'static str = "";
+        Response {
+            success: true,
+            reason: ResponseReason::AttemptCoordinatedNodeResume,
+        }
+    }
 
+    fn failover(&mut self, _replaced: SocketAddr) -> Response {
         Response {
             success: true,
-            reason,
+            reason: ResponseReason::FailoverProposed,
         }
     }
 
     fn send_shared_config(&mut self, _shared_config: MempoolNodeSharedConfig) -> Response {
-        let reason: &'static str = "";
-
         Response {
             success: true,
-            reason,
+            reason: ResponseReason::AttemptSendSharedConfig,
         }
     }
 
@@ -160,12 +183,29 @@ impl MempoolApi for MempoolTest {
         &self.druid_pool
     }
 
-    fn receive_transactions(&mut self, _transactions: Vec<Transaction>) -> Response {
-        let reason: &'static str = "";
+    fn get_network_id(&self) -> NetworkId {
+        NetworkId::Mainnet
+    }
+
+    fn get_tx_pool_hashes(&self) -> Vec<String> {
+        let droplets = self.druid_pool.values();
+        droplets.flat_map(|d| d.txs.keys().cloned()).collect()
+    }
+
+    fn get_partition_rounds(&self, start: u64, end: u64) -> Vec<PartitionRound> {
+        let miner: SocketAddr = "127.0.0.1:12340".parse().unwrap();
+        let coinbase = Transaction::new();
+        (start..=end.min(1))
+            .map(|b_num| {
+                PartitionRound::new(b_num, 0, vec![miner], vec![], miner, ("cb", &coinbase))
+            })
+            .collect()
+    }
 
+    fn receive_transactions(&mut self, _transactions: Vec<Transaction>) -> Response {
         Response {
             success: true,
-            reason,
+            reason: ResponseReason::TransactionsAddedToTxPool,
         }
     }
 
@@ -438,6 +478,103 @@ async fn test_get_export_keypairs() {
     assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Key-pairs successfully exported\",\"route\":\"export_keypairs\",\"content\":{\"addresses\":{\"13bd3351b78beb2d0dadf2058dcc926c\":{\"public_key\":\"5371832122a8e804fa3520ec6861c3fa554a7f6fb617e6f0768452090207e07c\",\"secret_key\":\"3053020101300506032b6570042204200186bc08f16428d2059227082b93e439ff50f8c162f24b9594b132f2cc15fca4a1230321005371832122a8e804fa3520ec6861c3fa554a7f6fb617e6f0768452090207e07c\",\"address_version\":null}}}}");
 }
 
+/// Test GET wallet signing audit log
+#[tokio::test(flavor = "current_thread")]
+async fn test_get_signing_audit_log() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mut db = get_wallet_db("").await;
+    let tx_used = vec![(
+        OutPoint::new(COMMON_PUB_KEY.to_owned(), 1),
+        COMMON_PUB_ADDR.to_owned(),
+    )];
+    let trigger = "api:MakePayment".to_owned();
+    with_signing_trigger(trigger, db.consume_inputs_for_payment(Vec::new(), tx_used))
+        .await
+        .unwrap();
+
+    let request = warp::test::request()
+        .method("GET")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .path("/signing_audit_log");
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::signing_audit_log(&mut dp(), db, Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let entries = body["content"].as_array().unwrap();
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["address"], COMMON_PUB_ADDR);
+    assert_eq!(entries[0]["out_point"]["n"], 1);
+    assert_eq!(entries[0]["trigger"], "api:MakePayment");
+}
+
+/// Test POST wallet transaction history
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_tx_history() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mut db = get_wallet_db("").await;
+    let (address, _) = db.generate_payment_address().await.unwrap();
+    let payments = ["tx_a", "tx_b"]
+        .iter()
+        .map(|hash| {
+            let out_p = OutPoint::new(hash.to_string(), 0);
+            (out_p, Asset::token_u64(5), address.clone(), 0)
+        })
+        .collect();
+    db.save_usable_payments_to_wallet(payments, 1)
+        .await
+        .unwrap();
+
+    let request = warp::test::request()
+        .method("POST")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .path("/tx_history")
+        .header("Content-Type", "application/json")
+        .json(&TxHistoryData {
+            page: 0,
+            page_size: Some(1),
+        });
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter =
+        routes::tx_history(&mut dp(), db, Default::default(), ks, cache).recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let entries = body["content"]["entries"].as_array().unwrap();
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(body["content"]["total"], 2);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["tx_hash"], "tx_b");
+    assert_eq!(entries[0]["direction"], "Incoming");
+    assert_eq!(entries[0]["status"], "Confirmed");
+}
+
 /// Test get user debug data
 #[tokio::test(flavor = "current_thread")]
 async fn test_get_user_debug_data() {
@@ -475,7 +612,7 @@ async fn test_get_user_debug_data() {
     //
     // Assert
     //
-    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"User\",\"node_api\":[\"wallet_info\",\"make_payment\",\"make_ip_payment\",\"request_donation\",\"export_keypairs\",\"import_keypairs\",\"update_running_total\",\"create_item_asset\",\"payment_address\",\"change_passphrase\",\"address_construction\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13000\",\"127.0.0.1:13000\",\"Mempool\"]],\"routes_pow\":{}}}";
+    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"User\",\"node_api\":[\"wallet_info\",\"wallets\",\"make_payment\",\"make_ip_payment\",\"request_donation\",\"make_trade_offer\",\"trades\",\"anchored_data\",\"export_keypairs\",\"import_keypairs\",\"export_backup\",\"export_mnemonic\",\"import_backup\",\"backup_wallet\",\"restore_wallet\",\"import_watch_only\",\"address_label\",\"signing_audit_log\",\"tx_history\",\"update_running_total\",\"dust_consolidation\",\"create_item_asset\",\"payment_address\",\"deposit_address\",\"change_passphrase\",\"address_construction\",\"compact_db\",\"log_filter\",\"get_peer_ip_filter\",\"update_peer_ip_filter\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13000\",\"127.0.0.1:13000\",\"Mempool\"]],\"routes_pow\":{},\"event_queues\":[{\"depth\":0,\"capacity\":4096}]}}";
     assert_eq!((res_a.status(), res_a.headers().clone()), success_json());
     assert_eq!(res_a.body(), expected_string);
 
@@ -515,15 +652,21 @@ async fn test_get_storage_debug_data() {
     //
     // Act
     //
-    let filter = routes::storage_node_routes(ks, Default::default(), db, self_node.clone())
-        .recover(handle_rejection);
+    let filter = routes::storage_node_routes(
+        ks,
+        Default::default(),
+        db,
+        Default::default(),
+        self_node.clone(),
+    )
+    .recover(handle_rejection);
     let res_a = request_x_api().reply(&filter).await;
     let res_m = request().reply(&filter).await;
 
     //
     // Assert
     //
-    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Storage\",\"node_api\":[\"block_by_num\",\"transactions_by_key\",\"latest_block\",\"blockchain_entry\",\"check_transaction_presence\",\"address_construction\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13010\",\"127.0.0.1:13010\",\"Mempool\"]],\"routes_pow\":{}}}";
+    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Storage\",\"node_api\":[\"block_by_num\",\"transactions_by_key\",\"latest_block\",\"blockchain_entry\",\"check_transaction_presence\",\"anchored_data\",\"block_stats\",\"raft_metrics\",\"compact_db\",\"log_filter\",\"get_peer_ip_filter\",\"update_peer_ip_filter\",\"health\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13010\",\"127.0.0.1:13010\",\"Mempool\"]],\"routes_pow\":{},\"event_queues\":[{\"depth\":0,\"capacity\":4096}]}}";
     assert_eq!((res_a.status(), res_a.headers().clone()), success_json());
     assert_eq!(res_a.body(), expected_string);
 
@@ -572,15 +715,16 @@ async fn test_get_mempool_debug_data() {
         .into_iter()
         .collect(),
     );
-    let filter = routes::mempool_node_routes(ks, routes_pow, tx, self_node.clone())
-        .recover(handle_rejection);
+    let filter =
+        routes::mempool_node_routes(ks, routes_pow, tx, Default::default(), self_node.clone())
+            .recover(handle_rejection);
     let res_a = request_x_api().reply(&filter).await;
     let res_m = request().reply(&filter).await;
 
     //
     // Assert
     //
-    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Mempool\",\"node_api\":[\"fetch_balance\",\"create_item_asset\",\"create_transactions\",\"utxo_addresses\",\"address_construction\",\"pause_nodes\",\"resume_nodes\",\"update_shared_config\",\"get_shared_config\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13020\",\"127.0.0.1:13020\",\"Mempool\"]],\"routes_pow\":{\"create_transactions\":2}}}";
+    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Mempool\",\"node_api\":[\"fetch_balance\",\"create_item_asset\",\"create_transactions\",\"total_supply\",\"issued_supply\",\"utxo_addresses\",\"mempool_transactions\",\"partition_rounds\",\"raft_metrics\",\"compact_db\",\"failover\",\"log_filter\",\"get_peer_ip_filter\",\"update_peer_ip_filter\",\"health\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13020\",\"127.0.0.1:13020\",\"Mempool\"]],\"routes_pow\":{\"create_transactions\":2},\"event_queues\":[{\"depth\":0,\"capacity\":4096}]}}";
     assert_eq!((res_a.status(), res_a.headers().clone()), success_json());
     assert_eq!(res_a.body(), expected_string);
 
@@ -630,7 +774,7 @@ async fn test_get_miner_debug_data() {
     //
     // Assert
     //
-    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Miner\",\"node_api\":[\"wallet_info\",\"export_keypairs\",\"import_keypairs\",\"payment_address\",\"change_passphrase\",\"current_mining_block\",\"address_construction\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13030\",\"127.0.0.1:13030\",\"Mempool\"]],\"routes_pow\":{}}}";
+    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Miner\",\"node_api\":[\"wallet_info\",\"export_keypairs\",\"import_keypairs\",\"export_backup\",\"import_backup\",\"signing_audit_log\",\"payment_address\",\"change_passphrase\",\"current_mining_block\",\"address_construction\",\"compact_db\",\"log_filter\",\"get_peer_ip_filter\",\"update_peer_ip_filter\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13030\",\"127.0.0.1:13030\",\"Mempool\"]],\"routes_pow\":{},\"event_queues\":[{\"depth\":0,\"capacity\":4096}]}}";
     assert_eq!((res_a.status(), res_a.headers().clone()), success_json());
     assert_eq!(res_a.body(), expected_string);
 
@@ -689,7 +833,7 @@ async fn test_get_miner_with_user_debug_data() {
     //
     // Assert
     //
-    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Miner/User\",\"node_api\":[\"wallet_info\",\"make_payment\",\"make_ip_payment\",\"request_donation\",\"export_keypairs\",\"import_keypairs\",\"update_running_total\",\"create_item_asset\",\"payment_address\",\"change_passphrase\",\"current_mining_block\",\"address_construction\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13040\",\"127.0.0.1:13040\",\"Mempool\"],[\"127.0.0.1:13041\",\"127.0.0.1:13041\",\"Storage\"]],\"routes_pow\":{}}}";
+    let expected_string = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Debug data successfully retrieved\",\"route\":\"debug_data\",\"content\":{\"node_type\":\"Miner/User\",\"node_api\":[\"wallet_info\",\"make_payment\",\"make_ip_payment\",\"request_donation\",\"export_keypairs\",\"import_keypairs\",\"export_backup\",\"import_backup\",\"signing_audit_log\",\"update_running_total\",\"create_item_asset\",\"payment_address\",\"change_passphrase\",\"current_mining_block\",\"address_construction\",\"compact_db\",\"log_filter\",\"get_peer_ip_filter\",\"update_peer_ip_filter\",\"debug_data\"],\"node_peers\":[[\"127.0.0.1:13040\",\"127.0.0.1:13040\",\"Mempool\"],[\"127.0.0.1:13041\",\"127.0.0.1:13041\",\"Storage\"]],\"routes_pow\":{},\"event_queues\":[{\"depth\":0,\"capacity\":4096},{\"depth\":0,\"capacity\":4096}]}}";
     assert_eq!((res_a.status(), res_a.headers().clone()), success_json());
     assert_eq!(res_a.body(), expected_string);
 
@@ -780,6 +924,198 @@ async fn auth_request_pow_with_invalid_api_key_failure() {
     .await;
 }
 
+// Authorize requests with API keys having roles, whatever keys the route lists
+#[tokio::test(flavor = "current_thread")]
+async fn auth_request_api_key_roles() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let to_keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+    let api_keys = to_api_keys(
+        vec![
+            ("make_payment".to_owned(), to_keys(&["route_key"])),
+            (
+                ApiRole::ReadOnly.config_key().to_owned(),
+                to_keys(&["monitor_key"]),
+            ),
+            (
+                ApiRole::WalletSpend.config_key().to_owned(),
+                to_keys(&["spend_key"]),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let filter = auth_request(to_route_pow_infos(Default::default()), api_keys)
+        .and_then(|_| ok_reply())
+        .recover(handle_rejection);
+    let request = |path: &str, api_key: &str| {
+        warp::test::request()
+            .method("GET")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .header("x-api-key", api_key)
+            .path(path)
+    };
+
+    //
+    // Act
+    //
+    let mut actual = Vec::new();
+    for (path, api_key) in [
+        ("/wallet_info", "monitor_key"),
+        ("/make_payment", "monitor_key"),
+        ("/pause_nodes", "monitor_key"),
+        ("/make_payment", "spend_key"),
+        ("/make_payment", "route_key"),
+    ] {
+        actual.push(request(path, api_key).reply(&filter).await.status());
+    }
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        actual,
+        vec![
+            StatusCode::OK,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::OK,
+            StatusCode::OK,
+        ]
+    );
+}
+
+// Reject read-only API keys on every administrative route, even those not listed by roles
+#[tokio::test(flavor = "current_thread")]
+async fn auth_admin_request_read_only_role() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let to_keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+    let api_keys = to_api_keys(
+        vec![
+            (
+                ApiRole::ReadOnly.config_key().to_owned(),
+                to_keys(&["monitor_key"]),
+            ),
+            (
+                ApiRole::Operator.config_key().to_owned(),
+                to_keys(&["operator_key"]),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let filter = auth_admin_request(to_route_pow_infos(Default::default()), api_keys.clone())
+        .and_then(|_| ok_reply())
+        .recover(handle_rejection);
+    let audit_filter = auth_request(to_route_pow_infos(Default::default()), api_keys)
+        .and_then(|_| ok_reply())
+        .recover(handle_rejection);
+    let request = |path: &str, api_key: &str| {
+        warp::test::request()
+            .method("GET")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .header("x-api-key", api_key)
+            .path(path)
+    };
+    let admin_routes = [
+        "/log_filter",
+        "/get_peer_ip_filter",
+        "/update_peer_ip_filter",
+        "/compact_db",
+        "/failover",
+        "/new_admin_route",
+    ];
+
+    //
+    // Act
+    //
+    let mut read_only = Vec::new();
+    let mut operator = Vec::new();
+    for path in admin_routes {
+        read_only.push(request(path, "monitor_key").reply(&filter).await.status());
+        operator.push(request(path, "operator_key").reply(&filter).await.status());
+    }
+    let audit_log = request("/signing_audit_log", "monitor_key")
+        .reply(&audit_filter)
+        .await;
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        read_only,
+        vec![StatusCode::UNAUTHORIZED; admin_routes.len()]
+    );
+    assert_eq!(operator, vec![StatusCode::OK; admin_routes.len()]);
+    assert_eq!(audit_log.status(), StatusCode::UNAUTHORIZED);
+}
+
+// Authorize requests from clients with certificate roles, whatever API key they send
+#[tokio::test(flavor = "current_thread")]
+async fn auth_request_client_cert_roles() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let api_keys = to_api_keys(
+        vec![("pause_nodes".to_owned(), vec!["route_key".to_owned()])]
+            .into_iter()
+            .collect(),
+    );
+    let filter = auth_request(to_route_pow_infos(Default::default()), api_keys)
+        .and_then(|_| ok_reply())
+        .recover(handle_rejection);
+    let request = |path: &str, roles: &[ApiRole]| {
+        warp::test::request()
+            .method("GET")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .extension(ClientCertRoles(roles.to_vec()))
+            .path(path)
+    };
+
+    //
+    // Act
+    //
+    let mut actual = Vec::new();
+    for (path, roles) in [
+        ("/pause_nodes", &[ApiRole::Operator][..]),
+        ("/pause_nodes", &[ApiRole::ReadOnly][..]),
+        ("/pause_nodes", &[][..]),
+        (
+            "/make_payment",
+            &[ApiRole::Operator, ApiRole::WalletSpend][..],
+        ),
+    ] {
+        actual.push(request(path, roles).reply(&filter).await.status());
+    }
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        actual,
+        vec![
+            StatusCode::OK,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::OK,
+        ]
+    );
+    assert_eq!(
+        ApiRole::from_name("wallet_spend"),
+        Some(ApiRole::WalletSpend)
+    );
+    assert_eq!(ApiRole::from_name("role:operator"), None);
+}
+
 async fn auth_request_common(
     api_key_and_keys: (BTreeMap<String, Vec<String>>, &str),
     difficulty_and_nonce: Option<(usize, &str)>,
@@ -842,7 +1178,7 @@ async fn test_get_wallet_info() {
     // Arrange
     //
     let db = get_wallet_db("").await;
-    let mut fund_store = db.get_fund_store();
+    let mut fund_store = db.get_fund_store().unwrap();
     let out_point = OutPoint::new("tx_hash".to_string(), 0);
     let out_point_s = OutPoint::new("tx_hash_spent".to_string(), 0);
     let asset = Asset::token_u64(11);
@@ -850,8 +1186,7 @@ async fn test_get_wallet_info() {
     fund_store.store_tx(out_point_s.clone(), asset.clone());
     fund_store.spend_tx(&out_point_s);
 
-    db.set_db_value(FUND_KEY, serialize(&fund_store).unwrap())
-        .await;
+    db.set_fund_store(fund_store).await.unwrap();
 
     db.save_transaction_to_wallet(out_point, "public_address".to_string())
         .await
@@ -885,10 +1220,10 @@ async fn test_get_wallet_info() {
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"confirmed_total\":0.0004365079365079365,\"confirmed_total_tokens\":11,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 
     assert_eq!((r_s.status(), r_s.headers().clone()), success_json());
-    assert_eq!(r_s.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"item_total\":{},\"addresses\":{\"public_address_spent\":[{\"out_point\":{\"t_hash\":\"tx_hash_spent\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(r_s.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"confirmed_total\":0.0004365079365079365,\"confirmed_total_tokens\":11,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address_spent\":[{\"out_point\":{\"t_hash\":\"tx_hash_spent\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 }
 
 /// Test GET shared config for mempool node
@@ -935,7 +1270,7 @@ async fn test_pagination() {
     // Arrange
     //
     let db = get_wallet_db("").await;
-    let mut fund_store = db.get_fund_store();
+    let mut fund_store = db.get_fund_store().unwrap();
     fund_store.add_transaction_pages();
 
     for i in 0..100 {
@@ -943,8 +1278,7 @@ async fn test_pagination() {
         let asset = Asset::token_u64(11);
         fund_store.store_tx(out_point.clone(), asset.clone());
 
-        db.set_db_value(FUND_KEY, serialize(&fund_store).unwrap())
-            .await;
+        db.set_fund_store(fund_store.clone()).await.unwrap();
 
         db.save_transaction_to_wallet(out_point, "public_address".to_string())
             .await
@@ -969,7 +1303,7 @@ async fn test_pagination() {
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.04365079365079365,\"running_total_tokens\":1100,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.04365079365079365,\"available_total_tokens\":1100,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash25\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash26\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash27\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash28\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash29\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash30\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash31\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash32\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash33\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash34\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash35\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash36\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash37\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash38\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash39\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash40\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash41\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash42\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash43\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash44\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash45\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash46\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash47\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash48\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash49\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.04365079365079365,\"running_total_tokens\":1100,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.04365079365079365,\"available_total_tokens\":1100,\"confirmed_total\":0.04365079365079365,\"confirmed_total_tokens\":1100,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash25\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash26\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash27\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash28\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash29\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash30\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash31\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash32\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash33\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash34\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash35\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash36\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash37\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash38\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash39\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash40\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash41\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash42\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash43\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash44\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash45\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash46\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash47\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash48\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash49\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 }
 
 #[tokio::test(flavor = "current_thread")]
@@ -980,15 +1314,14 @@ async fn test_pagination_zero_or_terminal() {
     // Arrange
     //
     let db = get_wallet_db("").await;
-    let mut fund_store = db.get_fund_store();
+    let mut fund_store = db.get_fund_store().unwrap();
 
     for i in 0..100 {
         let out_point = OutPoint::new("tx_hash".to_string() + &i.to_string(), 0);
         let asset = Asset::token_u64(11);
         fund_store.store_tx(out_point.clone(), asset.clone());
 
-        db.set_db_value(FUND_KEY, serialize(&fund_store).unwrap())
-            .await;
+        db.set_fund_store(fund_store.clone()).await.unwrap();
 
         db.save_transaction_to_wallet(out_point, "public_address".to_string())
             .await
@@ -1020,10 +1353,10 @@ async fn test_pagination_zero_or_terminal() {
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.04365079365079365,\"running_total_tokens\":1100,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.04365079365079365,\"available_total_tokens\":1100,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash0\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash1\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash10\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash11\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash12\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash13\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash14\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash15\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash16\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash17\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash18\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash19\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash2\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash20\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash21\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash22\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash23\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash24\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash25\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash26\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash27\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash28\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash29\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash3\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash30\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash31\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash32\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash33\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash34\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash35\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash36\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash37\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash38\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash39\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash4\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash40\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash41\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash42\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash43\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash44\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash45\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash46\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash47\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash48\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash49\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash5\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash50\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash51\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash52\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash53\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash54\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash55\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash56\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash57\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash58\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash59\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash6\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash60\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash61\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash62\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash63\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash64\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash65\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash66\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash67\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash68\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash69\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash7\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash70\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash71\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash72\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash73\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash74\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash75\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash76\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash77\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash78\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash79\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash8\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash80\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash81\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash82\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash83\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash84\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash85\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash86\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash87\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash88\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash89\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash9\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash90\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash91\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash92\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash93\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash94\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash95\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash96\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash97\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash98\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash99\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.04365079365079365,\"running_total_tokens\":1100,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.04365079365079365,\"available_total_tokens\":1100,\"confirmed_total\":0.04365079365079365,\"confirmed_total_tokens\":1100,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash0\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash1\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash10\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash11\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash12\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash13\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash14\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash15\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash16\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash17\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash18\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash19\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash2\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash20\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash21\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash22\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash23\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash24\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash25\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash26\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash27\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash28\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash29\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash3\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash30\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash31\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash32\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash33\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash34\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash35\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash36\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash37\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash38\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash39\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash4\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash40\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash41\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash42\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash43\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash44\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash45\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash46\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash47\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash48\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash49\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash5\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash50\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash51\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash52\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash53\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash54\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash55\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash56\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash57\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash58\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash59\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash6\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash60\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash61\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash62\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash63\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash64\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash65\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash66\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash67\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash68\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash69\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash7\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash70\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash71\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash72\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash73\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash74\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash75\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash76\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash77\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash78\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash79\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash8\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash80\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash81\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash82\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash83\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash84\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash85\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash86\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash87\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash88\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash89\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash9\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash90\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash91\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash92\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash93\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash94\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash95\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash96\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash97\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash98\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash99\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 
     assert_eq!((r_s.status(), r_s.headers().clone()), success_json());
-    assert_eq!(r_s.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.04365079365079365,\"running_total_tokens\":1100,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.04365079365079365,\"available_total_tokens\":1100,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash75\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash76\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash77\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash78\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash79\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash80\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash81\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash82\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash83\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash84\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash85\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash86\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash87\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash88\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash89\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash90\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash91\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash92\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash93\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash94\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash95\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash96\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash97\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash98\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash99\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(r_s.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.04365079365079365,\"running_total_tokens\":1100,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.04365079365079365,\"available_total_tokens\":1100,\"confirmed_total\":0.04365079365079365,\"confirmed_total_tokens\":1100,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash75\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash76\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash77\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash78\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash79\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash80\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash81\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash82\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash83\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash84\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash85\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash86\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash87\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash88\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash89\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash90\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash91\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash92\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash93\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash94\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash95\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash96\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash97\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash98\",\"n\":0},\"value\":{\"Token\":11}},{\"out_point\":{\"t_hash\":\"tx_hash99\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 }
 
 /// Test cache
@@ -1035,7 +1368,7 @@ async fn test_cache() {
     // Arrange
     //
     let db = get_wallet_db("").await;
-    let mut fund_store = db.get_fund_store();
+    let mut fund_store = db.get_fund_store().unwrap();
     let out_point = OutPoint::new("tx_hash".to_string(), 0);
     let out_point_s = OutPoint::new("tx_hash_spent".to_string(), 0);
     let asset = Asset::token_u64(11);
@@ -1043,8 +1376,7 @@ async fn test_cache() {
     fund_store.store_tx(out_point_s.clone(), asset.clone());
     fund_store.spend_tx(&out_point_s);
 
-    db.set_db_value(FUND_KEY, serialize(&fund_store).unwrap())
-        .await;
+    db.set_fund_store(fund_store).await.unwrap();
 
     db.save_transaction_to_wallet(out_point, "public_address".to_string())
         .await
@@ -1083,7 +1415,7 @@ async fn test_cache() {
     //
     // Assert
     //
-    let expected_cached_response = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash\",\"n\":0},\"value\":{\"Token\":11}}]}}}";
+    let expected_cached_response = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"confirmed_total\":0.0004365079365079365,\"confirmed_total_tokens\":11,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address\":[{\"out_point\":{\"t_hash\":\"tx_hash\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}";
     assert_eq!((res.status(), res.headers().clone()), success_json());
     assert_eq!(res.body(), expected_cached_response);
 
@@ -1096,7 +1428,7 @@ async fn test_cache() {
         (r_s_diff_id.status(), r_s_diff_id.headers().clone()),
         success_json()
     );
-    assert_eq!(r_s_diff_id.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"item_total\":{},\"addresses\":{\"public_address_spent\":[{\"out_point\":{\"t_hash\":\"tx_hash_spent\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(r_s_diff_id.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"confirmed_total\":0.0004365079365079365,\"confirmed_total_tokens\":11,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address_spent\":[{\"out_point\":{\"t_hash\":\"tx_hash_spent\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 
     thread::sleep(two_sec);
     //repeat with same id after value expires
@@ -1106,7 +1438,7 @@ async fn test_cache() {
         .path("/wallet_info/spent");
     let r_s = request_spent.reply(&filter).await;
     assert_eq!((r_s.status(), r_s.headers().clone()), success_json());
-    assert_eq!(r_s.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"item_total\":{},\"addresses\":{\"public_address_spent\":[{\"out_point\":{\"t_hash\":\"tx_hash_spent\",\"n\":0},\"value\":{\"Token\":11}}]}}}");
+    assert_eq!(r_s.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Wallet info successfully fetched\",\"route\":\"wallet_info\",\"content\":{\"running_total\":0.0004365079365079365,\"running_total_tokens\":11,\"locked_total\":0.0,\"locked_total_tokens\":0,\"available_total\":0.0004365079365079365,\"available_total_tokens\":11,\"confirmed_total\":0.0004365079365079365,\"confirmed_total_tokens\":11,\"pending_incoming_total\":0.0,\"pending_incoming_total_tokens\":0,\"locked_outgoing_total\":0.0,\"locked_outgoing_total_tokens\":0,\"item_total\":{},\"addresses\":{\"public_address_spent\":[{\"out_point\":{\"t_hash\":\"tx_hash_spent\",\"n\":0},\"value\":{\"Token\":11}}]},\"labels\":{},\"watch_only_total\":0.0,\"watch_only_total_tokens\":0,\"watch_only_addresses\":{}}}");
 }
 
 /// Test GET new payment address
@@ -1118,6 +1450,7 @@ async fn test_get_payment_address() {
     // Arrange
     //
     let db = get_wallet_db("").await;
+    db.check_network_id(NetworkId::Testnet).unwrap();
     let request = warp::test::request()
         .method("GET")
         .header("x-cache-id", COMMON_REQ_ID)
@@ -1131,14 +1464,109 @@ async fn test_get_payment_address() {
     let filter = routes::payment_address(&mut dp(), db.clone(), Default::default(), ks, cache)
         .recover(handle_rejection);
     let res = request.reply(&filter).await;
-    let store_address = db.get_known_addresses().pop().unwrap();
-    let expected = format!("{{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"New payment address generated\",\"route\":\"payment_address\",\"content\":\"{store_address}\"}}");
+    let store_address = db.get_known_addresses().unwrap().pop().unwrap();
+    let testnet = NetworkId::Testnet.address_byte();
+    let address = wallet_core::encode_checked_address(&store_address, testnet).unwrap();
+    let expected = format!("{{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"New payment address generated\",\"route\":\"payment_address\",\"content\":\"{address}\"}}");
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), &expected);
+}
+
+/// Test GET new payment address of a wallet selected by name
+#[tokio::test(flavor = "current_thread")]
+async fn test_get_payment_address_named_wallet() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let wallets = WalletManager::new(get_wallet_db("").await);
+    let kdf_params = Argon2Params::for_db_mode(DbMode::InMemory);
+    let savings = wallets
+        .open("savings", DbMode::InMemory, None, None, kdf_params)
+        .unwrap();
+    let request = |name: &str| {
+        warp::test::request()
+            .method("GET")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .header("x-wallet-name", name)
+            .path("/payment_address")
+    };
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::payment_address(&mut dp(), wallets.clone(), Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let res = request("savings").reply(&filter).await;
+    let res_unknown = request("mining").reply(&filter).await;
+    let store_address = savings.get_known_addresses().unwrap().pop().unwrap();
+    let address = savings.encode_checked_address(&store_address).unwrap();
+    let expected = format!("{{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"New payment address generated\",\"route\":\"payment_address\",\"content\":\"{address}\"}}");
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), &expected);
+    let default_addresses = wallets.get(None).unwrap().get_known_addresses().unwrap();
+    assert!(!default_addresses.contains(&store_address));
+    assert_eq!(
+        (res_unknown.status(), res_unknown.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(res_unknown.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"Unknown wallet: mining\",\"route\":\"payment_address\",\"content\":\"null\"}");
+}
+
+/// Test POST new deposit address for a customer
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_deposit_address() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let db = get_wallet_db("").await;
+    let json_body = DepositAddressData {
+        external_id: "customer_1".to_owned(),
+    };
+    let request = warp::test::request()
+        .method("POST")
+        .path("/deposit_address")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&json_body);
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::deposit_address(&mut dp(), db.clone(), Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+    let store_address = db.get_known_addresses().unwrap().pop().unwrap();
+    let accounts = load_deposit_accounts(&db).await.unwrap();
+    let address = db.encode_checked_address(&store_address).unwrap();
+    let expected = format!("{{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Deposit address generated\",\"route\":\"deposit_address\",\"content\":\"{address}\"}}");
 
     //
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
     assert_eq!(res.body(), &expected);
+    assert_eq!(
+        accounts.get(&store_address).map(|id| id.as_str()),
+        Some("customer_1")
+    );
 }
 
 /// Test GET all addresses on the UTXO set
@@ -1190,12 +1618,134 @@ async fn test_get_utxo_set_addresses() {
     );
 }
 
-/*------- POST TESTS--------*/
-
-/// Test POST for get blockchain block by key
+/// Test POST partition rounds of a range of blocks
 #[tokio::test(flavor = "current_thread")]
-async fn test_post_blockchain_entry_by_key_block() {
-    let expected_meta = success_json();
+async fn test_post_partition_rounds() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mempool = MempoolTest::new(Vec::new());
+    let request = warp::test::request()
+        .method("POST")
+        .path("/partition_rounds")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&BlockRange { start: 1, end: 5 });
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::partition_rounds(
+        &mut dp(),
+        mempool.threaded_calls.tx.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let handle = mempool.spawn();
+    let res = request.reply(&filter).await;
+    let _mempool = handle.await.unwrap();
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Partition rounds successfully retrieved\",\"route\":\"partition_rounds\",\"content\":[{\"b_num\":1,\"timestamp\":0,\"participants\":[\"127.0.0.1:12340\"],\"pow_miners\":[],\"winner\":\"127.0.0.1:12340\",\"coinbase_hash\":\"cb\",\"paid\":{}}]}");
+}
+
+/// Test GET transactions waiting in the mempool
+#[tokio::test(flavor = "current_thread")]
+async fn test_get_mempool_transactions() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let tx_vals = vec![
+        generate_transaction("tx_hash_1", "public_address_1"),
+        generate_transaction("tx_hash_2", "public_address_2"),
+    ];
+
+    let mempool = MempoolTest::new(tx_vals);
+    let request = warp::test::request()
+        .method("GET")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .path("/mempool_transactions");
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::mempool_transactions(
+        &mut dp(),
+        mempool.threaded_calls.tx.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let handle = mempool.spawn();
+    let res = request.reply(&filter).await;
+    let _mempool = handle.await.unwrap();
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(
+        res.body(),
+        "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Mempool transactions successfully retrieved\",\"route\":\"mempool_transactions\",\"content\":[\"g94a4029ed9a954e8f70df3ae0293b48\",\"gce1be964241e5213b9e00809914606a\"]}"
+    );
+}
+
+/// Test GET node health
+#[tokio::test(flavor = "current_thread")]
+async fn test_get_health() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let (self_node, _self_socket) = new_self_node(NodeType::Mempool).await;
+    let request = warp::test::request()
+        .method("GET")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .path("/health");
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::health(&mut dp(), self_node, Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(
+        res.body(),
+        "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Node is healthy\",\"route\":\"health\",\"content\":{\"node_type\":\"Mempool\",\"connected_peers\":0,\"event_queue\":{\"depth\":0,\"capacity\":4096}}}"
+    );
+}
+
+/*------- POST TESTS--------*/
+
+/// Test POST for get blockchain block by key
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_blockchain_entry_by_key_block() {
+    let expected_meta = success_json();
     let expected_body = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Database item(s) successfully retrieved\",\"route\":\"blockchain_entry\",\"content\":{\"Block\":{\"block\":{\"header\":{\"version\":2,\"bits\":0,\"nonce_and_mining_tx_hash\":[[120,12,5,128,106,59,112,177,92,150,115,57,97,113,103,79],\"test\"],\"b_num\":0,\"seed_value\":[],\"previous_hash\":null,\"txs_merkle_root_and_hash\":[\"42fbcc73bc0eeb41a991a32a6f6e145d1d45b2738657db5b4781d1fa707693cf\",\"35260a02627ae9d586dbb9f11de79afd46d1096f41ffb6b9ee88cca6b78bf374\"]},\"transactions\":[\"g98d0ab9304ca82f098a86ad6251803b\"]}}}}";
 
     test_post_blockchain_entry_by_key(
@@ -1315,6 +1865,55 @@ async fn test_post_block_info_by_nums() {
     assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Database item(s) successfully retrieved\",\"route\":\"block_by_num\",\"content\":[[\"b0004e829238707b7a600a95d3089e320448f706c2c7f6b0427201cc384c7fbfc\",{\"block\":{\"header\":{\"version\":2,\"bits\":0,\"nonce_and_mining_tx_hash\":[[120,12,5,128,106,59,112,177,92,150,115,57,97,113,103,79],\"test\"],\"b_num\":0,\"seed_value\":[],\"previous_hash\":null,\"txs_merkle_root_and_hash\":[\"42fbcc73bc0eeb41a991a32a6f6e145d1d45b2738657db5b4781d1fa707693cf\",\"35260a02627ae9d586dbb9f11de79afd46d1096f41ffb6b9ee88cca6b78bf374\"]},\"transactions\":[\"g98d0ab9304ca82f098a86ad6251803b\"]}}],[\"\",\"\"],[\"b0004e829238707b7a600a95d3089e320448f706c2c7f6b0427201cc384c7fbfc\",{\"block\":{\"header\":{\"version\":2,\"bits\":0,\"nonce_and_mining_tx_hash\":[[120,12,5,128,106,59,112,177,92,150,115,57,97,113,103,79],\"test\"],\"b_num\":0,\"seed_value\":[],\"previous_hash\":null,\"txs_merkle_root_and_hash\":[\"42fbcc73bc0eeb41a991a32a6f6e145d1d45b2738657db5b4781d1fa707693cf\",\"35260a02627ae9d586dbb9f11de79afd46d1096f41ffb6b9ee88cca6b78bf374\"]},\"transactions\":[\"g98d0ab9304ca82f098a86ad6251803b\"]}}]]}");
 }
 
+/// Test POST for get block statistics of a range
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_block_stats() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mut db = get_db_with_block_no_mutex().await;
+    let mut batch = db.batch_writer();
+    for b_num in 0..3 {
+        let stats = BlockStats {
+            b_num,
+            block_hash: format!("hash{b_num}"),
+            timestamp: 1_700_000_000 + 60 * b_num as i64,
+            interval: b_num.checked_sub(1).map(|_| 60),
+            tx_count: b_num,
+            amount_transferred: TokenAmount(10 * b_num),
+            unique_addresses: 2 * b_num,
+        };
+        put_block_stats(&mut batch, &stats);
+    }
+    let batch = batch.done();
+    db.write(batch).unwrap();
+
+    let request = warp::test::request()
+        .method("POST")
+        .path("/block_stats")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&BlockRange { start: 1, end: 5 });
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+    let db = Arc::new(Mutex::new(db));
+    let filter =
+        routes::block_stats(&mut dp(), db, Default::default(), ks, cache).recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Block statistics successfully retrieved\",\"route\":\"block_stats\",\"content\":[{\"b_num\":1,\"block_hash\":\"hash1\",\"timestamp\":1700000060,\"interval\":60,\"tx_count\":1,\"amount_transferred\":10,\"unique_addresses\":2},{\"b_num\":2,\"block_hash\":\"hash2\",\"timestamp\":1700000120,\"interval\":60,\"tx_count\":2,\"amount_transferred\":20,\"unique_addresses\":4}]}");
+}
+
 /// Test POST for get transactions info by tx_hash
 #[tokio::test(flavor = "current_thread")]
 async fn test_post_transactions_by_key() {
@@ -1407,37 +2006,38 @@ async fn test_post_make_payment() {
     assert_eq!(expected_frame, actual_frame);
 }
 
-/// Test POST make ip payment with correct address
+/// Test POST make payment to a typo'd address rejected before reaching the user node
 #[tokio::test(flavor = "current_thread")]
-async fn test_post_make_ip_payment() {
+async fn test_post_make_payment_invalid_address() {
     let _ = tracing_log_try_init();
 
     //
     // Arrange
     //
-    let (mut self_node, self_socket) = new_self_node(NodeType::User).await;
+    let (self_node, self_socket) = new_self_node(NodeType::User).await;
 
     let encapsulated_data = EncapsulatedPayment {
-        address: "127.0.0.1:12345".to_owned(),
+        address: format!("{}g", &COMMON_PUB_ADDR[1..]),
         amount: TokenAmount(25),
         passphrase: String::new(),
         locktime: None,
     };
+
     let db = get_wallet_db(&encapsulated_data.passphrase).await;
     let request = warp::test::request()
         .method("POST")
-        .path("/make_ip_payment")
+        .path("/make_payment")
         .remote_addr(self_socket)
         .header("Content-Type", "application/json")
         .header("x-cache-id", COMMON_REQ_ID)
         .json(&encapsulated_data);
+
     //
     // Act
     //
     let ks = to_api_keys(Default::default());
     let cache = create_new_cache(CACHE_LIVE_TIME);
-
-    let filter = routes::make_ip_payment(
+    let filter = routes::make_payment(
         &mut dp(),
         db,
         self_node.clone(),
@@ -1451,99 +2051,404 @@ async fn test_post_make_ip_payment() {
     //
     // Assert
     //
-    assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"IP payment processing\",\"route\":\"make_ip_payment\",\"content\":{\"127.0.0.1:12345\":{\"asset\":{\"Token\":25},\"extra_info\":null}}}");
-
-    // Frame expected
-    let (payment_peer, amount, locktime) = (
-        encapsulated_data.address.parse::<SocketAddr>().unwrap(),
-        encapsulated_data.amount,
-        encapsulated_data.locktime,
+    assert_eq!(
+        (res.status(), res.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
     );
-    let expected_frame = user_api_request_as_frame(UserApiRequest::MakeIpPayment {
-        payment_peer,
-        amount,
-        locktime,
-    });
-    let actual_frame = next_event_frame(&mut self_node).await;
-    assert_eq!(expected_frame, actual_frame);
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"Invalid payment address: invalid character 'g' at position 31, addresses are lowercase hex\",\"route\":\"make_payment\",\"content\":\"null\"}");
 }
 
-/// Test POST construct address from public key
+/// Test POST make payment to a checked address of another network rejected before reaching the user node
 #[tokio::test(flavor = "current_thread")]
-async fn test_address_construction() {
+async fn test_post_make_payment_other_network_address() {
     let _ = tracing_log_try_init();
 
     //
     // Arrange
     //
-    let mut address1 = BTreeMap::new();
-    address1.insert(
-        "pub_key",
-        vec![
-            109, 133, 37, 100, 46, 243, 13, 156, 189, 123, 142, 12, 24, 169, 49, 186, 187, 0, 63,
-            27, 129, 207, 183, 13, 156, 208, 171, 164, 179, 118, 131, 183,
-        ],
-    );
-
-    let mut address2 = BTreeMap::new();
-    address2.insert(
-        "pub_key_hex",
-        "6d8525642ef30d9cbd7b8e0c18a931babb003f1b81cfb70d9cd0aba4b37683b7",
-    );
-
-    let address3 = AddressConstructData {
-        pub_key_hex: Some(
-            "6d8525642ef30d9cbd7b8e0c18a931babb003f1b81cfb70d9cd0aba4b37683b7".to_owned(),
-        ),
-        version: Some(0),
-        ..Default::default()
-    };
+    let (self_node, self_socket) = new_self_node(NodeType::User).await;
+    let testnet = NetworkId::Testnet.address_byte();
+    let address = wallet_core::encode_checked_address(&"a".repeat(64), testnet).unwrap();
 
-    let request1 = || {
-        warp::test::request()
-            .method("POST")
-            .path("/address_construction")
-            .header("x-cache-id", COMMON_REQ_ID)
-            .header("Content-Type", "application/json")
+    let encapsulated_data = EncapsulatedPayment {
+        address,
+        amount: TokenAmount(25),
+        passphrase: String::new(),
+        locktime: None,
     };
 
-    let com_req_id_plus_1 = "2ae7bc9cba924e3cb73c0249893078d8";
-    let request2 = || {
-        warp::test::request()
-            .method("POST")
-            .path("/address_construction")
-            .header("x-cache-id", com_req_id_plus_1)
-            .header("Content-Type", "application/json")
-    };
+    let db = get_wallet_db(&encapsulated_data.passphrase).await;
+    let request = warp::test::request()
+        .method("POST")
+        .path("/make_payment")
+        .remote_addr(self_socket)
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&encapsulated_data);
 
-    let com_req_id_plus_2 = "2ae7bc9cba924e3cb73c0249893078d9";
-    let request3 = || {
-        warp::test::request()
-            .method("POST")
-            .path("/address_construction")
-            .header("x-cache-id", com_req_id_plus_2)
-            .header("Content-Type", "application/json")
-    };
     //
     // Act
     //
     let ks = to_api_keys(Default::default());
     let cache = create_new_cache(CACHE_LIVE_TIME);
-    let filter = routes::address_construction(&mut dp(), Default::default(), ks, cache)
-        .recover(handle_rejection);
-    let res1 = request1().json(&address1).reply(&filter).await;
-    let res2 = request2().json(&address2).reply(&filter).await;
-    let res3 = request3().json(&address3).reply(&filter).await;
+    let filter = routes::make_payment(
+        &mut dp(),
+        db,
+        self_node.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let res = request.reply(&filter).await;
 
     //
     // Assert
     //
-    let expected1 = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Address successfully constructed\",\"route\":\"address_construction\",\"content\":\"ca0abdcd2826a77218af0914601ee34c7ff44127aab9d0671267b25a7d36946a\"}";
-    let expected2 = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Address successfully constructed\",\"route\":\"address_construction\",\"content\":\"ca0abdcd2826a77218af0914601ee34c7ff44127aab9d0671267b25a7d36946a\"}";
+    assert_eq!(
+        (res.status(), res.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"Invalid payment address: address of network 1, expected network 0\",\"route\":\"make_payment\",\"content\":\"null\"}");
+}
 
-    assert_eq!((res1.status(), res1.headers().clone()), success_json());
-    assert_eq!(res1.body(), expected1);
+/// Test POST make payment to a checked address with a typo rejected before reaching the user node
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_make_payment_checked_address_typo() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let (self_node, self_socket) = new_self_node(NodeType::User).await;
+    let mainnet = NetworkId::Mainnet.address_byte();
+    let address = wallet_core::encode_checked_address(&"a".repeat(64), mainnet).unwrap();
+    let typo_at = address.len() / 2;
+    let digit = if &address[typo_at..typo_at + 1] == "0" {
+        "1"
+    } else {
+        "0"
+    };
+
+    let encapsulated_data = EncapsulatedPayment {
+        address: format!("{}{digit}{}", &address[..typo_at], &address[typo_at + 1..]),
+        amount: TokenAmount(25),
+        passphrase: String::new(),
+        locktime: None,
+    };
+
+    let db = get_wallet_db(&encapsulated_data.passphrase).await;
+    let request = warp::test::request()
+        .method("POST")
+        .path("/make_payment")
+        .remote_addr(self_socket)
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&encapsulated_data);
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+    let filter = routes::make_payment(
+        &mut dp(),
+        db,
+        self_node.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        (res.status(), res.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"Invalid payment address: checksum mismatch, the address has a typo\",\"route\":\"make_payment\",\"content\":\"null\"}");
+}
+
+/// Test POST make ip payment with correct address
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_make_ip_payment() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let (mut self_node, self_socket) = new_self_node(NodeType::User).await;
+
+    let encapsulated_data = EncapsulatedPayment {
+        address: "127.0.0.1:12345".to_owned(),
+        amount: TokenAmount(25),
+        passphrase: String::new(),
+        locktime: None,
+    };
+    let db = get_wallet_db(&encapsulated_data.passphrase).await;
+    let request = warp::test::request()
+        .method("POST")
+        .path("/make_ip_payment")
+        .remote_addr(self_socket)
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&encapsulated_data);
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::make_ip_payment(
+        &mut dp(),
+        db,
+        self_node.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"IP payment processing\",\"route\":\"make_ip_payment\",\"content\":{\"127.0.0.1:12345\":{\"asset\":{\"Token\":25},\"extra_info\":null}}}");
+
+    // Frame expected
+    let (payment_peer, amount, locktime) = (
+        encapsulated_data.address.parse::<SocketAddr>().unwrap(),
+        encapsulated_data.amount,
+        encapsulated_data.locktime,
+    );
+    let expected_frame = user_api_request_as_frame(UserApiRequest::MakeIpPayment {
+        payment_peer,
+        amount,
+        locktime,
+    });
+    let actual_frame = next_event_frame(&mut self_node).await;
+    assert_eq!(expected_frame, actual_frame);
+}
+
+/// Test POST make trade offer
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_make_trade_offer() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let (mut self_node, self_socket) = new_self_node(NodeType::User).await;
+
+    let trade_offer = TradeOfferData {
+        address: "127.0.0.1:12345".to_owned(),
+        amount: TokenAmount(25),
+        genesis_hash: "g_genesis".to_owned(),
+        passphrase: String::new(),
+    };
+    let db = get_wallet_db(&trade_offer.passphrase).await;
+    let request = warp::test::request()
+        .method("POST")
+        .path("/make_trade_offer")
+        .remote_addr(self_socket)
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&trade_offer);
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::make_trade_offer(
+        &mut dp(),
+        db,
+        self_node.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Trade offer processing\",\"route\":\"make_trade_offer\",\"content\":\"127.0.0.1:12345\"}");
+
+    // Frame expected
+    let expected_frame = user_api_request_as_frame(UserApiRequest::MakeTradeOffer {
+        trade_peer: trade_offer.address.parse::<SocketAddr>().unwrap(),
+        send_asset: Asset::Token(trade_offer.amount),
+        genesis_hash: Some(trade_offer.genesis_hash),
+    });
+    let actual_frame = next_event_frame(&mut self_node).await;
+    assert_eq!(expected_frame, actual_frame);
+}
+
+/// Test GET trades of the user node
+#[tokio::test(flavor = "current_thread")]
+async fn test_get_trades() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let db = get_wallet_db("").await;
+    let peer: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+    let send_asset = Asset::item(1, Some("g_genesis".to_owned()), None);
+    let mut trades = Trades::default();
+    let (id, druid) = ("half".to_owned(), "halfdruid".to_owned());
+    trades.accept(id, peer, send_asset, COMMON_PUB_ADDR.to_owned(), druid, 10);
+    save_trades(&db, &trades).await.unwrap();
+
+    let request = warp::test::request()
+        .method("GET")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .path("/trades");
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter =
+        routes::trades(&mut dp(), db, Default::default(), ks, cache).recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let trade = &body["content"]["half"];
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(trade["role"], "Acceptor");
+    assert_eq!(trade["state"], "Accepted");
+    assert_eq!(trade["druid"], "halfdruid");
+    assert_eq!(trade["receive_address"], COMMON_PUB_ADDR);
+}
+
+/// Test POST data anchored by wallet transactions
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_anchored_data_user() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let db = get_wallet_db("").await;
+    let mut tx = Transaction::new();
+    tx.outputs.push(TxOut {
+        value: Asset::item(1, None, Some("anchored".to_owned())),
+        ..Default::default()
+    });
+    save_anchored_data(&db, "g_anchor".to_owned(), &tx)
+        .await
+        .unwrap();
+
+    let request = warp::test::request()
+        .method("POST")
+        .path("/anchored_data")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&vec!["g_anchor", "g_unknown"]);
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::anchored_data_user(&mut dp(), db, Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Anchored data successfully retrieved\",\"route\":\"anchored_data\",\"content\":{\"g_anchor\":[{\"index\":0,\"data\":\"anchored\"}]}}");
+}
+
+/// Test POST construct address from public key
+#[tokio::test(flavor = "current_thread")]
+async fn test_address_construction() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mut address1 = BTreeMap::new();
+    address1.insert(
+        "pub_key",
+        vec![
+            109, 133, 37, 100, 46, 243, 13, 156, 189, 123, 142, 12, 24, 169, 49, 186, 187, 0, 63,
+            27, 129, 207, 183, 13, 156, 208, 171, 164, 179, 118, 131, 183,
+        ],
+    );
+
+    let mut address2 = BTreeMap::new();
+    address2.insert(
+        "pub_key_hex",
+        "6d8525642ef30d9cbd7b8e0c18a931babb003f1b81cfb70d9cd0aba4b37683b7",
+    );
+
+    let address3 = AddressConstructData {
+        pub_key_hex: Some(
+            "6d8525642ef30d9cbd7b8e0c18a931babb003f1b81cfb70d9cd0aba4b37683b7".to_owned(),
+        ),
+        version: Some(0),
+        ..Default::default()
+    };
+
+    let request1 = || {
+        warp::test::request()
+            .method("POST")
+            .path("/address_construction")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .header("Content-Type", "application/json")
+    };
+
+    let com_req_id_plus_1 = "2ae7bc9cba924e3cb73c0249893078d8";
+    let request2 = || {
+        warp::test::request()
+            .method("POST")
+            .path("/address_construction")
+            .header("x-cache-id", com_req_id_plus_1)
+            .header("Content-Type", "application/json")
+    };
+
+    let com_req_id_plus_2 = "2ae7bc9cba924e3cb73c0249893078d9";
+    let request3 = || {
+        warp::test::request()
+            .method("POST")
+            .path("/address_construction")
+            .header("x-cache-id", com_req_id_plus_2)
+            .header("Content-Type", "application/json")
+    };
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+    let filter = routes::address_construction(&mut dp(), Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let res1 = request1().json(&address1).reply(&filter).await;
+    let res2 = request2().json(&address2).reply(&filter).await;
+    let res3 = request3().json(&address3).reply(&filter).await;
+
+    //
+    // Assert
+    //
+    let expected1 = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Address successfully constructed\",\"route\":\"address_construction\",\"content\":\"ca0abdcd2826a77218af0914601ee34c7ff44127aab9d0671267b25a7d36946a\"}";
+    let expected2 = "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d8\",\"status\":\"Success\",\"reason\":\"Address successfully constructed\",\"route\":\"address_construction\",\"content\":\"ca0abdcd2826a77218af0914601ee34c7ff44127aab9d0671267b25a7d36946a\"}";
+
+    assert_eq!((res1.status(), res1.headers().clone()), success_json());
+    assert_eq!(res1.body(), expected1);
 
     assert_eq!((res2.status(), res2.headers().clone()), success_json());
     assert_eq!(res2.body(), expected2);
@@ -1610,41 +2515,291 @@ async fn test_post_import_keypairs_success() {
             address_version: None,
         },
     );
-    let imported_addresses = Addresses { addresses };
-    let ks = to_api_keys(Default::default());
-    let cache = create_new_cache(CACHE_LIVE_TIME);
+    let imported_addresses = Addresses { addresses };
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::import_keypairs(
+        &mut dp(),
+        db.clone(),
+        self_node,
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let wallet_addresses_before = db.get_known_addresses().unwrap();
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/import_keypairs")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&imported_addresses)
+        .reply(&filter)
+        .await;
+
+    error!("res: {:?}", res);
+
+    let wallet_addresses_after = db.get_known_addresses().unwrap();
+
+    // Header to match
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", HeaderValue::from_static("application/json"));
+    assert_eq!(wallet_addresses_before, Vec::<String>::new());
+    assert_eq!(wallet_addresses_after, vec![COMMON_PUB_ADDR.to_string()]);
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Key-pairs successfully imported\",\"route\":\"import_keypairs\",\"content\":[\"13bd3351b78beb2d0dadf2058dcc926c\"]}");
+}
+
+/// Test POST import watch-only addresses
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_import_watch_only() {
+    let _ = tracing_log_try_init();
+    let (self_node, _self_socket) = new_self_node(NodeType::User).await;
+    let db = get_wallet_db("").await;
+    let imported = ImportWatchOnlyData {
+        public_keys: vec![COMMON_PUB_KEY.to_string()],
+        ..Default::default()
+    };
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::import_watch_only(
+        &mut dp(),
+        db.clone(),
+        self_node,
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/import_watch_only")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&imported)
+        .reply(&filter)
+        .await;
+
+    let public_key = PublicKey::from_slice(&hex::decode(COMMON_PUB_KEY).unwrap()).unwrap();
+    let expected = vec![construct_address_for(&public_key, None)];
+    let watch_only = db.get_watch_only_store().unwrap();
+    let watched: Vec<_> = watch_only.addresses().keys().cloned().collect();
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(body["content"], serde_json::json!(expected));
+    assert_eq!(db.get_known_addresses().unwrap(), Vec::<String>::new());
+    assert_eq!(watched, expected);
+}
+
+/// Test POST label a wallet address
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_address_label() {
+    let _ = tracing_log_try_init();
+    let mut db = get_wallet_db("").await;
+    let (address, _) = db.generate_payment_address().await.unwrap();
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::address_label(&mut dp(), db.clone(), Default::default(), ks, cache)
+        .recover(handle_rejection);
+    let request = |address: &str, label: &str, cache_id: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/address_label")
+            .header("Content-Type", "application/json")
+            .header("x-cache-id", cache_id)
+            .json(&AddressLabelData {
+                address: address.to_owned(),
+                label: Some(label.to_owned()),
+            })
+    };
+
+    let res = request(&address, "salary", COMMON_REQ_ID)
+        .reply(&filter)
+        .await;
+    let unknown = request("unknown", "salary", "2ae7bc9cba924e3cb73c0249893078d8")
+        .reply(&filter)
+        .await;
+
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(unknown.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(db.get_addresses_by_label("salary").unwrap(), vec![address]);
+}
+
+/// Test POST export and import of an encrypted wallet backup
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_export_import_backup() {
+    let _ = tracing_log_try_init();
+    let (self_node, _self_socket) = new_self_node(NodeType::User).await;
+    let mut db = get_wallet_db("").await;
+    let (address, _) = db.generate_payment_address().await.unwrap();
+    let restored = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+
+    let export_filter = routes::export_backup(
+        &mut dp(),
+        db,
+        Default::default(),
+        to_api_keys(Default::default()),
+        create_new_cache(CACHE_LIVE_TIME),
+    )
+    .recover(handle_rejection);
+    let import_filter = routes::import_backup(
+        &mut dp(),
+        restored.clone(),
+        self_node,
+        Default::default(),
+        to_api_keys(Default::default()),
+        create_new_cache(CACHE_LIVE_TIME),
+    )
+    .recover(handle_rejection);
+
+    let export_res = warp::test::request()
+        .method("POST")
+        .path("/export_backup")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&ExportBackupData {
+            passphrase: "backup".to_owned(),
+        })
+        .reply(&export_filter)
+        .await;
+    let exported: serde_json::Value = serde_json::from_slice(export_res.body()).unwrap();
+    let backup: WalletBackup = serde_json::from_value(exported["content"].clone()).unwrap();
+
+    let import_res = warp::test::request()
+        .method("POST")
+        .path("/import_backup")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&ImportBackupData {
+            passphrase: "backup".to_owned(),
+            backup,
+        })
+        .reply(&import_filter)
+        .await;
+
+    assert_eq!(
+        (export_res.status(), export_res.headers().clone()),
+        success_json()
+    );
+    assert_eq!(
+        (import_res.status(), import_res.headers().clone()),
+        success_json()
+    );
+    assert_eq!(restored.get_known_addresses().unwrap(), vec![address]);
+}
+
+/// Test POST backup of the wallet to a file, and restore from it
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_backup_restore_wallet() {
+    let _ = tracing_log_try_init();
+    let (self_node, _self_socket) = new_self_node(NodeType::User).await;
+    let db = get_wallet_db("").await;
+    let restored = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+    let path = std::env::temp_dir().join(format!("backup_wallet_{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let info = WalletFileData {
+        path: path.to_string_lossy().into_owned(),
+        passphrase: "backup".to_owned(),
+    };
+
+    let backup_filter = routes::backup_wallet(
+        &mut dp(),
+        db.clone(),
+        Default::default(),
+        to_api_keys(Default::default()),
+        create_new_cache(CACHE_LIVE_TIME),
+    )
+    .recover(handle_rejection);
+    let restore_filter = routes::restore_wallet(
+        &mut dp(),
+        restored.clone(),
+        self_node,
+        Default::default(),
+        to_api_keys(Default::default()),
+        create_new_cache(CACHE_LIVE_TIME),
+    )
+    .recover(handle_rejection);
+
+    let backup_res = warp::test::request()
+        .method("POST")
+        .path("/backup_wallet")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&info)
+        .reply(&backup_filter)
+        .await;
+    let restore_res = warp::test::request()
+        .method("POST")
+        .path("/restore_wallet")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&info)
+        .reply(&restore_filter)
+        .await;
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        (backup_res.status(), backup_res.headers().clone()),
+        success_json()
+    );
+    assert_eq!(
+        (restore_res.status(), restore_res.headers().clone()),
+        success_json()
+    );
+    assert_eq!(
+        restored.get_known_addresses().unwrap(),
+        db.get_known_addresses().unwrap()
+    );
+    assert_eq!(
+        restored.get_fund_store().unwrap().running_total(),
+        db.get_fund_store().unwrap().running_total()
+    );
+}
 
-    let filter = routes::import_keypairs(
+/// Test POST export of the mnemonic phrase of the wallet seed
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_export_mnemonic() {
+    let _ = tracing_log_try_init();
+    let mut db = get_wallet_db("").await;
+    let (address, _) = db.generate_payment_address().await.unwrap();
+
+    let filter = routes::export_mnemonic(
         &mut dp(),
-        db.clone(),
-        self_node,
+        db,
         Default::default(),
-        ks,
-        cache,
+        to_api_keys(Default::default()),
+        create_new_cache(CACHE_LIVE_TIME),
     )
     .recover(handle_rejection);
-    let wallet_addresses_before = db.get_known_addresses();
+    let request = |passphrase: &str, call_id: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/export_mnemonic")
+            .header("Content-Type", "application/json")
+            .header("x-cache-id", call_id)
+            .json(&ExportMnemonicData {
+                passphrase: passphrase.to_owned(),
+            })
+    };
 
-    let res = warp::test::request()
-        .method("POST")
-        .path("/import_keypairs")
-        .header("Content-Type", "application/json")
-        .header("x-cache-id", COMMON_REQ_ID)
-        .json(&imported_addresses)
+    let wrong_res = request("wrong", "1ae7bc9cba924e3cb73c0249893078d7")
         .reply(&filter)
         .await;
+    let res = request("", COMMON_REQ_ID).reply(&filter).await;
+    let exported: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    let mnemonic = exported["content"].as_str().unwrap();
+    let restored = WalletDb::from_mnemonic(DbMode::InMemory, None, None, None, mnemonic)
+        .await
+        .unwrap();
 
-    error!("res: {:?}", res);
-
-    let wallet_addresses_after = db.get_known_addresses();
-
-    // Header to match
-    let mut headers = HeaderMap::new();
-    headers.insert("content-type", HeaderValue::from_static("application/json"));
-    assert_eq!(wallet_addresses_before, Vec::<String>::new());
-    assert_eq!(wallet_addresses_after, vec![COMMON_PUB_ADDR.to_string()]);
+    assert_eq!(wrong_res.status(), StatusCode::UNAUTHORIZED);
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Key-pairs successfully imported\",\"route\":\"import_keypairs\",\"content\":[\"13bd3351b78beb2d0dadf2058dcc926c\"]}");
+    assert!(restored.get_known_addresses().unwrap().contains(&address));
 }
 
 #[tokio::test(flavor = "current_thread")]
@@ -1782,6 +2937,47 @@ async fn test_post_update_running_total() {
     assert_eq!(expected_frame, actual_frame);
 }
 
+/// Test POST start the dust consolidation successful
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_dust_consolidation() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let (mut self_node, _self_socket) = new_self_node(NodeType::User).await;
+
+    let request = warp::test::request()
+        .method("POST")
+        .path("/dust_consolidation")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&DustConsolidationData { active: true });
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter =
+        routes::dust_consolidation(&mut dp(), self_node.clone(), Default::default(), ks, cache)
+            .recover(handle_rejection);
+    let res = request.reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Dust consolidation starting\",\"route\":\"dust_consolidation\",\"content\":true}");
+
+    // Expected Frame
+    let expected_frame =
+        user_api_request_as_frame(UserApiRequest::SetDustConsolidation { active: true });
+    let actual_frame = next_event_frame(&mut self_node).await;
+    assert_eq!(expected_frame, actual_frame);
+}
+
 /// Test POST create item asset on mempool node successfully
 #[tokio::test(flavor = "current_thread")]
 async fn test_post_create_transactions() {
@@ -1837,6 +3033,7 @@ async fn test_post_create_transactions_common(address_version: Option<u64>) {
         }]),
         version: 1,
         druid_info: None,
+        network_id: None,
     }];
 
     let request = warp::test::request()
@@ -1878,6 +3075,77 @@ async fn test_post_create_transactions_common(address_version: Option<u64>) {
     );
 }
 
+/// Test POST create transactions tagged for another network rejected by the mempool node
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_create_transactions_other_network() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mempool = MempoolTest::new(Vec::new());
+
+    let previous_out = OutPoint::new(COMMON_PUB_ADDR.to_owned(), 0);
+    let signable_data = construct_tx_in_signable_hash(&previous_out);
+    let secret_key = decode_secret_key(COMMON_SEC_KEY).unwrap();
+    let raw_signature = sign::sign_detached(signable_data.as_bytes(), &secret_key);
+
+    let json_body = vec![CreateTransaction {
+        inputs: vec![CreateTxIn {
+            previous_out: Some(previous_out),
+            script_signature: Some(CreateTxInScript::Pay2PkH {
+                signable_data,
+                signature: hex::encode(raw_signature.as_ref()),
+                public_key: COMMON_PUB_KEY.to_owned(),
+                address_version: None,
+            }),
+        }],
+        outputs: vec![TxOut {
+            value: Asset::Token(TokenAmount(1)),
+            script_public_key: Some(COMMON_ADDRS[0].to_owned()),
+            locktime: 0,
+        }],
+        fees: None,
+        version: 1,
+        druid_info: None,
+        network_id: Some(NetworkId::Testnet),
+    }];
+
+    let request = warp::test::request()
+        .method("POST")
+        .path("/create_transactions")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&json_body);
+
+    //
+    // Act
+    //
+    let ks = to_api_keys(Default::default());
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+
+    let filter = routes::create_transactions(
+        &mut dp(),
+        mempool.threaded_calls.tx.clone(),
+        Default::default(),
+        ks,
+        cache,
+    )
+    .recover(handle_rejection);
+    let handle = mempool.spawn();
+    let res = request.reply(&filter).await;
+    let _mempool = handle.await.unwrap();
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        (res.status(), res.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"Generic error: Transaction of network Testnet, expected Mainnet\",\"route\":\"create_transactions\",\"content\":\"null\"}");
+}
+
 /// Test POST create item asset on mempool node successfully
 #[tokio::test(flavor = "current_thread")]
 async fn test_post_create_item_asset_tx_mempool() {
@@ -2038,7 +3306,7 @@ async fn test_post_change_passphrase() {
     // Arrange
     //
     let mut db = get_wallet_db("old_passphrase").await;
-    let (payment_address, expected_address_store) = db.generate_payment_address().await;
+    let (payment_address, expected_address_store) = db.generate_payment_address().await.unwrap();
 
     let json_body = ChangePassphraseData {
         old_passphrase: String::from("old_passphrase"),
@@ -2061,7 +3329,7 @@ async fn test_post_change_passphrase() {
         .recover(handle_rejection);
     let res = request.reply(&filter).await;
     let actual = db.test_passphrase(String::from("new_passphrase")).await;
-    let actual_address_store = db.get_address_store(&payment_address);
+    let actual_address_store = db.get_address_store(&payment_address).unwrap();
 
     //
     // Assert
@@ -2166,6 +3434,217 @@ async fn test_post_change_blank_passphrase_failure() {
     assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"New passphrase cannot be blank\",\"route\":\"change_passphrase\",\"content\":\"null\"}");
 }
 
+/// Test POST compact db, for admin keys only
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_compact_db() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let db = get_db_with_block().await;
+    let request = || {
+        warp::test::request()
+            .method("POST")
+            .path("/compact_db")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .header("x-api-key", COMMON_VALID_API_KEY)
+    };
+    let mut admin_keys = BTreeMap::new();
+    admin_keys.insert(
+        "compact_db".to_owned(),
+        vec![COMMON_VALID_API_KEY.to_owned()],
+    );
+    let ks = to_api_keys(admin_keys);
+    let cache = create_new_cache(CACHE_LIVE_TIME);
+    let filter = routes::compact_storage_db(&mut dp(), db, Default::default(), ks, cache)
+        .recover(handle_rejection);
+
+    //
+    // Act
+    //
+    let res = request().reply(&filter).await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Database compacted\",\"route\":\"compact_db\",\"content\":\"null\"}");
+}
+
+/// Test POST failover to a standby mempool node
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_failover() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mempool = MempoolTest::new(Default::default());
+    let request = warp::test::request()
+        .method("POST")
+        .path("/failover")
+        .header("Content-Type", "application/json")
+        .header("x-cache-id", COMMON_REQ_ID)
+        .json(&"127.0.0.1:12300");
+
+    //
+    // Act
+    //
+    let filter = routes::failover(
+        &mut dp(),
+        mempool.threaded_calls.tx.clone(),
+        Default::default(),
+        Default::default(),
+        create_new_cache(CACHE_LIVE_TIME),
+    )
+    .recover(handle_rejection);
+    let handle = mempool.spawn();
+    let res = request.reply(&filter).await;
+    let _ = handle.await;
+
+    //
+    // Assert
+    //
+    assert_eq!((res.status(), res.headers().clone()), success_json());
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Failover to standby proposed\",\"route\":\"failover\",\"content\":\"null\"}");
+}
+
+/// Test POST update peer IP filter, rejecting invalid ranges
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_update_peer_ip_filter() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let (self_node, _self_socket) = new_self_node(NodeType::Mempool).await;
+    let request = |allow: &[&str]| {
+        warp::test::request()
+            .method("POST")
+            .path("/update_peer_ip_filter")
+            .header("Content-Type", "application/json")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .header("x-api-key", COMMON_VALID_API_KEY)
+            .json(&PeerIpFilterData {
+                allow: allow.iter().map(|a| a.to_string()).collect(),
+                deny: vec!["10.0.0.66".to_owned()],
+            })
+    };
+    let mut admin_keys = BTreeMap::new();
+    admin_keys.insert(
+        "update_peer_ip_filter".to_owned(),
+        vec![COMMON_VALID_API_KEY.to_owned()],
+    );
+    let filter = || {
+        let ks = to_api_keys(admin_keys.clone());
+        let cache = create_new_cache(CACHE_LIVE_TIME);
+        routes::update_peer_ip_filter(&mut dp(), self_node.clone(), Default::default(), ks, cache)
+            .recover(handle_rejection)
+    };
+
+    //
+    // Act
+    //
+    let res_invalid = request(&["10.0.0.0/33"]).reply(&filter()).await;
+    let res_ok = request(&["10.0.0.0/24"]).reply(&filter()).await;
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        (res_invalid.status(), res_invalid.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!((res_ok.status(), res_ok.headers().clone()), success_json());
+    assert_eq!(res_ok.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Peer IP filter updated\",\"route\":\"update_peer_ip_filter\",\"content\":{\"allow\":[\"10.0.0.0/24\"],\"deny\":[\"10.0.0.66/32\"]}}");
+    assert_eq!(
+        self_node.peer_ip_filter().await,
+        PeerIpFilter::from_strs(&["10.0.0.0/24".to_owned()], &["10.0.0.66".to_owned()]).unwrap()
+    );
+}
+
+/// Test POST change tracing filter directives
+#[tokio::test(flavor = "current_thread")]
+async fn test_post_log_filter() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    set_log_filter_reload(
+        Box::new({
+            let applied = applied.clone();
+            move |directives: &str| {
+                if directives == "invalid[" {
+                    return Err(StringError("invalid directives".to_owned()));
+                }
+                applied.lock().unwrap().push(directives.to_owned());
+                Ok(())
+            }
+        }),
+        "info".to_owned(),
+    );
+
+    let request = |directives: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/log_filter")
+            .header("Content-Type", "application/json")
+            .header("x-cache-id", COMMON_REQ_ID)
+            .json(&LogFilterData {
+                directives: directives.to_owned(),
+            })
+    };
+    let filter = |api_keys: BTreeMap<String, Vec<String>>| {
+        let ks = to_api_keys(api_keys);
+        let cache = create_new_cache(CACHE_LIVE_TIME);
+        routes::log_filter(&mut dp(), Default::default(), ks, cache).recover(handle_rejection)
+    };
+    let mut admin_keys = BTreeMap::new();
+    admin_keys.insert(
+        "log_filter".to_owned(),
+        vec![COMMON_VALID_API_KEY.to_owned()],
+    );
+
+    //
+    // Act
+    //
+    let res_no_key = request("debug").reply(&filter(Default::default())).await;
+    let res_invalid = request("invalid[")
+        .header("x-api-key", COMMON_VALID_API_KEY)
+        .reply(&filter(admin_keys.clone()))
+        .await;
+    let res_ok = request("info,aiblock_network::comms_handler=debug")
+        .header("x-api-key", COMMON_VALID_API_KEY)
+        .reply(&filter(admin_keys))
+        .await;
+
+    //
+    // Assert
+    //
+    assert_eq!(
+        (res_no_key.status(), res_no_key.headers().clone()),
+        fail_json(StatusCode::UNAUTHORIZED)
+    );
+    assert_eq!(
+        (res_invalid.status(), res_invalid.headers().clone()),
+        fail_json(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(res_invalid.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Error\",\"reason\":\"Generic error: invalid directives\",\"route\":\"log_filter\",\"content\":\"null\"}");
+    assert_eq!((res_ok.status(), res_ok.headers().clone()), success_json());
+    assert_eq!(res_ok.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Log filter updated\",\"route\":\"log_filter\",\"content\":\"info,aiblock_network::comms_handler=debug\"}");
+    assert_eq!(
+        current_log_filter().as_deref(),
+        Some("info,aiblock_network::comms_handler=debug")
+    );
+    assert_eq!(
+        *applied.lock().unwrap(),
+        vec!["info,aiblock_network::comms_handler=debug".to_owned()]
+    );
+}
+
 /// Test POST fetch block hashes for blocks that contain given `tx_hashes`
 #[tokio::test(flavor = "current_thread")]
 async fn test_post_block_nums_by_tx_hashes() {
@@ -2233,7 +3712,7 @@ async fn test_post_pause_nodes() {
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"\",\"route\":\"pause_nodes\",\"content\":\"null\"}");
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Attempt coordinated node pause\",\"route\":\"pause_nodes\",\"content\":\"null\"}");
 }
 
 /// Test POST resume nodes
@@ -2270,7 +3749,7 @@ async fn test_post_resume_nodes() {
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"\",\"route\":\"resume_nodes\",\"content\":\"null\"}");
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Attempt coordinated node resume\",\"route\":\"resume_nodes\",\"content\":\"null\"}");
 }
 
 /// Test POST update shared config
@@ -2313,5 +3792,5 @@ async fn test_post_update_shared_config() {
     // Assert
     //
     assert_eq!((res.status(), res.headers().clone()), success_json());
-    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"\",\"route\":\"update_shared_config\",\"content\":\"null\"}");
+    assert_eq!(res.body(), "{\"id\":\"2ae7bc9cba924e3cb73c0249893078d7\",\"status\":\"Success\",\"reason\":\"Attempt send shared config\",\"route\":\"update_shared_config\",\"content\":\"null\"}");
 }
@@ -1,11 +1,15 @@
 use super::{
+    client_auth::ClientCertRoles,
     errors::{ApiError, ApiErrorType},
     handlers::DbgPaths,
     responses::{common_error_reply, json_serialize_embed, CallResponse, JsonReply},
 };
 use crate::utils::{ApiKeys, RoutesPoWInfo};
+use crate::wallet::manager::is_node_wallet_name;
+use crate::wallet::{WalletDb, WalletManager};
 use futures::Future;
 use moka::future::{Cache, CacheBuilder};
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use std::time::Duration;
 use tracing::{log::error, warn};
@@ -22,6 +26,52 @@ pub fn with_node_component<T: Clone + Send>(
     warp::any().map(move || comp.clone())
 }
 
+// Wallet named by the x-wallet-name header, the node wallet if none is named
+pub fn with_wallet(
+    wallets: WalletManager,
+    route: &'static str,
+) -> impl Filter<Extract = (WalletDb,), Error = Rejection> + Clone {
+    select_wallet(wallets, route, false)
+}
+
+// Node wallet, for routes acting through the node: naming another wallet is rejected
+pub fn with_node_wallet(
+    wallets: WalletManager,
+    route: &'static str,
+) -> impl Filter<Extract = (WalletDb,), Error = Rejection> + Clone {
+    select_wallet(wallets, route, true)
+}
+
+// Select the wallet named by the x-wallet-name header, rejecting unknown names
+fn select_wallet(
+    wallets: WalletManager,
+    route: &'static str,
+    node_wallet_only: bool,
+) -> impl Filter<Extract = (WalletDb,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("x-wallet-name")
+        .and(warp::header::optional::<String>("x-cache-id"))
+        .and_then(move |name: Option<String>, id: Option<String>| {
+            let wallets = wallets.clone();
+            async move {
+                let message = match name.as_deref() {
+                    Some(name) if node_wallet_only && !is_node_wallet_name(name) => {
+                        ApiErrorType::NodeWalletOnly
+                    }
+                    name => match wallets.get(name) {
+                        Some(db) => return Ok(db),
+                        None => ApiErrorType::UnknownWallet(name.unwrap_or_default().to_owned()),
+                    },
+                };
+                Err(warp::reject::custom(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    message,
+                    id.unwrap_or_default(),
+                    route.to_owned(),
+                )))
+            }
+        })
+}
+
 // Add route path to mutable reference DbgPaths
 pub fn warp_path(
     dp: &mut DbgPaths,
@@ -52,67 +102,217 @@ pub fn map_api_res_and_cache(
 // Authorizes a request based on API keys as well as PoW requirements for the route
 // Successfull authorization will extract the x-cache-id header value
 pub fn auth_request(
-    _routes_pow: RoutesPoWInfo,
+    routes_pow: RoutesPoWInfo,
     api_keys: ApiKeys,
 ) -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
-    warp::path::full()
-        .and(warp::header::headers_cloned())
-        .and_then(move |path: FullPath, headers: HeaderMap| {
-            let route_path = path.as_str()[1..].to_owned(); /* Slice to remove '/' prefix */
-            // let route_difficulty = routes_pow.lock().unwrap().get(&route_path).cloned();
-            let needed_keys = api_keys.lock().unwrap().get(&route_path).cloned();
+    auth_request_with(routes_pow, api_keys, false)
+}
 
-            async move {
-                // Extract headers
-                let id = headers
-                    .get("x-cache-id")
-                    .and_then(|n| n.to_str().ok())
-                    .unwrap_or_default();
+// Authorizes a request for administrative routes: an API key must be configured for the route
+// Successfull authorization will extract the x-cache-id header value
+pub fn auth_admin_request(
+    routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+) -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    auth_request_with(routes_pow, api_keys, true)
+}
 
-                let _nonce = headers
-                    .get("x-nonce")
-                    .and_then(|n| n.to_str().ok())
-                    .unwrap_or_default();
+// Role attached to API keys, by listing them in the API keys config under `role:<name>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiRole {
+    /// Read node and wallet information only
+    ReadOnly,
+    /// Read, and administer the node
+    Operator,
+    /// Read, and spend or export wallet funds and keys
+    WalletSpend,
+}
+
+// Access a route needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouteAccess {
+    Read,
+    Operate,
+    Spend,
+}
+
+impl ApiRole {
+    pub const ALL: [ApiRole; 3] = [Self::ReadOnly, Self::Operator, Self::WalletSpend];
+
+    // Entry of the API keys config listing the keys with this role
+    pub fn config_key(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "role:read_only",
+            Self::Operator => "role:operator",
+            Self::WalletSpend => "role:wallet_spend",
+        }
+    }
+
+    // Role for its name in configs: read_only, operator or wallet_spend
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|role| role.config_key().strip_prefix("role:") == Some(name))
+    }
 
+    // Whether the role allows calling the route, an administrative route if `admin`
+    pub fn allows(self, route: &str, admin: bool) -> bool {
+        match route_access(route, admin) {
+            RouteAccess::Read => true,
+            RouteAccess::Operate => self == Self::Operator,
+            RouteAccess::Spend => self == Self::WalletSpend,
+        }
+    }
+}
+
+// Access needed by a route, reading by default and operating for administrative routes
+fn route_access(route: &str, admin: bool) -> RouteAccess {
+    match route {
+        "make_payment"
+        | "make_ip_payment"
+        | "request_donation"
+        | "make_trade_offer"
+        | "create_item_asset"
+        | "create_transactions"
+        | "export_keypairs"
+        | "import_keypairs"
+        | "export_backup"
+        | "export_mnemonic"
+        | "import_backup"
+        | "backup_wallet"
+        | "restore_wallet"
+        | "import_watch_only"
+        | "address_label"
+        | "change_passphrase"
+        | "payment_address"
+        | "deposit_address" => RouteAccess::Spend,
+        "pause_nodes"
+        | "resume_nodes"
+        | "failover"
+        | "update_shared_config"
+        | "get_shared_config"
+        | "log_filter"
+        | "get_peer_ip_filter"
+        | "update_peer_ip_filter"
+        | "update_running_total"
+        | "dust_consolidation"
+        | "compact_db"
+        | "signing_audit_log" => RouteAccess::Operate,
+        _ if admin => RouteAccess::Operate,
+        _ => RouteAccess::Read,
+    }
+}
+
+// Whether the roles of an API key allow calling a route, None if the key has no role
+pub fn api_key_role_access(
+    api_keys: &BTreeMap<String, Vec<String>>,
+    route: &str,
+    api_key: &str,
+    admin: bool,
+) -> Option<bool> {
+    let mut roles = ApiRole::ALL
+        .iter()
+        .filter(|role| {
+            api_keys
+                .get(role.config_key())
+                .is_some_and(|keys| keys.iter().any(|k| k == api_key))
+        })
+        .peekable();
+    roles.peek()?;
+    Some(roles.any(|role| role.allows(route, admin)))
+}
+
+// Authorizes a request, optionally rejecting routes with no API key configured
+fn auth_request_with(
+    _routes_pow: RoutesPoWInfo,
+    api_keys: ApiKeys,
+    api_key_required: bool,
+) -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(warp::header::headers_cloned())
+        .and(warp::ext::optional::<ClientCertRoles>())
+        .and_then(
+            move |path: FullPath, headers: HeaderMap, cert_roles: Option<ClientCertRoles>| {
+                let route_path = path.as_str()[1..].to_owned(); /* Slice to remove '/' prefix */
+                // let route_difficulty = routes_pow.lock().unwrap().get(&route_path).cloned();
                 let api_key = headers
                     .get("x-api-key")
                     .and_then(|n| n.to_str().ok())
-                    .unwrap_or_default();
-
-                // Error for authorization failure
-                let err_unauthorized = Err(warp::reject::custom(ApiError::new(
-                    StatusCode::UNAUTHORIZED,
-                    ApiErrorType::Unauthorized,
-                    id.to_owned(),
-                    route_path,
-                )));
-
-                // All requests require a unique ID of 32 characters
-                // if id.chars().count() != 32 {
-                //     return err_unauthorized;
-                // }
-
-                // API key is needed
-                if let Some(needed_api_keys) = needed_keys {
-                    if !needed_api_keys.contains(&api_key.to_string()) {
-                        warn!("Unauthorized - API Key needed");
+                    .unwrap_or_default()
+                    .to_owned();
+                let (needed_keys, role_access) = {
+                    let api_keys = api_keys.lock().unwrap();
+                    let role_access = match cert_roles.filter(|roles| !roles.0.is_empty()) {
+                        Some(roles) => Some(
+                            roles
+                                .0
+                                .iter()
+                                .any(|role| role.allows(&route_path, api_key_required)),
+                        ),
+                        None => {
+                            api_key_role_access(&api_keys, &route_path, &api_key, api_key_required)
+                        }
+                    };
+                    (api_keys.get(&route_path).cloned(), role_access)
+                };
+
+                async move {
+                    // Extract headers
+                    let id = headers
+                        .get("x-cache-id")
+                        .and_then(|n| n.to_str().ok())
+                        .unwrap_or_default();
+
+                    let _nonce = headers
+                        .get("x-nonce")
+                        .and_then(|n| n.to_str().ok())
+                        .unwrap_or_default();
+
+                    // Error for authorization failure
+                    let err_unauthorized = Err(warp::reject::custom(ApiError::new(
+                        StatusCode::UNAUTHORIZED,
+                        ApiErrorType::Unauthorized,
+                        id.to_owned(),
+                        route_path,
+                    )));
+
+                    // All requests require a unique ID of 32 characters
+                    // if id.chars().count() != 32 {
+                    //     return err_unauthorized;
+                    // }
+
+                    // Client certificate or API key with roles: the roles alone decide
+                    if let Some(allowed) = role_access {
+                        if !allowed {
+                            warn!("Unauthorized - Role does not allow route");
+                            return err_unauthorized;
+                        }
+                    } else if let Some(needed_api_keys) = needed_keys {
+                        // API key is needed
+                        if !needed_api_keys.contains(&api_key) {
+                            warn!("Unauthorized - API Key needed");
+                            return err_unauthorized;
+                        }
+                    } else if api_key_required {
+                        warn!("Unauthorized - No API Key configured for admin route");
                         return err_unauthorized;
                     }
-                }
 
-                // let hash_content = format!("{nonce}-{id}");
+                    // let hash_content = format!("{nonce}-{id}");
 
-                // This route requires PoW
-                // if let Some(difficulty) = route_difficulty {
-                //     if validate_pow_for_diff(difficulty, hash_content.as_bytes()).is_none() {
-                //         return err_unauthorized;
-                //     }
-                // }
+                    // This route requires PoW
+                    // if let Some(difficulty) = route_difficulty {
+                    //     if validate_pow_for_diff(difficulty, hash_content.as_bytes()).is_none() {
+                    //         return err_unauthorized;
+                    //     }
+                    // }
 
-                // No PoW required
-                Ok(id.to_owned())
-            }
-        })
+                    // No PoW required
+                    Ok(id.to_owned())
+                }
+            },
+        )
         .or_else(move |err| async move { Err(err) })
 }
 
@@ -0,0 +1,323 @@
+//! Command line handling shared by the node commands.
+
+use crate::{mempool, miner, pre_launch, storage, user};
+use aiblock_network::comms_handler::{read_recording, MessageRecorder, Node};
+use aiblock_network::crash_report;
+use aiblock_network::pid_file::{self, PidFile};
+use aiblock_network::{ResponseReason, RunnableNode};
+use clap::{App, Arg, ArgMatches};
+use std::any::Any;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Command, Stdio};
+use tokio::net::{lookup_host, TcpListener};
+use tracing::{error, info};
+
+/// Commands running each type of node
+pub fn node_subcommands<'a, 'b>() -> [App<'a, 'b>; 5] {
+    [
+        user::clap_app(),
+        miner::clap_app(),
+        mempool::clap_app(),
+        storage::clap_app(),
+        pre_launch::clap_app(),
+    ]
+}
+
+/// Run the node selected by the subcommand until it stops, then exit
+///
+/// ### Arguments
+///
+/// * `matches` - Parsed command line arguments, with one of the node subcommands.
+pub async fn run_node_command(matches: &ArgMatches<'_>) -> ! {
+    if let (_, Some(sub_matches)) = matches.subcommand() {
+        if sub_matches.is_present("daemon") {
+            start_daemon(sub_matches);
+        }
+    }
+
+    crash_report::tracing_log_init_with_crash_capture();
+    if let (sub_command, Some(sub_matches)) = matches.subcommand() {
+        if sub_matches.is_present("check") {
+            check_node_with_args(sub_command, sub_matches)
+                .await
+                .finish();
+        }
+    }
+
+    crash_report::install_panic_hook();
+    launch_node_with_args(matches).await;
+    info!("Node stopped");
+    std::process::exit(0);
+}
+
+async fn launch_node_with_args(matches: &ArgMatches<'_>) {
+    if let Some(sub_command) = matches.subcommand_name() {
+        let sub_matches = matches.subcommand_matches(sub_command).unwrap();
+        let _pid_file = match sub_matches.value_of("pid_file").map(PidFile::acquire) {
+            Some(Ok(pid_file)) => Some(pid_file),
+            Some(Err(e)) => {
+                error!("PID file not written: {e}");
+                std::process::exit(1);
+            }
+            None => None,
+        };
+
+        match sub_command {
+            "user" => user::run_node(sub_matches).await,
+            "miner" => miner::run_node(sub_matches).await,
+            "mempool" => mempool::run_node(sub_matches).await,
+            "storage" => storage::run_node(sub_matches).await,
+            "pre_launch" => pre_launch::run_node(sub_matches).await,
+            invalid_type => panic!("Invalid node type: {:?}", invalid_type),
+        }
+    } else {
+        info!("Node type needs to be specified.")
+    }
+}
+
+async fn check_node_with_args(sub_command: &str, sub_matches: &ArgMatches<'_>) -> SelfCheck {
+    match sub_command {
+        "user" => user::check_node(sub_matches).await,
+        "miner" => miner::check_node(sub_matches).await,
+        "mempool" => mempool::check_node(sub_matches).await,
+        "storage" => storage::check_node(sub_matches).await,
+        "pre_launch" => pre_launch::check_node(sub_matches).await,
+        invalid_type => panic!("Invalid node type: {:?}", invalid_type),
+    }
+}
+
+/// Completes with the reason to exit the node once SIGINT or SIGTERM is received
+///
+/// A signal that cannot be listened for never completes.
+pub async fn shutdown_signal() -> ResponseReason {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("SIGINT not handled: {e:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("SIGTERM not handled: {e:?}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("SIGINT received, shutting down"),
+        _ = terminate => info!("SIGTERM received, shutting down"),
+    }
+    ResponseReason::Shutdown
+}
+
+/// Arguments to record the frames received by a node or replay a recording into it
+pub fn message_record_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+    [
+        Arg::with_name("record_messages")
+            .long("record_messages")
+            .env("RECORD_MESSAGES")
+            .help("Append all the messages received by the node, with timestamps, to this file")
+            .takes_value(true),
+        Arg::with_name("replay_messages")
+            .long("replay_messages")
+            .env("REPLAY_MESSAGES")
+            .help("Feed the messages recorded in this file into the node, spaced as recorded")
+            .takes_value(true),
+    ]
+}
+
+/// Start recording or replaying the messages of the node as requested by the arguments
+///
+/// ### Arguments
+///
+/// * `matches`   - Parsed command line arguments.
+/// * `node_conn` - Connection of the node to record or replay into.
+pub fn start_message_record_replay(matches: &ArgMatches, node_conn: &Node) {
+    if let Some(path) = matches.value_of("record_messages") {
+        info!("Recording received messages to {path}");
+        node_conn.set_message_recorder(Some(MessageRecorder::create(path).unwrap()));
+    }
+
+    if let Some(path) = matches.value_of("replay_messages") {
+        let frames = read_recording(path).unwrap();
+        info!("Replaying {} messages from {path}", frames.len());
+        node_conn.replay_recording(frames);
+    }
+}
+
+/// Arguments to run the node in the background and manage its PID file
+pub fn daemon_args<'a, 'b>() -> [Arg<'a, 'b>; 3] {
+    [
+        Arg::with_name("daemon")
+            .long("daemon")
+            .help("Detach from the terminal and run the node in the background"),
+        Arg::with_name("pid_file")
+            .long("pid_file")
+            .env("PID_FILE")
+            .help("Write the node PID to this file, refusing to start if its node is still running")
+            .takes_value(true),
+        Arg::with_name("log_file")
+            .long("log_file")
+            .env("LOG_FILE")
+            .help("Append the logs of the node running in the background to this file")
+            .default_value("node.log")
+            .takes_value(true),
+    ]
+}
+
+/// Start this command again as a detached background process, then exit
+///
+/// The background process writes its logs to the log file and holds the PID file.
+///
+/// ### Arguments
+///
+/// * `matches` - Parsed command line arguments of the node.
+fn start_daemon(matches: &ArgMatches) -> ! {
+    if let Some(pid) = matches.value_of("pid_file").and_then(pid_file::running_pid) {
+        eprintln!("Node already running with PID {pid}");
+        std::process::exit(1);
+    }
+
+    let log_file = matches.value_of("log_file").unwrap();
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .unwrap_or_else(|e| panic!("Log file {} not opened: {}", log_file, e));
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command
+        .args(std::env::args_os().skip(1).filter(|arg| arg != "--daemon"))
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().unwrap())
+        .stderr(log);
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let child = command.spawn().unwrap();
+    println!("Node running in the background with PID {}", child.id());
+    std::process::exit(0);
+}
+
+/// Argument to check the environment of the node without joining the network
+pub fn check_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("check")
+        .long("check")
+        .help("Check the config, databases, TLS material, ports and peers of the node, then exit")
+}
+
+/// Report of the `--check` self-check, printed as each step completes
+#[derive(Default)]
+pub struct SelfCheck {
+    failed: usize,
+}
+
+impl SelfCheck {
+    /// Report the outcome of a step
+    ///
+    /// ### Arguments
+    ///
+    /// * `name`   - Description of the step.
+    /// * `result` - Outcome of the step.
+    pub fn step<T, E: fmt::Debug>(&mut self, name: &str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                println!("[ OK ] {name}");
+                Some(value)
+            }
+            Err(e) => {
+                println!("[FAIL] {name}: {e:?}");
+                self.failed += 1;
+                None
+            }
+        }
+    }
+
+    /// Load and validate the config, reporting a failure if the loading panics
+    ///
+    /// ### Arguments
+    ///
+    /// * `load` - Loads the config of the node.
+    pub fn config<T>(&mut self, load: impl FnOnce() -> T) -> Option<T> {
+        let result = panic::catch_unwind(AssertUnwindSafe(load)).map_err(panic_message);
+        self.step("Config parses and validates", result)
+    }
+
+    /// Resolve the address of each peer
+    ///
+    /// ### Arguments
+    ///
+    /// * `peers` - Addresses of the peers.
+    pub async fn peers<'c>(&mut self, peers: impl IntoIterator<Item = &'c str>) {
+        for peer in peers {
+            let result = lookup_host(peer).await.and_then(|mut addrs| {
+                addrs
+                    .next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No address"))
+            });
+            self.step(&format!("Peer {peer} resolves"), result);
+        }
+    }
+
+    /// Bind the API port, releasing it right away
+    ///
+    /// ### Arguments
+    ///
+    /// * `port` - Port of the API.
+    pub async fn api_port(&mut self, port: u16) {
+        let result = TcpListener::bind(SocketAddr::new([0, 0, 0, 0].into(), port)).await;
+        self.step(&format!("API port {port} binds"), result);
+    }
+
+    /// Scan the databases of a node constructed from the config
+    ///
+    /// Constructing the node loads its TLS material, opens its databases and binds its peer port.
+    ///
+    /// ### Arguments
+    ///
+    /// * `node` - Outcome of the node construction.
+    pub fn node<N: RunnableNode>(&mut self, node: Result<N, N::Error>) {
+        let name = "Node starts: TLS material loads, databases open and peer port binds";
+        if let Some(node) = self.step(name, node) {
+            let scan = node.scan_dbs();
+            let name = match &scan {
+                Ok(len) => format!("Databases scan, {len} entries read"),
+                Err(_) => "Databases scan".to_owned(),
+            };
+            self.step(&name, scan);
+        }
+    }
+
+    /// Print the summary and exit, with a non-zero status if a step failed
+    pub fn finish(self) -> ! {
+        if self.failed == 0 {
+            println!("All checks passed");
+            std::process::exit(0);
+        }
+        println!("{} check(s) failed", self.failed);
+        std::process::exit(1);
+    }
+}
+
+/// Message of a caught panic
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Unknown panic".to_owned(),
+        },
+    }
+}
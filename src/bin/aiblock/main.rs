@@ -0,0 +1,42 @@
+//! App running any AIBlock node or tool as a subcommand.
+//!
+//! The `node`, `upgrade` and `wallet` apps run the same commands.
+
+use clap::App;
+
+mod cli;
+mod mempool;
+mod miner;
+mod pre_launch;
+mod storage;
+mod upgrade;
+mod user;
+mod wallet;
+
+#[tokio::main]
+async fn main() {
+    let matches = clap_app().get_matches();
+    match matches.subcommand() {
+        ("upgrade", Some(sub_matches)) => {
+            if let Err(e) = upgrade::run(sub_matches) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        ("wallet", Some(sub_matches)) => {
+            if let Err(e) = wallet::run(sub_matches).await {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        _ => cli::run_node_command(&matches).await,
+    }
+}
+
+fn clap_app<'a, 'b>() -> App<'a, 'b> {
+    App::new("AIBlock")
+        .about("Runs an AIBlock node or tool.")
+        .subcommands(cli::node_subcommands())
+        .subcommand(upgrade::clap_app())
+        .subcommand(wallet::clap_app())
+}
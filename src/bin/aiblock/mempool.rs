@@ -1,126 +1,62 @@
 //! App to run a mempool node.
 
+use crate::cli::SelfCheck;
 use aiblock_network::configurations::MempoolNodeConfig;
-use aiblock_network::MempoolNode;
+use aiblock_network::crash_report::{self, CrashContext};
 use aiblock_network::{
-    get_sanction_addresses, loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, routes,
-    shutdown_connections, ResponseResult, SANC_LIST_PROD,
+    get_sanction_addresses, routes, serve_api, MempoolNode, NodeRunner, SANC_LIST_PROD,
 };
 use clap::{App, Arg, ArgMatches};
 use config::ConfigError;
-use std::net::SocketAddr;
 use tracing::info;
 
 pub async fn run_node(matches: &ArgMatches<'_>) {
     let mut config = configuration(load_settings(matches));
+    let crash_name = format!("mempool_{}", config.mempool_api_port);
+    crash_report::set_crash_context(CrashContext::new("mempool", &crash_name, &config));
+    crash_report::report_previous_crash(&crash_name);
 
     info!("Start node with config {config:?}");
 
     config.sanction_list = get_sanction_addresses(SANC_LIST_PROD.to_string(), &config.jurisdiction);
     let node = MempoolNode::new(config, Default::default()).await.unwrap();
     let api_inputs = node.api_inputs();
-
     info!("API Inputs: {api_inputs:?}");
-    info!("Started node at {}", node.local_address());
 
-    let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
-    let local_event_tx = node.local_event_tx().clone();
-    let threaded_calls_tx = node.threaded_call_tx().clone();
+    let (api_addr, api_tls, api_keys, routes_pow, peer) = api_inputs;
+    let api_routes = routes::mempool_node_routes(
+        api_keys,
+        routes_pow,
+        node.threaded_call_tx().clone(),
+        node.raft_metrics(),
+        peer,
+    );
 
-    // PERMANENT CONNEXION/DISCONNECTION HANDLING
-    let ((conn_loop_handle, stop_re_connect_tx), (disconn_loop_handle, stop_disconnect_tx)) = {
-        let (re_connect, disconnect_test) =
-            loops_re_connect_disconnect(node_conn.clone(), addrs_to_connect, local_event_tx);
+    let runner = NodeRunner::new(node);
+    crate::cli::start_message_record_replay(matches, &runner.node_conn());
+    runner
+        .with_api(serve_api(api_routes, api_addr, api_tls))
+        .with_exit(crate::cli::shutdown_signal())
+        .run()
+        .await;
+}
 
-        (
-            (tokio::spawn(re_connect.0), re_connect.1),
-            (tokio::spawn(disconnect_test.0), disconnect_test.1),
-        )
-    };
-
-    // Need to connect first so Raft messages can be sent.
-    loop_wait_connnect_to_peers_async(node_conn.clone(), expected_connected_addrs).await;
-    info!("Raft and Storage connection complete");
-
-    // RAFT HANDLING
-    let raft_loop_handle = {
-        let raft_loop = node.raft_loop();
-        tokio::spawn(async move {
-            info!("Peer connect complete, start Raft");
-            raft_loop.await;
-            info!("Raft complete");
-        })
-    };
-
-    // Warp API
-    let warp_handle = tokio::spawn({
-        let (api_addr, api_tls, api_keys, routes_pow, peer) = api_inputs;
-        let threaded_calls_tx = threaded_calls_tx;
-
-        info!("Warp API started on port {:?}", api_addr.port());
-        info!("");
-
-        let mut bind_address = "0.0.0.0:0".parse::<SocketAddr>().unwrap();
-        bind_address.set_port(api_addr.port());
-
-        async move {
-            let serve = warp::serve(routes::mempool_node_routes(
-                api_keys,
-                routes_pow,
-                threaded_calls_tx,
-                peer,
-            ));
-            if let Some(api_tls) = api_tls {
-                serve
-                    .tls()
-                    .key(&api_tls.pem_pkcs8_private_keys)
-                    .cert(&api_tls.pem_certs)
-                    .run(bind_address)
-                    .await;
-            } else {
-                serve.run(bind_address).await;
-            }
-        }
-    });
-
-    // REQUEST HANDLING
-    let main_loop_handle = tokio::spawn({
-        let mut node = node;
-        let mut node_conn = node_conn;
-
-        async move {
-            node.send_startup_requests().await.unwrap();
-
-            let mut exit = std::future::pending();
-            while let Some(response) = node.handle_next_event(&mut exit).await {
-                if node.handle_next_event_response(response).await == ResponseResult::Exit {
-                    break;
-                }
-            }
-            stop_re_connect_tx.send(()).unwrap();
-            stop_disconnect_tx.send(()).unwrap();
-
-            node.close_raft_loop().await;
-            shutdown_connections(&mut node_conn).await;
-        }
-    });
-
-    let (main, warp, raft, conn, disconn) = tokio::join!(
-        main_loop_handle,
-        warp_handle,
-        raft_loop_handle,
-        conn_loop_handle,
-        disconn_loop_handle
-    );
-    main.unwrap();
-    warp.unwrap();
-    raft.unwrap();
-    conn.unwrap();
-    disconn.unwrap();
+/// Check the environment of the node without joining the network
+pub async fn check_node(matches: &ArgMatches<'_>) -> SelfCheck {
+    let mut check = SelfCheck::default();
+    if let Some(config) = check.config(|| configuration(load_settings(matches))) {
+        let peers = config.mempool_nodes.iter().chain(&config.storage_nodes);
+        let peers = peers.chain(&config.user_nodes);
+        check.peers(peers.map(|peer| peer.address.as_str())).await;
+        check.api_port(config.mempool_api_port).await;
+        check.node(MempoolNode::new(config, Default::default()).await);
+    }
+    check
 }
 
 pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
     App::new("mempool")
+        .alias("compute")
         .about("Runs a basic mempool node.")
         .arg(
             Arg::with_name("config")
@@ -172,6 +108,13 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Run the mempool node using the given initial block config file.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("seed_utxo_file")
+                .long("seed_utxo_file")
+                .env("SEED_UTXO_FILE")
+                .help("Seed the UTXO set from the given JSON file instead of the initial block config.")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("mempool_miner_whitelist")
                 .long("mempool_miner_whitelist")
@@ -186,6 +129,9 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Run the specified mempool node index from config file")
                 .takes_value(true),
         )
+        .args(&crate::cli::message_record_args())
+        .args(&crate::cli::daemon_args())
+        .arg(crate::cli::check_arg())
         .arg(
             Arg::with_name("tls_private_key_override")
                 .long("tls_private_key_override")
@@ -280,6 +226,9 @@ fn load_settings(matches: &clap::ArgMatches) -> config::Config {
     if let Some(use_tls) = matches.value_of("api_use_tls") {
         settings.set("mempool_api_use_tls", use_tls).unwrap();
     }
+    if let Some(path) = matches.value_of("seed_utxo_file") {
+        settings.set("mempool_seed_utxo_file", path).unwrap();
+    }
     if let Some(enable_pipeline_reset) = matches.value_of("enable_pipeline_reset") {
         settings
             .set(
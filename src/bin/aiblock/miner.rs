@@ -1,225 +1,95 @@
 //! App to run a mining node.
 
-use aiblock_network::configurations::{ExtraNodeParams, MinerNodeConfig, UserNodeConfig};
-use aiblock_network::{
-    loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, routes, shutdown_connections,
-    ResponseResult,
-};
-use aiblock_network::{MinerNode, UserNode};
+use crate::cli::SelfCheck;
+use aiblock_network::configurations::{MinerNodeConfig, UserNodeConfig};
+use aiblock_network::crash_report::{self, CrashContext};
+use aiblock_network::{routes, serve_api, MinerNode, NodeBuilder, NodeRunner};
 use clap::{App, Arg, ArgMatches};
 use config::{ConfigError, Value};
 use std::collections::HashMap;
-use std::net::SocketAddr;
 use tracing::info;
 
 pub async fn run_node(matches: &ArgMatches<'_>) {
     let (config, user_config) = configuration(load_settings(matches));
+    let crash_name = format!("miner_{}", config.miner_api_port);
+    crash_report::set_crash_context(CrashContext::new("miner", &crash_name, &config));
+    crash_report::report_previous_crash(&crash_name);
     info!("Start node with config {:?}", config);
     let node = MinerNode::new(config, Default::default()).await.unwrap();
-    info!("Started node at {}", node.local_address());
+    let (miner_db, miner_node, api_addr, api_tls, api_keys, current_block, api_pow_info) =
+        node.api_inputs();
+    let shared_wallet_db = node.get_wallet_db().clone();
 
-    let miner_api_inputs = node.api_inputs();
-    let shared_wallet_db = Some(node.get_wallet_db().clone());
-    let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
-    let local_event_tx = node.local_event_tx().clone();
-
-    // PERMANENT CONNEXION/DISCONNECTION HANDLING
-    let ((conn_loop_handle, stop_re_connect_tx), (disconn_loop_handle, stop_disconnect_tx)) = {
-        let (re_connect, disconnect_test) =
-            loops_re_connect_disconnect(node_conn.clone(), addrs_to_connect, local_event_tx);
-
-        (
-            (tokio::spawn(re_connect.0), re_connect.1),
-            (tokio::spawn(disconnect_test.0), disconnect_test.1),
-        )
-    };
-
-    // Need to connect first so Raft messages can be sent.
-    loop_wait_connnect_to_peers_async(node_conn.clone(), expected_connected_addrs).await;
-
-    // Miner main loop
-    let main_loop_handle = tokio::spawn({
-        let mut node = node;
-        let mut node_conn = node_conn;
-
-        async move {
-            node.send_startup_requests().await.unwrap();
-
-            let mut exit = std::future::pending();
-            while let Some(response) = node.handle_next_event(&mut exit).await {
-                if node.handle_next_event_response(response).await == ResponseResult::Exit {
-                    break;
-                }
-            }
-            stop_re_connect_tx.send(()).unwrap();
-            stop_disconnect_tx.send(()).unwrap();
-
-            shutdown_connections(&mut node_conn).await;
-        }
-    });
+    let runner = NodeRunner::new(node);
+    crate::cli::start_message_record_replay(matches, &runner.node_conn());
 
     match user_config {
         Some(config) => {
-            let shared_members = ExtraNodeParams {
-                shared_wallet_db,
-                ..Default::default()
-            };
-
             info!("Start user node with config {config:?}");
-            let user_node = UserNode::new(config, shared_members).await.unwrap();
-            let api_inputs = (user_node.api_inputs(), miner_api_inputs);
-            info!("Started user node at {}", user_node.local_address());
-
-            let (user_node_conn, user_addrs_to_connect, user_expected_connected_addrs) =
-                user_node.connect_info_peers();
-            let user_local_event_tx = user_node.local_event_tx().clone();
-
-            // PERMANENT CONNEXION/DISCONNECTION HANDLING
-            let (
-                (user_conn_loop_handle, user_stop_re_connect_tx),
-                (user_disconn_loop_handle, user_stop_disconnect_tx),
-            ) = {
-                let (user_re_connect, user_disconnect_test) = loops_re_connect_disconnect(
-                    user_node_conn.clone(),
-                    user_addrs_to_connect,
-                    user_local_event_tx,
-                );
-
-                (
-                    (tokio::spawn(user_re_connect.0), user_re_connect.1),
-                    (tokio::spawn(user_disconnect_test.0), user_disconnect_test.1),
-                )
-            };
-
-            // Need to connect first so Raft messages can be sent.
-            loop_wait_connnect_to_peers_async(
-                user_node_conn.clone(),
-                user_expected_connected_addrs,
-            )
-            .await;
-
-            // User main loop
-            let user_main_loop_handle = tokio::spawn({
-                let mut node = user_node;
-                let mut node_conn = user_node_conn;
-
-                async move {
-                    node.send_startup_requests().await.unwrap();
-
-                    let mut exit = std::future::pending();
-                    while let Some(response) = node.handle_next_event(&mut exit).await {
-                        if node.handle_next_event_response(response).await == ResponseResult::Exit {
-                            break;
-                        }
-                    }
-                    user_stop_re_connect_tx.send(()).unwrap();
-                    user_stop_disconnect_tx.send(()).unwrap();
-
-                    shutdown_connections(&mut node_conn).await;
-                }
-            });
+            let user_node = NodeBuilder::new(config)
+                .with_shared_wallet_db(shared_wallet_db)
+                .build()
+                .await
+                .unwrap();
 
             // User / Miner combined warp API
-            let warp_handle = tokio::spawn({
-                let (
-                    (db, user_node, api_addr, api_tls, api_keys, api_pow_info),
-                    (_, miner_node, _, _, _, current_block, _),
-                ) = api_inputs;
-
-                info!("Warp API started on port {:?}", api_addr.port());
-                info!("");
-
-                let mut bind_address = "0.0.0.0:0".parse::<SocketAddr>().unwrap();
-                bind_address.set_port(api_addr.port());
-
-                async move {
-                    let serve = warp::serve(routes::miner_node_with_user_routes(
-                        api_keys,
-                        api_pow_info,
-                        current_block,
-                        db,
-                        miner_node,
-                        user_node,
-                    ));
-                    if let Some(api_tls) = api_tls {
-                        serve
-                            .tls()
-                            .key(&api_tls.pem_pkcs8_private_keys)
-                            .cert(&api_tls.pem_certs)
-                            .run(bind_address)
-                            .await;
-                    } else {
-                        serve.run(bind_address).await;
-                    }
-                }
-            });
-
-            let (result, result_user, conn, conn_user, disconn, disconn_user, warp_result) = tokio::join!(
-                main_loop_handle,
-                user_main_loop_handle,
-                conn_loop_handle,
-                user_conn_loop_handle,
-                disconn_loop_handle,
-                user_disconn_loop_handle,
-                warp_handle
+            let (wallets, user_node_conn, api_addr, api_tls, api_keys, api_pow_info) =
+                user_node.api_inputs();
+            let api_routes = routes::miner_node_with_user_routes(
+                api_keys,
+                api_pow_info,
+                current_block,
+                wallets,
+                miner_node,
+                user_node_conn,
             );
 
-            result.unwrap();
-            conn.unwrap();
-            disconn.unwrap();
-            result_user.unwrap();
-            conn_user.unwrap();
-            disconn_user.unwrap();
-            warp_result.unwrap();
+            tokio::join!(
+                runner
+                    .with_api(serve_api(api_routes, api_addr, api_tls))
+                    .with_exit(crate::cli::shutdown_signal())
+                    .run(),
+                NodeRunner::new(user_node)
+                    .with_exit(crate::cli::shutdown_signal())
+                    .run()
+            );
         }
         None => {
-            // Miner warp API
-            let warp_handle = tokio::spawn({
-                let (db, miner_node, api_addr, api_tls, api_keys, current_block, api_pow_info) =
-                    miner_api_inputs;
-
-                info!("Warp API started on port {:?}", api_addr.port());
-                info!("");
-
-                let mut bind_address = "0.0.0.0:0".parse::<SocketAddr>().unwrap();
-                bind_address.set_port(api_addr.port());
-
-                async move {
-                    let serve = warp::serve(routes::miner_node_routes(
-                        api_keys,
-                        api_pow_info,
-                        current_block,
-                        db,
-                        miner_node,
-                    ));
-                    if let Some(api_tls) = api_tls {
-                        serve
-                            .tls()
-                            .key(&api_tls.pem_pkcs8_private_keys)
-                            .cert(&api_tls.pem_certs)
-                            .run(bind_address)
-                            .await;
-                    } else {
-                        serve.run(bind_address).await;
-                    }
-                }
-            });
-
-            let (result, conn, disconn, warp_result) = tokio::join!(
-                main_loop_handle,
-                conn_loop_handle,
-                disconn_loop_handle,
-                warp_handle
+            let api_routes = routes::miner_node_routes(
+                api_keys,
+                api_pow_info,
+                current_block,
+                miner_db,
+                miner_node,
             );
 
-            result.unwrap();
-            conn.unwrap();
-            disconn.unwrap();
-            warp_result.unwrap();
+            runner
+                .with_api(serve_api(api_routes, api_addr, api_tls))
+                .with_exit(crate::cli::shutdown_signal())
+                .run()
+                .await;
         }
     }
 }
 
+/// Check the environment of the node without joining the network
+pub async fn check_node(matches: &ArgMatches<'_>) -> SelfCheck {
+    let mut check = SelfCheck::default();
+    if let Some((config, _)) = check.config(|| configuration(load_settings(matches))) {
+        check
+            .peers(
+                config
+                    .mempool_nodes
+                    .iter()
+                    .map(|peer| peer.address.as_str()),
+            )
+            .await;
+        check.api_port(config.miner_api_port).await;
+        check.node(MinerNode::new(config, Default::default()).await);
+    }
+    check
+}
+
 pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
     App::new("miner")
         .about("Runs a basic miner node.")
@@ -287,6 +157,9 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Run the specified miner node index from config file")
                 .takes_value(true),
         )
+        .args(&crate::cli::message_record_args())
+        .args(&crate::cli::daemon_args())
+        .arg(crate::cli::check_arg())
         .arg(
             Arg::with_name("with_user_index")
                 .long("with_user_index")
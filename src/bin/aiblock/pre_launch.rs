@@ -1,69 +1,42 @@
 //! App to run a pre-launch node.
 
-use aiblock_network::configurations::PreLaunchNodeConfig;
-use aiblock_network::PreLaunchNode;
-use aiblock_network::{
-    loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, shutdown_connections,
-    ResponseResult,
-};
+use crate::cli::SelfCheck;
+use aiblock_network::configurations::{PreLaunchNodeConfig, PreLaunchNodeType};
+use aiblock_network::crash_report::{self, CrashContext};
+use aiblock_network::{NodeRunner, PreLaunchNode};
 use clap::{App, Arg, ArgMatches};
 use config::ConfigError;
 use tracing::info;
 
 pub async fn run_node(matches: &ArgMatches<'_>) {
     let config = configuration(load_settings(matches));
+    let crash_name = match config.node_type {
+        PreLaunchNodeType::Mempool => format!("pre_launch_mempool_{}", config.mempool_node_idx),
+        PreLaunchNodeType::Storage => format!("pre_launch_storage_{}", config.storage_node_idx),
+    };
+    crash_report::set_crash_context(CrashContext::new("pre_launch", &crash_name, &config));
+    crash_report::report_previous_crash(&crash_name);
 
     info!("Start node with config {config:?}");
     let node = PreLaunchNode::new(config, Default::default())
         .await
         .unwrap();
 
-    info!("Started node at {}", node.local_address());
-
-    let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
-    let local_event_tx = node.local_event_tx().clone();
-
-    // PERMANENT CONNEXION/DISCONNECTION HANDLING
-    let ((conn_loop_handle, stop_re_connect_tx), (disconn_loop_handle, stop_disconnect_tx)) = {
-        let (re_connect, disconnect_test) =
-            loops_re_connect_disconnect(node_conn.clone(), addrs_to_connect, local_event_tx);
-
-        (
-            (tokio::spawn(re_connect.0), re_connect.1),
-            (tokio::spawn(disconnect_test.0), disconnect_test.1),
-        )
-    };
-
-    // Need to connect first so Raft messages can be sent.
-    loop_wait_connnect_to_peers_async(node_conn.clone(), expected_connected_addrs).await;
-
-    // REQUEST HANDLING
-    let main_loop_handle = tokio::spawn({
-        let mut node = node;
-        let mut node_conn = node_conn;
-
-        async move {
-            node.send_startup_requests().await.unwrap();
-
-            let mut exit = std::future::pending();
-            while let Some(response) = node.handle_next_event(&mut exit).await {
-                if node.handle_next_event_response(response).await == ResponseResult::Exit {
-                    break;
-                }
-            }
-            stop_re_connect_tx.send(()).unwrap();
-            stop_disconnect_tx.send(()).unwrap();
-
-            shutdown_connections(&mut node_conn).await;
-        }
-    });
-
-    let (main, conn, disconn) =
-        tokio::join!(main_loop_handle, conn_loop_handle, disconn_loop_handle);
+    NodeRunner::new(node)
+        .with_exit(crate::cli::shutdown_signal())
+        .run()
+        .await;
+}
 
-    main.unwrap();
-    conn.unwrap();
-    disconn.unwrap();
+/// Check the environment of the node without joining the network
+pub async fn check_node(matches: &ArgMatches<'_>) -> SelfCheck {
+    let mut check = SelfCheck::default();
+    if let Some(config) = check.config(|| configuration(load_settings(matches))) {
+        let peers = config.mempool_nodes.iter().chain(&config.storage_nodes);
+        check.peers(peers.map(|peer| peer.as_str())).await;
+        check.node(PreLaunchNode::new(config, Default::default()).await);
+    }
+    check
 }
 
 pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
@@ -103,6 +76,8 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Use PKCS8 private key as a string to use for this node TLS certificate.")
                 .takes_value(true),
         )
+        .args(&crate::cli::daemon_args())
+        .arg(crate::cli::check_arg())
 }
 
 fn load_settings(matches: &clap::ArgMatches) -> config::Config {
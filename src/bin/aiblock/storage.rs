@@ -1,119 +1,46 @@
 //! App to run a storage node.
 
+use crate::cli::SelfCheck;
 use aiblock_network::configurations::StorageNodeConfig;
-use aiblock_network::StorageNode;
-use aiblock_network::{
-    loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, routes, shutdown_connections,
-    ResponseResult,
-};
+use aiblock_network::crash_report::{self, CrashContext};
+use aiblock_network::{routes, serve_api, NodeRunner, StorageNode};
 use clap::{App, Arg, ArgMatches};
 use config::ConfigError;
-use std::net::SocketAddr;
 use tracing::info;
 
 pub async fn run_node(matches: &ArgMatches<'_>) {
     let config = configuration(load_settings(matches));
+    let crash_name = format!("storage_{}", config.storage_api_port);
+    crash_report::set_crash_context(CrashContext::new("storage", &crash_name, &config));
+    crash_report::report_previous_crash(&crash_name);
 
     info!("Start node with config {config:?}");
     let node = StorageNode::new(config, Default::default()).await.unwrap();
+    let (db, api_addr, api_tls, api_keys, api_pow_info) = node.api_inputs();
+    let raft_metrics = node.raft_metrics();
+
+    let runner = NodeRunner::new(node);
+    crate::cli::start_message_record_replay(matches, &runner.node_conn());
+    let api_routes =
+        routes::storage_node_routes(api_keys, api_pow_info, db, raft_metrics, runner.node_conn());
+
+    runner
+        .with_api(serve_api(api_routes, api_addr, api_tls))
+        .with_exit(crate::cli::shutdown_signal())
+        .run()
+        .await;
+}
 
-    info!("Started node at {}", node.local_address());
-
-    let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
-    let api_inputs = node.api_inputs();
-
-    let local_event_tx = node.local_event_tx().clone();
-
-    // PERMANENT CONNEXION/DISCONNECTION HANDLING
-    let ((conn_loop_handle, stop_re_connect_tx), (disconn_loop_handle, stop_disconnect_tx)) = {
-        let (re_connect, disconnect_test) =
-            loops_re_connect_disconnect(node_conn.clone(), addrs_to_connect, local_event_tx);
-
-        (
-            (tokio::spawn(re_connect.0), re_connect.1),
-            (tokio::spawn(disconnect_test.0), disconnect_test.1),
-        )
-    };
-
-    // Need to connect first so Raft messages can be sent.
-    loop_wait_connnect_to_peers_async(node_conn.clone(), expected_connected_addrs).await;
-
-    // RAFT HANDLING
-    let raft_loop_handle = {
-        let raft_loop = node.raft_loop();
-        tokio::spawn(async move {
-            info!("Peer connect complete, start Raft");
-            raft_loop.await;
-            info!("Raft complete");
-        })
-    };
-
-    // Warp API
-    let warp_handle = tokio::spawn({
-        let (db, api_addr, api_tls, api_keys, api_pow_info) = api_inputs;
-
-        info!("Warp API started on port {:?}", api_addr.port());
-        info!("");
-
-        let mut bind_address = "0.0.0.0:0".parse::<SocketAddr>().unwrap();
-        bind_address.set_port(api_addr.port());
-        let node_conn_debug = node_conn.clone();
-
-        async move {
-            let serve = warp::serve(routes::storage_node_routes(
-                api_keys,
-                api_pow_info,
-                db,
-                node_conn_debug,
-            ));
-            if let Some(api_tls) = api_tls {
-                serve
-                    .tls()
-                    .key(&api_tls.pem_pkcs8_private_keys)
-                    .cert(&api_tls.pem_certs)
-                    .run(bind_address)
-                    .await;
-            } else {
-                serve.run(bind_address).await;
-            }
-        }
-    });
-
-    // REQUEST HANDLING
-    let main_loop_handle = tokio::spawn({
-        let mut node = node;
-        let mut node_conn = node_conn.clone();
-
-        async move {
-            node.send_startup_requests().await.unwrap();
-
-            let mut exit = std::future::pending();
-            while let Some(response) = node.handle_next_event(&mut exit).await {
-                if node.handle_next_event_response(response).await == ResponseResult::Exit {
-                    break;
-                }
-            }
-            stop_re_connect_tx.send(()).unwrap();
-            stop_disconnect_tx.send(()).unwrap();
-
-            node.close_raft_loop().await;
-            shutdown_connections(&mut node_conn).await;
-        }
-    });
-
-    let (main, warp, raft, conn, disconn) = tokio::join!(
-        main_loop_handle,
-        warp_handle,
-        raft_loop_handle,
-        conn_loop_handle,
-        disconn_loop_handle
-    );
-
-    main.unwrap();
-    warp.unwrap();
-    raft.unwrap();
-    conn.unwrap();
-    disconn.unwrap();
+/// Check the environment of the node without joining the network
+pub async fn check_node(matches: &ArgMatches<'_>) -> SelfCheck {
+    let mut check = SelfCheck::default();
+    if let Some(config) = check.config(|| configuration(load_settings(matches))) {
+        let peers = config.mempool_nodes.iter().chain(&config.storage_nodes);
+        check.peers(peers.map(|peer| peer.address.as_str())).await;
+        check.api_port(config.storage_api_port).await;
+        check.node(StorageNode::new(config, Default::default()).await);
+    }
+    check
 }
 
 pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
@@ -148,6 +75,9 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Run the specified storage node index from config file")
                 .takes_value(true),
         )
+        .args(&crate::cli::message_record_args())
+        .args(&crate::cli::daemon_args())
+        .arg(crate::cli::check_arg())
         .arg(
             Arg::with_name("api_port")
                 .short("p")
@@ -1,99 +1,84 @@
 //! App to run a user node.
 
+use crate::cli::SelfCheck;
 use aiblock_network::configurations::UserNodeConfig;
-use aiblock_network::{
-    loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, routes, shutdown_connections,
-    ResponseResult, UserNode,
-};
+use aiblock_network::crash_report::{self, CrashContext};
+use aiblock_network::{routes, serve_api, NodeRunner, UserNode};
 use clap::{App, Arg, ArgMatches};
 use config::{ConfigError, Value};
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use tracing::info;
+use std::path::Path;
+use tracing::{error, info};
 
 pub async fn run_node(matches: &ArgMatches<'_>) {
     let config = configuration(load_settings(matches));
+    let crash_name = format!("user_{}", config.user_api_port);
+    crash_report::set_crash_context(CrashContext::new("user", &crash_name, &config));
+    crash_report::report_previous_crash(&crash_name);
 
     info!("Starting node with config: {config:?}");
     info!("");
 
+    let passphrase = config.passphrase.clone().unwrap_or_default();
     let node = UserNode::new(config, Default::default()).await.unwrap();
-
-    info!("Started node at {}", node.local_address());
-
-    let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
-    let local_event_tx = node.local_event_tx().clone();
-    let api_inputs = node.api_inputs();
-
-    // PERMANENT CONNEXION/DISCONNECTION HANDLING
-    let ((conn_loop_handle, stop_re_connect_tx), (disconn_loop_handle, stop_disconnect_tx)) = {
-        let (re_connect, disconnect_test) =
-            loops_re_connect_disconnect(node_conn.clone(), addrs_to_connect, local_event_tx);
-
-        (
-            (tokio::spawn(re_connect.0), re_connect.1),
-            (tokio::spawn(disconnect_test.0), disconnect_test.1),
-        )
-    };
-
-    // Need to connect first so Raft messages can be sent.
-    loop_wait_connnect_to_peers_async(node_conn.clone(), expected_connected_addrs).await;
-
-    // REQUEST HANDLING
-    let main_loop_handle = tokio::spawn({
-        let mut node = node;
-        let mut node_conn = node_conn;
-
-        async move {
-            node.send_startup_requests().await.unwrap();
-
-            let mut exit = std::future::pending();
-            while let Some(response) = node.handle_next_event(&mut exit).await {
-                if node.handle_next_event_response(response).await == ResponseResult::Exit {
-                    break;
-                }
+    if let Some(path) = matches.value_of("backup_wallet") {
+        match node
+            .get_wallet_db()
+            .export_backup(Path::new(path), &passphrase)
+        {
+            Ok(metadata) => info!(
+                "Wallet backup of {} keys written to {path}",
+                metadata.key_count
+            ),
+            Err(e) => {
+                error!("Wallet backup not written: {e}");
+                std::process::exit(1);
             }
-            stop_re_connect_tx.send(()).unwrap();
-            stop_disconnect_tx.send(()).unwrap();
-            shutdown_connections(&mut node_conn).await;
         }
-    });
-
-    // Warp API
-    let warp_handle = tokio::spawn({
-        let (db, node, api_addr, api_tls, api_keys, api_pow_info) = api_inputs;
-
-        info!("Warp API started on port {:?}", api_addr.port());
-        info!("");
-
-        let mut bind_address = "0.0.0.0:0".parse::<SocketAddr>().unwrap();
-        bind_address.set_port(api_addr.port());
-
-        async move {
-            let serve = warp::serve(routes::user_node_routes(api_keys, api_pow_info, db, node));
-            if let Some(api_tls) = api_tls {
-                serve
-                    .tls()
-                    .key(&api_tls.pem_pkcs8_private_keys)
-                    .cert(&api_tls.pem_certs)
-                    .run(bind_address)
-                    .await;
-            } else {
-                serve.run(bind_address).await;
+        std::process::exit(0);
+    }
+    if let Some(path) = matches.value_of("restore_wallet") {
+        match node
+            .get_wallet_db()
+            .import_backup(Path::new(path), &passphrase)
+            .await
+        {
+            Ok(addresses) => info!("Wallet restored from {path}: {} addresses", addresses.len()),
+            Err(e) => {
+                error!("Wallet not restored: {e}");
+                std::process::exit(1);
             }
         }
-    });
-
-    let (main_result, warp_result, conn, disconn) = tokio::join!(
-        main_loop_handle,
-        warp_handle,
-        conn_loop_handle,
-        disconn_loop_handle
-    );
-    main_result.unwrap();
-    warp_result.unwrap();
-    conn.unwrap();
-    disconn.unwrap();
+    }
+
+    let (wallets, node_conn, api_addr, api_tls, api_keys, api_pow_info) = node.api_inputs();
+    let api_routes = routes::user_node_routes(api_keys, api_pow_info, wallets, node_conn);
+
+    let runner = NodeRunner::new(node);
+    crate::cli::start_message_record_replay(matches, &runner.node_conn());
+    runner
+        .with_api(serve_api(api_routes, api_addr, api_tls))
+        .with_exit(crate::cli::shutdown_signal())
+        .run()
+        .await;
+}
+
+/// Check the environment of the node without joining the network
+pub async fn check_node(matches: &ArgMatches<'_>) -> SelfCheck {
+    let mut check = SelfCheck::default();
+    if let Some(config) = check.config(|| configuration(load_settings(matches))) {
+        check
+            .peers(
+                config
+                    .mempool_nodes
+                    .iter()
+                    .map(|peer| peer.address.as_str()),
+            )
+            .await;
+        check.api_port(config.user_api_port).await;
+        check.node(UserNode::new(config, Default::default()).await);
+    }
+    check
 }
 
 pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
@@ -155,6 +140,9 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Run the specified user node index from config file")
                 .takes_value(true),
         )
+        .args(&crate::cli::message_record_args())
+        .args(&crate::cli::daemon_args())
+        .arg(crate::cli::check_arg())
         .arg(
             Arg::with_name("mempool_index")
                 .long("mempool_index")
@@ -167,6 +155,27 @@ pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Enter a password or passphase for the encryption of the Wallet.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("wallet_name")
+                .long("wallet-name")
+                .help("Open the named wallet along with the node wallet, selectable from the API with the x-wallet-name header. Repeat to open several wallets.")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("backup_wallet")
+                .long("backup-wallet")
+                .help("Write an encrypted backup of the wallet to this new file, then exit. The backup is protected by the wallet passphrase.")
+                .takes_value(true)
+                .conflicts_with("restore_wallet"),
+        )
+        .arg(
+            Arg::with_name("restore_wallet")
+                .long("restore-wallet")
+                .help("Restore the wallet from this backup file, protected by the wallet passphrase, before running the node.")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("address")
                 .long("address")
@@ -321,6 +330,23 @@ fn load_settings(matches: &clap::ArgMatches) -> config::Config {
         settings.set("passphrase", index).unwrap();
     }
 
+    // Named wallets not in the config use the node passphrase
+    if let Some(names) = matches.values_of("wallet_name") {
+        let mut wallets = settings.get_array("user_wallets").unwrap_or_default();
+        for name in names {
+            let listed = wallets.iter().any(|wallet| {
+                let wallet = wallet.clone().into_table().unwrap_or_default();
+                wallet.get("name").map(|n| n.to_string()).as_deref() == Some(name)
+            });
+            if !listed {
+                let mut wallet = HashMap::new();
+                wallet.insert("name".to_owned(), name.to_owned());
+                wallets.push(Value::new(None, wallet));
+            }
+        }
+        settings.set("user_wallets", wallets).unwrap();
+    }
+
     if let Some(api_port) = matches.value_of("auto_donate") {
         settings.set("user_auto_donate", api_port).unwrap();
     }
@@ -0,0 +1,597 @@
+//! App to manage a wallet database without running a node.
+//!
+//! Opens the `WalletDb` of a user or miner node directly, so addresses can be
+//! generated, funds inspected, keys moved between wallets and payments signed
+//! on an offline machine or from scripts. Signed transactions are written to a
+//! file to be submitted from a connected machine.
+//! The node using the wallet must not be running at the same time.
+
+use aiblock_network::configurations::DbMode;
+use aiblock_network::db_utils::CustomDbSpec;
+use aiblock_network::denomination::{checked_sub, Denomination};
+use aiblock_network::wallet::hd::{
+    gap_limit_restore_count, mnemonic_to_seed, DEFAULT_HD_GAP_LIMIT, DEFAULT_HD_RESTORE_COUNT,
+};
+use aiblock_network::wallet::{
+    AddressStore, AddressStoreHex, KeyFormat, WalletBackup, WalletDb, WalletDbError,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::{fmt, io};
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::{OutPoint, TxOut};
+use tw_chain::utils::transaction_utils::{construct_tx_core, construct_tx_hash};
+
+/// Default directory of the wallet databases, as used by the nodes
+const DEFAULT_WALLET_PATH: &str = "src/wallet/wallet";
+
+/// Run the wallet command selected by the arguments
+///
+/// ### Arguments
+///
+/// * `matches` - Parsed command line arguments of the wallet command.
+pub async fn run(matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let mut wallet = open_wallet(matches)?;
+
+    match matches.subcommand() {
+        ("new_address", Some(_)) => new_address(&mut wallet).await,
+        ("balance", Some(_)) => balance(&wallet, matches).await,
+        ("utxos", Some(_)) => utxos(&wallet),
+        ("export_keys", Some(sub)) => export_keys(&wallet, sub),
+        ("import_keys", Some(sub)) => import_keys(&wallet, sub).await,
+        ("export_backup", Some(sub)) => export_backup(&wallet, sub),
+        ("import_backup", Some(sub)) => import_backup(&wallet, sub).await,
+        ("export_seed", Some(_)) => export_seed(&wallet, matches).await,
+        ("export_mnemonic", Some(_)) => export_mnemonic(&wallet, matches).await,
+        ("restore_seed", Some(sub)) => restore_seed(&mut wallet, sub).await,
+        ("pay", Some(sub)) => pay(&mut wallet, sub).await,
+        (other, _) => Err(WalletCliError::Args(format!("Unknown command: {other:?}"))),
+    }
+}
+
+/// Error running a wallet command
+#[derive(Debug)]
+pub enum WalletCliError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Wallet(WalletDbError),
+    Args(String),
+}
+
+impl fmt::Display for WalletCliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Json(err) => write!(f, "JSON error: {err}"),
+            Self::Wallet(err) => write!(f, "Wallet error: {err}"),
+            Self::Args(msg) => write!(f, "Argument error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for WalletCliError {}
+
+impl From<io::Error> for WalletCliError {
+    fn from(other: io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+impl From<serde_json::Error> for WalletCliError {
+    fn from(other: serde_json::Error) -> Self {
+        Self::Json(other)
+    }
+}
+
+impl From<WalletDbError> for WalletCliError {
+    fn from(other: WalletDbError) -> Self {
+        Self::Wallet(other)
+    }
+}
+
+/// Key-pairs file, in the format of the `export_keypairs` and `import_keypairs` APIs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeyFile {
+    addresses: BTreeMap<String, AddressStoreHex>,
+}
+
+/// Unspent output held by the wallet
+#[derive(Debug, Serialize)]
+struct Utxo {
+    out_point: OutPoint,
+    address: String,
+    asset: Asset,
+}
+
+/// Generate a new address and print it, as a checked address of the wallet network
+async fn new_address(wallet: &mut WalletDb) -> Result<(), WalletCliError> {
+    let address = wallet.generate_checked_payment_address().await?;
+    println!("{address}");
+    Ok(())
+}
+
+/// Print the total, locked and available token amounts and the item amounts
+///
+/// Token amounts are printed in units and in tokens.
+async fn balance(wallet: &WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let denomination = denomination(matches)?;
+    let mut fund_store = wallet.get_fund_store()?;
+    let total = fund_store.running_total().clone();
+    let locked_coinbase = wallet.get_locked_coinbase().await;
+    fund_store.filter_locked_coinbase(&locked_coinbase);
+    let available = fund_store.running_total();
+    let locked = checked_sub(total.tokens, available.tokens).unwrap_or_default();
+
+    let balance = serde_json::json!({
+        "total_tokens": total.tokens.0,
+        "locked_tokens": locked.0,
+        "available_tokens": available.tokens.0,
+        "total": denomination.format(total.tokens),
+        "locked": denomination.format(locked),
+        "available": denomination.format(available.tokens),
+        "items": total.items,
+    });
+    println!("{}", serde_json::to_string_pretty(&balance)?);
+    Ok(())
+}
+
+/// Print the unspent outputs held by the wallet with their address
+fn utxos(wallet: &WalletDb) -> Result<(), WalletCliError> {
+    let fund_store = wallet.get_fund_store()?;
+    let utxos = fund_store
+        .transactions()
+        .iter()
+        .map(|(out_point, asset)| {
+            Ok(Utxo {
+                out_point: out_point.clone(),
+                address: wallet.get_transaction_address(out_point)?,
+                asset: asset.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, WalletDbError>>()?;
+
+    println!("{}", serde_json::to_string_pretty(&utxos)?);
+    Ok(())
+}
+
+/// Write the key-pairs of all or the given addresses to a file, or print them
+fn export_keys(wallet: &WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let addresses: Vec<String> = match matches.values_of("address") {
+        Some(addresses) => addresses.map(|a| a.to_owned()).collect(),
+        None => wallet.get_known_addresses()?,
+    };
+
+    let known = wallet.get_known_addresses()?;
+    let mut keys = KeyFile::default();
+    for address in addresses {
+        if !known.contains(&address) {
+            return Err(WalletCliError::Args(format!("Unknown address: {address}")));
+        }
+        let store = wallet.get_address_store(&address)?.into();
+        keys.addresses.insert(address, store);
+    }
+
+    match matches.value_of("file") {
+        Some(path) => {
+            serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &keys)?;
+            eprintln!("Exported {} key-pairs to {path}", keys.addresses.len());
+        }
+        None => println!("{}", serde_json::to_string_pretty(&keys)?),
+    }
+    Ok(())
+}
+
+/// Add the key-pairs of a key file, or a secret key exported by other tooling, to the wallet
+async fn import_keys(wallet: &WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let secret_key = match (matches.value_of("hex"), matches.value_of("pem")) {
+        (Some(hex), _) => Some((KeyFormat::Hex, hex.to_owned())),
+        (_, Some(path)) => Some((KeyFormat::Pem, std::fs::read_to_string(path)?)),
+        (None, None) => None,
+    };
+    if let Some((format, secret_key)) = secret_key {
+        let address_version = parse_arg(matches, "address_version")?;
+        let expected_address = matches.value_of("address");
+        let address = wallet
+            .import_keypair(format, &secret_key, address_version, expected_address)
+            .await?;
+        println!("{address}");
+        return Ok(());
+    }
+
+    let path = matches
+        .value_of("file")
+        .ok_or_else(|| WalletCliError::Args("Missing --file, --hex or --pem".to_owned()))?;
+    let keys: KeyFile = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    for (address, store) in keys.addresses {
+        let store = AddressStore::try_from_hex_store(store)?;
+        wallet
+            .save_address_to_wallet(address.clone(), store)
+            .await?;
+        println!("{address}");
+    }
+    Ok(())
+}
+
+/// Write the key-pairs of the wallet to an encrypted backup file, in the format of the
+/// `export_backup` API
+fn export_backup(wallet: &WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let path = matches.value_of("file").unwrap();
+    let passphrase = matches.value_of("backup_passphrase").unwrap();
+
+    let backup = wallet.export_key_backup(passphrase)?;
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &backup)?;
+    eprintln!("Exported {} key-pairs to {path}", backup.metadata.key_count);
+    Ok(())
+}
+
+/// Add the key-pairs of an encrypted backup file to the wallet
+async fn import_backup(wallet: &WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let path = matches.value_of("file").unwrap();
+    let passphrase = matches.value_of("backup_passphrase").unwrap();
+
+    let backup: WalletBackup = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    for address in wallet.import_key_backup(&backup, passphrase).await? {
+        println!("{address}");
+    }
+    Ok(())
+}
+
+/// Print the hex master seed the addresses of the wallet are derived from
+async fn export_seed(wallet: &WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let passphrase = matches
+        .value_of("passphrase")
+        .unwrap_or_default()
+        .to_owned();
+    println!("{}", wallet.export_hd_seed(passphrase).await?);
+    Ok(())
+}
+
+/// Print the mnemonic phrase of the master seed the addresses of the wallet are derived from
+async fn export_mnemonic(
+    wallet: &WalletDb,
+    matches: &ArgMatches<'_>,
+) -> Result<(), WalletCliError> {
+    let passphrase = matches
+        .value_of("passphrase")
+        .unwrap_or_default()
+        .to_owned();
+    println!("{}", wallet.export_mnemonic(passphrase).await?);
+    Ok(())
+}
+
+/// Derive the addresses of a hex master seed or of a mnemonic phrase again, printing them
+///
+/// With `utxo_addresses`, addresses are derived until `gap_limit` addresses in
+/// a row hold no output in the UTXO set, instead of a fixed count.
+async fn restore_seed(
+    wallet: &mut WalletDb,
+    matches: &ArgMatches<'_>,
+) -> Result<(), WalletCliError> {
+    let seed = match matches.value_of("mnemonic") {
+        Some(mnemonic) => mnemonic_to_seed(mnemonic)?,
+        None => hex::decode(matches.value_of("seed").unwrap())
+            .map_err(|_| WalletCliError::Args("Invalid seed".to_owned()))?,
+    };
+    let count = match matches.value_of("utxo_addresses") {
+        Some(path) => {
+            let utxo_addresses = read_utxo_addresses(path)?;
+            let gap_limit = parse_arg(matches, "gap_limit")?.unwrap_or(DEFAULT_HD_GAP_LIMIT);
+            gap_limit_restore_count(&seed, gap_limit, |a| utxo_addresses.contains(a))
+        }
+        None => parse_arg(matches, "count")?.unwrap_or(DEFAULT_HD_RESTORE_COUNT),
+    };
+
+    let overwrite = matches.is_present("overwrite");
+
+    for address in wallet.restore_from_seed(&seed, count, overwrite).await? {
+        println!("{address}");
+    }
+    Ok(())
+}
+
+/// Addresses of the UTXO set, from a JSON list or a saved `utxo_addresses` API response
+///
+/// ### Arguments
+///
+/// * `path` - Path of the JSON file.
+fn read_utxo_addresses(path: &str) -> Result<BTreeSet<String>, WalletCliError> {
+    let value: serde_json::Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let addresses = value.get("content").cloned().unwrap_or(value);
+    Ok(serde_json::from_value(addresses)?)
+}
+
+/// Construct and sign a payment from the wallet funds, writing the transaction to a file
+///
+/// The spent outputs are marked as spent in the wallet, as the user node does
+/// when making a payment.
+async fn pay(wallet: &mut WalletDb, matches: &ArgMatches<'_>) -> Result<(), WalletCliError> {
+    let address = wallet.decode_payment_address(matches.value_of("address").unwrap())?;
+    let amount = matches.value_of("amount").unwrap();
+    let amount = Denomination::default()
+        .parse(amount)
+        .map_err(|e| WalletCliError::Args(e.to_string()))?;
+    let locktime = parse_arg(matches, "locktime")?;
+    let excess_address = matches.value_of("excess_address");
+    let excess_address = excess_address
+        .map(|a| wallet.decode_payment_address(a))
+        .transpose()?;
+    let path = matches.value_of("file").unwrap();
+
+    let tx_out = TxOut::new_token_amount(address, amount, locktime);
+    let (tx_ins, tx_outs) = wallet
+        .fetch_tx_ins_and_tx_outs_provided_excess(
+            Asset::Token(amount),
+            vec![tx_out],
+            excess_address,
+        )
+        .await?;
+    let transaction = construct_tx_core(tx_ins, tx_outs, None);
+
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &transaction)?;
+    println!("{}", construct_tx_hash(&transaction));
+    Ok(())
+}
+
+/// Open the wallet database selected by the arguments
+fn open_wallet(matches: &ArgMatches<'_>) -> Result<WalletDb, WalletCliError> {
+    let db_mode = match parse_arg(matches, "test_index")? {
+        Some(index) => DbMode::Test(index),
+        None => DbMode::Live,
+    };
+    let custom_db_spec = CustomDbSpec {
+        db_path: matches.value_of("wallet_path").unwrap().to_owned(),
+        suffix: matches.value_of("suffix").unwrap_or_default().to_owned(),
+    };
+    let passphrase = matches.value_of("passphrase").map(|p| p.to_owned());
+
+    Ok(WalletDb::new(
+        db_mode,
+        None,
+        passphrase,
+        Some(custom_db_spec),
+    )?)
+}
+
+/// Denomination of the displayed token amounts
+fn denomination(matches: &ArgMatches<'_>) -> Result<Denomination, WalletCliError> {
+    let decimal_places = parse_arg(matches, "decimal_places")?.unwrap();
+    Ok(Denomination::with_decimal_places(decimal_places))
+}
+
+/// Parse the optional value of an argument
+fn parse_arg<T: std::str::FromStr>(
+    matches: &ArgMatches<'_>,
+    name: &str,
+) -> Result<Option<T>, WalletCliError> {
+    matches
+        .value_of(name)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| WalletCliError::Args(format!("Invalid {name}: {v:?}")))
+        })
+        .transpose()
+}
+
+pub fn clap_app<'a, 'b>() -> App<'a, 'b> {
+    App::new("wallet")
+        .about("Manages a wallet database without running a node.")
+        .arg(
+            Arg::with_name("wallet_path")
+                .long("wallet_path")
+                .env("WALLET_PATH")
+                .help("Directory of the wallet databases")
+                .default_value(DEFAULT_WALLET_PATH)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("suffix")
+                .long("suffix")
+                .help("Suffix of the wallet database, as set for the node")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("decimal_places")
+                .long("decimal_places")
+                .help("Decimal places of the displayed token amounts")
+                .default_value("8")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("test_index")
+                .long("test_index")
+                .help("Open the test database of this node index instead of the live one")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("passphrase")
+                .long("passphrase")
+                .env("WALLET_PASSPHRASE")
+                .help("Passphrase of the wallet")
+                .takes_value(true),
+        )
+        .subcommand(SubCommand::with_name("new_address").about("Generate a new address"))
+        .subcommand(SubCommand::with_name("balance").about("Show the token and item amounts held"))
+        .subcommand(SubCommand::with_name("utxos").about("List the unspent outputs held"))
+        .subcommand(
+            SubCommand::with_name("export_keys")
+                .about("Export key-pairs in the import_keypairs API format")
+                .arg(
+                    Arg::with_name("address")
+                        .long("address")
+                        .help("Address to export, all addresses if not given")
+                        .multiple(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .help("File to write the key-pairs to, printed if not given")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import_keys")
+                .about("Import key-pairs in the export_keypairs API format, or a secret key")
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .help("File to read the key-pairs from")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("hex")
+                        .long("hex")
+                        .help("Hex encoded Ed25519 seed, optionally followed by the public key")
+                        .conflicts_with_all(&["file", "pem"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("pem")
+                        .long("pem")
+                        .help("File to read a PEM encoded PKCS#8 Ed25519 private key from")
+                        .conflicts_with("file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("address")
+                        .long("address")
+                        .help("Address the imported secret key must derive")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("address_version")
+                        .long("address_version")
+                        .help("Version of the address derived from the imported secret key")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export_backup")
+                .about("Write the key-pairs to an encrypted backup file")
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .help("File to write the backup to")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(backup_passphrase_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("import_backup")
+                .about("Import the key-pairs of an encrypted backup file")
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .help("File to read the backup from")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(backup_passphrase_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("export_seed")
+                .about("Print the master seed the addresses are derived from"),
+        )
+        .subcommand(
+            SubCommand::with_name("export_mnemonic").about(
+                "Print the mnemonic phrase of the master seed the addresses are derived from",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore_seed")
+                .about("Derive the addresses of a master seed again")
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .env("WALLET_SEED")
+                        .help("Hex encoded master seed")
+                        .required_unless("mnemonic")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("mnemonic")
+                        .long("mnemonic")
+                        .env("WALLET_MNEMONIC")
+                        .help("Mnemonic phrase of the master seed, instead of the seed")
+                        .conflicts_with("seed")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .help("Number of addresses to derive, 20 if not given")
+                        .conflicts_with("utxo_addresses")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("utxo_addresses")
+                        .long("utxo_addresses")
+                        .help("JSON file of the UTXO set addresses, from the utxo_addresses route, to scan for used addresses")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("gap_limit")
+                        .long("gap_limit")
+                        .help("Number of unused addresses in a row ending the scan, 20 if not given")
+                        .requires("utxo_addresses")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("overwrite")
+                        .long("overwrite")
+                        .help("Replace the seed of a wallet which has another one"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pay")
+                .about("Construct and sign a payment, writing the transaction to a file")
+                .arg(
+                    Arg::with_name("address")
+                        .long("address")
+                        .help("Address to pay")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("amount")
+                        .long("amount")
+                        .help("Amount of tokens to pay, such as 12 or 0.5")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("locktime")
+                        .long("locktime")
+                        .help("Block number before which the payment cannot be spent")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("excess_address")
+                        .long("excess_address")
+                        .help("Address receiving the change, a new address if not given")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .help("File to write the signed transaction to")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+}
+
+/// Passphrase argument of the backup commands
+fn backup_passphrase_arg() -> Arg<'static, 'static> {
+    Arg::with_name("backup_passphrase")
+        .long("backup_passphrase")
+        .env("BACKUP_PASSPHRASE")
+        .help("Passphrase protecting the backup")
+        .required(true)
+        .takes_value(true)
+}
@@ -0,0 +1,732 @@
+//! App to generate transaction load on a running network.
+//!
+//! Submits transactions at a configured rate, either as payments through the
+//! `make_payment` API of user nodes, or as signed transactions through the
+//! `create_transactions` API of a mempool node. Reports the achieved rate,
+//! latency percentiles and error counts once the run is over.
+//! The APIs must be served without TLS.
+
+use aiblock_network::configurations::WalletTxSpec;
+use aiblock_network::crash_report;
+use aiblock_network::utils::{create_valid_transaction_with_ins_outs, make_wallet_tx_info};
+use clap::{App, Arg, ArgMatches};
+use config::ConfigError;
+use futures::stream::{FuturesUnordered, StreamExt};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::{fmt, io};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey};
+use tw_chain::primitives::asset::TokenAmount;
+use tw_chain::primitives::transaction::OutPoint;
+use tw_chain::utils::transaction_utils::{construct_address_for, construct_tx_in_signable_hash};
+
+/// Percentiles of the request latency reported
+const LATENCY_PERCENTILES: &[f64] = &[50.0, 90.0, 99.0];
+
+#[tokio::main]
+async fn main() -> Result<(), LoadgenError> {
+    crash_report::tracing_log_init_with_crash_capture();
+
+    let matches = clap_app().get_matches();
+    let config = configuration(&matches)?;
+    info!("Start loadgen with config {config:?}");
+
+    let mut generator = match &config.target {
+        LoadTarget::User => PaymentGen::user(&config).await?,
+        LoadTarget::Mempool => PaymentGen::mempool(&config)?,
+    };
+    let report = run_load(&config, &mut generator).await;
+    println!("{report}");
+    Ok(())
+}
+
+/// Error running the load generation
+#[derive(Debug)]
+pub enum LoadgenError {
+    Io(io::Error),
+    Config(ConfigError),
+    Api(String),
+}
+
+impl fmt::Display for LoadgenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Config(err) => write!(f, "Config error: {err}"),
+            Self::Api(msg) => write!(f, "API error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadgenError {}
+
+impl From<io::Error> for LoadgenError {
+    fn from(other: io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+impl From<ConfigError> for LoadgenError {
+    fn from(other: ConfigError) -> Self {
+        Self::Config(other)
+    }
+}
+
+/// Node API the transactions are submitted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadTarget {
+    /// Payments made by the wallets of user nodes
+    User,
+    /// Transactions signed here and submitted to a mempool node
+    Mempool,
+}
+
+/// How the receiving address of a user payment is picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentPattern {
+    /// Each user node pays the next one
+    RoundRobin,
+    /// Each payment goes to a random user node
+    Random,
+    /// Each user node pays itself
+    ToSelf,
+}
+
+/// Configuration of a load generation run
+#[derive(Debug, Clone)]
+pub struct LoadgenConfig {
+    pub target: LoadTarget,
+    pub apis: Vec<SocketAddr>,
+    pub rate: f64,
+    pub duration: Duration,
+    pub concurrency: usize,
+    pub pattern: PaymentPattern,
+    pub amount: u64,
+    pub passphrase: String,
+    pub tx_specs: Vec<WalletTxSpec>,
+    pub respend_delay: Duration,
+    pub seed: u64,
+}
+
+/// Request to submit to a node API
+#[derive(Debug, Clone)]
+pub struct LoadRequest {
+    pub api: SocketAddr,
+    pub route: &'static str,
+    pub body: Value,
+    /// Seed output spent by the request, for mempool transactions
+    pub spent: Option<usize>,
+}
+
+/// Output of a seed key that can be spent by the next transaction
+#[derive(Debug, Clone)]
+pub struct SeedOutput {
+    key: usize,
+    out_point: OutPoint,
+    amount: TokenAmount,
+    ready_at: Instant,
+}
+
+/// Keys owning the seed outputs
+pub struct SeedKey {
+    pk: PublicKey,
+    sk: SecretKey,
+    address: String,
+    version: Option<u64>,
+}
+
+/// Generator of the requests to submit
+pub enum PaymentGen {
+    User {
+        apis: Vec<SocketAddr>,
+        addresses: Vec<String>,
+        pattern: PaymentPattern,
+        amount: TokenAmount,
+        passphrase: String,
+        next: usize,
+        rng: Box<StdRng>,
+    },
+    Mempool {
+        api: SocketAddr,
+        keys: Vec<SeedKey>,
+        outputs: VecDeque<SeedOutput>,
+        in_flight: BTreeMap<usize, SeedOutput>,
+        next_id: usize,
+        respend_delay: Duration,
+    },
+}
+
+impl PaymentGen {
+    /// Generator of payments between user nodes, using one payment address per node
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Configuration of the run.
+    pub async fn user(config: &LoadgenConfig) -> Result<Self, LoadgenError> {
+        let mut addresses = Vec::new();
+        for api in &config.apis {
+            let address = api_call(*api, "GET", "payment_address", None).await?;
+            let address = address
+                .as_str()
+                .ok_or_else(|| LoadgenError::Api(format!("{api}: invalid payment address")))?;
+            addresses.push(address.to_owned());
+        }
+
+        Ok(Self::User {
+            apis: config.apis.clone(),
+            addresses,
+            pattern: config.pattern,
+            amount: TokenAmount(config.amount),
+            passphrase: config.passphrase.clone(),
+            next: 0,
+            rng: Box::new(StdRng::seed_from_u64(config.seed)),
+        })
+    }
+
+    /// Generator of transactions spending the seed outputs, each paying its own key
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Configuration of the run.
+    pub fn mempool(config: &LoadgenConfig) -> Result<Self, LoadgenError> {
+        let api = *config.apis.first().ok_or_else(|| {
+            ConfigError::Message("Mempool target needs one API address".to_owned())
+        })?;
+        if config.tx_specs.is_empty() {
+            let msg = "Mempool target needs seed outputs in --tx_specs".to_owned();
+            return Err(ConfigError::Message(msg).into());
+        }
+
+        let now = Instant::now();
+        let mut keys = Vec::new();
+        let mut outputs = VecDeque::new();
+        for spec in &config.tx_specs {
+            let (out_point, pk, sk, amount, version) = make_wallet_tx_info(spec);
+            let address = construct_address_for(&pk, version);
+            outputs.push_back(SeedOutput {
+                key: keys.len(),
+                out_point,
+                amount,
+                ready_at: now,
+            });
+            keys.push(SeedKey {
+                pk,
+                sk,
+                address,
+                version,
+            });
+        }
+
+        Ok(Self::Mempool {
+            api,
+            keys,
+            outputs,
+            in_flight: Default::default(),
+            next_id: 0,
+            respend_delay: config.respend_delay,
+        })
+    }
+
+    /// The next request to submit, if any is available
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current time.
+    pub fn next_request(&mut self, now: Instant) -> Option<LoadRequest> {
+        match self {
+            Self::User {
+                apis,
+                addresses,
+                pattern,
+                amount,
+                passphrase,
+                next,
+                rng,
+            } => {
+                let from = *next % apis.len();
+                *next += 1;
+                let to = match pattern {
+                    PaymentPattern::RoundRobin => (from + 1) % apis.len(),
+                    PaymentPattern::Random => rng.gen_range(0, apis.len()),
+                    PaymentPattern::ToSelf => from,
+                };
+
+                Some(LoadRequest {
+                    api: apis[from],
+                    route: "make_payment",
+                    body: json!({
+                        "address": addresses[to],
+                        "amount": amount.0,
+                        "passphrase": passphrase,
+                        "locktime": null,
+                    }),
+                    spent: None,
+                })
+            }
+            Self::Mempool {
+                api,
+                keys,
+                outputs,
+                in_flight,
+                next_id,
+                ..
+            } => {
+                if outputs.front()?.ready_at > now {
+                    return None;
+                }
+                let output = outputs.pop_front()?;
+                let body = create_transaction_body(&keys[output.key], &output);
+
+                let id = *next_id;
+                *next_id += 1;
+                in_flight.insert(id, output);
+                Some(LoadRequest {
+                    api: *api,
+                    route: "create_transactions",
+                    body,
+                    spent: Some(id),
+                })
+            }
+        }
+    }
+
+    /// Update the seed outputs once a request completed
+    ///
+    /// ### Arguments
+    ///
+    /// * `spent`  - Seed output spent by the request.
+    /// * `result` - Content of the reply.
+    /// * `now`    - Current time.
+    pub fn complete(
+        &mut self,
+        spent: Option<usize>,
+        result: &Result<Value, LoadgenError>,
+        now: Instant,
+    ) {
+        if let Self::Mempool {
+            outputs,
+            in_flight,
+            respend_delay,
+            ..
+        } = self
+        {
+            let output = match spent.and_then(|id| in_flight.remove(&id)) {
+                Some(output) => output,
+                None => return,
+            };
+
+            // The new output can be spent once the transaction is in a block,
+            // a failed spend is retried after the same delay
+            let new_hash = result
+                .as_ref()
+                .ok()
+                .and_then(|content| content.as_object()?.keys().next().cloned());
+            let out_point = match new_hash {
+                Some(t_hash) => OutPoint::new(t_hash, 0),
+                None => output.out_point,
+            };
+            outputs.push_back(SeedOutput {
+                out_point,
+                ready_at: now + *respend_delay,
+                ..output
+            });
+        }
+    }
+}
+
+/// JSON body of `create_transactions` spending the output to the address of its key
+///
+/// ### Arguments
+///
+/// * `key`    - Key owning the output.
+/// * `output` - Output to spend.
+fn create_transaction_body(key: &SeedKey, output: &SeedOutput) -> Value {
+    let out_point = &output.out_point;
+    let (_, tx) = create_valid_transaction_with_ins_outs(
+        &[(out_point.n, out_point.t_hash.as_str())],
+        &[key.address.as_str()],
+        &key.pk,
+        &key.sk,
+        output.amount,
+        key.version,
+    );
+
+    let signable_data = construct_tx_in_signable_hash(out_point);
+    let signature = sign::sign_detached(signable_data.as_bytes(), &key.sk);
+    json!([{
+        "inputs": [{
+            "previous_out": out_point,
+            "script_signature": {
+                "Pay2PkH": {
+                    "signable_data": signable_data,
+                    "signature": hex::encode(signature.as_ref()),
+                    "public_key": hex::encode(key.pk.as_ref()),
+                    "address_version": key.version,
+                }
+            },
+        }],
+        "outputs": tx.outputs,
+        "version": tx.version,
+        "fees": null,
+        "druid_info": null,
+    }])
+}
+
+/// Outcome of a load generation run
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    pub elapsed: Duration,
+    pub submitted: usize,
+    pub succeeded: usize,
+    /// Ticks skipped because too many requests were in flight
+    pub saturated: usize,
+    /// Ticks skipped because no seed output was ready to spend
+    pub starved: usize,
+    pub errors: BTreeMap<String, usize>,
+    pub latencies: Vec<Duration>,
+}
+
+impl LoadReport {
+    /// Successful transactions per second
+    pub fn tps(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.succeeded as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Latency below which the given percentage of requests completed
+    ///
+    /// ### Arguments
+    ///
+    /// * `percentile` - Percentage of requests, between 0 and 100.
+    pub fn latency_percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut latencies = self.latencies.clone();
+        latencies.sort_unstable();
+        let rank = (percentile / 100.0 * latencies.len() as f64).ceil() as usize;
+        latencies.get(rank.max(1) - 1).copied()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Elapsed:    {:.1}s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "Submitted:  {}", self.submitted)?;
+        writeln!(f, "Succeeded:  {}", self.succeeded)?;
+        writeln!(f, "TPS:        {:.2}", self.tps())?;
+        writeln!(f, "Saturated:  {}", self.saturated)?;
+        writeln!(f, "Starved:    {}", self.starved)?;
+        for percentile in LATENCY_PERCENTILES {
+            if let Some(latency) = self.latency_percentile(*percentile) {
+                writeln!(f, "Latency p{percentile}: {latency:?}")?;
+            }
+        }
+        let errors: usize = self.errors.values().sum();
+        write!(f, "Errors:     {errors}")?;
+        for (error, count) in &self.errors {
+            write!(f, "\n  {count} x {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Submit requests at the configured rate until the duration elapsed
+///
+/// ### Arguments
+///
+/// * `config`    - Configuration of the run.
+/// * `generator` - Generator of the requests.
+async fn run_load(config: &LoadgenConfig, generator: &mut PaymentGen) -> LoadReport {
+    let start = Instant::now();
+    let end = start + config.duration;
+    let mut ticks = time::interval(Duration::from_secs_f64(1.0 / config.rate));
+    let mut in_flight = FuturesUnordered::new();
+    let mut report = LoadReport::default();
+
+    loop {
+        tokio::select! {
+            tick = ticks.tick(), if tick_open(end) => {
+                if in_flight.len() >= config.concurrency {
+                    report.saturated += 1;
+                    continue;
+                }
+                let request = match generator.next_request(tick) {
+                    Some(request) => request,
+                    None => {
+                        report.starved += 1;
+                        continue;
+                    }
+                };
+
+                report.submitted += 1;
+                in_flight.push(async move {
+                    let sent_at = Instant::now();
+                    let result = api_call(request.api, "POST", request.route, Some(request.body)).await;
+                    (request.spent, result, sent_at.elapsed())
+                });
+            }
+            Some((spent, result, latency)) = in_flight.next() => {
+                generator.complete(spent, &result, Instant::now());
+                report.latencies.push(latency);
+                match result {
+                    Ok(_) => report.succeeded += 1,
+                    Err(e) => {
+                        warn!("Request failed: {e}");
+                        *report.errors.entry(e.to_string()).or_default() += 1;
+                    }
+                }
+            }
+            else => break,
+        }
+    }
+
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Whether new requests should still be submitted
+fn tick_open(end: Instant) -> bool {
+    Instant::now() < end
+}
+
+/// Call a node API without TLS and return the reply content
+///
+/// ### Arguments
+///
+/// * `api`    - Address of the node API.
+/// * `method` - HTTP method.
+/// * `route`  - Route to call.
+/// * `body`   - JSON body of the request.
+async fn api_call(
+    api: SocketAddr,
+    method: &str,
+    route: &str,
+    body: Option<Value>,
+) -> Result<Value, LoadgenError> {
+    let api_err = |e: &dyn fmt::Display| LoadgenError::Api(format!("{route}: {e}"));
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let call_id = format!("{:032x}", rand::thread_rng().gen::<u128>());
+
+    let request = format!(
+        "{method} /{route} HTTP/1.1\r\n\
+         Host: {api}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         x-cache-id: {call_id}\r\n\
+         Connection: close\r\n\r\n\
+         {body}",
+        body.len()
+    );
+
+    let mut stream = TcpStream::connect(api).await?;
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, content) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| api_err(&"Invalid HTTP response"))?;
+    if !head.starts_with("HTTP/1.1 200") {
+        return Err(api_err(&head.lines().next().unwrap_or_default()));
+    }
+
+    let reply: Value = serde_json::from_str(content).map_err(|e| api_err(&e))?;
+    if reply["status"] != "Success" {
+        return Err(api_err(&reply["reason"]));
+    }
+    Ok(reply["content"].clone())
+}
+
+fn clap_app<'a, 'b>() -> App<'a, 'b> {
+    App::new("loadgen")
+        .about("Generates transaction load on a running AIBlock network.")
+        .arg(
+            Arg::with_name("target")
+                .long("target")
+                .help("API receiving the transactions: user (make_payment) or mempool (create_transactions).")
+                .possible_values(&["user", "mempool"])
+                .default_value("user"),
+        )
+        .arg(
+            Arg::with_name("api")
+                .long("api")
+                .help("Comma separated addresses of the node APIs to submit to.")
+                .default_value("127.0.0.1:3000"),
+        )
+        .arg(
+            Arg::with_name("rate")
+                .long("rate")
+                .help("Transactions submitted per second.")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .help("Seconds during which transactions are submitted.")
+                .default_value("60"),
+        )
+        .arg(
+            Arg::with_name("concurrency")
+                .long("concurrency")
+                .help("Maximum number of requests in flight.")
+                .default_value("32"),
+        )
+        .arg(
+            Arg::with_name("pattern")
+                .long("pattern")
+                .help("Receiver of user payments: the next user node, a random one, or the payer.")
+                .possible_values(&["round_robin", "random", "self"])
+                .default_value("round_robin"),
+        )
+        .arg(
+            Arg::with_name("amount")
+                .long("amount")
+                .help("Tokens sent by each user payment.")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("passphrase")
+                .long("passphrase")
+                .env("LOADGEN_PASSPHRASE")
+                .help("Passphrase of the user nodes wallets.")
+                .default_value(""),
+        )
+        .arg(
+            Arg::with_name("tx_specs")
+                .long("tx_specs")
+                .help("JSON file of seed outputs and their keys, spent by mempool transactions.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("respend_delay")
+                .long("respend_delay")
+                .help("Seconds before the output of a mempool transaction is spent again.")
+                .default_value("15"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .help("Seed of the random payment pattern.")
+                .default_value("0"),
+        )
+}
+
+fn configuration(matches: &ArgMatches) -> Result<LoadgenConfig, LoadgenError> {
+    let target = match matches.value_of("target") {
+        Some("mempool") => LoadTarget::Mempool,
+        _ => LoadTarget::User,
+    };
+    let pattern = match matches.value_of("pattern") {
+        Some("random") => PaymentPattern::Random,
+        Some("self") => PaymentPattern::ToSelf,
+        _ => PaymentPattern::RoundRobin,
+    };
+
+    let apis = matches
+        .value_of("api")
+        .unwrap_or_default()
+        .split(',')
+        .map(|api| parse_arg("api", api.trim()))
+        .collect::<Result<Vec<SocketAddr>, _>>()?;
+    if apis.is_empty() {
+        return Err(ConfigError::Message("No API address".to_owned()).into());
+    }
+
+    let rate: f64 = parse_arg("rate", matches.value_of("rate").unwrap())?;
+    if rate.is_nan() || rate <= 0.0 {
+        return Err(ConfigError::Message(format!("Invalid rate: {rate}")).into());
+    }
+
+    let tx_specs = match matches.value_of("tx_specs") {
+        Some(path) => {
+            let file = BufReader::new(File::open(path)?);
+            serde_json::from_reader(file)
+                .map_err(|e| ConfigError::Message(format!("Invalid tx_specs {path}: {e}")))?
+        }
+        None => Vec::new(),
+    };
+
+    Ok(LoadgenConfig {
+        target,
+        apis,
+        rate,
+        duration: Duration::from_secs(parse_arg(
+            "duration",
+            matches.value_of("duration").unwrap(),
+        )?),
+        concurrency: parse_arg("concurrency", matches.value_of("concurrency").unwrap())?,
+        pattern,
+        amount: parse_arg("amount", matches.value_of("amount").unwrap())?,
+        passphrase: matches
+            .value_of("passphrase")
+            .unwrap_or_default()
+            .to_owned(),
+        tx_specs,
+        respend_delay: Duration::from_secs(parse_arg(
+            "respend_delay",
+            matches.value_of("respend_delay").unwrap(),
+        )?),
+        seed: parse_arg("seed", matches.value_of("seed").unwrap())?,
+    })
+}
+
+/// Parse an argument
+///
+/// ### Arguments
+///
+/// * `name`  - Name of the argument.
+/// * `value` - Value to parse.
+fn parse_arg<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::Message(format!("Invalid {name}: {value}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Checks percentiles pick the latency reached by the given share of requests
+    fn latency_percentiles() {
+        //
+        // Arrange
+        //
+        let report = LoadReport {
+            latencies: (1..=100).rev().map(Duration::from_millis).collect(),
+            ..Default::default()
+        };
+
+        //
+        // Act
+        //
+        let percentiles: Vec<_> = [50.0, 90.0, 99.0, 100.0]
+            .iter()
+            .map(|p| report.latency_percentile(*p))
+            .collect();
+        let empty = LoadReport::default().latency_percentile(50.0);
+
+        //
+        // Assert
+        //
+        let expected: Vec<_> = [50, 90, 99, 100]
+            .iter()
+            .map(|ms| Some(Duration::from_millis(*ms)))
+            .collect();
+        assert_eq!(percentiles, expected);
+        assert_eq!(empty, None);
+    }
+}
@@ -1,43 +1,30 @@
 //! App to run an AIBlock node.
+//!
+//! Kept as a wrapper of the node commands of the `aiblock` app.
 
-use clap::{App, ArgMatches};
-use tracing::info;
+use clap::App;
 
+#[path = "../aiblock/cli.rs"]
+mod cli;
+#[path = "../aiblock/mempool.rs"]
 mod mempool;
+#[path = "../aiblock/miner.rs"]
 mod miner;
+#[path = "../aiblock/pre_launch.rs"]
 mod pre_launch;
+#[path = "../aiblock/storage.rs"]
 mod storage;
+#[path = "../aiblock/user.rs"]
 mod user;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
     let matches = clap_app().get_matches();
-    launch_node_with_args(matches).await;
-}
-
-async fn launch_node_with_args(matches: ArgMatches<'_>) {
-    if let Some(sub_command) = matches.subcommand_name() {
-        let sub_matches = matches.subcommand_matches(sub_command).unwrap();
-        match sub_command {
-            "user" => user::run_node(sub_matches).await,
-            "miner" => miner::run_node(sub_matches).await,
-            "mempool" => mempool::run_node(sub_matches).await,
-            "storage" => storage::run_node(sub_matches).await,
-            "pre_launch" => pre_launch::run_node(sub_matches).await,
-            invalid_type => panic!("Invalid node type: {:?}", invalid_type),
-        }
-    } else {
-        info!("Node type needs to be specified.")
-    }
+    cli::run_node_command(&matches).await
 }
 
 fn clap_app<'a, 'b>() -> App<'a, 'b> {
     App::new("AIBlock Node")
         .about("Runs an AIBlock node.")
-        .subcommand(user::clap_app())
-        .subcommand(miner::clap_app())
-        .subcommand(mempool::clap_app())
-        .subcommand(storage::clap_app())
-        .subcommand(pre_launch::clap_app())
+        .subcommands(cli::node_subcommands())
 }
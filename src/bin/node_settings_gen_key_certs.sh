@@ -91,8 +91,8 @@ echo "    ]," >> tls_certificates.json
 echo "    \"tls_config\": {" >> tls_certificates.json
 echo "        \"pem_certificates\": {" >> tls_certificates.json
 
-echo "/// !!! AUTOGENERATED: DO NOT EDIT !!!" >> test_tls_certificates.rs
-echo "/// Generated with: \`src/bin/node_settings_gen_key_certs.sh\`" >> test_tls_certificates.rs
+echo "// !!! AUTOGENERATED: DO NOT EDIT !!!" >> test_tls_certificates.rs
+echo "// Generated with: \`src/bin/node_settings_gen_key_certs.sh\`" >> test_tls_certificates.rs
 echo "" >> test_tls_certificates.rs
 echo "/// PEM certificates for node DNS names" >> test_tls_certificates.rs
 echo "pub const TEST_PEM_CERTIFICATES: &[(&str, &str)] = &[" >> test_tls_certificates.rs
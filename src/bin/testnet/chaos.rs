@@ -0,0 +1,455 @@
+//! Chaos mode for the test network.
+//!
+//! Nodes are disrupted one at a time on a seeded random schedule:
+//! - restarted: the process is killed, severing all its connections, then started again
+//!   on the same databases,
+//! - isolated: the process is frozen long enough for its peers to drop their connections,
+//! - raft delayed: a raft member is frozen briefly, delaying its raft messages.
+//!
+//! Once the schedule completes, the storage nodes must agree on the latest block and
+//! the tokens in the UTXO set must only have changed by the tokens issued meanwhile.
+
+use super::{Testnet, TestnetError, TestnetNode};
+use clap::{App, Arg, ArgMatches};
+use config::ConfigError;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+use std::time::Duration;
+use std::{io, process};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+/// Time a restarted node stays down
+const RESTART_DOWN_TIME: (Duration, Duration) = (Duration::from_secs(1), Duration::from_secs(10));
+
+/// Time an isolated node stays frozen, long enough for peers to drop it
+const ISOLATE_TIME: (Duration, Duration) = (Duration::from_secs(10), Duration::from_secs(30));
+
+/// Time a raft member stays frozen, short enough to not lose its leadership
+const RAFT_DELAY_TIME: (Duration, Duration) = (Duration::from_millis(200), Duration::from_secs(2));
+
+/// Interval between consistency checks while settling
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Configuration of the chaos mode
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    /// Seed of the disruption schedule
+    pub seed: u64,
+    /// Time during which nodes are disrupted
+    pub duration: Duration,
+    /// Time between two disruptions
+    pub interval: Duration,
+    /// Time allowed for the network to recover before checks fail
+    pub settle_time: Duration,
+}
+
+/// Disruption applied to a node, identified by its position in the testnet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChaosAction {
+    Restart(usize, Duration),
+    Isolate(usize, Duration),
+    DelayRaft(usize, Duration),
+}
+
+/// Seeded random schedule of disruptions
+pub struct ChaosSchedule {
+    rng: StdRng,
+    node_types: Vec<&'static str>,
+}
+
+impl ChaosSchedule {
+    /// Create a schedule for the given nodes
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed`       - Seed of the schedule.
+    /// * `node_types` - Type of each node of the testnet.
+    pub fn new(seed: u64, node_types: Vec<&'static str>) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            node_types,
+        }
+    }
+
+    /// Next disruption to apply
+    pub fn next_action(&mut self) -> ChaosAction {
+        let node = self.rng.gen_range(0, self.node_types.len());
+        let is_raft_member = matches!(self.node_types[node], "mempool" | "storage");
+        let kinds = if is_raft_member { 3 } else { 2 };
+
+        match self.rng.gen_range(0, kinds) {
+            0 => ChaosAction::Restart(node, self.duration_in(RESTART_DOWN_TIME)),
+            1 => ChaosAction::Isolate(node, self.duration_in(ISOLATE_TIME)),
+            _ => ChaosAction::DelayRaft(node, self.duration_in(RAFT_DELAY_TIME)),
+        }
+    }
+
+    fn duration_in(&mut self, (min, max): (Duration, Duration)) -> Duration {
+        let millis = self
+            .rng
+            .gen_range(min.as_millis() as u64, max.as_millis() as u64 + 1);
+        Duration::from_millis(millis)
+    }
+}
+
+/// Funds held in the UTXO set of a mempool node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FundsSnapshot {
+    utxo_tokens: u64,
+    issued_tokens: u64,
+}
+
+/// Apply the chaos schedule, then check the network recovered consistently
+///
+/// ### Arguments
+///
+/// * `testnet` - Running test network.
+/// * `config`  - Configuration of the chaos mode.
+pub async fn run_chaos(testnet: &mut Testnet, config: ChaosConfig) -> Result<(), TestnetError> {
+    let node_types = testnet.nodes.iter().map(|n| n.node_type).collect();
+    let mut schedule = ChaosSchedule::new(config.seed, node_types);
+    let funds_before = settled_funds(testnet, config.settle_time).await?;
+    info!("Chaos start with seed {}, {:?}", config.seed, funds_before);
+
+    let end = Instant::now() + config.duration;
+    while Instant::now() < end {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                info!("Ctrl-C received, stopping chaos");
+                return Ok(());
+            }
+            _ = time::sleep(config.interval) => (),
+        }
+
+        if let Some(name) = testnet.exited_node() {
+            return Err(TestnetError::NodeExited(name));
+        }
+
+        let action = schedule.next_action();
+        info!("Chaos action {:?}", action);
+        apply_action(testnet, action).await?;
+    }
+
+    info!("Chaos complete, wait for the network to settle");
+    check_latest_blocks(testnet, config.settle_time).await?;
+    let funds_after = settled_funds(testnet, config.settle_time).await?;
+    info!("Chaos end {:?}", funds_after);
+
+    let utxo_change = funds_after.utxo_tokens as i128 - funds_before.utxo_tokens as i128;
+    let issued_change = funds_after.issued_tokens as i128 - funds_before.issued_tokens as i128;
+    if utxo_change != issued_change {
+        return Err(TestnetError::ChaosCheck(format!(
+            "UTXO tokens changed by {utxo_change}, but {issued_change} tokens were issued"
+        )));
+    }
+
+    info!("Chaos checks passed: chain consistent and no funds lost");
+    Ok(())
+}
+
+/// Apply a disruption
+///
+/// ### Arguments
+///
+/// * `testnet` - Running test network.
+/// * `action`  - Disruption to apply.
+async fn apply_action(testnet: &mut Testnet, action: ChaosAction) -> Result<(), TestnetError> {
+    match action {
+        ChaosAction::Restart(idx, down_for) => testnet.restart_node(idx, down_for).await?,
+        ChaosAction::Isolate(idx, frozen_for) | ChaosAction::DelayRaft(idx, frozen_for) => {
+            let node = &testnet.nodes[idx];
+            signal_node(node, "STOP")?;
+            time::sleep(frozen_for).await;
+            signal_node(node, "CONT")?;
+        }
+    }
+    Ok(())
+}
+
+/// Send a signal to the node process, used to freeze and resume it
+///
+/// ### Arguments
+///
+/// * `node`   - Node to signal.
+/// * `signal` - Name of the signal.
+fn signal_node(node: &TestnetNode, signal: &str) -> io::Result<()> {
+    let pid = node.child.id().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("{} exited", node.name()))
+    })?;
+
+    let status = process::Command::new("kill")
+        .arg(format!("-{signal}"))
+        .arg(pid.to_string())
+        .status()?;
+    if !status.success() {
+        warn!("Failed to send {} to {}: {}", signal, node.name(), status);
+    }
+    Ok(())
+}
+
+/// Wait for all storage nodes to report the same latest block
+///
+/// ### Arguments
+///
+/// * `testnet`     - Running test network.
+/// * `settle_time` - Time allowed for the storage nodes to agree.
+async fn check_latest_blocks(testnet: &Testnet, settle_time: Duration) -> Result<(), TestnetError> {
+    let ports: Vec<_> = nodes_of_type(testnet, "storage")
+        .map(|n| n.api_port)
+        .collect();
+    let deadline = Instant::now() + settle_time;
+
+    loop {
+        let mut blocks = Vec::new();
+        for port in &ports {
+            blocks.push(api_call(*port, "GET", "latest_block", None).await);
+        }
+
+        let first = blocks.first().and_then(|b| b.as_ref().ok());
+        if first.is_some() && blocks.iter().all(|b| b.as_ref().ok() == first) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            let msg = format!("Storage nodes disagree on latest block: {blocks:?}");
+            return Err(TestnetError::ChaosCheck(msg));
+        }
+        time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+/// Funds of the mempool nodes, once they all agree and no block is committed meanwhile
+///
+/// ### Arguments
+///
+/// * `testnet`     - Running test network.
+/// * `settle_time` - Time allowed for the mempool nodes to agree.
+async fn settled_funds(
+    testnet: &Testnet,
+    settle_time: Duration,
+) -> Result<FundsSnapshot, TestnetError> {
+    let ports: Vec<_> = nodes_of_type(testnet, "mempool")
+        .map(|n| n.api_port)
+        .collect();
+    let deadline = Instant::now() + settle_time;
+
+    loop {
+        let mut snapshots = Vec::new();
+        for port in &ports {
+            snapshots.push(funds_snapshot(*port).await);
+        }
+
+        match snapshots.first() {
+            Some(Ok(first)) if snapshots.iter().all(|s| s.as_ref().ok() == Some(first)) => {
+                return Ok(*first);
+            }
+            None => return Err(TestnetError::Api("No mempool node running".to_owned())),
+            _ if Instant::now() >= deadline => {
+                let msg = format!("Mempool nodes disagree on funds: {snapshots:?}");
+                return Err(TestnetError::ChaosCheck(msg));
+            }
+            _ => time::sleep(CHECK_INTERVAL).await,
+        }
+    }
+}
+
+/// Funds of a mempool node, or an error if a block was committed while reading them
+///
+/// ### Arguments
+///
+/// * `api_port` - Port the mempool node API listens on.
+async fn funds_snapshot(api_port: u16) -> Result<FundsSnapshot, TestnetError> {
+    let issued_tokens = issued_tokens(api_port).await?;
+    let addresses = api_call(api_port, "GET", "utxo_addresses", None).await?;
+    let balance = api_call(api_port, "POST", "fetch_balance", Some(addresses)).await?;
+    let utxo_tokens = balance["total"]["tokens"]
+        .as_u64()
+        .ok_or_else(|| TestnetError::Api(format!("Invalid balance: {balance}")))?;
+
+    if issued_tokens != self::issued_tokens(api_port).await? {
+        return Err(TestnetError::Api(
+            "Block committed during snapshot".to_owned(),
+        ));
+    }
+    Ok(FundsSnapshot {
+        utxo_tokens,
+        issued_tokens,
+    })
+}
+
+/// Tokens issued by mining so far
+///
+/// ### Arguments
+///
+/// * `api_port` - Port the mempool node API listens on.
+async fn issued_tokens(api_port: u16) -> Result<u64, TestnetError> {
+    let issued = api_call(api_port, "GET", "issued_supply", None).await?;
+    issued
+        .as_u64()
+        .ok_or_else(|| TestnetError::Api(format!("Invalid issued supply: {issued}")))
+}
+
+fn nodes_of_type<'a>(
+    testnet: &'a Testnet,
+    node_type: &'a str,
+) -> impl Iterator<Item = &'a TestnetNode> + 'a {
+    testnet
+        .nodes
+        .iter()
+        .filter(move |n| n.node_type == node_type)
+}
+
+/// Call a node API route over plain HTTP, returning the response content
+///
+/// ### Arguments
+///
+/// * `api_port` - Port the node API listens on.
+/// * `method`   - HTTP method of the route.
+/// * `route`    - Name of the route.
+/// * `body`     - JSON body of the request.
+async fn api_call(
+    api_port: u16,
+    method: &str,
+    route: &str,
+    body: Option<Value>,
+) -> Result<Value, TestnetError> {
+    let api_err = |e: &dyn std::fmt::Display| TestnetError::Api(format!("{route}: {e}"));
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let call_id = format!("{:032x}", rand::thread_rng().gen::<u128>());
+
+    let request = format!(
+        "{method} /{route} HTTP/1.1\r\n\
+         Host: 127.0.0.1:{api_port}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         x-cache-id: {call_id}\r\n\
+         Connection: close\r\n\r\n\
+         {body}",
+        body.len()
+    );
+
+    let mut stream = TcpStream::connect(("127.0.0.1", api_port)).await?;
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, content) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| api_err(&"Invalid HTTP response"))?;
+    if !head.starts_with("HTTP/1.1 200") {
+        return Err(api_err(&head.lines().next().unwrap_or_default()));
+    }
+
+    let reply: Value = serde_json::from_str(content).map_err(|e| api_err(&e))?;
+    Ok(reply["content"].clone())
+}
+
+/// Command line arguments of the chaos mode
+///
+/// ### Arguments
+///
+/// * `app` - Application to add the arguments to.
+pub fn clap_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("chaos")
+            .long("chaos")
+            .help("Disrupt nodes on a schedule, then check the chain and funds are consistent."),
+    )
+    .arg(
+        Arg::with_name("chaos_seed")
+            .long("chaos_seed")
+            .help("Seed of the chaos schedule, random if unset.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("chaos_duration")
+            .long("chaos_duration")
+            .help("Seconds during which nodes are disrupted.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("chaos_interval")
+            .long("chaos_interval")
+            .help("Seconds between two disruptions.")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("chaos_settle_time")
+            .long("chaos_settle_time")
+            .help("Seconds allowed for the network to recover before checks fail.")
+            .takes_value(true),
+    )
+}
+
+/// Chaos configuration if chaos mode is enabled
+///
+/// ### Arguments
+///
+/// * `matches` - Command line arguments.
+pub fn configuration(matches: &ArgMatches) -> Result<Option<ChaosConfig>, ConfigError> {
+    if !matches.is_present("chaos") {
+        return Ok(None);
+    }
+
+    let secs = |name: &str, default: &str| {
+        let value = matches.value_of(name).unwrap_or(default);
+        super::parse_arg(name, value).map(Duration::from_secs)
+    };
+    let seed = match matches.value_of("chaos_seed") {
+        Some(seed) => super::parse_arg("chaos_seed", seed)?,
+        None => rand::thread_rng().gen(),
+    };
+
+    Ok(Some(ChaosConfig {
+        seed,
+        duration: secs("chaos_duration", "300")?,
+        interval: secs("chaos_interval", "20")?,
+        settle_time: secs("chaos_settle_time", "120")?,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Checks the schedule is reproducible from its seed and only delays raft members
+    fn schedule_deterministic_for_seed() {
+        //
+        // Arrange
+        //
+        let node_types = vec!["storage", "mempool", "miner", "miner", "user"];
+        let mut schedules: Vec<_> = (0..2)
+            .map(|_| ChaosSchedule::new(42, node_types.clone()))
+            .collect();
+
+        //
+        // Act
+        //
+        let actions: Vec<Vec<_>> = schedules
+            .iter_mut()
+            .map(|s| (0..64).map(|_| s.next_action()).collect())
+            .collect();
+
+        //
+        // Assert
+        //
+        assert_eq!(actions[0], actions[1]);
+        for action in &actions[0] {
+            match *action {
+                ChaosAction::Restart(_, down_for) => {
+                    assert!(down_for >= RESTART_DOWN_TIME.0 && down_for <= RESTART_DOWN_TIME.1)
+                }
+                ChaosAction::Isolate(_, frozen_for) => {
+                    assert!(frozen_for >= ISOLATE_TIME.0 && frozen_for <= ISOLATE_TIME.1)
+                }
+                ChaosAction::DelayRaft(idx, _) => assert!(idx < 2),
+            }
+        }
+    }
+}
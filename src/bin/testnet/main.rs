@@ -0,0 +1,621 @@
+//! App to run a local test network.
+//!
+//! Spawns the mempool, storage, miner and user nodes listed in a single node
+//! config as child processes of the `node` binary, waits for every node to accept
+//! peer connections, then tears everything down on Ctrl-C or when a node exits.
+//! In chaos mode, nodes are disrupted on a schedule and the chain consistency and
+//! funds are checked at the end.
+
+use aiblock_network::configurations::NodeSpec;
+use aiblock_network::crash_report::{self, CrashContext};
+use chaos::ChaosConfig;
+use clap::{App, Arg, ArgMatches};
+use config::ConfigError;
+use std::fs::{self, File};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use std::{fmt, io};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::time::{self, Instant};
+use tracing::{error, info, warn};
+
+mod chaos;
+
+/// Node types in start order: nodes are stopped in reverse order
+const NODE_TYPES: &[&str] = &["storage", "mempool", "miner", "user"];
+
+/// Folders holding the databases of nodes running in test mode
+const TEST_DB_FOLDERS: &[&str] = &["src/db/db", "src/wallet/wallet"];
+
+/// Prefix of the test databases removed before launching
+const TEST_DB_PREFIX: &str = "test.";
+
+/// Interval between checks of the running nodes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[tokio::main]
+async fn main() -> Result<(), TestnetError> {
+    crash_report::tracing_log_init_with_crash_capture();
+    crash_report::install_panic_hook();
+
+    let matches = clap_app().get_matches();
+    let config = configuration(&matches)?;
+    crash_report::set_crash_context(CrashContext::new("testnet", "testnet", &config));
+    crash_report::report_previous_crash("testnet");
+
+    info!("Start testnet with config {config:?}");
+    if !config.keep_dbs {
+        remove_test_dbs()?;
+    }
+    fs::create_dir_all(&config.log_dir)?;
+
+    let mut testnet = Testnet::new(config);
+    let result = run_testnet(&mut testnet).await;
+    testnet.shutdown().await;
+
+    if let Err(e) = &result {
+        error!("Testnet stopped with error: {e}");
+    }
+    result
+}
+
+/// Launch all nodes, and wait until stopped
+///
+/// ### Arguments
+///
+/// * `testnet` - Test network to run.
+async fn run_testnet(testnet: &mut Testnet) -> Result<(), TestnetError> {
+    testnet.spawn_all()?;
+    testnet.wait_for_connectivity().await?;
+    if let Some(chaos) = testnet.config.chaos.clone() {
+        return chaos::run_chaos(testnet, chaos).await;
+    }
+
+    info!(
+        "Testnet running with {} nodes, logs in {:?}. Press Ctrl-C to stop.",
+        testnet.nodes.len(),
+        testnet.config.log_dir
+    );
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            result?;
+            info!("Ctrl-C received, stopping testnet");
+            Ok(())
+        }
+        name = testnet.wait_for_node_exit() => Err(TestnetError::NodeExited(name)),
+    }
+}
+
+/// Error running the test network
+#[derive(Debug)]
+pub enum TestnetError {
+    Io(io::Error),
+    Config(ConfigError),
+    NodeExited(String),
+    ConnectTimeout(Vec<String>),
+    Api(String),
+    ChaosCheck(String),
+}
+
+impl fmt::Display for TestnetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Config(err) => write!(f, "Config error: {err}"),
+            Self::NodeExited(name) => write!(f, "Node exited: {name}"),
+            Self::ConnectTimeout(names) => write!(f, "Nodes not reachable: {names:?}"),
+            Self::Api(msg) => write!(f, "API error: {msg}"),
+            Self::ChaosCheck(msg) => write!(f, "Chaos check failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for TestnetError {}
+
+impl From<io::Error> for TestnetError {
+    fn from(other: io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+impl From<ConfigError> for TestnetError {
+    fn from(other: ConfigError) -> Self {
+        Self::Config(other)
+    }
+}
+
+/// Configuration of the test network
+#[derive(Debug, Clone)]
+pub struct TestnetConfig {
+    /// Path to the `node` binary
+    pub node_bin: PathBuf,
+    /// Node config shared by all nodes
+    pub node_config: String,
+    /// Initial block config seeding the UTXO set and wallets
+    pub seed_config: Option<String>,
+    /// Addresses of the nodes to run for each node type
+    pub nodes: Vec<(&'static str, Vec<SocketAddr>)>,
+    /// API port of the first node, others use the following ports
+    pub api_port_start: u16,
+    /// Whether nodes APIs use TLS, or the node config value if unset
+    pub api_use_tls: Option<String>,
+    /// Folder containing one log file per node
+    pub log_dir: PathBuf,
+    /// Keep the databases of previous runs
+    pub keep_dbs: bool,
+    /// Time allowed for all nodes to accept connections
+    pub connect_timeout: Duration,
+    /// Disruptions to apply once all nodes are connected
+    pub chaos: Option<ChaosConfig>,
+}
+
+/// Node running as a child process
+pub struct TestnetNode {
+    /// Type of node
+    pub node_type: &'static str,
+    /// Index of the node in the node config
+    pub index: usize,
+    /// Address the node listens on for peers
+    pub address: SocketAddr,
+    /// Port the node API listens on
+    pub api_port: u16,
+    /// Arguments the node was started with
+    pub args: Vec<String>,
+    /// Running process
+    pub child: Child,
+}
+
+impl TestnetNode {
+    /// Name of the node, used for its log file
+    pub fn name(&self) -> String {
+        node_name(self.node_type, self.index)
+    }
+}
+
+/// Test network of child processes
+pub struct Testnet {
+    pub config: TestnetConfig,
+    pub nodes: Vec<TestnetNode>,
+}
+
+impl Testnet {
+    /// Create a test network without running nodes
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Configuration of the test network.
+    pub fn new(config: TestnetConfig) -> Self {
+        Self {
+            config,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Spawn all configured nodes in start order
+    pub fn spawn_all(&mut self) -> Result<(), TestnetError> {
+        let mut api_port = self.config.api_port_start;
+        for (node_type, addresses) in self.config.nodes.clone() {
+            for (index, address) in addresses.into_iter().enumerate() {
+                let args = node_args(&self.config, node_type, index, api_port);
+                let child = spawn_node(&self.config, node_type, index, &args)?;
+                info!(
+                    "Started {} on {} (api port {})",
+                    node_name(node_type, index),
+                    address,
+                    api_port
+                );
+
+                self.nodes.push(TestnetNode {
+                    node_type,
+                    index,
+                    address,
+                    api_port,
+                    args,
+                    child,
+                });
+                api_port += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Kill a node and start it again with the same arguments and databases
+    ///
+    /// ### Arguments
+    ///
+    /// * `idx`      - Position of the node in `nodes`.
+    /// * `down_for` - Time to wait before restarting the node.
+    pub async fn restart_node(&mut self, idx: usize, down_for: Duration) -> io::Result<()> {
+        let node = &mut self.nodes[idx];
+        node.child.kill().await?;
+        time::sleep(down_for).await;
+
+        node.child = spawn_node(&self.config, node.node_type, node.index, &node.args)?;
+        info!("Restarted {}", node.name());
+        Ok(())
+    }
+
+    /// Wait for all nodes to accept peer connections
+    pub async fn wait_for_connectivity(&mut self) -> Result<(), TestnetError> {
+        let deadline = Instant::now() + self.config.connect_timeout;
+        let mut pending: Vec<usize> = (0..self.nodes.len()).collect();
+
+        while !pending.is_empty() {
+            if let Some(name) = self.exited_node() {
+                return Err(TestnetError::NodeExited(name));
+            }
+
+            let mut still_pending = Vec::new();
+            for idx in pending {
+                if TcpStream::connect(self.nodes[idx].address).await.is_err() {
+                    still_pending.push(idx);
+                }
+            }
+            pending = still_pending;
+
+            if !pending.is_empty() {
+                if Instant::now() >= deadline {
+                    let names = pending.iter().map(|idx| self.nodes[*idx].name());
+                    return Err(TestnetError::ConnectTimeout(names.collect()));
+                }
+                time::sleep(POLL_INTERVAL).await;
+            }
+        }
+
+        info!("All {} nodes accept peer connections", self.nodes.len());
+        Ok(())
+    }
+
+    /// Wait for any node to exit, returning its name
+    pub async fn wait_for_node_exit(&mut self) -> String {
+        loop {
+            if let Some(name) = self.exited_node() {
+                return name;
+            }
+            time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Name of the first node found to have exited
+    fn exited_node(&mut self) -> Option<String> {
+        self.nodes
+            .iter_mut()
+            .find_map(|node| match node.child.try_wait() {
+                Ok(Some(status)) => {
+                    warn!("Node {} exited with {}", node.name(), status);
+                    Some(node.name())
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Node {} status unavailable: {:?}", node.name(), e);
+                    Some(node.name())
+                }
+            })
+    }
+
+    /// Stop all nodes in reverse start order
+    pub async fn shutdown(&mut self) {
+        for mut node in self.nodes.drain(..).rev() {
+            if let Err(e) = node.child.kill().await {
+                warn!("Failed to stop node {}: {:?}", node.name(), e);
+            }
+        }
+        info!("Testnet stopped");
+    }
+}
+
+/// Spawn a node process, writing its output to its log file
+///
+/// ### Arguments
+///
+/// * `config`    - Configuration of the test network.
+/// * `node_type` - Type of node to spawn.
+/// * `index`     - Index of the node in the node config.
+/// * `args`      - Arguments to start the node with.
+pub fn spawn_node(
+    config: &TestnetConfig,
+    node_type: &str,
+    index: usize,
+    args: &[String],
+) -> io::Result<Child> {
+    let log_path = config
+        .log_dir
+        .join(format!("{}.log", node_name(node_type, index)));
+    let log = File::options().create(true).append(true).open(log_path)?;
+
+    Command::new(&config.node_bin)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .kill_on_drop(true)
+        .spawn()
+}
+
+/// Command line arguments for a node
+///
+/// ### Arguments
+///
+/// * `config`    - Configuration of the test network.
+/// * `node_type` - Type of node.
+/// * `index`     - Index of the node in the node config.
+/// * `api_port`  - Port the node API listens on.
+pub fn node_args(
+    config: &TestnetConfig,
+    node_type: &str,
+    index: usize,
+    api_port: u16,
+) -> Vec<String> {
+    let mut args = vec![
+        node_type.to_owned(),
+        format!("--config={}", config.node_config),
+        format!("--index={index}"),
+        format!("--api_port={api_port}"),
+    ];
+
+    if let Some(use_tls) = &config.api_use_tls {
+        args.push(format!("--api_use_tls={use_tls}"));
+    }
+
+    if matches!(node_type, "miner" | "user") {
+        let mempool_count = node_count(config, "mempool").max(1);
+        args.push(format!("--mempool_index={}", index % mempool_count));
+    }
+
+    if let Some(seed_config) = &config.seed_config {
+        if node_type != "storage" {
+            args.push(format!("--initial_block_config={seed_config}"));
+        }
+    }
+    args
+}
+
+/// Number of nodes of the given type to run
+///
+/// ### Arguments
+///
+/// * `config`    - Configuration of the test network.
+/// * `node_type` - Type of node.
+fn node_count(config: &TestnetConfig, node_type: &str) -> usize {
+    config
+        .nodes
+        .iter()
+        .find(|(t, _)| *t == node_type)
+        .map_or(0, |(_, addresses)| addresses.len())
+}
+
+/// Name of a node, e.g `mempool_0`
+fn node_name(node_type: &str, index: usize) -> String {
+    format!("{node_type}_{index}")
+}
+
+/// Remove databases left by previous test runs
+fn remove_test_dbs() -> io::Result<()> {
+    for folder in TEST_DB_FOLDERS {
+        let entries = match fs::read_dir(folder) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            let is_test_db = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(TEST_DB_PREFIX));
+            if is_test_db {
+                info!("Remove test database {:?}", path);
+                fs::remove_dir_all(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn clap_app<'a, 'b>() -> App<'a, 'b> {
+    let app = App::new("testnet")
+        .about("Runs a local test network of AIBlock nodes.")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .env("CONFIG")
+                .help("Node config listing the nodes to run.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("node_bin")
+                .long("node_bin")
+                .help("Path to the node binary, defaults to the one next to this binary.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("seed_config")
+                .long("seed_config")
+                .help("Initial block config used to seed the UTXO set and user wallets.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api_port")
+                .long("api_port")
+                .help("API port of the first node, other nodes use the following ports.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api_use_tls")
+                .long("api_use_tls")
+                .help("Whether the nodes APIs use TLS (0 or 1).")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log_dir")
+                .long("log_dir")
+                .help("Folder for the nodes log files.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keep_dbs")
+                .long("keep_dbs")
+                .help("Keep the test databases of previous runs."),
+        )
+        .arg(
+            Arg::with_name("connect_timeout")
+                .long("connect_timeout")
+                .help("Seconds allowed for all nodes to accept connections.")
+                .takes_value(true),
+        );
+
+    let app = chaos::clap_args(app);
+    NODE_TYPES.iter().fold(app, |app, node_type| {
+        app.arg(
+            Arg::with_name(node_type)
+                .long(node_type)
+                .help("Number of nodes of this type to run, defaults to all in the config.")
+                .takes_value(true),
+        )
+    })
+}
+
+fn configuration(matches: &ArgMatches) -> Result<TestnetConfig, TestnetError> {
+    let node_config = matches
+        .value_of("config")
+        .unwrap_or("src/bin/node_settings_local_raft_2.toml")
+        .to_owned();
+
+    let mut settings = config::Config::default();
+    settings.merge(config::File::with_name(&node_config))?;
+
+    let mut nodes = Vec::new();
+    for node_type in NODE_TYPES {
+        let specs: Vec<NodeSpec> = settings.get(&format!("{node_type}_nodes"))?;
+        let count = match matches.value_of(node_type) {
+            Some(count) => parse_arg(node_type, count)?,
+            None => specs.len(),
+        };
+        if count > specs.len() {
+            let msg = format!(
+                "{count} {node_type} nodes requested, {} in config",
+                specs.len()
+            );
+            return Err(ConfigError::Message(msg).into());
+        }
+
+        let addresses = specs.iter().take(count).map(|spec| {
+            let msg = format!("Invalid {node_type} node address {}", spec.address);
+            spec.address.parse().map_err(|_| ConfigError::Message(msg))
+        });
+        nodes.push((*node_type, addresses.collect::<Result<_, _>>()?));
+    }
+
+    let chaos = chaos::configuration(matches)?;
+    let api_use_tls = match (&chaos, matches.value_of("api_use_tls")) {
+        (Some(_), Some(use_tls)) if use_tls != "0" => {
+            let msg = "Chaos mode checks use the nodes APIs without TLS".to_owned();
+            return Err(ConfigError::Message(msg).into());
+        }
+        (Some(_), _) => Some("0".to_owned()),
+        (None, use_tls) => use_tls.map(ToOwned::to_owned),
+    };
+
+    let node_bin = match matches.value_of("node_bin") {
+        Some(path) => PathBuf::from(path),
+        None => default_node_bin()?,
+    };
+
+    Ok(TestnetConfig {
+        node_bin,
+        node_config,
+        seed_config: matches.value_of("seed_config").map(ToOwned::to_owned),
+        nodes,
+        api_port_start: parse_arg("api_port", matches.value_of("api_port").unwrap_or("3001"))?,
+        api_use_tls,
+        log_dir: Path::new(matches.value_of("log_dir").unwrap_or("testnet_logs")).to_owned(),
+        keep_dbs: matches.is_present("keep_dbs"),
+        connect_timeout: Duration::from_secs(parse_arg(
+            "connect_timeout",
+            matches.value_of("connect_timeout").unwrap_or("60"),
+        )?),
+        chaos,
+    })
+}
+
+/// The `node` binary built next to this one
+fn default_node_bin() -> io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    Ok(exe.with_file_name(format!("node{}", std::env::consts::EXE_SUFFIX)))
+}
+
+/// Parse a numeric argument
+///
+/// ### Arguments
+///
+/// * `name`  - Name of the argument.
+/// * `value` - Value to parse.
+fn parse_arg<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::Message(format!("Invalid {name}: {value}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Checks the nodes read from config are launched with the matching arguments
+    fn validate_startup_raft_2() {
+        //
+        // Arrange
+        //
+        let args = [
+            "bin_name",
+            "--config=src/bin/node_settings_local_raft_2.toml",
+            "--node_bin=target/node",
+            "--miner=3",
+            "--user=1",
+            "--api_use_tls=0",
+            "--seed_config=src/bin/initial_block.json",
+        ];
+
+        //
+        // Act
+        //
+        let matches = clap_app().get_matches_from_safe(args.iter()).unwrap();
+        let config = configuration(&matches).unwrap();
+        let counts: Vec<_> = NODE_TYPES.iter().map(|t| node_count(&config, t)).collect();
+        let storage_args = node_args(&config, "storage", 1, 3002);
+        let miner_args = node_args(&config, "miner", 2, 3007);
+
+        //
+        // Assert
+        //
+        assert_eq!(counts, vec![2, 2, 3, 1]);
+        assert_eq!(
+            storage_args,
+            vec![
+                "storage",
+                "--config=src/bin/node_settings_local_raft_2.toml",
+                "--index=1",
+                "--api_port=3002",
+                "--api_use_tls=0",
+            ]
+        );
+        assert_eq!(
+            miner_args,
+            vec![
+                "miner",
+                "--config=src/bin/node_settings_local_raft_2.toml",
+                "--index=2",
+                "--api_port=3007",
+                "--api_use_tls=0",
+                "--mempool_index=0",
+                "--initial_block_config=src/bin/initial_block.json",
+            ]
+        );
+    }
+}
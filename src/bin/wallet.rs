@@ -0,0 +1,11 @@
+//! App to manage a wallet database without running a node.
+//!
+//! Kept as a wrapper of the `wallet` command of the `aiblock` app.
+
+#[path = "aiblock/wallet.rs"]
+mod wallet;
+
+#[tokio::main]
+async fn main() -> Result<(), wallet::WalletCliError> {
+    wallet::run(&wallet::clap_app().get_matches()).await
+}
@@ -9,6 +9,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tracing::log::{debug, info};
 use tw_chain::primitives::asset::TokenAmount;
 use tw_chain::primitives::block::Block;
@@ -101,6 +102,8 @@ pub struct MiningPipelineInfo {
     empty_participants: Participants,
     /// The last round winning hashes
     last_winning_hashes: BTreeSet<String>,
+    /// The last round miners with a valid PoW
+    last_pow_miners: BTreeSet<SocketAddr>,
     /// The wining PoWs for selection
     all_winning_pow: Vec<(SocketAddr, WinningPoWInfo)>,
     /// The unicorn info for the selections
@@ -118,7 +121,7 @@ pub struct MiningPipelineInfo {
     /// Index of the last block,
     current_block_num: Option<u64>,
     /// Current block ready to mine (consensused).
-    current_block: Option<Block>,
+    current_block: Option<Arc<Block>>,
     /// All transactions present in current_block (consensused).
     current_block_tx: BTreeMap<String, Transaction>,
     /// The current reward for a given mempool node
@@ -155,7 +158,7 @@ impl MiningPipelineInfo {
     }
 
     /// Current block to mine or being mined.
-    pub fn get_mining_block(&self) -> &Option<Block> {
+    pub fn get_mining_block(&self) -> &Option<Arc<Block>> {
         &self.current_block
     }
 
@@ -170,7 +173,7 @@ impl MiningPipelineInfo {
         block: Block,
         block_tx: BTreeMap<String, Transaction>,
     ) {
-        self.current_block = Some(block);
+        self.current_block = Some(Arc::new(block));
         self.current_block_tx = block_tx;
     }
 
@@ -178,7 +181,7 @@ impl MiningPipelineInfo {
     pub fn take_mining_block(&mut self) -> Option<(Block, BTreeMap<String, Transaction>)> {
         let block = std::mem::take(&mut self.current_block);
         let block_tx = std::mem::take(&mut self.current_block_tx);
-        block.map(|b| (b, block_tx))
+        block.map(|b| (Arc::unwrap_or_clone(b), block_tx))
     }
 
     pub fn apply_ready_block_stored_info(&mut self, block_num: u64, reward: TokenAmount) {
@@ -209,6 +212,7 @@ impl MiningPipelineInfo {
 
         // Only keep relevant info for this phase
         self.last_winning_hashes = Default::default();
+        self.last_pow_miners = Default::default();
         self.all_winning_pow = Default::default();
         self.winning_pow = Default::default();
         self.current_phase_timeout_peer_ids = Default::default();
@@ -352,7 +356,7 @@ impl MiningPipelineInfo {
     pub fn test_skip_mining(&mut self, winning_pow: (SocketAddr, WinningPoWInfo), seed: Vec<u8>) {
         info!("test_skip_mining PoW entry: {:?} ({:?})", winning_pow, seed);
 
-        let block = self.current_block.as_mut().unwrap();
+        let block = Arc::make_mut(self.current_block.as_mut().unwrap());
         block.header.seed_value = seed;
 
         self.unicorn_select_participants_mining(usize::MAX);
@@ -377,6 +381,11 @@ impl MiningPipelineInfo {
         &self.winning_pow
     }
 
+    /// Retrieves the miners that sent a valid PoW in the current mining round
+    pub fn get_pow_miners(&self) -> &BTreeSet<SocketAddr> {
+        &self.last_pow_miners
+    }
+
     /// Get the mining pipeline status
     pub fn get_mining_pipeline_status(&self) -> &MiningPipelineStatus {
         &self.mining_pipeline_status
@@ -457,6 +466,7 @@ impl MiningPipelineInfo {
         self.winning_pow = self
             .get_unicorn_item(WINNING_MINER_UN, &all_winning_pow)
             .cloned();
+        self.last_pow_miners = all_winning_pow.iter().map(|(addr, _)| *addr).collect();
         self.last_winning_hashes = all_winning_pow
             .into_iter()
             .map(|(_, pow)| pow.mining_tx.0)
@@ -490,7 +500,7 @@ impl MiningPipelineInfo {
 
     /// Sets the new UNICORN value based on the latest info
     pub fn construct_unicorn(&mut self) {
-        let block = self.current_block.as_mut().unwrap();
+        let block = Arc::make_mut(self.current_block.as_mut().unwrap());
         let tx_inputs = &block.transactions;
 
         debug!(
@@ -562,7 +572,7 @@ impl MiningPipelineInfo {
         Self {
             unicorn_fixed_param,
             current_block_num,
-            current_block,
+            current_block: current_block.map(Arc::new),
             ..Default::default()
         }
     }
@@ -572,7 +582,7 @@ impl MiningPipelineInfo {
         MiningPipelineInfoImport {
             unicorn_fixed_param: self.unicorn_fixed_param,
             current_block_num: self.current_block_num,
-            current_block: self.current_block,
+            current_block: self.current_block.map(Arc::unwrap_or_clone),
         }
     }
 }
@@ -0,0 +1,192 @@
+//! Aggregates of the blocks stored by the storage node.
+//!
+//! The statistics of a block are computed when it is stored and written in the
+//! same batch, in their own column keyed by block number, so a range of blocks
+//! reads as a time series without reprocessing the blocks. A block replacing
+//! another at the same height replaces its statistics.
+
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch};
+use crate::screening::transaction_addresses;
+use crate::storage::DB_COL_BC_STATS;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::warn;
+use tw_chain::primitives::asset::TokenAmount;
+use tw_chain::primitives::block::Block;
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxOut};
+
+/// Maximum number of blocks returned for one range
+pub const MAX_BLOCK_STATS_RANGE: u64 = 1000;
+
+/// Aggregates of a stored block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockStats {
+    pub b_num: u64,
+    pub block_hash: String,
+    pub timestamp: i64,
+    /// Seconds since the previous block, None if it is not stored
+    pub interval: Option<i64>,
+    /// Transactions of the block, coinbase excluded
+    pub tx_count: u64,
+    /// Tokens paid to the outputs of the transactions, coinbase excluded
+    pub amount_transferred: TokenAmount,
+    /// Distinct addresses of the outputs spent and created by the block
+    pub unique_addresses: u64,
+}
+
+impl BlockStats {
+    /// Compute the statistics of a block
+    ///
+    /// ### Arguments
+    ///
+    /// * `block`              - Block to compute the statistics of.
+    /// * `block_hash`         - Hash of the block.
+    /// * `block_txs`          - Transactions of the block, by hash.
+    /// * `spent`              - Outputs spent by the block transactions, if known.
+    /// * `previous_timestamp` - Timestamp of the previous block, if stored.
+    pub fn new(
+        block: &Block,
+        block_hash: String,
+        block_txs: &BTreeMap<String, Transaction>,
+        spent: &BTreeMap<OutPoint, TxOut>,
+        previous_timestamp: Option<i64>,
+    ) -> Self {
+        let txs: Vec<_> = block
+            .transactions
+            .iter()
+            .filter_map(|hash| block_txs.get(hash))
+            .collect();
+        let coinbase = block_txs.get(&block.header.nonce_and_mining_tx_hash.1);
+
+        let amount_transferred = txs
+            .iter()
+            .flat_map(|tx| tx.outputs.iter())
+            .map(|tx_out| tx_out.value.token_amount())
+            .sum();
+        let addresses: BTreeSet<String> = txs
+            .iter()
+            .chain(coinbase.iter())
+            .flat_map(|tx| transaction_addresses(tx, |out_p| spent.get(out_p)))
+            .collect();
+
+        Self {
+            b_num: block.header.b_num,
+            block_hash,
+            timestamp: block.header.timestamp,
+            interval: previous_timestamp.map(|t| block.header.timestamp - t),
+            tx_count: txs.len() as u64,
+            amount_transferred,
+            unique_addresses: addresses.len() as u64,
+        }
+    }
+}
+
+/// The key of the statistics of a block, ordered by block number
+///
+/// ### Arguments
+///
+/// * `b_num` - The block number
+pub fn block_stats_key(b_num: u64) -> String {
+    format!("{b_num:016x}")
+}
+
+/// Add the statistics of a block to a batch
+///
+/// ### Arguments
+///
+/// * `batch` - Database writer
+/// * `stats` - Statistics of the block
+pub fn put_block_stats(batch: &mut SimpleDbWriteBatch, stats: &BlockStats) {
+    let value = serialize(stats).unwrap();
+    batch.put_cf(DB_COL_BC_STATS, block_stats_key(stats.b_num), &value);
+}
+
+/// Statistics of the stored blocks of a range, by ascending block number
+///
+/// ### Arguments
+///
+/// * `db`    - Database holding the statistics
+/// * `start` - First block number of the range
+/// * `end`   - Last block number of the range, capped to `MAX_BLOCK_STATS_RANGE` blocks
+pub fn get_block_stats(db: &SimpleDb, start: u64, end: u64) -> Vec<BlockStats> {
+    let end = end.min(start.saturating_add(MAX_BLOCK_STATS_RANGE - 1));
+    (start..=end)
+        .filter_map(|b_num| {
+            let value = db.get_cf(DB_COL_BC_STATS, block_stats_key(b_num));
+            value.unwrap_or_else(|e| {
+                warn!("get_block_stats {}: {}", b_num, e);
+                None
+            })
+        })
+        .filter_map(|value| deserialize(&value).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::asset::Asset;
+    use tw_chain::primitives::transaction::TxIn;
+
+    fn tx_out(address: &str, amount: u64) -> TxOut {
+        TxOut {
+            value: Asset::token_u64(amount),
+            script_public_key: Some(address.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    /// Checks the statistics count the block transactions and addresses, coinbase apart
+    fn block_stats_aggregates_block() {
+        //Arrange
+        let spent_out_p = OutPoint::new("prev".to_owned(), 0);
+        let spent = vec![(spent_out_p.clone(), tx_out("alice", 10))]
+            .into_iter()
+            .collect();
+        let mut payment = Transaction::new();
+        payment.inputs.push(TxIn {
+            previous_out: Some(spent_out_p),
+            ..Default::default()
+        });
+        payment.outputs = vec![tx_out("bob", 7), tx_out("alice", 3)];
+        let mut coinbase = Transaction::new();
+        coinbase.outputs = vec![tx_out("miner", 50)];
+
+        let mut block = Block::new();
+        block.header.b_num = 4;
+        block.header.timestamp = 1_700_000_060;
+        block.header.nonce_and_mining_tx_hash = (Vec::new(), "coinbase".to_owned());
+        block.transactions = vec!["payment".to_owned()];
+        let block_txs = vec![
+            ("payment".to_owned(), payment),
+            ("coinbase".to_owned(), coinbase),
+        ]
+        .into_iter()
+        .collect();
+
+        //Act
+        let stats = BlockStats::new(
+            &block,
+            "hash".to_owned(),
+            &block_txs,
+            &spent,
+            Some(1_700_000_000),
+        );
+
+        //Assert
+        assert_eq!(
+            stats,
+            BlockStats {
+                b_num: 4,
+                block_hash: "hash".to_owned(),
+                timestamp: 1_700_000_060,
+                interval: Some(60),
+                tx_count: 1,
+                amount_transferred: TokenAmount(10),
+                unique_addresses: 3,
+            }
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Validation of the timestamps of blocks.
+//!
+//! A block timestamp must not be behind the median timestamp of the blocks
+//! preceding it, so the timestamps of the chain keep moving forward even if a
+//! few blocks are stamped early, and must not be further in the future than a
+//! tolerated clock drift. Storage nodes refuse blocks breaking either rule,
+//! while mempool nodes never stamp a block behind the median.
+
+use std::error::Error;
+use std::fmt;
+
+/// Number of preceding blocks whose median timestamp a block must not be behind
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Seconds a block timestamp may be ahead of the node clock, when not configured
+pub const DEFAULT_MAX_BLOCK_TIME_DRIFT: i64 = 120;
+
+/// Block timestamp refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTimestampError {
+    /// Further in the future than the tolerated drift
+    TooFarInFuture { timestamp: i64, limit: i64 },
+    /// Behind the median timestamp of the preceding blocks
+    BehindMedian { timestamp: i64, median: i64 },
+}
+
+impl fmt::Display for BlockTimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooFarInFuture { timestamp, limit } => {
+                write!(f, "Block timestamp {timestamp} after {limit}")
+            }
+            Self::BehindMedian { timestamp, median } => {
+                write!(f, "Block timestamp {timestamp} behind median {median}")
+            }
+        }
+    }
+}
+
+impl Error for BlockTimestampError {}
+
+/// Median of the timestamps of the preceding blocks, None if there are none
+///
+/// ### Arguments
+///
+/// * `timestamps` - Timestamps of the preceding blocks, only the last `MEDIAN_TIME_SPAN` are used
+pub fn median_time_past(timestamps: &[i64]) -> Option<i64> {
+    let start = timestamps.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut recent = timestamps[start..].to_vec();
+    recent.sort_unstable();
+    recent.get(recent.len() / 2).copied()
+}
+
+/// Check a block timestamp against the preceding blocks and the node clock
+///
+/// ### Arguments
+///
+/// * `timestamp`  - Timestamp of the block
+/// * `timestamps` - Timestamps of the preceding blocks, oldest first
+/// * `now`        - Current time of the node
+/// * `max_drift`  - Seconds the block timestamp may be ahead of `now`
+pub fn check_block_timestamp(
+    timestamp: i64,
+    timestamps: &[i64],
+    now: i64,
+    max_drift: i64,
+) -> Result<(), BlockTimestampError> {
+    let limit = now.saturating_add(max_drift);
+    if timestamp > limit {
+        return Err(BlockTimestampError::TooFarInFuture { timestamp, limit });
+    }
+    match median_time_past(timestamps) {
+        Some(median) if timestamp < median => {
+            Err(BlockTimestampError::BehindMedian { timestamp, median })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the median only uses the last blocks of the span
+    fn median_time_past_of_last_blocks() {
+        let timestamps: Vec<i64> = (0..20).map(|t| t * 10).collect();
+
+        assert_eq!(median_time_past(&[]), None);
+        assert_eq!(median_time_past(&[30, 10, 20]), Some(20));
+        assert_eq!(median_time_past(&timestamps), Some(140));
+    }
+
+    #[test]
+    /// Checks timestamps behind the median or too far ahead are refused
+    fn check_block_timestamp_tolerance() {
+        //Arrange
+        let timestamps = vec![100, 160, 110, 130, 120];
+        let now = 1000;
+
+        //Act
+        let results: Vec<_> = vec![120, 119, 1120, 1121]
+            .into_iter()
+            .map(|t| check_block_timestamp(t, &timestamps, now, 120))
+            .collect();
+
+        //Assert
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Err(BlockTimestampError::BehindMedian {
+                    timestamp: 119,
+                    median: 120
+                }),
+                Ok(()),
+                Err(BlockTimestampError::TooFarInFuture {
+                    timestamp: 1121,
+                    limit: 1120
+                }),
+            ]
+        );
+        assert_eq!(check_block_timestamp(0, &[], now, 0), Ok(()));
+    }
+}
@@ -0,0 +1,133 @@
+//! Block hashes known in advance at given heights.
+//!
+//! Checkpoints are compiled in `BUILTIN_CHECKPOINTS` or added by the storage
+//! node configuration. Storage nodes refuse to store a block whose hash differs
+//! from the checkpoint at its height, whether committed or fetched during catch
+//! up, and refuse to start on a database holding such a block.
+
+use crate::configurations::CheckpointSpec;
+use std::collections::BTreeMap;
+
+/// Checkpoints of the main network, as (height, block hash)
+pub const BUILTIN_CHECKPOINTS: &[(u64, &str)] = &[];
+
+/// Error building the checkpoints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointConflict {
+    /// Height given two different block hashes
+    pub height: u64,
+}
+
+/// Block hashes by height
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checkpoints {
+    hashes: BTreeMap<u64, String>,
+}
+
+impl Checkpoints {
+    /// Built in checkpoints along with the configured ones
+    ///
+    /// ### Arguments
+    ///
+    /// * `configured` - Checkpoints from the node configuration
+    pub fn new(configured: &[CheckpointSpec]) -> Result<Self, CheckpointConflict> {
+        let builtin = BUILTIN_CHECKPOINTS.iter().map(|(h, b)| (*h, *b));
+        let configured = configured.iter().map(|c| (c.height, c.block_hash.as_str()));
+        Self::from_hashes(builtin.chain(configured))
+    }
+
+    /// Checkpoints from the given (height, block hash)
+    ///
+    /// ### Arguments
+    ///
+    /// * `hashes` - Block hash of each height, a height may only be given one hash
+    pub fn from_hashes<'a>(
+        hashes: impl IntoIterator<Item = (u64, &'a str)>,
+    ) -> Result<Self, CheckpointConflict> {
+        let mut checkpoints = Self::default();
+        for (height, block_hash) in hashes {
+            match checkpoints.hashes.get(&height) {
+                Some(known) if known != block_hash => return Err(CheckpointConflict { height }),
+                _ => checkpoints.hashes.insert(height, block_hash.to_owned()),
+            };
+        }
+        Ok(checkpoints)
+    }
+
+    /// Height of the last checkpoint
+    pub fn last_height(&self) -> Option<u64> {
+        self.hashes.keys().next_back().copied()
+    }
+
+    /// Whether a block agrees with the checkpoint at its height, if any
+    ///
+    /// ### Arguments
+    ///
+    /// * `b_num`      - Height of the block
+    /// * `block_hash` - Hash of the block
+    pub fn verify(&self, b_num: u64, block_hash: &str) -> bool {
+        self.hashes.get(&b_num).map_or(true, |h| h == block_hash)
+    }
+
+    /// Height of the first checkpoint a stored chain diverges from
+    ///
+    /// ### Arguments
+    ///
+    /// * `stored_hash` - Hash of the stored block at a height, None if not stored
+    pub fn first_divergence(&self, stored_hash: impl Fn(u64) -> Option<String>) -> Option<u64> {
+        self.hashes
+            .iter()
+            .find(|(height, hash)| stored_hash(**height).is_some_and(|s| &s != *hash))
+            .map(|(height, _)| *height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks only blocks at a checkpoint height are compared to it
+    fn checkpoints_verify_block_hashes() {
+        //Arrange
+        let checkpoints = Checkpoints::from_hashes([(10, "b10"), (20, "b20")]).unwrap();
+
+        //Act
+        let verified = [
+            checkpoints.verify(10, "b10"),
+            checkpoints.verify(10, "other"),
+            checkpoints.verify(15, "other"),
+        ];
+
+        //Assert
+        assert_eq!(verified, [true, false, true]);
+        assert_eq!(checkpoints.last_height(), Some(20));
+    }
+
+    #[test]
+    /// Checks conflicting checkpoints and diverging stored chains are detected
+    fn checkpoints_conflict_and_divergence() {
+        //Arrange
+        let checkpoints = Checkpoints::from_hashes([(10, "b10"), (20, "b20")]).unwrap();
+        let stored = |height: u64| match height {
+            10 => Some("b10".to_owned()),
+            20 => Some("fork".to_owned()),
+            _ => None,
+        };
+        let behind = |height: u64| (height == 10).then(|| "b10".to_owned());
+
+        //Act
+        let conflict = Checkpoints::from_hashes([(10, "b10"), (10, "fork")]);
+        let divergence = checkpoints.first_divergence(stored);
+        let no_divergence = checkpoints.first_divergence(behind);
+
+        //Assert
+        assert_eq!(conflict, Err(CheckpointConflict { height: 10 }));
+        assert_eq!(divergence, Some(20));
+        assert_eq!(no_divergence, None);
+        assert_eq!(
+            Checkpoints::new(&[]).unwrap().last_height(),
+            BUILTIN_CHECKPOINTS.last().map(|(h, _)| *h)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Clock used by the node timers.
+//!
+//! Nodes use the system clock by default. Tests can use a manual clock that only
+//! moves forward when advanced, so raft ticks, block and fetch timeouts and
+//! reconnect delays fire deterministically without waiting for real time.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{self, Instant};
+
+/// Error returned when a deadline elapsed before the future completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// Source of time for node timers
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    /// Real time
+    #[default]
+    System,
+    /// Time that only moves when advanced
+    Manual(ManualClock),
+}
+
+impl Clock {
+    /// Create a manual clock starting at the current time
+    pub fn manual() -> Self {
+        Self::Manual(ManualClock::new())
+    }
+
+    /// The manual clock if used
+    pub fn as_manual(&self) -> Option<&ManualClock> {
+        match self {
+            Self::System => None,
+            Self::Manual(clock) => Some(clock),
+        }
+    }
+
+    /// Current time
+    pub fn now(&self) -> Instant {
+        match self {
+            Self::System => Instant::now(),
+            Self::Manual(clock) => clock.now(),
+        }
+    }
+
+    /// Wait until the deadline is reached
+    ///
+    /// ### Arguments
+    ///
+    /// * `deadline` - Time to wait for.
+    pub async fn sleep_until(&self, deadline: Instant) {
+        match self {
+            Self::System => time::sleep_until(deadline).await,
+            Self::Manual(clock) => clock.sleep_until(deadline).await,
+        }
+    }
+
+    /// Wait for the given duration
+    ///
+    /// ### Arguments
+    ///
+    /// * `duration` - Duration to wait for.
+    pub async fn sleep(&self, duration: Duration) {
+        self.sleep_until(self.now() + duration).await
+    }
+
+    /// Wait for the future to complete, or the deadline to be reached
+    ///
+    /// ### Arguments
+    ///
+    /// * `deadline` - Time after which the future is abandoned.
+    /// * `future`   - Future to wait for.
+    pub async fn timeout_at<F: Future>(
+        &self,
+        deadline: Instant,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        tokio::pin!(future);
+        tokio::select! {
+            biased;
+            output = &mut future => Ok(output),
+            _ = self.sleep_until(deadline) => Err(Elapsed),
+        }
+    }
+}
+
+/// Clock only moving forward when advanced, shared by its clones
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+    advanced: Arc<Notify>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Create a manual clock starting at the current time
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Default::default(),
+            advanced: Default::default(),
+        }
+    }
+
+    /// Current time
+    pub fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    /// Move the clock forward, waking timers reaching their deadline
+    ///
+    /// ### Arguments
+    ///
+    /// * `duration` - Duration to move forward by.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+        self.advanced.notify_waiters();
+    }
+
+    /// Wait until the clock is advanced to the deadline
+    ///
+    /// ### Arguments
+    ///
+    /// * `deadline` - Time to wait for.
+    pub async fn sleep_until(&self, deadline: Instant) {
+        loop {
+            let advanced = self.advanced.notified();
+            if self.now() >= deadline {
+                return;
+            }
+            advanced.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::FutureExt;
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks manual timers only fire once the clock is advanced past their deadline
+    async fn manual_clock_fires_when_advanced() {
+        //
+        // Arrange
+        //
+        let clock = Clock::manual();
+        let manual = clock.as_manual().unwrap();
+        let deadline = clock.now() + Duration::from_secs(10);
+        let mut sleep = Box::pin(clock.sleep_until(deadline));
+        let mut pending = Box::pin(clock.timeout_at(deadline, futures::future::pending::<()>()));
+
+        //
+        // Act
+        //
+        let before = (&mut sleep).now_or_never();
+        manual.advance(Duration::from_secs(5));
+        tokio::task::yield_now().await;
+        let half_way = (&mut sleep).now_or_never();
+        manual.advance(Duration::from_secs(5));
+        let after = (&mut sleep).now_or_never();
+        let timed_out = (&mut pending).now_or_never();
+
+        //
+        // Assert
+        //
+        assert_eq!((before, half_way, after), (None, None, Some(())));
+        assert_eq!(timed_out, Some(Err(Elapsed)));
+        assert_eq!(clock.now(), deadline);
+    }
+}
@@ -28,10 +28,18 @@ pub enum CommsError {
     PeerDuplicate(PeerInfo),
     /// This peer is not compatible.
     PeerIncompatible(PeerInfo),
+    /// This peer did not present its pinned certificate.
+    PeerCertificateMismatch(PeerInfo),
+    /// This peer handshake is not signed by the identity it presented.
+    PeerIdentityInvalid(PeerInfo),
+    /// This peer IP address is not allowed.
+    PeerNotAllowed(PeerInfo),
     /// Serialization-related error.
     Serialization(bincode::Error),
     /// MPSC channel error.
     ChannelSendError(mpsc::error::SendError<Event>),
+    /// The event queue of the node is full.
+    EventQueueFull,
     /// Webpki error
     WebpkiError(webpki::Error),
 }
@@ -55,8 +63,14 @@ impl fmt::Display for CommsError {
             Self::PeerDuplicate(info) => write!(f, "Duplicate peer: {info:?}"),
             Self::PeerInvalidState(info) => write!(f, "Peer has invalid state: {info:?}"),
             Self::PeerIncompatible(info) => write!(f, "Peer incompatible: {info:?}"),
+            Self::PeerCertificateMismatch(info) => {
+                write!(f, "Peer certificate mismatch: {info:?}")
+            }
+            Self::PeerIdentityInvalid(info) => write!(f, "Peer identity invalid: {info:?}"),
+            Self::PeerNotAllowed(info) => write!(f, "Peer not allowed: {info:?}"),
             Self::Serialization(err) => write!(f, "Serialization error: {err}"),
             Self::ChannelSendError(err) => write!(f, "MPSC channel send error: {err}"),
+            Self::EventQueueFull => write!(f, "Event queue is full"),
             Self::WebpkiError(err) => write!(f, "Webpki error: {err}"),
         }
     }
@@ -75,8 +89,12 @@ impl Error for CommsError {
             Self::PeerInvalidState(_) => None,
             Self::PeerDuplicate(_) => None,
             Self::PeerIncompatible(_) => None,
+            Self::PeerCertificateMismatch(_) => None,
+            Self::PeerIdentityInvalid(_) => None,
+            Self::PeerNotAllowed(_) => None,
             Self::Serialization(err) => Some(err),
             Self::ChannelSendError(err) => Some(err),
+            Self::EventQueueFull => None,
             Self::WebpkiError(err) => Some(err),
         }
     }
@@ -100,6 +118,17 @@ impl From<mpsc::error::SendError<Event>> for CommsError {
     }
 }
 
+impl From<mpsc::error::TrySendError<Event>> for CommsError {
+    fn from(other: mpsc::error::TrySendError<Event>) -> Self {
+        match other {
+            mpsc::error::TrySendError::Full(_) => Self::EventQueueFull,
+            mpsc::error::TrySendError::Closed(event) => {
+                Self::ChannelSendError(mpsc::error::SendError(event))
+            }
+        }
+    }
+}
+
 impl From<TLSError> for CommsError {
     fn from(other: TLSError) -> Self {
         Self::TlsError(other)
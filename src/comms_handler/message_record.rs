@@ -0,0 +1,130 @@
+//! Recording and replay of the frames received by a node.
+//!
+//! A recording holds every frame the node event loop received, with the time it
+//! was received and the peer it came from, in the order it was processed.
+//! Replaying a recording feeds the frames back into a node event loop with the
+//! same spacing, so an issue observed on a live node can be reproduced exactly
+//! in a test environment.
+
+use super::{Event, Result};
+use bytes::Bytes;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+/// A frame received by a node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Time the frame was received, in milliseconds since the UNIX epoch
+    pub timestamp: i64,
+    /// Peer the frame was received from
+    pub peer: SocketAddr,
+    /// Frame content
+    pub frame: Vec<u8>,
+}
+
+/// Writer appending received frames to a recording file, shared by its clones
+#[derive(Debug, Clone)]
+pub struct MessageRecorder {
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl MessageRecorder {
+    /// Create a recorder appending to the given file
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Path of the recording file, created if missing.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+        })
+    }
+
+    /// Append a received frame to the recording
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer`  - Peer the frame was received from.
+    /// * `frame` - Frame content.
+    pub fn record(&self, peer: SocketAddr, frame: &Bytes) -> Result<()> {
+        let record = RecordedFrame {
+            timestamp: Utc::now().timestamp_millis(),
+            peer,
+            frame: frame.to_vec(),
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        bincode::serialize_into(&mut *writer, &record)?;
+        // Flush every frame so a recording survives the node crashing
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read all the frames of a recording file
+///
+/// ### Arguments
+///
+/// * `path` - Path of the recording file.
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<RecordedFrame>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut frames = Vec::new();
+
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(frame) => frames.push(frame),
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(ref io_e) if io_e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(frames)
+                }
+                _ => return Err(e.into()),
+            },
+        }
+    }
+}
+
+/// Feed recorded frames into an event loop, spaced as they were received
+///
+/// ### Arguments
+///
+/// * `event_tx` - Channel of the event loop to feed.
+/// * `frames`   - Recorded frames in the order they were received.
+pub(crate) fn spawn_replay(
+    event_tx: mpsc::Sender<Event>,
+    frames: Vec<RecordedFrame>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let start = Instant::now();
+        let first_timestamp = frames.first().map(|f| f.timestamp).unwrap_or_default();
+        let total = frames.len();
+
+        for RecordedFrame {
+            timestamp,
+            peer,
+            frame,
+        } in frames
+        {
+            let offset = (timestamp - first_timestamp).max(0) as u64;
+            time::sleep_until(start + Duration::from_millis(offset)).await;
+
+            let frame = Bytes::from(frame);
+            if let Err(error) = event_tx.send(Event::NewFrame { peer, frame }).await {
+                warn!(?error, "Replay stopped: event loop closed");
+                return;
+            }
+        }
+
+        info!("Replayed {} recorded frames", total);
+    })
+}
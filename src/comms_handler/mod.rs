@@ -1,7 +1,11 @@
 //! This module provides basic networking interfaces.
 
 mod error;
+pub mod message_record;
+#[cfg(test)]
+pub mod network_sim;
 pub mod node;
+pub mod peer_filter;
 mod stream_cancel;
 pub mod tcp_tls;
 #[cfg(test)]
@@ -10,7 +14,9 @@ pub mod test_tls_certificates;
 mod tests;
 
 pub use error::CommsError;
+pub use message_record::{read_recording, MessageRecorder, RecordedFrame};
 pub use node::Node;
+pub use peer_filter::{IpCidr, PeerIpFilter};
 pub use tcp_tls::{TcpTlsConfig, TcpTlsConnector, TcpTlsListner};
 
 use bytes::Bytes;
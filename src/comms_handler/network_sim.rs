@@ -0,0 +1,343 @@
+//! Test-only network simulation for the comms layer.
+//!
+//! A `NetworkSim` shared between nodes intercepts every frame sent by a node and
+//! applies the conditions configured for the (sender, receiver) link: latency,
+//! jitter, random drops and partitions. Random decisions use a seeded generator
+//! so a test run is reproducible. Frames on a link are delivered in order.
+
+use super::node::ResultBytesSender;
+use bytes::Bytes;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant};
+use tracing::{error, trace};
+
+/// Link between a sending node and a receiving node listener addresses
+pub type SimLink = (SocketAddr, SocketAddr);
+
+/// Conditions applied to frames sent over a link
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkConditions {
+    /// Fixed delay added to every frame
+    pub latency: Duration,
+    /// Maximum random delay added on top of the latency
+    pub jitter: Duration,
+    /// Probability in [0, 1] that a frame is dropped
+    pub drop_rate: f64,
+}
+
+/// Counters of frames handled by the simulation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkSimStats {
+    /// Frames sent without modification
+    pub delivered: usize,
+    /// Frames delivered after a delay
+    pub delayed: usize,
+    /// Frames dropped randomly or because of a partition
+    pub dropped: usize,
+}
+
+/// What happens to a frame sent over a link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameAction {
+    Deliver,
+    Delay(Duration),
+    Drop,
+}
+
+/// Frame waiting to be delivered on a delayed link
+struct DelayedFrame {
+    deliver_at: Instant,
+    tx: ResultBytesSender,
+    bytes: Bytes,
+}
+
+struct NetworkSimState {
+    rng: StdRng,
+    default_conditions: LinkConditions,
+    links: BTreeMap<SimLink, LinkConditions>,
+    partitioned: BTreeSet<SimLink>,
+    delayed_queues: BTreeMap<SimLink, mpsc::UnboundedSender<DelayedFrame>>,
+    stats: NetworkSimStats,
+}
+
+/// Simulated network shared by the nodes of a test
+#[derive(Clone)]
+pub struct NetworkSim {
+    state: Arc<Mutex<NetworkSimState>>,
+}
+
+impl std::fmt::Debug for NetworkSim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetworkSim").finish()
+    }
+}
+
+impl NetworkSim {
+    /// Create a simulation delivering all frames unmodified
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed` - Seed for drop and jitter decisions.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NetworkSimState {
+                rng: StdRng::seed_from_u64(seed),
+                default_conditions: Default::default(),
+                links: Default::default(),
+                partitioned: Default::default(),
+                delayed_queues: Default::default(),
+                stats: Default::default(),
+            })),
+        }
+    }
+
+    /// Set the conditions of links without specific conditions
+    ///
+    /// ### Arguments
+    ///
+    /// * `conditions` - Conditions to apply.
+    pub fn set_default_conditions(&self, conditions: LinkConditions) {
+        self.lock().default_conditions = conditions;
+    }
+
+    /// Set the conditions for frames sent from `from` to `to`
+    ///
+    /// ### Arguments
+    ///
+    /// * `from`       - Address of the sending node.
+    /// * `to`         - Address of the receiving node.
+    /// * `conditions` - Conditions to apply.
+    pub fn set_link_conditions(
+        &self,
+        from: SocketAddr,
+        to: SocketAddr,
+        conditions: LinkConditions,
+    ) {
+        self.lock().links.insert((from, to), conditions);
+    }
+
+    /// Set the conditions for frames sent in both directions between two nodes
+    ///
+    /// ### Arguments
+    ///
+    /// * `a`          - Address of the first node.
+    /// * `b`          - Address of the second node.
+    /// * `conditions` - Conditions to apply.
+    pub fn set_pair_conditions(&self, a: SocketAddr, b: SocketAddr, conditions: LinkConditions) {
+        let mut state = self.lock();
+        state.links.insert((a, b), conditions);
+        state.links.insert((b, a), conditions);
+    }
+
+    /// Drop all frames between nodes of the two groups, in both directions
+    ///
+    /// ### Arguments
+    ///
+    /// * `group_a` - Addresses of the nodes on one side of the partition.
+    /// * `group_b` - Addresses of the nodes on the other side of the partition.
+    pub fn partition(&self, group_a: &[SocketAddr], group_b: &[SocketAddr]) {
+        let mut state = self.lock();
+        for a in group_a {
+            for b in group_b {
+                state.partitioned.insert((*a, *b));
+                state.partitioned.insert((*b, *a));
+            }
+        }
+    }
+
+    /// Remove all partitions
+    pub fn heal_partitions(&self) {
+        self.lock().partitioned.clear();
+    }
+
+    /// Remove all partitions and link conditions
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        state.partitioned.clear();
+        state.links.clear();
+        state.default_conditions = Default::default();
+    }
+
+    /// Counters of frames handled so far
+    pub fn stats(&self) -> NetworkSimStats {
+        self.lock().stats
+    }
+
+    /// Send the frame applying the link conditions
+    ///
+    /// ### Arguments
+    ///
+    /// * `from`  - Address of the sending node.
+    /// * `to`    - Address of the receiving node.
+    /// * `tx`    - Channel to the receiving peer connection.
+    /// * `bytes` - Frame to send.
+    pub(crate) async fn send(
+        &self,
+        from: SocketAddr,
+        to: SocketAddr,
+        tx: &mut ResultBytesSender,
+        bytes: Bytes,
+    ) {
+        let delayed = match self.frame_action(from, to) {
+            FrameAction::Drop => {
+                trace!(?from, ?to, "network_sim dropped frame");
+                return;
+            }
+            FrameAction::Deliver if !self.has_delayed_queue((from, to)) => None,
+            FrameAction::Deliver => Some(Duration::from_secs(0)),
+            FrameAction::Delay(delay) => Some(delay),
+        };
+
+        if let Some(delay) = delayed {
+            let frame = DelayedFrame {
+                deliver_at: Instant::now() + delay,
+                tx: tx.clone(),
+                bytes,
+            };
+            self.queue_delayed((from, to), frame);
+        } else if let Err(error) = tx.send(Ok(bytes), "tx_bytes").await {
+            error!(?error, "Error sending a frame through the message channel");
+        }
+    }
+
+    /// Decide what happens to the next frame sent over the link
+    ///
+    /// ### Arguments
+    ///
+    /// * `from` - Address of the sending node.
+    /// * `to`   - Address of the receiving node.
+    pub(crate) fn frame_action(&self, from: SocketAddr, to: SocketAddr) -> FrameAction {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let link = (from, to);
+        let conditions = *state.links.get(&link).unwrap_or(&state.default_conditions);
+
+        let action = if state.partitioned.contains(&link)
+            || (conditions.drop_rate > 0.0 && state.rng.gen::<f64>() < conditions.drop_rate)
+        {
+            FrameAction::Drop
+        } else {
+            let jitter_us = conditions.jitter.as_micros() as u64;
+            let jitter = if jitter_us > 0 {
+                Duration::from_micros(state.rng.gen_range(0, jitter_us + 1))
+            } else {
+                Duration::from_secs(0)
+            };
+
+            let delay = conditions.latency + jitter;
+            if delay > Duration::from_secs(0) {
+                FrameAction::Delay(delay)
+            } else {
+                FrameAction::Deliver
+            }
+        };
+
+        match action {
+            FrameAction::Deliver => state.stats.delivered += 1,
+            FrameAction::Delay(_) => state.stats.delayed += 1,
+            FrameAction::Drop => state.stats.dropped += 1,
+        }
+        action
+    }
+
+    /// Whether frames may still be waiting on the link, so new frames must queue behind them
+    fn has_delayed_queue(&self, link: SimLink) -> bool {
+        self.lock()
+            .delayed_queues
+            .get(&link)
+            .is_some_and(|queue| !queue.is_closed())
+    }
+
+    /// Queue a frame on the link delivery task, starting it if needed
+    fn queue_delayed(&self, link: SimLink, frame: DelayedFrame) {
+        let mut state = self.lock();
+        let frame = match state.delayed_queues.get(&link) {
+            Some(queue) => match queue.send(frame) {
+                Ok(()) => return,
+                Err(mpsc::error::SendError(frame)) => frame,
+            },
+            None => frame,
+        };
+
+        let (queue_tx, queue_rx) = mpsc::unbounded_channel();
+        let _ = queue_tx.send(frame);
+        state.delayed_queues.insert(link, queue_tx);
+        tokio::spawn(deliver_delayed_frames(queue_rx));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, NetworkSimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Deliver the frames of a link in order once their delay elapsed
+///
+/// ### Arguments
+///
+/// * `queue_rx` - Frames queued on the link.
+async fn deliver_delayed_frames(mut queue_rx: mpsc::UnboundedReceiver<DelayedFrame>) {
+    while let Some(DelayedFrame {
+        deliver_at,
+        mut tx,
+        bytes,
+    }) = queue_rx.recv().await
+    {
+        time::sleep_until(deliver_at).await;
+        if let Err(error) = tx.send(Ok(bytes), "tx_bytes").await {
+            error!(
+                ?error,
+                "Error sending a delayed frame through the message channel"
+            );
+        }
+    }
+}
+
+mod test {
+    use super::*;
+
+    fn actions(sim: &NetworkSim, from: SocketAddr, to: SocketAddr) -> Vec<FrameAction> {
+        (0..32).map(|_| sim.frame_action(from, to)).collect()
+    }
+
+    #[test]
+    /// Checks the same seed gives the same drop and jitter decisions
+    fn frame_actions_deterministic_for_seed() {
+        //
+        // Arrange
+        //
+        let a: SocketAddr = "127.0.0.1:12300".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:12301".parse().unwrap();
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(10),
+            jitter: Duration::from_millis(5),
+            drop_rate: 0.5,
+        };
+        let sims: Vec<_> = (0..2).map(|_| NetworkSim::new(7)).collect();
+        sims.iter()
+            .for_each(|s| s.set_link_conditions(a, b, conditions));
+
+        //
+        // Act
+        //
+        let first = actions(&sims[0], a, b);
+        let second = actions(&sims[1], a, b);
+        let reverse = actions(&sims[0], b, a);
+        sims[0].partition(&[a], &[b]);
+        let partitioned = actions(&sims[0], b, a);
+
+        //
+        // Assert
+        //
+        assert_eq!(first, second);
+        assert!(first.contains(&FrameAction::Drop));
+        assert!(first.iter().any(|a| matches!(a, FrameAction::Delay(_))));
+        assert!(reverse.iter().all(|a| *a == FrameAction::Deliver));
+        assert!(partitioned.iter().all(|a| *a == FrameAction::Drop));
+    }
+}
@@ -7,6 +7,8 @@
 //! Nodes use a custom TCP protocol to communicate which is message-based. The available message types can be found in [`CommMessage`](crate::interfaces::CommMessage).
 //! The messages are serialized into a binary format using the `bincode` crate for efficiency (it can be easily replaced with any other [serde][serde]-compatible serialization
 //! format such as JSON if needed).
+//! The payloads of received [`Direct`](crate::interfaces::CommMessage::Direct) and [`Gossip`](crate::interfaces::CommMessage::Gossip) messages are
+//! sliced out of their frame without copying, see [`decode_comm_message`].
 //!
 //! Because each node acts as a server, a _listener_ service is started automatically when you crate a new [`Node`](crate::comms_handler::node::Node).
 //!
@@ -20,6 +22,8 @@
 //! Once the TCP connection with the peer is established, a node sends a [`HandshakeRequest`](crate::interfaces::CommMessage::HandshakeRequest),
 //! a specially crafted message which contains a [node type](crate::interfaces::NodeType) and the node's public address (which is derived from the
 //! listener address). Please note that this address should be publicly available (i.e., we assume that a node is not behind a NAT or a firewall).
+//! It also contains the network version and [network](crate::interfaces::NetworkId) of the node: peers of another version or network are rejected,
+//! so testnet nodes never exchange messages with mainnet ones.
 //!
 //! In response, a node expects a [`HandshakeResponse`](crate::interfaces::CommMessage::HandshakeResponse) message which contains a contact list
 //! of all other peers that form a _ring_.
@@ -50,6 +54,20 @@
 //! but both types of messages within a single `MempoolRequest` type). This simplifies deserialization and handling of these
 //! messages.
 //!
+//! ## Logical channels
+//!
+//! All the messages to a peer share a single connection, multiplexed over [logical channels](crate::comms_handler::node::PeerChannel):
+//! handshakes and heartbeats, gossip and direct messages each have their own budget of
+//! [`PEER_CHANNEL_CAPACITY`](crate::comms_handler::node::PEER_CHANNEL_CAPACITY) queued frames.
+//! A peer flooded with direct requests therefore only applies back pressure to the senders of direct messages,
+//! while heartbeats and gossip still go through without needing an extra connection.
+//! Frames are written in the order they were queued, whatever their channel.
+//!
+//! Received frames wait for the node event loop in a queue of
+//! [`DEFAULT_EVENT_QUEUE_CAPACITY`](crate::comms_handler::node::DEFAULT_EVENT_QUEUE_CAPACITY) events unless configured otherwise.
+//! Once it is full, frames stop being read from the peers until the event loop catches up, so a flood of messages
+//! is held back by the TCP connections instead of growing the memory of the node.
+//!
 //! ## Multicast
 //!
 //! Multicast messages are delivered to all nodes within a ring.
@@ -80,38 +98,52 @@
 //! [serde]: https://serde.rs
 //! [netbuffersize]: https://stackoverflow.com/a/7865130/168853
 
+use super::message_record::{spawn_replay, MessageRecorder, RecordedFrame};
+#[cfg(test)]
+use super::network_sim::NetworkSim;
 use super::tcp_tls::{
     verify_is_valid_for_dns_names, TcpTlsConnector, TcpTlsListner, TcpTlsStream, TlsCertificate,
 };
-use super::{CommsError, Event, Result, TcpTlsConfig};
+use super::{CommsError, Event, PeerIpFilter, Result, TcpTlsConfig};
 use crate::comms_handler::error::PeerInfo;
-use crate::constants::NETWORK_VERSION;
-use crate::interfaces::{node_type_as_str, CommMessage, NodeType, Token};
-use crate::utils::MpscTracingSender;
+use crate::constants::PEER_NETWORK_VERSION;
+use crate::event_bus::NodeEvent;
+use crate::event_hooks::{dispatch_event, EventHooks};
+use crate::interfaces::{node_type_as_str, CommMessage, IdentityProof, NetworkId, NodeType, Token};
+use crate::node_identity::{verify_handshake, NodeIdentity};
 use bincode::{deserialize, serialize};
 use bytes::Bytes;
 use futures::future::join_all;
 use futures::SinkExt;
 use rand::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::{fmt, io};
 use tokio::time::{interval, timeout, Duration};
 use tokio::{
-    self, spawn,
-    sync::{mpsc, oneshot, Mutex, RwLock},
+    self,
+    io::AsyncRead,
+    spawn,
+    sync::{mpsc, oneshot, Mutex, OwnedSemaphorePermit, RwLock, Semaphore},
     task::JoinHandle,
 };
+use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_stream::{Stream, StreamExt};
 use tokio_util::codec::{length_delimited, FramedRead, FramedWrite, LengthDelimitedCodec};
 use tracing::{debug, error, info, info_span, trace, warn, Span};
 use tracing_futures::Instrument;
+use tw_chain::crypto::sign_ed25519::PublicKey;
 
 extern crate serde_json;
 
-pub type ResultBytesSender = MpscTracingSender<io::Result<Bytes>>;
+pub type ResultBytesSender = ChannelSender;
+
+/// Frame queued for a peer, holding its place in the channel budget.
+type QueuedFrame = (io::Result<Bytes>, OwnedSemaphorePermit);
 
 /// Number of peers we select for gossip message retransmittion.
 const FANOUT: usize = 8;
@@ -122,6 +154,21 @@ const GOSSIP_MAX_TTL: u8 = 4;
 /// Generic Heartbeat interval in seconds
 const HEART_BEAT_INTERVAL: u64 = 3;
 
+/// Number of frames queued on each logical channel of a peer connection.
+pub const PEER_CHANNEL_CAPACITY: usize = 128;
+
+/// Default number of received events waiting for the node event loop.
+pub const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// Number of received events waiting for the node event loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventQueueDepth {
+    /// Events waiting.
+    pub depth: usize,
+    /// Events that can wait before reading from peers stops.
+    pub capacity: usize,
+}
+
 const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5); // 5 seconds is just a wild guess. Tweak if necessary.
 
 /// Contains a shared list of connected peers.
@@ -145,6 +192,12 @@ pub struct Node {
     listener_and_connect_paused: Arc<RwLock<bool>>,
     /// Connector used to initialize connection.
     tcp_tls_connector: Arc<RwLock<TcpTlsConnector>>,
+    /// Certificates the peers at these addresses must present.
+    pinned_peer_certs: Arc<BTreeMap<SocketAddr, TlsCertificate>>,
+    /// IP ranges of the peers to accept and connect to.
+    peer_ip_filter: Arc<RwLock<PeerIpFilter>>,
+    /// File persisting the peer IP ranges.
+    peer_ip_filter_path: Option<Arc<PathBuf>>,
     /// List of all connected peers.
     pub(crate) peers: Arc<RwLock<PeerList>>,
     /// Node type.
@@ -154,9 +207,9 @@ pub struct Node {
     /// Tracing context.
     span: Span,
     /// Channel to transmit incoming frames and events from peers.
-    event_tx: mpsc::UnboundedSender<Event>,
+    event_tx: mpsc::Sender<Event>,
     /// Incoming events from peers.
-    event_rx: Arc<Mutex<mpsc::UnboundedReceiver<Event>>>,
+    event_rx: Arc<Mutex<mpsc::Receiver<Event>>>,
     /// Contains message IDs that have been already seen and retransmitted by this node.
     // TODO: this requires further work - a list of seen gossip messages can grow unbounded,
     // and it requires to be purged periodically in order to conserve resources.
@@ -165,13 +218,24 @@ pub struct Node {
     connect_to_handshake_contacts: bool,
     /// Threadhandle for a HeartBeat Prober
     heartbeat_handle: Option<Arc<JoinHandle<()>>>,
+    /// Simulated network conditions applied to sent frames
+    #[cfg(test)]
+    network_sim: Arc<RwLock<Option<NetworkSim>>>,
+    /// Recorder of the frames received by the event loop
+    message_recorder: Arc<std::sync::Mutex<Option<MessageRecorder>>>,
+    /// Callbacks run when a peer connects
+    event_hooks: Arc<std::sync::Mutex<EventHooks>>,
+    /// Identity signing the handshakes of this node
+    identity: Arc<std::sync::Mutex<Option<NodeIdentity>>>,
+    /// Network of this node, only connecting to peers of the same network
+    network_id: Arc<std::sync::Mutex<NetworkId>>,
 }
 
 pub(crate) struct Peer {
     /// Node network version.
     network_version: Option<u32>,
-    /// Channel for sending frames to the peer.
-    send_tx: ResultBytesSender,
+    /// Channels for sending frames to the peer.
+    send_txs: PeerSenders,
     /// Peer remote address.
     addr: SocketAddr,
     /// Peer type.
@@ -181,6 +245,8 @@ pub(crate) struct Peer {
     /// `addr` will have its address with the ephemeral port, while `public_address` will contain
     /// the address that this peer is listening on.
     public_address: Option<SocketAddr>,
+    /// Identity key the peer signed its handshake with.
+    identity: Option<PublicKey>,
     /// Notification to trigger a task waiting for a handshake response.
     // TODO: move it to a separate state enum, manage state transitions in a better way
     notify_handshake_response: (Option<oneshot::Sender<()>>, Option<oneshot::Receiver<()>>),
@@ -200,6 +266,84 @@ impl fmt::Debug for Peer {
     }
 }
 
+/// Logical channel multiplexed over a peer connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerChannel {
+    /// Handshakes and heartbeats.
+    Control,
+    /// Gossip messages.
+    Gossip,
+    /// Direct messages.
+    Direct,
+}
+
+impl PeerChannel {
+    /// Logical channel a message is sent on.
+    pub fn for_message(message: &CommMessage) -> Self {
+        match message {
+            CommMessage::HandshakeRequest { .. }
+            | CommMessage::HandshakeResponse { .. }
+            | CommMessage::HeartBeatProbe(_) => Self::Control,
+            CommMessage::Gossip { .. } => Self::Gossip,
+            CommMessage::Direct { .. } => Self::Direct,
+        }
+    }
+}
+
+/// Sender of frames on one logical channel of a peer connection.
+#[derive(Clone)]
+pub struct ChannelSender {
+    /// Channel the frames are sent on.
+    channel: PeerChannel,
+    /// Queue of the frames of all the channels of the connection.
+    queue: mpsc::UnboundedSender<QueuedFrame>,
+    /// Frames this channel can still queue.
+    budget: Arc<Semaphore>,
+}
+
+impl ChannelSender {
+    /// Queue a frame, waiting if the channel has too many frames queued.
+    pub async fn send(
+        &mut self,
+        value: io::Result<Bytes>,
+        tag: &str,
+    ) -> std::result::Result<(), mpsc::error::SendError<io::Result<Bytes>>> {
+        let permit = match self.budget.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                trace!("send_tracing({}) {:?} full: waiting", tag, self.channel);
+                match self.budget.clone().acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => return Err(mpsc::error::SendError(value)),
+                }
+            }
+        };
+
+        self.queue
+            .send((value, permit))
+            .map_err(|mpsc::error::SendError((value, _))| mpsc::error::SendError(value))
+    }
+}
+
+/// Senders for each logical channel of a peer connection.
+#[derive(Clone)]
+struct PeerSenders {
+    control: ChannelSender,
+    gossip: ChannelSender,
+    direct: ChannelSender,
+}
+
+impl PeerSenders {
+    /// Sender of the given logical channel.
+    fn get(&self, channel: PeerChannel) -> ChannelSender {
+        match channel {
+            PeerChannel::Control => self.control.clone(),
+            PeerChannel::Gossip => self.gossip.clone(),
+            PeerChannel::Direct => self.direct.clone(),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum PeerState {
     WaitingForHandshake,
@@ -225,7 +369,7 @@ impl Node {
             config,
             peer_limit,
             node_type,
-            NETWORK_VERSION,
+            PEER_NETWORK_VERSION,
             disable_listening,
             send_heartbeat_messages,
         )
@@ -248,13 +392,14 @@ impl Node {
         disable_listening: bool,
         send_heartbeat_messages: bool,
     ) -> Result<Self> {
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::channel(config.event_queue_capacity());
 
         let listener = TcpTlsListner::new(config).await?;
         let local_listener_address = listener.listener_address();
         let span = info_span!("node", ?local_listener_address);
 
         let tcp_tls_connector = TcpTlsConnector::new(config)?;
+        let (peer_ip_filter, peer_ip_filter_path) = config.peer_ip_filter();
 
         let mut node = Self {
             network_version,
@@ -263,6 +408,9 @@ impl Node {
             listener_stop_and_join_handles: Arc::new(Mutex::new(None)),
             listener_and_connect_paused: Arc::new(RwLock::new(false)),
             tcp_tls_connector: Arc::new(RwLock::new(tcp_tls_connector)),
+            pinned_peer_certs: Arc::new(config.pinned_peer_certs().clone()),
+            peer_ip_filter: Arc::new(RwLock::new(peer_ip_filter.clone())),
+            peer_ip_filter_path: peer_ip_filter_path.cloned().map(Arc::new),
             node_type,
             peers: Arc::new(RwLock::new(HashMap::with_capacity(peer_limit))),
             peer_limit,
@@ -272,6 +420,12 @@ impl Node {
             seen_gossip_messages: Arc::new(RwLock::new(HashSet::new())),
             connect_to_handshake_contacts: false,
             heartbeat_handle: None,
+            #[cfg(test)]
+            network_sim: Default::default(),
+            message_recorder: Default::default(),
+            event_hooks: Default::default(),
+            identity: Default::default(),
+            network_id: Default::default(),
         };
 
         if !disable_listening {
@@ -295,14 +449,7 @@ impl Node {
             loop {
                 interval.tick().await;
 
-                let peers: Vec<SocketAddr> = node
-                    .peers
-                    .read()
-                    .await
-                    .iter()
-                    .map(|(addr, _)| addr)
-                    .cloned()
-                    .collect();
+                let peers: Vec<SocketAddr> = node.peers.read().await.keys().cloned().collect();
                 debug!("Peers to send HB {:?}", peers);
 
                 match node.send_heartbeat_message(peers.into_iter()).await {
@@ -318,8 +465,14 @@ impl Node {
         })
     }
 
-    fn is_compatible(&self, node_type: NodeType, network_version: u32) -> bool {
+    fn is_compatible(
+        &self,
+        node_type: NodeType,
+        network_version: u32,
+        network_id: NetworkId,
+    ) -> bool {
         self.network_version == network_version
+            && self.network_id() == network_id
             && (self.node_type == NodeType::PreLaunch) == (node_type == NodeType::PreLaunch)
     }
 
@@ -327,6 +480,32 @@ impl Node {
         self.connect_to_handshake_contacts = value;
     }
 
+    /// Get the IP ranges of the peers to accept and connect to
+    pub async fn peer_ip_filter(&self) -> PeerIpFilter {
+        self.peer_ip_filter.read().await.clone()
+    }
+
+    /// Set the IP ranges of the peers to accept and connect to, persisting them if configured
+    ///
+    /// Only new connections are filtered, existing peers stay connected.
+    ///
+    /// ### Arguments
+    ///
+    /// * `filter` - Allow and deny lists of IP ranges.
+    pub async fn set_peer_ip_filter(&self, filter: PeerIpFilter) -> Result<()> {
+        let mut current = self.peer_ip_filter.write().await;
+        if let Some(path) = &self.peer_ip_filter_path {
+            filter.save(path)?;
+        }
+        *current = filter;
+        Ok(())
+    }
+
+    /// Whether a peer at this address can be accepted or connected to
+    async fn is_peer_ip_allowed(&self, peer: SocketAddr) -> bool {
+        self.peer_ip_filter.read().await.is_allowed(peer.ip())
+    }
+
     /// Handles the listener.
     async fn listen(self, listener: TcpTlsListner) -> Result<Self> {
         let node = self.clone();
@@ -349,6 +528,10 @@ impl Node {
                                 // Ignore new connections
                                 continue;
                             }
+                            if !node.is_peer_ip_allowed(conn.peer_addr()).await {
+                                warn!(peer_addr = ?conn.peer_addr(), "Peer IP not allowed");
+                                continue;
+                            }
 
                             // TODO: have a timeout for incoming handshake to disconnect clients who linger on without any communication
                             let peer_span = info_span!(
@@ -386,7 +569,7 @@ impl Node {
     /// * `force_add`    - If true and the peer limit is reached, an old peer will be ejected to make space.
     /// * `peer_span`    - Tracing scope for this peer.
     /// * `is_initiator` - If `true`, this peer has connected to us. If `false`, then _we_ are
-    ///                    connecting to this peer.
+    ///   connecting to this peer.
     async fn add_peer(
         &self,
         socket: TcpTlsStream,
@@ -438,6 +621,12 @@ impl Node {
                     address: Some(peer),
                 }));
             }
+            if !self.is_peer_ip_allowed(peer).await {
+                return Err(CommsError::PeerNotAllowed(PeerInfo {
+                    node_type: None,
+                    address: Some(peer),
+                }));
+            }
 
             let stream = self.tcp_tls_connector.read().await.connect(peer).await?;
             let peer_addr = stream.peer_addr();
@@ -517,7 +706,8 @@ impl Node {
     pub async fn stop_listening(&mut self) -> Vec<JoinHandle<()>> {
         trace!("stop_listening {:?}", self.local_listener_address);
         if let Some((stop_tx, handle)) = self.listener_stop_and_join_handles.lock().await.take() {
-            stop_tx.send(()).unwrap();
+            // The listener may already have stopped on its own
+            let _ = stop_tx.send(());
             vec![handle]
         } else {
             Vec::new()
@@ -650,6 +840,12 @@ impl Node {
         bytes: Bytes,
     ) -> Result<()> {
         trace!(?bytes, ?peer, "send_bytes");
+        #[cfg(test)]
+        if let Some(sim) = self.network_sim.read().await.clone() {
+            sim.send(self.local_listener_address, peer, tx, bytes).await;
+            return Ok(());
+        }
+
         if let Err(error) = tx.send(Ok(bytes), "tx_bytes").await {
             error!(?error, "Error sending a frame through the message channel");
         }
@@ -673,6 +869,7 @@ impl Node {
 
     /// Sends data to a peer.
     async fn send_message(&mut self, peer_addr: SocketAddr, message: CommMessage) -> Result<()> {
+        let channel = PeerChannel::for_message(&message);
         let data = Bytes::from(serialize(&message)?);
 
         let peers = self.peers.read().await;
@@ -682,7 +879,7 @@ impl Node {
                 node_type: None,
                 address: Some(peer_addr),
             }))?;
-        let mut tx = peer.send_tx.clone();
+        let mut tx = peer.send_txs.get(channel);
         self.send_bytes(peer_addr, &mut tx, data).await
     }
 
@@ -742,8 +939,10 @@ impl Node {
             peer,
             CommMessage::HandshakeRequest {
                 network_version: self.network_version,
+                network_id: self.network_id(),
                 node_type: self.node_type,
                 public_address: self.local_listener_address,
+                identity: self.prove_handshake(self.local_listener_address),
             },
         )
         .await
@@ -751,7 +950,18 @@ impl Node {
 
     /// Blocks & waits for a next event from a peer.
     pub async fn next_event(&mut self) -> Option<Event> {
-        self.event_rx.lock().await.recv().await
+        let event = self.event_rx.lock().await.recv().await;
+
+        // Record without awaiting so the event is not lost if the caller drops this future
+        if let Some(Event::NewFrame { peer, frame }) = &event {
+            if let Some(recorder) = self.message_recorder.lock().unwrap().as_ref() {
+                if let Err(error) = recorder.record(*peer, frame) {
+                    warn!(?error, "Failed to record received frame");
+                }
+            }
+        }
+
+        event
     }
 
     pub fn inject_next_event(
@@ -760,12 +970,116 @@ impl Node {
         data: impl Serialize,
     ) -> Result<()> {
         let payload = Bytes::from(serialize(&data)?);
-        Ok(self.event_tx.send(Event::NewFrame {
+        Ok(self.event_tx.try_send(Event::NewFrame {
             peer: from_peer_addr,
             frame: payload,
         })?)
     }
 
+    /// Number of received events waiting for the event loop.
+    pub fn event_queue_depth(&self) -> EventQueueDepth {
+        let capacity = self.event_tx.max_capacity();
+        EventQueueDepth {
+            depth: capacity - self.event_tx.capacity(),
+            capacity,
+        }
+    }
+
+    /// Queue a received event for the event loop.
+    ///
+    /// Waits while the queue is full, which stops reading from the peer.
+    ///
+    /// ### Arguments
+    ///
+    /// * `event` - Event received.
+    async fn queue_event(&self, event: Event) -> Result<()> {
+        match self.event_tx.try_send(event) {
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                trace!("event queue full: waiting");
+                Ok(self.event_tx.send(event).await?)
+            }
+            other => Ok(other?),
+        }
+    }
+
+    /// Apply simulated network conditions to frames sent by this node and its clones.
+    ///
+    /// ### Arguments
+    ///
+    /// * `sim` - Simulated network to use, or None to send frames directly.
+    #[cfg(test)]
+    pub async fn set_network_sim(&self, sim: Option<NetworkSim>) {
+        *self.network_sim.write().await = sim;
+    }
+
+    /// Record the frames received by the event loop of this node and its clones.
+    ///
+    /// ### Arguments
+    ///
+    /// * `recorder` - Recorder to use, or None to stop recording.
+    pub fn set_message_recorder(&self, recorder: Option<MessageRecorder>) {
+        *self.message_recorder.lock().unwrap() = recorder;
+    }
+
+    /// Sign the handshakes of this node and its clones with the given identity.
+    ///
+    /// ### Arguments
+    ///
+    /// * `identity` - Identity of the node.
+    pub fn set_identity(&self, identity: NodeIdentity) {
+        *self.identity.lock().unwrap() = Some(identity);
+    }
+
+    /// Set the network of the node, peers of other networks are rejected at handshake.
+    ///
+    /// ### Arguments
+    ///
+    /// * `network_id` - Network of the node.
+    pub fn set_network_id(&self, network_id: NetworkId) {
+        *self.network_id.lock().unwrap() = network_id;
+    }
+
+    /// Network of the node.
+    pub fn network_id(&self) -> NetworkId {
+        *self.network_id.lock().unwrap()
+    }
+
+    /// Identity key a connected peer signed its handshake with, if any.
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer` - Public address of the peer.
+    pub async fn get_peer_identity(&self, peer: SocketAddr) -> Option<PublicKey> {
+        self.peers.read().await.get(&peer).and_then(|p| p.identity)
+    }
+
+    /// Proof of the node identity for a handshake, if it has one.
+    fn prove_handshake(&self, public_address: SocketAddr) -> Option<IdentityProof> {
+        let identity = self.identity.lock().unwrap();
+        let prove = |i: &NodeIdentity| {
+            i.prove_handshake(self.network_version, self.node_type, public_address)
+        };
+        identity.as_ref().map(prove)
+    }
+
+    /// Run the given hooks when a peer connects to this node or its clones.
+    ///
+    /// ### Arguments
+    ///
+    /// * `hooks` - Event hooks of the node.
+    pub fn set_event_hooks(&self, hooks: EventHooks) {
+        *self.event_hooks.lock().unwrap() = hooks;
+    }
+
+    /// Feed recorded frames into the event loop, spaced as they were received.
+    ///
+    /// ### Arguments
+    ///
+    /// * `frames` - Recorded frames in the order they were received.
+    pub fn replay_recording(&self, frames: Vec<RecordedFrame>) -> JoinHandle<()> {
+        spawn_replay(self.event_tx.clone(), frames)
+    }
+
     /// Returns this node's local listener address.
     pub fn local_address(&self) -> SocketAddr {
         self.local_listener_address
@@ -796,8 +1110,10 @@ impl Node {
             match message {
                 CommMessage::HandshakeRequest {
                     network_version: v,
+                    network_id,
                     node_type: t,
                     public_address,
+                    identity,
                 } => {
                     match self
                         .handle_handshake_request(
@@ -806,7 +1122,9 @@ impl Node {
                             &peer_cert,
                             send_tx.clone(),
                             v,
+                            network_id,
                             t,
+                            identity,
                         )
                         .await
                     {
@@ -820,17 +1138,22 @@ impl Node {
                 }
                 CommMessage::HandshakeResponse {
                     network_version,
+                    network_id,
                     node_type,
                     contacts,
                     public_address,
+                    identity,
                 } => {
                     match self
                         .handle_handshake_response(
                             peer_addr,
+                            &peer_cert,
                             network_version,
+                            network_id,
                             node_type,
                             contacts,
                             public_address,
+                            identity,
                         )
                         .await
                     {
@@ -866,10 +1189,13 @@ impl Node {
                     payload: frame,
                     id: _,
                 } => {
-                    if let Err(error) = self.event_tx.send(Event::NewFrame {
-                        peer: peer_addr,
-                        frame,
-                    }) {
+                    if let Err(error) = self
+                        .queue_event(Event::NewFrame {
+                            peer: peer_addr,
+                            frame,
+                        })
+                        .await
+                    {
                         warn!(?error, ?peer_addr, "event_tx.send");
                     }
                 }
@@ -910,10 +1236,11 @@ impl Node {
         }
 
         // Notify a user about the message.
-        self.event_tx.send(Event::NewFrame {
+        self.queue_event(Event::NewFrame {
             peer: from_peer,
             frame: payload.clone(),
-        })?;
+        })
+        .await?;
 
         // Retransmit the gossip message.
         if ttl > GOSSIP_MAX_TTL {
@@ -959,7 +1286,10 @@ impl Node {
     /// * `peer_cert`       - peer's certificate.
     /// * `send_tx`         - channel to send messages to the peer.
     /// * `network_version` - network version of the peer.
+    /// * `network_id`      - network of the peer.
     /// * `peer_type`       - type of the peer.
+    /// * `identity`        - identity proof of the peer, if any.
+    #[allow(clippy::too_many_arguments)]
     async fn handle_handshake_request(
         &self,
         peer_out_addr: SocketAddr,
@@ -967,22 +1297,28 @@ impl Node {
         peer_cert: &Option<TlsCertificate>,
         mut send_tx: ResultBytesSender,
         network_version: u32,
+        network_id: NetworkId,
         peer_type: NodeType,
+        identity: Option<IdentityProof>,
     ) -> Result<SocketAddr> {
         info!(
             "peer_out_addr: {:?}, peer_in_addr: {:?}",
             peer_out_addr, peer_in_addr
         );
+        let identity =
+            self.verify_peer_identity(identity, network_version, peer_type, peer_in_addr)?;
         // Derive IP from peer_out_addr; resolved through connection
         // Use port from peer_in_addr; resolved through handshake data
         peer_in_addr = SocketAddr::new(peer_out_addr.ip(), peer_in_addr.port());
-        if !self.is_compatible(peer_type, network_version) {
+        if !self.is_compatible(peer_type, network_version, network_id) {
             return Err(CommsError::PeerIncompatible(PeerInfo {
                 node_type: Some(peer_type),
                 address: Some(peer_in_addr),
             }));
         }
 
+        self.verify_pinned_certificate(peer_in_addr, peer_type, peer_cert)?;
+
         let mut all_peers = self.peers.write().await;
         if all_peers.contains_key(&peer_in_addr) {
             return Err(CommsError::PeerDuplicate(PeerInfo {
@@ -1013,13 +1349,16 @@ impl Node {
         peer.network_version = Some(network_version);
         peer.peer_type = Some(peer_type);
         peer.public_address = Some(peer_in_addr);
+        peer.identity = identity;
 
         // Send handshake response which will contain contacts of all valid peers within our ring.
         let response = CommMessage::HandshakeResponse {
             network_version: self.network_version,
+            network_id: self.network_id(),
             node_type: self.node_type,
             contacts: self.ring_peers(&all_peers).collect(),
             public_address: peer_in_addr,
+            identity: self.prove_handshake(peer_in_addr),
         };
         let message = Bytes::from(serialize(&response)?);
         self.send_bytes(peer_out_addr, &mut send_tx, message)
@@ -1028,23 +1367,81 @@ impl Node {
         Ok(peer_in_addr)
     }
 
+    /// Checks the identity a peer presented signed its handshake.
+    ///
+    /// ## Arguments
+    /// * `identity`        - identity proof of the peer, if any.
+    /// * `network_version` - network version sent by the peer.
+    /// * `peer_type`       - type sent by the peer.
+    /// * `public_address`  - address sent by the peer.
+    fn verify_peer_identity(
+        &self,
+        identity: Option<IdentityProof>,
+        network_version: u32,
+        peer_type: NodeType,
+        public_address: SocketAddr,
+    ) -> Result<Option<PublicKey>> {
+        match identity {
+            Some(proof)
+                if !verify_handshake(&proof, network_version, peer_type, public_address) =>
+            {
+                Err(CommsError::PeerIdentityInvalid(PeerInfo {
+                    node_type: Some(peer_type),
+                    address: Some(public_address),
+                }))
+            }
+            identity => Ok(identity.map(|proof| proof.public_key)),
+        }
+    }
+
+    /// Checks a pinned peer presented its pinned certificate.
+    ///
+    /// ## Arguments
+    /// * `peer_addr` - public address of the peer.
+    /// * `peer_type` - type of the peer.
+    /// * `peer_cert` - peer's certificate.
+    fn verify_pinned_certificate(
+        &self,
+        peer_addr: SocketAddr,
+        peer_type: NodeType,
+        peer_cert: &Option<TlsCertificate>,
+    ) -> Result<()> {
+        match self.pinned_peer_certs.get(&peer_addr) {
+            Some(pinned) if peer_cert.as_ref() != Some(pinned) => {
+                Err(CommsError::PeerCertificateMismatch(PeerInfo {
+                    node_type: Some(peer_type),
+                    address: Some(peer_addr),
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Handles a handshake response.
     /// Connects to all nodes that we receive in the contact list.
+    #[allow(clippy::too_many_arguments)]
     async fn handle_handshake_response(
         &mut self,
         peer_addr: SocketAddr,
+        peer_cert: &Option<TlsCertificate>,
         network_version: u32,
+        network_id: NetworkId,
         peer_type: NodeType,
         contacts: Vec<SocketAddr>,
         public_address: SocketAddr,
+        identity: Option<IdentityProof>,
     ) -> Result<()> {
-        if !self.is_compatible(peer_type, network_version) {
+        if !self.is_compatible(peer_type, network_version, network_id) {
             return Err(CommsError::PeerIncompatible(PeerInfo {
                 node_type: Some(peer_type),
                 address: Some(peer_addr),
             }));
         }
 
+        self.verify_pinned_certificate(peer_addr, peer_type, peer_cert)?;
+        let identity =
+            self.verify_peer_identity(identity, network_version, peer_type, public_address)?;
+
         let mut all_peers = self.peers.write().await;
 
         // Notify the peer about the handshake response if someone's waiting for it.
@@ -1057,9 +1454,11 @@ impl Node {
                 }))?;
             peer.network_version = Some(network_version);
             peer.peer_type = Some(peer_type);
+            peer.identity = identity;
 
             if let Some(notify) = peer.notify_handshake_response.0.take() {
-                notify.send(()).unwrap();
+                // The handshake may no longer be awaited
+                let _ = notify.send(());
             }
         }
 
@@ -1107,7 +1506,7 @@ impl Node {
     /// * `event_tx`     - A channel for user-level messages.
     /// * `span`         - The logging scope for this peer.
     /// * `is_initiator` - If `true`, this peer has connected to us. If `false`, then _we_ are
-    ///                    connecting to this peer.
+    ///   connecting to this peer.
     ///
     /// ### Returns
     /// A new `Peer` instance.
@@ -1115,14 +1514,11 @@ impl Node {
         let peer_addr = socket.peer_addr();
         let peer_cert = socket.peer_tls_certificate();
 
-        let (send_tx, mut send_rx) = mpsc::channel(128);
+        let (send_txs, send_rx) = peer_channels();
 
         // Wrap the peer socket into the tokio codec which handles length-delimited frames.
         let (sock_in, sock_out) = tokio::io::split(socket);
-        let codec_builder = *length_delimited::Builder::new().max_frame_length(
-            // max frame length of 100MB
-            100 * 1_024 * 1_024,
-        );
+        let codec_builder = frame_codec_builder();
         let sock_in = FramedRead::new(sock_in, codec_builder.new_codec());
         let mut sock_out = FramedWrite::new(sock_out, codec_builder.new_codec());
 
@@ -1130,13 +1526,7 @@ impl Node {
         // Redirect messages from the mpsc channel into the TCP socket
         let sock_out_h = spawn(
             async move {
-                let send_rx = async_stream::stream! {
-                    while let Some(item) = send_rx.recv().await {
-                        yield item;
-                    }
-                };
-                futures_util::pin_mut!(send_rx);
-
+                let mut send_rx = send_rx;
                 if let Err(error) = sock_out.send_all(&mut send_rx).await {
                     error!(?error, "Error while redirecting messages");
                 }
@@ -1150,7 +1540,7 @@ impl Node {
         let (messages, close_receiver_tx) = get_messages_stream(sock_in);
         let sock_in_h = spawn({
             let mut node = self.clone();
-            let send_tx = send_tx.clone().into();
+            let send_tx = send_txs.get(PeerChannel::Control);
             let peers = self.peers.clone();
             async move {
                 let mut messages = messages;
@@ -1189,6 +1579,10 @@ impl Node {
                     }
                 };
 
+                let hooks = node.event_hooks.lock().unwrap().clone();
+                dispatch_event(&hooks, || NodeEvent::PeerConnected {
+                    address: public_address,
+                });
                 node.handle_peer_recv(public_address, messages).await;
                 // Since we don't wait for any messages from this peer, we can drop the connection.
                 warn!("Remove peer: {}", public_address);
@@ -1202,9 +1596,10 @@ impl Node {
         Peer {
             network_version: None,
             addr: peer_addr,
-            send_tx: send_tx.into(),
+            send_txs,
             peer_type: None,
             public_address: if !is_initiator { Some(peer_addr) } else { None },
+            identity: None,
             notify_handshake_response: if is_initiator {
                 (None, None)
             } else {
@@ -1261,9 +1656,42 @@ impl Node {
     }
 }
 
+/// Create the logical channels of a peer connection.
+///
+/// ### Returns
+/// The senders for each channel, and the stream of frames to write in the order they were queued.
+fn peer_channels() -> (
+    PeerSenders,
+    impl Stream<Item = io::Result<Bytes>> + Send + Unpin,
+) {
+    let (queue, queue_rx) = mpsc::unbounded_channel();
+    let sender = |channel| ChannelSender {
+        channel,
+        queue: queue.clone(),
+        budget: Arc::new(Semaphore::new(PEER_CHANNEL_CAPACITY)),
+    };
+
+    let senders = PeerSenders {
+        control: sender(PeerChannel::Control),
+        gossip: sender(PeerChannel::Gossip),
+        direct: sender(PeerChannel::Direct),
+    };
+    let frames = UnboundedReceiverStream::new(queue_rx).map(|(frame, _permit)| frame);
+    (senders, frames)
+}
+
+/// Codec builder splitting the peer byte streams in length-delimited frames.
+pub fn frame_codec_builder() -> length_delimited::Builder {
+    *length_delimited::Builder::new().max_frame_length(
+        // max frame length of 100MB
+        100 * 1_024 * 1_024,
+    )
+}
+
 /// Transforms a stream of incoming TCP frames into a stream of deserialized messages.
-fn get_messages_stream(
-    sock_in: FramedRead<tokio::io::ReadHalf<TcpTlsStream>, LengthDelimitedCodec>,
+/// Stops at the first frame that cannot be decoded or deserialized.
+pub fn get_messages_stream<R: AsyncRead + Unpin>(
+    sock_in: FramedRead<R, LengthDelimitedCodec>,
 ) -> (impl Stream<Item = CommMessage>, oneshot::Sender<()>) {
     let messages = sock_in
         .map(|frame| {
@@ -1277,7 +1705,7 @@ fn get_messages_stream(
                 }
             };
 
-            match deserialize::<CommMessage>(&frame) {
+            match decode_comm_message(frame.freeze()) {
                 Ok(message) => Some(message),
                 Err(error) => {
                     warn!(?error, "Could not deserialize message; ignoring");
@@ -1296,6 +1724,55 @@ fn get_messages_stream(
     (cancellable_messages, close_tx)
 }
 
+/// bincode variant index of `CommMessage::Gossip`, checked by `comm_message_frame_layout`
+const GOSSIP_VARIANT: u32 = 2;
+
+/// bincode variant index of `CommMessage::Direct`, checked by `comm_message_frame_layout`
+const DIRECT_VARIANT: u32 = 3;
+
+/// Offset of the payload in a `Gossip` or `Direct` frame: u32 variant index, then u64 length
+const PAYLOAD_START: usize = 12;
+
+/// Deserializes a received frame into a `CommMessage`.
+///
+/// The payload of `Gossip` and `Direct` messages is sliced out of the frame
+/// rather than copied, so blocks and transactions forwarded to the node
+/// share the buffer they were received in.
+///
+/// ### Arguments
+///
+/// * `frame` - Frame received from a peer.
+pub fn decode_comm_message(frame: Bytes) -> bincode::Result<CommMessage> {
+    let variant = match frame.get(..4) {
+        Some(tag) => u32::from_le_bytes(tag.try_into().unwrap()),
+        None => return deserialize(&frame),
+    };
+    if variant != GOSSIP_VARIANT && variant != DIRECT_VARIANT {
+        return deserialize(&frame);
+    }
+
+    let payload_len: u64 = deserialize(&frame[4..])?;
+    let payload_start = PAYLOAD_START;
+    let payload_end = usize::try_from(payload_len)
+        .ok()
+        .and_then(|len| len.checked_add(payload_start))
+        .filter(|end| *end <= frame.len())
+        .ok_or_else(|| {
+            let unexpected = io::Error::from(io::ErrorKind::UnexpectedEof);
+            Box::new(bincode::ErrorKind::Io(unexpected))
+        })?;
+    let payload = frame.slice(payload_start..payload_end);
+    let rest = &frame[payload_end..];
+
+    Ok(if variant == GOSSIP_VARIANT {
+        let (ttl, id) = deserialize(rest)?;
+        CommMessage::Gossip { payload, ttl, id }
+    } else {
+        let id = deserialize(rest)?;
+        CommMessage::Direct { payload, id }
+    })
+}
+
 fn take_join_handles<'a>(peers: impl Iterator<Item = &'a mut Peer>) -> Vec<JoinHandle<()>> {
     peers
         .map(|p| &mut p.sock_in_out_join_handles)
@@ -1370,6 +1847,144 @@ mod test {
         complete_mempool_nodes(vec![n1, n2]).await;
     }
 
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a full channel only blocks its own senders, and frames keep their order
+    async fn peer_channels_have_separate_budgets() {
+        //
+        // Arrange
+        //
+        use futures::FutureExt;
+        let (senders, mut frames) = peer_channels();
+        let mut direct = senders.get(PeerChannel::Direct);
+        let mut control = senders.get(PeerChannel::Control);
+        let frame = |i: usize| Ok(Bytes::from(i.to_string()));
+
+        //
+        // Act
+        //
+        for i in 0..PEER_CHANNEL_CAPACITY {
+            direct.send(frame(i), "test").await.unwrap();
+        }
+        let direct_full = direct.send(frame(0), "test").now_or_never().is_none();
+        let control_sent = control.send(frame(1000), "test").now_or_never().is_some();
+
+        let first = frames.next().await.unwrap().unwrap();
+        let direct_freed = direct.send(frame(0), "test").now_or_never().is_some();
+
+        //
+        // Assert
+        //
+        assert!(direct_full);
+        assert!(control_sent);
+        assert_eq!(first, Bytes::from("0"));
+        assert!(direct_freed);
+    }
+
+    #[test]
+    /// Checks payloads are sliced out of the frame, and other messages decode as before
+    fn decode_comm_message_shares_payload() {
+        //
+        // Arrange
+        //
+        let payload = Bytes::from(serialize(&"Hello").unwrap());
+        let messages = [
+            CommMessage::Gossip {
+                payload: payload.clone(),
+                ttl: 3,
+                id: 7,
+            },
+            CommMessage::Direct {
+                payload: payload.clone(),
+                id: 9,
+            },
+            CommMessage::HeartBeatProbe(11),
+        ];
+        let frames: Vec<_> = messages
+            .iter()
+            .map(|m| Bytes::from(serialize(m).unwrap()))
+            .collect();
+        let truncated = frames[1].slice(..frames[1].len() - 10);
+
+        //
+        // Act
+        //
+        let decoded: Vec<_> = frames
+            .iter()
+            .map(|f| decode_comm_message(f.clone()).unwrap())
+            .collect();
+        let truncated = decode_comm_message(truncated);
+
+        //
+        // Assert
+        //
+        let shared = |frame: &Bytes, message: &CommMessage| match message {
+            CommMessage::Gossip { payload, .. } | CommMessage::Direct { payload, .. } => {
+                frame.as_ptr_range().contains(&payload.as_ptr())
+            }
+            _ => false,
+        };
+        assert_eq!(
+            decoded.iter().map(|m| format!("{m:?}")).collect::<Vec<_>>(),
+            messages
+                .iter()
+                .map(|m| format!("{m:?}"))
+                .collect::<Vec<_>>()
+        );
+        assert!(shared(&frames[0], &decoded[0]));
+        assert!(shared(&frames[1], &decoded[1]));
+        assert!(truncated.is_err());
+    }
+
+    /// Variant index of each message, in declaration order.
+    /// Matching every variant fails to compile when one is added, until it is listed here.
+    fn comm_variant_index(message: &CommMessage) -> u32 {
+        match message {
+            CommMessage::HandshakeRequest { .. } => 0,
+            CommMessage::HandshakeResponse { .. } => 1,
+            CommMessage::Gossip { .. } => GOSSIP_VARIANT,
+            CommMessage::Direct { .. } => DIRECT_VARIANT,
+            CommMessage::HeartBeatProbe(_) => 4,
+        }
+    }
+
+    #[test]
+    /// Checks the frame layout the decoder relies on matches the `CommMessage` enum
+    fn comm_message_frame_layout() {
+        //
+        // Arrange
+        //
+        let payload = Bytes::from_static(b"payload");
+        let messages = [
+            CommMessage::Gossip {
+                payload: payload.clone(),
+                ttl: 3,
+                id: 7,
+            },
+            CommMessage::Direct {
+                payload: payload.clone(),
+                id: 9,
+            },
+            CommMessage::HeartBeatProbe(11),
+        ];
+
+        //
+        // Act
+        //
+        let frames: Vec<_> = messages.iter().map(|m| serialize(m).unwrap()).collect();
+
+        //
+        // Assert
+        //
+        for (message, frame) in messages.iter().zip(&frames) {
+            let tag = u32::from_le_bytes(frame[..4].try_into().unwrap());
+            assert_eq!(tag, comm_variant_index(message), "{message:?}");
+        }
+        for frame in &frames[..2] {
+            let payload_end = PAYLOAD_START + payload.len();
+            assert_eq!(&frame[PAYLOAD_START..payload_end], &payload[..]);
+        }
+    }
+
     async fn create_mempool_node_version(peer_limit: usize, network_version: u32) -> Node {
         let tcp_tls_config = get_common_tls_config();
         Node::new_with_version(
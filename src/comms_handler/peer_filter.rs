@@ -0,0 +1,207 @@
+//! Allow and deny lists of peer IP ranges.
+//!
+//! A peer is accepted, or connected to, only if its IP address is in none of the
+//! denied ranges, and in one of the allowed ranges when any is configured. The
+//! lists can be changed at runtime, and are then persisted to a JSON file which
+//! takes precedence over the configured lists on the next start.
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Range of IP addresses in CIDR notation, e.g. `10.0.0.0/8`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// Whether the range contains the address
+    ///
+    /// IPv4-mapped IPv6 addresses are treated as their IPv4 address.
+    ///
+    /// ### Arguments
+    ///
+    /// * `ip` - Address to check.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical_ip(ip)) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32);
+                let mask = mask.unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32);
+                let mask = mask.unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid CIDR range: {s}");
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (s, None),
+        };
+        let addr = canonical_ip(addr.trim().parse().map_err(|_| invalid())?);
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len.trim().parse().map_err(|_| invalid())?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl TryFrom<String> for IpCidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<IpCidr> for String {
+    fn from(cidr: IpCidr) -> Self {
+        cidr.to_string()
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Allow and deny lists of peer IP ranges
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerIpFilter {
+    /// Ranges peers must be in, any address if empty
+    pub allow: Vec<IpCidr>,
+    /// Ranges peers must not be in
+    pub deny: Vec<IpCidr>,
+}
+
+impl PeerIpFilter {
+    /// Filter for lists of ranges in CIDR notation
+    ///
+    /// ### Arguments
+    ///
+    /// * `allow` - Ranges peers must be in, any address if empty.
+    /// * `deny`  - Ranges peers must not be in.
+    pub fn from_strs(allow: &[String], deny: &[String]) -> Result<Self, String> {
+        let parse = |l: &[String]| l.iter().map(|c| c.parse()).collect::<Result<_, _>>();
+        Ok(Self {
+            allow: parse(allow)?,
+            deny: parse(deny)?,
+        })
+    }
+
+    /// Whether a peer at this address can be accepted or connected to
+    ///
+    /// ### Arguments
+    ///
+    /// * `ip` - Address of the peer.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|c| c.contains(ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|c| c.contains(ip)))
+    }
+
+    /// Load the lists persisted to a file, if it exists
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - JSON file holding the lists.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Persist the lists to a file
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - JSON file to hold the lists.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+/// Address with IPv4-mapped IPv6 addresses converted to IPv4
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    /// Checks CIDR ranges are parsed, and match the addresses of their prefix only
+    fn ip_cidr_contains() {
+        let v4: IpCidr = "10.1.0.0/16".parse().unwrap();
+        let single: IpCidr = "192.168.0.7".parse().unwrap();
+        let v6: IpCidr = "fd00::/8".parse().unwrap();
+        let any: IpCidr = "0.0.0.0/0".parse().unwrap();
+
+        assert!(v4.contains(ip("10.1.200.3")));
+        assert!(v4.contains(ip("::ffff:10.1.0.1")));
+        assert!(!v4.contains(ip("10.2.0.1")));
+        assert!(single.contains(ip("192.168.0.7")));
+        assert!(!single.contains(ip("192.168.0.8")));
+        assert!(v6.contains(ip("fd12::1")));
+        assert!(!v6.contains(ip("10.1.0.1")));
+        assert!(any.contains(ip("8.8.8.8")));
+        assert_eq!(single.to_string(), "192.168.0.7/32");
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("10.0.0/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    /// Checks denied ranges take precedence, and an empty allow list allows any address
+    fn peer_ip_filter_allowed() {
+        let to_strings = |l: &[&str]| l.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let open = PeerIpFilter::from_strs(&[], &to_strings(&["10.0.0.66"])).unwrap();
+        let locked = PeerIpFilter::from_strs(
+            &to_strings(&["10.0.0.0/24", "172.16.0.0/12"]),
+            &to_strings(&["10.0.0.66"]),
+        )
+        .unwrap();
+
+        assert!(open.is_allowed(ip("8.8.8.8")));
+        assert!(!open.is_allowed(ip("10.0.0.66")));
+        assert!(locked.is_allowed(ip("10.0.0.1")));
+        assert!(locked.is_allowed(ip("172.20.1.1")));
+        assert!(!locked.is_allowed(ip("10.0.0.66")));
+        assert!(!locked.is_allowed(ip("8.8.8.8")));
+        assert!(PeerIpFilter::from_strs(&to_strings(&["nope"]), &[]).is_err());
+    }
+}
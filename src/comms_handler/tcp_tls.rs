@@ -1,13 +1,15 @@
 //! Utilities necessary to establish a TCP connection with TLS overay stream.
 
-use super::{CommsError, Result};
-use crate::configurations::{TlsPrivateInfo, TlsSpec};
+use super::node::DEFAULT_EVENT_QUEUE_CAPACITY;
+use super::{CommsError, PeerIpFilter, Result};
+use crate::configurations::{PeerIpFilterSpec, TlsPrivateInfo, TlsSpec};
 use rustls_pemfile::{certs, pkcs8_private_keys};
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
 use std::io::Cursor;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -15,6 +17,9 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Mutex;
 use tokio_rustls::rustls::client::ServerName;
+use tokio_rustls::rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient,
+};
 use tokio_rustls::rustls::{
     Certificate, ClientConfig, CommonState, PrivateKey, RootCertStore, ServerConfig,
 };
@@ -33,6 +38,10 @@ pub struct TcpTlsConfig {
     pem_certs: String,
     pem_pkcs8_private_keys: String,
     trusted_pem_certs: Vec<String>,
+    pinned_peer_certs: BTreeMap<SocketAddr, TlsCertificate>,
+    peer_ip_filter: PeerIpFilter,
+    peer_ip_filter_path: Option<PathBuf>,
+    event_queue_capacity: usize,
     use_tls: bool,
     listener: Arc<Mutex<Option<TcpListener>>>,
 }
@@ -45,6 +54,10 @@ impl TcpTlsConfig {
             pem_certs: Default::default(),
             pem_pkcs8_private_keys: Default::default(),
             trusted_pem_certs: Default::default(),
+            pinned_peer_certs: Default::default(),
+            peer_ip_filter: Default::default(),
+            peer_ip_filter_path: None,
+            event_queue_capacity: DEFAULT_EVENT_QUEUE_CAPACITY,
             use_tls: false,
             listener: Default::default(),
         }
@@ -94,12 +107,91 @@ impl TcpTlsConfig {
                     .ok_or(CommsError::ConfigError("Missing TLS node keys"))?
                     .clone(),
                 trusted_pem_certs,
+                pinned_peer_certs: Default::default(),
+                peer_ip_filter: Default::default(),
+                peer_ip_filter_path: None,
+                event_queue_capacity: DEFAULT_EVENT_QUEUE_CAPACITY,
                 use_tls: true,
                 listener: Default::default(),
             })
         }
     }
 
+    /// Pin the peers to their configured certificate
+    ///
+    /// Pinned peers must present exactly their certificate, also when they connect
+    /// to this node, so their certificate must allow client authentication.
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - TLS configuration holding the peers certificate.
+    /// * `peers`  - Addresses of the peers to pin.
+    pub fn pin_peer_certificates(&mut self, config: &TlsSpec, peers: &[SocketAddr]) -> Result<()> {
+        if !self.use_tls {
+            return Err(CommsError::ConfigError("Peer pinning requires TLS"));
+        }
+        for peer in peers {
+            let name = socket_name_mapping_or_default(&config.socket_name_mapping, *peer);
+            let pem = config
+                .pem_certificates
+                .get(&name)
+                .ok_or(CommsError::ConfigError(
+                    "Missing TLS pinned peer certificate",
+                ))?;
+            let cert = load_certs(pem)
+                .into_iter()
+                .next()
+                .ok_or(CommsError::ConfigError(
+                    "Invalid TLS pinned peer certificate",
+                ))?;
+            self.pinned_peer_certs.insert(*peer, cert);
+        }
+        Ok(())
+    }
+
+    pub fn pinned_peer_certs(&self) -> &BTreeMap<SocketAddr, TlsCertificate> {
+        &self.pinned_peer_certs
+    }
+
+    /// Set the IP ranges of the peers to accept and connect to
+    ///
+    /// Lists persisted to the configured file take precedence over the configured ones.
+    ///
+    /// ### Arguments
+    ///
+    /// * `spec` - Allow and deny lists in CIDR notation.
+    pub fn set_peer_ip_filter(&mut self, spec: &PeerIpFilterSpec) -> Result<()> {
+        let path = spec.persist_path.as_ref().map(PathBuf::from);
+        let persisted = match &path {
+            Some(path) => PeerIpFilter::load(path)?,
+            None => None,
+        };
+        self.peer_ip_filter = match persisted {
+            Some(filter) => filter,
+            None => PeerIpFilter::from_strs(&spec.allow, &spec.deny)
+                .map_err(|_| CommsError::ConfigError("Invalid peer IP filter"))?,
+        };
+        self.peer_ip_filter_path = path;
+        Ok(())
+    }
+
+    pub fn peer_ip_filter(&self) -> (&PeerIpFilter, Option<&PathBuf>) {
+        (&self.peer_ip_filter, self.peer_ip_filter_path.as_ref())
+    }
+
+    /// Set the number of received events waiting for the event loop
+    ///
+    /// ### Arguments
+    ///
+    /// * `capacity` - Events that can wait before reading from peers stops.
+    pub fn set_event_queue_capacity(&mut self, capacity: usize) {
+        self.event_queue_capacity = capacity.max(1);
+    }
+
+    pub fn event_queue_capacity(&self) -> usize {
+        self.event_queue_capacity
+    }
+
     pub fn mut_socket_name_mapping(&mut self) -> &mut BTreeMap<SocketAddr, String> {
         &mut self.socket_name_mapping
     }
@@ -121,6 +213,7 @@ impl TcpTlsConfig {
         TlsPrivateInfo {
             pem_certs: self.pem_certs.clone(),
             pem_pkcs8_private_keys: self.pem_pkcs8_private_keys.clone(),
+            client_auth: None,
         }
     }
 }
@@ -290,14 +383,56 @@ fn new_server_config(config: &TcpTlsConfig) -> Result<ServerConfig> {
     let mut keys = load_keys(&config.pem_pkcs8_private_keys);
 
     let server_config = ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_safe_default_protocol_versions()
+        .unwrap();
+
+    // Pinned peers authenticate as clients, other clients can stay anonymous
+    let server_config = if config.pinned_peer_certs.is_empty() {
+        server_config.with_no_client_auth()
+    } else {
+        let mut root_store = new_root_certs(&config.trusted_pem_certs)?;
+        let pinned_certs: Vec<_> = config.pinned_peer_certs.values().cloned().collect();
+        add_cert_to_root(&mut root_store, &pinned_certs)?;
+        server_config
+            .with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(root_store))
+    };
+    let server_config = server_config.with_single_cert(certs, keys.remove(0))?;
+
+    Ok(server_config)
+}
+
+/// Acceptor of API connections, requiring client certificates signed by the given CAs
+///
+/// ### Arguments
+///
+/// * `info`         - Certificate and private key of the API server.
+/// * `pem_ca_certs` - PEM certificates of the CAs client certificates must be signed by.
+pub fn new_api_client_auth_acceptor(
+    info: &TlsPrivateInfo,
+    pem_ca_certs: &str,
+) -> Result<TlsAcceptor> {
+    let root_store = new_root_certs(&[pem_ca_certs.to_owned()])?;
+    if root_store.is_empty() {
+        return Err(CommsError::ConfigError("No API client CA certificate"));
+    }
+    let certs = load_certs(&info.pem_certs);
+    let mut keys = load_keys(&info.pem_pkcs8_private_keys);
+    if keys.is_empty() {
+        return Err(CommsError::ConfigError("No API private key"));
+    }
+
+    let mut server_config = ServerConfig::builder()
         .with_safe_default_cipher_suites()
         .with_safe_default_kx_groups()
         .with_safe_default_protocol_versions()
         .unwrap()
-        .with_no_client_auth()
+        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(root_store))
         .with_single_cert(certs, keys.remove(0))?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
 
-    Ok(server_config)
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
 }
 
 fn new_client_config(config: &TcpTlsConfig) -> Result<ClientConfig> {
@@ -396,7 +531,7 @@ pub fn verify_is_valid_for_dns_names<'a>(
         tls_names.map(DnsNameRef::try_from_ascii_str).collect();
     let domains = domains.map_err(|_| CommsError::ConfigError("invalid dnsname"))?;
 
-    let cert = EndEntityCert::try_from(cert.0.as_slice()).unwrap();
+    let cert = EndEntityCert::try_from(cert.0.as_slice())?;
     cert.verify_is_valid_for_at_least_one_dns_name(domains.iter().copied())?;
     Ok(())
 }
@@ -1,5 +1,5 @@
-/// !!! AUTOGENERATED: DO NOT EDIT !!!
-/// Generated with: `src/bin/node_settings_gen_key_certs.sh`
+// !!! AUTOGENERATED: DO NOT EDIT !!!
+// Generated with: `src/bin/node_settings_gen_key_certs.sh`
 
 /// PEM certificates for node DNS names
 pub const TEST_PEM_CERTIFICATES: &[(&str, &str)] = &[
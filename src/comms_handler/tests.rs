@@ -1,8 +1,9 @@
 //! Tests for peer-to-peer communication.
 
-use super::{CommsError, Event, Node, TcpTlsConfig};
-use crate::constants::NETWORK_VERSION;
-use crate::interfaces::NodeType;
+use super::network_sim::{LinkConditions, NetworkSim};
+use super::{read_recording, CommsError, Event, MessageRecorder, Node, TcpTlsConfig};
+use crate::constants::PEER_NETWORK_VERSION;
+use crate::interfaces::{NetworkId, NodeType};
 use crate::test_utils::{get_bound_common_tls_configs, get_common_tls_config, get_test_tls_spec};
 use crate::utils::tracing_log_try_init;
 use bincode::deserialize;
@@ -48,8 +49,8 @@ async fn direct_prelaunch_messages() {
     let _ = tracing_log_try_init();
 
     let mut nodes = create_mempool_nodes(0, 2).await;
-    nodes.push(create_node_type_version(2, NodeType::PreLaunch, NETWORK_VERSION).await);
-    nodes.push(create_node_type_version(2, NodeType::PreLaunch, NETWORK_VERSION).await);
+    nodes.push(create_node_type_version(2, NodeType::PreLaunch, PEER_NETWORK_VERSION).await);
+    nodes.push(create_node_type_version(2, NodeType::PreLaunch, PEER_NETWORK_VERSION).await);
     let (n1, tail) = nodes.split_first_mut().unwrap();
     let (n2, _) = tail.split_first_mut().unwrap();
 
@@ -161,7 +162,7 @@ async fn disconnect_connection(subset: bool) {
         };
         joins.append(&mut n3.take_join_handle(n1.local_address()).await);
         joins.append(&mut n1.disconnect_all(subset.as_deref()).await);
-        join_all(joins.into_iter()).await;
+        join_all(joins).await;
 
         let actual2_1 = n2.send(n1.local_address(), "Hello1_2").await;
         let actual1_2 = n1.send(n2.local_address(), "Hello2_1").await;
@@ -322,8 +323,8 @@ async fn nodes_incompatible() {
     // Arrange
     //
     let mut nodes = create_mempool_nodes(1, 4).await;
-    nodes.push(create_mempool_node_version(4, NETWORK_VERSION + 1).await);
-    nodes.push(create_node_type_version(4, NodeType::PreLaunch, NETWORK_VERSION).await);
+    nodes.push(create_mempool_node_version(4, PEER_NETWORK_VERSION + 1).await);
+    nodes.push(create_node_type_version(4, NodeType::PreLaunch, PEER_NETWORK_VERSION).await);
     let (n1, tail) = nodes.split_first_mut().unwrap();
     let (n2, tail) = tail.split_first_mut().unwrap();
     let (n3, _) = tail.split_first_mut().unwrap();
@@ -372,6 +373,52 @@ async fn nodes_incompatible() {
     complete_mempool_nodes(nodes).await;
 }
 
+/// Check nodes of different networks cannot establish connections.
+#[tokio::test(flavor = "current_thread")]
+async fn nodes_network_id_mismatch() {
+    let _ = tracing_log_try_init();
+
+    //
+    // Arrange
+    //
+    let mut nodes = create_mempool_nodes(3, 4).await;
+    nodes[1].set_network_id(NetworkId::Testnet);
+    nodes[2].set_network_id(NetworkId::Testnet);
+    let (n1, tail) = nodes.split_first_mut().unwrap();
+    let (n2, tail) = tail.split_first_mut().unwrap();
+    let (n3, _) = tail.split_first_mut().unwrap();
+
+    //
+    // Act
+    //
+    let actual_c1_2 = n1.connect_to(n2.local_address()).await;
+    let actual_c2_1 = n2.connect_to(n1.local_address()).await;
+    let actual_s1_2 = n1.send(n2.local_address(), "Hello2").await;
+    let actual_c2_3 = n2.connect_to(n3.local_address()).await;
+    let actual_s2_3 = n2.send(n3.local_address(), "Hello3").await;
+
+    //
+    // Assert
+    //
+    let actual = (actual_c1_2, actual_c2_1, actual_s1_2);
+    assert!(
+        matches!(
+            actual,
+            (
+                Err(CommsError::PeerNotFound(_)),
+                Err(CommsError::PeerNotFound(_)),
+                Err(CommsError::PeerNotFound(_))
+            )
+        ),
+        "{:?}",
+        actual
+    );
+    assert!(actual_c2_3.is_ok(), "{:?}", actual_c2_3);
+    assert!(actual_s2_3.is_ok(), "{:?}", actual_s2_3);
+
+    complete_mempool_nodes(nodes).await;
+}
+
 /// Check nodes who cannot establish connections because of unexpected certificates.
 #[tokio::test(flavor = "current_thread")]
 async fn nodes_tls_mismatch() {
@@ -575,6 +622,191 @@ async fn nodes_tls_ca_unmapped_mismatch() {
     complete_mempool_nodes(nodes).await;
 }
 
+/// Check raft peers are only pinned to their configured certificate, and only with TLS.
+#[tokio::test(flavor = "current_thread")]
+async fn pin_peer_certificates_config() {
+    //
+    // Arrange
+    //
+    let address = "127.0.0.1:12540".parse::<SocketAddr>().unwrap();
+    let unknown = "127.0.0.1:12541".parse::<SocketAddr>().unwrap();
+    let mut mapping = BTreeMap::new();
+    mapping.insert(address, "mempool1.aiblock.ch".to_owned());
+    let tls_spec = get_test_tls_spec().make_tls_spec(&mapping);
+    let mut config = TcpTlsConfig::from_tls_spec(address, &tls_spec).unwrap();
+    let mut no_tls_config = TcpTlsConfig::new_no_tls(address);
+
+    //
+    // Act
+    //
+    let actual_pinned = config.pin_peer_certificates(&tls_spec, &[address]);
+    let actual_unknown = config.pin_peer_certificates(&tls_spec, &[unknown]);
+    let actual_no_tls = no_tls_config.pin_peer_certificates(&tls_spec, &[address]);
+
+    //
+    // Assert
+    //
+    assert!(actual_pinned.is_ok());
+    assert!(matches!(actual_unknown, Err(CommsError::ConfigError(_))));
+    assert!(matches!(actual_no_tls, Err(CommsError::ConfigError(_))));
+    assert_eq!(
+        config.pinned_peer_certs().keys().collect::<Vec<_>>(),
+        vec![&address]
+    );
+}
+
+/// Check that frames are delayed by the simulated latency of the link,
+/// and only in the configured direction.
+#[tokio::test(flavor = "current_thread")]
+async fn simulated_latency_delays_frames() {
+    let _ = tracing_log_try_init();
+
+    let mut nodes = create_mempool_nodes(2, 2).await;
+    let sim = NetworkSim::new(0);
+    for node in &nodes {
+        node.set_network_sim(Some(sim.clone())).await;
+    }
+    let (n1, tail) = nodes.split_first_mut().unwrap();
+    let (n2, _) = tail.split_first_mut().unwrap();
+    let latency = Duration::from_millis(300);
+    let conditions = LinkConditions {
+        latency,
+        ..Default::default()
+    };
+
+    n2.connect_to(n1.local_address()).await.unwrap();
+    sim.set_link_conditions(n2.local_address(), n1.local_address(), conditions);
+
+    let start = time::Instant::now();
+    n2.send(n1.local_address(), "Hello1").await.unwrap();
+    n1.send(n2.local_address(), "Hello2").await.unwrap();
+
+    let n2_frame = next_frame_str(n2).await;
+    let n2_elapsed = start.elapsed();
+    let n1_frame = next_frame_str(n1).await;
+    let n1_elapsed = start.elapsed();
+
+    assert_eq!((n1_frame.as_str(), n2_frame.as_str()), ("Hello1", "Hello2"));
+    assert!(n2_elapsed < latency, "{:?}", n2_elapsed);
+    assert!(n1_elapsed >= latency, "{:?}", n1_elapsed);
+    assert_eq!(sim.stats().delayed, 1);
+
+    complete_mempool_nodes(nodes).await;
+}
+
+/// Check that frames between partitioned nodes are dropped,
+/// and delivered again once the partition is healed.
+#[tokio::test(flavor = "current_thread")]
+async fn simulated_partition_drops_frames() {
+    let _ = tracing_log_try_init();
+
+    let mut nodes = create_mempool_nodes(2, 2).await;
+    let sim = NetworkSim::new(0);
+    for node in &nodes {
+        node.set_network_sim(Some(sim.clone())).await;
+    }
+    let (n1, tail) = nodes.split_first_mut().unwrap();
+    let (n2, _) = tail.split_first_mut().unwrap();
+
+    n2.connect_to(n1.local_address()).await.unwrap();
+    sim.partition(&[n1.local_address()], &[n2.local_address()]);
+    n2.send(n1.local_address(), "Dropped1").await.unwrap();
+    n1.send(n2.local_address(), "Dropped2").await.unwrap();
+
+    let partitioned = time::timeout(Duration::from_millis(300), n1.next_event()).await;
+
+    sim.heal_partitions();
+    n2.send(n1.local_address(), "Hello1").await.unwrap();
+    n1.send(n2.local_address(), "Hello2").await.unwrap();
+
+    assert!(partitioned.is_err(), "{:?}", partitioned);
+    assert_eq!(next_frame_str(n1).await, "Hello1");
+    assert_eq!(next_frame_str(n2).await, "Hello2");
+    assert_eq!(sim.stats().dropped, 2);
+
+    complete_mempool_nodes(nodes).await;
+}
+
+/// Check that the frames received by a node are recorded with their sender,
+/// and that replaying the recording feeds the same frames into another node.
+#[tokio::test(flavor = "current_thread")]
+async fn record_and_replay_frames() {
+    let _ = tracing_log_try_init();
+
+    let mut nodes = create_mempool_nodes(3, 2).await;
+    let (n1, tail) = nodes.split_first_mut().unwrap();
+    let (n2, tail) = tail.split_first_mut().unwrap();
+    let (n3, _) = tail.split_first_mut().unwrap();
+    let path = std::env::temp_dir().join(format!("record_{}.bin", n1.local_address().port()));
+    let _ = std::fs::remove_file(&path);
+
+    n1.set_message_recorder(Some(MessageRecorder::create(&path).unwrap()));
+    n2.connect_to(n1.local_address()).await.unwrap();
+    n2.send(n1.local_address(), "Hello1").await.unwrap();
+    n2.send(n1.local_address(), "Hello2").await.unwrap();
+    let received = [next_frame_str(n1).await, next_frame_str(n1).await];
+    n1.set_message_recorder(None);
+
+    let recording = read_recording(&path).unwrap();
+    n3.replay_recording(recording.clone()).await.unwrap();
+    let replayed = [next_frame_str(n3).await, next_frame_str(n3).await];
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(received, ["Hello1", "Hello2"]);
+    assert_eq!(replayed, received);
+    assert_eq!(recording.len(), 2);
+    assert!(recording.iter().all(|f| f.peer == recording[0].peer));
+    assert!(recording[0].timestamp <= recording[1].timestamp);
+
+    complete_mempool_nodes(nodes).await;
+}
+
+/// Check that a full event queue holds back the frames of peers instead of growing,
+/// and that they are delivered in order once the event loop catches up.
+#[tokio::test(flavor = "current_thread")]
+async fn full_event_queue_applies_backpressure() {
+    let _ = tracing_log_try_init();
+
+    let mut config = get_common_tls_config();
+    config.set_event_queue_capacity(2);
+    let mut nodes = create_config_mempool_nodes(vec![config, get_common_tls_config()], 2).await;
+    let (n1, tail) = nodes.split_first_mut().unwrap();
+    let (n2, _) = tail.split_first_mut().unwrap();
+
+    n2.connect_to(n1.local_address()).await.unwrap();
+    let sent: Vec<String> = (0..5).map(|i| format!("Hello{i}")).collect();
+    for frame in &sent {
+        n2.send(n1.local_address(), frame).await.unwrap();
+    }
+    let full = time::timeout(TIMEOUT_TEST_WAIT_DURATION, async {
+        while n1.event_queue_depth().depth < 2 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await;
+    let depth_full = n1.event_queue_depth();
+    let injected = n1.inject_next_event(n2.local_address(), "Injected");
+    let mut received = Vec::new();
+    for _ in 0..sent.len() {
+        received.push(next_frame_str(n1).await);
+    }
+
+    assert!(full.is_ok());
+    assert_eq!((depth_full.depth, depth_full.capacity), (2, 2));
+    assert!(matches!(injected, Err(CommsError::EventQueueFull)));
+    assert_eq!(received, sent);
+    assert_eq!(n1.event_queue_depth().depth, 0);
+
+    complete_mempool_nodes(nodes).await;
+}
+
+async fn next_frame_str(node: &mut Node) -> String {
+    match time::timeout(TIMEOUT_TEST_WAIT_DURATION, node.next_event()).await {
+        Ok(Some(Event::NewFrame { peer: _, frame })) => deserialize(&frame).unwrap(),
+        other => panic!("No frame received: {:?}", other),
+    }
+}
+
 async fn create_mempool_nodes(num_nodes: usize, peer_limit: usize) -> Vec<Node> {
     let configs = std::iter::repeat_with(get_common_tls_config)
         .take(num_nodes)
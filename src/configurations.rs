@@ -1,16 +1,53 @@
 // use crate::comms_handler::Node;
+use crate::api::client_auth::ApiClientAuth;
+use crate::clock::Clock;
 use crate::db_utils::{CustomDbSpec, SimpleDb};
-use crate::interfaces::InitialIssuance;
+use crate::event_hooks::EventHooks;
+use crate::interfaces::{InitialIssuance, NetworkId, Response};
 use crate::mempool_raft::MinerWhitelist;
-use crate::wallet::WalletDb;
+use crate::reward_schedule::RewardSchedule;
+use crate::wallet::{Argon2Params, KeyGen, WalletDb};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
 use tw_chain::primitives::asset::TokenAmount;
+use wallet_core::CoinSelection;
 
 pub type UtxoSetSpec = BTreeMap<String, Vec<TxOutSpec>>;
 
+/// Callback invoked with every response handled by a node
+#[derive(Clone)]
+pub struct ResponseHook(Arc<dyn Fn(&Response) + Send + Sync>);
+
+impl ResponseHook {
+    /// Wrap the given callback
+    ///
+    /// ### Arguments
+    ///
+    /// * `hook` - Callback invoked with each handled response
+    pub fn new(hook: impl Fn(&Response) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Invoke the callback
+    ///
+    /// ### Arguments
+    ///
+    /// * `response` - Response handled by the node
+    pub fn call(&self, response: &Response) {
+        (self.0)(response)
+    }
+}
+
+impl fmt::Debug for ResponseHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResponseHook")
+    }
+}
+
 /// Configuration info for TLS
 #[derive(Default, Clone, Deserialize)]
 pub struct TlsSpec {
@@ -26,12 +63,17 @@ pub struct TlsSpec {
     pub pem_certificate_override: Option<String>,
     /// Private key override to use for this node ignoring pem_pkcs8_private_keys
     pub pem_pkcs8_private_key_override: Option<String>,
+    /// Require raft peers to present their certificate from pem_certificates
+    #[serde(default)]
+    pub pin_raft_peer_certificates: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct TlsPrivateInfo {
     pub pem_certs: String,
     pub pem_pkcs8_private_keys: String,
+    /// Client certificate authentication of the API, clients are anonymous if not set
+    pub client_auth: Option<ApiClientAuth>,
 }
 
 impl fmt::Debug for TlsSpec {
@@ -46,6 +88,39 @@ impl fmt::Debug for TlsSpec {
     }
 }
 
+/// Configuration info for the IP ranges of accepted peers
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PeerIpFilterSpec {
+    /// CIDR ranges peers must be in, any address if empty
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// CIDR ranges peers must not be in
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// JSON file persisting the lists updated through the API
+    pub persist_path: Option<String>,
+}
+
+/// Configuration info for a block hash a chain must have at a height
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckpointSpec {
+    /// Number of the block
+    pub height: u64,
+    /// Hash of the block
+    pub block_hash: String,
+}
+
+/// Configuration info for client certificate authentication on the API
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiClientAuthSpec {
+    /// PEM certificates of the CAs client certificates must be signed by
+    pub pem_ca_certificates: String,
+    /// API role (read_only, operator or wallet_spend) of the clients whose certificate
+    /// is valid for a DNS name, other clients need API keys
+    #[serde(default)]
+    pub identity_roles: BTreeMap<String, String>,
+}
+
 /// Configuration info for unicorn
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct UnicornFixedInfo {
@@ -124,14 +199,37 @@ pub struct MempoolNodeConfig {
     pub mempool_api_port: u16,
     /// API use TLS
     pub mempool_api_use_tls: bool,
+    /// Require API clients to authenticate with a certificate, needs API TLS
+    pub mempool_api_client_auth: Option<ApiClientAuthSpec>,
     /// Timeout for ticking raft
     pub mempool_raft_tick_timeout: usize,
+    /// Number of last mempool_nodes mirroring the raft log as non-voting standbys, none if not set
+    pub mempool_raft_standby: Option<usize>,
+    /// Milliseconds without hearing from a voting mempool node before the raft leader
+    /// promotes a standby in its place, only on request if not set
+    pub mempool_raft_failover_timeout: Option<usize>,
     /// Timeout duration between mining event pipelines
     pub mempool_mining_event_timeout: usize,
     /// Timeout duration between committing transactions
     pub mempool_transaction_timeout: usize,
+    /// Number of local transaction changes buffered before a db write, 512 if not set
+    pub mempool_local_tx_flush_len: Option<usize>,
+    /// Time in milliseconds local transaction changes stay buffered, 50 if not set
+    pub mempool_local_tx_flush_timeout: Option<u64>,
+    /// Bytes of data a transaction output can carry, 1024 if not set
+    pub max_output_data_size: Option<usize>,
+    /// Bytes of data all outputs of a transaction can carry, 4096 if not set
+    pub max_tx_data_size: Option<usize>,
+    /// Reward of each block, decreasing with the tokens issued if not set
+    pub mempool_reward_schedule: Option<RewardSchedule>,
+    /// Seconds between compactions of the mempool database, never if not set
+    pub db_compaction_interval: Option<u64>,
     /// Transaction hash and TxOut info to use to seed utxo
     pub mempool_seed_utxo: UtxoSetSpec,
+    /// JSON file with the seed utxo, used instead of `mempool_seed_utxo` if set
+    pub mempool_seed_utxo_file: Option<String>,
+    /// Total of the seed utxo amounts in units, not checked if not set
+    pub mempool_genesis_allocation: Option<u64>,
     /// String to use for genesis block TxIn
     pub mempool_genesis_tx_in: Option<String>,
     /// Partition full size
@@ -150,14 +248,32 @@ pub struct MempoolNodeConfig {
     pub utxo_re_align_block_modulo: Option<u64>,
     /// Restore backup if true
     pub backup_restore: Option<bool>,
+    /// File holding the Ed25519 identity key signing the db backups, unsigned if not set
+    pub backup_signing_key: Option<String>,
+    /// File holding the node identity key, created next to the node db if not set
+    pub identity_file: Option<String>,
     /// Enable trigger messages to reset the pipeline when it gets stuck
     pub enable_trigger_messages_pipeline_reset: Option<bool>,
     /// Enable API-key based whitelisting for miners
     pub mempool_miner_whitelist: MinerWhitelist,
     /// Limit for the number of peers this node can have
     pub peer_limit: usize,
+    /// Network of the node, mainnet if not set
+    pub network_id: Option<NetworkId>,
+    /// IP ranges of the peers to accept and connect to, any if not set
+    pub peer_ip_filter: Option<PeerIpFilterSpec>,
+    /// Number of received events waiting for the event loop before reading from peers stops
+    pub event_queue_capacity: Option<usize>,
+    /// Number of local events waiting for the event loop, 10 if not set
+    pub local_event_capacity: Option<usize>,
+    /// Milliseconds to wait for peers to connect before starting, unlimited if not set
+    pub peer_wait_timeout: Option<u64>,
     /// Initial issuances
     pub initial_issuances: Vec<InitialIssuance>,
+    /// Message bus to publish accepted transactions to, disabled if not set
+    pub mempool_event_bus: Option<EventBusConfig>,
+    /// Screening of the addresses touched by received transactions, disabled if not set
+    pub mempool_screening: Option<ScreeningConfig>,
 }
 
 /// Configuration option for a mempool node that can be shared across peers
@@ -192,6 +308,8 @@ pub struct StorageNodeConfig {
     pub storage_api_port: u16,
     /// API use TLS
     pub storage_api_use_tls: bool,
+    /// Require API clients to authenticate with a certificate, needs API TLS
+    pub storage_api_client_auth: Option<ApiClientAuthSpec>,
     /// Timeout for ticking raft
     pub storage_raft_tick_timeout: usize,
     /// Timeout for fetch catchup
@@ -202,8 +320,32 @@ pub struct StorageNodeConfig {
     pub backup_block_modulo: Option<u64>,
     /// Restore backup if true
     pub backup_restore: Option<bool>,
+    /// File holding the Ed25519 identity key signing the db backups, unsigned if not set
+    pub backup_signing_key: Option<String>,
+    /// File holding the node identity key, created next to the node db if not set
+    pub identity_file: Option<String>,
+    /// Threads of the storage database pool, 4 if not set
+    pub db_threads: Option<usize>,
+    /// Seconds between compactions of the storage database, never if not set
+    pub db_compaction_interval: Option<u64>,
     /// Limit for the number of peers this node can have
     pub peer_limit: usize,
+    /// Network of the node, mainnet if not set
+    pub network_id: Option<NetworkId>,
+    /// IP ranges of the peers to accept and connect to, any if not set
+    pub peer_ip_filter: Option<PeerIpFilterSpec>,
+    /// Number of received events waiting for the event loop before reading from peers stops
+    pub event_queue_capacity: Option<usize>,
+    /// Number of local events waiting for the event loop, 10 if not set
+    pub local_event_capacity: Option<usize>,
+    /// Milliseconds to wait for peers to connect before starting, unlimited if not set
+    pub peer_wait_timeout: Option<u64>,
+    /// Message bus to publish stored blocks to, disabled if not set
+    pub storage_event_bus: Option<EventBusConfig>,
+    /// Block hashes the stored chain must have, along with the built in ones
+    pub storage_checkpoints: Option<Vec<CheckpointSpec>>,
+    /// Seconds a block timestamp may be ahead of the node clock, 120 if not set
+    pub storage_max_block_time_drift: Option<i64>,
 }
 
 /// Configuration option for a storage node
@@ -233,14 +375,34 @@ pub struct MinerNodeConfig {
     pub backup_block_modulo: Option<u64>,
     /// Restore backup if true
     pub backup_restore: Option<bool>,
+    /// File holding the Ed25519 identity key signing the db backups, unsigned if not set
+    pub backup_signing_key: Option<String>,
+    /// File holding the node identity key, created next to the node db if not set
+    pub identity_file: Option<String>,
+    /// Threads of the wallet database pool, 4 if not set
+    pub db_threads: Option<usize>,
+    /// Seconds between compactions of the wallet database, never if not set
+    pub db_compaction_interval: Option<u64>,
     /// When provided, all new coinbase transactions will be assigned to this address
     pub static_miner_address: Option<String>,
     /// When provided, the miner will use this API key to participate in mining
     pub mining_api_key: Option<String>,
     /// Limit for the number of peers this node can have
     pub peer_limit: usize,
+    /// Network of the node, mainnet if not set
+    pub network_id: Option<NetworkId>,
+    /// IP ranges of the peers to accept and connect to, any if not set
+    pub peer_ip_filter: Option<PeerIpFilterSpec>,
+    /// Number of received events waiting for the event loop before reading from peers stops
+    pub event_queue_capacity: Option<usize>,
+    /// Number of local events waiting for the event loop, 10 if not set
+    pub local_event_capacity: Option<usize>,
+    /// Milliseconds to wait for peers to connect before starting, unlimited if not set
+    pub peer_wait_timeout: Option<u64>,
     /// Aggregation limit
     pub address_aggregation_limit: Option<usize>,
+    /// Argon2id costs of the wallet passphrase, defaulting for the database mode
+    pub miner_wallet_kdf: Option<Argon2Params>,
 }
 
 /// Configuration option for a user node
@@ -262,6 +424,8 @@ pub struct UserNodeConfig {
     pub user_api_port: u16,
     /// API use TLS
     pub user_api_use_tls: bool,
+    /// Require API clients to authenticate with a certificate, needs API TLS
+    pub user_api_client_auth: Option<ApiClientAuthSpec>,
     /// Wallet seeds
     pub user_wallet_seeds: Vec<WalletTxSpec>,
     /// Option of the passphrase used for encryption
@@ -275,8 +439,185 @@ pub struct UserNodeConfig {
     pub routes_pow: BTreeMap<String, usize>,
     /// Backup block that given modulo result in 0
     pub backup_block_modulo: Option<u64>,
+    /// File holding the Ed25519 identity key signing the db backups, unsigned if not set
+    pub backup_signing_key: Option<String>,
+    /// File holding the node identity key, created next to the node db if not set
+    pub identity_file: Option<String>,
+    /// Threads of the wallet database pool, 4 if not set
+    pub db_threads: Option<usize>,
+    /// Seconds between compactions of the wallet database, never if not set
+    pub db_compaction_interval: Option<u64>,
     /// Limit for the number of peers this node can have
     pub peer_limit: usize,
+    /// Network of the node, mainnet if not set
+    pub network_id: Option<NetworkId>,
+    /// IP ranges of the peers to accept and connect to, any if not set
+    pub peer_ip_filter: Option<PeerIpFilterSpec>,
+    /// Number of received events waiting for the event loop before reading from peers stops
+    pub event_queue_capacity: Option<usize>,
+    /// Number of local events waiting for the event loop, 10 if not set
+    pub local_event_capacity: Option<usize>,
+    /// Milliseconds to wait for peers to connect before starting, unlimited if not set
+    pub peer_wait_timeout: Option<u64>,
+    /// Exchange deposit handling, disabled if not set
+    pub user_exchange: Option<UserExchangeConfig>,
+    /// Seconds before a trade not completed is abandoned, 600 if not set
+    pub user_trade_timeout: Option<u64>,
+    /// Signing service holding the wallet secret keys, signing locally if not set
+    pub user_remote_signer: Option<RemoteSignerConfig>,
+    /// Source of the wallet secret keys held in memory only, stored on disk if not set
+    pub user_hot_wallet_keys: Option<HotKeySource>,
+    /// Message bus to publish received payments to, disabled if not set
+    pub user_event_bus: Option<EventBusConfig>,
+    /// Argon2id costs of the wallet passphrase, defaulting for the database mode
+    pub user_wallet_kdf: Option<Argon2Params>,
+    /// Light client mode proving incoming payments from a storage node, disabled if not set
+    pub user_light_client: Option<LightClientConfig>,
+    /// Blocks a payment may be added to before it expires, never expiring if not set
+    pub user_payment_expiry: Option<u64>,
+    /// Other user node of the owner to sync the wallet with, disabled if not set
+    pub user_wallet_sync: Option<WalletSyncConfig>,
+    /// Selection of the outputs spent by payments, in outpoint order if not set
+    pub user_coin_selection: Option<CoinSelection>,
+    /// Pay the change of payments back to the first address spent, for test scenarios,
+    /// to a new address if not set
+    pub user_reuse_change_address: Option<bool>,
+    /// Consolidation of the dust outputs of the wallet, started from the API if not set
+    pub user_dust_consolidation: Option<DustConsolidationConfig>,
+    /// Named wallets opened along with the node wallet, selectable from the API
+    pub user_wallets: Option<Vec<NamedWalletConfig>>,
+}
+
+/// Configuration option for a named wallet of a user node
+#[derive(Debug, Clone, Deserialize)]
+pub struct NamedWalletConfig {
+    /// Name selecting the wallet, also suffixing its database
+    pub name: String,
+    /// Passphrase of the wallet, the node passphrase if not set
+    pub passphrase: Option<String>,
+}
+
+/// Configuration option for the dust consolidation of a user node wallet
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DustConsolidationConfig {
+    /// Token amount below which an unspent output is dust
+    pub dust_threshold: Option<u64>,
+    /// Minimum number of dust outputs worth consolidating
+    pub min_outputs: Option<usize>,
+    /// Maximum number of inputs of a consolidation transaction
+    pub max_inputs: Option<usize>,
+    /// Seconds between checks for dust to consolidate
+    pub check_interval: Option<u64>,
+    /// Maximum number of transactions in the last block for the mempool to be quiet
+    pub quiet_block_txs: Option<usize>,
+}
+
+/// Configuration option for the light client mode of a user node
+#[derive(Debug, Clone, Deserialize)]
+pub struct LightClientConfig {
+    /// Storage node to fetch the inclusion proofs from
+    pub storage_node: NodeSpec,
+}
+
+/// Configuration option for the wallet sync between two user nodes of the same owner
+#[derive(Debug, Clone, Deserialize)]
+pub struct WalletSyncConfig {
+    /// Other user node of the owner
+    pub peer: NodeSpec,
+    /// Hex encoded secret shared by both nodes, sealing the messages
+    pub secret: String,
+}
+
+/// Source of the decisions screening the addresses touched by transactions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreeningConfig {
+    /// JSON file holding the list of addresses to reject
+    File(String),
+    /// Screening service deciding on the addresses of each transaction
+    Service(ScreeningServiceConfig),
+}
+
+/// Configuration option for a remote screening service
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScreeningServiceConfig {
+    /// Addresses of the screening service instances, tried in order on failure
+    pub endpoints: Vec<String>,
+    /// Hex encoded key authenticating requests and responses
+    pub auth_key: String,
+    /// Timeout for connecting and for each request, in milliseconds
+    pub timeout_ms: u64,
+}
+
+/// Configuration option for a remote signing service
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteSignerConfig {
+    /// Addresses of the signing service instances, tried in order on failure
+    pub endpoints: Vec<String>,
+    /// Hex encoded key authenticating requests and responses
+    pub auth_key: String,
+    /// Timeout for connecting and for each request, in milliseconds
+    pub timeout_ms: u64,
+}
+
+/// Source the secret keys of a hot wallet are read from at startup
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotKeySource {
+    /// Standard input, until closed
+    Stdin,
+    /// First connection to a Unix socket created at this path, until closed
+    Socket(String),
+}
+
+/// Configuration option for publishing node events to a message bus
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventBusConfig {
+    /// Message bus to publish to
+    pub backend: EventBusBackend,
+    /// Topic of new block events, "new_block" if not set
+    pub new_block_topic: Option<String>,
+    /// Topic of payment received events, "payment_received" if not set
+    pub payment_received_topic: Option<String>,
+    /// Topic of transaction accepted events, "tx_accepted" if not set
+    pub tx_accepted_topic: Option<String>,
+    /// Topic of chain reorganization events, "chain_reorg" if not set
+    pub chain_reorg_topic: Option<String>,
+    /// Encoding of the published events, JSON if not set
+    pub serialization: Option<EventSerialization>,
+    /// Events waiting to be published before new ones are dropped, 1000 if not set
+    pub queue_size: Option<usize>,
+}
+
+/// Message bus events are published to
+#[derive(Debug, Clone, Deserialize)]
+pub enum EventBusBackend {
+    /// ZeroMQ PUB socket bound to the endpoint, e.g. "tcp://0.0.0.0:5556"
+    ZeroMq { endpoint: String },
+    /// Kafka cluster, reached through the comma separated list of brokers
+    Kafka { brokers: String },
+}
+
+/// Encoding of the events published to a message bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum EventSerialization {
+    Json,
+    Bincode,
+}
+
+/// Configuration option for exchange deposit handling on a user node
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserExchangeConfig {
+    /// Address receiving the swept deposits
+    pub cold_address: String,
+    /// Minimum token amount held by a deposit address for it to be swept
+    pub sweep_threshold: u64,
+    /// Number of blocks between sweeps
+    pub sweep_interval: u64,
+    /// Maximum number of inputs in each sweep transaction
+    pub sweep_max_inputs: usize,
+    /// HTTP URL receiving a JSON notification for each confirmed deposit
+    pub webhook_url: Option<String>,
 }
 
 /// Configuration option for a pre-launch node
@@ -331,6 +672,13 @@ pub struct ExtraNodeParams {
     pub shared_wallet_db: Option<WalletDb>,
     pub custom_wallet_spec: Option<CustomDbSpec>,
     pub disable_tcp_listener: bool,
+    pub clock: Clock,
+    pub key_gen: KeyGen,
+    pub tls_config: Option<TlsSpec>,
+    pub bind_address: Option<SocketAddr>,
+    pub listener: Option<TcpListener>,
+    pub response_hook: Option<ResponseHook>,
+    pub event_hooks: EventHooks,
 }
 
 ///Hacky deserializer to work around deserializatio error with u128
@@ -1,5 +1,11 @@
 pub use tw_chain::constants::*;
 
+/*------- NETWORK CONSTANTS --------*/
+
+/// Network version exchanged in peer handshakes, peers of another version being rejected.
+/// One more than the chain `NETWORK_VERSION` since the network id was added to the handshake.
+pub const PEER_NETWORK_VERSION: u32 = NETWORK_VERSION + 1;
+
 /*------- BLOCK CONSTANTS --------*/
 
 /// Bit shifting value for reward issuance
@@ -23,6 +29,9 @@ pub const MINER_PARTICIPATION_UN: u128 = 2;
 /// Key pointing to the current db version.
 pub const DB_VERSION_KEY: &str = "DbVersionKey";
 
+/// Key pointing to the network the db belongs to.
+pub const DB_NETWORK_ID_KEY: &str = "DbNetworkIdKey";
+
 /// The constant prepending character for a block hash
 pub const BLOCK_PREPEND: u8 = b'b';
 
@@ -42,6 +51,11 @@ pub const INDEXED_BLOCK_HASH_PREFIX_KEY: &str = "nIndexedBlockHashKey_";
 /// The block and transaction number need to be separated by `_`.
 pub const INDEXED_TX_HASH_PREFIX_KEY: &str = "nIndexedTxHashKey_";
 
+/// The constant for the named orphaned block hash with NAMED_CONSTANT_PREPEND.
+/// The block index number is 0-indexed hexadecimal value of 16 characters with leading 0,
+/// followed by `_` and the hash of the orphaned block.
+pub const ORPHANED_BLOCK_HASH_PREFIX_KEY: &str = "nOrphanedBlockHashKey_";
+
 /// Path to chain DB
 pub const DB_PATH: &str = "src/db/db";
 
@@ -51,13 +65,19 @@ pub const DB_PATH_TEST: &str = "test";
 /// Path to live net DB
 pub const DB_PATH_LIVE: &str = "live";
 
+/// Number of values read from the storage DB kept in its LRU cache
+pub const STORAGE_READ_CACHE_ENTRIES: u64 = 8192;
+
+/// Number of values read from a wallet DB kept in its LRU cache
+pub const WALLET_READ_CACHE_ENTRIES: u64 = 4096;
+
 /// Path to wallet DB
 pub const WALLET_PATH: &str = "src/wallet/wallet";
 
 /// Key for local addresses in wallet
 pub const KNOWN_ADDRESS_KEY: &str = "a";
 
-/// Key for a running total of wallet funds
+/// Key of the fund store of wallets written by previous versions
 pub const FUND_KEY: &str = "f";
 
 ///Key for storing encapsulation details
@@ -69,6 +89,12 @@ pub const SANC_LIST_PROD: &str = "src/db/sanc_list.json";
 /// Path to test sanction list
 pub const SANC_LIST_TEST: &str = "src/db/sanc_list_test.json";
 
+/// Path to crash reports directory
+pub const CRASH_REPORT_PATH: &str = "src/crash_reports";
+
+/// Number of recent log lines included in crash reports
+pub const CRASH_REPORT_LOG_LINES: usize = 200;
+
 /*------- LIMIT CONSTANTS -------*/
 
 /// Maximum number of attempts to resend trigger messages before proposing to reset the mining pipeline
@@ -95,6 +121,15 @@ pub const MR_PRIME_ITERS: u32 = 15;
 /// Number of old backups to keep before purging
 pub const OLD_BACKUP_COUNT: usize = 5;
 
+/// Commit index lag after which a raft follower is reported as lagging
+pub const RAFT_FOLLOWER_LAG_LIMIT: u64 = 100;
+
+/// Ticks without commit while proposals are pending after which raft is reported as stalled
+pub const RAFT_STALLED_TICK_LIMIT: u64 = 100;
+
+/// Locktimes from this value are Unix timestamps in seconds, block numbers below it
+pub const LOCKTIME_TIMESTAMP_THRESHOLD: u64 = 500_000_000;
+
 /// Coinbase locktime constant
 /// TODO: Update to 5 once locktime tests are introduced
 pub const COINBASE_MATURITY: u64 = if cfg!(test) { 0 } else { 100 };
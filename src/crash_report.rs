@@ -0,0 +1,406 @@
+//! Panic capture and crash report files.
+//!
+//! Bins install a panic hook that writes a crash report (backtrace, node type,
+//! config digest, last log lines and open DB paths) before the default panic
+//! handling runs. The process only exits when its main thread panics, so only
+//! those panics write a report: panics in spawned tasks are caught by their
+//! JoinHandle, and are kept in memory to be included in a later report.
+//! On the next startup the previous report is detected and reported.
+
+use crate::constants::{CRASH_REPORT_LOG_LINES, CRASH_REPORT_PATH};
+use crate::utils::{set_log_filter_reload, StringError};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::{fs, path::Path};
+use tracing::warn;
+use tracing_subscriber::EnvFilter;
+use tw_chain::crypto::sha3_256;
+
+static CRASH_CONTEXT: Mutex<Option<CrashContext>> = Mutex::new(None);
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static OPEN_DB_PATHS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+static LAST_TASK_PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Information about the running node included in crash reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashContext {
+    /// Type of node running (mempool, storage, miner, user...)
+    pub node_type: String,
+    /// Unique name used for the crash report file
+    pub node_name: String,
+    /// Digest of the configuration the node is running with
+    pub config_digest: String,
+}
+
+impl CrashContext {
+    /// Create a context for the given node with a digest of its configuration
+    ///
+    /// ### Arguments
+    ///
+    /// * `node_type` - Type of node running.
+    /// * `node_name` - Unique name for this node on the host (i.e type and port).
+    /// * `config`    - Configuration of the node.
+    pub fn new(node_type: &str, node_name: &str, config: &impl fmt::Debug) -> Self {
+        Self {
+            node_type: node_type.to_owned(),
+            node_name: node_name.to_owned(),
+            config_digest: config_digest(config),
+        }
+    }
+}
+
+/// Writer for tracing output mirroring stdout into the recent log buffer
+#[derive(Default)]
+pub struct RecentLogWriter;
+
+impl Write for RecentLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        push_recent_log(&String::from_utf8_lossy(buf));
+        io::stdout().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// Initialize tracing, keeping the last log lines for crash reports.
+/// Filter directives can be changed at runtime with `utils::reload_log_filter`.
+pub fn tracing_log_init_with_crash_capture() {
+    let directives = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(&directives))
+        .with_writer(RecentLogWriter::default)
+        .with_filter_reloading();
+    let handle = builder.reload_handle();
+    builder.init();
+
+    let reload = move |directives: &str| {
+        let filter = EnvFilter::try_new(directives).map_err(|e| StringError(e.to_string()))?;
+        handle
+            .reload(filter)
+            .map_err(|e| StringError(e.to_string()))
+    };
+    set_log_filter_reload(Box::new(reload), directives);
+}
+
+/// Install the panic hook writing a crash report before the default panic handling.
+/// Only panics ending the process write a report, other panics are kept for it.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = build_crash_report(&info.to_string());
+        if is_fatal_panic(std::thread::current().name()) {
+            if let Err(e) = write_crash_report(CRASH_REPORT_PATH, &report) {
+                eprintln!("Failed to write crash report: {e:?}");
+            }
+        } else if let Ok(mut last) = LAST_TASK_PANIC.try_lock() {
+            *last = Some(report);
+        }
+        default_hook(info);
+    }));
+}
+
+/// Set the node information to include in crash reports
+///
+/// ### Arguments
+///
+/// * `context` - Information about the running node.
+pub fn set_crash_context(context: CrashContext) {
+    *CRASH_CONTEXT.lock().unwrap_or_else(|e| e.into_inner()) = Some(context);
+}
+
+/// Record a database path opened by this process
+///
+/// ### Arguments
+///
+/// * `path` - Path of the opened database.
+pub fn register_open_db(path: &str) {
+    let mut paths = OPEN_DB_PATHS.lock().unwrap_or_else(|e| e.into_inner());
+    paths.insert(path.to_owned());
+}
+
+/// Detect and report crash report left by a previous run of the node.
+/// The report is renamed so it is only reported once.
+///
+/// ### Arguments
+///
+/// * `node_name` - Unique name for this node on the host.
+pub fn report_previous_crash(node_name: &str) -> Option<String> {
+    report_previous_crash_in(CRASH_REPORT_PATH, node_name)
+}
+
+/// Detect and report previous crash in given directory
+///
+/// ### Arguments
+///
+/// * `dir`       - Directory containing crash reports.
+/// * `node_name` - Unique name for this node on the host.
+fn report_previous_crash_in(dir: &str, node_name: &str) -> Option<String> {
+    let path = crash_report_path(dir, node_name);
+    let report = fs::read_to_string(&path).ok()?;
+    warn!(
+        "Node {} crashed during previous run, see {}",
+        node_name, path
+    );
+    for line in report.lines().take_while(|l| !l.is_empty()) {
+        warn!("{}", line);
+    }
+
+    if let Err(e) = fs::rename(&path, format!("{path}.reported")) {
+        warn!("Failed to mark crash report {} as reported: {:?}", path, e);
+    }
+    Some(report)
+}
+
+/// Whether a panic on the given thread ends the process
+///
+/// ### Arguments
+///
+/// * `thread` - Name of the panicking thread.
+fn is_fatal_panic(thread: Option<&str>) -> bool {
+    thread == Some("main")
+}
+
+/// Write the crash report file for the current node
+///
+/// ### Arguments
+///
+/// * `dir`    - Directory to write the report into.
+/// * `report` - Formatted crash report.
+fn write_crash_report(dir: &str, report: &str) -> io::Result<()> {
+    let node_name = current_context()
+        .map(|c| c.node_name)
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    fs::create_dir_all(dir)?;
+    let path = crash_report_path(dir, &node_name);
+    fs::write(&path, report)?;
+    eprintln!("Crash report written to {path}");
+    Ok(())
+}
+
+/// Format the crash report for a panic
+///
+/// ### Arguments
+///
+/// * `message` - Panic message and location.
+fn build_crash_report(message: &str) -> String {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    let thread = std::thread::current();
+    let task_panic = LAST_TASK_PANIC.try_lock().ok().and_then(|p| p.clone());
+    format_crash_report(
+        current_context().as_ref(),
+        thread.name().unwrap_or("<unnamed>"),
+        message,
+        &backtrace.to_string(),
+        task_panic.as_deref(),
+    )
+}
+
+/// Format the crash report content
+///
+/// ### Arguments
+///
+/// * `context`   - Information about the running node.
+/// * `thread`    - Name of the panicking thread.
+/// * `message`   - Panic message and location.
+/// * `backtrace`  - Captured backtrace.
+/// * `task_panic` - Report of the last panic of a task the process survived.
+fn format_crash_report(
+    context: Option<&CrashContext>,
+    thread: &str,
+    message: &str,
+    backtrace: &str,
+    task_panic: Option<&str>,
+) -> String {
+    let (node_type, node_name, digest) = context
+        .map(|c| {
+            (
+                c.node_type.as_str(),
+                c.node_name.as_str(),
+                c.config_digest.as_str(),
+            )
+        })
+        .unwrap_or(("unknown", "unknown", "unknown"));
+
+    let mut report = String::new();
+    let _ = writeln!(report, "Crash time: {}", chrono::Utc::now().to_rfc3339());
+    let _ = writeln!(report, "Node type: {node_type}");
+    let _ = writeln!(report, "Node name: {node_name}");
+    let _ = writeln!(report, "Config digest: {digest}");
+    let _ = writeln!(report, "Thread: {thread}");
+    let _ = writeln!(report, "Panic: {message}");
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "Open DB paths:");
+    if let Ok(paths) = OPEN_DB_PATHS.try_lock() {
+        for path in paths.iter() {
+            let _ = writeln!(report, "  {path}");
+        }
+    }
+    let _ = writeln!(report);
+
+    let _ = writeln!(report, "Backtrace:");
+    let _ = writeln!(report, "{backtrace}");
+
+    let _ = writeln!(report, "Last {CRASH_REPORT_LOG_LINES} log lines:");
+    if let Ok(logs) = RECENT_LOGS.try_lock() {
+        for line in logs.iter() {
+            let _ = writeln!(report, "{line}");
+        }
+    }
+
+    if let Some(task_panic) = task_panic {
+        let _ = writeln!(report);
+        let _ = writeln!(report, "Last task panic:");
+        let _ = writeln!(report, "{task_panic}");
+    }
+    report
+}
+
+/// Keep the given log output in the recent log buffer
+///
+/// ### Arguments
+///
+/// * `output` - Formatted log output, possibly multiple lines.
+fn push_recent_log(output: &str) {
+    if let Ok(mut logs) = RECENT_LOGS.try_lock() {
+        for line in output.lines().filter(|l| !l.is_empty()) {
+            if logs.len() >= CRASH_REPORT_LOG_LINES {
+                logs.pop_front();
+            }
+            logs.push_back(strip_ansi(line));
+        }
+    }
+}
+
+/// Remove terminal color escape sequences from a log line
+///
+/// ### Arguments
+///
+/// * `line` - Log line to clean.
+fn strip_ansi(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Digest of the configuration, so reports can be matched without leaking secrets
+///
+/// ### Arguments
+///
+/// * `config` - Configuration of the node.
+fn config_digest(config: &impl fmt::Debug) -> String {
+    hex::encode(sha3_256::digest(format!("{config:?}").as_bytes()))
+}
+
+/// Path of the crash report file for a node
+///
+/// ### Arguments
+///
+/// * `dir`       - Directory containing crash reports.
+/// * `node_name` - Unique name for this node on the host.
+fn crash_report_path(dir: &str, node_name: &str) -> String {
+    Path::new(dir)
+        .join(format!("crash_{node_name}.log"))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Current crash context if set and not locked
+fn current_context() -> Option<CrashContext> {
+    CRASH_CONTEXT.try_lock().ok().and_then(|c| c.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Checks color codes from tracing are removed from recorded lines
+    fn strip_ansi_from_log_line() {
+        let line = "\u{1b}[2m2024-01-01\u{1b}[0m \u{1b}[32m INFO\u{1b}[0m node: Started";
+        assert_eq!(strip_ansi(line), "2024-01-01  INFO node: Started");
+    }
+
+    #[test]
+    /// Checks a written crash report is reported once at next startup
+    fn write_and_report_previous_crash() {
+        //
+        // Arrange
+        //
+        let dir = "src/db/test.crash_reports";
+        let context = CrashContext::new("storage", "storage_3001", &"config");
+        let report = format_crash_report(Some(&context), "main", "panicked at test", "bt", None);
+        let path = crash_report_path(dir, &context.node_name);
+        let _ = fs::remove_file(format!("{path}.reported"));
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&path, &report).unwrap();
+
+        //
+        // Act
+        //
+        let first = report_previous_crash_in(dir, &context.node_name);
+        let second = report_previous_crash_in(dir, &context.node_name);
+
+        //
+        // Assert
+        //
+        assert_eq!(first.as_deref(), Some(report.as_str()));
+        assert_eq!(second, None);
+        assert!(report.contains("Node type: storage"));
+        assert!(report.contains(&format!("Config digest: {}", context.config_digest)));
+        assert!(report.contains("Panic: panicked at test"));
+    }
+
+    #[test]
+    /// Checks only main thread panics are fatal, and task panics are kept for their report
+    fn task_panic_kept_for_fatal_report() {
+        //
+        // Arrange
+        //
+        let context = CrashContext::new("mempool", "mempool_3000", &"config");
+        let task_panic = format_crash_report(
+            Some(&context),
+            "tokio-runtime-worker",
+            "panicked at task",
+            "bt",
+            None,
+        );
+
+        //
+        // Act
+        //
+        let report = format_crash_report(
+            Some(&context),
+            "main",
+            "task failed",
+            "bt",
+            Some(&task_panic),
+        );
+
+        //
+        // Assert
+        //
+        assert!(is_fatal_panic(Some("main")));
+        assert!(!is_fatal_panic(Some("tokio-runtime-worker")));
+        assert!(!is_fatal_panic(None));
+        assert!(report.contains("Panic: task failed"));
+        assert!(report.contains("Last task panic:"));
+        assert!(report.contains("Panic: panicked at task"));
+    }
+}
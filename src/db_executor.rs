@@ -0,0 +1,190 @@
+//! Dedicated thread pools for blocking database work.
+//!
+//! Database operations run on their own pools instead of tokio's shared blocking
+//! pool, so they do not compete with other blocking work such as PoW generation
+//! or DNS resolution. Wallet and storage operations use separate pools, so heavy
+//! storage writes cannot starve wallet reads, and the other way round.
+//!
+//! Each pool is started on first use with `DEFAULT_DB_THREADS` threads, unless
+//! sized beforehand with `configure_db_pool`.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+/// Number of threads of a pool not configured
+pub const DEFAULT_DB_THREADS: usize = 4;
+
+static WALLET_POOL: Mutex<Option<Arc<DbExecutor>>> = Mutex::new(None);
+static STORAGE_POOL: Mutex<Option<Arc<DbExecutor>>> = Mutex::new(None);
+
+type DbJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// Pool a database operation runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbPool {
+    Wallet,
+    Storage,
+}
+
+impl DbPool {
+    fn slot(self) -> &'static Mutex<Option<Arc<DbExecutor>>> {
+        match self {
+            Self::Wallet => &WALLET_POOL,
+            Self::Storage => &STORAGE_POOL,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Wallet => "wallet-db",
+            Self::Storage => "storage-db",
+        }
+    }
+}
+
+/// Fixed size pool of threads running blocking database operations
+#[derive(Debug)]
+pub struct DbExecutor {
+    jobs: Mutex<mpsc::Sender<DbJob>>,
+    threads: usize,
+}
+
+impl DbExecutor {
+    /// Start a pool, its threads stop once it is dropped
+    ///
+    /// ### Arguments
+    ///
+    /// * `name`    - Prefix of the thread names.
+    /// * `threads` - Number of threads, at least one.
+    pub fn new(name: &str, threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (tx, rx) = mpsc::channel::<DbJob>();
+        let rx = Arc::new(Mutex::new(rx));
+
+        for i in 0..threads {
+            let rx = rx.clone();
+            thread::Builder::new()
+                .name(format!("{name}-{i}"))
+                .spawn(move || loop {
+                    let job = rx.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("Failed to start database thread");
+        }
+
+        Self {
+            jobs: Mutex::new(tx),
+            threads,
+        }
+    }
+
+    /// Number of threads of the pool
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Run a blocking operation on the pool and wait for its result
+    ///
+    /// A panic of the operation is resumed in the caller.
+    ///
+    /// ### Arguments
+    ///
+    /// * `f` - Operation to run.
+    pub async fn run<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: DbJob = Box::new(move || {
+            let _ = tx.send(panic::catch_unwind(AssertUnwindSafe(f)));
+        });
+        self.jobs
+            .lock()
+            .unwrap()
+            .send(job)
+            .expect("Database threads stopped");
+
+        match rx.await.expect("Database job dropped") {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// Set the number of threads of a pool
+///
+/// Operations already queued complete on the previous threads.
+///
+/// ### Arguments
+///
+/// * `pool`    - Pool to size.
+/// * `threads` - Number of threads, at least one.
+pub fn configure_db_pool(pool: DbPool, threads: usize) {
+    let mut slot = pool.slot().lock().unwrap();
+    if slot.as_ref().map(|e| e.threads()) == Some(threads.max(1)) {
+        return;
+    }
+    if slot.is_some() {
+        warn!("Restarting {} pool with {} threads", pool.name(), threads);
+    }
+    *slot = Some(Arc::new(DbExecutor::new(pool.name(), threads)));
+}
+
+/// Run a blocking database operation on a pool and wait for its result
+///
+/// ### Arguments
+///
+/// * `pool` - Pool to run the operation on.
+/// * `f`    - Operation to run.
+pub async fn spawn_db<F, R>(pool: DbPool, f: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let executor = pool
+        .slot()
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| Arc::new(DbExecutor::new(pool.name(), DEFAULT_DB_THREADS)))
+        .clone();
+    executor.run(f).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks operations run on the pool threads, and their panics reach the caller
+    async fn db_executor_runs_on_pool() {
+        //Arrange
+        let executor = Arc::new(DbExecutor::new("test-db", 2));
+        let thread_name = || thread::current().name().unwrap_or_default().to_owned();
+
+        //Act
+        let names: BTreeSet<_> =
+            futures::future::join_all((0..8).map(|_| executor.run(thread_name)))
+                .await
+                .into_iter()
+                .collect();
+        let panicked = tokio::spawn({
+            let executor = executor.clone();
+            async move { executor.run(|| panic!("db job failed")).await }
+        })
+        .await;
+        let after_panic = executor.run(|| 5).await;
+
+        //Assert
+        assert!(names.iter().all(|n| n.starts_with("test-db-")));
+        assert!(panicked.unwrap_err().is_panic());
+        assert_eq!(after_panic, 5);
+    }
+}
@@ -1,16 +1,24 @@
 use crate::configurations::DbMode;
 use crate::constants::{
-    DB_PATH_LIVE, DB_PATH_TEST, DB_VERSION_KEY, NETWORK_VERSION_SERIALIZED, OLD_BACKUP_COUNT,
+    DB_NETWORK_ID_KEY, DB_PATH_LIVE, DB_PATH_TEST, DB_VERSION_KEY, NETWORK_VERSION_SERIALIZED,
+    OLD_BACKUP_COUNT,
 };
+use crate::crash_report;
+use crate::interfaces::NetworkId;
+use crate::snapshot_manifest::{self, SnapshotError};
 use rocksdb::backup::{BackupEngine, BackupEngineOptions};
 use rocksdb::Env;
 use rocksdb::{DBCompressionType, IteratorMode, Options, WriteBatch, DB};
 pub use rocksdb::{Error as DBError, DEFAULT_COLUMN_FAMILY_NAME as DB_COL_DEFAULT};
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Arc;
 use std::{error::Error, fmt};
+use tokio::time::{interval_at, Duration, Instant, Interval, MissedTickBehavior};
 use tracing::{debug, warn};
 
 pub type DbIteratorItem = (Vec<u8>, Vec<u8>);
+pub type DbIterator<'a> = Box<dyn Iterator<Item = DbIteratorItem> + 'a>;
 pub type InMemoryWriteBatch = Vec<(usize, Vec<u8>, Option<Vec<u8>>)>;
 pub type InMemoryColumns = BTreeMap<String, usize>;
 pub type InMemoryDb = Vec<BTreeMap<Vec<u8>, Vec<u8>>>;
@@ -53,12 +61,55 @@ impl From<DBError> for SimpleDbError {
     }
 }
 
+impl From<SnapshotError> for SimpleDbError {
+    fn from(other: SnapshotError) -> Self {
+        Self(other.to_string())
+    }
+}
+
 impl SimpleDbError {
     pub fn into_string(self) -> String {
         self.0
     }
 }
 
+/// Bounded LRU cache of the values read from a database.
+///
+/// Entries are invalidated when their key is written, and all of them when a batch is written.
+pub struct ReadCache(moka::sync::Cache<(&'static str, Vec<u8>), Option<Vec<u8>>>);
+
+impl ReadCache {
+    /// Create a cache holding up to `capacity` values
+    pub fn new(capacity: u64) -> Self {
+        Self(moka::sync::Cache::new(capacity))
+    }
+
+    fn get(&self, cf: &'static str, key: &[u8]) -> Option<Option<Vec<u8>>> {
+        self.0.get(&(cf, key.to_vec()))
+    }
+
+    fn insert(&self, cf: &'static str, key: &[u8], value: Option<Vec<u8>>) {
+        self.0.insert((cf, key.to_vec()), value);
+    }
+
+    fn invalidate(&self, cf: &'static str, key: &[u8]) {
+        self.0.invalidate(&(cf, key.to_vec()));
+    }
+
+    fn invalidate_all(&self) {
+        self.0.invalidate_all();
+    }
+}
+
+/// Encryption of the values written to a database, their keys staying in clear
+pub trait ValueCipher: Send + Sync {
+    /// Encrypt the value written at the given key
+    fn encrypt(&self, key: &[u8], value: &[u8]) -> Vec<u8>;
+
+    /// Decrypt the value read at the given key, None if it cannot be decrypted
+    fn decrypt(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>>;
+}
+
 /// Database that can store in memory or using rocksDB.
 pub enum SimpleDb {
     File {
@@ -66,10 +117,14 @@ pub enum SimpleDb {
         path: String,
         columns: BTreeSet<String>,
         db: DB,
+        read_cache: Option<ReadCache>,
+        value_cipher: Option<Arc<dyn ValueCipher>>,
     },
     InMemory {
         columns: InMemoryColumns,
         key_values: InMemoryDb,
+        read_cache: Option<ReadCache>,
+        value_cipher: Option<Arc<dyn ValueCipher>>,
     },
 }
 
@@ -100,6 +155,7 @@ impl SimpleDb {
     /// Create rocksDB
     pub fn new_file(path: String, columns: &[&str]) -> Result<Self> {
         debug!("Open/Create Db at {}", path);
+        crash_report::register_open_db(&path);
         let columns = [DB_COL_DEFAULT].iter().chain(columns.iter());
         let mut options = get_db_options();
 
@@ -114,6 +170,8 @@ impl SimpleDb {
                 options,
                 path,
                 db,
+                read_cache: None,
+                value_cipher: None,
             })
         } else {
             // Allow create empty db with required column families.
@@ -128,6 +186,8 @@ impl SimpleDb {
                 options,
                 path,
                 db,
+                read_cache: None,
+                value_cipher: None,
             })
         }
     }
@@ -155,6 +215,8 @@ impl SimpleDb {
             with_initial_data(Self::InMemory {
                 key_values,
                 columns,
+                read_cache: None,
+                value_cipher: None,
             })
         }
     }
@@ -166,6 +228,8 @@ impl SimpleDb {
             Self::InMemory {
                 key_values: Default::default(),
                 columns: Default::default(),
+                read_cache: None,
+                value_cipher: None,
             },
         )
     }
@@ -185,11 +249,15 @@ impl SimpleDb {
         if let Self::InMemory {
             columns,
             key_values,
+            value_cipher,
+            ..
         } = &self
         {
             Some(Self::InMemory {
                 columns: columns.clone(),
                 key_values: key_values.clone(),
+                read_cache: None,
+                value_cipher: value_cipher.clone(),
             })
         } else {
             // Drop/close file db
@@ -206,7 +274,7 @@ impl SimpleDb {
         }
     }
 
-    /// Backup for file db
+    /// Backup for file db, with its manifest
     pub fn file_backup(&self) -> Result<()> {
         if let Self::File { path, db, .. } = &self {
             let backup_path = path.clone() + "_backup";
@@ -220,6 +288,7 @@ impl SimpleDb {
 
             warn!("Purging old backups at {backup_path:?} leaving {OLD_BACKUP_COUNT:?} latest backups intact");
             backup_engine.purge_old_backups(OLD_BACKUP_COUNT)?;
+            snapshot_manifest::write_manifest(path, &backup_path)?;
         }
 
         Ok(())
@@ -242,6 +311,7 @@ impl SimpleDb {
             Self::InMemory {
                 columns,
                 key_values,
+                ..
             } => {
                 if !columns.contains_key(name) {
                     columns.insert(name.to_owned(), key_values.len());
@@ -252,16 +322,65 @@ impl SimpleDb {
         Ok(())
     }
 
+    /// Cache up to `capacity` values read with `get_cf`, in front of the database
+    ///
+    /// ### Arguments
+    ///
+    /// * `capacity` - Number of values cached, 0 to disable the cache
+    pub fn set_read_cache(&mut self, capacity: u64) {
+        let cache = Some(capacity).filter(|c| *c > 0).map(ReadCache::new);
+        match self {
+            Self::File { read_cache, .. } | Self::InMemory { read_cache, .. } => {
+                *read_cache = cache
+            }
+        }
+    }
+
+    fn read_cache(&self) -> Option<&ReadCache> {
+        match self {
+            Self::File { read_cache, .. } | Self::InMemory { read_cache, .. } => {
+                read_cache.as_ref()
+            }
+        }
+    }
+
+    /// Encrypt the values written from now on, and decrypt the values read
+    ///
+    /// ### Arguments
+    ///
+    /// * `cipher` - Cipher of the values, None to read and write them in clear
+    pub fn set_value_cipher(&mut self, cipher: Option<Arc<dyn ValueCipher>>) {
+        if let Some(cache) = self.read_cache() {
+            cache.invalidate_all();
+        }
+        match self {
+            Self::File { value_cipher, .. } | Self::InMemory { value_cipher, .. } => {
+                *value_cipher = cipher
+            }
+        }
+    }
+
+    fn value_cipher(&self) -> Option<&dyn ValueCipher> {
+        match self {
+            Self::File { value_cipher, .. } | Self::InMemory { value_cipher, .. } => {
+                value_cipher.as_deref()
+            }
+        }
+    }
+
     /// Writter to accumulate batch edits
-    pub fn batch_writer(&self) -> SimpleDbWriteBatch {
+    pub fn batch_writer(&self) -> SimpleDbWriteBatch<'_> {
+        let value_cipher = self.value_cipher();
         match self {
             Self::File { db, .. } => SimpleDbWriteBatch::File {
                 write: Default::default(),
                 db,
+                value_cipher,
             },
             Self::InMemory { columns, .. } => SimpleDbWriteBatch::InMemory {
                 write: Default::default(),
                 columns,
+                value_cipher,
             },
         }
     }
@@ -273,6 +392,9 @@ impl SimpleDb {
     /// * `batch` - batch of put/delete to process
     pub fn write(&mut self, batch: SimpleDbWriteBatchDone) -> Result<()> {
         use SimpleDbWriteBatchDone as Batch;
+        if let Some(cache) = self.read_cache() {
+            cache.invalidate_all();
+        }
         match (self, batch) {
             (Self::File { db, .. }, Batch::File { write }) => {
                 db.write(write)?;
@@ -322,6 +444,10 @@ impl SimpleDb {
         key: K,
         value: V,
     ) -> Result<()> {
+        if let Some(cache) = self.read_cache() {
+            cache.invalidate(cf, key.as_ref());
+        }
+        let value = encrypt_value(self.value_cipher(), key.as_ref(), value.as_ref());
         match self {
             Self::File { db, .. } => {
                 let cf = db.cf_handle(cf).unwrap();
@@ -330,9 +456,10 @@ impl SimpleDb {
             Self::InMemory {
                 key_values,
                 columns,
+                ..
             } => {
                 let cf = columns.get(cf).unwrap();
-                key_values[*cf].insert(key.as_ref().to_vec(), value.as_ref().to_vec());
+                key_values[*cf].insert(key.as_ref().to_vec(), value.into_owned());
             }
         }
         Ok(())
@@ -345,6 +472,9 @@ impl SimpleDb {
     /// * `cf`  - The column family to use
     /// * `key` - position in database to be deleted
     pub fn delete_cf<K: AsRef<[u8]>>(&mut self, cf: &'static str, key: K) -> Result<()> {
+        if let Some(cache) = self.read_cache() {
+            cache.invalidate(cf, key.as_ref());
+        }
         match self {
             Self::File { db, .. } => {
                 let cf = db.cf_handle(cf).unwrap();
@@ -353,6 +483,7 @@ impl SimpleDb {
             Self::InMemory {
                 key_values,
                 columns,
+                ..
             } => {
                 let cf = columns.get(cf).unwrap();
                 key_values[*cf].remove(key.as_ref());
@@ -368,19 +499,38 @@ impl SimpleDb {
     /// * `cf`  - The column family to use
     /// * `key` - used to find position in database
     pub fn get_cf<K: AsRef<[u8]>>(&self, cf: &'static str, key: K) -> Result<Option<Vec<u8>>> {
-        match self {
+        let cache = self.read_cache();
+        if let Some(value) = cache.and_then(|c| c.get(cf, key.as_ref())) {
+            return Ok(value);
+        }
+
+        let value = match self {
             Self::File { db, .. } => {
-                let cf = db.cf_handle(cf).unwrap();
-                Ok(db.get_cf(cf, key)?)
+                let cf_handle = db.cf_handle(cf).unwrap();
+                db.get_cf(cf_handle, key.as_ref())?
             }
             Self::InMemory {
                 key_values,
                 columns,
+                ..
             } => {
                 let cf = columns.get(cf).unwrap();
-                Ok(key_values[*cf].get(key.as_ref()).cloned())
+                key_values[*cf].get(key.as_ref()).cloned()
             }
+        };
+        let value = match (self.value_cipher(), value) {
+            (Some(cipher), Some(value)) => Some(
+                cipher
+                    .decrypt(key.as_ref(), &value)
+                    .ok_or_else(|| SimpleDbError("Value decryption failed".to_owned()))?,
+            ),
+            (_, value) => value,
+        };
+
+        if let Some(cache) = cache {
+            cache.insert(cf, key.as_ref(), value.clone());
         }
+        Ok(value)
     }
 
     /// Count entries from database
@@ -393,6 +543,7 @@ impl SimpleDb {
             Self::InMemory {
                 key_values,
                 columns,
+                ..
             } => {
                 let cf = columns.get(cf).unwrap();
                 key_values[*cf].len()
@@ -400,23 +551,63 @@ impl SimpleDb {
         }
     }
 
+    /// Read every entry of all opened columns, returning the number of entries
+    ///
+    /// Reading the entries of a file db verifies their checksums.
+    pub fn scan(&self) -> Result<usize> {
+        match self {
+            Self::File { db, columns, .. } => {
+                let mut len = 0;
+                for cf in columns {
+                    let cf = db.cf_handle(cf).unwrap();
+                    for item in db.iterator_cf(cf, IteratorMode::Start) {
+                        item?;
+                        len += 1;
+                    }
+                }
+                Ok(len)
+            }
+            Self::InMemory { key_values, .. } => Ok(key_values.iter().map(|kv| kv.len()).sum()),
+        }
+    }
+
     /// Get entries from database as iterable db items
-    pub fn iter_cf_clone(&self, cf: &'static str) -> Box<dyn Iterator<Item = DbIteratorItem> + '_> {
+    pub fn iter_cf_clone(&self, cf: &'static str) -> Result<DbIterator<'_>> {
         self.iter_cf_clone_pvt(cf)
     }
 
     /// Get entries from database as iterable db items for all opened columns
-    pub fn iter_all_cf_clone(
-        &self,
-    ) -> Vec<(String, Box<dyn Iterator<Item = DbIteratorItem> + '_>)> {
+    pub fn iter_all_cf_clone(&self) -> Result<Vec<(String, DbIterator<'_>)>> {
         self.open_columns()
             .iter()
-            .map(|cf| (cf.clone(), self.iter_cf_clone_pvt(cf)))
+            .map(|cf| Ok((cf.clone(), self.iter_cf_clone_pvt(cf)?)))
             .collect()
     }
 
-    /// Get entries from database as iterable db items
-    fn iter_cf_clone_pvt(&self, cf: &str) -> Box<dyn Iterator<Item = DbIteratorItem> + '_> {
+    /// Get entries from database as iterable db items, decrypting their values
+    ///
+    /// The values of an encrypted db are all decrypted before iterating,
+    /// so that a value failing decryption is returned as an error.
+    fn iter_cf_clone_pvt(&self, cf: &str) -> Result<DbIterator<'_>> {
+        let iter = self.iter_cf_clone_raw(cf);
+        match self.value_cipher() {
+            Some(cipher) => {
+                let items = iter
+                    .map(|(key, value)| {
+                        let value = cipher.decrypt(&key, &value);
+                        let value = value
+                            .ok_or_else(|| SimpleDbError("Value decryption failed".to_owned()))?;
+                        Ok((key, value))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Box::new(items.into_iter()))
+            }
+            None => Ok(iter),
+        }
+    }
+
+    /// Get entries from database as iterable db items, as stored
+    fn iter_cf_clone_raw(&self, cf: &str) -> DbIterator<'_> {
         match self {
             Self::File { db, .. } => {
                 let cf = db.cf_handle(cf).unwrap();
@@ -429,6 +620,7 @@ impl SimpleDb {
             Self::InMemory {
                 key_values,
                 columns,
+                ..
             } => {
                 let cf = columns.get(cf).unwrap();
                 let iter = key_values[*cf].iter().map(|(k, v)| (k.clone(), v.clone()));
@@ -437,6 +629,18 @@ impl SimpleDb {
         }
     }
 
+    /// Compact all the columns of a file db, discarding deleted and overwritten values
+    pub fn compact(&self) {
+        if let Self::File { db, columns, .. } = self {
+            for column in columns {
+                if let Some(cf) = db.cf_handle(column) {
+                    debug!("Compact db column {}", column);
+                    db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+                }
+            }
+        }
+    }
+
     /// Return all open columns
     fn open_columns(&self) -> Vec<String> {
         match self {
@@ -446,6 +650,41 @@ impl SimpleDb {
     }
 }
 
+/// Schedule of the compactions of a node database
+#[derive(Debug)]
+pub struct CompactionSchedule {
+    interval: Option<Interval>,
+}
+
+impl CompactionSchedule {
+    /// Create a schedule compacting every `interval_secs` seconds, never if not set
+    ///
+    /// ### Arguments
+    ///
+    /// * `interval_secs` - Seconds between compactions
+    pub fn new(interval_secs: Option<u64>) -> Self {
+        let interval = interval_secs.filter(|s| *s > 0).map(|secs| {
+            let period = Duration::from_secs(secs);
+            let mut interval = interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+        Self { interval }
+    }
+
+    /// Completes when the next compaction is due
+    ///
+    /// Never completes if compactions are not scheduled.
+    pub async fn tick(&mut self) {
+        match &mut self.interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
 /// Database Atomic update accross column with performance benefit.
 pub enum SimpleDbWriteBatchDone {
     File { write: WriteBatch },
@@ -457,10 +696,12 @@ pub enum SimpleDbWriteBatch<'a> {
     File {
         write: WriteBatch,
         db: &'a DB,
+        value_cipher: Option<&'a dyn ValueCipher>,
     },
     InMemory {
         write: InMemoryWriteBatch,
         columns: &'a InMemoryColumns,
+        value_cipher: Option<&'a dyn ValueCipher>,
     },
 }
 
@@ -498,17 +739,27 @@ impl SimpleDbWriteBatch<'_> {
         value: V,
     ) -> Result<()> {
         match self {
-            Self::File { write, db } => {
+            Self::File {
+                write,
+                db,
+                value_cipher,
+            } => {
                 let cf = db
                     .cf_handle(cf)
                     .ok_or_else(|| SimpleDbError(format!("Missing column {cf}")))?;
+                let value = encrypt_value(*value_cipher, key.as_ref(), value.as_ref());
                 write.put_cf(cf, key, value);
             }
-            Self::InMemory { write, columns } => {
+            Self::InMemory {
+                write,
+                columns,
+                value_cipher,
+            } => {
                 let cf = columns
                     .get(cf)
                     .ok_or_else(|| SimpleDbError(format!("Missing column {cf}")))?;
-                write.push((*cf, key.as_ref().to_vec(), Some(value.as_ref().to_vec())));
+                let value = encrypt_value(*value_cipher, key.as_ref(), value.as_ref());
+                write.push((*cf, key.as_ref().to_vec(), Some(value.into_owned())));
             }
         }
         Ok(())
@@ -522,11 +773,11 @@ impl SimpleDbWriteBatch<'_> {
     /// * `key` - position in database to be deleted
     pub fn delete_cf<K: AsRef<[u8]>>(&mut self, cf: &'static str, key: K) {
         match self {
-            Self::File { write, db } => {
+            Self::File { write, db, .. } => {
                 let cf = db.cf_handle(cf).unwrap();
                 write.delete_cf(cf, key);
             }
-            Self::InMemory { write, columns } => {
+            Self::InMemory { write, columns, .. } => {
                 let cf = columns.get(cf).unwrap();
                 write.push((*cf, key.as_ref().to_vec(), None));
             }
@@ -534,6 +785,18 @@ impl SimpleDbWriteBatch<'_> {
     }
 }
 
+/// Value to write at the given key, encrypted if the database has a cipher
+fn encrypt_value<'a>(
+    cipher: Option<&dyn ValueCipher>,
+    key: &[u8],
+    value: &'a [u8],
+) -> Cow<'a, [u8]> {
+    match cipher {
+        Some(cipher) => Cow::Owned(cipher.encrypt(key, value)),
+        None => Cow::Borrowed(value),
+    }
+}
+
 /// Add initial state to freshly created DB: Version.
 fn with_initial_data(mut db: SimpleDb) -> Result<SimpleDb> {
     if let Some(version) = db.get_cf(DB_COL_DEFAULT, DB_VERSION_KEY)? {
@@ -554,6 +817,34 @@ fn check_version(db: &SimpleDb, expected: Option<&[u8]>) -> Result<()> {
     }
 }
 
+/// Check the database belongs to the network, recording it on first use.
+///
+/// ### Arguments
+///
+/// * `db`         - Database to check.
+/// * `network_id` - Network of the node opening the database.
+pub fn check_network_id(db: &mut SimpleDb, network_id: NetworkId) -> Result<()> {
+    let expected = bincode::serialize(&network_id).unwrap();
+    match db.get_cf(DB_COL_DEFAULT, DB_NETWORK_ID_KEY)? {
+        Some(actual) if actual == expected => Ok(()),
+        Some(actual) => {
+            warn!("DB Network mismatch {:?} != {:?}", actual, expected);
+            Err(SimpleDbError("DB Network mismatch".to_owned()))
+        }
+        None => db.put_cf(DB_COL_DEFAULT, DB_NETWORK_ID_KEY, &expected),
+    }
+}
+
+/// Network recorded in the database, if any
+///
+/// ### Arguments
+///
+/// * `db` - Database to read.
+pub fn get_network_id(db: &SimpleDb) -> Result<Option<NetworkId>> {
+    let network_id = db.get_cf(DB_COL_DEFAULT, DB_NETWORK_ID_KEY)?;
+    Ok(network_id.and_then(|v| bincode::deserialize(&v).ok()))
+}
+
 /// Creates a set of DB opening options for rocksDB instances
 fn get_db_options() -> Options {
     let mut opts = Options::default();
@@ -671,7 +962,7 @@ pub fn new_db_save_path(
     }
 }
 
-/// Restore backup for file db
+/// Restore backup for file db, once checked against its manifest
 pub fn restore_file_backup(
     db_mode: DbMode,
     db_spec: &SimpleDbSpec,
@@ -683,6 +974,8 @@ pub fn restore_file_backup(
         let backup_opts = BackupEngineOptions::new(backup_path.clone()).unwrap();
         let mut backup_engine = BackupEngine::open(&backup_opts, &env).unwrap();
 
+        snapshot_manifest::verify_manifest(&path, &backup_path)?;
+
         let mut restore_option = rocksdb::backup::RestoreOptions::default();
         restore_option.set_keep_log_files(true);
 
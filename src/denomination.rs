@@ -0,0 +1,274 @@
+//! Token amounts as displayed to and entered by users.
+//!
+//! A `TokenAmount` counts the smallest units of the token, `D_DISPLAY_PLACES_U64`
+//! of them making a token. Users read and enter amounts in tokens with a
+//! configurable number of decimal places, converted exactly to and from units:
+//! a value that is not a whole number of units is rejected rather than rounded.
+
+use crate::constants::D_DISPLAY_PLACES_U64;
+use serde::de::{self, Deserializer, Visitor};
+use std::convert::TryFrom;
+use std::fmt;
+use tw_chain::primitives::asset::TokenAmount;
+
+/// Decimal places of the displayed token amounts, when not configured
+pub const DEFAULT_DECIMAL_PLACES: u32 = 8;
+
+/// Maximum decimal places, so the scaling factor fits a u64
+pub const MAX_DECIMAL_PLACES: u32 = 18;
+
+/// Error parsing a token amount
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DenominationError {
+    /// Not a decimal number
+    Invalid(String),
+    /// More decimal places than configured
+    TooPrecise(String),
+    /// Not a whole number of units
+    NotRepresentable(String),
+    /// Larger than a token amount can hold
+    Overflow(String),
+}
+
+impl fmt::Display for DenominationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Invalid(v) => write!(f, "Invalid token amount: {v:?}"),
+            Self::TooPrecise(v) => write!(f, "Too many decimal places: {v:?}"),
+            Self::NotRepresentable(v) => write!(f, "Not a whole number of units: {v:?}"),
+            Self::Overflow(v) => write!(f, "Token amount too large: {v:?}"),
+        }
+    }
+}
+
+impl std::error::Error for DenominationError {}
+
+/// Conversion between token amounts in units and in tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Denomination {
+    /// Units making a token
+    pub units_per_token: u64,
+    /// Decimal places of the amounts in tokens
+    pub decimal_places: u32,
+}
+
+impl Default for Denomination {
+    fn default() -> Self {
+        Self {
+            units_per_token: D_DISPLAY_PLACES_U64,
+            decimal_places: DEFAULT_DECIMAL_PLACES,
+        }
+    }
+}
+
+impl Denomination {
+    /// Denomination of the token with the given decimal places
+    ///
+    /// ### Arguments
+    ///
+    /// * `decimal_places` - Decimal places of the amounts in tokens, capped to `MAX_DECIMAL_PLACES`
+    pub fn with_decimal_places(decimal_places: u32) -> Self {
+        Self {
+            decimal_places: decimal_places.min(MAX_DECIMAL_PLACES),
+            ..Default::default()
+        }
+    }
+
+    /// Parse an amount in tokens, such as `12` or `0.125`
+    ///
+    /// ### Arguments
+    ///
+    /// * `value` - Amount in tokens
+    pub fn parse(&self, value: &str) -> Result<TokenAmount, DenominationError> {
+        let invalid = || DenominationError::Invalid(value.to_owned());
+        let (whole, fraction) = value.trim().split_once('.').unwrap_or((value.trim(), ""));
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(invalid());
+        }
+        if fraction.len() > self.decimal_places as usize {
+            return Err(DenominationError::TooPrecise(value.to_owned()));
+        }
+
+        let overflow = || DenominationError::Overflow(value.to_owned());
+        let whole: u128 = whole.parse().map_err(|_| overflow())?;
+        let scale = 10u128.pow(fraction.len() as u32);
+        let fraction: u128 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse().map_err(|_| invalid())?
+        };
+
+        let fraction_units = fraction * self.units_per_token as u128;
+        if fraction_units % scale != 0 {
+            return Err(DenominationError::NotRepresentable(value.to_owned()));
+        }
+        let units = whole
+            .checked_mul(self.units_per_token as u128)
+            .and_then(|w| w.checked_add(fraction_units / scale))
+            .and_then(|u| u64::try_from(u).ok())
+            .ok_or_else(overflow)?;
+        Ok(TokenAmount(units))
+    }
+
+    /// Format an amount in tokens, without trailing zeros, truncated to the decimal places
+    ///
+    /// ### Arguments
+    ///
+    /// * `amount` - Amount in units
+    pub fn format(&self, amount: TokenAmount) -> String {
+        let whole = amount.0 / self.units_per_token;
+        let remainder = (amount.0 % self.units_per_token) as u128;
+        let scale = 10u128.pow(self.decimal_places);
+        let fraction = remainder * scale / self.units_per_token as u128;
+
+        let fraction = format!("{:0width$}", fraction, width = self.decimal_places as usize);
+        match fraction.trim_end_matches('0') {
+            "" => whole.to_string(),
+            fraction => format!("{whole}.{fraction}"),
+        }
+    }
+
+    /// Approximate amount in tokens, for display
+    ///
+    /// ### Arguments
+    ///
+    /// * `amount` - Amount in units
+    pub fn to_tokens_f64(&self, amount: TokenAmount) -> f64 {
+        amount.0 as f64 / self.units_per_token as f64
+    }
+}
+
+/// Sum of two amounts, None on overflow
+pub fn checked_add(a: TokenAmount, b: TokenAmount) -> Option<TokenAmount> {
+    a.0.checked_add(b.0).map(TokenAmount)
+}
+
+/// Difference of two amounts, None if `b` is larger than `a`
+pub fn checked_sub(a: TokenAmount, b: TokenAmount) -> Option<TokenAmount> {
+    a.0.checked_sub(b.0).map(TokenAmount)
+}
+
+/// Amount multiplied by a count, None on overflow
+pub fn checked_mul(a: TokenAmount, count: u64) -> Option<TokenAmount> {
+    a.0.checked_mul(count).map(TokenAmount)
+}
+
+/// Sum of amounts, None on overflow
+pub fn checked_sum(amounts: impl IntoIterator<Item = TokenAmount>) -> Option<TokenAmount> {
+    amounts.into_iter().try_fold(TokenAmount(0), checked_add)
+}
+
+/// Deserialize a token amount given in units as a number, or in tokens as a string
+///
+/// Used with `#[serde(deserialize_with = "...")]` on API request fields, so
+/// `1000` is 1000 units while `"0.5"` is half a token.
+pub fn deserialize_token_amount<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<TokenAmount, D::Error> {
+    struct AmountVisitor;
+
+    impl<'de> Visitor<'de> for AmountVisitor {
+        type Value = TokenAmount;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an amount in units or a string amount in tokens")
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<TokenAmount, E> {
+            Ok(TokenAmount(value))
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<TokenAmount, E> {
+            Denomination::default().parse(value).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_any(AmountVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks amounts in tokens are converted exactly to units and back
+    fn denomination_parse_and_format() {
+        //Arrange
+        let denomination = Denomination {
+            units_per_token: 1000,
+            decimal_places: 3,
+        };
+
+        //Act
+        let parsed: Vec<_> = ["12", "0.5", "1.125", " 3.0 "]
+            .iter()
+            .map(|v| denomination.parse(v).unwrap())
+            .collect();
+        let formatted: Vec<_> = parsed.iter().map(|a| denomination.format(*a)).collect();
+
+        //Assert
+        assert_eq!(
+            parsed,
+            vec![
+                TokenAmount(12000),
+                TokenAmount(500),
+                TokenAmount(1125),
+                TokenAmount(3000)
+            ]
+        );
+        assert_eq!(formatted, vec!["12", "0.5", "1.125", "3"]);
+        assert_eq!(denomination.format(TokenAmount(1)), "0.001");
+    }
+
+    #[test]
+    /// Checks malformed, too precise, inexact and too large amounts are rejected
+    fn denomination_parse_rejects_invalid() {
+        //Arrange
+        let denomination = Denomination {
+            units_per_token: 8,
+            decimal_places: 2,
+        };
+
+        //Act
+        let results: Vec<_> = [
+            "",
+            ".5",
+            "1.2.3",
+            "-1",
+            "0.125",
+            "0.01",
+            "3000000000000000000",
+        ]
+        .iter()
+        .map(|v| denomination.parse(v))
+        .collect();
+
+        //Assert
+        assert!(matches!(results[0], Err(DenominationError::Invalid(_))));
+        assert!(matches!(results[1], Err(DenominationError::Invalid(_))));
+        assert!(matches!(results[2], Err(DenominationError::Invalid(_))));
+        assert!(matches!(results[3], Err(DenominationError::Invalid(_))));
+        assert!(matches!(results[4], Err(DenominationError::TooPrecise(_))));
+        assert!(matches!(
+            results[5],
+            Err(DenominationError::NotRepresentable(_))
+        ));
+        assert!(matches!(results[6], Err(DenominationError::Overflow(_))));
+    }
+
+    #[test]
+    /// Checks the arithmetic wrappers report overflows
+    fn token_amount_checked_arithmetic() {
+        let max = TokenAmount(u64::MAX);
+
+        assert_eq!(
+            checked_add(TokenAmount(1), TokenAmount(2)),
+            Some(TokenAmount(3))
+        );
+        assert_eq!(checked_add(max, TokenAmount(1)), None);
+        assert_eq!(checked_sub(TokenAmount(1), TokenAmount(2)), None);
+        assert_eq!(checked_mul(TokenAmount(2), 3), Some(TokenAmount(6)));
+        assert_eq!(checked_sum(vec![max, TokenAmount(1)]), None);
+    }
+}
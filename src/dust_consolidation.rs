@@ -0,0 +1,191 @@
+//! Consolidation of the dust outputs of a user node wallet.
+//!
+//! Many small outputs make the payments spending them large. When started, the
+//! user node periodically checks its wallet for confirmed unspent outputs below
+//! the dust threshold, and once there are enough of them pays them to a single
+//! new address of the wallet. The consolidation only happens while the mempool
+//! is quiet, i.e. the last block notified holds few transactions, so it does not
+//! compete with the payments of other users.
+
+use crate::configurations::DustConsolidationConfig;
+use crate::wallet::LockedCoinbase;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Token amount below which an unspent output is dust, if not configured
+pub const DEFAULT_DUST_THRESHOLD: u64 = 1_000;
+
+/// Minimum number of dust outputs worth consolidating, if not configured
+pub const DEFAULT_DUST_MIN_OUTPUTS: usize = 10;
+
+/// Maximum number of inputs of a consolidation transaction, if not configured
+pub const DEFAULT_DUST_MAX_INPUTS: usize = 100;
+
+/// Seconds between checks for dust to consolidate, if not configured
+pub const DEFAULT_DUST_CHECK_INTERVAL: u64 = 600;
+
+/// Maximum number of transactions in the last block for the mempool to be quiet,
+/// if not configured
+pub const DEFAULT_DUST_QUIET_BLOCK_TXS: usize = 10;
+
+/// Background task consolidating the dust outputs of a wallet
+#[derive(Debug)]
+pub struct DustConsolidation {
+    dust_threshold: u64,
+    min_outputs: usize,
+    max_inputs: usize,
+    quiet_block_txs: usize,
+    check_interval: Duration,
+    /// Checks for dust to consolidate, `None` while stopped
+    interval: Option<Interval>,
+}
+
+impl DustConsolidation {
+    /// Create the task, started if configured
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Dust consolidation configuration, stopped with defaults if not set.
+    pub fn new(config: Option<&DustConsolidationConfig>) -> Self {
+        let settings = config.cloned().unwrap_or_default();
+        let check_interval = settings
+            .check_interval
+            .unwrap_or(DEFAULT_DUST_CHECK_INTERVAL)
+            .max(1);
+        let mut task = Self {
+            dust_threshold: settings.dust_threshold.unwrap_or(DEFAULT_DUST_THRESHOLD),
+            min_outputs: settings
+                .min_outputs
+                .unwrap_or(DEFAULT_DUST_MIN_OUTPUTS)
+                .max(2),
+            max_inputs: settings.max_inputs.unwrap_or(DEFAULT_DUST_MAX_INPUTS),
+            quiet_block_txs: settings
+                .quiet_block_txs
+                .unwrap_or(DEFAULT_DUST_QUIET_BLOCK_TXS),
+            check_interval: Duration::from_secs(check_interval),
+            interval: None,
+        };
+        task.set_active(config.is_some());
+        task
+    }
+
+    /// Start or stop the task
+    ///
+    /// ### Arguments
+    ///
+    /// * `active` - Whether to start the task.
+    pub fn set_active(&mut self, active: bool) {
+        if active == self.is_active() {
+            return;
+        }
+        self.interval = active.then(|| {
+            let period = self.check_interval;
+            let mut interval = interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+    }
+
+    /// Whether the task is started
+    pub fn is_active(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// Completes when the next check for dust is due
+    ///
+    /// Never completes while the task is stopped.
+    pub async fn tick(&mut self) {
+        match &mut self.interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Whether the mempool is quiet enough to consolidate
+    ///
+    /// ### Arguments
+    ///
+    /// * `block_txs` - Number of transactions in the last block notified.
+    pub fn is_quiet(&self, block_txs: usize) -> bool {
+        block_txs <= self.quiet_block_txs
+    }
+
+    /// Dust outputs to consolidate, smallest first, if there are enough of them
+    ///
+    /// ### Arguments
+    ///
+    /// * `outputs`         - Confirmed unspent outputs of the wallet.
+    /// * `locked_coinbase` - Coinbase transactions not yet spendable.
+    pub fn select_dust(
+        &self,
+        outputs: &BTreeMap<OutPoint, Asset>,
+        locked_coinbase: &LockedCoinbase,
+    ) -> Option<Vec<(OutPoint, Asset)>> {
+        let is_locked = |out_p: &OutPoint| {
+            locked_coinbase
+                .as_ref()
+                .is_some_and(|locked| locked.contains_key(&out_p.t_hash))
+        };
+        let mut dust: Vec<_> = outputs
+            .iter()
+            .filter(|(out_p, _)| !is_locked(out_p))
+            .filter_map(|(out_p, asset)| match asset {
+                Asset::Token(amount) if amount.0 < self.dust_threshold => {
+                    Some((out_p.clone(), asset.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        if dust.len() < self.min_outputs {
+            return None;
+        }
+
+        dust.sort_by_key(|(_, asset)| asset.token_amount().0);
+        dust.truncate(self.max_inputs.max(self.min_outputs));
+        Some(dust)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks only enough unlocked token outputs below the threshold are selected
+    async fn dust_selected_below_threshold() {
+        //Arrange
+        let config = DustConsolidationConfig {
+            dust_threshold: Some(10),
+            min_outputs: Some(3),
+            max_inputs: Some(3),
+            ..Default::default()
+        };
+        let mut task = DustConsolidation::new(Some(&config));
+        let out_p = |t_hash: &str, n| OutPoint::new(t_hash.to_owned(), n);
+        let mut outputs: BTreeMap<_, _> = [(0, 9), (1, 2), (2, 10), (3, 5), (4, 1)]
+            .iter()
+            .map(|(n, amount)| (out_p("tx", *n), Asset::token_u64(*amount)))
+            .collect();
+        outputs.insert(out_p("coinbase", 0), Asset::token_u64(1));
+        let locked_coinbase = Some(vec![("coinbase".to_owned(), 10)].into_iter().collect());
+
+        //Act
+        let dust = task.select_dust(&outputs, &locked_coinbase);
+        outputs.remove(&out_p("tx", 1));
+        outputs.remove(&out_p("tx", 4));
+        let too_few = task.select_dust(&outputs, &locked_coinbase);
+        task.set_active(false);
+
+        //Assert
+        let selected: Vec<_> = dust.unwrap().into_iter().map(|(o, _)| o.n).collect();
+        assert_eq!(selected, vec![4, 1, 3]);
+        assert!(too_few.is_none());
+        assert!(!task.is_active());
+        assert!(task.is_quiet(10) && !task.is_quiet(11));
+    }
+}
@@ -0,0 +1,431 @@
+//! Publication of node events to an external message bus.
+//!
+//! Nodes configured with an event bus publish the events they observe, such as
+//! stored blocks, received payments and accepted transactions, to a ZeroMQ PUB
+//! socket or a Kafka cluster, so downstream systems can react without polling
+//! the node APIs. Events are queued and published in the background: when the
+//! bus cannot keep up, new events are dropped rather than slowing the node.
+//!
+//! The ZeroMQ backend requires the `zmq` feature and the Kafka backend the
+//! `kafka` feature.
+
+use crate::configurations::{EventBusBackend, EventBusConfig, EventSerialization};
+use crate::event_hooks::EventHooks;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, warn};
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Events waiting to be published if not configured
+pub const DEFAULT_QUEUE_SIZE: usize = 1000;
+
+/// Result wrapper for event bus errors
+pub type Result<T> = std::result::Result<T, EventBusError>;
+
+#[derive(Debug)]
+pub enum EventBusError {
+    Unsupported(&'static str),
+    Serialization(String),
+    Backend(String),
+}
+
+impl fmt::Display for EventBusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(err) => write!(f, "Unsupported event bus: {err}"),
+            Self::Serialization(err) => write!(f, "Serialization error: {err}"),
+            Self::Backend(err) => write!(f, "Event bus error: {err}"),
+        }
+    }
+}
+
+impl Error for EventBusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// Event observed by a node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeEvent {
+    /// Block stored by a storage node
+    NewBlock {
+        b_num: u64,
+        block_hash: String,
+        transactions: Vec<String>,
+    },
+    /// Payment to an address of a user node wallet
+    PaymentReceived {
+        address: String,
+        out_point: OutPoint,
+        amount: Asset,
+        b_num: u64,
+    },
+    /// Transaction accepted into the pool of a mempool node
+    TxAccepted { tx_hash: String },
+    /// Stored block replaced by a different block at its height on a storage node
+    ChainReorg {
+        b_num: u64,
+        orphaned_hash: String,
+        block_hash: String,
+        dropped_transactions: Vec<String>,
+    },
+    /// Peer connected to a node, passed to the event hooks only
+    PeerConnected { address: SocketAddr },
+}
+
+/// Destination of the published events
+#[async_trait]
+pub trait EventSink: Send {
+    /// Publish an encoded event on a topic
+    ///
+    /// ### Arguments
+    ///
+    /// * `topic`   - Topic of the event.
+    /// * `payload` - Encoded event.
+    async fn send(&mut self, topic: String, payload: Vec<u8>) -> Result<()>;
+}
+
+/// Handle queuing events for publication
+#[derive(Debug, Clone)]
+pub struct EventPublisher {
+    tx: mpsc::Sender<(String, Vec<u8>)>,
+    topics: [String; 4],
+    serialization: EventSerialization,
+}
+
+impl EventPublisher {
+    /// Connect to the configured message bus and start publishing in the background
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Event bus configuration.
+    pub async fn start(config: &EventBusConfig) -> Result<Self> {
+        let sink = connect_sink(&config.backend).await?;
+        Ok(Self::with_sink(config, sink))
+    }
+
+    /// Start publishing to the given sink in the background
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Event bus configuration.
+    /// * `sink`   - Destination of the events.
+    pub fn with_sink(config: &EventBusConfig, mut sink: Box<dyn EventSink>) -> Self {
+        let queue_size = config.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE).max(1);
+        let (tx, mut rx) = mpsc::channel::<(String, Vec<u8>)>(queue_size);
+        tokio::spawn(async move {
+            while let Some((topic, payload)) = rx.recv().await {
+                if let Err(e) = sink.send(topic, payload).await {
+                    error!("Event not published: {}", e);
+                }
+            }
+        });
+
+        let topic = |topic: &Option<String>, default: &str| {
+            topic.clone().unwrap_or_else(|| default.to_owned())
+        };
+        Self {
+            tx,
+            topics: [
+                topic(&config.new_block_topic, "new_block"),
+                topic(&config.payment_received_topic, "payment_received"),
+                topic(&config.tx_accepted_topic, "tx_accepted"),
+                topic(&config.chain_reorg_topic, "chain_reorg"),
+            ],
+            serialization: config.serialization.unwrap_or(EventSerialization::Json),
+        }
+    }
+
+    /// Topic an event is published on, None if not published
+    ///
+    /// ### Arguments
+    ///
+    /// * `event` - Event to publish.
+    pub fn topic(&self, event: &NodeEvent) -> Option<&str> {
+        match event {
+            NodeEvent::NewBlock { .. } => Some(&self.topics[0]),
+            NodeEvent::PaymentReceived { .. } => Some(&self.topics[1]),
+            NodeEvent::TxAccepted { .. } => Some(&self.topics[2]),
+            NodeEvent::ChainReorg { .. } => Some(&self.topics[3]),
+            NodeEvent::PeerConnected { .. } => None,
+        }
+    }
+
+    /// Queue an event for publication, dropping it if the queue is full
+    ///
+    /// ### Arguments
+    ///
+    /// * `event` - Event to publish.
+    pub fn publish(&self, event: NodeEvent) {
+        let topic = match self.topic(&event) {
+            Some(topic) => topic.to_owned(),
+            None => return,
+        };
+        let payload = match encode_event(self.serialization, &event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Event not encoded: {}", e);
+                return;
+            }
+        };
+
+        match self.tx.try_send((topic, payload)) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => warn!("Event bus queue full, dropping {:?}", event),
+            Err(TrySendError::Closed(_)) => error!("Event bus stopped, dropping {:?}", event),
+        }
+    }
+}
+
+/// Publish an event if the node has an event bus, and run the node event hooks on it
+///
+/// ### Arguments
+///
+/// * `publisher` - Publisher of the node, if any.
+/// * `hooks`     - Event hooks of the node.
+/// * `event`     - Function constructing the event to publish.
+pub fn publish_event(
+    publisher: &Option<EventPublisher>,
+    hooks: &EventHooks,
+    event: impl FnOnce() -> NodeEvent,
+) {
+    if publisher.is_none() && hooks.is_empty() {
+        return;
+    }
+
+    let event = event();
+    hooks.dispatch(&event);
+    if let Some(publisher) = publisher {
+        publisher.publish(event);
+    }
+}
+
+/// Encode an event for publication
+///
+/// ### Arguments
+///
+/// * `serialization` - Encoding to use.
+/// * `event`         - Event to encode.
+pub fn encode_event(serialization: EventSerialization, event: &NodeEvent) -> Result<Vec<u8>> {
+    match serialization {
+        EventSerialization::Json => {
+            serde_json::to_vec(event).map_err(|e| EventBusError::Serialization(e.to_string()))
+        }
+        EventSerialization::Bincode => {
+            bincode::serialize(event).map_err(|e| EventBusError::Serialization(e.to_string()))
+        }
+    }
+}
+
+/// Connect to the configured message bus
+///
+/// ### Arguments
+///
+/// * `backend` - Message bus to connect to.
+async fn connect_sink(backend: &EventBusBackend) -> Result<Box<dyn EventSink>> {
+    match backend {
+        #[cfg(feature = "zmq")]
+        EventBusBackend::ZeroMq { endpoint } => {
+            Ok(Box::new(zmq_sink::ZmqSink::bind(endpoint).await?))
+        }
+        #[cfg(not(feature = "zmq"))]
+        EventBusBackend::ZeroMq { .. } => Err(EventBusError::Unsupported(
+            "ZeroMQ requires the zmq feature",
+        )),
+        #[cfg(feature = "kafka")]
+        EventBusBackend::Kafka { brokers } => Ok(Box::new(kafka_sink::KafkaSink::new(brokers)?)),
+        #[cfg(not(feature = "kafka"))]
+        EventBusBackend::Kafka { .. } => Err(EventBusError::Unsupported(
+            "Kafka requires the kafka feature",
+        )),
+    }
+}
+
+#[cfg(feature = "zmq")]
+mod zmq_sink {
+    use super::{EventBusError, EventSink, Result};
+    use async_trait::async_trait;
+    use zeromq::{PubSocket, Socket, SocketSend, ZmqMessage};
+
+    /// ZeroMQ PUB socket, sending the topic and the event as two frames
+    pub struct ZmqSink {
+        socket: PubSocket,
+    }
+
+    impl ZmqSink {
+        /// Bind a PUB socket for subscribers to connect to
+        ///
+        /// ### Arguments
+        ///
+        /// * `endpoint` - Endpoint to bind to.
+        pub async fn bind(endpoint: &str) -> Result<Self> {
+            let mut socket = PubSocket::new();
+            socket
+                .bind(endpoint)
+                .await
+                .map_err(|e| EventBusError::Backend(e.to_string()))?;
+            Ok(Self { socket })
+        }
+    }
+
+    #[async_trait]
+    impl EventSink for ZmqSink {
+        async fn send(&mut self, topic: String, payload: Vec<u8>) -> Result<()> {
+            let mut message = ZmqMessage::from(topic.into_bytes());
+            message.push_back(payload.into());
+            self.socket
+                .send(message)
+                .await
+                .map_err(|e| EventBusError::Backend(e.to_string()))
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka_sink {
+    use super::{EventBusError, EventSink, Result};
+    use async_trait::async_trait;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use std::time::Duration;
+
+    /// Time to wait for space in the producer queue
+    const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Kafka producer, sending each event as a record of its topic
+    pub struct KafkaSink {
+        producer: FutureProducer,
+    }
+
+    impl KafkaSink {
+        /// Create a producer for the cluster
+        ///
+        /// ### Arguments
+        ///
+        /// * `brokers` - Comma separated list of brokers.
+        pub fn new(brokers: &str) -> Result<Self> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .create()
+                .map_err(|e| EventBusError::Backend(e.to_string()))?;
+            Ok(Self { producer })
+        }
+    }
+
+    #[async_trait]
+    impl EventSink for KafkaSink {
+        async fn send(&mut self, topic: String, payload: Vec<u8>) -> Result<()> {
+            let record = FutureRecord::<(), _>::to(&topic).payload(&payload);
+            self.producer
+                .send(record, QUEUE_TIMEOUT)
+                .await
+                .map(|_| ())
+                .map_err(|(e, _)| EventBusError::Backend(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sink forwarding the events to a channel
+    struct ChannelSink(mpsc::UnboundedSender<(String, Vec<u8>)>);
+
+    #[async_trait]
+    impl EventSink for ChannelSink {
+        async fn send(&mut self, topic: String, payload: Vec<u8>) -> Result<()> {
+            self.0
+                .send((topic, payload))
+                .map_err(|e| EventBusError::Backend(e.to_string()))
+        }
+    }
+
+    fn config(serialization: Option<EventSerialization>) -> EventBusConfig {
+        EventBusConfig {
+            backend: EventBusBackend::ZeroMq {
+                endpoint: "tcp://127.0.0.1:5556".to_owned(),
+            },
+            new_block_topic: Some("blocks".to_owned()),
+            payment_received_topic: None,
+            tx_accepted_topic: None,
+            chain_reorg_topic: None,
+            serialization,
+            queue_size: None,
+        }
+    }
+
+    fn block_event() -> NodeEvent {
+        NodeEvent::NewBlock {
+            b_num: 1,
+            block_hash: "b000001".to_owned(),
+            transactions: vec!["g000001".to_owned()],
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks events are published on their topic, JSON encoded by default
+    async fn publish_json_events() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let publisher = EventPublisher::with_sink(&config(None), Box::new(ChannelSink(tx)));
+        let tx_event = NodeEvent::TxAccepted {
+            tx_hash: "g000001".to_owned(),
+        };
+        let reorg_event = NodeEvent::ChainReorg {
+            b_num: 1,
+            orphaned_hash: "b000001".to_owned(),
+            block_hash: "b000002".to_owned(),
+            dropped_transactions: vec!["g000001".to_owned()],
+        };
+
+        publisher.publish(block_event());
+        publisher.publish(tx_event.clone());
+        publisher.publish(reorg_event.clone());
+
+        let (block_topic, block_payload) = rx.recv().await.unwrap();
+        let (tx_topic, tx_payload) = rx.recv().await.unwrap();
+        let (reorg_topic, reorg_payload) = rx.recv().await.unwrap();
+        let block: NodeEvent = serde_json::from_slice(&block_payload).unwrap();
+        let tx: NodeEvent = serde_json::from_slice(&tx_payload).unwrap();
+        let reorg: NodeEvent = serde_json::from_slice(&reorg_payload).unwrap();
+        assert_eq!((block_topic.as_str(), block), ("blocks", block_event()));
+        assert_eq!((tx_topic.as_str(), tx), ("tx_accepted", tx_event));
+        assert_eq!((reorg_topic.as_str(), reorg), ("chain_reorg", reorg_event));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks events can be bincode encoded
+    async fn publish_bincode_events() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let config = config(Some(EventSerialization::Bincode));
+        let publisher = EventPublisher::with_sink(&config, Box::new(ChannelSink(tx)));
+
+        publisher.publish(block_event());
+
+        let (_, payload) = rx.recv().await.unwrap();
+        let block: NodeEvent = bincode::deserialize(&payload).unwrap();
+        assert_eq!(block, block_event());
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a backend not compiled in is reported
+    async fn start_unsupported_backend() {
+        let config = EventBusConfig {
+            backend: EventBusBackend::Kafka {
+                brokers: "127.0.0.1:9092".to_owned(),
+            },
+            ..config(None)
+        };
+
+        let result = EventPublisher::start(&config).await;
+
+        assert!(matches!(result, Err(EventBusError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,131 @@
+//! Callbacks run on node events.
+//!
+//! Embedders register async callbacks on the events a node observes, such as
+//! stored blocks, accepted transactions, received payments and connected peers,
+//! to add their own behavior without changing the node event loops. Each
+//! callback runs in its own task: a slow callback does not delay the node, and
+//! a panicking one is logged without affecting the node or the other callbacks.
+
+use crate::event_bus::NodeEvent;
+use futures::FutureExt;
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use tracing::error;
+
+/// Future run by an event hook
+pub type EventHookFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Callback run on each event of a node
+type EventHook = Arc<dyn Fn(NodeEvent) -> EventHookFuture + Send + Sync>;
+
+/// Callbacks run on the events of a node
+#[derive(Clone, Default)]
+pub struct EventHooks {
+    hooks: Vec<EventHook>,
+}
+
+impl EventHooks {
+    /// Run the given callback on each event
+    ///
+    /// ### Arguments
+    ///
+    /// * `hook` - Async callback invoked with each event
+    pub fn register<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(NodeEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks
+            .push(Arc::new(move |event| Box::pin(hook(event))));
+    }
+
+    /// Whether no callback is registered
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Run each callback on the event in its own task
+    ///
+    /// ### Arguments
+    ///
+    /// * `event` - Event observed by the node
+    pub fn dispatch(&self, event: &NodeEvent) {
+        for hook in &self.hooks {
+            let hook = hook.clone();
+            let event = event.clone();
+            tokio::spawn(async move {
+                let run = AssertUnwindSafe(async move { hook(event).await }).catch_unwind();
+                if let Err(payload) = run.await {
+                    error!("Event hook panicked: {}", panic_message(payload));
+                }
+            });
+        }
+    }
+}
+
+impl fmt::Debug for EventHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EventHooks({})", self.hooks.len())
+    }
+}
+
+/// Run the hooks on an event, constructing it only if a hook is registered
+///
+/// ### Arguments
+///
+/// * `hooks` - Hooks of the node.
+/// * `event` - Function constructing the event.
+pub fn dispatch_event(hooks: &EventHooks, event: impl FnOnce() -> NodeEvent) {
+    if !hooks.is_empty() {
+        hooks.dispatch(&event());
+    }
+}
+
+/// Message of a caught panic
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Unknown panic".to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn tx_event() -> NodeEvent {
+        NodeEvent::TxAccepted {
+            tx_hash: "g000001".to_owned(),
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks every hook runs on the event, even when another hook panics
+    async fn dispatch_isolates_panicking_hooks() {
+        //Arrange
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut hooks = EventHooks::default();
+        hooks.register(|_| async { panic!("hook failure") });
+        hooks.register(move |event| {
+            let tx = tx.clone();
+            async move {
+                tx.send(event).unwrap();
+            }
+        });
+
+        //Act
+        dispatch_event(&hooks, tx_event);
+        let received = rx.recv().await;
+
+        //Assert
+        assert_eq!(received, Some(tx_event()));
+    }
+}
@@ -0,0 +1,335 @@
+//! Exchange deposit handling for user nodes.
+//!
+//! In exchange mode, the user node hands out a deposit address per customer,
+//! tagged with the customer external id. Deposits are confirmed once they are in
+//! the UTXO set of the mempool node, requested for the deposit addresses on each
+//! mined block, and each new deposit is posted to the configured webhook.
+//! Deposit addresses holding at least the sweep threshold are periodically swept
+//! into the cold address, in transactions with a bounded number of inputs.
+
+use crate::configurations::UserExchangeConfig;
+use crate::wallet::{WalletDb, WalletDbError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::primitives::transaction::OutPoint;
+use url::Url;
+
+/// Key for the deposit addresses and their customer external id
+pub const DEPOSIT_ACCOUNTS_KEY: &str = "ExchangeDepositAccountsKey";
+
+/// Key for the deposits confirmed and swept by the node
+pub const DEPOSIT_TRACKING_KEY: &str = "ExchangeDepositTrackingKey";
+
+/// Customer external id by deposit address
+pub type DepositAccounts = BTreeMap<String, String>;
+
+/// Deposits confirmed and swept by the node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositTracking {
+    /// Deposit outputs already confirmed
+    pub confirmed: BTreeSet<OutPoint>,
+    /// Total confirmed by deposit address
+    pub confirmed_totals: BTreeMap<String, TokenAmount>,
+    /// Deposit outputs spent by a sweep, with the block number of the sweep
+    pub sweeping: BTreeMap<OutPoint, u64>,
+    /// Block number of the last sweep
+    pub last_sweep_b_num: Option<u64>,
+}
+
+/// Deposit confirmed in the UTXO set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfirmedDeposit {
+    pub external_id: String,
+    pub address: String,
+    pub out_point: OutPoint,
+    pub amount: TokenAmount,
+    pub b_num: u64,
+}
+
+impl DepositTracking {
+    /// Record the deposits among received payments, returning the newly confirmed ones
+    ///
+    /// ### Arguments
+    ///
+    /// * `accounts` - Deposit addresses and their customer external id.
+    /// * `payments` - Payments OutPoint, amount and receiver key address.
+    /// * `b_num`    - Current block number.
+    pub fn confirm_deposits(
+        &mut self,
+        accounts: &DepositAccounts,
+        payments: &[(OutPoint, Asset, String, u64)],
+        b_num: u64,
+    ) -> Vec<ConfirmedDeposit> {
+        let mut deposits = Vec::new();
+        for (out_point, asset, address, _) in payments {
+            let (external_id, amount) = match (accounts.get(address), asset) {
+                (Some(external_id), Asset::Token(amount)) => (external_id, *amount),
+                _ => continue,
+            };
+            if !self.confirmed.insert(out_point.clone()) {
+                continue;
+            }
+
+            self.confirmed_totals.entry(address.clone()).or_default().0 += amount.0;
+            deposits.push(ConfirmedDeposit {
+                external_id: external_id.clone(),
+                address: address.clone(),
+                out_point: out_point.clone(),
+                amount,
+                b_num,
+            });
+        }
+        deposits
+    }
+
+    /// Whether a sweep is due at the given block
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Exchange configuration.
+    /// * `b_num`  - Current block number.
+    pub fn is_sweep_due(&self, config: &UserExchangeConfig, b_num: u64) -> bool {
+        match self.last_sweep_b_num {
+            Some(last) => b_num >= last.saturating_add(config.sweep_interval),
+            None => true,
+        }
+    }
+
+    /// Start a sweep at the given block, allowing outputs of older sweeps to be swept again
+    ///
+    /// Outputs of a sweep that was not committed after a full interval are back
+    /// in the wallet and can be retried.
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Exchange configuration.
+    /// * `b_num`  - Current block number.
+    pub fn start_sweep(&mut self, config: &UserExchangeConfig, b_num: u64) {
+        let interval = config.sweep_interval;
+        self.sweeping
+            .retain(|_, swept| swept.saturating_add(interval) > b_num);
+        self.last_sweep_b_num = Some(b_num);
+    }
+}
+
+/// Group the deposit outputs to sweep into transactions inputs
+///
+/// Only addresses holding at least the sweep threshold are swept, and each
+/// batch holds at most the configured number of inputs.
+///
+/// ### Arguments
+///
+/// * `config`  - Exchange configuration.
+/// * `outputs` - Unspent deposit outputs, with their address and amount.
+pub fn sweep_batches(
+    config: &UserExchangeConfig,
+    outputs: Vec<(OutPoint, String, TokenAmount)>,
+) -> Vec<Vec<(OutPoint, Asset)>> {
+    let mut by_address: BTreeMap<String, (u64, Vec<(OutPoint, Asset)>)> = BTreeMap::new();
+    for (out_point, address, amount) in outputs {
+        let (total, address_outputs) = by_address.entry(address).or_default();
+        *total += amount.0;
+        address_outputs.push((out_point, Asset::Token(amount)));
+    }
+
+    let to_sweep: Vec<_> = by_address
+        .into_values()
+        .filter(|(total, _)| *total >= config.sweep_threshold)
+        .flat_map(|(_, address_outputs)| address_outputs)
+        .collect();
+
+    let max_inputs = config.sweep_max_inputs.max(1);
+    to_sweep.chunks(max_inputs).map(|c| c.to_vec()).collect()
+}
+
+/// Load the deposit addresses and their customer external id
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the deposit addresses.
+pub async fn load_deposit_accounts(wallet_db: &WalletDb) -> Result<DepositAccounts, WalletDbError> {
+    let accounts = wallet_db.get_db_value(DEPOSIT_ACCOUNTS_KEY).await?;
+    Ok(accounts
+        .and_then(|v| bincode::deserialize(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Generate a deposit address for a customer
+///
+/// ### Arguments
+///
+/// * `wallet_db`   - Wallet holding the deposit addresses.
+/// * `external_id` - Customer external id.
+pub async fn add_deposit_account(wallet_db: &mut WalletDb, external_id: String) -> Result<String, WalletDbError> {
+    let (address, _) = wallet_db.generate_payment_address().await?;
+    let mut accounts = load_deposit_accounts(wallet_db).await?;
+    accounts.insert(address.clone(), external_id);
+
+    let accounts = bincode::serialize(&accounts).unwrap();
+    wallet_db.set_db_value(DEPOSIT_ACCOUNTS_KEY, accounts).await?;
+    Ok(address)
+}
+
+/// Load the deposits confirmed and swept by the node
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the deposit addresses.
+pub async fn load_deposit_tracking(wallet_db: &WalletDb) -> Result<DepositTracking, WalletDbError> {
+    let tracking = wallet_db.get_db_value(DEPOSIT_TRACKING_KEY).await?;
+    Ok(tracking
+        .and_then(|v| bincode::deserialize(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Save the deposits confirmed and swept by the node
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the deposit addresses.
+/// * `tracking`  - Deposits confirmed and swept.
+pub async fn save_deposit_tracking(wallet_db: &WalletDb, tracking: &DepositTracking) -> Result<(), WalletDbError> {
+    let tracking = bincode::serialize(tracking).unwrap();
+    wallet_db.set_db_value(DEPOSIT_TRACKING_KEY, tracking).await
+}
+
+/// Post a JSON notification to an HTTP webhook
+///
+/// ### Arguments
+///
+/// * `url`  - URL of the webhook, using the `http` scheme.
+/// * `body` - Notification to post.
+pub async fn post_webhook(url: &str, body: &serde_json::Value) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    if url.scheme() != "http" {
+        return Err(format!("Unsupported webhook scheme: {}", url.scheme()));
+    }
+    let host = url.host_str().ok_or("Webhook URL without host")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    };
+
+    let body = body.to_string();
+    let request = format!(
+        "POST {path} HTTP/1.1\r\n\
+         Host: {host}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n\
+         {body}",
+        body.len()
+    );
+
+    let mut stream = TcpStream::connect((host, port))
+        .await
+        .map_err(|e| e.to_string())?;
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("Webhook rejected notification: {status:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(sweep_threshold: u64, sweep_max_inputs: usize) -> UserExchangeConfig {
+        UserExchangeConfig {
+            cold_address: "cold".to_owned(),
+            sweep_threshold,
+            sweep_interval: 10,
+            sweep_max_inputs,
+            webhook_url: None,
+        }
+    }
+
+    fn out_point(n: i32) -> OutPoint {
+        OutPoint::new("000000".to_owned(), n)
+    }
+
+    #[test]
+    /// Checks deposits to deposit addresses are confirmed once
+    fn confirm_deposits_once() {
+        let accounts: DepositAccounts = Some(("addr".to_owned(), "customer".to_owned()))
+            .into_iter()
+            .collect();
+        let payments = vec![
+            (out_point(0), Asset::token_u64(5), "addr".to_owned(), 0),
+            (out_point(1), Asset::token_u64(3), "other".to_owned(), 0),
+        ];
+        let mut tracking = DepositTracking::default();
+
+        let first = tracking.confirm_deposits(&accounts, &payments, 2);
+        let second = tracking.confirm_deposits(&accounts, &payments, 3);
+
+        assert_eq!(
+            first,
+            vec![ConfirmedDeposit {
+                external_id: "customer".to_owned(),
+                address: "addr".to_owned(),
+                out_point: out_point(0),
+                amount: TokenAmount(5),
+                b_num: 2,
+            }]
+        );
+        assert!(second.is_empty());
+        assert_eq!(tracking.confirmed_totals["addr"], TokenAmount(5));
+    }
+
+    #[test]
+    /// Checks only addresses above the threshold are swept, in bounded batches
+    fn sweep_batches_above_threshold() {
+        let outputs = vec![
+            (out_point(0), "a".to_owned(), TokenAmount(4)),
+            (out_point(1), "a".to_owned(), TokenAmount(4)),
+            (out_point(2), "b".to_owned(), TokenAmount(5)),
+            (out_point(3), "c".to_owned(), TokenAmount(10)),
+        ];
+
+        let batches = sweep_batches(&config(6, 2), outputs);
+
+        let batches: Vec<Vec<_>> = batches
+            .into_iter()
+            .map(|b| b.into_iter().map(|(o, _)| o.n).collect())
+            .collect();
+        assert_eq!(batches, vec![vec![0, 1], vec![3]]);
+    }
+
+    #[test]
+    /// Checks sweeps are due every interval, and stale sweeps can be retried
+    fn sweep_interval_and_retry() {
+        let config = config(1, 1);
+        let mut tracking = DepositTracking::default();
+        tracking.sweeping.insert(out_point(0), 0);
+
+        let due_first = tracking.is_sweep_due(&config, 0);
+        tracking.start_sweep(&config, 0);
+        let due_early = tracking.is_sweep_due(&config, 9);
+        let due_later = tracking.is_sweep_due(&config, 10);
+        let retried_early = tracking.sweeping.contains_key(&out_point(0));
+        tracking.start_sweep(&config, 10);
+
+        assert!(due_first);
+        assert!(!due_early);
+        assert!(due_later);
+        assert!(retried_early);
+        assert!(tracking.sweeping.is_empty());
+    }
+}
@@ -0,0 +1,365 @@
+//! C ABI bindings for wallet operations.
+//!
+//! Exposes address generation and validation, signing and payment construction
+//! over an opaque `AiblockWallet` handle, so point-of-sale software in C or C++
+//! can use a wallet without going through the HTTP API. Declarations are in
+//! `include/aiblock_wallet.h`.
+//!
+//! Strings returned by these functions are owned by the caller and must be
+//! released with `aiblock_string_free`. Functions return null or false on
+//! failure, with the reason available from `aiblock_last_error`.
+
+use crate::configurations::DbMode;
+use crate::db_utils::CustomDbSpec;
+use crate::wallet::WalletDb;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+use tokio::runtime::{Builder, Runtime};
+use tw_chain::crypto::sign_ed25519 as sign;
+use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::primitives::transaction::{OutPoint, TxOut};
+use tw_chain::utils::transaction_utils::{construct_payment_tx_ins, construct_tx_core};
+use wallet_core::PaymentAddress;
+
+thread_local! {
+    /// Reason of the last failure on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opaque wallet handle
+pub struct AiblockWallet {
+    runtime: Runtime,
+    wallet: WalletDb,
+}
+
+/// Open a wallet database, or an in-memory wallet if `path` is null
+///
+/// Returns null on failure. The handle must be released with `aiblock_wallet_free`.
+///
+/// # Safety
+///
+/// `path` and `passphrase` must be null or valid NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_open(
+    path: *const c_char,
+    passphrase: *const c_char,
+) -> *mut AiblockWallet {
+    let open = || -> Result<AiblockWallet, String> {
+        let (db_mode, custom_db_spec) = match opt_str(path)? {
+            Some(db_path) => (
+                DbMode::Live,
+                Some(CustomDbSpec {
+                    db_path,
+                    suffix: String::new(),
+                }),
+            ),
+            None => (DbMode::InMemory, None),
+        };
+        let passphrase = opt_str(passphrase)?;
+
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let wallet =
+            WalletDb::new(db_mode, None, passphrase, custom_db_spec).map_err(|e| e.to_string())?;
+        Ok(AiblockWallet { runtime, wallet })
+    };
+
+    match open() {
+        Ok(wallet) => Box::into_raw(Box::new(wallet)),
+        Err(e) => fail(e, ptr::null_mut()),
+    }
+}
+
+/// Release a wallet handle
+///
+/// # Safety
+///
+/// `wallet` must be null or a handle returned by `aiblock_wallet_open`, not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_free(wallet: *mut AiblockWallet) {
+    if !wallet.is_null() {
+        drop(Box::from_raw(wallet));
+    }
+}
+
+/// Generate a new address, saving its keys to the wallet
+///
+/// The address is returned as a checked address of the wallet network.
+///
+/// # Safety
+///
+/// `wallet` must be a handle returned by `aiblock_wallet_open`.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_new_address(wallet: *mut AiblockWallet) -> *mut c_char {
+    let AiblockWallet { runtime, wallet } = &mut *wallet;
+    match runtime.block_on(wallet.generate_checked_payment_address()) {
+        Ok(address) => to_c_string(address),
+        Err(e) => fail(e.to_string(), ptr::null_mut()),
+    }
+}
+
+/// Check a string is an address that can be paid to
+///
+/// The checksum of checked addresses is checked.
+///
+/// # Safety
+///
+/// `address` must be a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_address_is_valid(address: *const c_char) -> bool {
+    match opt_str(address) {
+        Ok(Some(address)) => PaymentAddress::validate(&address).is_ok(),
+        _ => false,
+    }
+}
+
+/// Record an output paid to an address of the wallet, so it can be spent
+///
+/// # Safety
+///
+/// `wallet` must be a handle returned by `aiblock_wallet_open`,
+/// `tx_hash` and `address` valid NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_add_output(
+    wallet: *mut AiblockWallet,
+    tx_hash: *const c_char,
+    index: i32,
+    amount: u64,
+    address: *const c_char,
+) -> bool {
+    let AiblockWallet { runtime, wallet } = &mut *wallet;
+    let (tx_hash, address) = match (req_str(tx_hash), req_address(wallet, address)) {
+        (Ok(tx_hash), Ok(address)) => (tx_hash, address),
+        (Err(e), _) | (_, Err(e)) => return fail(e, false),
+    };
+
+    let out_p = OutPoint::new(tx_hash, index);
+    let payments = vec![(out_p, Asset::Token(TokenAmount(amount)), address, 0)];
+    match runtime.block_on(wallet.save_usable_payments_to_wallet(payments, 0)) {
+        Ok(saved) if !saved.is_empty() => true,
+        Ok(_) => fail("Address not in wallet".to_owned(), false),
+        Err(e) => fail(e.to_string(), false),
+    }
+}
+
+/// Sign data with the key of an address, returning the hex encoded signature
+///
+/// # Safety
+///
+/// `wallet` must be a handle returned by `aiblock_wallet_open`, `address` a valid
+/// NUL terminated string and `data` point to `data_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_sign(
+    wallet: *mut AiblockWallet,
+    address: *const c_char,
+    data: *const u8,
+    data_len: usize,
+) -> *mut c_char {
+    let AiblockWallet { wallet, .. } = &*wallet;
+    let address = match req_address(wallet, address) {
+        Ok(address) => address,
+        Err(e) => return fail(e, ptr::null_mut()),
+    };
+    match wallet.get_known_addresses() {
+        Ok(known) if known.contains(&address) => (),
+        Ok(_) => return fail(format!("Address not in wallet: {address}"), ptr::null_mut()),
+        Err(e) => return fail(e.to_string(), ptr::null_mut()),
+    }
+
+    let data = slice::from_raw_parts(data, data_len);
+    let keys = match wallet.get_address_store(&address) {
+        Ok(keys) => keys,
+        Err(e) => return fail(e.to_string(), ptr::null_mut()),
+    };
+    let signature = sign::sign_detached(data, &keys.secret_key);
+    to_c_string(hex::encode(signature.as_ref()))
+}
+
+/// Sign an output of the wallet as a transaction input, returning the JSON encoded `TxIn`
+///
+/// # Safety
+///
+/// `wallet` must be a handle returned by `aiblock_wallet_open`,
+/// `tx_hash` a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_sign_input(
+    wallet: *mut AiblockWallet,
+    tx_hash: *const c_char,
+    index: i32,
+) -> *mut c_char {
+    let AiblockWallet { wallet, .. } = &*wallet;
+    let out_p = match req_str(tx_hash) {
+        Ok(tx_hash) => OutPoint::new(tx_hash, index),
+        Err(e) => return fail(e, ptr::null_mut()),
+    };
+    match wallet.get_fund_store() {
+        Ok(fund_store) if fund_store.transactions().contains_key(&out_p) => (),
+        Ok(_) => return fail(format!("Output not in wallet: {out_p:?}"), ptr::null_mut()),
+        Err(e) => return fail(e.to_string(), ptr::null_mut()),
+    }
+
+    let keys = match wallet
+        .get_transaction_address(&out_p)
+        .and_then(|address| wallet.get_address_store(&address))
+    {
+        Ok(keys) => keys.into(),
+        Err(e) => return fail(e.to_string(), ptr::null_mut()),
+    };
+    let tx_in = construct_payment_tx_ins(vec![wallet_core::sign_input(out_p, &keys)]);
+    to_c_json(&tx_in[0])
+}
+
+/// Construct a signed payment from the wallet funds, returning the JSON encoded transaction
+///
+/// The spent outputs are marked as spent in the wallet. The change goes to
+/// `excess_address`, or to a new address of the wallet if it is null.
+///
+/// # Safety
+///
+/// `wallet` must be a handle returned by `aiblock_wallet_open`, `address` a valid
+/// NUL terminated string and `excess_address` null or a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_wallet_make_payment(
+    wallet: *mut AiblockWallet,
+    address: *const c_char,
+    amount: u64,
+    excess_address: *const c_char,
+) -> *mut c_char {
+    let AiblockWallet { runtime, wallet } = &mut *wallet;
+    let excess_address = opt_str(excess_address).and_then(|excess_address| {
+        let decode = |a: String| wallet.decode_payment_address(&a).map_err(|e| e.to_string());
+        excess_address.map(decode).transpose()
+    });
+    let (address, excess_address) = match (req_address(wallet, address), excess_address) {
+        (Ok(address), Ok(excess_address)) => (address, excess_address),
+        (Err(e), _) | (_, Err(e)) => return fail(e, ptr::null_mut()),
+    };
+
+    let amount = TokenAmount(amount);
+    let tx_out = TxOut::new_token_amount(address, amount, None);
+    let inputs = runtime.block_on(wallet.fetch_tx_ins_and_tx_outs_provided_excess(
+        Asset::Token(amount),
+        vec![tx_out],
+        excess_address,
+    ));
+    match inputs {
+        Ok((tx_ins, tx_outs)) => to_c_json(&construct_tx_core(tx_ins, tx_outs, None)),
+        Err(e) => fail(e.to_string(), ptr::null_mut()),
+    }
+}
+
+/// Reason of the last failure on the calling thread, or null
+///
+/// The string is owned by the library and valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn aiblock_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Release a string returned by this library
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library, not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn aiblock_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Record the failure reason and return the failure value
+fn fail<T>(error: String, value: T) -> T {
+    let error = CString::new(error).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(error));
+    value
+}
+
+/// Read an optional NUL terminated UTF-8 string
+unsafe fn opt_str(s: *const c_char) -> Result<Option<String>, String> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    let s = CStr::from_ptr(s).to_str().map_err(|e| e.to_string())?;
+    Ok(Some(s.to_owned()))
+}
+
+/// Read a required NUL terminated UTF-8 string
+unsafe fn req_str(s: *const c_char) -> Result<String, String> {
+    opt_str(s)?.ok_or_else(|| "Null string argument".to_owned())
+}
+
+/// Read a required address, decoding a checked address of the wallet network
+unsafe fn req_address(wallet: &WalletDb, s: *const c_char) -> Result<String, String> {
+    let address = req_str(s)?;
+    wallet
+        .decode_payment_address(&address)
+        .map_err(|e| e.to_string())
+}
+
+/// Give a string to the caller
+fn to_c_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(e) => fail(e.to_string(), ptr::null_mut()),
+    }
+}
+
+/// Give a JSON encoded value to the caller
+fn to_c_json<T: serde::Serialize>(value: &T) -> *mut c_char {
+    match serde_json::to_string(value) {
+        Ok(json) => to_c_string(json),
+        Err(e) => fail(e.to_string(), ptr::null_mut()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::transaction::Transaction;
+
+    #[test]
+    /// Checks a payment can be made and signed through the C functions
+    fn make_payment_through_handle() {
+        unsafe {
+            let wallet = aiblock_wallet_open(ptr::null(), ptr::null());
+            let address = aiblock_wallet_new_address(wallet);
+            let tx_hash = CString::new("000001").unwrap();
+            let payee = CString::new("a".repeat(64)).unwrap();
+            let typo = CString::new("payee").unwrap();
+
+            let address_valid = aiblock_address_is_valid(address);
+            let added = aiblock_wallet_add_output(wallet, tx_hash.as_ptr(), 0, 10, address);
+            let signature = aiblock_wallet_sign(wallet, address, b"data".as_ptr(), 4);
+            let tx_in = aiblock_wallet_sign_input(wallet, tx_hash.as_ptr(), 0);
+            let invalid = aiblock_wallet_make_payment(wallet, typo.as_ptr(), 4, address);
+            let payment = aiblock_wallet_make_payment(wallet, payee.as_ptr(), 4, address);
+            let overspend = aiblock_wallet_make_payment(wallet, payee.as_ptr(), 4, address);
+
+            let tx: Transaction =
+                serde_json::from_str(CStr::from_ptr(payment).to_str().unwrap()).unwrap();
+            assert!(address_valid);
+            assert_eq!(
+                CStr::from_ptr(address).to_bytes().len(),
+                wallet_core::CHECKED_ADDRESS_LEN
+            );
+            assert!(added);
+            assert_eq!(CStr::from_ptr(signature).to_bytes().len(), 128);
+            assert!(!tx_in.is_null());
+            assert_eq!((tx.inputs.len(), tx.outputs.len()), (1, 2));
+            assert!(invalid.is_null());
+            assert!(overspend.is_null());
+            assert!(!aiblock_last_error().is_null());
+
+            for s in [address, signature, tx_in, payment] {
+                aiblock_string_free(s);
+            }
+            aiblock_wallet_free(wallet);
+        }
+    }
+}
@@ -1,16 +1,22 @@
+use crate::comms_handler::node::EventQueueDepth;
 use crate::configurations::MempoolNodeSharedConfig;
 use crate::mempool::MempoolError;
 use crate::mempool_raft::MempoolConsensusedRuntimeData;
+use crate::partition_rounds::PartitionRound;
 use crate::raft::{CommittedIndex, RaftMessageWrapper};
+use crate::replay_guard::{ReplayError, ReplayTag};
 use crate::tracked_utxo::TrackedUtxoSet;
 use crate::unicorn::Unicorn;
 use crate::utils::rug_integer;
+use crate::wallet::SealedWalletSync;
 use bytes::Bytes;
 use rug::Integer;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use tw_chain::crypto::sign_ed25519::{PublicKey, Signature};
 use tw_chain::primitives::asset::Asset;
 use tw_chain::primitives::asset::TokenAmount;
 use tw_chain::primitives::block::{Block, BlockHeader};
@@ -100,11 +106,305 @@ pub struct RbPaymentResponseData {
     pub sender_druid_expectation: DruidExpectation,
 }
 
+/// Kind of event handled by a node, with its payload where useful
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseReason {
+    AddressAddedToWhitelist,
+    AddressesDeleted,
+    AlreadyDisconnectedFromMempool,
+    AttemptCoordinatedNodePause,
+    AttemptCoordinatedNodeResume,
+    AttemptSendSharedConfig,
+    BarrierComplete,
+    BlockCommitted,
+    BlockCompleteStored,
+    BlockIsNotValid,
+    BlockIsValid,
+    BlockMiningNotified,
+    BlockPowComplete,
+    BlockReceivedNotAddedPowInvalid,
+    BlockReceivedNotAddedTimestampInvalid,
+    BlockReceivedNotAddedTransactionHashesMismatch,
+    BlockReceivedNotAddedTransactionsInvalid,
+    BlockReceivedToBeAdded,
+    BlockShutdown,
+    BlockStoredNotFromStoragePeer,
+    BlockchainItemFetchedFromStorage,
+    BlockchainItemReceived,
+    BlockchainItemReceivedBlockFailed,
+    CatchUpStoredBlocks,
+    CoinbaseTransactionInvalid,
+    ConnectedToMempool,
+    DatabaseCompacted,
+    DisconnectedFromMempool,
+    DonationRequested,
+    DustConsolidationStarted,
+    DustConsolidationStopped,
+    FailedToConnectToMempool,
+    FailedToDisconnectFromMempool,
+    FailedToInitiateCoordinatedPause,
+    FailedToInitiateCoordinatedResume,
+    FailedToInitiateSharingOfConfig,
+    FailedToProposeFailover,
+    FailedToSendRuntimeDataToPeer,
+    FailedToSendStartupRequests,
+    FailedToSendTradeOffer,
+    FailoverProposed,
+    FirstBlockCommitted,
+    IgnoreUnexpectedTransaction,
+    InitiatePauseNode,
+    InsufficientFundsForPayment,
+    InvalidBlockMiningNotifier,
+    InvalidPaymentAddress,
+    InvalidPowForBlock,
+    ItemAssetCreateTransactionReady,
+    ItemBasedPaymentRejected,
+    MempoolShutdown,
+    MinerNotAuthorized,
+    MinerRemovalRequestReceived,
+    MinerRemovedAckFromNonMempoolPeer,
+    MinerUnauthorizedFromNonMempoolPeer,
+    NewAddressGenerated,
+    NewAddressReadyToBeSent,
+    NextPaymentTransactionReady,
+    NoBlockToVerify,
+    NoSharedConfigToApply,
+    NoValidTransactionsProvided,
+    NodeIsConnected,
+    NodeIsDisconnected,
+    NodeIsMining,
+    NodeIsNotMining,
+    NodeIsPaused,
+    NodeIsResumed,
+    NodePauseConfigurationSet,
+    NodeResumed,
+    NotBlockCurrentlyMined,
+    NotImplementedYet,
+    OutputDataTooLarge,
+    PartitionListComplete,
+    PartitionPowComplete,
+    PartitionPowReceivedSuccessfully,
+    PaymentInclusionRejected,
+    PaymentInclusionVerified,
+    PaymentNotConstructed,
+    PaymentTransactionReceived,
+    PipelineHalted,
+    PipelineReset,
+    PowReceivedIsInvalid,
+    PreBlockReceivedSuccessfully,
+    ReceivedBlockNotification,
+    ReceivedBlockStored,
+    ReceivedCoordinatedPauseRequest,
+    ReceivedCoordinatedResumeRequest,
+    ReceivedDbItems,
+    ReceivedFirstFullPartitionRequest,
+    ReceivedInvalidDbItems,
+    ReceivedItemBasedPaymentRequest,
+    ReceivedItemBasedPaymentResponse,
+    ReceivedPartitionRequestSuccessfully,
+    ReceivedPowSuccessfully,
+    ReceivedRandomNumberSuccessfully,
+    ReceivedRuntimeDataFromPeer,
+    ReceivedRuntimeDataFromUnknownPeer,
+    ReceivedSharedConfig,
+    ReceivedUtxoFetchRequest,
+    ReceivedUtxoSet,
+    RemovingUnauthorizedMiner,
+    RequestPaymentAddress,
+    RequestUtxoSet,
+    SentRuntimeDataToPeer,
+    SentStartupRequests,
+    SentUtxoRequest,
+    SharedConfigApplied,
+    Shutdown,
+    ShutdownPending,
+    SnapshotApplied,
+    SnapshotAppliedFetchMissingBlocks,
+    SomeTransactionsInvalid,
+    StartCoordinatedShutdown,
+    StaticMinerAddressSet,
+    TestTimeoutElapsed,
+    TradeOfferSent,
+    TransactionsAddedToTxPool,
+    TransactionsCommitted,
+    TxPoolFull,
+    UnexpectedItemBasedPaymentResponse,
+    WalletSyncApplied,
+    WalletSyncRejected,
+    WalletUpdateFailed,
+    WinningPowIntakeOpen,
+    /// Block received during catch up stored, `complete` once caught up
+    BlockchainItemStored {
+        complete: bool,
+    },
+    /// Message rejected by the replay guard
+    MessageRejected(ReplayError),
+}
+
+impl ResponseReason {
+    /// Human readable description of the event
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AddressAddedToWhitelist => "Address added to whitelist",
+            Self::AddressesDeleted => "Addresses deleted",
+            Self::AlreadyDisconnectedFromMempool => "Already disconnected from mempool",
+            Self::AttemptCoordinatedNodePause => "Attempt coordinated node pause",
+            Self::AttemptCoordinatedNodeResume => "Attempt coordinated node resume",
+            Self::AttemptSendSharedConfig => "Attempt send shared config",
+            Self::BarrierComplete => "Barrier complete",
+            Self::BlockCommitted => "Block committed",
+            Self::BlockCompleteStored => "Block complete stored",
+            Self::BlockIsNotValid => "Block is not valid",
+            Self::BlockIsValid => "Block is valid",
+            Self::BlockMiningNotified => "Block mining notified",
+            Self::BlockPowComplete => "Block PoW complete",
+            Self::BlockReceivedNotAddedPowInvalid => "Block received not added. PoW invalid",
+            Self::BlockReceivedNotAddedTimestampInvalid => {
+                "Block received not added. Timestamp invalid"
+            }
+            Self::BlockReceivedNotAddedTransactionHashesMismatch => {
+                "Block received not added. Transaction hashes mismatch"
+            }
+            Self::BlockReceivedNotAddedTransactionsInvalid => {
+                "Block received not added. Transactions invalid"
+            }
+            Self::BlockReceivedToBeAdded => "Block received to be added",
+            Self::BlockShutdown => "Block shutdown",
+            Self::BlockStoredNotFromStoragePeer => {
+                "Received block stored not from our storage peer"
+            }
+            Self::BlockchainItemFetchedFromStorage => "Blockchain item fetched from storage",
+            Self::BlockchainItemReceived => "Blockchain item received",
+            Self::BlockchainItemReceivedBlockFailed => "Blockchain item received: Block failed",
+            Self::CatchUpStoredBlocks => "Catch up stored blocks",
+            Self::CoinbaseTransactionInvalid => "Coinbase transaction invalid",
+            Self::ConnectedToMempool => "Connected to mempool",
+            Self::DatabaseCompacted => "Database compacted",
+            Self::DisconnectedFromMempool => "Disconnected from mempool",
+            Self::DonationRequested => "Donation Requested",
+            Self::DustConsolidationStarted => "Dust consolidation started",
+            Self::DustConsolidationStopped => "Dust consolidation stopped",
+            Self::FailedToConnectToMempool => "Failed to connect to mempool",
+            Self::FailedToDisconnectFromMempool => "Failed to disconnect from mempool",
+            Self::FailedToInitiateCoordinatedPause => "Failed to initiate coordinated pause",
+            Self::FailedToInitiateCoordinatedResume => "Failed to initiate coordinated resume",
+            Self::FailedToInitiateSharingOfConfig => "Failed to initiate sharing of config",
+            Self::FailedToProposeFailover => "Failed to propose failover",
+            Self::FailedToSendRuntimeDataToPeer => "Failed to send runtime data to peer",
+            Self::FailedToSendStartupRequests => "Failed to send startup requests on reconnection",
+            Self::FailedToSendTradeOffer => "Failed to send trade offer",
+            Self::FailoverProposed => "Failover to standby proposed",
+            Self::FirstBlockCommitted => "First Block committed",
+            Self::IgnoreUnexpectedTransaction => "Ignore unexpected transaction",
+            Self::InitiatePauseNode => "Initiate pause node",
+            Self::InsufficientFundsForPayment => "Insufficient funds for payment",
+            Self::InvalidBlockMiningNotifier => "Invalid block mining notifier",
+            Self::InvalidPaymentAddress => "Invalid payment address",
+            Self::InvalidPowForBlock => "Invalid PoW for block",
+            Self::ItemAssetCreateTransactionReady => "Item asset create transaction ready",
+            Self::ItemBasedPaymentRejected => "Item-based payment rejected",
+            Self::MempoolShutdown => "Mempool Shutdown",
+            Self::MinerNotAuthorized => "Miner not authorized",
+            Self::MinerRemovalRequestReceived => "Miner removal request received",
+            Self::MinerRemovedAckFromNonMempoolPeer => {
+                "Received miner removed ack from non-mempool peer"
+            }
+            Self::MinerUnauthorizedFromNonMempoolPeer => {
+                "Received miner unauthorized notification from non-mempool peer"
+            }
+            Self::NewAddressGenerated => "New address generated",
+            Self::NewAddressReadyToBeSent => "New address ready to be sent",
+            Self::NextPaymentTransactionReady => "Next payment transaction ready",
+            Self::NoBlockToVerify => "No block to verify",
+            Self::NoSharedConfigToApply => "No shared config to apply",
+            Self::NoValidTransactionsProvided => "No valid transactions provided",
+            Self::NodeIsConnected => "Node is connected",
+            Self::NodeIsDisconnected => "Node is disconnected",
+            Self::NodeIsMining => "Node is mining",
+            Self::NodeIsNotMining => "Node is not mining",
+            Self::NodeIsPaused => "Node is paused",
+            Self::NodeIsResumed => "Node is resumed",
+            Self::NodePauseConfigurationSet => "Node pause configuration set",
+            Self::NodeResumed => "Node resumed",
+            Self::NotBlockCurrentlyMined => "Not block currently mined",
+            Self::NotImplementedYet => "Not implemented yet",
+            Self::OutputDataTooLarge => "Output data too large",
+            Self::PartitionListComplete => "Partition list complete",
+            Self::PartitionPowComplete => "Partition PoW complete",
+            Self::PartitionPowReceivedSuccessfully => "Partition PoW received successfully",
+            Self::PaymentInclusionRejected => "Payment inclusion rejected",
+            Self::PaymentInclusionVerified => "Payment inclusion verified",
+            Self::PaymentNotConstructed => "Payment not constructed",
+            Self::PaymentTransactionReceived => "Payment transaction received",
+            Self::PipelineHalted => "Pipeline halted",
+            Self::PipelineReset => "Pipeline reset",
+            Self::PowReceivedIsInvalid => "PoW received is invalid",
+            Self::PreBlockReceivedSuccessfully => "Pre-block received successfully",
+            Self::ReceivedBlockNotification => "Received block notification",
+            Self::ReceivedBlockStored => "Received block stored",
+            Self::ReceivedCoordinatedPauseRequest => "Received coordinated pause request",
+            Self::ReceivedCoordinatedResumeRequest => "Received coordinated resume request",
+            Self::ReceivedDbItems => "Received Db Items",
+            Self::ReceivedFirstFullPartitionRequest => "Received first full partition request",
+            Self::ReceivedInvalidDbItems => "Received Invalid Db Items",
+            Self::ReceivedItemBasedPaymentRequest => "Received item-based payment request",
+            Self::ReceivedItemBasedPaymentResponse => "Received item-based payment response",
+            Self::ReceivedPartitionRequestSuccessfully => "Received partition request successfully",
+            Self::ReceivedPowSuccessfully => "Received PoW successfully",
+            Self::ReceivedRandomNumberSuccessfully => "Received random number successfully",
+            Self::ReceivedRuntimeDataFromPeer => "Received runtime data from peer",
+            Self::ReceivedRuntimeDataFromUnknownPeer => "Received runtime data from unknown peer",
+            Self::ReceivedSharedConfig => "Received shared config",
+            Self::ReceivedUtxoFetchRequest => "Received UTXO fetch request",
+            Self::ReceivedUtxoSet => "Received UTXO set",
+            Self::RemovingUnauthorizedMiner => "Removing unauthorized miner",
+            Self::RequestPaymentAddress => "Request Payment Address",
+            Self::RequestUtxoSet => "Request UTXO set",
+            Self::SentRuntimeDataToPeer => "Sent runtime data to peer",
+            Self::SentStartupRequests => "Sent startup requests on reconnection",
+            Self::SentUtxoRequest => "Sent UTXO Request",
+            Self::SharedConfigApplied => "Shared config applied",
+            Self::Shutdown => "Shutdown",
+            Self::ShutdownPending => "Shutdown pending",
+            Self::SnapshotApplied => "Snapshot applied",
+            Self::SnapshotAppliedFetchMissingBlocks => "Snapshot applied: Fetch missing blocks",
+            Self::SomeTransactionsInvalid => {
+                "Some transactions invalid. Adding valid transactions only"
+            }
+            Self::StartCoordinatedShutdown => "Start coordinated shutdown",
+            Self::StaticMinerAddressSet => "Static miner address set",
+            Self::TestTimeoutElapsed => "Test timeout elapsed",
+            Self::TradeOfferSent => "Trade offer sent",
+            Self::TransactionsAddedToTxPool => "Transactions added to tx pool",
+            Self::TransactionsCommitted => "Transactions committed",
+            Self::TxPoolFull => "Transaction pool for this mempool node is full",
+            Self::UnexpectedItemBasedPaymentResponse => "Unexpected item-based payment response",
+            Self::WalletSyncApplied => "Wallet sync applied",
+            Self::WalletSyncRejected => "Wallet sync rejected",
+            Self::WalletUpdateFailed => "Wallet update failed",
+            Self::WinningPowIntakeOpen => "Winning PoW intake open",
+            Self::BlockchainItemStored { complete: false } => {
+                "Blockchain item received: Block stored"
+            }
+            Self::BlockchainItemStored { complete: true } => {
+                "Blockchain item received: Block stored(Done)"
+            }
+            Self::MessageRejected(e) => e.reason(),
+        }
+    }
+}
+
+impl fmt::Display for ResponseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// A placeholder struct for sensible feedback
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
     pub success: bool,
-    pub reason: &'static str,
+    pub reason: ResponseReason,
 }
 
 /// Mined block as stored in DB.
@@ -232,6 +532,28 @@ pub enum NodeType {
     PreLaunch,
 }
 
+/// Network a node belongs to: nodes of different networks never connect
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkId {
+    #[default]
+    Mainnet,
+    Testnet,
+    /// Private network identified by a number
+    Custom(u32),
+}
+
+impl NetworkId {
+    /// Network byte of the checked addresses of the network, shared by the
+    /// private networks
+    pub fn address_byte(self) -> u8 {
+        match self {
+            Self::Mainnet => 0,
+            Self::Testnet => 1,
+            Self::Custom(_) => 2,
+        }
+    }
+}
+
 /// Returns a string to represent the specified node type
 pub fn node_type_as_str(node_type: NodeType) -> &'static str {
     match node_type {
@@ -305,6 +627,13 @@ pub struct DbItem {
     pub data: Vec<u8>,
 }
 
+/// Identity key of a node, with its signature of a handshake
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityProof {
+    pub public_key: PublicKey,
+    pub signature: Signature,
+}
+
 /// Internal protocol messages exchanged between nodes.
 /// Handle nodes membership & bootstrapping. Wrap higher-level protocols.
 ///
@@ -316,27 +645,40 @@ pub struct DbItem {
 ///
 /// Message IDs are also used for _gossip_: a node will remember message IDs it has seen and will
 /// retransmit all messages it has not seen yet.
+///
+/// ### Frame layout
+/// `comms_handler::node::decode_comm_message` slices the payload of `Gossip` and `Direct`
+/// out of their frames using their bincode variant index. Add new variants at the end,
+/// and update the `comm_message_frame_layout` test along with the enum.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum CommMessage {
     /// Handshake request that peers send when they connect to someone.
     HandshakeRequest {
         /// Node network version.
         network_version: u32,
+        /// Network of the node sending the request.
+        network_id: NetworkId,
         /// Type of node sending the request.
         node_type: NodeType,
         /// Publicly available socket address of the node that can be used for inbound connections.
         public_address: SocketAddr,
+        /// Identity of the node sending the request, signing the other fields.
+        identity: Option<IdentityProof>,
     },
     /// Handshake response.
     HandshakeResponse {
         /// Node network version.
         network_version: u32,
+        /// Network of the node sending the response.
+        network_id: NetworkId,
         /// Type of node sending the response.
         node_type: NodeType,
         /// contacts of ring members.
         contacts: Vec<SocketAddr>,
         /// Publicly resolved IP address of the node who made the handshake request
         public_address: SocketAddr,
+        /// Identity of the node sending the response, signing the other fields.
+        identity: Option<IdentityProof>,
     },
     /// Gossip message, multicast to all peers within the same ring.
     Gossip {
@@ -357,19 +699,35 @@ pub enum CommMessage {
     HeartBeatProbe(Token),
 }
 
-///============ STORAGE NODE ============///
+//============ STORAGE NODE ============//
 
 /// Encapsulates storage requests
 #[allow(clippy::large_enum_variant)]
 #[derive(Deserialize, Serialize, Clone)]
 pub enum StorageRequest {
-    GetBlockchainItem { key: String },
-    SendBlockchainItem { key: String, item: BlockchainItem },
-    GetHistory { start_time: u64, end_time: u64 },
-    GetUnicornTable { n_last_items: Option<u64> },
-    SendPow { pow: ProofOfWork },
-    SendBlock { mined_block: Option<MinedBlock> },
-    Store { incoming_contract: Contract },
+    GetBlockchainItem {
+        key: String,
+    },
+    SendBlockchainItem {
+        key: String,
+        item: BlockchainItem,
+    },
+    GetHistory {
+        start_time: u64,
+        end_time: u64,
+    },
+    GetUnicornTable {
+        n_last_items: Option<u64>,
+    },
+    SendPow {
+        pow: ProofOfWork,
+    },
+    SendBlock {
+        mined_block: Option<Arc<MinedBlock>>,
+    },
+    Store {
+        incoming_contract: Contract,
+    },
     Closing,
     SendRaftCmd(RaftMessageWrapper),
 }
@@ -449,7 +807,7 @@ pub trait StorageInterface {
     fn receive_contracts(&self, contract: Contract) -> Response;
 }
 
-///============ MINER NODE ============///
+//============ MINER NODE ============//
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Deserialize, Serialize, Clone)]
@@ -566,7 +924,7 @@ pub enum Rs2JsMsg {
     Exit,
 }
 
-///============ COMPUTE NODE ============///
+//============ COMPUTE NODE ============//
 
 // Encapsulates mempool requests injected by API
 #[allow(clippy::enum_variant_names)]
@@ -611,10 +969,12 @@ pub enum MempoolRequest {
         block_num: u64,
         nonce: Vec<u8>,
         coinbase: Transaction,
+        replay_tag: ReplayTag,
     },
     SendPartitionEntry {
         pow_info: PowInfo,
         partition_entry: ProofOfWork,
+        replay_tag: ReplayTag,
     },
     SendTransactions {
         transactions: Vec<Transaction>,
@@ -634,6 +994,12 @@ pub enum MempoolRequest {
         runtime_data: MempoolConsensusedRuntimeData,
     },
     SendRaftCmd(RaftMessageWrapper),
+    /// Transactions never added to a block once expired
+    SendExpiringTransactions {
+        transactions: Vec<Transaction>,
+        /// Block number, or timestamp above `LOCKTIME_TIMESTAMP_THRESHOLD`
+        expiry: u64,
+    },
 }
 
 impl fmt::Debug for MempoolRequest {
@@ -667,6 +1033,7 @@ impl fmt::Debug for MempoolRequest {
             RequestRuntimeData => write!(f, "RequestRuntimeData"),
             SendRuntimeData { .. } => write!(f, "SendRuntimeData"),
             SendRaftCmd(_) => write!(f, "SendRaftCmd"),
+            SendExpiringTransactions { .. } => write!(f, "SendExpiringTransactions"),
         }
     }
 }
@@ -700,6 +1067,9 @@ pub trait MempoolInterface {
 
 /// Mempool node API
 pub trait MempoolApi {
+    /// Compact the mempool database
+    fn compact_db(&mut self) -> Response;
+
     /// Get mempool node configuration that is shareable between peers
     fn get_shared_config(&self) -> MempoolNodeSharedConfig;
 
@@ -709,6 +1079,9 @@ pub trait MempoolApi {
     /// Resume all mempool nodes
     fn resume_nodes(&mut self) -> Response;
 
+    /// Promote a standby mempool node in place of a failed one
+    fn failover(&mut self, replaced: SocketAddr) -> Response;
+
     /// Share mempool node config with other mempool nodes
     fn send_shared_config(&mut self, shared_config: MempoolNodeSharedConfig) -> Response;
 
@@ -721,6 +1094,20 @@ pub trait MempoolApi {
     /// Get pending DRUID pool
     fn get_pending_druid_pool(&self) -> &DruidPool;
 
+    /// Get the network of the node, transactions tagged for another one are refused
+    fn get_network_id(&self) -> NetworkId;
+
+    /// Get the hashes of the transactions waiting to be added to a block
+    fn get_tx_pool_hashes(&self) -> Vec<String>;
+
+    /// Get the recorded partition rounds of a range of blocks
+    ///
+    /// ### Arguments
+    ///
+    /// * `start` - First block number of the range.
+    /// * `end`   - Last block number of the range.
+    fn get_partition_rounds(&self, start: u64, end: u64) -> Vec<PartitionRound>;
+
     /// Receives transactions to be bundled into blocks
     ///
     /// ### Arguments
@@ -740,7 +1127,7 @@ pub trait MempoolApi {
     ) -> Result<(Transaction, String), MempoolError>;
 }
 
-///============ USER NODE ============///
+//============ USER NODE ============//
 
 /// Encapsulates user requests injected by API
 #[derive(Deserialize, Serialize, Clone)]
@@ -803,6 +1190,16 @@ pub enum UserApiRequest {
         addresses: BTreeSet<String>,
         excess_address: Option<String>,
     },
+
+    /// Offer to trade an asset for an item asset of a peer
+    MakeTradeOffer {
+        trade_peer: SocketAddr,
+        send_asset: Asset,
+        genesis_hash: Option<String>,
+    },
+
+    /// Start or stop the consolidation of the dust outputs of the wallet
+    SetDustConsolidation { active: bool },
 }
 
 /// Encapsulates user requests
@@ -817,10 +1214,13 @@ pub enum UserRequest {
     },
     /// Provide response for item-based payment request
     SendRbPaymentResponse {
+        trade_id: String,
         rb_payment_response: Option<RbPaymentResponseData>,
     },
     /// Request payment address with optional proof of work
-    SendAddressRequest,
+    SendAddressRequest {
+        replay_tag: ReplayTag,
+    },
     /// Provide payment address with optional proof of work
     SendPaymentAddress {
         address: String,
@@ -836,7 +1236,16 @@ pub enum UserRequest {
     },
     /// Process received block being mined
     BlockMining {
-        block: Block,
+        block: Arc<Block>,
+    },
+    /// Process blockchain item received from storage, in light client mode
+    SendBlockchainItem {
+        key: String,
+        item: BlockchainItem,
+    },
+    /// Process wallet changes sealed by the other user node of the owner
+    SendWalletSync {
+        sealed: SealedWalletSync,
     },
     Closing,
 }
@@ -861,6 +1270,8 @@ impl fmt::Debug for UserRequest {
             UserApi(DisconnectFromMempool) => write!(f, "DisconnectFromMempool"),
             UserApi(DeleteAddresses { .. }) => write!(f, "DeleteAddresses"),
             UserApi(MergeAddresses { .. }) => write!(f, "MergeAddresses"),
+            UserApi(MakeTradeOffer { .. }) => write!(f, "MakeTradeOffer"),
+            UserApi(SetDustConsolidation { .. }) => write!(f, "SetDustConsolidation"),
 
             SendAddressRequest { .. } => write!(f, "SendAddressRequest"),
             SendPaymentAddress { .. } => write!(f, "SendPaymentAddress"),
@@ -871,11 +1282,13 @@ impl fmt::Debug for UserRequest {
 
             SendUtxoSet { .. } => write!(f, "SendUtxoSet"),
             BlockMining { .. } => write!(f, "BlockMining"),
+            SendBlockchainItem { .. } => write!(f, "SendBlockchainItem"),
+            SendWalletSync { .. } => write!(f, "SendWalletSync"),
             Closing => write!(f, "Closing"),
         }
     }
 }
-///============ PRE-LAUNCH NODE ============///
+//============ PRE-LAUNCH NODE ============//
 
 /// API Debug Data Struct
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -885,6 +1298,15 @@ pub struct DebugData {
     pub node_api: Vec<&'static str>,
     pub node_peers: Vec<(String, SocketAddr, String)>,
     pub routes_pow: BTreeMap<String, usize>,
+    pub event_queues: Vec<EventQueueDepth>,
+}
+
+/// API node health
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NodeHealth {
+    pub node_type: String,
+    pub connected_peers: usize,
+    pub event_queue: EventQueueDepth,
 }
 
 /// Encapsulates storage requests
@@ -6,19 +6,46 @@
 mod active_raft;
 mod api;
 mod block_pipeline;
+pub mod block_stats;
+pub mod block_timestamp;
+pub mod checkpoints;
+pub mod clock;
 pub mod comms_handler;
 pub mod configurations;
 mod constants;
+pub mod crash_report;
+pub mod db_executor;
 pub mod db_utils;
+pub mod denomination;
+mod dust_consolidation;
+pub mod event_bus;
+pub mod event_hooks;
+mod exchange;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod interfaces;
 pub mod key_creation;
+mod light_client;
+pub mod local_network;
+mod local_tx_store;
 mod mempool;
 mod mempool_raft;
 mod miner;
+pub mod node_builder;
+pub mod node_identity;
+pub mod node_runner;
+pub mod output_data;
+pub mod partition_rounds;
+pub mod pid_file;
 mod pre_launch;
 mod raft;
 mod raft_store;
 mod raft_util;
+pub mod replay_guard;
+pub mod reward_schedule;
+pub mod screening;
+pub mod snapshot_manifest;
+pub mod spend_script;
 mod storage;
 mod storage_fetch;
 mod storage_raft;
@@ -28,24 +55,32 @@ mod test_utils;
 mod tests;
 pub mod threaded_call;
 mod tracked_utxo;
+pub mod trade;
 pub mod transaction_gen;
 pub mod transactor;
+mod tx_pool;
 mod unicorn;
 pub mod upgrade;
 mod user;
+mod user_handle;
 pub mod utils;
 pub mod wallet;
 
+pub use api::client_auth::serve_with_client_auth;
 pub use api::routes;
 pub use constants::SANC_LIST_PROD;
 pub use interfaces::Rs2JsMsg;
-pub use interfaces::{MempoolRequest, MinerInterface, Response, StorageInterface};
+pub use interfaces::{MempoolRequest, MinerInterface, Response, ResponseReason, StorageInterface};
+pub use local_network::{LocalNetwork, LocalNetworkBuilder};
 pub use mempool::MempoolNode;
 pub use miner::MinerNode;
+pub use node_builder::NodeBuilder;
+pub use node_runner::{serve_api, NodeRunner, RunnableNode};
 pub use pre_launch::PreLaunchNode;
 pub use storage::StorageNode;
 pub use transaction_gen::TransactionGen;
 pub use user::UserNode;
+pub use user_handle::UserNodeHandle;
 pub use utils::LocalEvent;
 pub use utils::{
     create_and_save_fake_to_wallet, create_valid_transaction, get_sanction_addresses,
@@ -0,0 +1,366 @@
+//! Light client mode of the user node.
+//!
+//! A light client keeps only the headers of the blocks holding its incoming
+//! payments. For each payment, the transaction is fetched from a storage node
+//! to find its block, then the block itself: its list of transaction hashes is
+//! the inclusion proof. The proof is accepted only if the header commits to it
+//! with a valid proof of work and links to the neighbouring headers already
+//! held, and is dropped once checked so only the header is kept.
+//!
+//! Payments to outputs not yet held by the wallet are only made spendable once
+//! their transaction is proven included, rather than on the word of the node
+//! reporting them.
+
+use crate::interfaces::{BlockchainItem, BlockchainItemMeta, StoredSerializingBlock};
+use crate::storage::{all_ordered_stored_block_tx_hashes, indexed_block_hash_key};
+use crate::utils::construct_valid_block_pow_hash;
+use bincode::deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::SocketAddr;
+use std::str;
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::block::BlockHeader;
+use tw_chain::primitives::transaction::{OutPoint, Transaction};
+use tw_chain::utils::transaction_utils::construct_tx_hash;
+
+/// Payment to the wallet: output, amount, receiving address and locktime
+pub type Payment = (OutPoint, Asset, String, u64);
+
+/// Header of a verified block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedHeader {
+    /// Hash of the block
+    pub block_hash: String,
+    /// Header of the block
+    pub header: BlockHeader,
+}
+
+/// Outcome of processing an item received from the storage node
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LightClientUpdate {
+    /// Keys of the items to fetch next
+    pub to_fetch: Vec<String>,
+    /// Transactions proven included in a block, with the block number
+    pub verified: Vec<(String, u64)>,
+    /// Transactions whose inclusion could not be proven
+    pub rejected: Vec<String>,
+}
+
+/// Inclusion proofs of the incoming payments of a user node
+#[derive(Debug, Clone)]
+pub struct LightClient {
+    storage_addr: SocketAddr,
+    headers: BTreeMap<u64, VerifiedHeader>,
+    pending: BTreeMap<String, Option<u64>>,
+    verified: BTreeMap<String, u64>,
+    unconfirmed: BTreeMap<String, BTreeMap<OutPoint, Payment>>,
+}
+
+impl LightClient {
+    /// Light client fetching proofs from the given storage node
+    ///
+    /// ### Arguments
+    ///
+    /// * `storage_addr` - Storage node to fetch the proofs from
+    /// * `headers`      - Headers verified before a restart
+    pub fn new(storage_addr: SocketAddr, headers: BTreeMap<u64, VerifiedHeader>) -> Self {
+        Self {
+            storage_addr,
+            headers,
+            pending: Default::default(),
+            verified: Default::default(),
+            unconfirmed: Default::default(),
+        }
+    }
+
+    /// Storage node to fetch the proofs from
+    pub fn storage_addr(&self) -> SocketAddr {
+        self.storage_addr
+    }
+
+    /// Headers of the verified blocks
+    pub fn headers(&self) -> &BTreeMap<u64, VerifiedHeader> {
+        &self.headers
+    }
+
+    /// Block number of a transaction proven included, None if not proven
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    pub fn verified_block_num(&self, tx_hash: &str) -> Option<u64> {
+        self.verified.get(tx_hash).copied()
+    }
+
+    /// Start proving the inclusion of a transaction, returning the key to fetch
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash` - Hash of the transaction
+    pub fn watch(&mut self, tx_hash: &str) -> Option<String> {
+        if self.verified.contains_key(tx_hash) || self.pending.contains_key(tx_hash) {
+            return None;
+        }
+        self.pending.insert(tx_hash.to_owned(), None);
+        Some(tx_hash.to_owned())
+    }
+
+    /// Hold payments until their transaction is proven included
+    ///
+    /// Returns the payments already proven, and the keys to fetch for the others.
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments reported to the wallet
+    pub fn hold_payments(&mut self, payments: Vec<Payment>) -> (Vec<Payment>, Vec<String>) {
+        let mut proven = Vec::new();
+        let mut to_fetch = Vec::new();
+        for payment in payments {
+            let tx_hash = payment.0.t_hash.clone();
+            if self.verified.contains_key(&tx_hash) {
+                proven.push(payment);
+                continue;
+            }
+            to_fetch.extend(self.watch(&tx_hash));
+            let held = self.unconfirmed.entry(tx_hash).or_default();
+            held.insert(payment.0.clone(), payment);
+        }
+        (proven, to_fetch)
+    }
+
+    /// Release the payments proven by an update, dropping the ones rejected
+    ///
+    /// ### Arguments
+    ///
+    /// * `update` - Outcome of processing an item
+    pub fn take_proven_payments(&mut self, update: &LightClientUpdate) -> Vec<Payment> {
+        for tx_hash in &update.rejected {
+            self.unconfirmed.remove(tx_hash);
+        }
+        update
+            .verified
+            .iter()
+            .filter_map(|(tx_hash, _)| self.unconfirmed.remove(tx_hash))
+            .flat_map(|held| held.into_values())
+            .collect()
+    }
+
+    /// Process an item received from the storage node
+    ///
+    /// ### Arguments
+    ///
+    /// * `key`  - Key the item was fetched with
+    /// * `item` - Item received, empty if not found
+    pub fn receive_item(&mut self, key: &str, item: BlockchainItem) -> LightClientUpdate {
+        let mut update = LightClientUpdate::default();
+        match item.item_meta {
+            _ if item.is_empty() => {
+                if self.pending.remove(key).is_some() {
+                    update.rejected.push(key.to_owned());
+                }
+            }
+            BlockchainItemMeta::Tx { block_num, .. } => {
+                if !self.pending.contains_key(key) {
+                    return update;
+                }
+                let tx = deserialize::<Transaction>(&item.data).ok();
+                if tx.map_or(true, |tx| construct_tx_hash(&tx) != key) {
+                    self.pending.remove(key);
+                    update.rejected.push(key.to_owned());
+                    return update;
+                }
+                self.pending.insert(key.to_owned(), Some(block_num));
+                update.to_fetch.push(indexed_block_hash_key(block_num));
+            }
+            BlockchainItemMeta::Block { block_num, .. } => {
+                let waiting: Vec<String> = self
+                    .pending
+                    .iter()
+                    .filter(|(_, b_num)| **b_num == Some(block_num))
+                    .map(|(tx_hash, _)| tx_hash.clone())
+                    .collect();
+                let included = self.verify_block(block_num, &item);
+                for tx_hash in waiting {
+                    self.pending.remove(&tx_hash);
+                    match &included {
+                        Some(included) if included.contains(&tx_hash) => {
+                            self.verified.insert(tx_hash.clone(), block_num);
+                            update.verified.push((tx_hash, block_num));
+                        }
+                        _ => update.rejected.push(tx_hash),
+                    }
+                }
+            }
+        }
+        update
+    }
+
+    /// Verify a received block and keep its header, returning its transaction hashes
+    ///
+    /// ### Arguments
+    ///
+    /// * `block_num` - Number of the block expected
+    /// * `item`      - Block received
+    fn verify_block(&mut self, block_num: u64, item: &BlockchainItem) -> Option<BTreeSet<String>> {
+        let stored: StoredSerializingBlock = deserialize(&item.data).ok()?;
+        let block = stored.block;
+        let block_hash = construct_valid_block_pow_hash(&block).ok()?;
+        if block.header.b_num != block_num || str::from_utf8(&item.key).ok()? != block_hash {
+            return None;
+        }
+
+        let links_to = |b_num: u64, hash: Option<&String>| {
+            self.headers
+                .get(&b_num)
+                .map_or(true, |h| Some(&h.block_hash) == hash)
+        };
+        let agrees = self
+            .headers
+            .get(&block_num)
+            .map_or(true, |h| h.block_hash == block_hash);
+        let links_previous = block_num
+            .checked_sub(1)
+            .map_or(true, |b| links_to(b, block.header.previous_hash.as_ref()));
+        let linked_by_next = self.headers.get(&(block_num + 1)).map_or(true, |h| {
+            h.header.previous_hash.as_ref() == Some(&block_hash)
+        });
+        if !(agrees && links_previous && linked_by_next) {
+            return None;
+        }
+
+        let included = all_ordered_stored_block_tx_hashes(
+            &block.transactions,
+            std::iter::once(&block.header.nonce_and_mining_tx_hash),
+        )
+        .map(|(_, tx_hash)| tx_hash.clone())
+        .collect();
+        self.headers.insert(
+            block_num,
+            VerifiedHeader {
+                block_hash,
+                header: block.header,
+            },
+        );
+        Some(included)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{apply_mining_tx, generate_pow_for_block};
+    use bincode::serialize;
+    use tw_chain::primitives::block::Block;
+
+    async fn block_item(block_num: u64, transactions: Vec<String>) -> BlockchainItem {
+        let mut block = Block::new();
+        block.header.b_num = block_num;
+        block.transactions = transactions;
+        block.set_txs_merkle_root_and_hash().await;
+        block.header = apply_mining_tx(block.header, Vec::new(), "g_mining".to_owned());
+        block.header = generate_pow_for_block(block.header);
+        let block_hash = construct_valid_block_pow_hash(&block).unwrap();
+
+        BlockchainItem {
+            version: 0,
+            item_meta: BlockchainItemMeta::Block {
+                block_num,
+                tx_len: 1,
+            },
+            key: block_hash.into_bytes(),
+            data: serialize(&StoredSerializingBlock { block }).unwrap(),
+            data_json: Vec::new(),
+        }
+    }
+
+    fn tx_item(tx: &Transaction, block_num: u64) -> BlockchainItem {
+        BlockchainItem {
+            version: 0,
+            item_meta: BlockchainItemMeta::Tx {
+                block_num,
+                tx_num: 0,
+            },
+            key: construct_tx_hash(tx).into_bytes(),
+            data: serialize(tx).unwrap(),
+            data_json: Vec::new(),
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a payment is verified from its block and only the header is kept
+    async fn light_client_verifies_inclusion() {
+        //Arrange
+        let tx = Transaction::new();
+        let tx_hash = construct_tx_hash(&tx);
+        let mut client = LightClient::new("127.0.0.1:12330".parse().unwrap(), Default::default());
+        let block = block_item(3, vec![tx_hash.clone()]).await;
+
+        //Act
+        let watched = client.watch(&tx_hash);
+        let tx_update = client.receive_item(&tx_hash, tx_item(&tx, 3));
+        let block_update = client.receive_item(&indexed_block_hash_key(3), block);
+
+        //Assert
+        assert_eq!(watched, Some(tx_hash.clone()));
+        assert_eq!(tx_update.to_fetch, vec![indexed_block_hash_key(3)]);
+        assert_eq!(block_update.verified, vec![(tx_hash.clone(), 3)]);
+        assert_eq!(client.verified_block_num(&tx_hash), Some(3));
+        assert_eq!(
+            client.headers().keys().copied().collect::<Vec<_>>(),
+            vec![3]
+        );
+        assert_eq!(client.watch(&tx_hash), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a payment missing from its block or from storage is rejected
+    async fn light_client_rejects_missing_inclusion() {
+        //Arrange
+        let tx = Transaction::new();
+        let tx_hash = construct_tx_hash(&tx);
+        let mut client = LightClient::new("127.0.0.1:12330".parse().unwrap(), Default::default());
+        let block = block_item(3, vec!["g_other".to_owned()]).await;
+
+        //Act
+        client.watch(&tx_hash);
+        client.receive_item(&tx_hash, tx_item(&tx, 3));
+        let block_update = client.receive_item(&indexed_block_hash_key(3), block);
+        client.watch("g_unknown");
+        let not_found = client.receive_item("g_unknown", Default::default());
+
+        //Assert
+        assert_eq!(block_update.rejected, vec![tx_hash.clone()]);
+        assert_eq!(not_found.rejected, vec!["g_unknown".to_owned()]);
+        assert_eq!(client.verified_block_num(&tx_hash), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks payments are only released once their transaction is proven included
+    async fn light_client_holds_unproven_payments() {
+        //Arrange
+        let tx = Transaction::new();
+        let tx_hash = construct_tx_hash(&tx);
+        let mut client = LightClient::new("127.0.0.1:12330".parse().unwrap(), Default::default());
+        let payment = |n| {
+            let out_point = OutPoint::new(tx_hash.clone(), n);
+            (out_point, Asset::token_u64(10), "addr".to_owned(), 0)
+        };
+        let block = block_item(3, vec![tx_hash.clone()]).await;
+
+        //Act
+        let (proven, to_fetch) = client.hold_payments(vec![payment(0), payment(1)]);
+        let tx_update = client.receive_item(&tx_hash, tx_item(&tx, 3));
+        let tx_released = client.take_proven_payments(&tx_update);
+        let block_update = client.receive_item(&indexed_block_hash_key(3), block);
+        let block_released = client.take_proven_payments(&block_update);
+        let (proven_again, _) = client.hold_payments(vec![payment(0)]);
+
+        //Assert
+        assert!(proven.is_empty());
+        assert_eq!(to_fetch, vec![tx_hash.clone()]);
+        assert!(tx_released.is_empty());
+        assert_eq!(block_released, vec![payment(0), payment(1)]);
+        assert_eq!(proven_again, vec![payment(0)]);
+    }
+}
@@ -0,0 +1,487 @@
+//! Networks of nodes hosted in a single process.
+//!
+//! All the nodes of a `LocalNetwork` run on the current tokio runtime, with
+//! in-memory databases and no TLS. Their listeners are bound on loopback
+//! before any node is created, so every node knows its peers up front and
+//! the network is connected as soon as the node loops start.
+
+use crate::comms_handler::Node;
+use crate::configurations::{
+    DbMode, MempoolNodeConfig, MinerNodeConfig, NodeSpec, StorageNodeConfig, UserNodeConfig,
+    UtxoSetSpec,
+};
+use crate::interfaces::{NodeType, ResponseReason};
+use crate::utils::{get_test_common_unicorn, LocalEventSender};
+use crate::{mempool, miner, storage, user};
+use crate::{NodeBuilder, NodeRunner, RunnableNode};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::{fmt, io};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Result wrapper for local network errors
+pub type Result<T> = std::result::Result<T, LocalNetworkError>;
+
+/// Number of peers each local node accepts
+const LOCAL_PEER_LIMIT: usize = 1000;
+
+#[derive(Debug)]
+pub enum LocalNetworkError {
+    Io(io::Error),
+    Mempool(mempool::MempoolError),
+    Storage(storage::StorageError),
+    Miner(miner::MinerError),
+    User(user::UserError),
+}
+
+impl fmt::Display for LocalNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Mempool(err) => write!(f, "Mempool node error: {err}"),
+            Self::Storage(err) => write!(f, "Storage node error: {err}"),
+            Self::Miner(err) => write!(f, "Miner node error: {err}"),
+            Self::User(err) => write!(f, "User node error: {err}"),
+        }
+    }
+}
+
+impl Error for LocalNetworkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(ref e) => Some(e),
+            Self::Mempool(ref e) => Some(e),
+            Self::Storage(ref e) => Some(e),
+            Self::Miner(ref e) => Some(e),
+            Self::User(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LocalNetworkError {
+    fn from(other: io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+impl From<mempool::MempoolError> for LocalNetworkError {
+    fn from(other: mempool::MempoolError) -> Self {
+        Self::Mempool(other)
+    }
+}
+
+impl From<storage::StorageError> for LocalNetworkError {
+    fn from(other: storage::StorageError) -> Self {
+        Self::Storage(other)
+    }
+}
+
+impl From<miner::MinerError> for LocalNetworkError {
+    fn from(other: miner::MinerError) -> Self {
+        Self::Miner(other)
+    }
+}
+
+impl From<user::UserError> for LocalNetworkError {
+    fn from(other: user::UserError) -> Self {
+        Self::User(other)
+    }
+}
+
+/// Describes the nodes of a local network, with one mempool and one storage node
+#[derive(Debug, Default, Clone)]
+pub struct LocalNetworkBuilder {
+    miner_len: usize,
+    user_len: usize,
+    mempool_seed_utxo: UtxoSetSpec,
+    passphrase: Option<String>,
+}
+
+impl LocalNetworkBuilder {
+    /// Describe a network with only a mempool and a storage node
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add miner nodes, named `miner1` onward
+    ///
+    /// ### Arguments
+    ///
+    /// * `miner_len` - Number of miner nodes
+    pub fn with_miners(mut self, miner_len: usize) -> Self {
+        self.miner_len = miner_len;
+        self
+    }
+
+    /// Add user nodes, named `user1` onward
+    ///
+    /// ### Arguments
+    ///
+    /// * `user_len` - Number of user nodes
+    pub fn with_users(mut self, user_len: usize) -> Self {
+        self.user_len = user_len;
+        self
+    }
+
+    /// Create the first UTXO set of the mempool node
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed_utxo` - Outputs of the seed transactions
+    pub fn with_mempool_seed_utxo(mut self, seed_utxo: UtxoSetSpec) -> Self {
+        self.mempool_seed_utxo = seed_utxo;
+        self
+    }
+
+    /// Encrypt the miner and user wallets with the given passphrase
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Wallet passphrase
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Create all the nodes and start their loops
+    pub async fn start(self) -> Result<LocalNetwork> {
+        let names: Vec<(NodeType, String)> = std::iter::empty()
+            .chain(Some((NodeType::Storage, "storage1".to_owned())))
+            .chain(Some((NodeType::Mempool, "mempool1".to_owned())))
+            .chain((1..=self.miner_len).map(|i| (NodeType::Miner, format!("miner{i}"))))
+            .chain((1..=self.user_len).map(|i| (NodeType::User, format!("user{i}"))))
+            .collect();
+
+        let mut listeners = BTreeMap::new();
+        for (node_type, name) in &names {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let address = listener.local_addr()?;
+            listeners.insert(name.clone(), (*node_type, address, listener));
+        }
+        let specs = |node_type: NodeType| -> Vec<NodeSpec> {
+            listeners
+                .values()
+                .filter(|(t, _, _)| *t == node_type)
+                .map(|(_, address, _)| NodeSpec {
+                    address: address.to_string(),
+                })
+                .collect()
+        };
+        let mempool_nodes = specs(NodeType::Mempool);
+        let storage_nodes = specs(NodeType::Storage);
+        let user_nodes = specs(NodeType::User);
+
+        let mut nodes = BTreeMap::new();
+        for (_, name) in names {
+            let (node_type, address, listener) = listeners.remove(&name).unwrap();
+            let node = match node_type {
+                NodeType::Storage => {
+                    let config = self.storage_config(&mempool_nodes, &storage_nodes);
+                    let builder = NodeBuilder::new(config).with_listener(listener);
+                    LocalNode::spawn(builder.build().await?)
+                }
+                NodeType::Mempool => {
+                    let config = self.mempool_config(&mempool_nodes, &storage_nodes, &user_nodes);
+                    let builder = NodeBuilder::new(config).with_listener(listener);
+                    LocalNode::spawn(builder.build().await?)
+                }
+                NodeType::Miner => {
+                    let config = self.miner_config(address, &mempool_nodes);
+                    let builder = NodeBuilder::new(config).with_listener(listener);
+                    LocalNode::spawn(builder.build().await?)
+                }
+                NodeType::User | NodeType::PreLaunch => {
+                    let config = self.user_config(address, &mempool_nodes);
+                    let builder = NodeBuilder::new(config).with_listener(listener);
+                    LocalNode::spawn(builder.build().await?)
+                }
+            };
+            info!("Local {:?} node {} at {}", node_type, name, address);
+            nodes.insert(name, node);
+        }
+
+        Ok(LocalNetwork { nodes })
+    }
+
+    fn storage_config(
+        &self,
+        mempool_nodes: &[NodeSpec],
+        storage_nodes: &[NodeSpec],
+    ) -> StorageNodeConfig {
+        StorageNodeConfig {
+            storage_node_idx: 0,
+            storage_db_mode: DbMode::InMemory,
+            tls_config: Default::default(),
+            api_keys: Default::default(),
+            mempool_nodes: mempool_nodes.to_vec(),
+            storage_nodes: storage_nodes.to_vec(),
+            storage_raft: 0,
+            storage_api_port: 0,
+            storage_api_use_tls: false,
+            storage_api_client_auth: None,
+            storage_raft_tick_timeout: 10,
+            storage_catchup_duration: 1000,
+            routes_pow: Default::default(),
+            backup_block_modulo: None,
+            backup_restore: None,
+            backup_signing_key: None,
+            identity_file: None,
+            db_threads: None,
+            db_compaction_interval: None,
+            peer_limit: LOCAL_PEER_LIMIT,
+            network_id: None,
+            peer_ip_filter: None,
+            event_queue_capacity: None,
+            local_event_capacity: None,
+            peer_wait_timeout: None,
+            storage_event_bus: None,
+            storage_checkpoints: None,
+            storage_max_block_time_drift: None,
+        }
+    }
+
+    fn mempool_config(
+        &self,
+        mempool_nodes: &[NodeSpec],
+        storage_nodes: &[NodeSpec],
+        user_nodes: &[NodeSpec],
+    ) -> MempoolNodeConfig {
+        MempoolNodeConfig {
+            mempool_node_idx: 0,
+            mempool_db_mode: DbMode::InMemory,
+            tls_config: Default::default(),
+            api_keys: Default::default(),
+            mempool_unicorn_fixed_param: get_test_common_unicorn(),
+            mempool_nodes: mempool_nodes.to_vec(),
+            storage_nodes: storage_nodes.to_vec(),
+            user_nodes: user_nodes.to_vec(),
+            mempool_raft: 0,
+            mempool_api_port: 0,
+            mempool_api_use_tls: false,
+            mempool_api_client_auth: None,
+            mempool_raft_tick_timeout: 10,
+            mempool_mining_event_timeout: 500,
+            mempool_transaction_timeout: 100,
+            mempool_local_tx_flush_len: None,
+            mempool_local_tx_flush_timeout: None,
+            max_output_data_size: None,
+            max_tx_data_size: None,
+            mempool_reward_schedule: None,
+            db_compaction_interval: None,
+            mempool_seed_utxo: self.mempool_seed_utxo.clone(),
+            mempool_genesis_tx_in: None,
+            mempool_seed_utxo_file: None,
+            mempool_genesis_allocation: None,
+            mempool_partition_full_size: 1,
+            mempool_minimum_miner_pool_len: 1,
+            jurisdiction: "US".to_owned(),
+            sanction_list: Vec::new(),
+            routes_pow: Default::default(),
+            backup_block_modulo: None,
+            utxo_re_align_block_modulo: None,
+            backup_restore: None,
+            backup_signing_key: None,
+            identity_file: None,
+            enable_trigger_messages_pipeline_reset: None,
+            mempool_miner_whitelist: Default::default(),
+            peer_limit: LOCAL_PEER_LIMIT,
+            network_id: None,
+            peer_ip_filter: None,
+            event_queue_capacity: None,
+            local_event_capacity: None,
+            peer_wait_timeout: None,
+            initial_issuances: Vec::new(),
+            mempool_event_bus: None,
+            mempool_screening: None,
+            mempool_raft_standby: None,
+            mempool_raft_failover_timeout: None,
+        }
+    }
+
+    fn miner_config(&self, address: SocketAddr, mempool_nodes: &[NodeSpec]) -> MinerNodeConfig {
+        MinerNodeConfig {
+            miner_address: address.to_string(),
+            miner_db_mode: DbMode::InMemory,
+            tls_config: Default::default(),
+            api_keys: Default::default(),
+            miner_mempool_node_idx: 0,
+            mempool_nodes: mempool_nodes.to_vec(),
+            miner_api_port: 0,
+            miner_api_use_tls: false,
+            passphrase: self.passphrase.clone(),
+            routes_pow: Default::default(),
+            backup_block_modulo: None,
+            backup_restore: None,
+            backup_signing_key: None,
+            identity_file: None,
+            db_threads: None,
+            db_compaction_interval: None,
+            static_miner_address: None,
+            mining_api_key: None,
+            peer_limit: LOCAL_PEER_LIMIT,
+            network_id: None,
+            peer_ip_filter: None,
+            event_queue_capacity: None,
+            local_event_capacity: None,
+            peer_wait_timeout: None,
+            address_aggregation_limit: None,
+            miner_wallet_kdf: None,
+        }
+    }
+
+    fn user_config(&self, address: SocketAddr, mempool_nodes: &[NodeSpec]) -> UserNodeConfig {
+        UserNodeConfig {
+            user_address: address.to_string(),
+            user_db_mode: DbMode::InMemory,
+            tls_config: Default::default(),
+            api_keys: Default::default(),
+            user_mempool_node_idx: 0,
+            mempool_nodes: mempool_nodes.to_vec(),
+            user_api_port: 0,
+            user_api_use_tls: false,
+            user_api_client_auth: None,
+            user_wallet_seeds: Vec::new(),
+            passphrase: self.passphrase.clone(),
+            user_auto_donate: 0,
+            user_test_auto_gen_setup: Default::default(),
+            routes_pow: Default::default(),
+            backup_block_modulo: None,
+            backup_signing_key: None,
+            identity_file: None,
+            db_threads: None,
+            db_compaction_interval: None,
+            peer_limit: LOCAL_PEER_LIMIT,
+            network_id: None,
+            peer_ip_filter: None,
+            event_queue_capacity: None,
+            local_event_capacity: None,
+            peer_wait_timeout: None,
+            user_exchange: None,
+            user_trade_timeout: None,
+            user_remote_signer: None,
+            user_hot_wallet_keys: None,
+            user_event_bus: None,
+            user_wallet_kdf: None,
+            user_light_client: None,
+            user_payment_expiry: None,
+            user_wallet_sync: None,
+            user_coin_selection: None,
+            user_reuse_change_address: None,
+            user_dust_consolidation: None,
+            user_wallets: None,
+        }
+    }
+}
+
+/// Node running in a local network
+struct LocalNode {
+    address: SocketAddr,
+    node_conn: Node,
+    local_event_tx: LocalEventSender,
+    stop_tx: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl LocalNode {
+    /// Run the node until stopped
+    ///
+    /// ### Arguments
+    ///
+    /// * `node` - Node to run
+    fn spawn(node: impl RunnableNode) -> Self {
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let runner = NodeRunner::new(node).with_exit(async move {
+            let _ = stop_rx.await;
+            ResponseReason::Shutdown
+        });
+
+        Self {
+            address: runner.node().local_address(),
+            node_conn: runner.node_conn(),
+            local_event_tx: runner.node().local_event_tx().clone(),
+            stop_tx,
+            handle: tokio::spawn(runner.run()),
+        }
+    }
+}
+
+/// Nodes running in the current process
+pub struct LocalNetwork {
+    nodes: BTreeMap<String, LocalNode>,
+}
+
+impl LocalNetwork {
+    /// Names of the running nodes
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.nodes.keys().map(|name| name.as_str())
+    }
+
+    /// Address the node listens on for peers
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the node
+    pub fn address(&self, name: &str) -> Option<SocketAddr> {
+        self.nodes.get(name).map(|node| node.address)
+    }
+
+    /// Connection of the node, to inspect its peers or send it messages
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the node
+    pub fn node_conn(&self, name: &str) -> Option<Node> {
+        self.nodes.get(name).map(|node| node.node_conn.clone())
+    }
+
+    /// Sender of local events, such as requests from a UI, to the node
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the node
+    pub fn local_event_tx(&self, name: &str) -> Option<LocalEventSender> {
+        self.nodes.get(name).map(|node| node.local_event_tx.clone())
+    }
+
+    /// Stop all the nodes and wait for them to close
+    pub async fn stop(self) {
+        let mut handles = Vec::new();
+        for (name, node) in self.nodes {
+            let _ = node.stop_tx.send(());
+            handles.push((name, node.handle));
+        }
+        for (name, handle) in handles {
+            if let Err(e) = handle.await {
+                info!("Local node {} stopped with error: {:?}", name, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    /// Checks a network of one mempool, one storage and two user nodes starts and stops
+    async fn local_network_starts_and_stops() {
+        //Arrange
+        let builder = LocalNetworkBuilder::new().with_users(2);
+
+        //Act
+        let network = builder.start().await.unwrap();
+        let names: Vec<_> = network.names().map(|name| name.to_owned()).collect();
+        let mempool_address = network.address("mempool1").unwrap();
+        let user_address = network.address("user2").unwrap();
+        network.stop().await;
+
+        //Assert
+        assert_eq!(names, vec!["mempool1", "storage1", "user1", "user2"]);
+        assert_ne!(mempool_address, user_address);
+        assert!(mempool_address.ip().is_loopback());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::db_utils::SimpleDb;
+use bincode::serialize;
+use std::collections::BTreeMap;
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::error;
+use tw_chain::primitives::transaction::Transaction;
+
+/// Default number of buffered changes triggering a flush
+pub const DEFAULT_FLUSH_LEN: usize = 512;
+
+/// Default time in milliseconds changes stay buffered
+pub const DEFAULT_FLUSH_TIMEOUT: u64 = 50;
+
+/// Coalesces the writes of local transactions into batches
+///
+/// Changes are buffered until `flush_len` of them are pending or the oldest
+/// has waited `flush_timeout`, then written in a single batch. A transaction
+/// deleted before being flushed is never written.
+///
+/// A transaction given an expiry is stored along with it, so it still expires
+/// once proposed again after a restart.
+#[derive(Debug)]
+pub struct LocalTxWriter {
+    /// Column the transactions are stored in
+    column: &'static str,
+    /// Serialized transactions to put, or `None` to delete
+    pending: BTreeMap<String, Option<Vec<u8>>>,
+    /// Time the oldest pending change was buffered
+    pending_since: Option<Instant>,
+    /// Number of pending changes triggering a flush
+    flush_len: usize,
+    /// Time after which pending changes are flushed
+    flush_timeout: Duration,
+}
+
+impl LocalTxWriter {
+    /// Create a writer with an empty buffer
+    ///
+    /// ### Arguments
+    ///
+    /// * `column`        - Column the transactions are stored in
+    /// * `flush_len`     - Number of pending changes triggering a flush
+    /// * `flush_timeout` - Time after which pending changes are flushed
+    pub fn new(column: &'static str, flush_len: usize, flush_timeout: Duration) -> Self {
+        Self {
+            column,
+            pending: Default::default(),
+            pending_since: None,
+            flush_len: flush_len.max(1),
+            flush_timeout,
+        }
+    }
+
+    /// Buffer transactions to store, flushing if the buffer is full
+    ///
+    /// ### Arguments
+    ///
+    /// * `db`           - Database
+    /// * `transactions` - Transactions to store
+    pub fn store(&mut self, db: &mut SimpleDb, transactions: &BTreeMap<String, Transaction>) {
+        for (key, value) in transactions {
+            match serialize(value) {
+                Ok(value) => self.buffer(key.clone(), Some(value)),
+                Err(e) => error!("Local transaction {} not serialized: {:?}", key, e),
+            }
+        }
+        self.flush_if_full(db);
+    }
+
+    /// Buffer transactions to store with their expiry, flushing if the buffer is full
+    ///
+    /// ### Arguments
+    ///
+    /// * `db`           - Database
+    /// * `transactions` - Transactions to store
+    /// * `expiry`       - Block number or timestamp the transactions expire at
+    pub fn store_with_expiry(
+        &mut self,
+        db: &mut SimpleDb,
+        transactions: &BTreeMap<String, Transaction>,
+        expiry: u64,
+    ) {
+        for (key, value) in transactions {
+            match serialize(&(value, expiry)) {
+                Ok(value) => self.buffer(key.clone(), Some(value)),
+                Err(e) => error!("Local transaction {} not serialized: {:?}", key, e),
+            }
+        }
+        self.flush_if_full(db);
+    }
+
+    /// Buffer transactions to delete, flushing if the buffer is full
+    ///
+    /// ### Arguments
+    ///
+    /// * `db`   - Database
+    /// * `keys` - Keys to delete
+    pub fn delete(&mut self, db: &mut SimpleDb, keys: &[String]) {
+        for key in keys {
+            self.buffer(key.clone(), None);
+        }
+        self.flush_if_full(db);
+    }
+
+    /// Whether changes are waiting to be flushed
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Completes once the oldest pending change waited long enough
+    ///
+    /// Never completes while nothing is pending.
+    pub async fn flush_timeout(&self) {
+        match self.pending_since {
+            Some(since) => sleep_until(since + self.flush_timeout).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Write all pending changes in a single batch
+    ///
+    /// ### Arguments
+    ///
+    /// * `db` - Database
+    pub fn flush(&mut self, db: &mut SimpleDb) {
+        self.pending_since = None;
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let mut batch = db.batch_writer();
+        for (key, value) in std::mem::take(&mut self.pending) {
+            match value {
+                Some(value) => batch.put_cf(self.column, key, value),
+                None => batch.delete_cf(self.column, key),
+            }
+        }
+        let batch = batch.done();
+        if let Err(e) = db.write(batch) {
+            error!("Error writing local transactions to disk: {:?}", e);
+        }
+    }
+
+    fn buffer(&mut self, key: String, value: Option<Vec<u8>>) {
+        self.pending_since.get_or_insert_with(Instant::now);
+        self.pending.insert(key, value);
+    }
+
+    fn flush_if_full(&mut self, db: &mut SimpleDb) {
+        if self.pending.len() >= self.flush_len {
+            self.flush(db);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configurations::DbMode;
+    use crate::db_utils;
+    use crate::mempool::{DB_COL_LOCAL_TXS as TEST_COL, DB_SPEC};
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks changes are written once the buffer is full or flushed
+    async fn local_tx_writer_coalesces_changes() {
+        //Arrange
+        let mut db = db_utils::new_db(DbMode::InMemory, &DB_SPEC, None, None);
+        let mut writer = LocalTxWriter::new(TEST_COL, 3, Duration::from_secs(60));
+        let txs: BTreeMap<String, Transaction> = (0..2)
+            .map(|i| (format!("tx{i}"), Transaction::new()))
+            .collect();
+        let stored = |db: &SimpleDb| db.iter_cf_clone(TEST_COL).unwrap().count();
+
+        //Act
+        writer.store(&mut db, &txs);
+        let stored_buffered = stored(&db);
+        writer.delete(&mut db, &["tx0".to_owned()]);
+        let stored_coalesced = stored(&db);
+        writer.delete(&mut db, &["tx2".to_owned()]);
+        let stored_full = stored(&db);
+        writer.store(&mut db, &txs);
+        writer.flush(&mut db);
+        let stored_flushed = stored(&db);
+
+        //Assert
+        assert_eq!(stored_buffered, 0);
+        assert_eq!(stored_coalesced, 0);
+        assert_eq!(stored_full, 1);
+        assert_eq!(stored_flushed, 2);
+        assert!(!writer.has_pending());
+    }
+}
@@ -1,31 +1,49 @@
+use crate::api::client_auth::ApiClientAuth;
 use crate::block_pipeline::{MiningPipelineItem, MiningPipelineStatus, Participants};
 use crate::comms_handler::{CommsError, Event, Node, TcpTlsConfig};
 use crate::configurations::{
-    ExtraNodeParams, MempoolNodeConfig, MempoolNodeSharedConfig, TlsPrivateInfo,
+    ExtraNodeParams, MempoolNodeConfig, MempoolNodeSharedConfig, ResponseHook, ScreeningConfig,
+    TlsPrivateInfo,
 };
 use crate::constants::{DB_PATH, RESEND_TRIGGER_MESSAGES_COMPUTE_LIMIT};
-use crate::db_utils::{self, SimpleDb, SimpleDbError, SimpleDbSpec};
+use crate::db_utils::{self, CompactionSchedule, SimpleDb, SimpleDbError, SimpleDbSpec};
+use crate::event_bus::{publish_event, EventPublisher, NodeEvent};
+use crate::event_hooks::EventHooks;
 use crate::interfaces::{
     BlockStoredInfo, CommonBlockInfo, Contract, DruidDroplet, DruidPool, InitialIssuance,
     MempoolApi, MempoolApiRequest, MempoolInterface, MempoolRequest, MineRequest, MinedBlock,
-    MinedBlockExtraInfo, NodeType, PowInfo, ProofOfWork, Response, StorageRequest, UserRequest,
-    UtxoFetchType, UtxoSet, WinningPoWInfo,
+    MinedBlockExtraInfo, NetworkId, NodeType, PowInfo, ProofOfWork, Response, ResponseReason,
+    StorageRequest, UserRequest, UtxoFetchType, UtxoSet, WinningPoWInfo,
 };
+use crate::local_tx_store::{self, LocalTxWriter};
 use crate::mempool_raft::{
     CommittedItem, CoordinatedCommand, MempoolConsensusedRuntimeData, MempoolRaft,
     MempoolRuntimeItem,
 };
-use crate::raft::RaftCommit;
+use crate::node_identity::load_node_identity;
+use crate::output_data::DataLimits;
+use crate::partition_rounds::{get_partition_rounds, put_partition_round, PartitionRound};
+use crate::raft::{RaftCommit, SharedRaftMetrics};
+use crate::replay_guard::{ReplayTag, ReplayWindows};
+use crate::screening::{
+    append_screening_audit, get_screening_audit_log, screen, transaction_addresses,
+    AddressListScreener, RemoteScreener, Screener, ScreeningAuditEntry,
+};
+use crate::snapshot_manifest::{set_snapshot_identity, set_snapshot_signer};
+use crate::spend_script::tx_spends_are_valid;
 use crate::threaded_call::{ThreadedCallChannel, ThreadedCallSender};
 use crate::tracked_utxo::TrackedUtxoSet;
+use crate::tx_pool::TxPool;
 use crate::utils::{
     apply_mining_tx, check_druid_participants, create_item_asset_tx_from_sig, create_socket_addr,
-    format_parition_pow_address, generate_pow_random_num, to_api_keys, to_route_pow_infos,
-    validate_pow_block, validate_pow_for_address, ApiKeys, LocalEvent, LocalEventChannel,
-    LocalEventSender, ResponseResult, RoutesPoWInfo, StringError,
+    create_socket_addr_for_list, format_parition_pow_address, generate_pow_random_num,
+    get_timestamp_now, is_locktime_expired, load_seed_utxo_file, to_api_keys, to_route_pow_infos,
+    validate_pow_block, validate_pow_for_address, validate_seed_utxo, ApiKeys, LocalEvent,
+    LocalEventChannel, LocalEventSender, ResponseResult, RoutesPoWInfo, StringError,
 };
 use bincode::{deserialize, serialize};
 use bytes::Bytes;
+use rayon::prelude::*;
 use serde::Serialize;
 use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
@@ -34,6 +52,7 @@ use std::{
     fmt,
     future::Future,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
 };
 use tokio::sync::RwLock;
 use tokio::task;
@@ -43,7 +62,6 @@ use tw_chain::primitives::asset::TokenAmount;
 use tw_chain::primitives::block::Block;
 use tw_chain::primitives::transaction::{GenesisTxHashSpec, Transaction};
 use tw_chain::utils::druid_utils::druid_expectations_are_met;
-use tw_chain::utils::script_utils::{tx_has_valid_create_script, tx_is_valid};
 use tw_chain::utils::transaction_utils::construct_tx_hash;
 
 /// Key for local miner list
@@ -52,6 +70,7 @@ pub const USER_NOTIFY_LIST_KEY: &str = "UserNotifyListKey";
 pub const POW_RANDOM_NUM_KEY: &str = "PowRandomNumKey";
 pub const POW_PREV_RANDOM_NUM_KEY: &str = "PowPreviousRandomNumKey";
 pub const RAFT_KEY_RUN: &str = "RaftKeyRun";
+pub const ROUND_STATE_KEY: &str = "RoundStateKey";
 
 /// Database columns
 pub const DB_COL_INTERNAL: &str = "internal";
@@ -74,6 +93,7 @@ pub enum MempoolError {
     Serialization(bincode::Error),
     AsyncTask(task::JoinError),
     GenericError(StringError),
+    MissingState(&'static str),
 }
 
 impl fmt::Display for MempoolError {
@@ -85,6 +105,7 @@ impl fmt::Display for MempoolError {
             Self::AsyncTask(err) => write!(f, "Async task error: {err}"),
             Self::Serialization(err) => write!(f, "Serialization error: {err}"),
             Self::GenericError(err) => write!(f, "Generic error: {err}"),
+            Self::MissingState(err) => write!(f, "Missing state: {err}"),
         }
     }
 }
@@ -98,6 +119,7 @@ impl Error for MempoolError {
             Self::AsyncTask(ref e) => Some(e),
             Self::Serialization(ref e) => Some(e),
             Self::GenericError(ref e) => Some(e),
+            Self::MissingState(_) => None,
         }
     }
 }
@@ -140,13 +162,18 @@ pub struct MempoolNode {
     node: Node,
     node_raft: MempoolRaft,
     db: SimpleDb,
+    local_txs: LocalTxWriter,
+    data_limits: DataLimits,
+    db_compaction: CompactionSchedule,
     local_events: LocalEventChannel,
+    peer_wait_timeout: Option<Duration>,
     b_num_to_pause: Option<u64>,
     pause_node: Arc<RwLock<bool>>,
     disable_trigger_messages: Arc<RwLock<bool>>,
     threaded_calls: ThreadedCallChannel<dyn MempoolApi>,
     jurisdiction: String,
-    current_mined_block: Option<MinedBlock>,
+    current_mined_block: Option<Arc<MinedBlock>>,
+    resumed_round: bool,
     druid_pool: DruidPool,
     previous_random_num: Vec<u8>,
     current_random_num: Vec<u8>,
@@ -171,6 +198,11 @@ pub struct MempoolNode {
         Node,
     ),
     init_issuances: Vec<InitialIssuance>,
+    event_publisher: Option<EventPublisher>,
+    event_hooks: EventHooks,
+    replay_windows: ReplayWindows,
+    response_hook: Option<ResponseHook>,
+    screener: Option<Arc<dyn Screener>>,
 }
 
 impl MempoolNode {
@@ -178,7 +210,10 @@ impl MempoolNode {
     /// ### Arguments
     /// * `config` - MempoolNodeConfig for the current mempool node containing mempool nodes and storage nodes
     /// * `extra`  - additional parameter for construction
-    pub async fn new(config: MempoolNodeConfig, mut extra: ExtraNodeParams) -> Result<Self> {
+    pub async fn new(mut config: MempoolNodeConfig, mut extra: ExtraNodeParams) -> Result<Self> {
+        if let Some(tls_config) = extra.tls_config.take() {
+            config.tls_config = tls_config;
+        }
         let raw_addr = config
             .mempool_nodes
             .get(config.mempool_node_idx)
@@ -187,6 +222,20 @@ impl MempoolNode {
             MempoolError::ConfigError("Invalid mempool node address in config file")
         })?;
 
+        if let Some(reward_schedule) = &config.mempool_reward_schedule {
+            if !reward_schedule.is_well_formed() {
+                return Err(MempoolError::ConfigError("Invalid reward schedule"));
+            }
+        }
+
+        if let Some(path) = &config.mempool_seed_utxo_file {
+            config.mempool_seed_utxo =
+                load_seed_utxo_file(path).map_err(MempoolError::GenericError)?;
+        }
+        let genesis_allocation = config.mempool_genesis_allocation.map(TokenAmount);
+        validate_seed_utxo(&config.mempool_seed_utxo, genesis_allocation)
+            .map_err(MempoolError::GenericError)?;
+
         let init_issuances = config.initial_issuances.clone();
         let raw_storage_addr = config
             .storage_nodes
@@ -198,11 +247,57 @@ impl MempoolNode {
                 MempoolError::ConfigError("Invalid storage node address in config file")
             })?;
 
-        let tcp_tls_config = TcpTlsConfig::from_tls_spec(addr, &config.tls_config)?;
+        let mut tcp_tls_config =
+            TcpTlsConfig::from_tls_spec(extra.bind_address.unwrap_or(addr), &config.tls_config)?;
+        if let Some(listener) = extra.listener.take() {
+            tcp_tls_config = tcp_tls_config.with_listener(listener).await;
+        }
+        if config.mempool_raft != 0 && config.tls_config.pin_raft_peer_certificates {
+            let raw_raft_addrs: Vec<_> = config
+                .mempool_nodes
+                .iter()
+                .map(|n| n.address.clone())
+                .collect();
+            let raft_addrs = create_socket_addr_for_list(&raw_raft_addrs)
+                .await
+                .map_err(|_| {
+                    MempoolError::ConfigError("Invalid mempool node address in config file")
+                })?;
+            tcp_tls_config.pin_peer_certificates(&config.tls_config, &raft_addrs)?;
+        }
+        if let Some(spec) = &config.peer_ip_filter {
+            tcp_tls_config.set_peer_ip_filter(spec)?;
+        }
+        if let Some(capacity) = config.event_queue_capacity {
+            tcp_tls_config.set_event_queue_capacity(capacity);
+        }
+        if let Some(key_path) = &config.backup_signing_key {
+            set_snapshot_signer(key_path)
+                .map_err(|_| MempoolError::ConfigError("Invalid backup signing key"))?;
+        }
+        let identity = load_node_identity(
+            config.identity_file.as_deref(),
+            config.mempool_db_mode,
+            &DB_SPEC,
+        )
+        .map_err(|_| MempoolError::ConfigError("Invalid node identity file"))?;
+        set_snapshot_identity(&identity);
         let api_addr = SocketAddr::new(addr.ip(), config.mempool_api_port);
-        let api_tls_info = config
-            .mempool_api_use_tls
-            .then(|| tcp_tls_config.clone_private_info());
+        let api_tls_info = match (config.mempool_api_use_tls, &config.mempool_api_client_auth) {
+            (true, client_auth) => {
+                let mut info = tcp_tls_config.clone_private_info();
+                if let Some(spec) = client_auth {
+                    info.client_auth = Some(ApiClientAuth::from_spec(&info, spec)?);
+                }
+                Some(info)
+            }
+            (false, None) => None,
+            (false, Some(_)) => {
+                return Err(MempoolError::ConfigError(
+                    "API client authentication needs API TLS",
+                ))
+            }
+        };
 
         let node = Node::new(
             &tcp_tls_config,
@@ -212,12 +307,16 @@ impl MempoolNode {
             true,
         )
         .await?;
-        let node_raft = MempoolRaft::new(&config, extra.raft_db.take()).await;
+        node.set_event_hooks(extra.event_hooks.clone());
+        node.set_identity(identity);
+        node.set_network_id(config.network_id.unwrap_or_default());
+        let node_raft = MempoolRaft::new(&config, extra.raft_db.take(), extra.clock).await;
 
         if config.backup_restore.unwrap_or(false) {
-            db_utils::restore_file_backup(config.mempool_db_mode, &DB_SPEC, None).unwrap();
+            db_utils::restore_file_backup(config.mempool_db_mode, &DB_SPEC, None)?;
         }
-        let db = db_utils::new_db(config.mempool_db_mode, &DB_SPEC, extra.db.take(), None);
+        let mut db = db_utils::new_db(config.mempool_db_mode, &DB_SPEC, extra.db.take(), None);
+        db_utils::check_network_id(&mut db, node.network_id())?;
         let shutdown_group = {
             let storage = std::iter::once(storage_addr);
             let raft_peers = node_raft.raft_peer_addrs().copied();
@@ -230,6 +329,26 @@ impl MempoolNode {
             .enable_trigger_messages_pipeline_reset
             .unwrap_or(false);
         let api_info = (api_addr, api_tls_info, api_keys, api_pow_info, node.clone());
+        let event_publisher = match &config.mempool_event_bus {
+            Some(bus) => Some(
+                EventPublisher::start(bus)
+                    .await
+                    .map_err(|_| MempoolError::ConfigError("Invalid event bus"))?,
+            ),
+            None => None,
+        };
+        let screener: Option<Arc<dyn Screener>> = match &config.mempool_screening {
+            Some(ScreeningConfig::File(path)) => Some(Arc::new(
+                AddressListScreener::from_file(path)
+                    .map_err(|_| MempoolError::ConfigError("Invalid screening file"))?,
+            )),
+            Some(ScreeningConfig::Service(service)) => {
+                Some(Arc::new(RemoteScreener::new(service).map_err(|_| {
+                    MempoolError::ConfigError("Invalid screening service")
+                })?))
+            }
+            None => None,
+        };
 
         let shared_config = MempoolNodeSharedConfig {
             mempool_mining_event_timeout: config.mempool_mining_event_timeout,
@@ -237,19 +356,36 @@ impl MempoolNode {
             mempool_miner_whitelist: config.mempool_miner_whitelist,
         };
 
+        let local_txs = LocalTxWriter::new(
+            DB_COL_LOCAL_TXS,
+            config
+                .mempool_local_tx_flush_len
+                .unwrap_or(local_tx_store::DEFAULT_FLUSH_LEN),
+            Duration::from_millis(
+                config
+                    .mempool_local_tx_flush_timeout
+                    .unwrap_or(local_tx_store::DEFAULT_FLUSH_TIMEOUT),
+            ),
+        );
+
         MempoolNode {
             node,
             node_raft,
             db,
+            local_txs,
+            data_limits: DataLimits::new(config.max_output_data_size, config.max_tx_data_size),
+            db_compaction: CompactionSchedule::new(config.db_compaction_interval),
             shared_config,
             received_shared_config: Default::default(),
             received_runtime_data: Default::default(),
-            local_events: Default::default(),
+            local_events: LocalEventChannel::new(config.local_event_capacity),
+            peer_wait_timeout: config.peer_wait_timeout.map(Duration::from_millis),
             pause_node: Default::default(),
             b_num_to_pause: Default::default(),
             disable_trigger_messages: Default::default(),
             threaded_calls: Default::default(),
             current_mined_block: None,
+            resumed_round: false,
             druid_pool: Default::default(),
             current_trigger_messages_count: Default::default(),
             enable_trigger_messages_pipeline_reset,
@@ -269,6 +405,11 @@ impl MempoolNode {
             api_info,
             fetched_utxo_set: None,
             init_issuances,
+            event_publisher,
+            event_hooks: extra.event_hooks,
+            replay_windows: Default::default(),
+            response_hook: extra.response_hook,
+            screener,
         }
         .load_local_db()
     }
@@ -325,7 +466,7 @@ impl MempoolNode {
     }
 
     /// Get the node's mined block if any
-    pub fn get_current_mined_block(&self) -> &Option<MinedBlock> {
+    pub fn get_current_mined_block(&self) -> &Option<Arc<MinedBlock>> {
         &self.current_mined_block
     }
 
@@ -361,14 +502,35 @@ impl MempoolNode {
         {
             error!("Failed to send RequestRuntimeData to mempool peers: {}", e);
         }
+        if std::mem::take(&mut self.resumed_round) {
+            self.resume_round().await?;
+        }
         Ok(())
     }
 
+    /// Resume the round restored from the database
+    ///
+    /// A mined block goes to storage, otherwise the block to mine goes to the miners.
+    async fn resume_round(&mut self) -> Result<()> {
+        if self.current_mined_block.is_some() {
+            info!("Resume round: send block to storage");
+            self.send_block_to_storage().await
+        } else {
+            info!("Resume round: send block to partition");
+            self.flood_rand_and_block_to_partition().await
+        }
+    }
+
     /// Local event channel.
     pub fn local_event_tx(&self) -> &LocalEventSender {
         &self.local_events.tx
     }
 
+    /// Time to wait for peers to connect before starting, unlimited if None
+    pub fn peer_wait_timeout(&self) -> Option<Duration> {
+        self.peer_wait_timeout
+    }
+
     /// Threaded call channel.
     pub fn threaded_call_tx(&self) -> &ThreadedCallSender<dyn MempoolApi> {
         &self.threaded_calls.tx
@@ -388,7 +550,7 @@ impl MempoolNode {
     }
 
     /// The current tx_pool that will be used to generate next block
-    pub fn get_committed_tx_pool(&self) -> &BTreeMap<String, Transaction> {
+    pub fn get_committed_tx_pool(&self) -> &TxPool {
         self.node_raft.get_committed_tx_pool()
     }
 
@@ -444,14 +606,21 @@ impl MempoolNode {
         self.node_raft.raft_loop()
     }
 
+    /// Return the metrics updated by the raft loop.
+    pub fn raft_metrics(&self) -> SharedRaftMetrics {
+        self.node_raft.raft_metrics()
+    }
+
     /// Signal to the raft loop to complete
     pub async fn close_raft_loop(&mut self) {
+        self.local_txs.flush(&mut self.db);
         self.node_raft.close_raft_loop().await
     }
 
     /// Extract persistent dbs
     pub async fn take_closed_extra_params(&mut self) -> ExtraNodeParams {
         let raft_db = self.node_raft.take_closed_persistent_store().await;
+        self.local_txs.flush(&mut self.db);
         ExtraNodeParams {
             db: self.db.take().in_memory(),
             raft_db: raft_db.in_memory(),
@@ -462,12 +631,18 @@ impl MempoolNode {
     /// Backup persistent dbs
     pub async fn backup_persistent_dbs(&mut self) {
         if self.node_raft.need_backup() {
+            self.local_txs.flush(&mut self.db);
             if let Err(e) = self.db.file_backup() {
                 error!("Error bakup up main db: {:?}", e);
             }
         }
     }
 
+    /// Read every entry of the main database, returning the number of entries
+    pub fn scan_dbs(&self) -> db_utils::Result<usize> {
+        self.db.scan()
+    }
+
     /// Info needed to run the API point.
     pub fn api_inputs(
         &self,
@@ -507,9 +682,10 @@ impl MempoolNode {
                 if droplet.txs.len() == droplet.participants {
                     let valid = druid_expectations_are_met(&druid, droplet.txs.values())
                         && check_druid_participants(droplet);
-                    ready_txs.push((valid, droplet.txs.clone()));
                     // TODO: Implement time-based removal?
-                    self.druid_pool.remove(&druid);
+                    if let Some(droplet) = self.druid_pool.remove(&druid) {
+                        ready_txs.push((valid, droplet.txs));
+                    }
                 }
             }
         }
@@ -517,7 +693,7 @@ impl MempoolNode {
     }
 
     /// Returns the mining block from the node_raft
-    pub fn get_mining_block(&self) -> &Option<Block> {
+    pub fn get_mining_block(&self) -> &Option<Arc<Block>> {
         self.node_raft.get_mining_block()
     }
 
@@ -544,7 +720,9 @@ impl MempoolNode {
     /// * `winning_pow`    - Address nad PoW for the winner to use
     pub fn test_skip_mining(&mut self, winning_pow: (SocketAddr, WinningPoWInfo), seed: Vec<u8>) {
         self.node_raft.test_skip_mining(winning_pow, seed);
-        self.mining_block_mined();
+        if let Err(e) = self.mining_block_mined() {
+            error!("Mined block not set {:?}", e);
+        }
     }
 
     /// Gets a decremented socket address of peer for storage
@@ -558,39 +736,36 @@ impl MempoolNode {
         storage_address
     }
 
-    /// Return closure use to validate a transaction
-    fn transactions_validator(&self) -> impl Fn(&Transaction) -> bool + '_ {
+    /// Return closure use to validate a transaction, shareable between validation threads
+    fn transactions_validator(&self) -> impl Fn(&Transaction) -> bool + Sync + '_ {
         let utxo_set = self.node_raft.get_committed_utxo_set();
-        let lock_expired = self
-            .node_raft
-            .get_committed_current_block_num()
-            .unwrap_or_default();
+        let timestamp = self.node_raft.get_committed_timestamp();
         //let sanction_list = &self.sanction_list;
         let b_num = self
             .node_raft
             .get_committed_current_block_num()
             .unwrap_or_default();
+        let data_limits = self.data_limits;
 
         move |tx| {
-            if tx.is_create_tx() {
-                return tx_has_valid_create_script(
-                    &tx.inputs[0].script_signature,
-                    &tx.outputs[0].value,
-                );
+            if !data_limits.allows(tx) {
+                return false;
             }
 
             !tx.is_coinbase()
-                && tx_is_valid(tx, b_num, |v| {
-                    utxo_set
-                        .get(v)
-                        // .filter(|_| !sanction_list.contains(&v.t_hash))
-                        .filter(|tx_out| lock_expired >= tx_out.locktime)
+                && tx_spends_are_valid(tx, b_num, timestamp, |v| {
+                    utxo_set.get(v)
+                    // .filter(|_| !sanction_list.contains(&v.t_hash))
                 })
         }
     }
 
     /// Sends the latest block to storage
     pub async fn send_block_to_storage(&mut self) -> Result<()> {
+        if self.node_raft.is_standby() {
+            // Standby nodes follow the round without acting on it
+            return Ok(());
+        }
         let mined_block = self.current_mined_block.clone();
 
         info!("");
@@ -655,11 +830,14 @@ impl MempoolNode {
         response: Result<Response>,
     ) -> ResponseResult {
         debug!("Response: {:?}", response);
+        if let (Some(hook), Ok(resp)) = (&self.response_hook, &response) {
+            hook.call(resp);
+        }
 
         match response {
             Ok(Response {
                 success: true,
-                reason: "Received UTXO fetch request",
+                reason: ResponseReason::ReceivedUtxoFetchRequest,
             }) => {
                 if let Err(e) = self.send_fetched_utxo_set().await {
                     error!("Requested UTXO set not sent {:?}", e);
@@ -667,86 +845,86 @@ impl MempoolNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Received coordinated pause request",
+                reason: ResponseReason::ReceivedCoordinatedPauseRequest,
             }) => {
                 debug!("Received coordinated pause request");
             }
             Ok(Response {
                 success: true,
-                reason: "Node pause configuration set",
+                reason: ResponseReason::NodePauseConfigurationSet,
             }) => {
                 debug!("Node pause configuration set");
             }
             Ok(Response {
                 success: true,
-                reason: "Received coordinated resume request",
+                reason: ResponseReason::ReceivedCoordinatedResumeRequest,
             }) => {
                 debug!("Received coordinated resume request");
             }
             Ok(Response {
                 success: true,
-                reason: "Node resumed",
+                reason: ResponseReason::NodeResumed,
             }) => {
                 warn!("NODE RESUMED");
             }
             Ok(Response {
                 success: true,
-                reason: "Received shared config",
+                reason: ResponseReason::ReceivedSharedConfig,
             }) => {
                 debug!("Shared config received");
             }
             Ok(Response {
                 success: true,
-                reason: "Shared config applied",
+                reason: ResponseReason::SharedConfigApplied,
             }) => {
                 debug!("Shared config applied");
             }
             Ok(Response {
                 success: false,
-                reason: "No shared config to apply",
+                reason: ResponseReason::NoSharedConfigToApply,
             }) => {
                 warn!("No shared config to apply");
             }
             Ok(Response {
                 success: true,
-                reason: "Miner removal request received",
+                reason: ResponseReason::MinerRemovalRequestReceived,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Shutdown",
+                reason: ResponseReason::Shutdown,
             }) => {
                 warn!("Shutdown now");
                 return ResponseResult::Exit;
             }
             Ok(Response {
                 success: true,
-                reason: "Shutdown pending",
+                reason: ResponseReason::ShutdownPending,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Start coordinated shutdown",
+                reason: ResponseReason::StartCoordinatedShutdown,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Received partition request successfully",
+                reason: ResponseReason::ReceivedPartitionRequestSuccessfully,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Received first full partition request",
+                reason: ResponseReason::ReceivedFirstFullPartitionRequest,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Removing unauthorized miner",
+                reason: ResponseReason::RemovingUnauthorizedMiner,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Received PoW successfully",
+                reason: ResponseReason::ReceivedPowSuccessfully,
             }) => {
                 debug!("Proposing winning PoW entry");
             }
             Ok(Response {
                 success: true,
-                reason: "Winning PoW intake open",
+                reason: ResponseReason::WinningPowIntakeOpen,
             }) => {
                 debug!(
                     "Block and participants ready to mine: {:?}",
@@ -756,11 +934,13 @@ impl MempoolNode {
                     "No. of connected miners: {:?}",
                     self.get_connected_miners().await.len()
                 );
-                self.flood_rand_and_block_to_partition().await.unwrap();
+                if let Err(e) = self.flood_rand_and_block_to_partition().await {
+                    error!("Block not sent to partition {:?}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Pipeline halted",
+                reason: ResponseReason::PipelineHalted,
             }) => {
                 info!("Send Block to storage");
                 debug!("CURRENT MINED BLOCK: {:?}", self.current_mined_block);
@@ -770,7 +950,7 @@ impl MempoolNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Pipeline reset",
+                reason: ResponseReason::PipelineReset,
             }) => {
                 warn!(
                     "Pipeline reset to :{:?}",
@@ -779,15 +959,15 @@ impl MempoolNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Sent runtime data to peer",
+                reason: ResponseReason::SentRuntimeDataToPeer,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Failed to send runtime data to peer",
+                reason: ResponseReason::FailedToSendRuntimeDataToPeer,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Received runtime data from peer",
+                reason: ResponseReason::ReceivedRuntimeDataFromPeer,
             }) => {
                 debug!("Received runtime data from peer");
                 if let Some(runtime_data) = self.received_runtime_data.take() {
@@ -801,23 +981,27 @@ impl MempoolNode {
             }
             Ok(Response {
                 success: false,
-                reason: "Received runtime data from unknown peer",
+                reason: ResponseReason::ReceivedRuntimeDataFromUnknownPeer,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Transactions added to tx pool",
+                reason: ResponseReason::TransactionsAddedToTxPool,
             }) => {
                 debug!("Transactions received and processed successfully");
             }
             Ok(Response {
                 success: true,
-                reason: "First Block committed",
+                reason: ResponseReason::FirstBlockCommitted,
             }) => {
                 // Only continue with the mining process if the node is not paused
                 if !self.is_paused().await {
                     debug!("First block ready to mine: {:?}", self.get_mining_block());
-                    self.flood_block_to_users().await.unwrap();
-                    self.flood_rand_and_block_to_partition().await.unwrap()
+                    if let Err(e) = self.flood_block_to_users().await {
+                        error!("Block not sent to users {:?}", e);
+                    }
+                    if let Err(e) = self.flood_rand_and_block_to_partition().await {
+                        error!("Block not sent to partition {:?}", e);
+                    }
                 } else {
                     warn!("NODE PAUSED");
                     // Disable trigger messages to ensure mining cannot take place
@@ -826,13 +1010,17 @@ impl MempoolNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Block committed",
+                reason: ResponseReason::BlockCommitted,
             }) => {
                 // Only continue with the mining process if the node is not paused
                 if !self.is_paused().await {
                     debug!("Block ready to mine: {:?}", self.get_mining_block());
-                    self.flood_block_to_users().await.unwrap();
-                    self.flood_rand_and_block_to_partition().await.unwrap()
+                    if let Err(e) = self.flood_block_to_users().await {
+                        error!("Block not sent to users {:?}", e);
+                    }
+                    if let Err(e) = self.flood_rand_and_block_to_partition().await {
+                        error!("Block not sent to partition {:?}", e);
+                    }
                 } else {
                     warn!("NODE PAUSED");
                     // Disable trigger messages to ensure mining cannot take place
@@ -841,56 +1029,58 @@ impl MempoolNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Block shutdown",
+                reason: ResponseReason::BlockShutdown,
             }) => {
                 debug!("Block shutdown (not ready to mine)");
-                self.flood_closing_events().await.unwrap();
+                if let Err(e) = self.flood_closing_events().await {
+                    error!("Closing events not sent {:?}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Transactions committed",
+                reason: ResponseReason::TransactionsCommitted,
             }) => {
                 debug!("Transactions ready to be used in next block");
             }
             Ok(Response {
                 success: true,
-                reason: "Received block stored",
+                reason: ResponseReason::ReceivedBlockStored,
             }) => {
                 info!("Block info received from storage: ready to generate block");
             }
             Ok(Response {
                 success: true,
-                reason: "Snapshot applied",
+                reason: ResponseReason::SnapshotApplied,
             }) => {
                 warn!("Snapshot applied");
             }
             Ok(Response {
                 success: true,
-                reason: "Received block notification",
+                reason: ResponseReason::ReceivedBlockNotification,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Partition PoW received successfully",
+                reason: ResponseReason::PartitionPowReceivedSuccessfully,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Sent startup requests on reconnection",
+                reason: ResponseReason::SentStartupRequests,
             }) => debug!("Sent startup requests on reconnection"),
             Ok(Response {
                 success: false,
-                reason: "Failed to send startup requests on reconnection",
+                reason: ResponseReason::FailedToSendStartupRequests,
             }) => error!("Failed to send startup requests on reconnection"),
             Ok(Response {
                 success: false,
-                reason: "Partition list complete",
+                reason: ResponseReason::PartitionListComplete,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "PoW received is invalid",
+                reason: ResponseReason::PowReceivedIsInvalid,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Not block currently mined",
+                reason: ResponseReason::NotBlockCurrentlyMined,
             }) => {}
             Ok(Response {
                 success: true,
@@ -914,7 +1104,7 @@ impl MempoolNode {
 
     /// Listens for new events from peers and handles them.
     /// The future returned from this function should be executed in the runtime. It will block execution.
-    pub async fn handle_next_event<E: Future<Output = &'static str> + Unpin>(
+    pub async fn handle_next_event<E: Future<Output = ResponseReason> + Unpin>(
         &mut self,
         exit: &mut E,
     ) -> Option<Result<Response>> {
@@ -971,6 +1161,14 @@ impl MempoolNode {
                 Some(f) = self.threaded_calls.rx.recv(), if ready => {
                     f(self);
                 }
+                _ = self.local_txs.flush_timeout(), if self.local_txs.has_pending() => {
+                    trace!("handle_next_event flush local transactions");
+                    self.local_txs.flush(&mut self.db);
+                }
+                _ = self.db_compaction.tick() => {
+                    trace!("handle_next_event compact db");
+                    self.compact_db();
+                }
                 reason = &mut *exit => return Some(Ok(Response {
                     success: true,
                     reason,
@@ -988,51 +1186,63 @@ impl MempoolNode {
         match self.node_raft.received_commit(commit_data).await {
             Some(CommittedItem::FirstBlock) => {
                 self.reset_mining_block_process().await;
+                self.store_round_state();
                 self.backup_persistent_dbs().await;
                 Some(Ok(Response {
                     success: true,
-                    reason: "First Block committed",
+                    reason: ResponseReason::FirstBlockCommitted,
                 }))
             }
             Some(CommittedItem::Block) => {
                 self.reset_mining_block_process().await;
+                self.store_round_state();
                 self.backup_persistent_dbs().await;
                 Some(Ok(Response {
                     success: true,
-                    reason: "Block committed",
+                    reason: ResponseReason::BlockCommitted,
                 }))
             }
             Some(CommittedItem::BlockShutdown) => {
                 self.reset_mining_block_process().await;
+                self.store_round_state();
                 self.backup_persistent_dbs().await;
                 Some(Ok(Response {
                     success: true,
-                    reason: "Block shutdown",
+                    reason: ResponseReason::BlockShutdown,
+                }))
+            }
+            Some(CommittedItem::StartPhasePowIntake) => {
+                self.store_round_state();
+                Some(Ok(Response {
+                    success: true,
+                    reason: ResponseReason::WinningPowIntakeOpen,
                 }))
             }
-            Some(CommittedItem::StartPhasePowIntake) => Some(Ok(Response {
-                success: true,
-                reason: "Winning PoW intake open",
-            })),
             Some(CommittedItem::StartPhaseHalted) => {
-                self.mining_block_mined();
+                self.store_round_state();
+                if let Err(e) = self.mining_block_mined() {
+                    return Some(Err(e));
+                }
                 Some(Ok(Response {
                     success: true,
-                    reason: "Pipeline halted",
+                    reason: ResponseReason::PipelineHalted,
+                }))
+            }
+            Some(CommittedItem::ResetPipeline) => {
+                self.store_round_state();
+                Some(Ok(Response {
+                    success: true,
+                    reason: ResponseReason::PipelineReset,
                 }))
             }
-            Some(CommittedItem::ResetPipeline) => Some(Ok(Response {
-                success: true,
-                reason: "Pipeline reset",
-            })),
             Some(CommittedItem::Transactions) => {
-                delete_local_transactions(
+                self.local_txs.delete(
                     &mut self.db,
                     &self.node_raft.take_local_tx_hash_last_commited(),
                 );
                 Some(Ok(Response {
                     success: true,
-                    reason: "Transactions committed",
+                    reason: ResponseReason::TransactionsCommitted,
                 }))
             }
             Some(CommittedItem::Snapshot) => {
@@ -1042,7 +1252,7 @@ impl MempoolNode {
 
                 Some(Ok(Response {
                     success: true,
-                    reason: "Snapshot applied",
+                    reason: ResponseReason::SnapshotApplied,
                 }))
             }
             Some(CommittedItem::CoordinatedCmd(cmd)) => self.handle_coordinated_cmd(cmd).await,
@@ -1066,19 +1276,19 @@ impl MempoolNode {
                     error!("Failed to send startup requests on reconnect: {}", err);
                     return Some(Response {
                         success: false,
-                        reason: "Failed to send startup requests on reconnection",
+                        reason: ResponseReason::FailedToSendStartupRequests,
                     });
                 }
                 Some(Response {
                     success: true,
-                    reason: "Sent startup requests on reconnection",
+                    reason: ResponseReason::SentStartupRequests,
                 })
             }
             LocalEvent::CoordinatedShutdown(shutdown) => {
                 self.coordinated_shutdown = shutdown;
                 Some(Response {
                     success: true,
-                    reason: "Start coordinated shutdown",
+                    reason: ResponseReason::StartCoordinatedShutdown,
                 })
             }
             LocalEvent::Ignore => None,
@@ -1143,15 +1353,27 @@ impl MempoolNode {
                 block_num,
                 nonce,
                 coinbase,
-            } => self.receive_pow(peer, block_num, nonce, coinbase).await,
+                replay_tag,
+            } => match self.check_replay(peer, replay_tag) {
+                Err(response) => Some(response),
+                Ok(()) => self.receive_pow(peer, block_num, nonce, coinbase).await,
+            },
             SendPartitionEntry {
                 pow_info,
                 partition_entry,
-            } => {
-                self.receive_partition_entry(peer, pow_info, partition_entry)
-                    .await
-            }
+                replay_tag,
+            } => match self.check_replay(peer, replay_tag) {
+                Err(response) => Some(response),
+                Ok(()) => {
+                    self.receive_partition_entry(peer, pow_info, partition_entry)
+                        .await
+                }
+            },
             SendTransactions { transactions } => Some(self.receive_transactions(transactions)),
+            SendExpiringTransactions {
+                transactions,
+                expiry,
+            } => Some(self.receive_transactions_with_expiry(transactions, Some(expiry))),
             SendUserBlockNotificationRequest => {
                 Some(self.receive_block_user_notification_request(peer))
             }
@@ -1210,14 +1432,14 @@ impl MempoolNode {
         {
             return Some(Response {
                 success: false,
-                reason: "Received runtime data from unknown peer",
+                reason: ResponseReason::ReceivedRuntimeDataFromUnknownPeer,
             });
         }
 
         self.received_runtime_data = Some(runtime_data);
         Some(Response {
             success: true,
-            reason: "Received runtime data from peer",
+            reason: ResponseReason::ReceivedRuntimeDataFromPeer,
         })
     }
 
@@ -1229,13 +1451,13 @@ impl MempoolNode {
             error!("Failed to send runtime data to peer: {}", e);
             return Some(Response {
                 success: false,
-                reason: "Failed to send runtime data to peer",
+                reason: ResponseReason::FailedToSendRuntimeDataToPeer,
             });
         }
 
         Some(Response {
             success: true,
-            reason: "Sent runtime data to peer",
+            reason: ResponseReason::SentRuntimeDataToPeer,
         })
     }
 
@@ -1247,7 +1469,7 @@ impl MempoolNode {
         self.miner_removal_list.write().await.insert(peer);
         Some(Response {
             success: true,
-            reason: "Miner removal request received",
+            reason: ResponseReason::MinerRemovalRequestReceived,
         })
     }
 
@@ -1266,7 +1488,7 @@ impl MempoolNode {
                 warn!("Pausing node at b_num: {b_num}");
                 Some(Ok(Response {
                     success: true,
-                    reason: "Node pause configuration set",
+                    reason: ResponseReason::NodePauseConfigurationSet,
                 }))
             }
             CoordinatedCommand::ResumeNodes => {
@@ -1278,7 +1500,7 @@ impl MempoolNode {
                 *self.disable_trigger_messages.write().await = false;
                 Some(Ok(Response {
                     success: true,
-                    reason: "Node resumed",
+                    reason: ResponseReason::NodeResumed,
                 }))
             }
             CoordinatedCommand::ApplySharedConfig => {
@@ -1286,12 +1508,12 @@ impl MempoolNode {
                     self.apply_shared_config(received_shared_config).await;
                     return Some(Ok(Response {
                         success: true,
-                        reason: "Shared config applied",
+                        reason: ResponseReason::SharedConfigApplied,
                     }));
                 }
                 Some(Ok(Response {
                     success: false,
-                    reason: "No shared config to apply",
+                    reason: ResponseReason::NoSharedConfigToApply,
                 }))
             }
         }
@@ -1357,13 +1579,13 @@ impl MempoolNode {
         if !self.shutdown_group.is_empty() {
             return Some(Response {
                 success: true,
-                reason: "Shutdown pending",
+                reason: ResponseReason::ShutdownPending,
             });
         }
 
         Some(Response {
             success: true,
-            reason: "Shutdown",
+            reason: ResponseReason::Shutdown,
         })
     }
 
@@ -1419,7 +1641,9 @@ impl MempoolNode {
             let b_num_to_pause = current_b_num + b_num;
             info!("Initiating coordinated pause for b_num {}", b_num_to_pause);
             self.propose_pause_nodes(b_num_to_pause).await;
-            self.initiate_pause_nodes(b_num_to_pause).await.unwrap();
+            if let Err(e) = self.initiate_pause_nodes(b_num_to_pause).await {
+                error!("Coordinated pause not sent to peers {:?}", e);
+            }
         } else if self.node_raft.get_peers().contains(&peer) {
             // We are receiving the coordinated pause so we just need b_num here
             // - the original coordinator of the pause event has already added current b_num
@@ -1429,7 +1653,7 @@ impl MempoolNode {
         }
         Some(Response {
             success: true,
-            reason: "Received coordinated pause request",
+            reason: ResponseReason::ReceivedCoordinatedPauseRequest,
         })
     }
 
@@ -1443,11 +1667,13 @@ impl MempoolNode {
         // We are initiating the coordinated resume
         if self.local_address() == peer {
             info!("Initiating coordinated resume");
-            self.initiate_resume_nodes().await.unwrap();
+            if let Err(e) = self.initiate_resume_nodes().await {
+                error!("Coordinated resume not sent to peers {:?}", e);
+            }
         }
         Some(Response {
             success: true,
-            reason: "Received coordinated resume request",
+            reason: ResponseReason::ReceivedCoordinatedResumeRequest,
         })
     }
 
@@ -1466,16 +1692,19 @@ impl MempoolNode {
         // We are initiating the shared config sending process
         if self.local_address() == peer {
             info!("Initiating shared config");
-            self.initiate_send_shared_config(shared_config.clone())
+            if let Err(e) = self
+                .initiate_send_shared_config(shared_config.clone())
                 .await
-                .unwrap();
+            {
+                error!("Shared config not sent to peers {:?}", e);
+            }
         }
         // The sharing of a shared config was initiated by a peer
         debug!("Received shared config {:?} from {}", &shared_config, peer);
         self.received_shared_config = Some(shared_config);
         Some(Response {
             success: true,
-            reason: "Received shared config",
+            reason: ResponseReason::ReceivedSharedConfig,
         })
     }
 
@@ -1485,17 +1714,15 @@ impl MempoolNode {
     /// * `peer` - Sending peer's socket address
     fn receive_block_user_notification_request(&mut self, peer: SocketAddr) -> Response {
         self.user_notification_list.insert(peer);
-        self.db
-            .put_cf(
-                DB_COL_INTERNAL,
-                USER_NOTIFY_LIST_KEY,
-                &serialize(&self.user_notification_list).unwrap(),
-            )
-            .unwrap();
+        store_addr_list(
+            &mut self.db,
+            USER_NOTIFY_LIST_KEY,
+            &self.user_notification_list,
+        );
 
         Response {
             success: true,
-            reason: "Received block notification",
+            reason: ResponseReason::ReceivedBlockNotification,
         }
     }
 
@@ -1559,7 +1786,7 @@ impl MempoolNode {
             }
             return Response {
                 success: true,
-                reason: "Removing unauthorized miner",
+                reason: ResponseReason::RemovingUnauthorizedMiner,
             };
         }
 
@@ -1571,24 +1798,18 @@ impl MempoolNode {
         }
 
         self.request_list.insert(peer);
-        self.db
-            .put_cf(
-                DB_COL_INTERNAL,
-                REQUEST_LIST_KEY,
-                &serialize(&self.request_list).unwrap(),
-            )
-            .unwrap();
+        store_addr_list(&mut self.db, REQUEST_LIST_KEY, &self.request_list);
         if self.request_list_first_flood == Some(self.request_list.len()) {
             self.request_list_first_flood = None;
             self.node_raft.propose_initial_item().await;
             Response {
                 success: true,
-                reason: "Received first full partition request",
+                reason: ResponseReason::ReceivedFirstFullPartitionRequest,
             }
         } else {
             Response {
                 success: true,
-                reason: "Received partition request successfully",
+                reason: ResponseReason::ReceivedPartitionRequestSuccessfully,
             }
         }
     }
@@ -1616,7 +1837,7 @@ impl MempoolNode {
             (MiningPipelineStatus::Halted, _) => {
                 return Some(Response {
                     success: false,
-                    reason: "Partition list complete",
+                    reason: ResponseReason::PartitionListComplete,
                 });
             }
             _ => return None,
@@ -1628,7 +1849,7 @@ impl MempoolNode {
         if !valid_pow {
             return Some(Response {
                 success: false,
-                reason: "PoW received is invalid",
+                reason: ResponseReason::PowReceivedIsInvalid,
             });
         }
 
@@ -1642,7 +1863,7 @@ impl MempoolNode {
 
         Some(Response {
             success: true,
-            reason: "Partition PoW received successfully",
+            reason: ResponseReason::PartitionPowReceivedSuccessfully,
         })
     }
 
@@ -1651,8 +1872,7 @@ impl MempoolNode {
         let _ = self
             .node
             .send_to_all(Some(self.storage_addr).into_iter(), StorageRequest::Closing)
-            .await
-            .unwrap();
+            .await?;
 
         let _ = self
             .node
@@ -1660,14 +1880,12 @@ impl MempoolNode {
                 self.node_raft.raft_peer_addrs().copied(),
                 MempoolRequest::Closing,
             )
-            .await
-            .unwrap();
+            .await?;
 
         let _ = self
             .node
             .send_to_all(self.request_list.iter().copied(), MineRequest::Closing)
-            .await
-            .unwrap();
+            .await?;
 
         let _ = self
             .node
@@ -1675,8 +1893,7 @@ impl MempoolNode {
                 self.user_notification_list.iter().copied(),
                 UserRequest::Closing,
             )
-            .await
-            .unwrap();
+            .await?;
 
         Ok(())
     }
@@ -1687,6 +1904,10 @@ impl MempoolNode {
 
     /// Floods the current block to participants for mining
     pub async fn flood_rand_and_block_to_partition(&mut self) -> Result<()> {
+        if self.node_raft.is_standby() {
+            // Standby nodes follow the round without acting on it
+            return Ok(());
+        }
         let (rnum, participant_only) = match self.node_raft.get_mining_pipeline_status() {
             MiningPipelineStatus::ParticipantOnlyIntake => (self.previous_random_num.clone(), true),
             MiningPipelineStatus::AllItemsIntake => (self.current_random_num.clone(), false),
@@ -1694,7 +1915,11 @@ impl MempoolNode {
         };
 
         let win_coinbases = self.node_raft.get_last_mining_transaction_hashes().clone();
-        let block: &Block = self.node_raft.get_mining_block().as_ref().unwrap();
+        let block: &Block = self
+            .node_raft
+            .get_mining_block()
+            .as_deref()
+            .ok_or(MempoolError::MissingState("No mining block"))?;
 
         info!(
             "RANDOM NUMBER IN COMPUTE: {:?}, (mined:{})",
@@ -1826,13 +2051,7 @@ impl MempoolNode {
             .retain(|addr| !stale_miners.contains(addr));
 
         // Update DB
-        if let Err(e) = self.db.put_cf(
-            DB_COL_INTERNAL,
-            REQUEST_LIST_KEY,
-            &serialize(&self.request_list).unwrap(),
-        ) {
-            error!("Error writing updated miner request list to disk: {e:?}");
-        }
+        store_addr_list(&mut self.db, REQUEST_LIST_KEY, &self.request_list);
 
         // Cleanup miners from block pipeline
         self.node_raft.flush_stale_miners(&stale_miners);
@@ -1841,7 +2060,11 @@ impl MempoolNode {
 
     /// Floods the current block to participants for mining
     pub async fn flood_transactions_to_partition(&mut self) -> Result<()> {
-        let block: &Block = self.node_raft.get_mining_block().as_ref().unwrap();
+        let block: &Block = self
+            .node_raft
+            .get_mining_block()
+            .as_deref()
+            .ok_or(MempoolError::MissingState("No mining block"))?;
         let tx_merkle_verification = block.transactions.clone();
 
         self.node
@@ -1851,15 +2074,22 @@ impl MempoolNode {
                     tx_merkle_verification,
                 },
             )
-            .await
-            .unwrap();
+            .await?;
 
         Ok(())
     }
 
     /// Floods the current block to user listening for updates
     pub async fn flood_block_to_users(&mut self) -> Result<()> {
-        let block: Block = self.node_raft.get_mining_block().clone().unwrap();
+        if self.node_raft.is_standby() {
+            // Standby nodes follow the round without acting on it
+            return Ok(());
+        }
+        let block: Arc<Block> = self
+            .node_raft
+            .get_mining_block()
+            .clone()
+            .ok_or(MempoolError::MissingState("No mining block"))?;
 
         let unsent = self
             .node
@@ -1872,25 +2102,40 @@ impl MempoolNode {
         if !unsent.is_empty() {
             warn!("Purging users: {:?}", unsent);
             self.user_notification_list.retain(|v| !unsent.contains(v));
-            self.db
-                .put_cf(
-                    DB_COL_INTERNAL,
-                    USER_NOTIFY_LIST_KEY,
-                    &serialize(&self.user_notification_list).unwrap(),
-                )
-                .unwrap();
+            store_addr_list(
+                &mut self.db,
+                USER_NOTIFY_LIST_KEY,
+                &self.user_notification_list,
+            );
         }
 
         Ok(())
     }
 
     /// Logs the winner of the block and changes the current block to a new block to be mined
-    pub fn mining_block_mined(&mut self) {
-        let (mut block, mut block_txs) = self.node_raft.take_mining_block().unwrap();
-        let (_, winning_pow) = self.node_raft.get_winning_miner().clone().unwrap();
+    pub fn mining_block_mined(&mut self) -> Result<()> {
+        let (winner, winning_pow) = self
+            .node_raft
+            .get_winning_miner()
+            .clone()
+            .ok_or(MempoolError::MissingState("No winning miner"))?;
+        let (mut block, mut block_txs) = self
+            .node_raft
+            .take_mining_block()
+            .ok_or(MempoolError::MissingState("No mining block"))?;
         let unicorn = self.node_raft.get_current_unicorn().clone();
 
         let mining_tx = winning_pow.mining_tx;
+        let round = PartitionRound::new(
+            block.header.b_num,
+            block.header.timestamp,
+            self.node_raft.get_mining_participants_iter().collect(),
+            self.node_raft.get_pow_miners().iter().copied().collect(),
+            winner,
+            (&mining_tx.0, &mining_tx.1),
+        );
+        put_partition_round(&mut self.db, &round);
+
         let nonce = winning_pow.nonce;
         block.header = apply_mining_tx(block.header, nonce, mining_tx.0.clone());
         block_txs.insert(mining_tx.0, mining_tx.1);
@@ -1906,7 +2151,30 @@ impl MempoolNode {
             unicorn: unicorn.unicorn,
             unicorn_witness: unicorn.witness,
         };
-        self.current_mined_block = Some(MinedBlock { common, extra_info });
+        self.current_mined_block = Some(Arc::new(MinedBlock { common, extra_info }));
+        Ok(())
+    }
+
+    /// Store the state of the current round, unless kept by raft
+    ///
+    /// The halted phase is stored before its block is taken, so the mined block can be rebuilt.
+    fn store_round_state(&mut self) {
+        if self.node_raft.use_raft() {
+            return;
+        }
+        let round_state = match self.node_raft.get_round_state() {
+            Ok(round_state) => round_state,
+            Err(e) => {
+                error!("Error serializing round state: {:?}", e);
+                return;
+            }
+        };
+        if let Err(e) = self
+            .db
+            .put_cf(DB_COL_INTERNAL, ROUND_STATE_KEY, &round_state)
+        {
+            error!("Error writing {} to disk: {:?}", ROUND_STATE_KEY, e);
+        }
     }
 
     /// Reset the mining block processing to allow a new block.
@@ -1916,20 +2184,14 @@ impl MempoolNode {
             .unwrap_or_else(generate_pow_random_num);
 
         self.current_random_num = generate_pow_random_num();
-        self.db
-            .put_cf(
-                DB_COL_INTERNAL,
-                POW_PREV_RANDOM_NUM_KEY,
-                &self.previous_random_num,
-            )
-            .unwrap();
-        self.db
-            .put_cf(
-                DB_COL_INTERNAL,
-                POW_RANDOM_NUM_KEY,
-                &self.current_random_num,
-            )
-            .unwrap();
+        for (key, num) in [
+            (POW_PREV_RANDOM_NUM_KEY, &self.previous_random_num),
+            (POW_RANDOM_NUM_KEY, &self.current_random_num),
+        ] {
+            if let Err(e) = self.db.put_cf(DB_COL_INTERNAL, key, num) {
+                error!("Error writing {} to disk: {:?}", key, e);
+            }
+        }
 
         self.current_mined_block = None;
         self.node_raft.clear_block_pipeline_proposed_keys();
@@ -1948,7 +2210,7 @@ impl MempoolNode {
                 list
             }
             Ok(None) => self.request_list,
-            Err(e) => panic!("Error accessing db: {:?}", e),
+            Err(e) => return Err(e.into()),
         };
         if let Some(first) = self.request_list_first_flood {
             if first <= self.request_list.len() {
@@ -1964,7 +2226,7 @@ impl MempoolNode {
                 list
             }
             Ok(None) => self.user_notification_list,
-            Err(e) => panic!("Error accessing db: {:?}", e),
+            Err(e) => return Err(e.into()),
         };
 
         for (num, key) in [
@@ -1975,12 +2237,10 @@ impl MempoolNode {
                 let current_random_num = match self.db.get_cf(DB_COL_INTERNAL, key) {
                     Ok(Some(num)) => num,
                     Ok(None) => generate_pow_random_num(),
-                    Err(e) => panic!("Error accessing db: {:?}", e),
+                    Err(e) => return Err(e.into()),
                 };
                 debug!("load_local_db: {} {:?}", key, current_random_num);
-                if let Err(e) = self.db.put_cf(DB_COL_INTERNAL, key, &current_random_num) {
-                    panic!("Error accessing db: {:?}", e);
-                }
+                self.db.put_cf(DB_COL_INTERNAL, key, &current_random_num)?;
                 current_random_num
             };
         }
@@ -1989,24 +2249,56 @@ impl MempoolNode {
             let key_run = match self.db.get_cf(DB_COL_INTERNAL, RAFT_KEY_RUN) {
                 Ok(Some(key_run)) => deserialize::<u64>(&key_run)? + 1,
                 Ok(None) => 0,
-                Err(e) => panic!("Error accessing db: {:?}", e),
+                Err(e) => return Err(e.into()),
             };
             debug!("load_local_db: key_run update to {:?}", key_run);
-            if let Err(e) = self
-                .db
-                .put_cf(DB_COL_INTERNAL, RAFT_KEY_RUN, &serialize(&key_run)?)
-            {
-                panic!("Error accessing db: {:?}", e);
-            }
+            self.db
+                .put_cf(DB_COL_INTERNAL, RAFT_KEY_RUN, &serialize(&key_run)?)?;
             key_run
         });
 
+        let (local_txs, local_tx_expiries) = get_local_transactions(&self.db);
         self.node_raft
-            .append_to_tx_pool(get_local_transactions(&self.db));
+            .append_to_tx_pool_with_expiry(local_txs, local_tx_expiries);
+
+        if !self.node_raft.use_raft() {
+            if let Some(round_state) = self.db.get_cf(DB_COL_INTERNAL, ROUND_STATE_KEY)? {
+                self.node_raft.restore_round_state(&round_state)?;
+                let mined = self.node_raft.get_winning_miner().is_some();
+                if mined && self.get_mining_block().is_some() {
+                    self.mining_block_mined()?;
+                }
+                self.resumed_round =
+                    self.current_mined_block.is_some() || self.get_mining_block().is_some();
+                debug!("load_local_db: round resumed {:?}", self.resumed_round);
+            }
+        }
 
         Ok(self)
     }
 
+    /// Checks a tagged message from a peer is neither expired nor replayed
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer` - Peer the message was received from
+    /// * `tag`  - Replay tag of the message
+    fn check_replay(
+        &mut self,
+        peer: SocketAddr,
+        tag: ReplayTag,
+    ) -> std::result::Result<(), Response> {
+        self.replay_windows
+            .check(peer, &tag, get_timestamp_now())
+            .map_err(|e| {
+                warn!("Rejected message from {:?}: {}", peer, e);
+                Response {
+                    success: false,
+                    reason: ResponseReason::MessageRejected(e),
+                }
+            })
+    }
+
     /// Recieves a ProofOfWork from miner
     ///
     /// ### Arguments
@@ -2034,7 +2326,7 @@ impl MempoolNode {
             trace!(?address, "Received outdated PoW");
             return Some(Response {
                 success: false,
-                reason: "Not block currently mined",
+                reason: ResponseReason::NotBlockCurrentlyMined,
             });
         };
 
@@ -2043,7 +2335,7 @@ impl MempoolNode {
         if !coinbase.is_coinbase() || coinbase.outputs[0].value.token_amount() != *coinbase_amount {
             return Some(Response {
                 success: false,
-                reason: "Coinbase transaction invalid",
+                reason: ResponseReason::CoinbaseTransactionInvalid,
             });
         }
 
@@ -2053,7 +2345,7 @@ impl MempoolNode {
         if !validate_pow_block(&block_to_check) {
             return Some(Response {
                 success: false,
-                reason: "Invalid PoW for block",
+                reason: ResponseReason::InvalidPowForBlock,
             });
         }
 
@@ -2080,7 +2372,7 @@ impl MempoolNode {
 
         Some(Response {
             success: true,
-            reason: "Received PoW successfully",
+            reason: ResponseReason::ReceivedPowSuccessfully,
         })
     }
 
@@ -2098,7 +2390,7 @@ impl MempoolNode {
         if peer != self.storage_addr {
             return Some(Response {
                 success: false,
-                reason: "Received block stored not from our storage peer",
+                reason: ResponseReason::BlockStoredNotFromStoragePeer,
             });
         }
 
@@ -2113,7 +2405,7 @@ impl MempoolNode {
 
         Some(Response {
             success: true,
-            reason: "Received block stored",
+            reason: ResponseReason::ReceivedBlockStored,
         })
     }
 
@@ -2214,24 +2506,99 @@ impl MempoolNode {
         &self.node
     }
 
+    /// Get the audit log of the transactions dropped by the screening, oldest first
+    pub fn get_screening_audit_log(&self) -> Result<Vec<ScreeningAuditEntry>> {
+        Ok(get_screening_audit_log(&self.db)?)
+    }
+
+    /// Drop the transactions touching addresses rejected by the screener,
+    /// recording the decisions in the audit log
+    ///
+    /// ### Arguments
+    ///
+    /// * `transactions` - Transactions to screen
+    fn screen_transactions(&mut self, transactions: Vec<Transaction>) -> Vec<Transaction> {
+        let screener = match &self.screener {
+            Some(screener) => screener.clone(),
+            None => return transactions,
+        };
+
+        let utxo_set = self.node_raft.get_committed_utxo_set();
+        let mut decisions = Vec::new();
+        let allowed = transactions
+            .into_iter()
+            .filter(|tx| {
+                let addresses = transaction_addresses(tx, |op| utxo_set.get(op));
+                match screen(screener.as_ref(), &addresses) {
+                    None => true,
+                    Some(decision) => {
+                        let tx_hash = construct_tx_hash(tx);
+                        warn!(
+                            "Transaction {} dropped by screening: {:?}",
+                            tx_hash, decision
+                        );
+                        decisions.push((tx_hash, decision));
+                        false
+                    }
+                }
+            })
+            .collect();
+
+        if !decisions.is_empty() {
+            let mut batch = self.db.batch_writer();
+            append_screening_audit(&self.db, &mut batch, &decisions, get_timestamp_now());
+            let batch = batch.done();
+            if let Err(e) = self.db.write(batch) {
+                error!("Error writing screening decisions to disk: {:?}", e);
+            }
+        }
+        allowed
+    }
+
     /// Receive incoming transactions
     ///
     /// ### Arguments
     ///
     /// * `transactions` - Transactions to be processed
     pub fn receive_transactions(&mut self, transactions: Vec<Transaction>) -> Response {
+        self.receive_transactions_with_expiry(transactions, None)
+    }
+
+    /// Receive incoming transactions, never added to a block once expired
+    ///
+    /// Transactions already expired, and DRUID transactions given an expiry, are invalid.
+    ///
+    /// ### Arguments
+    ///
+    /// * `transactions` - Transactions to be processed
+    /// * `expiry`       - Block number or timestamp the transactions expire at, if any
+    pub fn receive_transactions_with_expiry(
+        &mut self,
+        transactions: Vec<Transaction>,
+        expiry: Option<u64>,
+    ) -> Response {
         let transactions_len = transactions.len();
         if !self.node_raft.tx_pool_can_accept(transactions_len) {
             return Response {
                 success: false,
-                reason: "Transaction pool for this mempool node is full",
+                reason: ResponseReason::TxPoolFull,
             };
         }
 
+        let transactions = self.screen_transactions(transactions);
+
+        let expired = expiry.is_some_and(|expiry| {
+            let b_num = self
+                .node_raft
+                .get_committed_current_block_num()
+                .unwrap_or_default();
+            is_locktime_expired(expiry, b_num, self.node_raft.get_committed_timestamp())
+        });
         let (valid_dde_txs, valid_txs): (BTreeMap<_, _>, BTreeMap<_, _>) = {
             let tx_validator = self.transactions_validator();
             transactions
-                .into_iter()
+                .into_par_iter()
+                .filter(|tx| !expired && (expiry.is_none() || tx.druid_info.is_none()))
                 .filter(|tx| tx_validator(tx))
                 .map(|tx| (construct_tx_hash(&tx), tx))
                 .partition(|tx| tx.1.druid_info.is_some())
@@ -2243,13 +2610,30 @@ impl MempoolNode {
         if total_valid_txs_len == 0 {
             return Response {
                 success: false,
-                reason: "No valid transactions provided",
+                reason: ResponseReason::NoValidTransactionsProvided,
             };
         }
 
         // `Normal` transactions
-        store_local_transactions(&mut self.db, &valid_txs);
-        self.node_raft.append_to_tx_pool(valid_txs);
+        match expiry {
+            Some(expiry) => self
+                .local_txs
+                .store_with_expiry(&mut self.db, &valid_txs, expiry),
+            None => self.local_txs.store(&mut self.db, &valid_txs),
+        }
+        for tx_hash in valid_txs.keys() {
+            publish_event(&self.event_publisher, &self.event_hooks, || {
+                NodeEvent::TxAccepted {
+                    tx_hash: tx_hash.clone(),
+                }
+            });
+        }
+        let expiries = valid_txs
+            .keys()
+            .filter_map(|hash| Some((hash.clone(), expiry?)))
+            .collect();
+        self.node_raft
+            .append_to_tx_pool_with_expiry(valid_txs, expiries);
 
         // `DDE` transactions
         // TODO: Save DDE transactions to local DB storage
@@ -2260,6 +2644,13 @@ impl MempoolNode {
                 invalid_dde_txs_len += 1;
                 continue;
             }
+            for tx_hash in ready.keys() {
+                publish_event(&self.event_publisher, &self.event_hooks, || {
+                    NodeEvent::TxAccepted {
+                        tx_hash: tx_hash.clone(),
+                    }
+                });
+            }
             self.node_raft.append_to_tx_druid_pool(ready);
         }
 
@@ -2268,13 +2659,13 @@ impl MempoolNode {
         if (total_valid_txs_len < transactions_len) || invalid_dde_txs_len != 0 {
             return Response {
                 success: true,
-                reason: "Some transactions invalid. Adding valid transactions only",
+                reason: ResponseReason::SomeTransactionsInvalid,
             };
         }
 
         Response {
             success: true,
-            reason: "Transactions added to tx pool",
+            reason: ResponseReason::TransactionsAddedToTxPool,
         }
     }
 
@@ -2345,28 +2736,28 @@ impl MempoolInterface for MempoolNode {
         };
         Response {
             success: true,
-            reason: "Received UTXO fetch request",
+            reason: ResponseReason::ReceivedUtxoFetchRequest,
         }
     }
 
     fn partition(&self, _uuids: Vec<&'static str>) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 
     fn get_service_levels(&self) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 
     fn execute_contract(&self, _contract: Contract) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 
@@ -2376,6 +2767,15 @@ impl MempoolInterface for MempoolNode {
 }
 
 impl MempoolApi for MempoolNode {
+    fn compact_db(&mut self) -> Response {
+        self.local_txs.flush(&mut self.db);
+        self.db.compact();
+        Response {
+            success: true,
+            reason: ResponseReason::DatabaseCompacted,
+        }
+    }
+
     fn get_shared_config(&self) -> MempoolNodeSharedConfig {
         MempoolNodeSharedConfig {
             mempool_mining_event_timeout: self.node_raft.get_mempool_mining_event_timeout(),
@@ -2392,6 +2792,19 @@ impl MempoolApi for MempoolNode {
         self.get_pending_druid_pool()
     }
 
+    fn get_network_id(&self) -> NetworkId {
+        self.node.network_id()
+    }
+
+    fn get_tx_pool_hashes(&self) -> Vec<String> {
+        let tx_pool = self.node_raft.get_committed_tx_pool();
+        tx_pool.iter().map(|(hash, _)| hash.clone()).collect()
+    }
+
+    fn get_partition_rounds(&self, start: u64, end: u64) -> Vec<PartitionRound> {
+        get_partition_rounds(&self.db, start, end)
+    }
+
     fn get_issued_supply(&self) -> TokenAmount {
         *self.node_raft.get_current_issuance()
     }
@@ -2429,12 +2842,25 @@ impl MempoolApi for MempoolNode {
         {
             return Response {
                 success: false,
-                reason: "Failed to initiate coordinated pause",
+                reason: ResponseReason::FailedToInitiateCoordinatedPause,
             };
         }
         Response {
             success: true,
-            reason: "Attempt coordinated node pause",
+            reason: ResponseReason::AttemptCoordinatedNodePause,
+        }
+    }
+
+    fn failover(&mut self, replaced: SocketAddr) -> Response {
+        if !self.node_raft.failover(replaced) {
+            return Response {
+                success: false,
+                reason: ResponseReason::FailedToProposeFailover,
+            };
+        }
+        Response {
+            success: true,
+            reason: ResponseReason::FailoverProposed,
         }
     }
 
@@ -2445,12 +2871,12 @@ impl MempoolApi for MempoolNode {
         {
             return Response {
                 success: false,
-                reason: "Failed to initiate coordinated resume",
+                reason: ResponseReason::FailedToInitiateCoordinatedResume,
             };
         }
         Response {
             success: true,
-            reason: "Attempt coordinated node resume",
+            reason: ResponseReason::AttemptCoordinatedNodeResume,
         }
     }
 
@@ -2467,55 +2893,60 @@ impl MempoolApi for MempoolNode {
         {
             return Response {
                 success: false,
-                reason: "Failed to initiate sharing of config",
+                reason: ResponseReason::FailedToInitiateSharingOfConfig,
             };
         }
         Response {
             success: true,
-            reason: "Attempt send shared config",
+            reason: ResponseReason::AttemptSendSharedConfig,
         }
     }
 }
 
-/// Get pending transactions
-///
-/// ### Arguments
-///
-/// * `db`             - Database
-fn get_local_transactions(db: &SimpleDb) -> BTreeMap<String, Transaction> {
-    db.iter_cf_clone(DB_COL_LOCAL_TXS)
-        .map(|(k, v)| (String::from_utf8(k), deserialize(&v)))
-        .map(|(k, v)| (k.unwrap(), v.unwrap()))
-        .collect()
-}
-
-/// Add pending transactions
+/// Get pending transactions, along with the expiry of those given one
 ///
 /// ### Arguments
 ///
 /// * `db`             - Database
-/// * `transactions`   - Transactions to store
-fn store_local_transactions(db: &mut SimpleDb, transactions: &BTreeMap<String, Transaction>) {
-    let mut batch = db.batch_writer();
-    for (key, value) in transactions {
-        let value = serialize(value).unwrap();
-        batch.put_cf(DB_COL_LOCAL_TXS, key, &value);
-    }
-    let batch = batch.done();
-    db.write(batch).unwrap();
+fn get_local_transactions(db: &SimpleDb) -> (BTreeMap<String, Transaction>, BTreeMap<String, u64>) {
+    let mut txs = BTreeMap::new();
+    let mut expiries = BTreeMap::new();
+    let items = match db.iter_cf_clone(DB_COL_LOCAL_TXS) {
+        Ok(items) => items,
+        Err(e) => {
+            error!("Failed to read local transactions from db: {:?}", e);
+            return (txs, expiries);
+        }
+    };
+    for (k, v) in items {
+        let value = deserialize::<(Transaction, u64)>(&v)
+            .map(|(tx, expiry)| (tx, Some(expiry)))
+            .or_else(|_| deserialize(&v).map(|tx| (tx, None)));
+        match (String::from_utf8(k), value) {
+            (Ok(k), Ok((tx, expiry))) => {
+                if let Some(expiry) = expiry {
+                    expiries.insert(k.clone(), expiry);
+                }
+                txs.insert(k, tx);
+            }
+            _ => error!("Skipping invalid local transaction in db"),
+        }
+    }
+    (txs, expiries)
 }
 
-/// Delete no longer relevant transaction
+/// Store a list of peer addresses, logging failures
 ///
 /// ### Arguments
 ///
-/// * `db`     - Database
-/// * `keys`   - Keys to delete
-fn delete_local_transactions(db: &mut SimpleDb, keys: &[String]) {
-    let mut batch = db.batch_writer();
-    for key in keys {
-        batch.delete_cf(DB_COL_LOCAL_TXS, key);
-    }
-    let batch = batch.done();
-    db.write(batch).unwrap();
+/// * `db`   - Database
+/// * `key`  - Key of the list
+/// * `list` - Addresses to store
+fn store_addr_list(db: &mut SimpleDb, key: &str, list: &BTreeSet<SocketAddr>) {
+    let result = serialize(list)
+        .map_err(MempoolError::from)
+        .and_then(|list| Ok(db.put_cf(DB_COL_INTERNAL, key, &list)?));
+    if let Err(e) = result {
+        error!("Error writing {} to disk: {:?}", key, e);
+    }
 }
@@ -1,18 +1,22 @@
-use crate::active_raft::ActiveRaft;
+use crate::active_raft::{ActiveRaft, RaftStandby};
 use crate::block_pipeline::{
     MiningPipelineInfo, MiningPipelineInfoImport, MiningPipelineItem, MiningPipelinePhaseChange,
     MiningPipelineStatus, Participants, PipelineEventInfo,
 };
+use crate::block_timestamp::{median_time_past, MEDIAN_TIME_SPAN};
+use crate::clock::Clock;
 use crate::configurations::{MempoolNodeConfig, UnicornFixedInfo};
 use crate::constants::{BLOCK_SIZE_IN_TX, COINBASE_MATURITY, DB_PATH, TX_POOL_LIMIT};
 use crate::db_utils::{self, SimpleDb, SimpleDbError, SimpleDbSpec};
 use crate::interfaces::{BlockStoredInfo, InitialIssuance, UtxoSet, WinningPoWInfo};
-use crate::raft::{RaftCommit, RaftCommitData, RaftData, RaftMessageWrapper};
+use crate::raft::{RaftCommit, RaftCommitData, RaftData, RaftMessageWrapper, SharedRaftMetrics};
 use crate::raft_util::{RaftContextKey, RaftInFlightProposals};
+use crate::reward_schedule::RewardSchedule;
 use crate::tracked_utxo::TrackedUtxoSet;
+use crate::tx_pool::TxPool;
 use crate::unicorn::{UnicornFixedParam, UnicornInfo};
 use crate::utils::{
-    calculate_reward, construct_coinbase_tx, create_socket_addr_for_list, get_timestamp_now,
+    construct_coinbase_tx, create_socket_addr_for_list, get_timestamp_now,
     get_total_coinbase_tokens, make_utxo_set_from_seed, BackupCheck, UtxoReAlignCheck,
 };
 use bincode::{deserialize, serialize};
@@ -21,8 +25,9 @@ use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::future::Future;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::{self, Instant};
+use tokio::time::Instant;
 use tracing::{debug, error, info, trace, warn};
 use tw_chain::crypto::sha3_256;
 use tw_chain::primitives::asset::TokenAmount;
@@ -66,6 +71,8 @@ pub enum MempoolRaftItem {
     CoordinatedCmd(CoordinatedCommand),
     Timestamp(i64),
     RuntimeData(MempoolRuntimeItem),
+    RewardSchedule(RewardSchedule),
+    ExpiringTransactions(BTreeMap<String, (Transaction, u64)>),
 }
 
 /// Mempool RAFT runtime item; will not get stored to disk
@@ -149,7 +156,7 @@ pub struct MempoolConsensused {
     /// Number of miners
     partition_full_size: usize,
     /// Committed transaction pool.
-    tx_pool: BTreeMap<String, Transaction>,
+    tx_pool: TxPool,
     /// Committed DRUID transactions.
     tx_druid_pool: Vec<BTreeMap<String, Transaction>>,
     /// Header to use for next block if ready to generate.
@@ -179,11 +186,18 @@ pub struct MempoolConsensused {
     miner_whitelist: MinerWhitelist,
     /// Timestamp for the current block
     timestamp: i64,
+    /// Timestamps of the last blocks generated, oldest first
+    recent_timestamps: Vec<i64>,
     /// Runtime data that does not get stored to disk
     #[serde(skip)]
     runtime_data: MempoolConsensusedRuntimeData,
     /// Initial issuances
     init_issuances: Vec<InitialIssuance>,
+    /// Reward schedule committed first, used by all the peers
+    committed_reward_schedule: Option<RewardSchedule>,
+    /// Reward schedule from the node configuration, proposed to the peers
+    #[serde(skip)]
+    reward_schedule: RewardSchedule,
 }
 
 /// Consensused info to apply on start up after upgrade.
@@ -216,6 +230,8 @@ pub struct MempoolRaft {
     local_initial_proposal: Option<InitialProposal>,
     /// Local transaction pool.
     local_tx_pool: BTreeMap<String, Transaction>,
+    /// Expiry of the local transactions given one.
+    local_tx_expiries: BTreeMap<String, u64>,
     /// Local DRUID transaction pool.
     local_tx_druid_pool: Vec<BTreeMap<String, Transaction>>,
     /// Ordered transaction hashes from the last commit.
@@ -244,6 +260,10 @@ pub struct MempoolRaft {
     utxo_re_align_check: UtxoReAlignCheck,
     /// Timestamp of the current block
     timestamp: i64,
+    /// Peers committed different reward schedules: no block is proposed or applied
+    reward_schedule_mismatch: bool,
+    /// Clock used for proposal timeouts
+    clock: Clock,
 }
 
 impl fmt::Debug for MempoolRaft {
@@ -259,7 +279,8 @@ impl MempoolRaft {
     ///
     /// * `config`  - Configuration option for a mempoolr node.
     /// * `raft_db` - Override raft db to use.
-    pub async fn new(config: &MempoolNodeConfig, raft_db: Option<SimpleDb>) -> Self {
+    /// * `clock`   - Clock used for raft ticks and proposal timeouts.
+    pub async fn new(config: &MempoolNodeConfig, raft_db: Option<SimpleDb>, clock: Clock) -> Self {
         let use_raft = config.mempool_raft != 0;
         let timestamp = get_timestamp_now();
 
@@ -279,16 +300,23 @@ impl MempoolRaft {
                 .unwrap_or_default(),
             use_raft,
             Duration::from_millis(config.mempool_raft_tick_timeout as u64),
+            RaftStandby {
+                learners: config.mempool_raft_standby.unwrap_or_default(),
+                failover_timeout: config
+                    .mempool_raft_failover_timeout
+                    .map(|ms| Duration::from_millis(ms as u64)),
+            },
             db_utils::new_db(config.mempool_db_mode, &DB_SPEC, raft_db, None),
+            clock.clone(),
         );
 
         let propose_transactions_timeout_duration =
             Duration::from_millis(config.mempool_transaction_timeout as u64);
-        let propose_transactions_timeout_at = Instant::now();
+        let propose_transactions_timeout_at = clock.now();
 
         let propose_mining_event_timeout_duration =
             Duration::from_millis(config.mempool_mining_event_timeout as u64);
-        let propose_mining_event_timeout_at = Instant::now();
+        let propose_mining_event_timeout_at = clock.now();
 
         let utxo_set =
             make_utxo_set_from_seed(&config.mempool_seed_utxo, &config.mempool_genesis_tx_in);
@@ -301,6 +329,7 @@ impl MempoolRaft {
             .with_partition_full_size(config.mempool_partition_full_size)
             .with_unicorn_fixed_param(config.mempool_unicorn_fixed_param.clone())
             .with_initial_issuances(config.initial_issuances.clone())
+            .with_reward_schedule(config.mempool_reward_schedule.clone().unwrap_or_default())
             .init_block_pipeline_status();
         let local_initial_proposal = Some(InitialProposal::PendingItem {
             item: MempoolRaftItem::FirstBlock(utxo_set),
@@ -316,6 +345,7 @@ impl MempoolRaft {
             consensused_snapshot_applied: !use_raft,
             local_initial_proposal,
             local_tx_pool: Default::default(),
+            local_tx_expiries: Default::default(),
             local_tx_druid_pool: Default::default(),
             local_tx_hash_last_commited: Default::default(),
             propose_transactions_timeout_duration,
@@ -330,6 +360,8 @@ impl MempoolRaft {
             backup_check,
             utxo_re_align_check,
             timestamp,
+            reward_schedule_mismatch: false,
+            clock,
         }
     }
 
@@ -447,6 +479,11 @@ impl MempoolRaft {
         self.raft_active.raft_loop()
     }
 
+    /// Metrics of the raft loop.
+    pub fn raft_metrics(&self) -> SharedRaftMetrics {
+        self.raft_active.metrics()
+    }
+
     /// Signal to the raft loop to complete
     pub async fn close_raft_loop(&mut self) {
         self.raft_active.close_raft_loop().await
@@ -462,6 +499,58 @@ impl MempoolRaft {
         self.raft_active.backup_persistent_store().await
     }
 
+    /// Whether the consensused fields are kept by raft
+    pub fn use_raft(&self) -> bool {
+        self.raft_active.use_raft()
+    }
+
+    /// Whether this node is a standby mirroring the raft log without voting
+    pub fn is_standby(&self) -> bool {
+        self.raft_active.is_standby()
+    }
+
+    /// Propose to replace a voting mempool node with a standby
+    ///
+    /// ### Arguments
+    ///
+    /// * `replaced` - Address of the mempool node to replace
+    pub fn failover(&self, replaced: SocketAddr) -> bool {
+        self.raft_active.failover(replaced)
+    }
+
+    /// Serialized consensused fields holding the state of the current round
+    pub fn get_round_state(&self) -> bincode::Result<Vec<u8>> {
+        serialize(&self.consensused)
+    }
+
+    /// Restore the round state stored before a restart
+    ///
+    /// A round with a winning PoW continues with its block, other rounds
+    /// restart their intake so the block to mine is proposed again.
+    ///
+    /// ### Arguments
+    ///
+    /// * `round_state` - Round state from `get_round_state`
+    pub fn restore_round_state(&mut self, round_state: &[u8]) -> bincode::Result<()> {
+        self.consensused = deserialize(round_state)?;
+        let mined = self.consensused.get_winning_miner().is_some();
+        if !mined && self.consensused.get_mining_block().is_some() {
+            self.consensused.restart_items_intake();
+        }
+        if let Some(proposal) = &mut self.local_initial_proposal {
+            *proposal = InitialProposal::PendingAll;
+        }
+        self.set_ignore_dedeup_b_num_less_than_current();
+        self.set_next_propose_transactions_timeout_at();
+        self.set_next_propose_mining_event_timeout_at();
+        debug!(
+            "restore_round_state: tx_current_block_num({:?}), status({:?})",
+            self.consensused.block_pipeline.current_block_num(),
+            self.get_mining_pipeline_status()
+        );
+        Ok(())
+    }
+
     /// Check if we are waiting for initial state
     pub fn need_initial_state(&self) -> bool {
         !self.consensused_snapshot_applied
@@ -504,7 +593,15 @@ impl MempoolRaft {
         } else {
             // Non empty snapshot
             warn!("apply_snapshot called self.consensused updated");
-            self.consensused = deserialize(&consensused_ser).unwrap();
+            let reward_schedule = self.consensused.reward_schedule.clone();
+            self.consensused = match deserialize(&consensused_ser) {
+                Ok(consensused) => consensused,
+                Err(e) => {
+                    error!("apply_snapshot: invalid snapshot ignored {:?}", e);
+                    return None;
+                }
+            };
+            self.consensused.reward_schedule = reward_schedule;
             self.set_ignore_dedeup_b_num_less_than_current();
             self.set_next_propose_transactions_timeout_at();
             self.set_next_propose_mining_event_timeout_at();
@@ -536,13 +633,25 @@ impl MempoolRaft {
             .received_commit_proposal(&raft_data, &raft_ctx)
             .await?;
         if removed {
-            if let MempoolRaftItem::Transactions(ref txs) = &item {
-                self.proposed_tx_pool_len -= txs.len();
+            match &item {
+                MempoolRaftItem::Transactions(txs) => self.proposed_tx_pool_len -= txs.len(),
+                MempoolRaftItem::ExpiringTransactions(txs) => {
+                    self.proposed_tx_pool_len -= txs.len()
+                }
+                _ => (),
             }
         }
 
         trace!("received_commit_proposal {:?} -> {:?}", key, item);
         match item {
+            MempoolRaftItem::FirstBlock(_) | MempoolRaftItem::Block(_)
+                if self.reward_schedule_mismatch =>
+            {
+                error!(
+                    "Block not applied, peers use different reward schedules {:?}",
+                    key
+                );
+            }
             MempoolRaftItem::FirstBlock(uxto_set) => {
                 if !self.consensused.is_first_block() {
                     error!("Proposed FirstBlock after startup {:?}", key);
@@ -564,9 +673,26 @@ impl MempoolRaft {
                     return Some(CommittedItem::FirstBlock);
                 }
             }
-            MempoolRaftItem::Transactions(mut txs) => {
+            MempoolRaftItem::Transactions(txs) => {
                 self.local_tx_hash_last_commited = txs.keys().cloned().collect();
-                self.consensused.tx_pool.append(&mut txs);
+                let rejected = self.consensused.tx_pool.append(txs);
+                if !rejected.is_empty() {
+                    debug!(
+                        "Transactions spending pooled outputs rejected: {:?}",
+                        rejected
+                    );
+                }
+                return Some(CommittedItem::Transactions);
+            }
+            MempoolRaftItem::ExpiringTransactions(txs) => {
+                self.local_tx_hash_last_commited = txs.keys().cloned().collect();
+                let rejected = self.consensused.tx_pool.append_with_expiry(txs);
+                if !rejected.is_empty() {
+                    debug!(
+                        "Expiring transactions spending pooled outputs rejected: {:?}",
+                        rejected
+                    );
+                }
                 return Some(CommittedItem::Transactions);
             }
             MempoolRaftItem::DruidTransactions(mut txs) => {
@@ -574,7 +700,7 @@ impl MempoolRaft {
                 return Some(CommittedItem::Transactions);
             }
             MempoolRaftItem::Timestamp(timestamp) => {
-                self.consensused.timestamp = timestamp;
+                self.consensused.apply_committed_timestamp(timestamp);
             }
             MempoolRaftItem::Block(info) => {
                 let b_num = info.block_num;
@@ -652,13 +778,27 @@ impl MempoolRaft {
             MempoolRaftItem::RuntimeData(runtime_item) => {
                 self.consensused.handle_runtime_item(runtime_item);
             }
+            MempoolRaftItem::RewardSchedule(reward_schedule) => {
+                if !self
+                    .consensused
+                    .apply_committed_reward_schedule(reward_schedule.clone())
+                {
+                    error!(
+                        "Proposed reward schedule is different {:?}: {:?}, halting blocks",
+                        key, reward_schedule
+                    );
+                    self.reward_schedule_mismatch = true;
+                }
+            }
         }
         None
     }
 
     /// Blocks & waits for a new mining pipeline event.
     pub async fn timeout_propose_mining_event(&self) {
-        time::sleep_until(self.propose_mining_event_timeout_at).await;
+        self.clock
+            .sleep_until(self.propose_mining_event_timeout_at)
+            .await;
     }
 
     /// Get the mining pipeline status
@@ -694,11 +834,18 @@ impl MempoolRaft {
 
     /// Blocks & waits for a timeout to propose transactions.
     pub async fn timeout_propose_transactions(&self) {
-        time::sleep_until(self.propose_transactions_timeout_at).await;
+        self.clock
+            .sleep_until(self.propose_transactions_timeout_at)
+            .await;
     }
 
-    /// Propose initial item
+    /// Propose initial item, along with the reward schedule for the peers to check
     pub async fn propose_initial_item(&mut self) {
+        if self.raft_active.use_raft() {
+            let reward_schedule = self.consensused.reward_schedule.clone();
+            self.propose_item(&MempoolRaftItem::RewardSchedule(reward_schedule))
+                .await;
+        }
         self.local_initial_proposal = match self.local_initial_proposal.take() {
             Some(InitialProposal::PendingAll) => Some(InitialProposal::PendingAuthorized),
             Some(InitialProposal::PendingItem { item, dedup_b_num }) => {
@@ -717,6 +864,11 @@ impl MempoolRaft {
 
     /// Process as received block info necessary for new block to be generated.
     pub async fn propose_block_with_last_info(&mut self, block: BlockStoredInfo) -> bool {
+        if self.reward_schedule_mismatch {
+            error!("Block not proposed, peers use different reward schedules");
+            return false;
+        }
+
         let b_num = block.block_num;
         let item = MempoolRaftItem::Block(block);
 
@@ -738,13 +890,13 @@ impl MempoolRaft {
     ///Returns the clock time after the proposed block time out
     fn set_next_propose_transactions_timeout_at(&mut self) {
         self.propose_transactions_timeout_at =
-            Instant::now() + self.propose_transactions_timeout_duration
+            self.clock.now() + self.propose_transactions_timeout_duration
     }
 
     ///Returns the clock time after the proposed mining time out
     fn set_next_propose_mining_event_timeout_at(&mut self) {
         self.propose_mining_event_timeout_at =
-            Instant::now() + self.propose_mining_event_timeout_duration
+            self.clock.now() + self.propose_mining_event_timeout_duration
     }
 
     /// Propose a new mining event if relecant
@@ -836,10 +988,25 @@ impl MempoolRaft {
 
         let max_propose_len = std::cmp::min(max_add, self.proposed_tx_pool_len_max);
         let txs = take_first_n(max_propose_len, &mut self.local_tx_pool);
+        let (expiring, txs): (BTreeMap<_, _>, BTreeMap<_, _>) = txs
+            .into_iter()
+            .partition(|(hash, _)| self.local_tx_expiries.contains_key(hash));
+        let expiring: BTreeMap<_, _> = expiring
+            .into_iter()
+            .filter_map(|(hash, tx)| {
+                let expiry = self.local_tx_expiries.remove(&hash)?;
+                Some((hash, (tx, expiry)))
+            })
+            .collect();
         if !txs.is_empty() {
             self.proposed_tx_pool_len += txs.len();
             self.propose_item(&MempoolRaftItem::Transactions(txs)).await;
         }
+        if !expiring.is_empty() {
+            self.proposed_tx_pool_len += expiring.len();
+            self.propose_item(&MempoolRaftItem::ExpiringTransactions(expiring))
+                .await;
+        }
     }
 
     /// Process as a result of timeout_propose_transactions.
@@ -856,7 +1023,8 @@ impl MempoolRaft {
     pub async fn propose_timestamp(&mut self) {
         if self.first_raft_peer {
             info!("Proposing timestamp as first peer");
-            self.timestamp = get_timestamp_now();
+            let median = self.consensused.median_time_past();
+            self.timestamp = get_timestamp_now().max(median.unwrap_or(i64::MIN));
 
             self.propose_item(&MempoolRaftItem::Timestamp(self.timestamp))
                 .await;
@@ -922,9 +1090,14 @@ impl MempoolRaft {
         self.consensused.get_winning_miner()
     }
 
+    /// Get the miners that sent a valid PoW in the current mining round
+    pub fn get_pow_miners(&self) -> &BTreeSet<SocketAddr> {
+        self.consensused.block_pipeline.get_pow_miners()
+    }
+
     /// The current tx_pool that will be used to generate next block
     /// Returns a BTreeMap reference which contains a String and a Transaction.
-    pub fn get_committed_tx_pool(&self) -> &BTreeMap<String, Transaction> {
+    pub fn get_committed_tx_pool(&self) -> &TxPool {
         &self.consensused.tx_pool
     }
 
@@ -972,6 +1145,26 @@ impl MempoolRaft {
         self.local_tx_pool.append(&mut transactions);
     }
 
+    /// Append new transactions expiring at a block number or timestamp to our
+    /// local pool from which to propose consensused transactions.
+    ///
+    /// ### Arguments
+    ///
+    /// * `transactions` - Transactions to append
+    /// * `expiries`     - Expiry of the transactions given one
+    pub fn append_to_tx_pool_with_expiry(
+        &mut self,
+        transactions: BTreeMap<String, Transaction>,
+        expiries: BTreeMap<String, u64>,
+    ) {
+        self.local_tx_expiries.extend(
+            expiries
+                .into_iter()
+                .filter(|(hash, _)| transactions.contains_key(hash)),
+        );
+        self.append_to_tx_pool(transactions);
+    }
+
     /// Append new transaction to our local pool from which to propose
     /// consensused transactions.
     pub fn append_to_tx_druid_pool(&mut self, transactions: BTreeMap<String, Transaction>) {
@@ -979,7 +1172,7 @@ impl MempoolRaft {
     }
 
     /// Current block to mine or being mined.
-    pub fn get_mining_block(&self) -> &Option<Block> {
+    pub fn get_mining_block(&self) -> &Option<Arc<Block>> {
         self.consensused.get_mining_block()
     }
 
@@ -1086,6 +1279,11 @@ impl MempoolRaft {
         self.consensused.block_pipeline.current_block_num()
     }
 
+    /// The timestamp of the block currently mined
+    pub fn get_committed_timestamp(&self) -> i64 {
+        self.consensused.timestamp
+    }
+
     /// The mining transactions accepted for previous block
     pub fn get_last_mining_transaction_hashes(&self) -> &Vec<String> {
         &self.consensused.last_mining_transaction_hashes
@@ -1180,6 +1378,21 @@ impl MempoolConsensused {
         self
     }
 
+    /// Specify the reward schedule
+    pub fn with_reward_schedule(mut self, reward_schedule: RewardSchedule) -> Self {
+        self.reward_schedule = reward_schedule;
+        self
+    }
+
+    /// Specify the committed transactions and the timestamps of the last blocks.
+    /// Test only.
+    #[cfg(test)]
+    pub fn with_round_state(mut self, tx_pool: TxPool, recent_timestamps: Vec<i64>) -> Self {
+        self.tx_pool = tx_pool;
+        self.recent_timestamps = recent_timestamps;
+        self
+    }
+
     /// Specify the partition_full_size
     pub fn with_partition_full_size(mut self, partition_full_size: usize) -> Self {
         self.partition_full_size = partition_full_size;
@@ -1249,7 +1462,10 @@ impl MempoolConsensused {
             special_handling,
             miner_whitelist,
             timestamp,
+            recent_timestamps: Default::default(),
             init_issuances,
+            committed_reward_schedule: Default::default(),
+            reward_schedule: Default::default(),
         }
     }
 
@@ -1276,6 +1492,46 @@ impl MempoolConsensused {
         }
     }
 
+    /// Median timestamp of the last blocks generated, None before the first one
+    pub fn median_time_past(&self) -> Option<i64> {
+        median_time_past(&self.recent_timestamps)
+    }
+
+    /// Apply the committed timestamp of the next block.
+    /// A timestamp behind the median of the last blocks is refused for the median.
+    ///
+    /// ### Arguments
+    ///
+    /// * `timestamp` - Committed timestamp
+    pub fn apply_committed_timestamp(&mut self, timestamp: i64) {
+        self.timestamp = match self.median_time_past() {
+            Some(median) if timestamp < median => {
+                warn!(
+                    "Committed timestamp {} behind median {}: using the median",
+                    timestamp, median
+                );
+                median
+            }
+            _ => timestamp,
+        };
+    }
+
+    /// Apply a committed reward schedule: the first one committed is used by all the peers.
+    /// Returns false if it differs from the one in use.
+    ///
+    /// ### Arguments
+    ///
+    /// * `reward_schedule` - Committed reward schedule
+    pub fn apply_committed_reward_schedule(&mut self, reward_schedule: RewardSchedule) -> bool {
+        match &self.committed_reward_schedule {
+            Some(committed) => *committed == reward_schedule,
+            None => {
+                self.committed_reward_schedule = Some(reward_schedule);
+                true
+            }
+        }
+    }
+
     /// Set consensused committed block to mine.
     /// Internal call, public for test only.
     /// ### Arguments
@@ -1322,7 +1578,7 @@ impl MempoolConsensused {
     }
 
     /// Current block to mine or being mined.
-    pub fn get_mining_block(&self) -> &Option<Block> {
+    pub fn get_mining_block(&self) -> &Option<Arc<Block>> {
         self.block_pipeline.get_mining_block()
     }
 
@@ -1447,13 +1703,20 @@ impl MempoolConsensused {
         block: &mut Block,
         block_tx: &mut BTreeMap<String, Transaction>,
     ) {
-        // Clean tx_pool of invalid transactions for this block.
-        for invalid in self.find_invalid_new_txs(&self.tx_pool) {
-            self.tx_pool.remove(&invalid);
+        // Expired transactions are never added to a block.
+        if let Some(b_num) = self.block_pipeline.current_block_num() {
+            let expired = self.tx_pool.evict_expired(b_num, self.timestamp);
+            if !expired.is_empty() {
+                debug!("Expired transactions evicted: {:?}", expired);
+            }
         }
 
-        // Select subset of transaction to fill the block.
-        let txs = take_first_n(BLOCK_SIZE_IN_TX, &mut self.tx_pool);
+        // Select subset of transaction to fill the block, dropping invalid ones.
+        // Pooled transactions never spend the same outpoint.
+        let utxo_set = &self.utxo_set;
+        let txs = self
+            .tx_pool
+            .take_block_txs(BLOCK_SIZE_IN_TX, |op| utxo_set.contains_key(op));
 
         // Process valid set of transactions.
         self.update_current_block_tx_with_given_valid_txs(txs, block, block_tx);
@@ -1470,6 +1733,13 @@ impl MempoolConsensused {
         block.header.previous_hash = Some(previous_hash);
         block.header.timestamp = self.timestamp;
         block.header.b_num = b_num;
+
+        self.recent_timestamps.push(self.timestamp);
+        let excess = self
+            .recent_timestamps
+            .len()
+            .saturating_sub(MEDIAN_TIME_SPAN);
+        self.recent_timestamps.drain(..excess);
         block.set_txs_merkle_root_and_hash().await;
     }
 
@@ -1713,7 +1983,13 @@ impl MempoolConsensused {
                 info.block_num + 1
             }
         };
-        let reward = calculate_reward(self.current_issuance) / self.unanimous_majority as u64;
+        // Without raft, no schedule is proposed and the configured one is used
+        let reward_schedule = self
+            .committed_reward_schedule
+            .as_ref()
+            .unwrap_or(&self.reward_schedule);
+        let reward = reward_schedule.reward(block_num, self.current_issuance)
+            / self.unanimous_majority as u64;
 
         self.block_pipeline
             .apply_ready_block_stored_info(block_num, reward);
@@ -1754,6 +2030,17 @@ impl MempoolConsensused {
         self.block_pipeline.start_items_intake(extra);
     }
 
+    /// Restart participants intake, keeping the block to mine
+    pub fn restart_items_intake(&mut self) {
+        let extra = PipelineEventInfo {
+            proposer_id: 0,
+            unanimous_majority: self.unanimous_majority,
+            sufficient_majority: self.sufficient_majority,
+            partition_full_size: self.partition_full_size,
+        };
+        self.block_pipeline.handle_reset_pipeline(extra);
+    }
+
     /// Handle a mining pipeline item
     pub async fn handle_mining_pipeline_item(
         &mut self,
@@ -1790,10 +2077,11 @@ fn take_first_n<K: Clone + Ord, V>(n: usize, from: &mut BTreeMap<K, V>) -> BTree
 mod test {
     use super::*;
     use crate::configurations::{DbMode, NodeSpec, TxOutSpec};
-    use crate::utils::{create_socket_addr, create_valid_transaction, get_test_common_unicorn};
+    use crate::test_utils::TransactionBuilder;
+    use crate::utils::{create_socket_addr, get_test_common_unicorn};
     use rug::Integer;
     use std::collections::BTreeSet;
-    use tw_chain::crypto::sign_ed25519 as sign;
+    use tokio::time;
     use tw_chain::primitives::asset::TokenAmount;
 
     #[tokio::test]
@@ -1990,6 +2278,25 @@ mod test {
         assert_eq!(node.consensused.tx_current_block_previous_hash, None);
     }
 
+    #[test]
+    /// Checks a committed timestamp behind the median of the last blocks is refused
+    fn committed_timestamp_not_behind_median() {
+        //Arrange
+        let mut consensused = MempoolConsensused {
+            recent_timestamps: vec![100, 130, 120],
+            ..Default::default()
+        };
+
+        //Act
+        consensused.apply_committed_timestamp(110);
+        let refused = consensused.timestamp;
+        consensused.apply_committed_timestamp(140);
+        let accepted = consensused.timestamp;
+
+        //Assert
+        assert_eq!((refused, accepted), (120, 140));
+    }
+
     #[tokio::test]
     async fn in_flight_transactions_no_raft() {
         //
@@ -2077,6 +2384,45 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn different_reward_schedules_halt_blocks() {
+        //
+        // Arrange
+        //
+        let mut node = new_test_node(&["000000"]).await;
+        let halving = RewardSchedule::Halving {
+            initial_reward: 100,
+            halving_interval: 10,
+        };
+        let peer_schedules = vec![(1, RewardSchedule::Issuance), (2, halving)];
+
+        //
+        // Act
+        //
+        let mut schedule_commits = Vec::new();
+        for (index, (proposer_id, reward_schedule)) in peer_schedules.into_iter().enumerate() {
+            let commit = reward_schedule_commit(proposer_id, index as u64 + 1, reward_schedule);
+            schedule_commits.push(node.received_commit(commit).await);
+        }
+        let proposed = node.propose_block_with_last_info(Default::default()).await;
+        node.propose_initial_item().await;
+        let commit = node.next_commit().await.unwrap();
+        let first_block = node.received_commit(commit).await;
+
+        //
+        // Assert
+        //
+        assert_eq!(schedule_commits, vec![None, None]);
+        assert_eq!(
+            node.consensused.committed_reward_schedule,
+            Some(RewardSchedule::Issuance)
+        );
+        assert!(node.reward_schedule_mismatch);
+        assert!(!proposed);
+        assert_eq!(first_block, None);
+        assert!(node.consensused.is_first_block());
+    }
+
     async fn new_test_node(seed_utxo: &[&str]) -> MempoolRaft {
         let mempool_node = create_socket_addr("0.0.0.0").await.unwrap();
         let tx_out = TxOutSpec {
@@ -2100,43 +2446,84 @@ mod test {
             mempool_raft_tick_timeout: 10,
             mempool_mining_event_timeout: 500,
             mempool_transaction_timeout: 50,
+            mempool_local_tx_flush_len: None,
+            mempool_local_tx_flush_timeout: None,
+            max_output_data_size: None,
+            max_tx_data_size: None,
+            mempool_reward_schedule: None,
+            db_compaction_interval: None,
             mempool_seed_utxo: seed_utxo
                 .iter()
                 .map(|v| (v.to_string(), vec![tx_out.clone()]))
                 .collect(),
             mempool_genesis_tx_in: None,
+            mempool_seed_utxo_file: None,
+            mempool_genesis_allocation: None,
             mempool_partition_full_size: 1,
             mempool_minimum_miner_pool_len: 1,
             jurisdiction: "US".to_string(),
             sanction_list: Vec::new(),
             mempool_api_use_tls: true,
+            mempool_api_client_auth: None,
             mempool_api_port: 3003,
             routes_pow: Default::default(),
             backup_block_modulo: Default::default(),
             utxo_re_align_block_modulo: Default::default(),
             backup_restore: Default::default(),
+            backup_signing_key: None,
+            identity_file: None,
             enable_trigger_messages_pipeline_reset: Default::default(),
             mempool_miner_whitelist: Default::default(),
             peer_limit: 1000,
+            network_id: None,
+            peer_ip_filter: None,
+            event_queue_capacity: None,
+            local_event_capacity: None,
+            peer_wait_timeout: None,
             initial_issuances: Default::default(),
+            mempool_event_bus: None,
+            mempool_screening: None,
+            mempool_raft_standby: None,
+            mempool_raft_failover_timeout: None,
         };
-        let mut node = MempoolRaft::new(&mempool_config, Default::default()).await;
+        let mut node =
+            MempoolRaft::new(&mempool_config, Default::default(), Default::default()).await;
         node.set_key_run(0);
         node
     }
 
+    fn reward_schedule_commit(
+        proposer_id: u64,
+        index: u64,
+        reward_schedule: RewardSchedule,
+    ) -> RaftCommit {
+        let key = RaftContextKey {
+            proposer_id,
+            proposer_run: 0,
+            proposal_id: index,
+        };
+        let item = MempoolRaftItem::RewardSchedule(reward_schedule);
+        let data = serialize(&item).unwrap();
+        let context = serialize(&key).unwrap();
+        RaftCommit {
+            term: 1,
+            index,
+            data: RaftCommitData::Proposed(data, context),
+        }
+    }
+
     fn valid_transaction(
         intial_t_hashes: &[&str],
         receiver_addrs: &[&str],
         new_hashes: &mut BTreeMap<String, String>,
     ) -> BTreeMap<String, Transaction> {
-        let (pk, sk) = sign::gen_keypair();
+        let builder = TransactionBuilder::new();
 
         let txs: Vec<_> = intial_t_hashes
             .iter()
             .copied()
             .zip(receiver_addrs.iter().copied())
-            .map(|(hash, addr)| (create_valid_transaction(hash, 0, addr, &pk, &sk), addr))
+            .map(|(hash, addr)| (builder.clone().spending(0, hash).paying(addr).build(), addr))
             .collect();
 
         new_hashes.extend(
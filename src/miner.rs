@@ -1,11 +1,15 @@
 use crate::comms_handler::Node;
 use crate::comms_handler::{CommsError, Event, TcpTlsConfig};
-use crate::configurations::{ExtraNodeParams, MinerNodeConfig, TlsPrivateInfo};
-use crate::db_utils;
+use crate::configurations::{ExtraNodeParams, MinerNodeConfig, ResponseHook, TlsPrivateInfo};
+use crate::db_executor::{configure_db_pool, DbPool};
+use crate::db_utils::{self, CompactionSchedule};
 use crate::interfaces::{
     BlockchainItem, MempoolRequest, MineApiRequest, MineRequest, MinerInterface, NodeType, PowInfo,
-    ProofOfWork, Response, Rs2JsMsg, StorageRequest, UtxoFetchType, UtxoSet,
+    ProofOfWork, Response, ResponseReason, Rs2JsMsg, StorageRequest, UtxoFetchType, UtxoSet,
 };
+use crate::node_identity::load_node_identity;
+use crate::replay_guard::ReplayTagger;
+use crate::snapshot_manifest::{set_snapshot_identity, set_snapshot_signer};
 use crate::threaded_call::{ThreadedCallChannel, ThreadedCallSender};
 use crate::transactor::Transactor;
 use crate::utils::{
@@ -15,7 +19,9 @@ use crate::utils::{
     LocalEvent, LocalEventChannel, LocalEventSender, ResponseResult, RoutesPoWInfo,
     RunningTaskOrResult,
 };
-use crate::wallet::{LockedCoinbase, WalletDb, WalletDbError, DB_SPEC};
+use crate::wallet::{
+    with_signing_trigger, Argon2Params, LockedCoinbase, WalletDb, WalletDbError, DB_SPEC,
+};
 use async_trait::async_trait;
 use bincode::{deserialize, serialize};
 use bytes::Bytes;
@@ -29,7 +35,7 @@ use std::{
     net::SocketAddr,
     net::{IpAddr, Ipv4Addr},
     str,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::task;
@@ -79,18 +85,13 @@ pub enum MinerError {
     WalletError(WalletDbError),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub enum AggregationStatus {
+    #[default]
     Idle,
     UtxoUpdate(String),
 }
 
-impl Default for AggregationStatus {
-    fn default() -> Self {
-        Self::Idle
-    }
-}
-
 impl fmt::Display for MinerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -145,6 +146,8 @@ pub struct MinerNode {
     node: Node,
     wallet_db: WalletDb,
     local_events: LocalEventChannel,
+    peer_wait_timeout: Option<Duration>,
+    db_compaction: CompactionSchedule,
     threaded_calls: ThreadedCallChannel<MinerNode>,
     ui_feedback_tx: Option<mpsc::Sender<Rs2JsMsg>>,
     mempool_addr: SocketAddr,
@@ -164,6 +167,8 @@ pub struct MinerNode {
     mining_api_key: Option<String>,
     blockchain_item_received: Option<(String, BlockchainItem, SocketAddr)>,
     api_info: (SocketAddr, Option<TlsPrivateInfo>, ApiKeys, RoutesPoWInfo),
+    replay_tagger: ReplayTagger,
+    response_hook: Option<ResponseHook>,
 }
 
 impl MinerNode {
@@ -173,7 +178,10 @@ impl MinerNode {
     ///
     /// * `config`   - MinerNodeConfig object that hold the miner_nodes and miner_db_mode
     /// * `extra`  - additional parameter for construction
-    pub async fn new(config: MinerNodeConfig, mut extra: ExtraNodeParams) -> Result<MinerNode> {
+    pub async fn new(mut config: MinerNodeConfig, mut extra: ExtraNodeParams) -> Result<MinerNode> {
+        if let Some(tls_config) = extra.tls_config.take() {
+            config.tls_config = tls_config;
+        }
         let addr = config.miner_address;
         let raw_mempool_addr = config
             .mempool_nodes
@@ -183,20 +191,54 @@ impl MinerNode {
             .await
             .map_err(|_| MinerError::ConfigError("Invalid mempool node address in config file"))?;
 
+        if let Some(key_path) = &config.backup_signing_key {
+            set_snapshot_signer(key_path)
+                .map_err(|_| MinerError::ConfigError("Invalid backup signing key"))?;
+        }
+        let identity = load_node_identity(
+            config.identity_file.as_deref(),
+            config.miner_db_mode,
+            &DB_SPEC,
+        )
+        .map_err(|_| MinerError::ConfigError("Invalid node identity file"))?;
+        set_snapshot_identity(&identity);
+        if let Some(threads) = config.db_threads {
+            configure_db_pool(DbPool::Wallet, threads);
+        }
+
         // Restore old keys if backup is present
         if config.backup_restore.unwrap_or(false) {
-            db_utils::restore_file_backup(config.miner_db_mode, &DB_SPEC, None).unwrap();
+            db_utils::restore_file_backup(config.miner_db_mode, &DB_SPEC, None)
+                .map_err(|_| MinerError::ConfigError("Invalid wallet backup"))?;
         }
 
-        let wallet_db = WalletDb::new(
+        let db_mode = config.miner_db_mode;
+        let kdf_params = config
+            .miner_wallet_kdf
+            .unwrap_or_else(|| Argon2Params::for_db_mode(db_mode));
+        let wallet_db = WalletDb::new_with_kdf(
             config.miner_db_mode,
             extra.wallet_db.take(),
             config.passphrase,
             extra.custom_wallet_spec,
-        )?;
+            kdf_params,
+        )?
+        .with_key_gen(extra.key_gen);
         let disable_tcp_listener = extra.disable_tcp_listener;
         let tls_addr = create_socket_addr(&addr).await.unwrap();
-        let tcp_tls_config = TcpTlsConfig::from_tls_spec(tls_addr, &config.tls_config)?;
+        let mut tcp_tls_config = TcpTlsConfig::from_tls_spec(
+            extra.bind_address.unwrap_or(tls_addr),
+            &config.tls_config,
+        )?;
+        if let Some(listener) = extra.listener.take() {
+            tcp_tls_config = tcp_tls_config.with_listener(listener).await;
+        }
+        if let Some(spec) = &config.peer_ip_filter {
+            tcp_tls_config.set_peer_ip_filter(spec)?;
+        }
+        if let Some(capacity) = config.event_queue_capacity {
+            tcp_tls_config.set_event_queue_capacity(capacity);
+        }
         let api_addr = SocketAddr::new(tls_addr.ip(), config.miner_api_port);
         let api_tls_info = config
             .miner_api_use_tls
@@ -210,6 +252,10 @@ impl MinerNode {
             false,
         )
         .await?;
+        node.set_event_hooks(extra.event_hooks.clone());
+        node.set_identity(identity);
+        node.set_network_id(config.network_id.unwrap_or_default());
+        wallet_db.check_network_id(node.network_id())?;
         let api_pow_info = to_route_pow_infos(config.routes_pow.clone());
         let static_miner_address = Arc::new(RwLock::new(config.static_miner_address.clone()));
         let mining_api_key = config.mining_api_key.clone();
@@ -217,7 +263,9 @@ impl MinerNode {
 
         MinerNode {
             node,
-            local_events: Default::default(),
+            local_events: LocalEventChannel::new(config.local_event_capacity),
+            peer_wait_timeout: config.peer_wait_timeout.map(Duration::from_millis),
+            db_compaction: CompactionSchedule::new(config.db_compaction_interval),
             threaded_calls: Default::default(),
             ui_feedback_tx: Default::default(),
             wallet_db,
@@ -238,11 +286,18 @@ impl MinerNode {
             mining_api_key,
             api_info: (api_addr, api_tls_info, api_keys, api_pow_info),
             address_aggregation_limit,
+            replay_tagger: Default::default(),
+            response_hook: extra.response_hook,
         }
         .load_local_db()
         .await
     }
 
+    /// Read every entry of the wallet database, returning the number of entries
+    pub fn scan_dbs(&self) -> db_utils::Result<usize> {
+        self.wallet_db.scan_persistent_store()
+    }
+
     /// Info needed to run the API point.
     pub fn api_inputs(
         &self,
@@ -349,6 +404,11 @@ impl MinerNode {
         &self.local_events.tx
     }
 
+    /// Time to wait for peers to connect before starting, unlimited if None
+    pub fn peer_wait_timeout(&self) -> Option<Duration> {
+        self.peer_wait_timeout
+    }
+
     /// Local event channel.
     pub fn local_event_tx_mut(&mut self) -> &mut LocalEventSender {
         &mut self.local_events.tx
@@ -380,6 +440,7 @@ impl MinerNode {
         let wallet_db = self.wallet_db.take_closed_persistent_store().await;
         ExtraNodeParams {
             wallet_db: wallet_db.in_memory(),
+            key_gen: self.wallet_db.key_gen().clone(),
             ..Default::default()
         }
     }
@@ -390,14 +451,17 @@ impl MinerNode {
         response: Result<Response>,
     ) -> ResponseResult {
         debug!("Response: {:?}", response);
+        if let (Some(hook), Ok(resp)) = (&self.response_hook, &response) {
+            hook.call(resp);
+        }
 
         if let Ok(resp) = &response {
             let ui_message = match resp.success {
                 true => Rs2JsMsg::Info {
-                    info: resp.reason.to_owned(),
+                    info: resp.reason.to_string(),
                 },
                 false => Rs2JsMsg::Error {
-                    error: resp.reason.to_owned(),
+                    error: resp.reason.to_string(),
                 },
             };
             try_send_to_ui(self.ui_feedback_tx.as_ref(), ui_message).await;
@@ -406,15 +470,15 @@ impl MinerNode {
         match response {
             Ok(Response {
                 success: true,
-                reason: "Sent startup requests on reconnection",
+                reason: ResponseReason::SentStartupRequests,
             }) => debug!("Sent startup requests on reconnection"),
             Ok(Response {
                 success: false,
-                reason: "Failed to send startup requests on reconnection",
+                reason: ResponseReason::FailedToSendStartupRequests,
             }) => error!("Failed to send startup requests on reconnection"),
             Ok(Response {
                 success: true,
-                reason: "Shutdown",
+                reason: ResponseReason::Shutdown,
             }) => {
                 warn!("Shutdown now");
                 try_send_to_ui(self.ui_feedback_tx.as_ref(), Rs2JsMsg::Exit).await;
@@ -422,7 +486,7 @@ impl MinerNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Blockchain item received",
+                reason: ResponseReason::BlockchainItemReceived,
             }) => {
                 if let Some((key, item, peer)) = self.blockchain_item_received.as_ref() {
                     log_received_blockchain_item(key, item, peer);
@@ -432,13 +496,13 @@ impl MinerNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Received random number successfully",
+                reason: ResponseReason::ReceivedRandomNumberSuccessfully,
             }) => {
                 info!("RANDOM NUMBER RECEIVED: {:?}", self.rand_num);
             }
             Ok(Response {
                 success: true,
-                reason: "Partition PoW complete",
+                reason: ResponseReason::PartitionPowComplete,
             }) => {
                 if self.process_found_partition_pow().await {
                     info!("Partition Pow found and sent");
@@ -446,25 +510,25 @@ impl MinerNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Pre-block received successfully",
+                reason: ResponseReason::PreBlockReceivedSuccessfully,
             }) => {
                 info!("PRE-BLOCK RECEIVED");
             }
             Ok(Response {
                 success: true,
-                reason: "Block is valid",
+                reason: ResponseReason::BlockIsValid,
             }) => {
                 info!("MERKLE ROOT VALID");
             }
             Ok(Response {
                 success: false,
-                reason: "Block is not valid",
+                reason: ResponseReason::BlockIsNotValid,
             }) => {
                 info!("MERKLE ROOT INVALID");
             }
             Ok(Response {
                 success: true,
-                reason: "Block PoW complete",
+                reason: ResponseReason::BlockPowComplete,
             }) => {
                 if self.process_found_block_pow().await {
                     info!("Block PoW found and sent");
@@ -472,53 +536,55 @@ impl MinerNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Received UTXO set",
+                reason: ResponseReason::ReceivedUtxoSet,
             }) => {
-                self.update_running_total().await;
+                if let Err(e) = self.update_running_total().await {
+                    error!("Failed to update running total {:?}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Node is not mining",
+                reason: ResponseReason::NodeIsNotMining,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Node is mining",
+                reason: ResponseReason::NodeIsMining,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Node is connected",
+                reason: ResponseReason::NodeIsConnected,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             }) => {}
             Ok(Response {
                 success: true, // Not always an error
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Connected to mempool",
+                reason: ResponseReason::ConnectedToMempool,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Disconnected from mempool",
+                reason: ResponseReason::DisconnectedFromMempool,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Failed to connect to mempool",
+                reason: ResponseReason::FailedToConnectToMempool,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Failed to disconnect from mempool",
+                reason: ResponseReason::FailedToDisconnectFromMempool,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Already disconnected from mempool",
+                reason: ResponseReason::AlreadyDisconnectedFromMempool,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Initiate pause node",
+                reason: ResponseReason::InitiatePauseNode,
             }) => {
                 info!("Initiate pause node");
                 if let Err(e) = self
@@ -531,33 +597,33 @@ impl MinerNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Static miner address set",
+                reason: ResponseReason::StaticMinerAddressSet,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Node is paused",
+                reason: ResponseReason::NodeIsPaused,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Node is resumed",
+                reason: ResponseReason::NodeIsResumed,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Received miner removed ack from non-mempool peer",
+                reason: ResponseReason::MinerRemovedAckFromNonMempoolPeer,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Sent UTXO Request",
+                reason: ResponseReason::SentUtxoRequest,
             }) => {
                 debug!("Sent UTXO Request for wallet update")
             }
             Ok(Response {
                 success: false,
-                reason: "Received miner unauthorized notification from non-mempool peer",
+                reason: ResponseReason::MinerUnauthorizedFromNonMempoolPeer,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Miner not authorized",
+                reason: ResponseReason::MinerNotAuthorized,
             }) => return ResponseResult::Exit,
             Ok(Response {
                 success: true,
@@ -581,7 +647,7 @@ impl MinerNode {
 
     /// Listens for new events from peers and handles them.
     /// The future returned from this function should be executed in the runtime. It will block execution.
-    pub async fn handle_next_event<E: Future<Output = &'static str> + Unpin>(
+    pub async fn handle_next_event<E: Future<Output = ResponseReason> + Unpin>(
         &mut self,
         exit: &mut E,
     ) -> Option<Result<Response>> {
@@ -600,15 +666,19 @@ impl MinerNode {
                     self.wait_partition_task = false;
                     return Some(Ok(Response {
                         success: true,
-                        reason: "Partition PoW complete",
+                        reason: ResponseReason::PartitionPowComplete,
                     }));
                 }
                 _ = self.mining_block_task.wait(), if !self.wait_partition_task => {
                     return Some(Ok(Response {
                         success: true,
-                        reason: "Block PoW complete",
+                        reason: ResponseReason::BlockPowComplete,
                     }));
                 }
+                _ = self.db_compaction.tick() => {
+                    trace!("handle_next_event compact db");
+                    self.wallet_db.compact_db().await;
+                }
                 Some(event) = self.local_events.rx.recv() => {
                     if let Some(res) = self.handle_local_event(event).await {
                         return Some(Ok(res));
@@ -641,12 +711,12 @@ impl MinerNode {
                     error!("Failed to send startup requests on reconnect: {}", err);
                     return Some(Response {
                         success: false,
-                        reason: "Failed to send startup requests on reconnection",
+                        reason: ResponseReason::FailedToSendStartupRequests,
                     });
                 }
                 Some(Response {
                     success: true,
-                    reason: "Sent startup requests on reconnection",
+                    reason: ResponseReason::SentStartupRequests,
                 })
             }
             LocalEvent::CoordinatedShutdown(_) => None,
@@ -699,6 +769,7 @@ impl MinerNode {
     ///
     /// * `peer`     - Sending peer's socket address
     /// * `req`   - MineRequest object that is the recieved request
+    ///
     /// TODO: Find something to do with win_coinbase. Allows to know winner
     async fn handle_request(&mut self, peer: SocketAddr, req: MineRequest) -> Option<Response> {
         use MineRequest::*;
@@ -773,7 +844,7 @@ impl MinerNode {
                 .ok()
                 .map(|_| Response {
                     success: true,
-                    reason: "Sent UTXO Request",
+                    reason: ResponseReason::SentUtxoRequest,
                 }),
             MineApiRequest::SetStaticMinerAddress { address } => {
                 Some(self.handle_set_static_miner_address(address).await)
@@ -787,7 +858,7 @@ impl MinerNode {
 
         Response {
             success: true,
-            reason: "Static miner address set",
+            reason: ResponseReason::StaticMinerAddressSet,
         }
     }
 
@@ -823,12 +894,12 @@ impl MinerNode {
             .await;
             Response {
                 success: false,
-                reason: "Miner not authorized",
+                reason: ResponseReason::MinerNotAuthorized,
             }
         } else {
             Response {
                 success: false,
-                reason: "Received miner unauthorized notification from non-mempool peer",
+                reason: ResponseReason::MinerUnauthorizedFromNonMempoolPeer,
             }
         }
     }
@@ -846,12 +917,12 @@ impl MinerNode {
             .await;
             Response {
                 success: true,
-                reason: "Node is paused",
+                reason: ResponseReason::NodeIsPaused,
             }
         } else {
             Response {
                 success: false,
-                reason: "Received miner removed ack from non-mempool peer",
+                reason: ResponseReason::MinerRemovedAckFromNonMempoolPeer,
             }
         }
     }
@@ -863,7 +934,7 @@ impl MinerNode {
         if join_handles.is_empty() {
             return Response {
                 success: false,
-                reason: "Already disconnected from mempool",
+                reason: ResponseReason::AlreadyDisconnectedFromMempool,
             };
         }
         for join_handle in join_handles {
@@ -871,7 +942,7 @@ impl MinerNode {
                 error!("Failed to disconnect from mempool: {}", err);
                 return Response {
                     success: false,
-                    reason: "Failed to disconnect from mempool",
+                    reason: ResponseReason::FailedToDisconnectFromMempool,
                 };
             }
         }
@@ -884,7 +955,7 @@ impl MinerNode {
         .await;
         Response {
             success: true,
-            reason: "Disconnected from mempool",
+            reason: ResponseReason::DisconnectedFromMempool,
         }
     }
 
@@ -895,7 +966,7 @@ impl MinerNode {
             error!("Failed to connect to mempool: {e:?}");
             return Response {
                 success: false,
-                reason: "Failed to connect to mempool",
+                reason: ResponseReason::FailedToConnectToMempool,
             };
         }
         try_send_to_ui(
@@ -909,7 +980,7 @@ impl MinerNode {
         // because we don't necessarily want to start mining
         Response {
             success: true,
-            reason: "Connected to mempool",
+            reason: ResponseReason::ConnectedToMempool,
         }
     }
 
@@ -926,7 +997,7 @@ impl MinerNode {
 
             return Response {
                 success: false,
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             };
         }
 
@@ -937,7 +1008,7 @@ impl MinerNode {
             // Pause mining
             Response {
                 success: true,
-                reason: "Initiate pause node",
+                reason: ResponseReason::InitiatePauseNode,
             }
         } else {
             // Resume mining
@@ -952,7 +1023,7 @@ impl MinerNode {
                 .await;
                 return Response {
                     success: false,
-                    reason: "Failed to send startup requests on reconnection",
+                    reason: ResponseReason::FailedToSendStartupRequests,
                 };
             }
             *self.pause_node.write().await = false;
@@ -965,7 +1036,7 @@ impl MinerNode {
             .await;
             Response {
                 success: true,
-                reason: "Node is resumed",
+                reason: ResponseReason::NodeIsResumed,
             }
         }
     }
@@ -982,7 +1053,7 @@ impl MinerNode {
             .await;
             return Response {
                 success: true,
-                reason: "Node is not mining",
+                reason: ResponseReason::NodeIsNotMining,
             };
         }
         try_send_to_ui(
@@ -994,7 +1065,7 @@ impl MinerNode {
         .await;
         Response {
             success: true,
-            reason: "Node is mining",
+            reason: ResponseReason::NodeIsMining,
         }
     }
 
@@ -1010,7 +1081,7 @@ impl MinerNode {
             .await;
             return Response {
                 success: true,
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             };
         }
         try_send_to_ui(
@@ -1022,7 +1093,7 @@ impl MinerNode {
         .await;
         Response {
             success: true,
-            reason: "Node is connected",
+            reason: ResponseReason::NodeIsConnected,
         }
     }
 
@@ -1038,7 +1109,7 @@ impl MinerNode {
 
         Some(Response {
             success: true,
-            reason: "Shutdown",
+            reason: ResponseReason::Shutdown,
         })
     }
 
@@ -1099,18 +1170,20 @@ impl MinerNode {
             false
         };
 
-        self.wallet_db.filter_locked_coinbase(b_num).await;
+        if let Err(e) = self.wallet_db.filter_locked_coinbase(b_num).await {
+            error!("Failed to filter locked coinbase {:?}", e);
+        }
         // TODO: should we check even if coinbase was not committed?
         self.check_for_threshold_and_send_aggregation_tx().await;
 
         match (process_rnd, process_block) {
             (true, false) => Some(Response {
                 success: true,
-                reason: "Received random number successfully",
+                reason: ResponseReason::ReceivedRandomNumberSuccessfully,
             }),
             (_, true) => Some(Response {
                 success: true,
-                reason: "Pre-block received successfully",
+                reason: ResponseReason::PreBlockReceivedSuccessfully,
             }),
             (false, false) => None,
         }
@@ -1198,26 +1271,34 @@ impl MinerNode {
         &self,
         tx_merkle_verification: Vec<String>,
     ) -> Option<Response> {
-        let current_block_info = self.current_block.lock().await.clone().unwrap();
+        let current_block_info = match self.current_block.lock().await.clone() {
+            Some(current_block_info) => current_block_info,
+            None => {
+                return Some(Response {
+                    success: false,
+                    reason: ResponseReason::NoBlockToVerify,
+                })
+            }
+        };
         let merkle_root = current_block_info.block.txs_merkle_root_and_hash.0.clone();
         let mut valid = true;
 
         if !merkle_root.is_empty() {
-            let (mtree, _) = block::build_merkle_tree(&tx_merkle_verification)
-                .await
-                .unwrap();
-            valid = hex::encode(mtree.root()) == merkle_root;
+            valid = match block::build_merkle_tree(&tx_merkle_verification).await {
+                Some((mtree, _)) => hex::encode(mtree.root()) == merkle_root,
+                None => false,
+            };
         }
 
         if valid {
             Some(Response {
                 success: true,
-                reason: "Block is valid",
+                reason: ResponseReason::BlockIsValid,
             })
         } else {
             Some(Response {
                 success: false,
-                reason: "Block is not valid",
+                reason: ResponseReason::BlockIsNotValid,
             })
         }
     }
@@ -1305,6 +1386,7 @@ impl MinerNode {
                     block_num,
                     nonce,
                     coinbase,
+                    replay_tag: self.replay_tagger.next_tag(),
                 },
             )
             .await?;
@@ -1357,6 +1439,7 @@ impl MinerNode {
                 MempoolRequest::SendPartitionEntry {
                     pow_info,
                     partition_entry,
+                    replay_tag: self.replay_tagger.next_tag(),
                 },
             )
             .await?;
@@ -1391,16 +1474,26 @@ impl MinerNode {
     /// Commit our winning mining tx to wallet
     async fn commit_found_coinbase(&mut self) {
         trace!("Committing our latest winning");
-        self.current_payment_address = Some(
-            self.get_static_miner_address()
-                .await
-                .unwrap_or(generate_mining_address(&mut self.wallet_db).await),
-        );
-        let (hash, transaction) = std::mem::replace(
+        self.current_payment_address = match self.get_static_miner_address().await {
+            Some(static_address) => Some(static_address),
+            None => match generate_mining_address(&mut self.wallet_db).await {
+                Ok(addr) => Some(addr),
+                Err(e) => {
+                    error!("Error generating mining address: {:?}", e);
+                    self.current_payment_address.take()
+                }
+            },
+        };
+        let (hash, transaction) = match std::mem::replace(
             &mut self.current_coinbase,
             store_last_coinbase(&self.wallet_db, None).await,
-        )
-        .unwrap();
+        ) {
+            Some(coinbase) => coinbase,
+            None => {
+                error!("No coinbase to commit");
+                return;
+            }
+        };
 
         let payments = get_payments_for_wallet(Some((&hash, &transaction)).into_iter());
 
@@ -1424,13 +1517,18 @@ impl MinerNode {
             b_num - 1
         );
 
-        self.wallet_db
+        if let Err(e) = self
+            .wallet_db
             .save_usable_payments_to_wallet(payments, b_num)
             .await
-            .unwrap();
+        {
+            error!("Winnings not saved to wallet {:?}", e);
+        }
 
         // Backup wallet after committing the coinbase
-        self.wallet_db.backup_persistent_store().await.unwrap();
+        if let Err(e) = self.wallet_db.backup_persistent_store().await {
+            error!("Wallet backup failed {:?}", e);
+        }
 
         // Notify the end user that a winning PoW has been found
         try_send_to_ui(
@@ -1453,7 +1551,13 @@ impl MinerNode {
                 );
 
                     // All last known addresses
-                    let known_addresses = self.wallet_db.get_known_addresses();
+                    let known_addresses = match self.wallet_db.get_known_addresses() {
+                        Ok(addresses) => addresses,
+                        Err(e) => {
+                            error!("Error reading known addresses: {:?}", e);
+                            return;
+                        }
+                    };
 
                     // Check if we have a reached the threshold of addresses stored
                     if known_addresses.len() >= address_aggregation_limit {
@@ -1467,14 +1571,18 @@ impl MinerNode {
                             .collect::<BTreeSet<_>>();
 
                         // Fetch the aggregating transaction inputs and outputs
-                        let (tx_ins, tx_outs) = self
-                            .wallet_db
-                            .fetch_tx_ins_and_tx_outs_merge_input_addrs(
-                                addresses_to_aggregate,
-                                None,
-                            )
-                            .await
-                            .unwrap();
+                        let trigger = "internal:aggregate_winnings".to_owned();
+                        let fetched = self.wallet_db.fetch_tx_ins_and_tx_outs_merge_input_addrs(
+                            addresses_to_aggregate,
+                            None,
+                        );
+                        let (tx_ins, tx_outs) = match with_signing_trigger(trigger, fetched).await {
+                            Ok(fetched) => fetched,
+                            Err(e) => {
+                                error!("Error fetching aggregation tx inputs: {:?}", e);
+                                return;
+                            }
+                        };
 
                         // Aggregation address is last generated address,
                         // which is generated by passing `None` as the `excess_address`
@@ -1522,14 +1630,23 @@ impl MinerNode {
                             .map(|c| c.block.b_num)
                             .unwrap_or_default();
 
-                        self.wallet_db
+                        if let Err(e) = self
+                            .wallet_db
                             .store_payment_transaction(aggregating_tx, b_num)
-                            .await;
+                            .await
+                        {
+                            error!("Error storing aggregation tx: {:?}", e);
+                            return;
+                        }
 
                         trace!("Pruning the wallet of old keys after aggregation");
-                        self.wallet_db
+                        if let Err(e) = self
+                            .wallet_db
                             .destroy_spent_transactions_and_keys(None)
-                            .await;
+                            .await
+                        {
+                            error!("Error pruning spent keys: {:?}", e);
+                        }
 
                         self.aggregation_status = AggregationStatus::UtxoUpdate(aggregating_addr);
                     }
@@ -1662,7 +1779,7 @@ impl MinerNode {
             warn!("load_local_db: generating new locked coinbase from UTXO set");
             // Existing locked coinbase failed to deserialize, so we need to
             // generate a new one using a UTXO subset from the mempool node
-            let all_known_addresses = self.wallet_db.get_known_addresses();
+            let all_known_addresses = self.wallet_db.get_known_addresses()?;
             let request = MempoolRequest::SendUtxoRequest {
                 address_list: UtxoFetchType::AnyOf(all_known_addresses),
                 requester_node_type: NodeType::Miner,
@@ -1689,7 +1806,7 @@ impl MinerNode {
                     debug!("load_local_db: current_payment_address {:?}", addr);
                     Some(addr)
                 } else {
-                    Some(generate_mining_address(&mut self.wallet_db).await)
+                    Some(generate_mining_address(&mut self.wallet_db).await?)
                 }
             }
         };
@@ -1722,7 +1839,7 @@ impl MinerInterface for MinerNode {
             Some((key, item, peer)).filter(|(_, i, _)| !i.data.is_empty());
         Response {
             success: true,
-            reason: "Blockchain item received",
+            reason: ResponseReason::BlockchainItemReceived,
         }
     }
 }
@@ -1775,11 +1892,11 @@ impl Transactor for MinerNode {
 
         Response {
             success: true,
-            reason: "Received UTXO set",
+            reason: ResponseReason::ReceivedUtxoSet,
         }
     }
 
-    async fn update_running_total(&mut self) {
+    async fn update_running_total(&mut self) -> Result<()> {
         let utxo_set = self.received_utxo_set.take();
         let payments = get_payments_for_wallet_from_utxo(utxo_set.into_iter().flatten());
         let b_num = self
@@ -1791,8 +1908,8 @@ impl Transactor for MinerNode {
             .unwrap();
         self.wallet_db
             .save_usable_payments_to_wallet(payments, b_num)
-            .await
-            .unwrap();
+            .await?;
+        Ok(())
     }
 }
 
@@ -1800,24 +1917,24 @@ impl Transactor for MinerNode {
 async fn load_mining_address(wallet_db: &WalletDb) -> Result<Option<String>> {
     Ok(wallet_db
         .get_db_value(MINING_ADDRESS_KEY)
-        .await
+        .await?
         .map(|v| deserialize(&v))
         .transpose()?)
 }
 
 /// Generate mining address storing it in wallet
-async fn generate_mining_address(wallet_db: &mut WalletDb) -> String {
-    let addr: String = wallet_db.generate_payment_address().await.0;
+async fn generate_mining_address(wallet_db: &mut WalletDb) -> Result<String> {
+    let addr: String = wallet_db.generate_payment_address().await?.0;
     let ser_addr = serialize(&addr).unwrap();
-    wallet_db.set_db_value(MINING_ADDRESS_KEY, ser_addr).await;
-    addr
+    wallet_db.set_db_value(MINING_ADDRESS_KEY, ser_addr).await?;
+    Ok(addr)
 }
 
 /// Load last coinbase from wallet
 async fn load_last_coinbase(wallet_db: &WalletDb) -> Result<Option<(String, Transaction)>> {
     Ok(wallet_db
         .get_db_value(LAST_COINBASE_KEY)
-        .await
+        .await?
         .map(|v| deserialize(&v))
         .transpose()?)
 }
@@ -1829,9 +1946,11 @@ async fn store_last_coinbase(
 ) -> Option<(String, Transaction)> {
     if let Some(cb) = &coinbase {
         let ser_cb = serialize(cb).unwrap();
-        wallet_db.set_db_value(LAST_COINBASE_KEY, ser_cb).await;
-    } else {
-        wallet_db.delete_db_value(LAST_COINBASE_KEY).await;
+        if let Err(e) = wallet_db.set_db_value(LAST_COINBASE_KEY, ser_cb).await {
+            error!("Error storing last coinbase: {:?}", e);
+        }
+    } else if let Err(e) = wallet_db.delete_db_value(LAST_COINBASE_KEY).await {
+        error!("Error deleting last coinbase: {:?}", e);
     }
     coinbase
 }
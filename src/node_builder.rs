@@ -0,0 +1,250 @@
+use crate::clock::Clock;
+use crate::configurations::{
+    ExtraNodeParams, MempoolNodeConfig, MinerNodeConfig, PreLaunchNodeConfig, ResponseHook,
+    StorageNodeConfig, TlsSpec, UserNodeConfig,
+};
+use crate::db_utils::{CustomDbSpec, SimpleDb};
+use crate::event_bus::NodeEvent;
+use crate::interfaces::Response;
+use crate::wallet::{KeyGen, WalletDb};
+use crate::{mempool, miner, pre_launch, storage, user};
+use crate::{MempoolNode, MinerNode, PreLaunchNode, StorageNode, UserNode};
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Builds a node from its config, with components supplied by the caller
+///
+/// Anything not supplied is created from the config, as done by the node constructors.
+pub struct NodeBuilder<C> {
+    config: C,
+    extra: ExtraNodeParams,
+}
+
+impl<C> NodeBuilder<C> {
+    /// Start building a node from its config
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Config of the node to build
+    pub fn new(config: C) -> Self {
+        Self {
+            config,
+            extra: Default::default(),
+        }
+    }
+
+    /// Use an already opened database
+    ///
+    /// ### Arguments
+    ///
+    /// * `db` - Node database
+    pub fn with_db(mut self, db: SimpleDb) -> Self {
+        self.extra.db = Some(db);
+        self
+    }
+
+    /// Use an already opened raft database
+    ///
+    /// ### Arguments
+    ///
+    /// * `raft_db` - Node raft database
+    pub fn with_raft_db(mut self, raft_db: SimpleDb) -> Self {
+        self.extra.raft_db = Some(raft_db);
+        self
+    }
+
+    /// Use an already opened wallet database
+    ///
+    /// ### Arguments
+    ///
+    /// * `wallet_db` - Node wallet database
+    pub fn with_wallet_db(mut self, wallet_db: SimpleDb) -> Self {
+        self.extra.wallet_db = Some(wallet_db);
+        self
+    }
+
+    /// Use a wallet shared with another node
+    ///
+    /// ### Arguments
+    ///
+    /// * `wallet_db` - Shared wallet
+    pub fn with_shared_wallet_db(mut self, wallet_db: WalletDb) -> Self {
+        self.extra.shared_wallet_db = Some(wallet_db);
+        self
+    }
+
+    /// Use a custom spec for the wallet database
+    ///
+    /// ### Arguments
+    ///
+    /// * `spec` - Wallet database spec
+    pub fn with_custom_wallet_spec(mut self, spec: CustomDbSpec) -> Self {
+        self.extra.custom_wallet_spec = Some(spec);
+        self
+    }
+
+    /// Use the given TLS material instead of the one in the config
+    ///
+    /// ### Arguments
+    ///
+    /// * `tls_config` - TLS certificates and keys
+    pub fn with_tls_config(mut self, tls_config: TlsSpec) -> Self {
+        self.extra.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Listen for peers on the given address instead of the one in the config
+    ///
+    /// ### Arguments
+    ///
+    /// * `bind_address` - Address to listen on
+    pub fn with_bind_address(mut self, bind_address: SocketAddr) -> Self {
+        self.extra.bind_address = Some(bind_address);
+        self
+    }
+
+    /// Accept peers on an already bound listener
+    ///
+    /// The address of the node in its config must be the listener address.
+    ///
+    /// ### Arguments
+    ///
+    /// * `listener` - Bound listener
+    pub fn with_listener(mut self, listener: TcpListener) -> Self {
+        self.extra.listener = Some(listener);
+        self
+    }
+
+    /// Invoke a callback with every response handled by the node
+    ///
+    /// ### Arguments
+    ///
+    /// * `hook` - Callback invoked with each handled response
+    pub fn with_response_hook(mut self, hook: impl Fn(&Response) + Send + Sync + 'static) -> Self {
+        self.extra.response_hook = Some(ResponseHook::new(hook));
+        self
+    }
+
+    /// Run an async callback on each event of the node
+    ///
+    /// ### Arguments
+    ///
+    /// * `hook` - Callback invoked with each block stored, transaction accepted,
+    ///   payment received and peer connected event
+    pub fn with_event_hook<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(NodeEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.extra.event_hooks.register(hook);
+        self
+    }
+
+    /// Use the given clock for the node timers
+    ///
+    /// ### Arguments
+    ///
+    /// * `clock` - Clock to use
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.extra.clock = clock;
+        self
+    }
+
+    /// Use the given key generator for the node wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `key_gen` - Key generator to use
+    pub fn with_key_gen(mut self, key_gen: KeyGen) -> Self {
+        self.extra.key_gen = key_gen;
+        self
+    }
+
+    /// Do not listen for incoming peer connections
+    pub fn without_tcp_listener(mut self) -> Self {
+        self.extra.disable_tcp_listener = true;
+        self
+    }
+
+    /// Config and construction parameters gathered so far
+    pub fn into_parts(self) -> (C, ExtraNodeParams) {
+        (self.config, self.extra)
+    }
+}
+
+impl NodeBuilder<MempoolNodeConfig> {
+    /// Construct the mempool node
+    pub async fn build(self) -> mempool::Result<MempoolNode> {
+        MempoolNode::new(self.config, self.extra).await
+    }
+}
+
+impl NodeBuilder<StorageNodeConfig> {
+    /// Construct the storage node
+    pub async fn build(self) -> storage::Result<StorageNode> {
+        StorageNode::new(self.config, self.extra).await
+    }
+}
+
+impl NodeBuilder<MinerNodeConfig> {
+    /// Construct the miner node
+    pub async fn build(self) -> miner::Result<MinerNode> {
+        MinerNode::new(self.config, self.extra).await
+    }
+}
+
+impl NodeBuilder<UserNodeConfig> {
+    /// Construct the user node
+    pub async fn build(self) -> user::Result<UserNode> {
+        UserNode::new(self.config, self.extra).await
+    }
+}
+
+impl NodeBuilder<PreLaunchNodeConfig> {
+    /// Construct the pre-launch node
+    pub async fn build(self) -> pre_launch::Result<PreLaunchNode> {
+        PreLaunchNode::new(self.config, self.extra).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::ResponseReason;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    /// Checks the supplied components are passed on to the node constructor
+    fn node_builder_gathers_extra_params() {
+        //Arrange
+        let bind_address: SocketAddr = "127.0.0.1:12600".parse().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let hook_calls = calls.clone();
+
+        //Act
+        let (config, extra) = NodeBuilder::new("config")
+            .with_bind_address(bind_address)
+            .with_tls_config(Default::default())
+            .with_response_hook(move |_| {
+                hook_calls.fetch_add(1, Ordering::SeqCst);
+            })
+            .with_event_hook(|_| async {})
+            .without_tcp_listener()
+            .into_parts();
+        extra.response_hook.as_ref().unwrap().call(&Response {
+            success: true,
+            reason: ResponseReason::Shutdown,
+        });
+
+        //Assert
+        assert_eq!(config, "config");
+        assert_eq!(extra.bind_address, Some(bind_address));
+        assert!(extra.tls_config.is_some());
+        assert!(extra.disable_tcp_listener);
+        assert!(extra.db.is_none());
+        assert!(!extra.event_hooks.is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
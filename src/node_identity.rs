@@ -0,0 +1,217 @@
+//! Persistent identity key of a node.
+//!
+//! Each node holds an Ed25519 identity key-pair, generated on first start and
+//! stored as a hex encoded seed in its identity file, by default next to its
+//! db. The key signs the node handshakes, so peers are known by their identity
+//! beyond their socket address, and signs the db backups when no backup
+//! signing key is configured. Nodes with an in-memory db get a new identity
+//! each time they start.
+
+use crate::configurations::DbMode;
+use crate::db_utils::{self, SimpleDbSpec};
+use crate::interfaces::{IdentityProof, NodeType};
+use crate::wallet::key_gen::keypair_from_seed;
+use crate::wallet::key_import::{decode_keypair, KeyFormat, PKCS8_SEED_RANGE};
+use rand::Rng;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use std::fs;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use tracing::info;
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey};
+
+/// Prefix of the signed handshake content, so the signature is not valid for other uses
+const HANDSHAKE_DOMAIN: &[u8] = b"aiblock-handshake:";
+
+/// Ed25519 key-pair identifying a node
+#[derive(Clone)]
+pub struct NodeIdentity {
+    public_key: PublicKey,
+    secret_key: SecretKey,
+}
+
+impl std::fmt::Debug for NodeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NodeIdentity({})", hex::encode(self.public_key.as_ref()))
+    }
+}
+
+impl NodeIdentity {
+    /// Generate a new identity
+    pub fn generate() -> Self {
+        let seed: [u8; 32] = rand::thread_rng().gen();
+        let (public_key, secret_key) = keypair_from_seed(&seed);
+        Self {
+            public_key,
+            secret_key,
+        }
+    }
+
+    /// Read the identity in the file, generating and storing it if the file does not exist
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Identity file holding the hex encoded seed
+    pub fn load_or_create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(seed) => {
+                let (public_key, secret_key) = decode_keypair(KeyFormat::Hex, &seed)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid identity"))?;
+                Ok(Self {
+                    public_key,
+                    secret_key,
+                })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let identity = Self::generate();
+                identity.save(path)?;
+                info!("New node identity {:?} stored in {:?}", identity, path);
+                Ok(identity)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the hex encoded seed to a new file, only readable by its owner
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Identity file to create
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let seed = &self.secret_key.as_ref()[PKCS8_SEED_RANGE];
+        options.open(path)?.write_all(hex::encode(seed).as_bytes())
+    }
+
+    /// Public key identifying the node
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Secret key of the identity
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    /// Proof of this identity for a handshake
+    ///
+    /// ### Arguments
+    ///
+    /// * `network_version` - Network version sent in the handshake
+    /// * `node_type`       - Node type sent in the handshake
+    /// * `public_address`  - Address sent in the handshake
+    pub fn prove_handshake(
+        &self,
+        network_version: u32,
+        node_type: NodeType,
+        public_address: SocketAddr,
+    ) -> IdentityProof {
+        let content = handshake_content(network_version, node_type, public_address);
+        IdentityProof {
+            public_key: self.public_key,
+            signature: sign::sign_detached(&content, &self.secret_key),
+        }
+    }
+}
+
+/// Check a handshake is signed by the identity it presents
+///
+/// ### Arguments
+///
+/// * `proof`           - Identity proof sent in the handshake
+/// * `network_version` - Network version sent in the handshake
+/// * `node_type`       - Node type sent in the handshake
+/// * `public_address`  - Address sent in the handshake
+pub fn verify_handshake(
+    proof: &IdentityProof,
+    network_version: u32,
+    node_type: NodeType,
+    public_address: SocketAddr,
+) -> bool {
+    let content = handshake_content(network_version, node_type, public_address);
+    UnparsedPublicKey::new(&ED25519, proof.public_key.as_ref())
+        .verify(&content, proof.signature.as_ref())
+        .is_ok()
+}
+
+/// Identity of a node: from the configured file, or the default file next to its db
+///
+/// ### Arguments
+///
+/// * `identity_file` - Configured identity file, if any
+/// * `db_mode`       - Mode of the node db
+/// * `db_spec`       - Spec of the node db
+pub fn load_node_identity(
+    identity_file: Option<&str>,
+    db_mode: DbMode,
+    db_spec: &SimpleDbSpec,
+) -> io::Result<NodeIdentity> {
+    let default_file =
+        || db_utils::new_db_save_path(db_mode, db_spec, None).map(|db| format!("{db}_identity"));
+    match identity_file.map(str::to_owned).or_else(default_file) {
+        Some(path) => NodeIdentity::load_or_create(path),
+        None => Ok(NodeIdentity::generate()),
+    }
+}
+
+/// Signed content of a handshake
+fn handshake_content(
+    network_version: u32,
+    node_type: NodeType,
+    public_address: SocketAddr,
+) -> Vec<u8> {
+    let fields = bincode::serialize(&(network_version, node_type, public_address)).unwrap();
+    [HANDSHAKE_DOMAIN, &fields].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the identity is stored on first start and reused afterwards
+    fn load_or_create_reuses_identity() {
+        //Arrange
+        let dir = std::env::temp_dir().join(format!("node_identity_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("db_identity");
+
+        //Act
+        let created = NodeIdentity::load_or_create(&path).unwrap();
+        let reloaded = NodeIdentity::load_or_create(&path).unwrap();
+        fs::write(&path, "not a seed").unwrap();
+        let invalid = NodeIdentity::load_or_create(&path);
+        let _ = fs::remove_dir_all(&dir);
+
+        //Assert
+        assert_eq!(created.public_key(), reloaded.public_key());
+        assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    /// Checks a handshake proof is only valid for the signed handshake
+    fn handshake_proof_bound_to_content() {
+        //Arrange
+        let identity = NodeIdentity::generate();
+        let addr: SocketAddr = "127.0.0.1:12300".parse().unwrap();
+        let other_addr: SocketAddr = "127.0.0.1:12301".parse().unwrap();
+
+        //Act
+        let proof = identity.prove_handshake(1, NodeType::Mempool, addr);
+
+        //Assert
+        assert!(verify_handshake(&proof, 1, NodeType::Mempool, addr));
+        assert!(!verify_handshake(&proof, 1, NodeType::Mempool, other_addr));
+        assert!(!verify_handshake(&proof, 1, NodeType::Storage, addr));
+    }
+}
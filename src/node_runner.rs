@@ -0,0 +1,497 @@
+use crate::api::client_auth::serve_with_client_auth;
+use crate::comms_handler::Node;
+use crate::configurations::TlsPrivateInfo;
+use crate::db_utils::SimpleDbError;
+use crate::interfaces::{Response, ResponseReason};
+use crate::utils::{
+    loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, shutdown_connections,
+    LocalEventSender, ResponseResult,
+};
+use crate::{mempool, miner, pre_launch, storage, user};
+use crate::{MempoolNode, MinerNode, PreLaunchNode, StorageNode, UserNode};
+use async_trait::async_trait;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+use warp::{Filter, Rejection, Reply};
+
+/// Future completing with the reason to stop a node
+pub type ExitFuture = Pin<Box<dyn Future<Output = ResponseReason> + Send>>;
+
+/// Future running alongside a node, such as its raft loop or API server
+pub type TaskFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Node driven by a `NodeRunner`
+///
+/// The methods forward to the node methods of the same name.
+#[async_trait]
+pub trait RunnableNode: Send + 'static {
+    type Error: fmt::Debug + Send;
+
+    /// Address the node listens on
+    fn local_address(&self) -> SocketAddr;
+
+    /// Connection of the node, peers to connect to and peers to wait for
+    fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>);
+
+    /// Sender of the node local events
+    fn local_event_tx(&self) -> &LocalEventSender;
+
+    /// Time to wait for peers to connect before starting, unlimited if None
+    fn peer_wait_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Raft loop to run once peers are connected, if the node has one
+    fn raft_loop(&self) -> Option<TaskFuture> {
+        None
+    }
+
+    /// Read every entry of the node databases, returning the number of entries
+    fn scan_dbs(&self) -> Result<usize, SimpleDbError>;
+
+    /// Send the requests starting the node activity
+    async fn send_startup_requests(&mut self) -> Result<(), Self::Error>;
+
+    /// Handle the next event, or complete with the exit reason
+    ///
+    /// ### Arguments
+    ///
+    /// * `exit` - Future completing when the node should stop
+    async fn handle_next_event(
+        &mut self,
+        exit: &mut ExitFuture,
+    ) -> Option<Result<Response, Self::Error>>;
+
+    /// Process the response of a handled event
+    ///
+    /// ### Arguments
+    ///
+    /// * `response` - Response of the handled event
+    async fn handle_next_event_response(
+        &mut self,
+        response: Result<Response, Self::Error>,
+    ) -> ResponseResult;
+
+    /// Close the raft loop and flush pending writes before the node stops
+    async fn close_raft_loop(&mut self) {}
+}
+
+/// Runs a node: peer connection loops, raft loop, event loop and API server
+///
+/// Once the event loop exits, the connection loops and the API server are
+/// stopped and the raft loop closed before `run` completes. Exiting while
+/// waiting for peers stops the connection loops before the raft loop starts.
+pub struct NodeRunner<N> {
+    node: N,
+    exit: ExitFuture,
+    api: Option<TaskFuture>,
+}
+
+impl<N: RunnableNode> NodeRunner<N> {
+    /// Create a runner for the given node, running until the node exits by itself
+    ///
+    /// ### Arguments
+    ///
+    /// * `node` - Node to run
+    pub fn new(node: N) -> Self {
+        Self {
+            node,
+            exit: Box::pin(std::future::pending()),
+            api: None,
+        }
+    }
+
+    /// Stop the node once the given future completes
+    ///
+    /// ### Arguments
+    ///
+    /// * `exit` - Future completing with the reason to stop
+    pub fn with_exit(
+        mut self,
+        exit: impl Future<Output = ResponseReason> + Send + 'static,
+    ) -> Self {
+        self.exit = Box::pin(exit);
+        self
+    }
+
+    /// Serve an API while the node runs
+    ///
+    /// ### Arguments
+    ///
+    /// * `api` - API server, started once peers are connected
+    pub fn with_api(mut self, api: impl Future<Output = ()> + Send + 'static) -> Self {
+        self.api = Some(Box::pin(api));
+        self
+    }
+
+    /// Node being run
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// Connection of the node being run
+    pub fn node_conn(&self) -> Node {
+        self.node.connect_info_peers().0
+    }
+
+    /// Run the node until it exits
+    pub async fn run(self) {
+        let Self {
+            mut node,
+            mut exit,
+            api,
+        } = self;
+
+        info!("Started node at {}", node.local_address());
+        let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
+        let local_event_tx = node.local_event_tx().clone();
+
+        // PERMANENT CONNEXION/DISCONNECTION HANDLING
+        let ((conn_loop_handle, stop_re_connect_tx), (disconn_loop_handle, stop_disconnect_tx)) = {
+            let (re_connect, disconnect_test) =
+                loops_re_connect_disconnect(node_conn.clone(), addrs_to_connect, local_event_tx);
+
+            (
+                (tokio::spawn(re_connect.0), re_connect.1),
+                (tokio::spawn(disconnect_test.0), disconnect_test.1),
+            )
+        };
+
+        // Need to connect first so Raft messages can be sent.
+        // Stop waiting if asked to exit, as the peers may never connect.
+        let peer_wait_timeout = node.peer_wait_timeout();
+        tokio::select! {
+            connected = loop_wait_connnect_to_peers_async(
+                node_conn.clone(),
+                expected_connected_addrs,
+                peer_wait_timeout,
+            ) => {
+                if !connected {
+                    warn!("Not all peers connected after {peer_wait_timeout:?}, starting anyway");
+                }
+            }
+            reason = &mut exit => {
+                info!("Node exit before peers connected: {reason:?}");
+                stop_re_connect_tx.send(()).unwrap();
+                stop_disconnect_tx.send(()).unwrap();
+                shutdown_connections(&mut node_conn.clone()).await;
+                conn_loop_handle.await.unwrap();
+                disconn_loop_handle.await.unwrap();
+                return;
+            }
+        }
+
+        // RAFT HANDLING
+        let raft_loop_handle = node.raft_loop().map(|raft_loop| {
+            tokio::spawn(async move {
+                info!("Peer connect complete, start Raft");
+                raft_loop.await;
+                info!("Raft complete");
+            })
+        });
+
+        // API, stopped once the node closed
+        let (stop_api_tx, stop_api_rx) = oneshot::channel::<()>();
+        let api_handle = api.map(|api| {
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = api => {}
+                    _ = stop_api_rx => info!("Warp API stopped"),
+                }
+            })
+        });
+
+        // REQUEST HANDLING
+        let main_loop_handle = tokio::spawn({
+            let mut node_conn = node_conn;
+
+            async move {
+                node.send_startup_requests().await.unwrap();
+
+                while let Some(response) = node.handle_next_event(&mut exit).await {
+                    if node.handle_next_event_response(response).await == ResponseResult::Exit {
+                        break;
+                    }
+                }
+                stop_re_connect_tx.send(()).unwrap();
+                stop_disconnect_tx.send(()).unwrap();
+
+                node.close_raft_loop().await;
+                shutdown_connections(&mut node_conn).await;
+                let _ = stop_api_tx.send(());
+            }
+        });
+
+        main_loop_handle.await.unwrap();
+        conn_loop_handle.await.unwrap();
+        disconn_loop_handle.await.unwrap();
+        if let Some(handle) = raft_loop_handle {
+            handle.await.unwrap();
+        }
+        if let Some(handle) = api_handle {
+            handle.await.unwrap();
+        }
+    }
+}
+
+/// Serve the API routes on all interfaces at the port of the API address
+///
+/// ### Arguments
+///
+/// * `routes`   - Routes of the API
+/// * `api_addr` - Address of the API, only its port is used
+/// * `api_tls`  - TLS material of the API, and client authentication if any
+pub async fn serve_api<F, R>(routes: F, api_addr: SocketAddr, api_tls: Option<TlsPrivateInfo>)
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    info!("Warp API started on port {:?}", api_addr.port());
+    let bind_address = SocketAddr::new([0, 0, 0, 0].into(), api_addr.port());
+
+    let client_auth = api_tls.as_ref().and_then(|tls| tls.client_auth.clone());
+    if let Some(client_auth) = client_auth {
+        serve_with_client_auth(routes, bind_address, client_auth).await;
+    } else if let Some(api_tls) = api_tls {
+        warp::serve(routes)
+            .tls()
+            .key(&api_tls.pem_pkcs8_private_keys)
+            .cert(&api_tls.pem_certs)
+            .run(bind_address)
+            .await;
+    } else {
+        warp::serve(routes).run(bind_address).await;
+    }
+}
+
+#[async_trait]
+impl RunnableNode for MempoolNode {
+    type Error = mempool::MempoolError;
+
+    fn local_address(&self) -> SocketAddr {
+        MempoolNode::local_address(self)
+    }
+
+    fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>) {
+        MempoolNode::connect_info_peers(self)
+    }
+
+    fn local_event_tx(&self) -> &LocalEventSender {
+        MempoolNode::local_event_tx(self)
+    }
+
+    fn peer_wait_timeout(&self) -> Option<Duration> {
+        MempoolNode::peer_wait_timeout(self)
+    }
+
+    fn scan_dbs(&self) -> Result<usize, SimpleDbError> {
+        MempoolNode::scan_dbs(self)
+    }
+
+    fn raft_loop(&self) -> Option<TaskFuture> {
+        Some(Box::pin(MempoolNode::raft_loop(self)))
+    }
+
+    async fn send_startup_requests(&mut self) -> Result<(), Self::Error> {
+        MempoolNode::send_startup_requests(self).await
+    }
+
+    async fn handle_next_event(
+        &mut self,
+        exit: &mut ExitFuture,
+    ) -> Option<Result<Response, Self::Error>> {
+        MempoolNode::handle_next_event(self, exit).await
+    }
+
+    async fn handle_next_event_response(
+        &mut self,
+        response: Result<Response, Self::Error>,
+    ) -> ResponseResult {
+        MempoolNode::handle_next_event_response(self, response).await
+    }
+
+    async fn close_raft_loop(&mut self) {
+        MempoolNode::close_raft_loop(self).await
+    }
+}
+
+#[async_trait]
+impl RunnableNode for StorageNode {
+    type Error = storage::StorageError;
+
+    fn local_address(&self) -> SocketAddr {
+        StorageNode::local_address(self)
+    }
+
+    fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>) {
+        StorageNode::connect_info_peers(self)
+    }
+
+    fn local_event_tx(&self) -> &LocalEventSender {
+        StorageNode::local_event_tx(self)
+    }
+
+    fn peer_wait_timeout(&self) -> Option<Duration> {
+        StorageNode::peer_wait_timeout(self)
+    }
+
+    fn scan_dbs(&self) -> Result<usize, SimpleDbError> {
+        StorageNode::scan_dbs(self)
+    }
+
+    fn raft_loop(&self) -> Option<TaskFuture> {
+        Some(Box::pin(StorageNode::raft_loop(self)))
+    }
+
+    async fn send_startup_requests(&mut self) -> Result<(), Self::Error> {
+        StorageNode::send_startup_requests(self).await
+    }
+
+    async fn handle_next_event(
+        &mut self,
+        exit: &mut ExitFuture,
+    ) -> Option<Result<Response, Self::Error>> {
+        StorageNode::handle_next_event(self, exit).await
+    }
+
+    async fn handle_next_event_response(
+        &mut self,
+        response: Result<Response, Self::Error>,
+    ) -> ResponseResult {
+        StorageNode::handle_next_event_response(self, response).await
+    }
+
+    async fn close_raft_loop(&mut self) {
+        StorageNode::close_raft_loop(self).await
+    }
+}
+
+#[async_trait]
+impl RunnableNode for MinerNode {
+    type Error = miner::MinerError;
+
+    fn local_address(&self) -> SocketAddr {
+        MinerNode::local_address(self)
+    }
+
+    fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>) {
+        MinerNode::connect_info_peers(self)
+    }
+
+    fn local_event_tx(&self) -> &LocalEventSender {
+        MinerNode::local_event_tx(self)
+    }
+
+    fn peer_wait_timeout(&self) -> Option<Duration> {
+        MinerNode::peer_wait_timeout(self)
+    }
+
+    fn scan_dbs(&self) -> Result<usize, SimpleDbError> {
+        MinerNode::scan_dbs(self)
+    }
+
+    async fn send_startup_requests(&mut self) -> Result<(), Self::Error> {
+        MinerNode::send_startup_requests(self).await
+    }
+
+    async fn handle_next_event(
+        &mut self,
+        exit: &mut ExitFuture,
+    ) -> Option<Result<Response, Self::Error>> {
+        MinerNode::handle_next_event(self, exit).await
+    }
+
+    async fn handle_next_event_response(
+        &mut self,
+        response: Result<Response, Self::Error>,
+    ) -> ResponseResult {
+        MinerNode::handle_next_event_response(self, response).await
+    }
+}
+
+#[async_trait]
+impl RunnableNode for UserNode {
+    type Error = user::UserError;
+
+    fn local_address(&self) -> SocketAddr {
+        UserNode::local_address(self)
+    }
+
+    fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>) {
+        UserNode::connect_info_peers(self)
+    }
+
+    fn local_event_tx(&self) -> &LocalEventSender {
+        UserNode::local_event_tx(self)
+    }
+
+    fn peer_wait_timeout(&self) -> Option<Duration> {
+        UserNode::peer_wait_timeout(self)
+    }
+
+    fn scan_dbs(&self) -> Result<usize, SimpleDbError> {
+        UserNode::scan_dbs(self)
+    }
+
+    async fn send_startup_requests(&mut self) -> Result<(), Self::Error> {
+        UserNode::send_startup_requests(self).await
+    }
+
+    async fn handle_next_event(
+        &mut self,
+        exit: &mut ExitFuture,
+    ) -> Option<Result<Response, Self::Error>> {
+        UserNode::handle_next_event(self, exit).await
+    }
+
+    async fn handle_next_event_response(
+        &mut self,
+        response: Result<Response, Self::Error>,
+    ) -> ResponseResult {
+        UserNode::handle_next_event_response(self, response).await
+    }
+}
+
+#[async_trait]
+impl RunnableNode for PreLaunchNode {
+    type Error = pre_launch::PreLaunchError;
+
+    fn local_address(&self) -> SocketAddr {
+        PreLaunchNode::local_address(self)
+    }
+
+    fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>) {
+        PreLaunchNode::connect_info_peers(self)
+    }
+
+    fn local_event_tx(&self) -> &LocalEventSender {
+        PreLaunchNode::local_event_tx(self)
+    }
+
+    fn scan_dbs(&self) -> Result<usize, SimpleDbError> {
+        PreLaunchNode::scan_dbs(self)
+    }
+
+    async fn send_startup_requests(&mut self) -> Result<(), Self::Error> {
+        PreLaunchNode::send_startup_requests(self).await
+    }
+
+    async fn handle_next_event(
+        &mut self,
+        exit: &mut ExitFuture,
+    ) -> Option<Result<Response, Self::Error>> {
+        PreLaunchNode::handle_next_event(self, exit).await
+    }
+
+    async fn handle_next_event_response(
+        &mut self,
+        response: Result<Response, Self::Error>,
+    ) -> ResponseResult {
+        PreLaunchNode::handle_next_event_response(self, response).await
+    }
+}
@@ -0,0 +1,214 @@
+//! Data payloads anchored by transaction outputs.
+//!
+//! Arbitrary data is attached to an output as the metadata of its item asset,
+//! so anchoring data is creating an item. Mempool nodes reject transactions
+//! whose payloads exceed their size limits, storage nodes serve the payloads of
+//! the transactions they store, and user nodes record the payloads they anchor
+//! in their wallet by transaction hash.
+
+use crate::wallet::{Result, WalletDb};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::{Transaction, TxOut};
+
+/// Key for the data anchored by the wallet transactions
+pub const ANCHORED_DATA_KEY: &str = "AnchoredDataKey";
+
+/// Bytes of data an output can carry, when not configured
+pub const DEFAULT_MAX_OUTPUT_DATA_SIZE: usize = 1024;
+
+/// Bytes of data all outputs of a transaction can carry, when not configured
+pub const DEFAULT_MAX_TX_DATA_SIZE: usize = 4096;
+
+/// Size limits of the data carried by transaction outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLimits {
+    /// Bytes of data an output can carry
+    pub max_output_size: usize,
+    /// Bytes of data all outputs of a transaction can carry
+    pub max_tx_size: usize,
+}
+
+impl Default for DataLimits {
+    fn default() -> Self {
+        Self {
+            max_output_size: DEFAULT_MAX_OUTPUT_DATA_SIZE,
+            max_tx_size: DEFAULT_MAX_TX_DATA_SIZE,
+        }
+    }
+}
+
+impl DataLimits {
+    /// Limits from the configured values, using the defaults for the ones not set
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_output_size` - Configured bytes of data an output can carry
+    /// * `max_tx_size`     - Configured bytes of data a transaction can carry
+    pub fn new(max_output_size: Option<usize>, max_tx_size: Option<usize>) -> Self {
+        Self {
+            max_output_size: max_output_size.unwrap_or(DEFAULT_MAX_OUTPUT_DATA_SIZE),
+            max_tx_size: max_tx_size.unwrap_or(DEFAULT_MAX_TX_DATA_SIZE),
+        }
+    }
+
+    /// Whether the data of each output and of the whole transaction is within the limits
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx` - Transaction to check
+    pub fn allows(&self, tx: &Transaction) -> bool {
+        let mut total = 0;
+        for data in tx.outputs.iter().filter_map(output_data) {
+            if data.len() > self.max_output_size {
+                return false;
+            }
+            total += data.len();
+        }
+        total <= self.max_tx_size
+    }
+}
+
+/// Data carried by an output of a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputData {
+    /// Index of the output in the transaction
+    pub index: u32,
+    pub data: String,
+}
+
+/// Data carried by an output, if any
+///
+/// ### Arguments
+///
+/// * `tx_out` - Output to read
+pub fn output_data(tx_out: &TxOut) -> Option<&str> {
+    match &tx_out.value {
+        Asset::Item(item) => item.metadata.as_deref(),
+        _ => None,
+    }
+}
+
+/// Data carried by the outputs of a transaction
+///
+/// ### Arguments
+///
+/// * `tx` - Transaction to read
+pub fn tx_output_data(tx: &Transaction) -> Vec<OutputData> {
+    let indexed = tx.outputs.iter().enumerate();
+    indexed
+        .filter_map(|(index, tx_out)| {
+            let data = output_data(tx_out)?;
+            Some(OutputData {
+                index: index as u32,
+                data: data.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Load the data anchored by the wallet transactions, by transaction hash
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the anchored data.
+pub async fn load_anchored_data(
+    wallet_db: &WalletDb,
+) -> Result<BTreeMap<String, Vec<OutputData>>> {
+    let anchored = wallet_db.get_db_value(ANCHORED_DATA_KEY).await?;
+    Ok(anchored
+        .and_then(|v| bincode::deserialize(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Record the data anchored by a wallet transaction
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the anchored data.
+/// * `tx_hash`   - Hash of the transaction anchoring the data.
+/// * `tx`        - Transaction anchoring the data.
+pub async fn save_anchored_data(
+    wallet_db: &WalletDb,
+    tx_hash: String,
+    tx: &Transaction,
+) -> Result<()> {
+    let data = tx_output_data(tx);
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    let mut anchored = load_anchored_data(wallet_db).await?;
+    anchored.insert(tx_hash, data);
+    let anchored = bincode::serialize(&anchored).unwrap();
+    wallet_db.set_db_value(ANCHORED_DATA_KEY, anchored).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::asset::TokenAmount;
+
+    fn data_tx(payloads: &[&str]) -> Transaction {
+        let mut tx = Transaction::new();
+        tx.outputs = payloads
+            .iter()
+            .map(|data| TxOut {
+                value: Asset::item(1, None, Some(data.to_string())),
+                ..Default::default()
+            })
+            .collect();
+        tx.outputs.push(TxOut {
+            value: Asset::Token(TokenAmount(5)),
+            ..Default::default()
+        });
+        tx
+    }
+
+    #[test]
+    /// Checks the data of each output is read with its output index
+    fn tx_output_data_reads_item_metadata() {
+        //Arrange
+        let tx = data_tx(&["first", "second"]);
+
+        //Act
+        let data = tx_output_data(&tx);
+
+        //Assert
+        assert_eq!(
+            data,
+            vec![
+                OutputData {
+                    index: 0,
+                    data: "first".to_owned()
+                },
+                OutputData {
+                    index: 1,
+                    data: "second".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    /// Checks both the output and the transaction size limits are enforced
+    fn data_limits_reject_oversized_payloads() {
+        //Arrange
+        let limits = DataLimits::new(Some(4), Some(6));
+        let within = data_tx(&["abc", "abc"]);
+        let large_output = data_tx(&["abcde"]);
+        let large_tx = data_tx(&["abcd", "abcd"]);
+
+        //Act
+        let allowed = limits.allows(&within);
+        let output_allowed = limits.allows(&large_output);
+        let tx_allowed = limits.allows(&large_tx);
+
+        //Assert
+        assert!(allowed);
+        assert!(!output_allowed);
+        assert!(!tx_allowed);
+        assert!(DataLimits::default().allows(&Transaction::new()));
+    }
+}
@@ -0,0 +1,169 @@
+//! Record of the mining partition rounds of the mempool node.
+//!
+//! Once the winning PoW of a block is selected, the mempool node records the
+//! round in its database: the miners of its partition, the miners that sent a
+//! valid PoW, the winner and the reward its coinbase pays. The records are kept
+//! by block number, so pools and auditors can check how the rewards were
+//! distributed over any range of blocks.
+
+use crate::db_utils::SimpleDb;
+use crate::mempool::DB_COL_INTERNAL;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use tracing::warn;
+use tw_chain::primitives::asset::TokenAmount;
+use tw_chain::primitives::transaction::Transaction;
+
+/// Prefix of the keys of the partition rounds
+pub const PARTITION_ROUND_PREFIX: &str = "PartitionRound_";
+
+/// Maximum number of rounds returned for one range
+pub const MAX_PARTITION_ROUND_RANGE: u64 = 1000;
+
+/// Mining partition round of a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionRound {
+    pub b_num: u64,
+    /// Timestamp of the block
+    pub timestamp: i64,
+    /// Miners of the partition of this mempool node
+    pub participants: Vec<SocketAddr>,
+    /// Miners that sent a valid PoW, in any partition
+    pub pow_miners: Vec<SocketAddr>,
+    pub winner: SocketAddr,
+    pub coinbase_hash: String,
+    /// Tokens paid by the coinbase to each address
+    pub paid: BTreeMap<String, TokenAmount>,
+}
+
+impl PartitionRound {
+    /// Record of a round won by the given coinbase
+    ///
+    /// ### Arguments
+    ///
+    /// * `b_num`        - Number of the block mined.
+    /// * `timestamp`    - Timestamp of the block.
+    /// * `participants` - Miners of the partition.
+    /// * `pow_miners`   - Miners that sent a valid PoW.
+    /// * `winner`       - Winning miner.
+    /// * `coinbase`     - Hash and transaction of the winning coinbase.
+    pub fn new(
+        b_num: u64,
+        timestamp: i64,
+        participants: Vec<SocketAddr>,
+        pow_miners: Vec<SocketAddr>,
+        winner: SocketAddr,
+        (coinbase_hash, coinbase): (&str, &Transaction),
+    ) -> Self {
+        let mut paid = BTreeMap::new();
+        for tx_out in &coinbase.outputs {
+            if let Some(address) = &tx_out.script_public_key {
+                let total = paid.entry(address.clone()).or_insert(TokenAmount(0));
+                *total += tx_out.value.token_amount();
+            }
+        }
+
+        Self {
+            b_num,
+            timestamp,
+            participants,
+            pow_miners,
+            winner,
+            coinbase_hash: coinbase_hash.to_owned(),
+            paid,
+        }
+    }
+
+    /// Total tokens paid by the coinbase
+    pub fn reward(&self) -> TokenAmount {
+        self.paid.values().copied().sum()
+    }
+}
+
+/// Record a partition round, replacing the one of the same block if any
+///
+/// ### Arguments
+///
+/// * `db`    - Mempool database.
+/// * `round` - Round to record.
+pub fn put_partition_round(db: &mut SimpleDb, round: &PartitionRound) {
+    let value = serialize(round).unwrap();
+    if let Err(e) = db.put_cf(DB_COL_INTERNAL, partition_round_key(round.b_num), value) {
+        warn!("put_partition_round {}: {}", round.b_num, e);
+    }
+}
+
+/// Partition rounds of a range of blocks, by ascending block number
+///
+/// ### Arguments
+///
+/// * `db`    - Mempool database.
+/// * `start` - First block number of the range.
+/// * `end`   - Last block number of the range, capped to `MAX_PARTITION_ROUND_RANGE` blocks.
+pub fn get_partition_rounds(db: &SimpleDb, start: u64, end: u64) -> Vec<PartitionRound> {
+    let end = end.min(start.saturating_add(MAX_PARTITION_ROUND_RANGE - 1));
+    (start..=end)
+        .filter_map(|b_num| {
+            let value = db.get_cf(DB_COL_INTERNAL, partition_round_key(b_num));
+            value.unwrap_or_else(|e| {
+                warn!("get_partition_rounds {}: {}", b_num, e);
+                None
+            })
+        })
+        .filter_map(|value| deserialize(&value).ok())
+        .collect()
+}
+
+/// Key of the partition round of a block
+fn partition_round_key(b_num: u64) -> String {
+    format!("{PARTITION_ROUND_PREFIX}{b_num:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configurations::DbMode;
+    use crate::db_utils::new_db;
+    use crate::mempool::DB_SPEC;
+    use tw_chain::primitives::asset::Asset;
+    use tw_chain::primitives::transaction::TxOut;
+
+    #[test]
+    /// Checks the rounds are recorded with the coinbase payments and read by range
+    fn partition_rounds_recorded_by_block() {
+        //Arrange
+        let mut db = new_db(DbMode::InMemory, &DB_SPEC, None, None);
+        let miner: SocketAddr = "127.0.0.1:12340".parse().unwrap();
+        let mut coinbase = Transaction::new();
+        coinbase.outputs = ["a", "b", "a"]
+            .iter()
+            .map(|address| TxOut {
+                value: Asset::token_u64(5),
+                script_public_key: Some(address.to_string()),
+                ..Default::default()
+            })
+            .collect();
+
+        //Act
+        for b_num in 1..4 {
+            let round = PartitionRound::new(
+                b_num,
+                0,
+                vec![miner],
+                vec![miner],
+                miner,
+                ("coinbase", &coinbase),
+            );
+            put_partition_round(&mut db, &round);
+        }
+        let rounds = get_partition_rounds(&db, 2, 10);
+
+        //Assert
+        let b_nums: Vec<_> = rounds.iter().map(|round| round.b_num).collect();
+        assert_eq!(b_nums, vec![2, 3]);
+        assert_eq!(rounds[0].paid["a"], TokenAmount(10));
+        assert_eq!(rounds[0].reward(), TokenAmount(15));
+    }
+}
@@ -0,0 +1,100 @@
+//! PID file held by a node process for service management.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// PID file of the running process, removed when dropped
+///
+/// A PID file left behind by a process no longer running is replaced.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the PID of the current process, unless a live process holds the file
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Path of the PID file
+    pub fn acquire(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(pid) = running_pid(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Node already running with PID {pid}"),
+            ));
+        }
+
+        fs::write(&path, format!("{}\n", std::process::id()))?;
+        Ok(Self { path })
+    }
+
+    /// Path of the PID file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("PID file {:?} not removed: {:?}", self.path, e);
+        }
+    }
+}
+
+/// PID recorded in the file, if the process is still running
+///
+/// ### Arguments
+///
+/// * `path` - Path of the PID file
+pub fn running_pid(path: impl AsRef<Path>) -> Option<u32> {
+    let pid = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    is_running(pid).then_some(pid)
+}
+
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_running(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_or(true, |s| s.success())
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks a live PID file is refused, a stale one replaced, and the file removed on drop
+    fn pid_file_refuses_live_process() {
+        //Arrange
+        let path = std::env::temp_dir().join(format!("pid_file_test_{}.pid", std::process::id()));
+        fs::write(&path, "4294967295\n").unwrap();
+
+        //Act
+        let pid_file = PidFile::acquire(&path).unwrap();
+        let recorded = running_pid(&path);
+        let second = PidFile::acquire(&path);
+        drop(pid_file);
+
+        //Assert
+        assert_eq!(recorded, Some(std::process::id()));
+        assert_eq!(second.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert!(!path.exists());
+    }
+}
@@ -1,9 +1,9 @@
 use crate::comms_handler::{CommsError, Event, Node, TcpTlsConfig};
 use crate::configurations::{
-    DbMode, ExtraNodeParams, PreLaunchNodeConfig, PreLaunchNodeType, TlsSpec,
+    DbMode, ExtraNodeParams, PreLaunchNodeConfig, PreLaunchNodeType, ResponseHook, TlsSpec,
 };
 use crate::db_utils::{self, SimpleDb, SimpleDbSpec};
-use crate::interfaces::{DbItem, NodeType, PreLaunchRequest, Response};
+use crate::interfaces::{DbItem, NodeType, PreLaunchRequest, Response, ResponseReason};
 use crate::raft_store::{get_presistent_committed, CommittedIndex};
 use crate::utils::{
     create_socket_addr_for_list, LocalEvent, LocalEventChannel, LocalEventSender, ResponseResult,
@@ -124,6 +124,7 @@ pub struct PreLaunchNode {
     pre_launch_nodes: Vec<SocketAddr>,
     shutdown_group: BTreeSet<SocketAddr>,
     raft_db_send: Option<PreLaunchRequest>,
+    response_hook: Option<ResponseHook>,
 }
 
 impl PreLaunchNode {
@@ -134,16 +135,23 @@ impl PreLaunchNode {
     /// * `config` - PreLaunchNodeConfig object containing PreLaunchNode parameters.
     /// * `extra`  - additional parameter for construction
     pub async fn new(
-        config: PreLaunchNodeConfig,
+        mut config: PreLaunchNodeConfig,
         mut extra: ExtraNodeParams,
     ) -> Result<PreLaunchNode> {
+        if let Some(tls_config) = extra.tls_config.take() {
+            config.tls_config = tls_config;
+        }
         let config = PreLaunchNodeConfigSelected::new(config).await;
         let addr = config
             .pre_launch_nodes
             .get(config.pre_launch_node_idx)
             .ok_or(PreLaunchError::ConfigError("Invalid pre-launch index"))?;
 
-        let tcp_tls_config = TcpTlsConfig::from_tls_spec(*addr, &config.tls_config)?;
+        let mut tcp_tls_config =
+            TcpTlsConfig::from_tls_spec(extra.bind_address.unwrap_or(*addr), &config.tls_config)?;
+        if let Some(listener) = extra.listener.take() {
+            tcp_tls_config = tcp_tls_config.with_listener(listener).await;
+        }
 
         let node = Node::new(
             &tcp_tls_config,
@@ -153,6 +161,7 @@ impl PreLaunchNode {
             false,
         )
         .await?;
+        node.set_event_hooks(extra.event_hooks.clone());
         let db = {
             let spec = &config.db_spec;
             db_utils::new_db(config.pre_launch_db_mode, spec, extra.db.take(), None)
@@ -176,6 +185,10 @@ impl PreLaunchNode {
                     .unwrap_or_default(),
                 items: raft_db
                     .iter_all_cf_clone()
+                    .map_err(|e| {
+                        error!("Invalid pre-launch db: {:?}", e);
+                        PreLaunchError::ConfigError("Invalid pre-launch db")
+                    })?
                     .into_iter()
                     .flat_map(|(c, it)| it.map(move |(k, v)| (c.clone(), k, v)))
                     .map(|(column, key, data)| DbItem { column, key, data })
@@ -193,6 +206,7 @@ impl PreLaunchNode {
             pre_launch_nodes,
             shutdown_group,
             raft_db_send,
+            response_hook: extra.response_hook,
         })
     }
 
@@ -240,6 +254,11 @@ impl PreLaunchNode {
         Ok(())
     }
 
+    /// Read every entry of the main and raft databases, returning the number of entries
+    pub fn scan_dbs(&self) -> db_utils::Result<usize> {
+        Ok(self.db.scan()? + self.raft_db.scan()?)
+    }
+
     /// Extract persistent dbs
     pub async fn take_closed_extra_params(&mut self) -> ExtraNodeParams {
         ExtraNodeParams {
@@ -255,30 +274,33 @@ impl PreLaunchNode {
         response: Result<Response>,
     ) -> ResponseResult {
         debug!("Response: {:?}", response);
+        if let (Some(hook), Ok(resp)) = (&self.response_hook, &response) {
+            hook.call(resp);
+        }
 
         match response {
             Ok(Response {
                 success: true,
-                reason: "Sent startup requests on reconnection",
+                reason: ResponseReason::SentStartupRequests,
             }) => debug!("Sent startup requests on reconnection"),
             Ok(Response {
                 success: false,
-                reason: "Failed to send startup requests on reconnection",
+                reason: ResponseReason::FailedToSendStartupRequests,
             }) => error!("Failed to send startup requests on reconnection"),
             Ok(Response {
                 success: true,
-                reason: "Shutdown",
+                reason: ResponseReason::Shutdown,
             }) => {
                 warn!("Shutdown now");
                 return ResponseResult::Exit;
             }
             Ok(Response {
                 success: true,
-                reason: "Shutdown pending",
+                reason: ResponseReason::ShutdownPending,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Received Db Items",
+                reason: ResponseReason::ReceivedDbItems,
             }) => {
                 info!("Received Db Items: Closing");
                 if self.flood_closing_events().await.unwrap() {
@@ -308,7 +330,7 @@ impl PreLaunchNode {
 
     /// Listens for new events from peers and handles them.
     /// The future returned from this function should be executed in the runtime. It will block execution.
-    pub async fn handle_next_event<E: Future<Output = &'static str> + Unpin>(
+    pub async fn handle_next_event<E: Future<Output = ResponseReason> + Unpin>(
         &mut self,
         exit: &mut E,
     ) -> Option<Result<Response>> {
@@ -357,12 +379,12 @@ impl PreLaunchNode {
                     error!("Failed to send startup requests on reconnect: {}", err);
                     return Some(Response {
                         success: false,
-                        reason: "Failed to send startup requests on reconnection",
+                        reason: ResponseReason::FailedToSendStartupRequests,
                     });
                 }
                 Some(Response {
                     success: true,
-                    reason: "Sent startup requests on reconnection",
+                    reason: ResponseReason::SentStartupRequests,
                 })
             }
             LocalEvent::CoordinatedShutdown(_) => None,
@@ -450,13 +472,13 @@ impl PreLaunchNode {
             error!("Received invalid item: {:?}", e);
             return Some(Response {
                 success: false,
-                reason: "Received Invalid Db Items",
+                reason: ResponseReason::ReceivedInvalidDbItems,
             });
         }
 
         Some(Response {
             success: true,
-            reason: "Received Db Items",
+            reason: ResponseReason::ReceivedDbItems,
         })
     }
 
@@ -473,13 +495,13 @@ impl PreLaunchNode {
         if !self.shutdown_group.is_empty() {
             return Some(Response {
                 success: true,
-                reason: "Shutdown pending",
+                reason: ResponseReason::ShutdownPending,
             });
         }
 
         Some(Response {
             success: true,
-            reason: "Shutdown",
+            reason: ResponseReason::Shutdown,
         })
     }
 
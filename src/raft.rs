@@ -1,12 +1,17 @@
+use crate::clock::Clock;
+use crate::constants::{RAFT_FOLLOWER_LAG_LIMIT, RAFT_STALLED_TICK_LIMIT};
 use crate::db_utils::{SimpleDb, SimpleDbError};
 use crate::raft_store::{self, RaftStore};
 use crate::utils::MpscTracingSender;
 use raft::prelude::*;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::mpsc;
-use tokio::time::{timeout_at, Instant};
-use tracing::{error, info, trace};
+use tokio::time::Instant;
+use tracing::{error, info, trace, warn};
 
 pub type RaftData = Vec<u8>;
 pub type CommitSender = MpscTracingSender<Vec<RaftCommit>>;
@@ -16,6 +21,7 @@ pub type RaftCmdReceiver = mpsc::UnboundedReceiver<RaftCmd>;
 pub type RaftMsgSender = MpscTracingSender<Message>;
 pub type RaftMsgReceiver = mpsc::Receiver<Message>;
 pub type CommittedIndex = raft_store::CommittedIndex;
+pub type SharedRaftMetrics = Arc<Mutex<RaftMetrics>>;
 
 /// Raft Commit entry
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
@@ -56,11 +62,69 @@ impl Default for RaftCommitData {
     }
 }
 
+/// Health of the raft consensus as seen by this peer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RaftHealth {
+    /// No leader known: election in progress or peer partitioned
+    #[default]
+    NoLeader,
+    /// Leader known and entries committing
+    Healthy,
+    /// Leader with at least one follower lagging behind the commit index
+    FollowerLagging,
+    /// Proposals pending but nothing committed for too many ticks
+    Stalled,
+}
+
+/// Metrics of the raft loop, updated as the loop processes events
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RaftMetrics {
+    /// The raft peer id.
+    pub peer_id: u64,
+    /// Current term.
+    pub term: u64,
+    /// Current leader id (0 if none).
+    pub leader_id: u64,
+    /// Number of term changes observed.
+    pub term_changes: u64,
+    /// Number of leader changes observed.
+    pub leader_changes: u64,
+    /// Last committed index.
+    pub committed_index: u64,
+    /// Number of proposals made by this peer.
+    pub proposals: u64,
+    /// Number of proposals made by this peer that were committed.
+    pub proposals_committed: u64,
+    /// Number of proposals waiting for a leader or a commit.
+    pub pending_proposals: usize,
+    /// Latency between proposal and commit of the last committed proposal.
+    pub last_proposal_latency_ms: u64,
+    /// Maximum latency between proposal and commit.
+    pub max_proposal_latency_ms: u64,
+    /// Commit index lag for each follower (only populated on leader).
+    pub follower_commit_lag: BTreeMap<u64, u64>,
+    /// Number of snapshots created by this peer.
+    pub snapshots_created: u64,
+    /// Number of snapshots received and applied from the leader.
+    pub snapshots_applied: u64,
+    /// Index of the last snapshot.
+    pub last_snapshot_idx: u64,
+    /// Ticks elapsed since the commit index last changed.
+    pub ticks_since_commit: u64,
+    /// Health derived from the metrics.
+    pub health: RaftHealth,
+    /// Whether this peer is a non-voting standby.
+    pub is_learner: bool,
+    /// Number of failovers to a standby proposed by this peer.
+    pub failovers: u64,
+}
+
 /// Channels needed to interact with the running raft instance.
 pub struct RaftNodeChannels {
     pub msg_out_rx: RaftMsgReceiver,
     pub cmd_tx: RaftCmdSender,
     pub committed_rx: CommitReceiver,
+    pub metrics: SharedRaftMetrics,
 }
 
 /// Fields necessary for launching a Raft loop.
@@ -77,6 +141,12 @@ pub struct RaftConfig {
     msg_out_tx: RaftMsgSender,
     /// Tick timeout duration.
     tick_timeout_duration: Duration,
+    /// Clock used for tick timeouts.
+    clock: Clock,
+    /// Metrics updated by the raft loop.
+    metrics: SharedRaftMetrics,
+    /// Ticks without hearing from a voter before the leader fails over to a standby.
+    failover_ticks: Option<usize>,
 }
 
 /// Wrapper for raft Messages enabling Serialize/Deserialize
@@ -113,6 +183,9 @@ pub enum RaftCmd {
         backup: bool,
     },
     Raft(RaftMessageWrapper),
+    Failover {
+        replaced_id: u64,
+    },
     Close,
 }
 
@@ -131,6 +204,8 @@ pub struct RaftNode {
     tick_timeout_duration: Duration,
     /// Tick timeout expiration time.
     tick_timeout_at: Instant,
+    /// Clock used for tick timeouts.
+    clock: Clock,
     /// Committed entries/group count.
     committed_entries_and_groups_count: (usize, usize),
     /// Outgoing messages/group count.
@@ -141,6 +216,16 @@ pub struct RaftNode {
     total_tick_count: usize,
     /// Last snapshot index, and whether it need compacting.
     previous_snapshot_idx: (u64, bool),
+    /// Time of the proposals made by this peer, waiting for commit.
+    proposal_times: HashMap<RaftData, Instant>,
+    /// Metrics updated by the raft loop.
+    metrics: SharedRaftMetrics,
+    /// Ticks without hearing from a voter before the leader fails over to a standby.
+    failover_ticks: Option<usize>,
+    /// Tick count when each peer was last heard from.
+    peer_last_heard: HashMap<u64, usize>,
+    /// Whether a failover waits for its membership changes to commit.
+    failover_pending: bool,
     // Context already waiting for committing
 }
 
@@ -151,7 +236,7 @@ impl RaftNode {
     ///
     /// * `raft_Config` - RaftConfig object containing tick_timeout_duration and cfg
     pub fn new(raft_config: RaftConfig) -> Self {
-        let tick_timeout_at = Instant::now() + raft_config.tick_timeout_duration;
+        let tick_timeout_at = raft_config.clock.now() + raft_config.tick_timeout_duration;
 
         let node = {
             let (storage, cfg) = Self::storage_and_config(raft_config.cfg, raft_config.raft_db);
@@ -173,11 +258,17 @@ impl RaftNode {
             msg_out_tx: raft_config.msg_out_tx,
             tick_timeout_duration: raft_config.tick_timeout_duration,
             tick_timeout_at,
+            clock: raft_config.clock,
             committed_entries_and_groups_count: (0, 0),
             outgoing_msgs_and_groups_count: (0, 0),
             incoming_msgs_count: 0,
             total_tick_count: 0,
             previous_snapshot_idx: (0, false),
+            proposal_times: Default::default(),
+            metrics: raft_config.metrics,
+            failover_ticks: raft_config.failover_ticks,
+            peer_last_heard: Default::default(),
+            failover_pending: false,
         }
     }
 
@@ -187,14 +278,23 @@ impl RaftNode {
     ///
     /// * `node_cfg` - Config object
     /// * `tick_timeout_duration` - Duration object holding the tick timeout duration
+    /// * `failover_ticks` - Ticks without hearing from a voter before failing over, never if None
+    /// * `clock` - Clock used for tick timeouts
     pub fn init_config(
         node_cfg: Config,
         raft_db: SimpleDb,
         tick_timeout_duration: Duration,
+        failover_ticks: Option<usize>,
+        clock: Clock,
     ) -> (RaftConfig, RaftNodeChannels) {
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
         let (committed_tx, committed_rx) = mpsc::channel(100);
         let (msg_out_tx, msg_out_rx) = mpsc::channel(100);
+        let metrics: SharedRaftMetrics = Arc::new(Mutex::new(RaftMetrics {
+            peer_id: node_cfg.id,
+            is_learner: node_cfg.learners.contains(&node_cfg.id),
+            ..Default::default()
+        }));
 
         (
             RaftConfig {
@@ -203,12 +303,16 @@ impl RaftNode {
                 committed_tx: committed_tx.into(),
                 msg_out_tx: msg_out_tx.into(),
                 tick_timeout_duration,
+                clock,
                 raft_db,
+                metrics: metrics.clone(),
+                failover_ticks,
             },
             RaftNodeChannels {
                 msg_out_rx,
                 cmd_tx,
                 committed_rx,
+                metrics,
             },
         )
     }
@@ -244,7 +348,11 @@ impl RaftNode {
 
     /// Async RAFT loop processing inputs and populating output channels.
     async fn next_event(&mut self) -> Option<()> {
-        match timeout_at(self.tick_timeout_at, self.cmd_rx.recv()).await {
+        match self
+            .clock
+            .timeout_at(self.tick_timeout_at, self.cmd_rx.recv())
+            .await
+        {
             Ok(Some(RaftCmd::Propose { data, context })) => {
                 trace!("next_event Propose({}, {:?})", self.node.raft.id, data);
                 self.propose_data_backlog
@@ -256,6 +364,9 @@ impl RaftNode {
                 let (prev_idx, need_compact) = self.previous_snapshot_idx;
                 if idx != prev_idx {
                     store.create_snapshot(idx, None, None, data).unwrap();
+                    let mut metrics = self.metrics.lock().unwrap();
+                    metrics.snapshots_created += 1;
+                    metrics.last_snapshot_idx = idx;
                 }
                 if need_compact {
                     store.compact(prev_idx).unwrap();
@@ -271,7 +382,21 @@ impl RaftNode {
                 trace!("next_event receive message({}, {:?})", self.node.raft.id, m);
                 self.incoming_msgs_count += 1;
                 let from = m.get_from();
-                if m.get_msg_type() == MessageType::MsgPropose {
+                self.peer_last_heard.insert(from, self.total_tick_count);
+                let is_propose = m.get_msg_type() == MessageType::MsgPropose;
+                let is_conf_change = m
+                    .get_entries()
+                    .iter()
+                    .any(|e| e.get_entry_type() == EntryType::EntryConfChange);
+                if is_propose && is_conf_change {
+                    // Forwarded membership change: only proposed by the leader
+                    if let Err(e) = self.node.step(m) {
+                        warn!(
+                            peer_id = self.node.raft.id,
+                            "Raft conf change dropped: {:?}", e
+                        );
+                    }
+                } else if is_propose {
                     for mut e in m.take_entries().into_iter() {
                         let data = e.take_data();
                         let context = e.take_context();
@@ -283,9 +408,14 @@ impl RaftNode {
             }
             Err(_) => {
                 // Timeout
-                self.tick_timeout_at = Instant::now() + self.tick_timeout_duration;
+                self.tick_timeout_at = self.clock.now() + self.tick_timeout_duration;
                 self.total_tick_count += 1;
+                self.metrics.lock().unwrap().ticks_since_commit += 1;
                 self.node.tick();
+                self.check_failover();
+            }
+            Ok(Some(RaftCmd::Failover { replaced_id })) => {
+                self.propose_failover(replaced_id);
             }
             Ok(Some(RaftCmd::Close)) | Ok(None) => {
                 // Disconnected
@@ -306,18 +436,106 @@ impl RaftNode {
 
         if self.node.raft.leader_id != raft::INVALID_ID {
             let is_leader = self.node.raft.leader_id == self.node.raft.id;
+            let is_learner = self.node.raft.is_learner;
             for (data, context, from) in self.propose_data_backlog.drain(..) {
-                let can_propose = is_leader || from == self.node.raft.id;
+                // A standby mirrors the log without proposing its own items
+                let own = from == self.node.raft.id;
+                let can_propose = (is_leader || own) && !(own && is_learner);
                 if can_propose && !self.node.get_store().is_context_in_log(&context) {
+                    if from == self.node.raft.id {
+                        self.proposal_times
+                            .insert(context.clone(), self.clock.now());
+                        self.metrics.lock().unwrap().proposals += 1;
+                    }
                     self.node.propose(context, data).unwrap();
                 }
             }
         }
 
         self.process_ready().await;
+        self.update_metrics();
         Some(())
     }
 
+    /// Update the metrics from the current raft state, logging consensus changes.
+    fn update_metrics(&mut self) {
+        let raft = &self.node.raft;
+        let mut metrics = self.metrics.lock().unwrap();
+
+        if metrics.term != raft.term {
+            info!(
+                peer_id = raft.id,
+                term = raft.term,
+                previous_term = metrics.term,
+                "Raft term changed"
+            );
+            metrics.term_changes += 1;
+            metrics.term = raft.term;
+        }
+
+        if metrics.leader_id != raft.leader_id {
+            info!(
+                peer_id = raft.id,
+                leader_id = raft.leader_id,
+                previous_leader_id = metrics.leader_id,
+                term = raft.term,
+                "Raft leader changed"
+            );
+            // Proposals in flight may be dropped by the previous leader, if any
+            if metrics.leader_id != raft::INVALID_ID {
+                self.proposal_times.clear();
+            }
+            metrics.leader_changes += 1;
+            metrics.leader_id = raft.leader_id;
+            self.failover_pending = false;
+        }
+        metrics.is_learner = raft.is_learner;
+
+        let committed_index = raft.raft_log.committed;
+        if metrics.committed_index != committed_index {
+            metrics.committed_index = committed_index;
+            metrics.ticks_since_commit = 0;
+        }
+
+        let is_leader = raft.leader_id == raft.id;
+        metrics.follower_commit_lag = if is_leader {
+            raft.prs()
+                .iter()
+                .filter(|(id, _)| **id != raft.id)
+                .map(|(id, pr)| (*id, committed_index.saturating_sub(pr.matched)))
+                .collect()
+        } else {
+            Default::default()
+        };
+
+        metrics.pending_proposals = self.propose_data_backlog.len() + self.proposal_times.len();
+        let previous_health = metrics.health;
+        metrics.health = if raft.leader_id == raft::INVALID_ID {
+            RaftHealth::NoLeader
+        } else if metrics.pending_proposals > 0
+            && metrics.ticks_since_commit > RAFT_STALLED_TICK_LIMIT
+        {
+            RaftHealth::Stalled
+        } else if metrics
+            .follower_commit_lag
+            .values()
+            .any(|lag| *lag > RAFT_FOLLOWER_LAG_LIMIT)
+        {
+            RaftHealth::FollowerLagging
+        } else {
+            RaftHealth::Healthy
+        };
+
+        if previous_health != metrics.health {
+            warn!(
+                peer_id = raft.id,
+                health = ?metrics.health,
+                previous_health = ?previous_health,
+                "Raft health changed"
+            );
+        }
+    }
+
     ///If current node has_ready is true then it returns. Otherwise, it sends messages to peers and updates ready.
     ///
     /// Advance notifies the node that the application has applied and saved progress in the last Ready results.
@@ -368,6 +586,10 @@ impl RaftNode {
 
             let snap_idx = ready.snapshot().get_metadata().index;
             self.previous_snapshot_idx = (snap_idx, false);
+
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.snapshots_applied += 1;
+            metrics.last_snapshot_idx = snap_idx;
         }
 
         if !ready.entries().is_empty() {
@@ -405,6 +627,12 @@ impl RaftNode {
         }
 
         if let Some(mut committed_entries) = ready.committed_entries.take() {
+            for entry in committed_entries
+                .iter()
+                .filter(|entry| entry.get_entry_type() == EntryType::EntryConfChange)
+            {
+                self.apply_conf_change(entry);
+            }
             committed.extend(
                 committed_entries
                     .drain(..)
@@ -422,6 +650,7 @@ impl RaftNode {
             );
         }
 
+        self.record_proposal_latencies(&committed);
         if !committed.is_empty() {
             self.committed_entries_and_groups_count.1 += 1;
             self.committed_entries_and_groups_count.0 += committed.len();
@@ -429,10 +658,158 @@ impl RaftNode {
         }
     }
 
+    /// Record latency for committed entries proposed by this peer
+    ///
+    /// ### Arguments
+    ///
+    /// * `committed` - Entries committed.
+    fn record_proposal_latencies(&mut self, committed: &[RaftCommit]) {
+        let now = self.clock.now();
+        let mut metrics = self.metrics.lock().unwrap();
+        for commit in committed {
+            if let RaftCommitData::Proposed(_, context) = &commit.data {
+                if let Some(proposed_at) = self.proposal_times.remove(context) {
+                    let latency = (now - proposed_at).as_millis() as u64;
+                    metrics.proposals_committed += 1;
+                    metrics.last_proposal_latency_ms = latency;
+                    metrics.max_proposal_latency_ms =
+                        std::cmp::max(metrics.max_proposal_latency_ms, latency);
+                }
+            }
+        }
+    }
+
+    /// Propose to replace a voting member with a standby learner
+    ///
+    /// The learner is promoted first, and the replaced member removed once the promotion
+    /// is committed, so the group never runs with fewer voters.
+    ///
+    /// ### Arguments
+    ///
+    /// * `replaced_id` - Peer id of the voting member to replace
+    fn propose_failover(&mut self, replaced_id: u64) {
+        let prs = self.node.raft.prs();
+        let learner_id = prs
+            .learner_ids()
+            .iter()
+            .max_by_key(|id| self.peer_last_heard.get(id))
+            .copied();
+        let learner_id = match learner_id {
+            Some(id) if prs.voter_ids().contains(&replaced_id) => id,
+            _ => {
+                warn!(
+                    peer_id = self.node.raft.id,
+                    replaced_id, "Raft failover ignored: no standby or not a voter"
+                );
+                return;
+            }
+        };
+
+        let mut cc = ConfChange::new();
+        cc.set_change_type(ConfChangeType::AddNode);
+        cc.set_node_id(learner_id);
+        cc.set_context(replaced_id.to_be_bytes().to_vec());
+        match self.node.propose_conf_change(Vec::new(), cc) {
+            Ok(()) => {
+                warn!(
+                    peer_id = self.node.raft.id,
+                    replaced_id, learner_id, "Raft failover proposed"
+                );
+                self.failover_pending = true;
+                self.metrics.lock().unwrap().failovers += 1;
+            }
+            Err(e) => error!("Raft failover not proposed: {:?}", e),
+        }
+    }
+
+    /// Fail over to a standby when the leader has not heard from a voter for too long
+    fn check_failover(&mut self) {
+        let failover_ticks = match self.failover_ticks {
+            Some(ticks) => ticks,
+            None => return,
+        };
+        let raft = &self.node.raft;
+        if raft.leader_id != raft.id || self.failover_pending {
+            return;
+        }
+
+        let now = self.total_tick_count;
+        let silent = |id: &u64| {
+            let last_heard = self.peer_last_heard.get(id).copied().unwrap_or_default();
+            now - last_heard > failover_ticks
+        };
+        let standby_alive = raft.prs().learner_ids().iter().any(|id| !silent(id));
+        let failed_id = raft
+            .prs()
+            .voter_ids()
+            .iter()
+            .copied()
+            .find(|id| *id != raft.id && silent(id));
+
+        if let (true, Some(failed_id)) = (standby_alive, failed_id) {
+            warn!(
+                peer_id = raft.id,
+                failed_id, "Raft voter silent: failing over"
+            );
+            self.propose_failover(failed_id);
+        }
+    }
+
+    /// Apply a committed membership change, completing a failover once the standby is promoted
+    ///
+    /// ### Arguments
+    ///
+    /// * `entry` - Committed conf change entry
+    fn apply_conf_change(&mut self, entry: &Entry) {
+        let cc: ConfChange = match protobuf::Message::parse_from_bytes(entry.get_data()) {
+            Ok(cc) => cc,
+            Err(e) => {
+                error!("Invalid raft conf change: {:?}", e);
+                return;
+            }
+        };
+        let cs = match self.node.apply_conf_change(&cc) {
+            Ok(cs) => cs,
+            Err(e) => {
+                error!("Raft conf change not applied: {:?}", e);
+                return;
+            }
+        };
+        info!(
+            peer_id = self.node.raft.id,
+            node_id = cc.get_node_id(),
+            change = ?cc.get_change_type(),
+            voters = ?cs.get_nodes(),
+            learners = ?cs.get_learners(),
+            "Raft membership changed"
+        );
+        if let Err(e) = self.node.mut_store().set_conf_state(cs) {
+            error!("Raft membership not persisted: {:?}", e);
+        }
+
+        match cc.get_change_type() {
+            ConfChangeType::AddNode => {
+                let is_leader = self.node.raft.leader_id == self.node.raft.id;
+                let replaced_id = <[u8; 8]>::try_from(cc.get_context()).map(u64::from_be_bytes);
+                if let (true, Ok(replaced_id)) = (is_leader, replaced_id) {
+                    let mut cc = ConfChange::new();
+                    cc.set_change_type(ConfChangeType::RemoveNode);
+                    cc.set_node_id(replaced_id);
+                    if let Err(e) = self.node.propose_conf_change(Vec::new(), cc) {
+                        error!("Raft failover removal not proposed: {:?}", e);
+                    }
+                }
+            }
+            ConfChangeType::RemoveNode => self.failover_pending = false,
+            _ => (),
+        }
+    }
+
     /// Create storage and config to use for new node
     fn storage_and_config(mut cfg: Config, db: SimpleDb) -> (RaftStore, Config) {
         let mut cs = ConfState::new();
         cs.set_nodes(std::mem::take(&mut cfg.peers));
+        cs.set_learners(std::mem::take(&mut cfg.learners));
 
         let storage = RaftStore::new(db).load_in_memory_or_default(cs).unwrap();
 
@@ -467,6 +844,7 @@ mod tests {
         pub committed_rx: CommitReceiver,
         pub last_committed: Option<RaftCommit>,
         pub last_proposed_id: u8,
+        pub metrics: SharedRaftMetrics,
     }
 
     struct RaftHooks {
@@ -510,6 +888,113 @@ mod tests {
         close_nodes_loops(test_nodes, join_handles).await;
     }
 
+    // Verify term, leader and proposal metrics are reported consistently by peers.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_metrics_after_proposal_3_nodes() {
+        let _ = tracing_log_try_init();
+        let (peer_indexes, mut test_nodes) = test_configs(3);
+        let peer_msg_lost = Arc::new(Mutex::new(HashSet::new()));
+        let (join_handles, _) = spawn_nodes_loops(&peer_indexes, &mut test_nodes, &peer_msg_lost);
+        all_recv_initial_snapshot(&mut test_nodes).await;
+
+        all_recv_send_proposed_data(&mut test_nodes, 0, vec![17]).await;
+        let metrics: Vec<RaftMetrics> = test_nodes
+            .iter()
+            .map(|node| node.metrics.lock().unwrap().clone())
+            .collect();
+
+        close_nodes_loops(test_nodes, join_handles).await;
+
+        let (leader_id, term) = (metrics[0].leader_id, metrics[0].term);
+        assert_ne!(leader_id, raft::INVALID_ID);
+        assert!(metrics.iter().all(|m| m.leader_id == leader_id));
+        assert!(metrics.iter().all(|m| m.term == term && m.term_changes > 0));
+        assert_eq!(
+            metrics.iter().map(|m| m.peer_id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            (metrics[0].proposals, metrics[0].proposals_committed),
+            (1, 1)
+        );
+    }
+
+    // Verify raft only ticks and elects a leader once the manual clock is advanced.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_manual_clock_ticks_1_node() {
+        let _ = tracing_log_try_init();
+        let clock = Clock::manual();
+        let (peer_indexes, mut test_nodes) = test_configs(1);
+        for node in &mut test_nodes {
+            node.raft_config.as_mut().unwrap().clock = clock.clone();
+        }
+        let peer_msg_lost = Arc::new(Mutex::new(HashSet::new()));
+        let (join_handles, _) = spawn_nodes_loops(&peer_indexes, &mut test_nodes, &peer_msg_lost);
+        all_recv_initial_snapshot(&mut test_nodes).await;
+
+        time::sleep(Duration::from_millis(20)).await;
+        let leader_before = test_nodes[0].metrics.lock().unwrap().leader_id;
+
+        let manual = clock.as_manual().unwrap();
+        let mut leader_after = raft::INVALID_ID;
+        for _ in 0..100 {
+            manual.advance(Duration::from_millis(1));
+            time::sleep(Duration::from_millis(1)).await;
+            leader_after = test_nodes[0].metrics.lock().unwrap().leader_id;
+            if leader_after != raft::INVALID_ID {
+                break;
+            }
+        }
+        all_recv_send_proposed_data(&mut test_nodes, 0, vec![17]).await;
+
+        close_nodes_loops(test_nodes, join_handles).await;
+
+        assert_eq!((leader_before, leader_after), (raft::INVALID_ID, 1));
+    }
+
+    // Setup a peer group with a standby learner mirroring the log.
+    // Verify the standby replaces a failed voter on failover and can then propose.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_failover_to_learner_3_nodes() {
+        let _ = tracing_log_try_init();
+        let (peer_indexes, mut test_nodes) = test_configs_with_learners(3, 1);
+        let peer_msg_lost = Arc::new(Mutex::new(HashSet::new()));
+        let (join_handles, _) = spawn_nodes_loops(&peer_indexes, &mut test_nodes, &peer_msg_lost);
+        all_recv_initial_snapshot(&mut test_nodes).await;
+
+        info!("Standby mirrors the log");
+        all_recv_send_proposed_data(&mut test_nodes, 0, vec![17]).await;
+        let learner_before = test_nodes[3].metrics.lock().unwrap().is_learner;
+
+        info!("Node 3 fails and is replaced by the standby");
+        peer_msg_lost.lock().await.insert(3);
+        let cmd = RaftCmd::Failover { replaced_id: 3 };
+        test_nodes[0].cmd_tx.send(cmd).unwrap();
+        let mut learner_after = true;
+        for _ in 0..500 {
+            time::sleep(Duration::from_millis(10)).await;
+            learner_after = test_nodes[3].metrics.lock().unwrap().is_learner;
+            if !learner_after {
+                break;
+            }
+        }
+
+        info!("Promoted standby proposes");
+        send_proposal(&mut test_nodes[3], vec![33]).await;
+        let mut commits = Vec::new();
+        for idx in [0, 1, 3] {
+            commits.push(one_recv_commited(&mut test_nodes[idx], 0, 1).await);
+        }
+        let failovers = test_nodes[0].metrics.lock().unwrap().failovers;
+
+        close_nodes_loops(test_nodes, join_handles).await;
+
+        assert!(learner_before);
+        assert!(!learner_after);
+        assert_eq!(failovers, 1);
+        assert_eq!(commits, vec![vec![vec![33]]; 3]);
+    }
+
     #[tokio::test(flavor = "current_thread")]
     async fn test_snapshot_1_node() {
         test_snapshot(1).await;
@@ -1114,15 +1599,37 @@ mod tests {
         (peer_to_indexes, test_nodes)
     }
 
+    fn test_configs_with_learners(
+        num_peers: u64,
+        num_learners: u64,
+    ) -> (HashMap<u64, usize>, Vec<TestNode>) {
+        let peers: Vec<u64> = (1..num_peers + 1).collect();
+        let learners: Vec<u64> = (num_peers + 1..num_peers + num_learners + 1).collect();
+        let all: Vec<u64> = peers.iter().chain(&learners).copied().collect();
+        let test_nodes: Vec<_> = all
+            .iter()
+            .map(|peer_id| test_config_with_learners(*peer_id, &peers, &learners))
+            .collect();
+        let peer_to_indexes = all.iter().enumerate().map(|(idx, id)| (*id, idx)).collect();
+        (peer_to_indexes, test_nodes)
+    }
+
     fn test_config(peer_id: u64, peers: &[u64]) -> TestNode {
+        test_config_with_learners(peer_id, peers, &[])
+    }
+
+    fn test_config_with_learners(peer_id: u64, peers: &[u64], learners: &[u64]) -> TestNode {
         let (raft_config, node_channels) = RaftNode::init_config(
             Config {
                 id: peer_id,
                 peers: peers.to_owned(),
+                learners: learners.to_owned(),
                 ..Default::default()
             },
             SimpleDb::new_in_memory(&[], None).unwrap(),
             Duration::from_millis(1),
+            None,
+            Clock::default(),
         );
 
         TestNode {
@@ -1133,6 +1640,7 @@ mod tests {
             msg_out_rx: Some(node_channels.msg_out_rx),
             last_committed: None,
             last_proposed_id: 0,
+            metrics: node_channels.metrics,
         }
     }
 }
@@ -11,8 +11,11 @@ use std::collections::HashMap;
 use tracing::{error, info};
 
 pub const HARDSTATE_KEY: &str = "HardStateKey";
+pub const CONF_STATE_KEY: &str = "ConfStateKey";
 pub const SNAPSHOT_DATA_KEY: &str = "SnaphotDataKey";
 pub const SNAPSHOT_META_KEY: &str = "SnaphotMetaKey";
+pub const SNAPSHOT_CHUNKS_KEY: &str = "SnaphotChunksKey";
+pub const SNAPSHOT_CHUNK_KEY: &str = "SnaphotChunkKey";
 pub const ENTRY_KEY: &str = "EntryKey";
 pub const LAST_ENTRY_KEY: &str = "LastEntryKey";
 
@@ -22,6 +25,16 @@ pub struct SnapMetadata {
     pub term: u64,
 }
 
+/// Maximum size of a persisted snapshot data chunk
+pub const SNAPSHOT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Chunks holding the data of the snapshot at index
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SnapChunks {
+    pub index: u64,
+    pub count: u64,
+}
+
 #[derive(Default, Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CommittedIndex {
     pub index: u64,
@@ -29,7 +42,8 @@ pub struct CommittedIndex {
 }
 
 pub struct RaftStore {
-    /// In memory storage used during normal operation
+    /// In memory storage used during normal operation.
+    /// Snapshot data is only kept in persistent storage.
     in_memory: MemStorage,
     /// Persistent storage used when restarting
     presistent: SimpleDb,
@@ -70,13 +84,29 @@ impl RaftStore {
         Ok(())
     }
 
+    /// Saves the membership after a change, used instead of the configured one on restart.
+    pub fn set_conf_state(&mut self, cs: ConfState) -> RaftResult<()> {
+        let bytes = cs.write_to_bytes()?;
+
+        self.in_memory.wl().set_conf_state(cs, None);
+        self.presistent
+            .put_cf(DB_COL_DEFAULT, CONF_STATE_KEY, &bytes)
+            .map_err(from_db_err)?;
+
+        Ok(())
+    }
+
     /// Overwrites the contents of this Storage object with those of the given snapshot.
-    pub fn apply_snapshot(&mut self, snapshot: Snapshot) -> RaftResult<()> {
-        self.in_memory.wl().apply_snapshot(snapshot.clone())?;
+    pub fn apply_snapshot(&mut self, mut snapshot: Snapshot) -> RaftResult<()> {
+        let data = snapshot.take_data();
         let index = snapshot.get_metadata().get_index();
+        self.in_memory.wl().apply_snapshot(snapshot.clone())?;
+
+        let chunks = put_persistent_snapshot_chunks(&mut self.presistent, index, &data)?;
+        drop(data);
 
         let mut batch = self.presistent.batch_writer();
-        set_persistent_snapshot(&mut batch, &snapshot)?;
+        set_persistent_snapshot(&self.presistent, &mut batch, &snapshot, chunks)?;
         set_last_persistent_entry(&mut batch, index)?;
         discard_persistent_entries_before_snapshot(
             &mut batch,
@@ -89,6 +119,8 @@ impl RaftStore {
     }
 
     /// Create and store snapshot
+    ///
+    /// The data is written in chunks to persistent storage and not retained in memory.
     pub fn create_snapshot(
         &mut self,
         idx: u64,
@@ -96,11 +128,17 @@ impl RaftStore {
         pending_membership_change: Option<ConfChange>,
         data: Vec<u8>,
     ) -> RaftResult<()> {
-        let mut wl = self.in_memory.wl();
-        let snapshot = wl.create_snapshot(idx, cs, pending_membership_change, data)?;
+        let snapshot = {
+            let mut wl = self.in_memory.wl();
+            wl.create_snapshot(idx, cs, pending_membership_change, Vec::new())?
+                .clone()
+        };
+
+        let chunks = put_persistent_snapshot_chunks(&mut self.presistent, idx, &data)?;
+        drop(data);
 
         let mut batch = self.presistent.batch_writer();
-        set_persistent_snapshot(&mut batch, snapshot)?;
+        set_persistent_snapshot(&self.presistent, &mut batch, &snapshot, chunks)?;
         discard_persistent_entries_before_snapshot(
             &mut batch,
             &mut self.persistent_first_entry,
@@ -153,6 +191,7 @@ impl RaftStore {
     pub fn load_in_memory_or_default(mut self, init_cs: ConfState) -> RaftResult<Self> {
         let in_memory = &self.in_memory;
         let presistent = &self.presistent;
+        let init_cs = get_persistent_conf_state(presistent)?.unwrap_or(init_cs);
         in_memory.wl().set_conf_state(init_cs.clone(), None);
 
         if let Some(snapshot) = get_persistent_snapshot(presistent, init_cs)? {
@@ -237,8 +276,19 @@ impl Storage for RaftStore {
     }
 
     /// Implements the Storage trait.
+    /// Snapshot data is loaded from persistent storage only when requested.
     fn snapshot(&self) -> RaftResult<Snapshot> {
-        self.in_memory.snapshot()
+        let mut snapshot = self.in_memory.snapshot()?;
+        if raft::is_empty_snap(&snapshot) {
+            return Ok(snapshot);
+        }
+
+        let index = snapshot.get_metadata().get_index();
+        match get_persistent_snapshot_data(&self.presistent, index)? {
+            Some(data) => snapshot.set_data(data),
+            None => return Err(StorageError::SnapshotTemporarilyUnavailable.into()),
+        }
+        Ok(snapshot)
     }
 }
 
@@ -319,20 +369,27 @@ fn set_last_persistent_entry(presistent: &mut SimpleDbWriteBatch, index: u64) ->
     Ok(())
 }
 
+/// Format snapshot data chunk key for db
+fn format_snapshot_chunk_key(index: u64, chunk: u64) -> String {
+    format!("{SNAPSHOT_CHUNK_KEY}_{index}_{chunk}")
+}
+
+/// Get the persisted snapshot without its data
 fn get_persistent_snapshot(
     presistent: &SimpleDb,
     init_cs: ConfState,
 ) -> RaftResult<Option<Snapshot>> {
-    let data = presistent
-        .get_cf(DB_COL_DEFAULT, SNAPSHOT_DATA_KEY)
-        .map_err(from_db_err)?;
     let meta = presistent
         .get_cf(DB_COL_DEFAULT, SNAPSHOT_META_KEY)
         .map_err(from_db_err)?;
 
-    if let (Some(data), Some(meta)) = (data, meta) {
+    if let Some(meta) = meta {
+        let metadata: SnapMetadata = deserialize(&meta).map_err(from_ser_err)?;
+        if !has_persistent_snapshot_data(presistent, metadata.index)? {
+            return Ok(None);
+        }
+
         let meta = {
-            let metadata: SnapMetadata = deserialize(&meta).map_err(from_ser_err)?;
             let mut meta = SnapshotMetadata::new();
             meta.set_conf_state(init_cs);
             meta.index = metadata.index;
@@ -341,7 +398,6 @@ fn get_persistent_snapshot(
         };
 
         let mut snapshot = Snapshot::new();
-        snapshot.set_data(data);
         snapshot.set_metadata(meta);
         Ok(Some(snapshot))
     } else {
@@ -349,19 +405,133 @@ fn get_persistent_snapshot(
     }
 }
 
+/// Get the chunks holding the persisted snapshot data, if written in chunks
+fn get_persistent_snapshot_chunks(presistent: &SimpleDb) -> RaftResult<Option<SnapChunks>> {
+    if let Some(bytes) = presistent
+        .get_cf(DB_COL_DEFAULT, SNAPSHOT_CHUNKS_KEY)
+        .map_err(from_db_err)?
+    {
+        Ok(Some(deserialize(&bytes).map_err(from_ser_err)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Whether data is persisted for the snapshot at the given index
+fn has_persistent_snapshot_data(presistent: &SimpleDb, index: u64) -> RaftResult<bool> {
+    match get_persistent_snapshot_chunks(presistent)? {
+        Some(chunks) => Ok(chunks.index == index),
+        None => Ok(presistent
+            .get_cf(DB_COL_DEFAULT, SNAPSHOT_DATA_KEY)
+            .map_err(from_db_err)?
+            .is_some()),
+    }
+}
+
+/// Reassemble the persisted data of the snapshot at the given index.
+/// Falls back to the single value written before snapshots were chunked.
+///
+/// ### Arguments
+///
+/// * `presistent` - Persistent storage
+/// * `index`      - Index of the snapshot
+pub fn get_persistent_snapshot_data(
+    presistent: &SimpleDb,
+    index: u64,
+) -> RaftResult<Option<Vec<u8>>> {
+    let chunks = match get_persistent_snapshot_chunks(presistent)? {
+        Some(chunks) => chunks,
+        None => {
+            return presistent
+                .get_cf(DB_COL_DEFAULT, SNAPSHOT_DATA_KEY)
+                .map_err(|e| from_db_err(e).into())
+        }
+    };
+    if chunks.index != index {
+        return Ok(None);
+    }
+
+    let mut data = Vec::new();
+    for chunk in 0..chunks.count {
+        let key = format_snapshot_chunk_key(index, chunk);
+        match presistent
+            .get_cf(DB_COL_DEFAULT, key)
+            .map_err(from_db_err)?
+        {
+            Some(bytes) => data.extend_from_slice(&bytes),
+            None => {
+                error!("Snapshot chunk unavailable {} for idx={}", chunk, index);
+                return Err(StorageError::Unavailable.into());
+            }
+        }
+    }
+    Ok(Some(data))
+}
+
+/// Write the snapshot data chunk by chunk, returning the chunks written.
+/// The chunks are only used once the snapshot metadata is written.
+///
+/// ### Arguments
+///
+/// * `presistent` - Persistent storage
+/// * `index`      - Index of the snapshot
+/// * `data`       - Snapshot data
+fn put_persistent_snapshot_chunks(
+    presistent: &mut SimpleDb,
+    index: u64,
+    data: &[u8],
+) -> RaftResult<SnapChunks> {
+    let mut count = 0;
+    for bytes in data.chunks(SNAPSHOT_CHUNK_SIZE) {
+        let key = format_snapshot_chunk_key(index, count);
+        presistent
+            .put_cf(DB_COL_DEFAULT, key, bytes)
+            .map_err(from_db_err)?;
+        count += 1;
+    }
+    Ok(SnapChunks { index, count })
+}
+
+/// Add the snapshot metadata and its chunks to the batch.
+/// The chunks of the previous snapshot are discarded in the same batch.
+///
+/// ### Arguments
+///
+/// * `presistent` - Persistent storage
+/// * `batch`      - Batch completing the snapshot
+/// * `snapshot`   - Snapshot metadata
+/// * `chunks`     - Chunks holding the snapshot data
 fn set_persistent_snapshot(
-    presistent: &mut SimpleDbWriteBatch,
+    presistent: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
     snapshot: &Snapshot,
+    chunks: SnapChunks,
 ) -> RaftResult<()> {
-    let data = snapshot.get_data();
+    if let Some(previous) = get_persistent_snapshot_chunks(presistent)? {
+        // Chunks overwritten by the new snapshot are kept
+        let first_stale = if previous.index == chunks.index {
+            chunks.count
+        } else {
+            0
+        };
+        for chunk in first_stale..previous.count {
+            batch.delete_cf(
+                DB_COL_DEFAULT,
+                format_snapshot_chunk_key(previous.index, chunk),
+            );
+        }
+    }
+
     let meta = snapshot.get_metadata();
     let meta = serialize(&SnapMetadata {
         index: meta.index,
         term: meta.term,
     })
     .map_err(from_ser_err)?;
-    presistent.put_cf(DB_COL_DEFAULT, SNAPSHOT_DATA_KEY, data);
-    presistent.put_cf(DB_COL_DEFAULT, SNAPSHOT_META_KEY, meta);
+    let chunks = serialize(&chunks).map_err(from_ser_err)?;
+    batch.delete_cf(DB_COL_DEFAULT, SNAPSHOT_DATA_KEY);
+    batch.put_cf(DB_COL_DEFAULT, SNAPSHOT_CHUNKS_KEY, chunks);
+    batch.put_cf(DB_COL_DEFAULT, SNAPSHOT_META_KEY, meta);
     Ok(())
 }
 
@@ -383,6 +553,17 @@ fn set_persistent_hardstate(presistent: &mut SimpleDb, bytes: &[u8]) -> RaftResu
     Ok(())
 }
 
+fn get_persistent_conf_state(presistent: &SimpleDb) -> RaftResult<Option<ConfState>> {
+    if let Some(bytes) = presistent
+        .get_cf(DB_COL_DEFAULT, CONF_STATE_KEY)
+        .map_err(from_db_err)?
+    {
+        Ok(Some(protobuf::Message::parse_from_bytes(&bytes)?))
+    } else {
+        Ok(None)
+    }
+}
+
 pub fn get_presistent_committed(presistent: &SimpleDb) -> RaftResult<Option<CommittedIndex>> {
     if let Some(v) = get_persistent_hardstate(presistent)? {
         return Ok(Some(CommittedIndex {
@@ -404,3 +585,76 @@ pub fn get_presistent_committed(presistent: &SimpleDb) -> RaftResult<Option<Comm
 
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks snapshot data is persisted in chunks and only loaded on request
+    fn create_snapshot_persists_data_in_chunks() {
+        //Arrange
+        let mut cs = ConfState::new();
+        cs.set_nodes(vec![1]);
+        let db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let mut store = RaftStore::new(db)
+            .load_in_memory_or_default(cs.clone())
+            .unwrap();
+        let entries: Vec<Entry> = (1..4)
+            .map(|index| {
+                let mut e = Entry::new();
+                e.set_index(index);
+                e.set_term(1);
+                e
+            })
+            .collect();
+        store.append(&entries).unwrap();
+        let data: Vec<u8> = (0..SNAPSHOT_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+
+        //Act
+        store.create_snapshot(2, None, None, vec![1; 20]).unwrap();
+        store.create_snapshot(3, None, None, data.clone()).unwrap();
+        let in_memory_data_len = store.in_memory.snapshot().unwrap().get_data().len();
+        let snapshot = store.snapshot().unwrap();
+        let chunks = get_persistent_snapshot_chunks(&store.presistent).unwrap();
+        let stale_chunk = store
+            .presistent
+            .get_cf(DB_COL_DEFAULT, format_snapshot_chunk_key(2, 0))
+            .unwrap();
+        let reloaded = RaftStore::new(store.take_persistent())
+            .load_in_memory_or_default(cs)
+            .unwrap();
+
+        //Assert
+        assert_eq!(in_memory_data_len, 0);
+        assert_eq!(snapshot.get_metadata().get_index(), 3);
+        assert_eq!(snapshot.get_data(), &data[..]);
+        assert_eq!(chunks, Some(SnapChunks { index: 3, count: 3 }));
+        assert_eq!(stale_chunk, None);
+        assert_eq!(reloaded.snapshot().unwrap().get_data(), &data[..]);
+    }
+
+    #[test]
+    /// Checks a membership change is used instead of the configured membership on reload
+    fn set_conf_state_overrides_config_on_reload() {
+        //Arrange
+        let mut cs = ConfState::new();
+        cs.set_nodes(vec![1, 2]);
+        cs.set_learners(vec![3]);
+        let mut changed = ConfState::new();
+        changed.set_nodes(vec![1, 3]);
+        let db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let mut store = RaftStore::new(db)
+            .load_in_memory_or_default(cs.clone())
+            .unwrap();
+
+        //Act
+        store.set_conf_state(changed.clone()).unwrap();
+        let reloaded = RaftStore::new(store.take_persistent())
+            .load_in_memory_or_default(cs)
+            .unwrap();
+
+        //Assert
+        assert_eq!(reloaded.initial_state().unwrap().conf_state, changed);
+    }
+}
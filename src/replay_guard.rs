@@ -0,0 +1,225 @@
+//! Replay protection for protocol messages.
+//!
+//! Messages whose replay would trigger duplicate behavior, such as payment
+//! address requests and PoW submissions, carry a `ReplayTag`: a sequence number
+//! increasing with each message of the sender, and the time it was sent.
+//! Receivers keep a window of the sequence numbers accepted from each peer, and
+//! reject messages sent too long ago or too far in the future, and messages
+//! whose sequence number was already accepted or fell out of the window.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+
+/// Maximum difference in seconds between the send time of a message and its receipt
+pub const REPLAY_MAX_AGE_SECS: i64 = 120;
+
+/// Number of sequence numbers below the highest accepted that can still be accepted
+const REPLAY_WINDOW_LEN: u64 = 64;
+
+/// Sequence number and send time of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayTag {
+    pub seq: u64,
+    /// Send time, in seconds since the UNIX epoch
+    pub timestamp: i64,
+}
+
+/// Generator of the tags of the messages sent by a node
+///
+/// Sequence numbers start from the current time in microseconds, so they keep
+/// increasing across restarts of the node.
+#[derive(Debug, Clone)]
+pub struct ReplayTagger {
+    next_seq: u64,
+}
+
+impl Default for ReplayTagger {
+    fn default() -> Self {
+        let now = chrono::Utc::now();
+        let micros = now.timestamp() as u64 * 1_000_000 + now.timestamp_subsec_micros() as u64;
+        Self { next_seq: micros }
+    }
+}
+
+impl ReplayTagger {
+    /// Tag for the next message sent
+    pub fn next_tag(&mut self) -> ReplayTag {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        ReplayTag {
+            seq,
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Reason a tagged message is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Sent outside of the accepted time range
+    Expired,
+    /// Sequence number already accepted, or too old
+    Replayed,
+}
+
+impl ReplayError {
+    /// Reason used in the node response
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::Expired => "Expired message",
+            Self::Replayed => "Replayed message",
+        }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason())
+    }
+}
+
+/// Sequence numbers accepted from a peer
+#[derive(Debug, Clone, Copy)]
+struct PeerWindow {
+    /// Highest sequence number accepted
+    highest: u64,
+    /// Bit `i` is set if `highest - i` was accepted
+    seen: u64,
+    /// Send time of the last message accepted
+    last_timestamp: i64,
+}
+
+/// Replay windows of the peers a node received tagged messages from
+#[derive(Debug, Clone, Default)]
+pub struct ReplayWindows {
+    peers: BTreeMap<SocketAddr, PeerWindow>,
+}
+
+impl ReplayWindows {
+    /// Accept a tagged message from a peer, unless it is expired or replayed
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer` - Peer the message was received from.
+    /// * `tag`  - Tag of the message.
+    /// * `now`  - Current time, in seconds since the UNIX epoch.
+    pub fn check(
+        &mut self,
+        peer: SocketAddr,
+        tag: &ReplayTag,
+        now: i64,
+    ) -> Result<(), ReplayError> {
+        if (now - tag.timestamp).abs() > REPLAY_MAX_AGE_SECS {
+            return Err(ReplayError::Expired);
+        }
+
+        let window = match self.peers.get_mut(&peer) {
+            Some(window) => window,
+            None => {
+                // Expired windows are no longer needed: their messages are rejected as expired
+                self.peers
+                    .retain(|_, w| now - w.last_timestamp <= 2 * REPLAY_MAX_AGE_SECS);
+                self.peers.insert(
+                    peer,
+                    PeerWindow {
+                        highest: tag.seq,
+                        seen: 1,
+                        last_timestamp: tag.timestamp,
+                    },
+                );
+                return Ok(());
+            }
+        };
+
+        if tag.seq > window.highest {
+            let shift = tag.seq - window.highest;
+            window.seen = if shift < REPLAY_WINDOW_LEN {
+                (window.seen << shift) | 1
+            } else {
+                1
+            };
+            window.highest = tag.seq;
+        } else {
+            let offset = window.highest - tag.seq;
+            if offset >= REPLAY_WINDOW_LEN || window.seen & (1 << offset) != 0 {
+                return Err(ReplayError::Replayed);
+            }
+            window.seen |= 1 << offset;
+        }
+        window.last_timestamp = window.last_timestamp.max(tag.timestamp);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn tag(seq: u64) -> ReplayTag {
+        ReplayTag {
+            seq,
+            timestamp: NOW,
+        }
+    }
+
+    #[test]
+    /// Checks each sequence number is accepted once per peer, also out of order
+    fn replay_windows_reject_replayed() {
+        let peer = "127.0.0.1:12300".parse().unwrap();
+        let other_peer = "127.0.0.1:12301".parse().unwrap();
+        let mut windows = ReplayWindows::default();
+
+        let actual: Vec<_> = [
+            (peer, 100),
+            (peer, 102),
+            (peer, 101),
+            (peer, 102),
+            (other_peer, 102),
+        ]
+        .iter()
+        .map(|(p, seq)| windows.check(*p, &tag(*seq), NOW))
+        .collect();
+        let too_old = windows.check(peer, &tag(102 - REPLAY_WINDOW_LEN), NOW);
+
+        assert_eq!(
+            actual,
+            vec![Ok(()), Ok(()), Ok(()), Err(ReplayError::Replayed), Ok(())]
+        );
+        assert_eq!(too_old, Err(ReplayError::Replayed));
+    }
+
+    #[test]
+    /// Checks messages sent outside of the accepted time range are rejected
+    fn replay_windows_reject_expired() {
+        let peer = "127.0.0.1:12300".parse().unwrap();
+        let mut windows = ReplayWindows::default();
+        let old = ReplayTag {
+            seq: 1,
+            timestamp: NOW - REPLAY_MAX_AGE_SECS - 1,
+        };
+        let future = ReplayTag {
+            seq: 2,
+            timestamp: NOW + REPLAY_MAX_AGE_SECS + 1,
+        };
+
+        assert_eq!(windows.check(peer, &old, NOW), Err(ReplayError::Expired));
+        assert_eq!(windows.check(peer, &future, NOW), Err(ReplayError::Expired));
+        assert_eq!(windows.check(peer, &tag(3), NOW), Ok(()));
+    }
+
+    #[test]
+    /// Checks tags of a node keep increasing, also from a restarted node
+    fn replay_tagger_increasing() {
+        let mut tagger = ReplayTagger::default();
+
+        let first = tagger.next_tag();
+        let second = tagger.next_tag();
+
+        assert_eq!(second.seq, first.seq + 1);
+        assert!(ReplayTagger::default().next_tag().seq >= first.seq);
+    }
+}
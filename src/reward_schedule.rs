@@ -0,0 +1,158 @@
+//! Reward paid to the miners of each block.
+//!
+//! The schedule is part of the mempool node configuration and must be the same
+//! on all mempool peers: each peer proposes its schedule to the raft group on
+//! start up and reports the peers proposing a different one, and the coinbase
+//! of a winning miner is only accepted if it pays the reward of the schedule.
+
+use crate::utils::calculate_reward;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use tw_chain::primitives::asset::TokenAmount;
+
+/// Reward paid from a block height
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardHeight {
+    /// First block paying the reward
+    pub height: u64,
+    /// Reward of the block, in units
+    pub reward: u64,
+}
+
+/// Reward of each block, shared by all its winning miners
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RewardSchedule {
+    /// Reward decreasing with the tokens already issued
+    #[default]
+    Issuance,
+    /// Reward halved every `halving_interval` blocks
+    Halving {
+        initial_reward: u64,
+        halving_interval: u64,
+    },
+    /// Reward of each height range, by ascending first height from 0
+    Table(Vec<RewardHeight>),
+}
+
+impl RewardSchedule {
+    /// Whether the schedule covers every block
+    pub fn is_well_formed(&self) -> bool {
+        match self {
+            Self::Issuance => true,
+            Self::Halving {
+                halving_interval, ..
+            } => *halving_interval > 0,
+            Self::Table(heights) => {
+                let starts_at_genesis = heights.first().map(|h| h.height) == Some(0);
+                let ascending = heights.windows(2).all(|w| w[0].height < w[1].height);
+                starts_at_genesis && ascending
+            }
+        }
+    }
+
+    /// Reward of a block, shared by all its winning miners
+    ///
+    /// ### Arguments
+    ///
+    /// * `b_num`            - Number of the block
+    /// * `current_issuance` - Tokens issued before the block
+    pub fn reward(&self, b_num: u64, current_issuance: TokenAmount) -> TokenAmount {
+        match self {
+            Self::Issuance => calculate_reward(current_issuance),
+            Self::Halving {
+                initial_reward,
+                halving_interval,
+            } => {
+                let halvings = b_num / (*halving_interval).max(1);
+                let reward = u32::try_from(halvings)
+                    .ok()
+                    .and_then(|h| initial_reward.checked_shr(h))
+                    .unwrap_or_default();
+                TokenAmount(reward)
+            }
+            Self::Table(heights) => {
+                let reward = heights
+                    .iter()
+                    .take_while(|h| h.height <= b_num)
+                    .last()
+                    .map(|h| h.reward)
+                    .unwrap_or_default();
+                TokenAmount(reward)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the reward halves at each interval until it runs out
+    fn reward_schedule_halving() {
+        //Arrange
+        let schedule = RewardSchedule::Halving {
+            initial_reward: 100,
+            halving_interval: 10,
+        };
+
+        //Act
+        let rewards: Vec<_> = [0, 9, 10, 25, 100, 10_000]
+            .iter()
+            .map(|b_num| schedule.reward(*b_num, TokenAmount(0)).0)
+            .collect();
+
+        //Assert
+        assert_eq!(rewards, vec![100, 100, 50, 25, 0, 0]);
+    }
+
+    #[test]
+    /// Checks the reward of a height is the one of the last range started
+    fn reward_schedule_table() {
+        //Arrange
+        let schedule = RewardSchedule::Table(vec![
+            RewardHeight {
+                height: 0,
+                reward: 30,
+            },
+            RewardHeight {
+                height: 5,
+                reward: 20,
+            },
+            RewardHeight {
+                height: 50,
+                reward: 0,
+            },
+        ]);
+
+        //Act
+        let rewards: Vec<_> = [0, 4, 5, 49, 50, 51]
+            .iter()
+            .map(|b_num| schedule.reward(*b_num, TokenAmount(0)).0)
+            .collect();
+
+        //Assert
+        assert_eq!(rewards, vec![30, 30, 20, 20, 0, 0]);
+        assert_eq!(
+            RewardSchedule::Issuance.reward(7, TokenAmount(0)),
+            calculate_reward(TokenAmount(0))
+        );
+    }
+
+    #[test]
+    /// Checks schedules leaving blocks without a reward are rejected
+    fn reward_schedule_well_formed() {
+        let at = |height| RewardHeight { height, reward: 1 };
+        let zero_interval = RewardSchedule::Halving {
+            initial_reward: 1,
+            halving_interval: 0,
+        };
+
+        assert!(RewardSchedule::Issuance.is_well_formed());
+        assert!(RewardSchedule::Table(vec![at(0), at(3)]).is_well_formed());
+        assert!(!zero_interval.is_well_formed());
+        assert!(!RewardSchedule::Table(Vec::new()).is_well_formed());
+        assert!(!RewardSchedule::Table(vec![at(1)]).is_well_formed());
+        assert!(!RewardSchedule::Table(vec![at(0), at(3), at(3)]).is_well_formed());
+    }
+}
@@ -0,0 +1,491 @@
+//! Screening of the addresses touched by the transactions a mempool node receives.
+//!
+//! A `Screener` decides which addresses a transaction may not touch, among the
+//! addresses of the outputs it spends and of the outputs it creates.
+//! `AddressListScreener` rejects the addresses listed in a JSON file and
+//! `RemoteScreener` asks a screening service over TCP. A transaction touching a
+//! rejected address, or that could not be screened, is dropped, and the decision
+//! is recorded in an append-only audit log in the mempool database.
+//!
+//! Each service request is a JSON line `{"addresses", "nonce", "mac"}` and each
+//! response a JSON line `{"rejected", "mac"}` or `{"error"}`. Both MACs are
+//! HMAC-SHA256 with the shared authentication key: over `nonce:addresses` for
+//! the request and over `nonce:rejected` for the response, the addresses being
+//! joined with commas.
+
+use crate::configurations::ScreeningServiceConfig;
+use crate::db_utils::{self, SimpleDb, SimpleDbWriteBatch};
+use crate::mempool::DB_COL_INTERNAL;
+use bincode::{deserialize, serialize};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{error, fmt};
+use tracing::warn;
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxOut};
+
+/// Maximum length of a screening service response line
+const MAX_RESPONSE_LEN: u64 = 64 * 1024;
+
+/// Key of the sequence number of the next audit entry
+pub const SCREENING_AUDIT_NEXT_KEY: &str = "ScreeningAuditNext";
+
+/// Prefix of the keys of the audit entries
+pub const SCREENING_AUDIT_PREFIX: &str = "ScreeningAudit_";
+
+/// Result wrapper for screening errors
+pub type Result<T> = std::result::Result<T, ScreeningError>;
+
+/// Errors that occur while screening
+#[derive(Debug)]
+pub enum ScreeningError {
+    IO(io::Error),
+    Serialization(serde_json::Error),
+    Config(String),
+    Rejected(String),
+    InvalidResponse(&'static str),
+    Unavailable(String),
+}
+
+impl fmt::Display for ScreeningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "I/O Error: {err}"),
+            Self::Serialization(err) => write!(f, "Serialization Error: {err}"),
+            Self::Config(err) => write!(f, "Config Error: {err}"),
+            Self::Rejected(err) => write!(f, "Screening refused: {err}"),
+            Self::InvalidResponse(err) => write!(f, "Invalid screening response: {err}"),
+            Self::Unavailable(err) => write!(f, "No screening service available: {err}"),
+        }
+    }
+}
+
+impl error::Error for ScreeningError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IO(ref e) => Some(e),
+            Self::Serialization(ref e) => Some(e),
+            Self::Config(_) => None,
+            Self::Rejected(_) => None,
+            Self::InvalidResponse(_) => None,
+            Self::Unavailable(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScreeningError {
+    fn from(other: io::Error) -> Self {
+        Self::IO(other)
+    }
+}
+
+impl From<serde_json::Error> for ScreeningError {
+    fn from(other: serde_json::Error) -> Self {
+        Self::Serialization(other)
+    }
+}
+
+/// Decider of the addresses transactions may not touch
+pub trait Screener: fmt::Debug + Send + Sync {
+    /// Addresses, among the given ones, a transaction may not touch
+    ///
+    /// ### Arguments
+    ///
+    /// * `addresses` - Addresses touched by a transaction.
+    fn rejected(&self, addresses: &BTreeSet<String>) -> Result<BTreeSet<String>>;
+}
+
+/// Decision to drop a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScreeningDecision {
+    /// The transaction touches the given rejected addresses
+    Rejected(Vec<String>),
+    /// The transaction could not be screened
+    Failed(String),
+}
+
+/// Record of a transaction dropped by the screening
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreeningAuditEntry {
+    pub seq: u64,
+    /// Decision time, in seconds since the UNIX epoch
+    pub timestamp: i64,
+    pub tx_hash: String,
+    pub decision: ScreeningDecision,
+}
+
+/// Screener rejecting a fixed list of addresses
+#[derive(Debug, Clone, Default)]
+pub struct AddressListScreener {
+    addresses: BTreeSet<String>,
+}
+
+impl AddressListScreener {
+    /// Screener rejecting the given addresses
+    ///
+    /// ### Arguments
+    ///
+    /// * `addresses` - Addresses to reject.
+    pub fn new(addresses: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            addresses: addresses.into_iter().collect(),
+        }
+    }
+
+    /// Screener rejecting the addresses of a JSON file holding a list of addresses
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Path to the file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let addresses: Vec<String> = serde_json::from_reader(File::open(path)?)?;
+        Ok(Self::new(addresses))
+    }
+}
+
+impl Screener for AddressListScreener {
+    fn rejected(&self, addresses: &BTreeSet<String>) -> Result<BTreeSet<String>> {
+        Ok(addresses.intersection(&self.addresses).cloned().collect())
+    }
+}
+
+/// Request sent to the screening service
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScreeningRequest {
+    pub addresses: Vec<String>,
+    pub nonce: String,
+    pub mac: String,
+}
+
+/// Response of the screening service
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScreeningResponse {
+    pub rejected: Option<Vec<String>>,
+    pub mac: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Screener asking a screening service, failing over between its instances
+pub struct RemoteScreener {
+    endpoints: Vec<String>,
+    auth_key: hmac::Key,
+    timeout: Duration,
+    preferred: AtomicUsize,
+}
+
+impl fmt::Debug for RemoteScreener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteScreener")
+            .field("endpoints", &self.endpoints)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RemoteScreener {
+    /// Create a screener for the configured screening service
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Screening service endpoints, authentication key and timeout.
+    pub fn new(config: &ScreeningServiceConfig) -> Result<Self> {
+        if config.endpoints.is_empty() {
+            return Err(ScreeningError::Config("No screening endpoints".to_owned()));
+        }
+        let auth_key = hex::decode(&config.auth_key)
+            .map_err(|e| ScreeningError::Config(format!("Invalid auth key: {e}")))?;
+
+        Ok(Self {
+            endpoints: config.endpoints.clone(),
+            auth_key: hmac::Key::new(hmac::HMAC_SHA256, &auth_key),
+            timeout: Duration::from_millis(config.timeout_ms),
+            preferred: AtomicUsize::new(0),
+        })
+    }
+
+    /// Request a decision from one instance of the screening service
+    ///
+    /// ### Arguments
+    ///
+    /// * `endpoint`  - Address of the instance.
+    /// * `addresses` - Addresses to screen.
+    fn rejected_at(&self, endpoint: &str, addresses: &[String]) -> Result<BTreeSet<String>> {
+        let addr = endpoint
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| ScreeningError::Config(format!("Invalid endpoint: {endpoint}")))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        let mac = hmac::sign(&self.auth_key, mac_data(&nonce, addresses).as_bytes());
+        let request = ScreeningRequest {
+            addresses: addresses.to_vec(),
+            nonce: nonce.clone(),
+            mac: hex::encode(mac.as_ref()),
+        };
+        let mut request = serde_json::to_vec(&request)?;
+        request.push(b'\n');
+        stream.write_all(&request)?;
+
+        let mut line = String::new();
+        BufReader::new(stream.take(MAX_RESPONSE_LEN)).read_line(&mut line)?;
+        let response: ScreeningResponse = serde_json::from_str(&line)?;
+        if let Some(error) = response.error {
+            return Err(ScreeningError::Rejected(error));
+        }
+
+        let (rejected, mac) = match (response.rejected, response.mac) {
+            (Some(rejected), Some(mac)) => (rejected, mac),
+            _ => return Err(ScreeningError::InvalidResponse("Missing decision")),
+        };
+        let mac = hex::decode(mac).map_err(|_| ScreeningError::InvalidResponse("Invalid MAC"))?;
+        hmac::verify(&self.auth_key, mac_data(&nonce, &rejected).as_bytes(), &mac)
+            .map_err(|_| ScreeningError::InvalidResponse("Unauthenticated response"))?;
+        Ok(rejected.into_iter().collect())
+    }
+}
+
+impl Screener for RemoteScreener {
+    fn rejected(&self, addresses: &BTreeSet<String>) -> Result<BTreeSet<String>> {
+        let addresses: Vec<_> = addresses.iter().cloned().collect();
+        let count = self.endpoints.len();
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let mut errors = Vec::new();
+
+        for idx in (0..count).map(|i| (preferred + i) % count) {
+            let endpoint = &self.endpoints[idx];
+            match self.rejected_at(endpoint, &addresses) {
+                Ok(rejected) => {
+                    self.preferred.store(idx, Ordering::Relaxed);
+                    return Ok(rejected);
+                }
+                Err(ScreeningError::Rejected(error)) => {
+                    return Err(ScreeningError::Rejected(error))
+                }
+                Err(e) => {
+                    warn!("Screening service {} failed: {}", endpoint, e);
+                    errors.push(format!("{endpoint}: {e}"));
+                }
+            }
+        }
+
+        Err(ScreeningError::Unavailable(errors.join(", ")))
+    }
+}
+
+/// Data authenticated by the MAC of a request or a response
+pub fn mac_data(nonce: &str, addresses: &[String]) -> String {
+    format!("{}:{}", nonce, addresses.join(","))
+}
+
+/// Addresses of the outputs a transaction spends and creates, fees included
+///
+/// ### Arguments
+///
+/// * `tx`          - Transaction to screen.
+/// * `get_tx_out`  - Output spent at an outpoint, if known.
+pub fn transaction_addresses<'a>(
+    tx: &'a Transaction,
+    get_tx_out: impl Fn(&OutPoint) -> Option<&'a TxOut>,
+) -> BTreeSet<String> {
+    let spent = tx
+        .inputs
+        .iter()
+        .filter_map(|tx_in| tx_in.previous_out.as_ref())
+        .filter_map(get_tx_out);
+    spent
+        .chain(tx.outputs.iter())
+        .chain(tx.fees.iter())
+        .filter_map(|tx_out| tx_out.script_public_key.clone())
+        .collect()
+}
+
+/// Decision to drop a transaction touching the given addresses, None if it may be kept
+///
+/// ### Arguments
+///
+/// * `screener`  - Screener deciding on the addresses.
+/// * `addresses` - Addresses touched by the transaction.
+pub fn screen(screener: &dyn Screener, addresses: &BTreeSet<String>) -> Option<ScreeningDecision> {
+    match screener.rejected(addresses) {
+        Ok(rejected) if rejected.is_empty() => None,
+        Ok(rejected) => Some(ScreeningDecision::Rejected(rejected.into_iter().collect())),
+        Err(e) => Some(ScreeningDecision::Failed(e.to_string())),
+    }
+}
+
+/// Append the decisions to the audit log
+///
+/// ### Arguments
+///
+/// * `db`        - Mempool database, to get the next sequence number from.
+/// * `batch`     - Write batch to add the entries to.
+/// * `decisions` - Hash of each transaction dropped, with its decision.
+/// * `timestamp` - Decision time, in seconds since the UNIX epoch.
+pub fn append_screening_audit(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    decisions: &[(String, ScreeningDecision)],
+    timestamp: i64,
+) {
+    let mut seq: u64 = match db.get_cf(DB_COL_INTERNAL, SCREENING_AUDIT_NEXT_KEY) {
+        Ok(Some(next)) => deserialize(&next).unwrap_or_default(),
+        _ => 0,
+    };
+
+    for (tx_hash, decision) in decisions {
+        let entry = ScreeningAuditEntry {
+            seq,
+            timestamp,
+            tx_hash: tx_hash.clone(),
+            decision: decision.clone(),
+        };
+        batch.put_cf(DB_COL_INTERNAL, audit_key(seq), serialize(&entry).unwrap());
+        seq += 1;
+    }
+    batch.put_cf(
+        DB_COL_INTERNAL,
+        SCREENING_AUDIT_NEXT_KEY,
+        serialize(&seq).unwrap(),
+    );
+}
+
+/// Get the audit log entries, oldest first
+///
+/// ### Arguments
+///
+/// * `db` - Mempool database.
+pub fn get_screening_audit_log(db: &SimpleDb) -> db_utils::Result<Vec<ScreeningAuditEntry>> {
+    let mut entries: Vec<ScreeningAuditEntry> = db
+        .iter_cf_clone(DB_COL_INTERNAL)?
+        .filter(|(key, _)| key.starts_with(SCREENING_AUDIT_PREFIX.as_bytes()))
+        .filter_map(|(_, value)| deserialize(&value).ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.seq);
+    Ok(entries)
+}
+
+/// Key of the audit entry with the given sequence number
+fn audit_key(seq: u64) -> String {
+    format!("{SCREENING_AUDIT_PREFIX}{seq:020}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configurations::DbMode;
+    use crate::db_utils;
+    use crate::mempool::DB_SPEC;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const AUTH_KEY: &str = "00112233445566778899aabbccddeeff";
+
+    /// Serve one screening request, rejecting the given addresses
+    fn spawn_screening_service(blocked: &'static [&'static str]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let auth_key = hmac::Key::new(hmac::HMAC_SHA256, &hex::decode(AUTH_KEY).unwrap());
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let request: ScreeningRequest = serde_json::from_str(&line).unwrap();
+
+            let rejected: Vec<_> = request
+                .addresses
+                .into_iter()
+                .filter(|a| blocked.contains(&a.as_str()))
+                .collect();
+            let mac = hmac::sign(&auth_key, mac_data(&request.nonce, &rejected).as_bytes());
+            let response = ScreeningResponse {
+                rejected: Some(rejected),
+                mac: Some(hex::encode(mac.as_ref())),
+                error: None,
+            };
+
+            let mut response = serde_json::to_vec(&response).unwrap();
+            response.push(b'\n');
+            (&stream).write_all(&response).unwrap();
+        });
+        endpoint
+    }
+
+    /// Address of a closed port
+    fn closed_endpoint() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    fn addresses(addresses: &[&str]) -> BTreeSet<String> {
+        addresses.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    /// Checks listed addresses are rejected and the decisions logged in order
+    fn address_list_screening_audit() {
+        //Arrange
+        let screener = AddressListScreener::new(vec!["blocked".to_owned()]);
+        let mut db = db_utils::new_db(DbMode::InMemory, &DB_SPEC, None, None);
+
+        //Act
+        let allowed = screen(&screener, &addresses(&["payer", "payee"]));
+        let rejected = screen(&screener, &addresses(&["payer", "blocked"]));
+        for tx_hash in &["tx_1", "tx_2"] {
+            let decisions = vec![(tx_hash.to_string(), rejected.clone().unwrap())];
+            let mut batch = db.batch_writer();
+            append_screening_audit(&db, &mut batch, &decisions, 10);
+            let batch = batch.done();
+            db.write(batch).unwrap();
+        }
+        let log = get_screening_audit_log(&db).unwrap();
+
+        //Assert
+        assert_eq!(allowed, None);
+        assert_eq!(
+            rejected,
+            Some(ScreeningDecision::Rejected(vec!["blocked".to_owned()]))
+        );
+        let logged: Vec<_> = log.iter().map(|e| (e.seq, e.tx_hash.as_str())).collect();
+        assert_eq!(logged, vec![(0, "tx_1"), (1, "tx_2")]);
+    }
+
+    #[test]
+    /// Checks the service is asked through its next instance when one fails
+    fn remote_screening_failover() {
+        //Arrange
+        let service = spawn_screening_service(&["blocked"]);
+        let screener = RemoteScreener::new(&ScreeningServiceConfig {
+            endpoints: vec![closed_endpoint(), service],
+            auth_key: AUTH_KEY.to_owned(),
+            timeout_ms: 1000,
+        })
+        .unwrap();
+
+        //Act
+        let decision = screen(&screener, &addresses(&["payer", "blocked"]));
+        let unavailable = RemoteScreener::new(&ScreeningServiceConfig {
+            endpoints: vec![closed_endpoint()],
+            auth_key: AUTH_KEY.to_owned(),
+            timeout_ms: 1000,
+        })
+        .map(|s| screen(&s, &addresses(&["payer"])))
+        .unwrap();
+
+        //Assert
+        assert_eq!(
+            decision,
+            Some(ScreeningDecision::Rejected(vec!["blocked".to_owned()]))
+        );
+        assert_eq!(screener.preferred.load(Ordering::Relaxed), 1);
+        assert!(matches!(unavailable, Some(ScreeningDecision::Failed(_))));
+    }
+}
@@ -0,0 +1,385 @@
+//! Signed manifests of database backups.
+//!
+//! Each file db backup is written with a manifest next to it, holding the
+//! manifest format version, the name and network version of the backed up db,
+//! and the SHA3-256 hash of every file of the backup. When a backup signing key
+//! is configured, the manifest is signed with it and a restore requires a
+//! manifest signed by the same key, matching the backup files. A tampered
+//! backup, or the backup of another db, is then never restored silently.
+//! Otherwise the manifest is signed with the node identity, which a restore
+//! does not require.
+
+use crate::constants::NETWORK_VERSION;
+use crate::node_identity::NodeIdentity;
+use crate::wallet::key_import::{decode_keypair, KeyFormat};
+use ring::signature::{Ed25519KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::{error::Error, fmt, fs, io};
+use tracing::warn;
+use tw_chain::crypto::sha3_256;
+use tw_chain::crypto::sign_ed25519::{PublicKey, SecretKey};
+
+/// Current version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+static SNAPSHOT_SIGNER: Mutex<Option<Arc<SnapshotSigner>>> = Mutex::new(None);
+
+/// Result wrapper for snapshot manifest errors
+pub type Result<T> = std::result::Result<T, SnapshotError>;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    IO(io::Error),
+    Serialization(serde_json::Error),
+    InvalidKey,
+    MissingManifest,
+    UnsupportedVersion(u32),
+    Mismatch(&'static str),
+    InvalidSignature,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "IO error: {err}"),
+            Self::Serialization(err) => write!(f, "Serialization error: {err}"),
+            Self::InvalidKey => write!(f, "Invalid snapshot signing key"),
+            Self::MissingManifest => write!(f, "Missing signed backup manifest"),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported backup manifest version: {v}"),
+            Self::Mismatch(reason) => write!(f, "Backup does not match manifest: {reason}"),
+            Self::InvalidSignature => write!(f, "Invalid backup manifest signature"),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IO(ref e) => Some(e),
+            Self::Serialization(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(other: io::Error) -> Self {
+        Self::IO(other)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(other: serde_json::Error) -> Self {
+        Self::Serialization(other)
+    }
+}
+
+/// Node identity key signing the backup manifests
+pub struct SnapshotSigner {
+    public_key: PublicKey,
+    secret_key: SecretKey,
+    /// Whether restored backups must be signed by this key
+    required: bool,
+}
+
+impl fmt::Debug for SnapshotSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SnapshotSigner({})",
+            hex::encode(self.public_key.as_ref())
+        )
+    }
+}
+
+impl SnapshotSigner {
+    /// Signer for an Ed25519 key, PEM encoded PKCS#8 or hex encoded seed
+    ///
+    /// ### Arguments
+    ///
+    /// * `secret_key` - Encoded secret key.
+    pub fn from_encoded(secret_key: &str) -> Result<Self> {
+        let format = match secret_key.contains("-----BEGIN") {
+            true => KeyFormat::Pem,
+            false => KeyFormat::Hex,
+        };
+        let (public_key, secret_key) =
+            decode_keypair(format, secret_key).map_err(|_| SnapshotError::InvalidKey)?;
+        Ok(Self {
+            public_key,
+            secret_key,
+            required: true,
+        })
+    }
+
+    /// Public key checked on restore
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Sign a message
+    ///
+    /// ### Arguments
+    ///
+    /// * `message` - Message to sign.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(self.secret_key.as_ref())
+            .map_err(|_| SnapshotError::InvalidKey)?;
+        Ok(key_pair.sign(message).as_ref().to_vec())
+    }
+}
+
+/// Use the identity key in this file to sign and check the backups of the process
+///
+/// ### Arguments
+///
+/// * `key_path` - File holding the Ed25519 secret key.
+pub fn set_snapshot_signer(key_path: &str) -> Result<()> {
+    let signer = SnapshotSigner::from_encoded(&fs::read_to_string(key_path)?)?;
+    *SNAPSHOT_SIGNER.lock().unwrap() = Some(Arc::new(signer));
+    Ok(())
+}
+
+/// Sign the backups of the process with the node identity, unless a key is already set
+///
+/// Unlike a configured signing key, the identity is not required on restore.
+///
+/// ### Arguments
+///
+/// * `identity` - Identity of the node.
+pub fn set_snapshot_identity(identity: &NodeIdentity) {
+    let mut signer = SNAPSHOT_SIGNER.lock().unwrap();
+    if signer.is_none() {
+        *signer = Some(Arc::new(SnapshotSigner {
+            public_key: *identity.public_key(),
+            secret_key: identity.secret_key().clone(),
+            required: false,
+        }));
+    }
+}
+
+/// Identity key signing the backups, if any
+fn snapshot_signer() -> Option<Arc<SnapshotSigner>> {
+    SNAPSHOT_SIGNER.lock().unwrap().clone()
+}
+
+/// Signed content of a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestContent {
+    pub version: u32,
+    pub db_name: String,
+    pub network_version: u32,
+    pub created_at: String,
+    /// SHA3-256 hash of each backup file, by path relative to the backup
+    pub files: BTreeMap<String, String>,
+}
+
+/// Manifest of a db backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub content: ManifestContent,
+    pub public_key: Option<String>,
+    pub signature: Option<String>,
+}
+
+/// Path of the manifest of a backup
+///
+/// ### Arguments
+///
+/// * `backup_path` - Directory of the backup.
+pub fn manifest_path(backup_path: &str) -> String {
+    format!("{backup_path}_manifest.json")
+}
+
+/// Write the manifest of a backup, signed if an identity key is set
+///
+/// ### Arguments
+///
+/// * `db_path`     - Path of the backed up db.
+/// * `backup_path` - Directory of the backup.
+pub fn write_manifest(db_path: &str, backup_path: &str) -> Result<()> {
+    let content = ManifestContent {
+        version: MANIFEST_VERSION,
+        db_name: db_name(db_path),
+        network_version: NETWORK_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        files: hash_backup_files(Path::new(backup_path))?,
+    };
+    let (public_key, signature) = match snapshot_signer() {
+        Some(signer) => {
+            let signature = signer.sign(&serde_json::to_vec(&content)?)?;
+            let public_key = hex::encode(signer.public_key().as_ref());
+            (Some(public_key), Some(hex::encode(signature)))
+        }
+        None => (None, None),
+    };
+    let manifest = SnapshotManifest {
+        content,
+        public_key,
+        signature,
+    };
+
+    let path = manifest_path(backup_path);
+    let tmp_path = format!("{path}.tmp");
+    fs::write(&tmp_path, serde_json::to_vec_pretty(&manifest)?)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+/// Check a backup matches its manifest before restoring it
+///
+/// The manifest must be signed by the signing key if one is configured.
+/// Backups without manifest are accepted only if no signing key is configured.
+///
+/// ### Arguments
+///
+/// * `db_path`     - Path of the db to restore.
+/// * `backup_path` - Directory of the backup.
+pub fn verify_manifest(db_path: &str, backup_path: &str) -> Result<()> {
+    let signer = snapshot_signer().filter(|s| s.required);
+    let manifest: SnapshotManifest = match fs::read(manifest_path(backup_path)) {
+        Ok(content) => serde_json::from_slice(&content)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && signer.is_none() => {
+            warn!("Restoring backup {} without manifest", backup_path);
+            return Ok(());
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(SnapshotError::MissingManifest)
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let content = &manifest.content;
+    if content.version != MANIFEST_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(content.version));
+    }
+    if content.db_name != db_name(db_path) {
+        return Err(SnapshotError::Mismatch("Backup of another db"));
+    }
+    if content.network_version != NETWORK_VERSION {
+        return Err(SnapshotError::Mismatch("Backup of another network version"));
+    }
+
+    let expected_key = signer.map(|s| hex::encode(s.public_key().as_ref()));
+    match (&manifest.public_key, &manifest.signature, expected_key) {
+        (Some(key), _, Some(expected)) if *key != expected => {
+            return Err(SnapshotError::InvalidSignature)
+        }
+        (Some(key), Some(signature), _) => {
+            let key = hex::decode(key).map_err(|_| SnapshotError::InvalidSignature)?;
+            let signature = hex::decode(signature).map_err(|_| SnapshotError::InvalidSignature)?;
+            UnparsedPublicKey::new(&ED25519, key)
+                .verify(&serde_json::to_vec(content)?, &signature)
+                .map_err(|_| SnapshotError::InvalidSignature)?;
+        }
+        (_, _, Some(_)) => return Err(SnapshotError::MissingManifest),
+        (_, _, None) => (),
+    }
+
+    if content.files != hash_backup_files(Path::new(backup_path))? {
+        return Err(SnapshotError::Mismatch("Backup files modified"));
+    }
+    Ok(())
+}
+
+/// Name identifying a db, independent of the directory it is in
+///
+/// ### Arguments
+///
+/// * `db_path` - Path of the db.
+fn db_name(db_path: &str) -> String {
+    Path::new(db_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// SHA3-256 hash of each file in a backup, by relative path
+///
+/// ### Arguments
+///
+/// * `backup_path` - Directory of the backup.
+fn hash_backup_files(backup_path: &Path) -> io::Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![backup_path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Ok(relative) = path.strip_prefix(backup_path) {
+                let hash = hex::encode(sha3_256::digest(&fs::read(&path)?));
+                files.insert(relative.to_string_lossy().into_owned(), hash);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str = "d4ee72dbf913584ad5b6d8f1f769f8ad3afe7c28cbf1d4fbe097a88f44755842";
+
+    fn make_backup(name: &str) -> (String, String) {
+        let dir = std::env::temp_dir().join(format!("snapshot_manifest_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        let backup_path = dir.join("db_backup");
+        fs::create_dir_all(backup_path.join("meta")).unwrap();
+        fs::write(backup_path.join("meta").join("1"), b"meta").unwrap();
+        fs::write(backup_path.join("000001.sst"), b"data").unwrap();
+
+        let db_path = dir.join("db").to_string_lossy().into_owned();
+        (db_path, backup_path.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    /// Checks a backup is restorable once written, and not once modified or moved
+    fn verify_manifest_detects_changes() {
+        let (db_path, backup_path) = make_backup("changes");
+        write_manifest(&db_path, &backup_path).unwrap();
+
+        let unmodified = verify_manifest(&db_path, &backup_path);
+        let other_db = verify_manifest("other/db_other", &backup_path);
+        fs::write(Path::new(&backup_path).join("000001.sst"), b"tampered").unwrap();
+        let tampered = verify_manifest(&db_path, &backup_path);
+
+        assert!(unmodified.is_ok());
+        assert!(matches!(other_db, Err(SnapshotError::Mismatch(_))));
+        assert!(matches!(tampered, Err(SnapshotError::Mismatch(_))));
+    }
+
+    #[test]
+    /// Checks manifests are signed and their signature is checked
+    fn signed_manifest_signature() {
+        let (db_path, backup_path) = make_backup("signed");
+        let signer = SnapshotSigner::from_encoded(SEED).unwrap();
+        let content = ManifestContent {
+            version: MANIFEST_VERSION,
+            db_name: db_name(&db_path),
+            network_version: NETWORK_VERSION,
+            created_at: Default::default(),
+            files: hash_backup_files(Path::new(&backup_path)).unwrap(),
+        };
+        let signature = signer.sign(&serde_json::to_vec(&content).unwrap()).unwrap();
+        let mut manifest = SnapshotManifest {
+            content,
+            public_key: Some(hex::encode(signer.public_key().as_ref())),
+            signature: Some(hex::encode(&signature)),
+        };
+        let path = manifest_path(&backup_path);
+
+        fs::write(&path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+        let signed = verify_manifest(&db_path, &backup_path);
+        manifest.content.created_at = "2000-01-01T00:00:00+00:00".to_owned();
+        fs::write(&path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+        let altered = verify_manifest(&db_path, &backup_path);
+
+        assert!(signed.is_ok());
+        assert!(matches!(altered, Err(SnapshotError::InvalidSignature)));
+    }
+}
@@ -0,0 +1,524 @@
+//! Spending conditions beyond single signatures.
+//!
+//! An output is locked to a condition by paying the address of the condition,
+//! the hash of its encoding. The input spending it carries the condition and a
+//! witness of signatures and hash preimages satisfying it. Conditions combine
+//! signatures, multisig thresholds, hash-locks and time-locks, and are bounded
+//! in depth, size and signature checks so evaluation stays cheap for the
+//! mempool nodes.
+
+use crate::utils::is_locktime_expired;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tw_chain::crypto::sha3_256;
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey, Signature};
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxIn, TxOut};
+use tw_chain::script::lang::Script;
+use tw_chain::script::StackEntry;
+use tw_chain::utils::script_utils::{tx_has_valid_create_script, tx_is_valid};
+
+/// First stack entry of the inputs spending a condition output
+pub const CONDITION_SPEND_MARKER: &str = "SpendCondition";
+
+/// Prefix of the hashed content of a condition address
+const CONDITION_ADDRESS_DOMAIN: &[u8] = b"aiblock-spend-condition:";
+
+/// Maximum nesting of conditions
+pub const MAX_CONDITION_DEPTH: usize = 8;
+
+/// Maximum number of conditions, nested ones included
+pub const MAX_CONDITION_NODES: usize = 32;
+
+/// Maximum number of keys in a multisig condition
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Maximum number of signatures in a witness
+pub const MAX_WITNESS_SIGNATURES: usize = 16;
+
+/// Maximum bytes of hash preimages in a witness
+pub const MAX_WITNESS_PREIMAGE_SIZE: usize = 1024;
+
+/// Condition to satisfy to spend an output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SpendCondition {
+    /// Signed by the key
+    Signature(PublicKey),
+    /// Signed by at least `threshold` of the keys
+    Multisig {
+        threshold: usize,
+        keys: Vec<PublicKey>,
+    },
+    /// Preimage of the hex encoded SHA3-256 hash revealed
+    HashLock(String),
+    /// Block number or timestamp reached, as an output locktime
+    After(u64),
+    /// All conditions satisfied
+    All(Vec<SpendCondition>),
+    /// Any condition satisfied
+    Any(Vec<SpendCondition>),
+}
+
+impl SpendCondition {
+    /// Whether the condition is within the resource limits and well formed
+    pub fn is_well_formed(&self) -> bool {
+        let mut nodes = 0;
+        self.check_shape(1, &mut nodes)
+    }
+
+    fn check_shape(&self, depth: usize, nodes: &mut usize) -> bool {
+        *nodes += 1;
+        if depth > MAX_CONDITION_DEPTH || *nodes > MAX_CONDITION_NODES {
+            return false;
+        }
+
+        match self {
+            Self::Signature(_) | Self::After(_) => true,
+            Self::Multisig { threshold, keys } => {
+                keys.len() <= MAX_MULTISIG_KEYS && *threshold > 0 && *threshold <= keys.len()
+            }
+            Self::HashLock(hash) => hex::decode(hash).is_ok_and(|h| h.len() == 32),
+            Self::All(conditions) | Self::Any(conditions) => {
+                !conditions.is_empty() && conditions.iter().all(|c| c.check_shape(depth + 1, nodes))
+            }
+        }
+    }
+
+    /// Whether the witness satisfies the condition
+    ///
+    /// ### Arguments
+    ///
+    /// * `signed`    - Keys with a valid signature in the witness, hex encoded
+    /// * `hashes`    - Hashes of the preimages in the witness, hex encoded
+    /// * `b_num`     - Current block number
+    /// * `timestamp` - Current timestamp, in seconds since the epoch
+    fn is_satisfied(
+        &self,
+        signed: &BTreeSet<String>,
+        hashes: &BTreeSet<String>,
+        b_num: u64,
+        timestamp: i64,
+    ) -> bool {
+        let is_signed = |key: &PublicKey| signed.contains(&hex::encode(key.as_ref()));
+        match self {
+            Self::Signature(key) => is_signed(key),
+            Self::Multisig { threshold, keys } => {
+                let keys: BTreeSet<_> = keys.iter().map(|k| k.as_ref()).collect();
+                let signers = keys
+                    .into_iter()
+                    .filter(|k| signed.contains(&hex::encode(k)));
+                signers.count() >= *threshold
+            }
+            Self::HashLock(hash) => hashes.contains(&hash.to_lowercase()),
+            Self::After(locktime) => is_locktime_expired(*locktime, b_num, timestamp),
+            Self::All(conditions) => conditions
+                .iter()
+                .all(|c| c.is_satisfied(signed, hashes, b_num, timestamp)),
+            Self::Any(conditions) => conditions
+                .iter()
+                .any(|c| c.is_satisfied(signed, hashes, b_num, timestamp)),
+        }
+    }
+}
+
+/// Signatures and preimages satisfying a condition
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendWitness {
+    pub signatures: Vec<(PublicKey, Signature)>,
+    /// Hex encoded hash preimages
+    pub preimages: Vec<String>,
+}
+
+impl SpendWitness {
+    /// Add the signature of the spent output by a key
+    ///
+    /// ### Arguments
+    ///
+    /// * `signable_data` - Data signed for the spending input
+    /// * `public_key`    - Key signing
+    /// * `secret_key`    - Secret key of the signing key
+    pub fn sign(&mut self, signable_data: &str, public_key: PublicKey, secret_key: &SecretKey) {
+        let signature = sign::sign_detached(signable_data.as_bytes(), secret_key);
+        self.signatures.push((public_key, signature));
+    }
+
+    /// Add the preimage of a hash-lock
+    ///
+    /// ### Arguments
+    ///
+    /// * `preimage` - Preimage revealed
+    pub fn reveal(&mut self, preimage: &[u8]) {
+        self.preimages.push(hex::encode(preimage));
+    }
+
+    /// Keys with a valid signature, hex encoded, None if the witness is too large
+    ///
+    /// ### Arguments
+    ///
+    /// * `signable_data` - Data signed for the spending input
+    fn signed_keys(&self, signable_data: &str) -> Option<BTreeSet<String>> {
+        if self.signatures.len() > MAX_WITNESS_SIGNATURES {
+            return None;
+        }
+
+        let valid = self.signatures.iter().filter(|(key, signature)| {
+            UnparsedPublicKey::new(&ED25519, key.as_ref())
+                .verify(signable_data.as_bytes(), signature.as_ref())
+                .is_ok()
+        });
+        Some(valid.map(|(key, _)| hex::encode(key.as_ref())).collect())
+    }
+
+    /// Hashes of the preimages, hex encoded, None if the witness is too large or malformed
+    fn preimage_hashes(&self) -> Option<BTreeSet<String>> {
+        let size: usize = self.preimages.iter().map(|p| p.len() / 2).sum();
+        if size > MAX_WITNESS_PREIMAGE_SIZE {
+            return None;
+        }
+
+        let preimages = self.preimages.iter().map(|p| hex::decode(p).ok());
+        preimages.map(|p| p.map(|p| hash_preimage(&p))).collect()
+    }
+}
+
+/// Hash-lock value of a preimage
+///
+/// ### Arguments
+///
+/// * `preimage` - Secret locking the output
+pub fn hash_preimage(preimage: &[u8]) -> String {
+    hex::encode(sha3_256::digest(preimage))
+}
+
+/// Address paid to lock an output to the condition
+///
+/// ### Arguments
+///
+/// * `condition` - Condition to spend the output
+pub fn condition_address(condition: &SpendCondition) -> String {
+    let encoded = bincode::serialize(condition).unwrap();
+    hex::encode(sha3_256::digest(
+        &[CONDITION_ADDRESS_DOMAIN, &encoded].concat(),
+    ))
+}
+
+/// Data signed by the keys of a condition to spend an output to the given outputs
+///
+/// ### Arguments
+///
+/// * `previous_out` - Output spent
+/// * `outputs`      - Outputs of the spending transaction
+pub fn condition_signable_data(previous_out: &OutPoint, outputs: &[TxOut]) -> String {
+    let encoded = bincode::serialize(&(previous_out, outputs)).unwrap();
+    hex::encode(sha3_256::digest(&encoded))
+}
+
+/// Input spending an output locked to a condition
+///
+/// ### Arguments
+///
+/// * `previous_out` - Output spent
+/// * `condition`    - Condition the output is locked to
+/// * `witness`      - Signatures and preimages satisfying the condition
+pub fn construct_condition_tx_in(
+    previous_out: OutPoint,
+    condition: &SpendCondition,
+    witness: &SpendWitness,
+) -> TxIn {
+    let mut script_signature = Script::new();
+    let entries = vec![
+        CONDITION_SPEND_MARKER.to_owned(),
+        hex::encode(bincode::serialize(condition).unwrap()),
+        hex::encode(bincode::serialize(witness).unwrap()),
+    ];
+    script_signature
+        .stack
+        .extend(entries.into_iter().map(StackEntry::Bytes));
+    TxIn {
+        previous_out: Some(previous_out),
+        script_signature,
+    }
+}
+
+/// Whether the input spends an output locked to a condition
+///
+/// ### Arguments
+///
+/// * `tx_in` - Input to check
+pub fn is_condition_spend(tx_in: &TxIn) -> bool {
+    matches!(
+        tx_in.script_signature.stack.first(),
+        Some(StackEntry::Bytes(marker)) if marker == CONDITION_SPEND_MARKER
+    )
+}
+
+/// Condition and witness carried by an input, None if not a condition spend or malformed
+///
+/// ### Arguments
+///
+/// * `tx_in` - Input spending a condition output
+fn decode_condition_spend(tx_in: &TxIn) -> Option<(SpendCondition, SpendWitness)> {
+    match tx_in.script_signature.stack.as_slice() {
+        [StackEntry::Bytes(marker), StackEntry::Bytes(condition), StackEntry::Bytes(witness)]
+            if marker == CONDITION_SPEND_MARKER =>
+        {
+            let condition = bincode::deserialize(&hex::decode(condition).ok()?).ok()?;
+            let witness = bincode::deserialize(&hex::decode(witness).ok()?).ok()?;
+            Some((condition, witness))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a transaction spending condition outputs is valid
+///
+/// All inputs must spend condition outputs with a satisfied condition, and
+/// the assets spent must all go to the outputs and fees.
+///
+/// ### Arguments
+///
+/// * `tx`        - Transaction to check
+/// * `b_num`     - Current block number
+/// * `timestamp` - Current timestamp, in seconds since the epoch
+/// * `utxo`      - Unspent output for an OutPoint, if any
+pub fn condition_tx_is_valid<'a>(
+    tx: &Transaction,
+    b_num: u64,
+    timestamp: i64,
+    utxo: impl Fn(&OutPoint) -> Option<&'a TxOut>,
+) -> bool {
+    if tx.inputs.is_empty() || tx.druid_info.is_some() {
+        return false;
+    }
+
+    let mut spent = BTreeSet::new();
+    let mut assets_in = AssetTotals::default();
+    for tx_in in &tx.inputs {
+        let previous_out = match &tx_in.previous_out {
+            Some(previous_out) if spent.insert(previous_out) => previous_out,
+            _ => return false,
+        };
+        let (condition, witness) = match decode_condition_spend(tx_in) {
+            Some(spend) => spend,
+            None => return false,
+        };
+        let tx_out = match utxo(previous_out) {
+            Some(tx_out) => tx_out,
+            None => return false,
+        };
+
+        let locked = tx_out.script_public_key.as_deref() != Some(&condition_address(&condition));
+        if locked
+            || !is_locktime_expired(tx_out.locktime, b_num, timestamp)
+            || !condition.is_well_formed()
+        {
+            return false;
+        }
+
+        let signable_data = condition_signable_data(previous_out, &tx.outputs);
+        let satisfied = witness
+            .signed_keys(&signable_data)
+            .zip(witness.preimage_hashes())
+            .is_some_and(|(signed, hashes)| {
+                condition.is_satisfied(&signed, &hashes, b_num, timestamp)
+            });
+        if !satisfied || !assets_in.add(&tx_out.value) {
+            return false;
+        }
+    }
+
+    if tx
+        .outputs
+        .iter()
+        .any(|tx_out| tx_out.script_public_key.is_none())
+    {
+        return false;
+    }
+
+    let mut assets_out = AssetTotals::default();
+    let mut outputs = tx.outputs.iter().chain(&tx.fees);
+    outputs.all(|tx_out| assets_out.add(&tx_out.value)) && assets_in == assets_out
+}
+
+/// Whether a transaction spends unlocked outputs with valid scripts
+///
+/// Create transactions are checked for their create script, and transactions
+/// spending condition outputs against their conditions.
+///
+/// ### Arguments
+///
+/// * `tx`        - Transaction to check
+/// * `b_num`     - Current block number
+/// * `timestamp` - Current timestamp, in seconds since the epoch
+/// * `utxo`      - Unspent output for an OutPoint, if any
+pub fn tx_spends_are_valid<'a>(
+    tx: &Transaction,
+    b_num: u64,
+    timestamp: i64,
+    utxo: impl Fn(&OutPoint) -> Option<&'a TxOut> + 'a,
+) -> bool {
+    if tx.is_create_tx() {
+        return tx_has_valid_create_script(&tx.inputs[0].script_signature, &tx.outputs[0].value);
+    }
+
+    if tx.inputs.iter().any(is_condition_spend) {
+        return condition_tx_is_valid(tx, b_num, timestamp, utxo);
+    }
+
+    tx_is_valid(tx, b_num, move |out_p| {
+        utxo(out_p).filter(|tx_out| is_locktime_expired(tx_out.locktime, b_num, timestamp))
+    })
+}
+
+/// Tokens and items by genesis hash of a set of outputs
+#[derive(Debug, Default, PartialEq, Eq)]
+struct AssetTotals {
+    tokens: u64,
+    items: BTreeMap<Option<String>, u64>,
+}
+
+impl AssetTotals {
+    /// Add an asset, returning false on overflow
+    fn add(&mut self, asset: &Asset) -> bool {
+        let added = match asset {
+            Asset::Token(amount) => self.tokens.checked_add(amount.0).map(|t| self.tokens = t),
+            Asset::Item(item) => {
+                let total = self.items.entry(item.genesis_hash.clone()).or_default();
+                total.checked_add(item.amount).map(|t| *total = t)
+            }
+        };
+        added.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::asset::TokenAmount;
+
+    fn condition_utxo(condition: &SpendCondition, locktime: u64) -> (OutPoint, TxOut) {
+        let tx_out = TxOut {
+            value: Asset::Token(TokenAmount(10)),
+            locktime,
+            script_public_key: Some(condition_address(condition)),
+        };
+        (OutPoint::new("g_locked".to_owned(), 0), tx_out)
+    }
+
+    fn spend_tx(
+        out_p: &OutPoint,
+        condition: &SpendCondition,
+        witness: SpendWitness,
+    ) -> Transaction {
+        let mut tx = Transaction::new();
+        tx.inputs = vec![construct_condition_tx_in(
+            out_p.clone(),
+            condition,
+            &witness,
+        )];
+        tx.outputs = vec![TxOut::new_token_amount(
+            "receiver".to_owned(),
+            TokenAmount(10),
+            None,
+        )];
+        tx
+    }
+
+    #[test]
+    /// Checks a 2-of-3 multisig output needs two distinct valid signatures
+    fn condition_tx_multisig_threshold() {
+        //Arrange
+        let keys: Vec<_> = (0..3).map(|_| sign::gen_keypair()).collect();
+        let condition = SpendCondition::Multisig {
+            threshold: 2,
+            keys: keys.iter().map(|(pk, _)| *pk).collect(),
+        };
+        let (out_p, tx_out) = condition_utxo(&condition, 0);
+        let outputs = spend_tx(&out_p, &condition, Default::default()).outputs;
+        let signable = condition_signable_data(&out_p, &outputs);
+
+        let mut one_signer = SpendWitness::default();
+        one_signer.sign(&signable, keys[0].0, &keys[0].1);
+        let mut same_signer = one_signer.clone();
+        same_signer.sign(&signable, keys[0].0, &keys[0].1);
+        let mut two_signers = one_signer.clone();
+        two_signers.sign(&signable, keys[2].0, &keys[2].1);
+
+        //Act
+        let utxo = |o: &OutPoint| Some(&tx_out).filter(|_| *o == out_p);
+        let valid = |w| condition_tx_is_valid(&spend_tx(&out_p, &condition, w), 0, 0, utxo);
+
+        //Assert
+        assert!(!valid(one_signer));
+        assert!(!valid(same_signer));
+        assert!(valid(two_signers));
+    }
+
+    #[test]
+    /// Checks a hash-lock with a time-locked refund, and the asset balance
+    fn condition_tx_hash_lock_or_refund() {
+        //Arrange
+        let (pk, sk) = sign::gen_keypair();
+        let condition = SpendCondition::Any(vec![
+            SpendCondition::HashLock(hash_preimage(b"secret")),
+            SpendCondition::All(vec![
+                SpendCondition::After(100),
+                SpendCondition::Signature(pk),
+            ]),
+        ]);
+        let (out_p, tx_out) = condition_utxo(&condition, 0);
+        let utxo = |o: &OutPoint| Some(&tx_out).filter(|_| *o == out_p);
+
+        let mut revealed = SpendWitness::default();
+        revealed.reveal(b"secret");
+        let mut wrong = SpendWitness::default();
+        wrong.reveal(b"guess");
+        let outputs = spend_tx(&out_p, &condition, Default::default()).outputs;
+        let mut refund = SpendWitness::default();
+        refund.sign(&condition_signable_data(&out_p, &outputs), pk, &sk);
+        let mut unbalanced = spend_tx(&out_p, &condition, revealed.clone());
+        unbalanced.outputs[0].value = Asset::Token(TokenAmount(11));
+
+        //Act
+        let valid = |w, b_num| {
+            let tx = spend_tx(&out_p, &condition, w);
+            condition_tx_is_valid(&tx, b_num, 0, utxo)
+        };
+
+        //Assert
+        assert!(valid(revealed, 0));
+        assert!(!valid(wrong, 1000));
+        assert!(!valid(refund.clone(), 99));
+        assert!(valid(refund, 100));
+        assert!(!condition_tx_is_valid(&unbalanced, 0, 0, utxo));
+    }
+
+    #[test]
+    /// Checks conditions beyond the resource limits are rejected
+    fn condition_shape_limits() {
+        //Arrange
+        let (pk, _) = sign::gen_keypair();
+        let mut deep = SpendCondition::Signature(pk);
+        for _ in 0..MAX_CONDITION_DEPTH {
+            deep = SpendCondition::All(vec![deep]);
+        }
+        let wide = SpendCondition::Any(vec![SpendCondition::After(0); MAX_CONDITION_NODES]);
+        let multisig = SpendCondition::Multisig {
+            threshold: 0,
+            keys: vec![pk],
+        };
+
+        //Act
+        let shapes = [
+            deep,
+            wide,
+            multisig,
+            SpendCondition::HashLock("00".to_owned()),
+        ];
+
+        //Assert
+        assert!(shapes.iter().all(|c| !c.is_well_formed()));
+        assert!(SpendCondition::Signature(pk).is_well_formed());
+    }
+}
@@ -1,25 +1,40 @@
+use crate::api::client_auth::ApiClientAuth;
+use crate::block_stats::{get_block_stats, put_block_stats, BlockStats};
+use crate::block_timestamp::{
+    check_block_timestamp, DEFAULT_MAX_BLOCK_TIME_DRIFT, MEDIAN_TIME_SPAN,
+};
+use crate::checkpoints::Checkpoints;
 use crate::comms_handler::{CommsError, Event, Node, TcpTlsConfig};
-use crate::configurations::{ExtraNodeParams, StorageNodeConfig, TlsPrivateInfo};
+use crate::configurations::{ExtraNodeParams, ResponseHook, StorageNodeConfig, TlsPrivateInfo};
 use crate::constants::{
     DB_PATH, INDEXED_BLOCK_HASH_PREFIX_KEY, INDEXED_TX_HASH_PREFIX_KEY, LAST_BLOCK_HASH_KEY,
-    NAMED_CONSTANT_PREPEND,
+    NAMED_CONSTANT_PREPEND, ORPHANED_BLOCK_HASH_PREFIX_KEY, STORAGE_READ_CACHE_ENTRIES,
+};
+use crate::db_executor::{configure_db_pool, spawn_db, DbPool};
+use crate::db_utils::{
+    self, CompactionSchedule, SimpleDb, SimpleDbError, SimpleDbSpec, SimpleDbWriteBatch,
 };
-use crate::db_utils::{self, SimpleDb, SimpleDbError, SimpleDbSpec, SimpleDbWriteBatch};
+use crate::event_bus::{publish_event, EventPublisher, NodeEvent};
+use crate::event_hooks::EventHooks;
 use crate::interfaces::{
-    BlockStoredInfo, BlockchainItem, BlockchainItemMeta, Contract, DruidTxInfo, MempoolRequest,
-    MineRequest, MinedBlock, NodeType, ProofOfWork, Response, StorageInterface, StorageRequest,
-    StoredSerializingBlock,
+    BlockStoredInfo, BlockchainItem, BlockchainItemMeta, CommonBlockInfo, Contract, DruidTxInfo,
+    MempoolRequest, MineRequest, MinedBlock, NodeType, ProofOfWork, Response, ResponseReason,
+    StorageInterface, StorageRequest, StoredSerializingBlock, UserRequest,
 };
-use crate::raft::RaftCommit;
+use crate::node_identity::load_node_identity;
+use crate::raft::{RaftCommit, SharedRaftMetrics};
+use crate::snapshot_manifest::{set_snapshot_identity, set_snapshot_signer};
+use crate::spend_script::tx_spends_are_valid;
 use crate::storage_fetch::{FetchStatus, FetchedBlockChain, StorageFetch};
 use crate::storage_raft::{CommittedItem, CompleteBlock, StorageRaft};
 use crate::utils::{
-    construct_valid_block_pow_hash, create_socket_addr, get_genesis_tx_in_display, to_api_keys,
-    to_route_pow_infos, ApiKeys, LocalEvent, LocalEventChannel, LocalEventSender, ResponseResult,
-    RoutesPoWInfo,
+    construct_valid_block_pow_hash, create_socket_addr, create_socket_addr_for_list,
+    get_genesis_tx_in_display, get_timestamp_now, to_api_keys, to_route_pow_infos, ApiKeys,
+    LocalEvent, LocalEventChannel, LocalEventSender, ResponseResult, RoutesPoWInfo,
 };
 use bincode::{deserialize, serialize};
 use bytes::Bytes;
+use rayon::prelude::*;
 use serde::Serialize;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::error::Error;
@@ -28,8 +43,11 @@ use std::future::Future;
 use std::net::SocketAddr;
 use std::str;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::{debug, error, error_span, info, trace, warn};
 use tracing_futures::Instrument;
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxOut};
+use tw_chain::utils::transaction_utils::construct_tx_hash;
 
 /// Key storing current proposer run
 pub const RAFT_KEY_RUN: &str = "RaftKeyRun";
@@ -41,6 +59,7 @@ pub const DB_COL_BC_ALL: &str = "block_chain_all";
 pub const DB_COL_BC_NAMED: &str = "block_chain_named";
 pub const DB_COL_BC_META: &str = "block_chain_meta";
 pub const DB_COL_BC_JSON: &str = "block_chain_json";
+pub const DB_COL_BC_STATS: &str = "block_chain_stats";
 pub const DB_COL_BC_NOW: &str = "block_chain_v0.7.0";
 pub const DB_COL_BC_V0_6_0: &str = "block_chain_v0.6.0";
 pub const DB_COL_BC_V0_5_0: &str = "block_chain_v0.5.0";
@@ -70,6 +89,7 @@ pub const DB_SPEC: SimpleDbSpec = SimpleDbSpec {
         DB_COL_BC_NAMED,
         DB_COL_BC_META,
         DB_COL_BC_JSON,
+        DB_COL_BC_STATS,
         DB_COL_BC_NOW,
         DB_COL_BC_V0_6_0,
         DB_COL_BC_V0_5_0,
@@ -130,18 +150,34 @@ impl From<bincode::Error> for StorageError {
     }
 }
 
+/// Stored block replaced by a committed block at the same height
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedBlock {
+    /// Hash of the replaced block, still stored under its hash
+    pub block_hash: String,
+    /// Transactions of the replaced block missing from the new one
+    pub dropped_transactions: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct StorageNode {
     node: Node,
     node_raft: StorageRaft,
     catchup_fetch: StorageFetch,
+    checkpoints: Arc<Checkpoints>,
+    max_block_time_drift: i64,
     db: Arc<Mutex<SimpleDb>>,
     local_events: LocalEventChannel,
+    peer_wait_timeout: Option<Duration>,
+    db_compaction: CompactionSchedule,
     mempool_addr: SocketAddr,
     api_info: (SocketAddr, Option<TlsPrivateInfo>, ApiKeys, RoutesPoWInfo),
     whitelisted: HashMap<SocketAddr, bool>,
     shutdown_group: BTreeSet<SocketAddr>,
     blockchain_item_fetched: Option<(String, BlockchainItem, SocketAddr)>,
+    event_publisher: Option<EventPublisher>,
+    event_hooks: EventHooks,
+    response_hook: Option<ResponseHook>,
 }
 
 impl StorageNode {
@@ -151,7 +187,13 @@ impl StorageNode {
     ///
     /// * `config` - StorageNodeConfig object containing the parameters for the new StorageNode
     /// * `extra`  - additional parameter for construction
-    pub async fn new(config: StorageNodeConfig, mut extra: ExtraNodeParams) -> Result<StorageNode> {
+    pub async fn new(
+        mut config: StorageNodeConfig,
+        mut extra: ExtraNodeParams,
+    ) -> Result<StorageNode> {
+        if let Some(tls_config) = extra.tls_config.take() {
+            config.tls_config = tls_config;
+        }
         let raw_addr = config
             .storage_nodes
             .get(config.storage_node_idx)
@@ -168,11 +210,60 @@ impl StorageNode {
             .await
             .map_err(|_| StorageError::ConfigError("Invalid mempool address supplied"))?;
 
-        let tcp_tls_config = TcpTlsConfig::from_tls_spec(addr, &config.tls_config)?;
+        let mut tcp_tls_config =
+            TcpTlsConfig::from_tls_spec(extra.bind_address.unwrap_or(addr), &config.tls_config)?;
+        if let Some(listener) = extra.listener.take() {
+            tcp_tls_config = tcp_tls_config.with_listener(listener).await;
+        }
+        if config.storage_raft != 0 && config.tls_config.pin_raft_peer_certificates {
+            let raw_raft_addrs: Vec<_> = config
+                .storage_nodes
+                .iter()
+                .map(|n| n.address.clone())
+                .collect();
+            let raft_addrs = create_socket_addr_for_list(&raw_raft_addrs)
+                .await
+                .map_err(|_| {
+                    StorageError::ConfigError("Invalid storage node address in config file")
+                })?;
+            tcp_tls_config.pin_peer_certificates(&config.tls_config, &raft_addrs)?;
+        }
+        if let Some(spec) = &config.peer_ip_filter {
+            tcp_tls_config.set_peer_ip_filter(spec)?;
+        }
+        if let Some(capacity) = config.event_queue_capacity {
+            tcp_tls_config.set_event_queue_capacity(capacity);
+        }
+        if let Some(key_path) = &config.backup_signing_key {
+            set_snapshot_signer(key_path)
+                .map_err(|_| StorageError::ConfigError("Invalid backup signing key"))?;
+        }
+        let identity = load_node_identity(
+            config.identity_file.as_deref(),
+            config.storage_db_mode,
+            &DB_SPEC,
+        )
+        .map_err(|_| StorageError::ConfigError("Invalid node identity file"))?;
+        set_snapshot_identity(&identity);
+        if let Some(threads) = config.db_threads {
+            configure_db_pool(DbPool::Storage, threads);
+        }
         let api_addr = SocketAddr::new(addr.ip(), config.storage_api_port);
-        let api_tls_info = config
-            .storage_api_use_tls
-            .then(|| tcp_tls_config.clone_private_info());
+        let api_tls_info = match (config.storage_api_use_tls, &config.storage_api_client_auth) {
+            (true, client_auth) => {
+                let mut info = tcp_tls_config.clone_private_info();
+                if let Some(spec) = client_auth {
+                    info.client_auth = Some(ApiClientAuth::from_spec(&info, spec)?);
+                }
+                Some(info)
+            }
+            (false, None) => None,
+            (false, Some(_)) => {
+                return Err(StorageError::ConfigError(
+                    "API client authentication needs API TLS",
+                ))
+            }
+        };
         let api_keys = to_api_keys(config.api_keys.clone());
 
         let node = Node::new(
@@ -183,15 +274,26 @@ impl StorageNode {
             false,
         )
         .await?;
-        let node_raft = StorageRaft::new(&config, extra.raft_db.take()).await;
-        let catchup_fetch = StorageFetch::new(&config, addr).await;
+        node.set_event_hooks(extra.event_hooks.clone());
+        node.set_identity(identity);
+        node.set_network_id(config.network_id.unwrap_or_default());
+        let node_raft = StorageRaft::new(&config, extra.raft_db.take(), extra.clock.clone()).await;
+        let catchup_fetch = StorageFetch::new(&config, addr, extra.clock).await;
         let api_pow_info = to_route_pow_infos(config.routes_pow.clone());
+        let checkpoints = {
+            let configured = config.storage_checkpoints.as_deref().unwrap_or_default();
+            Checkpoints::new(configured)
+                .map_err(|_| StorageError::ConfigError("Conflicting checkpoints"))?
+        };
 
         if config.backup_restore.unwrap_or(false) {
-            db_utils::restore_file_backup(config.storage_db_mode, &DB_SPEC, None).unwrap();
+            db_utils::restore_file_backup(config.storage_db_mode, &DB_SPEC, None)?;
         }
         let db = {
-            let raw_db = db_utils::new_db(config.storage_db_mode, &DB_SPEC, extra.db.take(), None);
+            let mut raw_db =
+                db_utils::new_db(config.storage_db_mode, &DB_SPEC, extra.db.take(), None);
+            db_utils::check_network_id(&mut raw_db, node.network_id())?;
+            raw_db.set_read_cache(STORAGE_READ_CACHE_ENTRIES);
             Arc::new(Mutex::new(raw_db))
         };
 
@@ -201,17 +303,35 @@ impl StorageNode {
             raft_peers.chain(mempool).collect()
         };
 
+        let event_publisher = match &config.storage_event_bus {
+            Some(bus) => Some(
+                EventPublisher::start(bus)
+                    .await
+                    .map_err(|_| StorageError::ConfigError("Invalid event bus"))?,
+            ),
+            None => None,
+        };
+
         StorageNode {
             node,
             node_raft,
             catchup_fetch,
+            checkpoints: Arc::new(checkpoints),
+            max_block_time_drift: config
+                .storage_max_block_time_drift
+                .unwrap_or(DEFAULT_MAX_BLOCK_TIME_DRIFT),
             db,
             api_info: (api_addr, api_tls_info, api_keys, api_pow_info),
-            local_events: Default::default(),
+            local_events: LocalEventChannel::new(config.local_event_capacity),
+            peer_wait_timeout: config.peer_wait_timeout.map(Duration::from_millis),
+            db_compaction: CompactionSchedule::new(config.db_compaction_interval),
             mempool_addr,
             whitelisted: Default::default(),
             shutdown_group,
             blockchain_item_fetched: Default::default(),
+            event_publisher,
+            event_hooks: extra.event_hooks,
+            response_hook: extra.response_hook,
         }
         .load_local_db()
     }
@@ -276,11 +396,21 @@ impl StorageNode {
         &self.local_events.tx
     }
 
+    /// Time to wait for peers to connect before starting, unlimited if None
+    pub fn peer_wait_timeout(&self) -> Option<Duration> {
+        self.peer_wait_timeout
+    }
+
     /// Return the raft loop to spawn in it own task.
     pub fn raft_loop(&self) -> impl Future<Output = ()> {
         self.node_raft.raft_loop()
     }
 
+    /// Return the metrics updated by the raft loop.
+    pub fn raft_metrics(&self) -> SharedRaftMetrics {
+        self.node_raft.raft_metrics()
+    }
+
     /// Signal to the raft loop to complete
     pub async fn close_raft_loop(&mut self) {
         self.node_raft.close_raft_loop().await
@@ -308,25 +438,38 @@ impl StorageNode {
         }
     }
 
+    /// Read every entry of the main database, returning the number of entries
+    pub fn scan_dbs(&self) -> db_utils::Result<usize> {
+        self.db.lock().unwrap().scan()
+    }
+
+    /// Compact the storage db
+    pub async fn compact_db(&self) {
+        compact_storage_db(self.db.clone()).await
+    }
+
     /// Listens for new events from peers and handles them, processing any errors.
     pub async fn handle_next_event_response(
         &mut self,
         response: Result<Response>,
     ) -> ResponseResult {
         debug!("Response: {:?}", response);
+        if let (Some(hook), Ok(resp)) = (&self.response_hook, &response) {
+            hook.call(resp);
+        }
 
         match response {
             Ok(Response {
                 success: true,
-                reason: "Sent startup requests on reconnection",
+                reason: ResponseReason::SentStartupRequests,
             }) => debug!("Sent startup requests on reconnection"),
             Ok(Response {
                 success: false,
-                reason: "Failed to send startup requests on reconnection",
+                reason: ResponseReason::FailedToSendStartupRequests,
             }) => error!("Failed to send startup requests on reconnection"),
             Ok(Response {
                 success: true,
-                reason: "Blockchain item fetched from storage",
+                reason: ResponseReason::BlockchainItemFetchedFromStorage,
             }) => {
                 if let Err(e) = self.send_blockchain_item().await {
                     error!("Blockchain item not sent {:?}", e);
@@ -334,32 +477,36 @@ impl StorageNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Shutdown",
+                reason: ResponseReason::Shutdown,
             }) => {
                 warn!("Shutdown now");
                 return ResponseResult::Exit;
             }
             Ok(Response {
                 success: true,
-                reason: "Mempool Shutdown",
+                reason: ResponseReason::MempoolShutdown,
             }) => {
                 debug!("Mempool shutdown");
-                if self.flood_closing_events().await.unwrap() {
-                    warn!("Flood closing event shutdown");
-                    return ResponseResult::Exit;
+                match self.flood_closing_events().await {
+                    Ok(true) => {
+                        warn!("Flood closing event shutdown");
+                        return ResponseResult::Exit;
+                    }
+                    Ok(false) => (),
+                    Err(e) => error!("Closing events not sent {:?}", e),
                 }
             }
             Ok(Response {
                 success: true,
-                reason: "Shutdown pending",
+                reason: ResponseReason::ShutdownPending,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Block received to be added",
+                reason: ResponseReason::BlockReceivedToBeAdded,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Block complete stored",
+                reason: ResponseReason::BlockCompleteStored,
             }) => {
                 info!("Block stored: Send to mempool");
                 if let Err(e) = self.send_stored_block().await {
@@ -368,19 +515,19 @@ impl StorageNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Snapshot applied",
+                reason: ResponseReason::SnapshotApplied,
             }) => {
                 warn!("Snapshot applied");
             }
             Ok(Response {
                 success: true,
-                reason: "Snapshot applied: Fetch missing blocks",
+                reason: ResponseReason::SnapshotAppliedFetchMissingBlocks,
             }) => {
                 warn!("Snapshot applied: Fetch missing blocks");
             }
             Ok(Response {
                 success: true,
-                reason: "Catch up stored blocks",
+                reason: ResponseReason::CatchUpStoredBlocks,
             }) => {
                 if let Err(e) = self.catchup_fetch_blockchain_item().await {
                     error!("Resend block stored failed {:?}", e);
@@ -388,19 +535,15 @@ impl StorageNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Blockchain item received",
+                reason: ResponseReason::BlockchainItemReceived,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Blockchain item received: Block stored",
-            }) => {}
-            Ok(Response {
-                success: true,
-                reason: "Blockchain item received: Block stored(Done)",
+                reason: ResponseReason::BlockchainItemStored { .. },
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Blockchain item received: Block failed",
+                reason: ResponseReason::BlockchainItemReceivedBlockFailed,
             }) => {}
             Ok(Response {
                 success: true,
@@ -424,7 +567,7 @@ impl StorageNode {
 
     /// Listens for new events from peers and handles them.
     /// The future returned from this function should be executed in the runtime. It will block execution.
-    pub async fn handle_next_event<E: Future<Output = &'static str> + Unpin>(
+    pub async fn handle_next_event<E: Future<Output = ResponseReason> + Unpin>(
         &mut self,
         exit: &mut E,
     ) -> Option<Result<Response>> {
@@ -465,9 +608,13 @@ impl StorageNode {
                     }
                     return Some(Ok(Response {
                         success: true,
-                        reason: "Catch up stored blocks",
+                        reason: ResponseReason::CatchUpStoredBlocks,
                     }))
                 }
+                _ = self.db_compaction.tick() => {
+                    trace!("handle_next_event compact db");
+                    self.compact_db().await;
+                }
                 Some(event) = self.local_events.rx.recv(), if ready => {
                     if let Some(res) = self.handle_local_event(event).await {
                         return Some(Ok(res));
@@ -497,12 +644,12 @@ impl StorageNode {
                     error!("Failed to send startup requests on reconnect: {}", err);
                     return Some(Response {
                         success: false,
-                        reason: "Failed to send startup requests on reconnection",
+                        reason: ResponseReason::FailedToSendStartupRequests,
                     });
                 }
                 Some(Response {
                     success: true,
-                    reason: "Sent startup requests on reconnection",
+                    reason: ResponseReason::SentStartupRequests,
                 })
             }
             LocalEvent::CoordinatedShutdown(_) => None,
@@ -519,23 +666,49 @@ impl StorageNode {
         match self.node_raft.received_commit(commit_data).await {
             Some(CommittedItem::Block) => {
                 let block = self.node_raft.generate_complete_block();
-                let block_stored = {
-                    let mut self_db = self.db.lock().unwrap();
+                let transactions = block.common.block.transactions.clone();
+                let (block_stored, orphaned) = {
+                    let db = self.db.clone();
+                    let checkpoints = self.checkpoints.clone();
 
                     let b_num = block.common.block.header.b_num;
                     let contiguous = self.catchup_fetch.check_contiguous_block_num(b_num);
-                    let stored = Self::store_complete_block(&mut self_db, contiguous, block);
+                    let stored = spawn_db(DbPool::Storage, move || {
+                        let mut db = db.lock().unwrap();
+                        Self::store_complete_block(&mut db, &checkpoints, contiguous, block)
+                    });
+                    let stored = match stored.await {
+                        Ok(stored) => stored,
+                        Err(e) => return Some(Err(e)),
+                    };
                     self.catchup_fetch.update_contiguous_block_num(contiguous);
                     self.catchup_fetch.increase_running_target(b_num);
 
                     stored
                 };
+                if let Some(orphaned) = orphaned {
+                    publish_event(&self.event_publisher, &self.event_hooks, || {
+                        NodeEvent::ChainReorg {
+                            b_num: block_stored.block_num,
+                            orphaned_hash: orphaned.block_hash,
+                            block_hash: block_stored.block_hash.clone(),
+                            dropped_transactions: orphaned.dropped_transactions,
+                        }
+                    });
+                }
+                publish_event(&self.event_publisher, &self.event_hooks, || {
+                    NodeEvent::NewBlock {
+                        b_num: block_stored.block_num,
+                        block_hash: block_stored.block_hash.clone(),
+                        transactions,
+                    }
+                });
                 self.node_raft
                     .event_processed_generate_snapshot(block_stored);
                 self.backup_persistent_dbs().await;
                 Some(Ok(Response {
                     success: true,
-                    reason: "Block complete stored",
+                    reason: ResponseReason::BlockCompleteStored,
                 }))
             }
             Some(CommittedItem::Snapshot) => {
@@ -548,13 +721,13 @@ impl StorageNode {
                         );
                         return Some(Ok(Response {
                             success: true,
-                            reason: "Snapshot applied: Fetch missing blocks",
+                            reason: ResponseReason::SnapshotAppliedFetchMissingBlocks,
                         }));
                     }
                 }
                 Some(Ok(Response {
                     success: true,
-                    reason: "Snapshot applied",
+                    reason: ResponseReason::SnapshotApplied,
                 }))
             }
             None => None,
@@ -647,6 +820,11 @@ impl StorageNode {
                         .send(peer, StorageRequest::SendBlockchainItem { key, item })
                         .await?
                 }
+                NodeType::User => {
+                    self.node
+                        .send(peer, UserRequest::SendBlockchainItem { key, item })
+                        .await?
+                }
                 _ => return Ok(()),
             }
         }
@@ -655,17 +833,22 @@ impl StorageNode {
 
     ///Stores a completed block including transactions and mining transactions.
     ///
+    /// A different block already stored at the same height is orphaned: it stays
+    /// stored under its hash and an orphaned block key, while the height indexes
+    /// now point to the new block, which becomes part of the canonical chain.
+    ///
     /// ### Arguments
     ///
-    /// * `self_db`  - Database to update
-    /// * `status`   - Block is contiguous with last contiguous
-    /// * `complete` - CompleteBlock object to be stored.
+    /// * `self_db`     - Database to update
+    /// * `checkpoints` - Block hashes the chain must have
+    /// * `status`      - Block is contiguous with last contiguous
+    /// * `complete`    - CompleteBlock object to be stored.
     fn store_complete_block(
         self_db: &mut SimpleDb,
+        checkpoints: &Checkpoints,
         status: FetchStatus,
         complete: CompleteBlock,
-    ) -> BlockStoredInfo {
-        // TODO: Makes the DB save process async
+    ) -> Result<(BlockStoredInfo, Option<OrphanedBlock>)> {
         // TODO: only accept whitelisted blocks
 
         // Save the complete block
@@ -691,6 +874,17 @@ impl StorageNode {
         let block_json = serde_json::to_vec(&stored_block).unwrap();
         let block_hash = construct_valid_block_pow_hash(&stored_block.block)
             .unwrap_or_else(|e| panic!("Block always validated before: {}", e));
+        if !checkpoints.verify(block_num, &block_hash) {
+            error!(
+                "Refuse block {} diverging from checkpoint: {}",
+                block_num, block_hash
+            );
+            return Err(StorageError::ConfigError(
+                "Block diverges from a checkpoint",
+            ));
+        }
+        let replaced =
+            canonical_block_at(self_db, block_num).filter(|(_, hash, _)| hash != &block_hash);
 
         let (nonce, mining_tx_hash) = stored_block.block.header.nonce_and_mining_tx_hash.clone();
         let last_block_stored_info = BlockStoredInfo {
@@ -712,6 +906,21 @@ impl StorageNode {
             block_hash
         );
 
+        let stats = {
+            let spent = stored_block_spent_outputs(self_db, &all_block_txs);
+            let previous_timestamp = block_num
+                .checked_sub(1)
+                .and_then(|b_num| canonical_block_at(self_db, b_num))
+                .map(|(_, _, previous)| previous.block.header.timestamp);
+            BlockStats::new(
+                &stored_block.block,
+                block_hash.clone(),
+                &all_block_txs,
+                &spent,
+                previous_timestamp,
+            )
+        };
+
         //
         // Store to database
         //
@@ -763,6 +972,38 @@ impl StorageNode {
             }
         }
 
+        // Block statistics
+        put_block_stats(&mut batch, &stats);
+
+        // Orphaned block
+        let orphaned = replaced.map(|(pointer, orphaned_hash, orphaned_block)| {
+            let orphaned_txs: Vec<_> = all_ordered_stored_block_tx_hashes(
+                &orphaned_block.block.transactions,
+                std::iter::once(&orphaned_block.block.header.nonce_and_mining_tx_hash),
+            )
+            .map(|(_, tx_hash)| tx_hash.clone())
+            .collect();
+            warn!(
+                "Chain reorganization at b_num={}: {} replaced by {}",
+                block_num, orphaned_hash, block_hash
+            );
+
+            let orphaned_key = orphaned_block_hash_key(block_num, &orphaned_hash);
+            batch.put_cf(DB_COL_BC_NAMED, orphaned_key, pointer);
+            for tx_num in tx_len..orphaned_txs.len() as u32 {
+                batch.delete_cf(DB_COL_BC_NAMED, indexed_tx_hash_key(block_num, tx_num));
+            }
+
+            let dropped_transactions = orphaned_txs
+                .into_iter()
+                .filter(|tx_hash| !all_block_txs.contains_key(tx_hash))
+                .collect();
+            OrphanedBlock {
+                block_hash: orphaned_hash,
+                dropped_transactions,
+            }
+        });
+
         // Druid store
         for (druid, tx_hashes) in druid_store {
             let druid_entry = DruidTxInfo { tx_hashes };
@@ -776,7 +1017,7 @@ impl StorageNode {
         }
 
         let batch = batch.done();
-        self_db.write(batch).unwrap();
+        self_db.write(batch)?;
 
         //
         // Celebrate genesis block:
@@ -784,7 +1025,10 @@ impl StorageNode {
         if block_num == 0 {
             info!("!!! Stored Genesis Block !!!");
             for hash in &stored_block.block.transactions {
-                let tx = all_block_txs.get(hash).unwrap();
+                let tx = match all_block_txs.get(hash) {
+                    Some(tx) => tx,
+                    None => continue,
+                };
                 let tx_in = get_genesis_tx_in_display(tx);
                 info!("Genesis Transaction: Hash:{} -> TxIn:{}", hash, tx_in);
 
@@ -801,7 +1045,7 @@ impl StorageNode {
             }
         }
 
-        last_block_stored_info
+        Ok((last_block_stored_info, orphaned))
     }
 
     ///Stores a completed block including transactions and mining transactions.
@@ -857,7 +1101,7 @@ impl StorageNode {
         }
 
         let batch = batch.done();
-        self_db.write(batch).unwrap();
+        self_db.write(batch)?;
         Ok(status)
     }
 
@@ -917,16 +1161,14 @@ impl StorageNode {
     pub async fn flood_closing_events(&mut self) -> Result<bool> {
         self.node
             .send_to_all(Some(self.mempool_addr).into_iter(), MempoolRequest::Closing)
-            .await
-            .unwrap();
+            .await?;
 
         self.node
             .send_to_all(
                 self.node_raft.raft_peer_addrs().copied(),
                 StorageRequest::Closing,
             )
-            .await
-            .unwrap();
+            .await?;
 
         Ok(self.shutdown_group.is_empty())
     }
@@ -944,20 +1186,20 @@ impl StorageNode {
         if peer == self.mempool_addr {
             return Some(Response {
                 success: true,
-                reason: "Mempool Shutdown",
+                reason: ResponseReason::MempoolShutdown,
             });
         }
 
         if !self.shutdown_group.is_empty() {
             return Some(Response {
                 success: true,
-                reason: "Shutdown pending",
+                reason: ResponseReason::ShutdownPending,
             });
         }
 
         Some(Response {
             success: true,
-            reason: "Shutdown",
+            reason: ResponseReason::Shutdown,
         })
     }
 
@@ -970,8 +1212,9 @@ impl StorageNode {
     async fn receive_block(
         &mut self,
         peer: SocketAddr,
-        mined_block: Option<MinedBlock>,
+        mined_block: Option<Arc<MinedBlock>>,
     ) -> Option<Response> {
+        let mined_block = mined_block.map(Arc::unwrap_or_clone);
         let (common, extra_info) = if let Some(MinedBlock { common, extra_info }) = mined_block {
             (common, extra_info)
         } else {
@@ -983,7 +1226,47 @@ impl StorageNode {
             debug!("Block received not added. PoW invalid: {}", e);
             return Some(Response {
                 success: false,
-                reason: "Block received not added. PoW invalid",
+                reason: ResponseReason::BlockReceivedNotAddedPowInvalid,
+            });
+        }
+
+        let timestamp_check = {
+            let db = self.db.lock().unwrap();
+            let header = &common.block.header;
+            let timestamps = recent_block_timestamps(&db, header.b_num);
+            let now = get_timestamp_now();
+            check_block_timestamp(
+                header.timestamp,
+                &timestamps,
+                now,
+                self.max_block_time_drift,
+            )
+        };
+        if let Err(e) = timestamp_check {
+            debug!("Block received not added. Timestamp invalid: {}", e);
+            return Some(Response {
+                success: false,
+                reason: ResponseReason::BlockReceivedNotAddedTimestampInvalid,
+            });
+        }
+
+        if !block_txs_match_hashes(&common) {
+            debug!("Block received not added. Transaction hashes mismatch");
+            return Some(Response {
+                success: false,
+                reason: ResponseReason::BlockReceivedNotAddedTransactionHashesMismatch,
+            });
+        }
+
+        let spent = {
+            let db = self.db.lock().unwrap();
+            stored_block_spent_outputs(&db, &common.block_txs)
+        };
+        if !block_txs_are_valid(&common, &spent) {
+            debug!("Block received not added. Transactions invalid");
+            return Some(Response {
+                success: false,
+                reason: ResponseReason::BlockReceivedNotAddedTransactionsInvalid,
             });
         }
 
@@ -999,7 +1282,7 @@ impl StorageNode {
 
         Some(Response {
             success: true,
-            reason: "Block received to be added",
+            reason: ResponseReason::BlockReceivedToBeAdded,
         })
     }
 
@@ -1010,12 +1293,10 @@ impl StorageNode {
             let key_run = match db.get_cf(DB_COL_INTERNAL, RAFT_KEY_RUN) {
                 Ok(Some(key_run)) => deserialize::<u64>(&key_run)? + 1,
                 Ok(None) => 0,
-                Err(e) => panic!("Error accessing db: {:?}", e),
+                Err(e) => return Err(e.into()),
             };
             debug!("load_local_db: key_run update to {:?}", key_run);
-            if let Err(e) = db.put_cf(DB_COL_INTERNAL, RAFT_KEY_RUN, &serialize(&key_run)?) {
-                panic!("Error accessing db: {:?}", e);
-            }
+            db.put_cf(DB_COL_INTERNAL, RAFT_KEY_RUN, &serialize(&key_run)?)?;
             key_run
         });
 
@@ -1024,13 +1305,72 @@ impl StorageNode {
             match db.get_cf(DB_COL_INTERNAL, LAST_CONTIGUOUS_BLOCK_KEY) {
                 Ok(Some(b_num)) => Some(deserialize::<u64>(&b_num)?),
                 Ok(None) => None,
-                Err(e) => panic!("Error accessing db: {:?}", e),
+                Err(e) => return Err(e.into()),
             }
         });
 
+        let divergence = {
+            let db = self.db.lock().unwrap();
+            self.checkpoints
+                .first_divergence(|b_num| stored_block_hash(&db, b_num))
+        };
+        if let Some(b_num) = divergence {
+            error!("Stored block {} diverges from checkpoint", b_num);
+            return Err(StorageError::ConfigError(
+                "Stored chain diverges from a checkpoint",
+            ));
+        }
+
         Ok(self)
     }
 
+    /// Check a fetched block agrees with the checkpoints and links to the stored chain
+    ///
+    /// ### Arguments
+    ///
+    /// * `self_db` - Database holding the stored chain
+    /// * `block`   - Fetched block and its transactions
+    fn verify_fetched_block(
+        &self,
+        self_db: &SimpleDb,
+        (b_num, items): &FetchedBlockChain,
+    ) -> std::result::Result<(), &'static str> {
+        let item = items
+            .iter()
+            .find(|item| match item.item_meta {
+                BlockchainItemMeta::Block { block_num, .. } => block_num == *b_num,
+                BlockchainItemMeta::Tx { .. } => false,
+            })
+            .ok_or("Block not specified")?;
+        let block_hash = str::from_utf8(&item.key).map_err(|_| "Non UTF-8 block hash")?;
+        if !self.checkpoints.verify(*b_num, block_hash) {
+            return Err("Block diverges from a checkpoint");
+        }
+        if let Ok(stored) = deserialize::<StoredSerializingBlock>(&item.data) {
+            let timestamps = recent_block_timestamps(self_db, *b_num);
+            let now = get_timestamp_now();
+            let timestamp = stored.block.header.timestamp;
+            check_block_timestamp(timestamp, &timestamps, now, self.max_block_time_drift)
+                .map_err(|_| "Block timestamp invalid")?;
+        }
+
+        let before_last_checkpoint = self
+            .checkpoints
+            .last_height()
+            .is_some_and(|last| *b_num <= last);
+        let previous = b_num
+            .checked_sub(1)
+            .and_then(|b| stored_block_hash(self_db, b));
+        if let (true, Some(previous)) = (before_last_checkpoint, previous) {
+            let stored: StoredSerializingBlock =
+                deserialize(&item.data).map_err(|_| "Invalid block")?;
+            if stored.block.header.previous_hash.as_ref() != Some(&previous) {
+                return Err("Block does not link to the stored chain");
+            }
+        }
+        Ok(())
+    }
+
     /// Get `Node` member
     pub fn get_node(&self) -> &Node {
         &self.node
@@ -1043,7 +1383,7 @@ impl StorageInterface for StorageNode {
         self.blockchain_item_fetched = Some((key, item, peer));
         Response {
             success: true,
-            reason: "Blockchain item fetched from storage",
+            reason: ResponseReason::BlockchainItemFetchedFromStorage,
         }
     }
 
@@ -1060,6 +1400,18 @@ impl StorageInterface for StorageNode {
             let mut self_db = self.db.lock().unwrap();
             let b_num = block.0;
 
+            if let Err(e) = self.verify_fetched_block(&self_db, &block) {
+                error!(
+                    "receive_blockchain_item from {} refused block {}: {}",
+                    peer, b_num, e
+                );
+                self.catchup_fetch.refetch_from_next_peer(b_num);
+                return Response {
+                    success: false,
+                    reason: ResponseReason::BlockchainItemReceivedBlockFailed,
+                };
+            }
+
             let result = match self.node_raft.get_last_block_stored() {
                 Some(last_stored) if last_stored.block_num >= b_num => {
                     let contiguous = self.catchup_fetch.check_contiguous_block_num(b_num);
@@ -1074,10 +1426,8 @@ impl StorageInterface for StorageNode {
                 Ok(status) => {
                     self.catchup_fetch.update_contiguous_block_num(status);
                     self.catchup_fetch.set_first_timeout();
-                    let reason = if is_complete {
-                        "Blockchain item received: Block stored(Done)"
-                    } else {
-                        "Blockchain item received: Block stored"
+                    let reason = ResponseReason::BlockchainItemStored {
+                        complete: is_complete,
                     };
 
                     info!("{}(b_num = {})", reason, b_num);
@@ -1093,7 +1443,7 @@ impl StorageInterface for StorageNode {
                     );
                     Response {
                         success: false,
-                        reason: "Blockchain item received: Block failed",
+                        reason: ResponseReason::BlockchainItemReceivedBlockFailed,
                     }
                 }
             }
@@ -1104,7 +1454,7 @@ impl StorageInterface for StorageNode {
 
             Response {
                 success: true,
-                reason: "Blockchain item received",
+                reason: ResponseReason::BlockchainItemReceived,
             }
         }
     }
@@ -1112,7 +1462,7 @@ impl StorageInterface for StorageNode {
     fn get_history(&self, _start_time: &u64, _end_time: &u64) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 
@@ -1121,28 +1471,28 @@ impl StorageInterface for StorageNode {
 
         Response {
             success: true,
-            reason: "Address added to whitelist",
+            reason: ResponseReason::AddressAddedToWhitelist,
         }
     }
 
     fn get_unicorn_table(&self, _n_last_items: Option<u64>) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 
     fn receive_pow(&self, _pow: ProofOfWork) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 
     fn receive_contracts(&self, _contract: Contract) -> Response {
         Response {
             success: false,
-            reason: "Not implemented yet",
+            reason: ResponseReason::NotImplementedYet,
         }
     }
 }
@@ -1235,6 +1585,73 @@ pub fn all_ordered_stored_block_tx_hashes<'a>(
     all_txs.enumerate().map(|(idx, v)| (idx as u32, v))
 }
 
+/// Check the transactions of a received block are the ones it lists, hashing them in parallel
+///
+/// Every transaction listed by the block must be provided and, past the
+/// genesis block whose seed transactions are named, keyed by its hash.
+/// Signatures and inputs are checked by `block_txs_are_valid`.
+///
+/// ### Arguments
+///
+/// * `common` - The received block and its transactions
+fn block_txs_match_hashes(common: &CommonBlockInfo) -> bool {
+    let block = &common.block;
+    let mining_tx_hash = &block.header.nonce_and_mining_tx_hash.1;
+    let mut listed = block.transactions.iter().chain(Some(mining_tx_hash));
+    if !listed.all(|tx_hash| common.block_txs.contains_key(tx_hash)) {
+        return false;
+    }
+
+    block.header.b_num == 0
+        || common
+            .block_txs
+            .par_iter()
+            .all(|(tx_hash, tx)| construct_tx_hash(tx) == *tx_hash)
+}
+
+/// Check the signatures and spent outputs of the transactions of a received block,
+/// validating them in parallel
+///
+/// The spent outputs are read once from the stored chain, a snapshot shared
+/// by the validation threads. No output may be spent twice in the block.
+/// The genesis block and the mining transactions are not checked here.
+///
+/// ### Arguments
+///
+/// * `common` - The received block and its transactions
+/// * `spent`  - Outputs spent by the block transactions
+fn block_txs_are_valid(common: &CommonBlockInfo, spent: &BTreeMap<OutPoint, TxOut>) -> bool {
+    let header = &common.block.header;
+    if header.b_num == 0 {
+        return true;
+    }
+
+    let mut out_ps = BTreeSet::new();
+    let mut all_out_ps = common
+        .block_txs
+        .values()
+        .flat_map(|tx| tx.inputs.iter())
+        .filter_map(|tx_in| tx_in.previous_out.as_ref());
+    if !all_out_ps.all(|out_p| out_ps.insert(out_p)) {
+        return false;
+    }
+
+    common
+        .block_txs
+        .par_iter()
+        .filter(|(_, tx)| !tx.is_coinbase())
+        .all(|(_, tx)| tx_spends_are_valid(tx, header.b_num, header.timestamp, |v| spent.get(v)))
+}
+
+/// Compact the storage db on the storage db pool
+///
+/// ### Arguments
+///
+/// * `db` - Database to compact
+pub async fn compact_storage_db(db: Arc<Mutex<SimpleDb>>) {
+    spawn_db(DbPool::Storage, move || db.lock().unwrap().compact()).await
+}
+
 /// Get the stored value at the given key
 ///
 /// ### Arguments
@@ -1306,6 +1723,115 @@ pub fn indexed_tx_hash_key(b_num: u64, tx_num: u32) -> String {
     format!("{INDEXED_TX_HASH_PREFIX_KEY}{b_num:016x}_{tx_num:08x}")
 }
 
+/// Hash of the stored block at the given height
+///
+/// ### Arguments
+///
+/// * `db`    - Database holding the chain
+/// * `b_num` - The block number
+pub fn stored_block_hash(db: &SimpleDb, b_num: u64) -> Option<String> {
+    let key = indexed_block_hash_key(b_num);
+    let pointer = ok_or_warn(db.get_cf(DB_COL_BC_NAMED, key), "stored_block_hash")?;
+    let (_, _, hash) = decode_version_pointer(&pointer);
+    str::from_utf8(hash).ok().map(str::to_owned)
+}
+
+/// Version pointer, hash and content of the canonical block stored at the given height
+///
+/// ### Arguments
+///
+/// * `db`    - Database holding the chain
+/// * `b_num` - The block number
+pub fn canonical_block_at(
+    db: &SimpleDb,
+    b_num: u64,
+) -> Option<(Vec<u8>, String, StoredSerializingBlock)> {
+    let key = indexed_block_hash_key(b_num);
+    let pointer = ok_or_warn(db.get_cf(DB_COL_BC_NAMED, key), "stored_block pointer")?;
+    let (_, cf, hash) = decode_version_pointer(&pointer);
+    let data = ok_or_warn(db.get_cf(cf, hash), "stored_block data")?;
+    let block = deserialize(&data).ok()?;
+    let hash = str::from_utf8(hash).ok()?.to_owned();
+    Some((pointer, hash, block))
+}
+
+/// The outputs spent by the transactions of a block, from the block or the stored chain
+///
+/// ### Arguments
+///
+/// * `db`        - Database holding the chain
+/// * `block_txs` - Transactions of the block, by hash
+pub fn stored_block_spent_outputs(
+    db: &SimpleDb,
+    block_txs: &BTreeMap<String, Transaction>,
+) -> BTreeMap<OutPoint, TxOut> {
+    let mut stored_txs: BTreeMap<&str, Option<Transaction>> = BTreeMap::new();
+    let mut spent = BTreeMap::new();
+    let out_ps = block_txs
+        .values()
+        .flat_map(|tx| tx.inputs.iter())
+        .filter_map(|tx_in| tx_in.previous_out.as_ref());
+    for out_p in out_ps {
+        let tx = match block_txs.get(&out_p.t_hash) {
+            Some(tx) => Some(tx),
+            None => stored_txs
+                .entry(&out_p.t_hash)
+                .or_insert_with(|| stored_tx_at(db, &out_p.t_hash))
+                .as_ref(),
+        };
+        if let Some(tx_out) = tx.and_then(|tx| tx.outputs.get(out_p.n as usize)) {
+            spent.insert(out_p.clone(), tx_out.clone());
+        }
+    }
+    spent
+}
+
+/// The stored transaction with the given hash
+///
+/// ### Arguments
+///
+/// * `db`      - Database holding the chain
+/// * `tx_hash` - The transaction hash
+pub fn stored_tx_at(db: &SimpleDb, tx_hash: &str) -> Option<Transaction> {
+    let pointer = ok_or_warn(db.get_cf(DB_COL_BC_ALL, tx_hash), "stored_tx pointer")?;
+    let (_, cf, key) = decode_version_pointer(&pointer);
+    let data = ok_or_warn(db.get_cf(cf, key), "stored_tx data")?;
+    deserialize(&data).ok()
+}
+
+/// The timestamps of the stored blocks preceding a block, oldest first
+///
+/// ### Arguments
+///
+/// * `db`    - Database holding the chain
+/// * `b_num` - The block number
+pub fn recent_block_timestamps(db: &SimpleDb, b_num: u64) -> Vec<i64> {
+    let start = b_num.saturating_sub(MEDIAN_TIME_SPAN as u64);
+    let stats: BTreeMap<u64, i64> = match b_num.checked_sub(1) {
+        Some(end) => get_block_stats(db, start, end)
+            .into_iter()
+            .map(|stats| (stats.b_num, stats.timestamp))
+            .collect(),
+        None => return Vec::new(),
+    };
+    (start..b_num)
+        .filter_map(|b| {
+            let stored = || canonical_block_at(db, b).map(|(_, _, s)| s.block.header.timestamp);
+            stats.get(&b).copied().or_else(stored)
+        })
+        .collect()
+}
+
+/// The key for an orphaned block
+///
+/// ### Arguments
+///
+/// * `b_num`      - The block number
+/// * `block_hash` - The hash of the orphaned block
+pub fn orphaned_block_hash_key(b_num: u64, block_hash: &str) -> String {
+    format!("{ORPHANED_BLOCK_HASH_PREFIX_KEY}{b_num:016x}_{block_hash}")
+}
+
 /// Decodes a version pointer
 ///
 /// ### Arguments
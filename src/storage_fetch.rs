@@ -1,3 +1,4 @@
+use crate::clock::Clock;
 use crate::configurations::StorageNodeConfig;
 use crate::interfaces::{BlockchainItem, BlockchainItemMeta};
 use crate::storage::{indexed_block_hash_key, indexed_tx_hash_key};
@@ -6,7 +7,7 @@ use std::fmt;
 use std::net::SocketAddr;
 use std::ops::Range;
 use std::time::Duration;
-use tokio::time::{self, Instant};
+use tokio::time::Instant;
 use tracing::{debug, error};
 
 pub type FetchedBlockChain = (u64, Vec<BlockchainItem>);
@@ -17,18 +18,20 @@ pub enum FetchStatus {
     NotContiguous(u64),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 enum FetchReceiveState {
+    #[default]
     NoWait,
-    WaitBlock { b_num: u64 },
-    WaitTxs { b_num: u64, txs: Range<u32> },
-    DoneBlock { b_num: u64 },
-}
-
-impl Default for FetchReceiveState {
-    fn default() -> Self {
-        Self::NoWait
-    }
+    WaitBlock {
+        b_num: u64,
+    },
+    WaitTxs {
+        b_num: u64,
+        txs: Range<u32>,
+    },
+    DoneBlock {
+        b_num: u64,
+    },
 }
 
 impl FetchReceiveState {
@@ -53,12 +56,12 @@ struct FetchReceive {
 
 impl FetchReceive {
     /// Start up fetching blocks
-    pub fn new_block_fetch(start_b_num: u64, target_b_num: u64) -> Self {
+    pub fn new_block_fetch(start_b_num: u64, target_b_num: u64, now: Instant) -> Self {
         let b_num = start_b_num;
         Self {
             target_b_num,
             state: FetchReceiveState::WaitBlock { b_num },
-            timeout_at: (Instant::now(), false),
+            timeout_at: (now, false),
             blockchain_items: Default::default(),
         }
     }
@@ -137,11 +140,13 @@ pub struct StorageFetch {
     last_contiguous_block_num: Option<u64>,
     /// Target and receive state for catch up
     to_receive: Option<FetchReceive>,
+    /// Clock used for fetch timeouts
+    clock: Clock,
 }
 
 impl StorageFetch {
     /// Initialize with database info
-    pub async fn new(config: &StorageNodeConfig, addr: SocketAddr) -> Self {
+    pub async fn new(config: &StorageNodeConfig, addr: SocketAddr, clock: Clock) -> Self {
         let timeout_duration = Duration::from_millis(config.storage_catchup_duration as u64);
         let storage_nodes_filtered = config
             .storage_nodes
@@ -163,6 +168,7 @@ impl StorageFetch {
             fetch_peer_idx: 0,
             last_contiguous_block_num: None,
             to_receive: None,
+            clock,
         }
     }
 
@@ -203,7 +209,11 @@ impl StorageFetch {
                 .last_contiguous_block_num
                 .map(|v| v + 1)
                 .unwrap_or_default();
-            Some(FetchReceive::new_block_fetch(next, block_num))
+            Some(FetchReceive::new_block_fetch(
+                next,
+                block_num,
+                self.clock.now(),
+            ))
         };
         true
     }
@@ -211,7 +221,7 @@ impl StorageFetch {
     /// Provvide the key to fetch
     pub async fn timeout_fetch_blockchain_item(&self) -> Option<()> {
         if let Some(to_receive) = &self.to_receive {
-            time::sleep_until(to_receive.timeout_at.0).await;
+            self.clock.sleep_until(to_receive.timeout_at.0).await;
             Some(())
         } else {
             None
@@ -222,7 +232,7 @@ impl StorageFetch {
     pub fn set_retry_timeout(&mut self) -> bool {
         if let Some(to_receive) = &mut self.to_receive {
             let was_retry = to_receive.timeout_at.1;
-            to_receive.timeout_at = (Instant::now() + self.timeout_duration, true);
+            to_receive.timeout_at = (self.clock.now() + self.timeout_duration, true);
             was_retry
         } else {
             false
@@ -232,7 +242,7 @@ impl StorageFetch {
     /// Update next timeout
     pub fn set_first_timeout(&mut self) {
         if let Some(to_receive) = &mut self.to_receive {
-            to_receive.timeout_at = (Instant::now(), false);
+            to_receive.timeout_at = (self.clock.now(), false);
         }
     }
 
@@ -253,6 +263,24 @@ impl StorageFetch {
         };
     }
 
+    /// Fetch again from the given block, from the next peer
+    ///
+    /// ### Arguments
+    ///
+    /// * `b_num` - First block to fetch again
+    pub fn refetch_from_next_peer(&mut self, b_num: u64) {
+        self.change_to_next_fetch_peer();
+        let target = self
+            .to_receive
+            .as_ref()
+            .map_or(b_num, |r| r.target().max(b_num));
+        self.to_receive = Some(FetchReceive::new_block_fetch(
+            b_num,
+            target,
+            self.clock.now(),
+        ));
+    }
+
     /// Process received item
     pub fn receive_blockchain_items(
         &mut self,
@@ -1,9 +1,10 @@
 use crate::active_raft::ActiveRaft;
+use crate::clock::Clock;
 use crate::configurations::StorageNodeConfig;
 use crate::constants::DB_PATH;
 use crate::db_utils::{self, SimpleDb, SimpleDbError, SimpleDbSpec};
 use crate::interfaces::{BlockStoredInfo, CommonBlockInfo, MinedBlockExtraInfo};
-use crate::raft::{RaftCommit, RaftCommitData, RaftData, RaftMessageWrapper};
+use crate::raft::{RaftCommit, RaftCommitData, RaftData, RaftMessageWrapper, SharedRaftMetrics};
 use crate::raft_util::{RaftContextKey, RaftInFlightProposals};
 use crate::utils::{create_socket_addr_for_list, BackupCheck};
 use bincode::{deserialize, serialize};
@@ -13,7 +14,7 @@ use std::fmt;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::time::Duration;
-use tracing::{debug, trace, warn};
+use tracing::{debug, error, trace, warn};
 use tw_chain::crypto::sha3_256;
 
 pub const DB_SPEC: SimpleDbSpec = SimpleDbSpec {
@@ -113,7 +114,8 @@ impl StorageRaft {
     ///
     /// * `config`  - Configuration option for a storage node.
     /// * `raft_db` - Override raft db to use.
-    pub async fn new(config: &StorageNodeConfig, raft_db: Option<SimpleDb>) -> Self {
+    /// * `clock`   - Clock used for raft ticks.
+    pub async fn new(config: &StorageNodeConfig, raft_db: Option<SimpleDb>, clock: Clock) -> Self {
         let use_raft = config.storage_raft != 0;
 
         if config.backup_restore.unwrap_or(false) {
@@ -131,7 +133,9 @@ impl StorageRaft {
                 .unwrap_or_default(),
             use_raft,
             Duration::from_millis(config.storage_raft_tick_timeout as u64),
+            Default::default(),
             db_utils::new_db(config.storage_db_mode, &DB_SPEC, raft_db, None),
+            clock,
         );
 
         let first_raft_peer = config.storage_node_idx == 0 || !raft_active.use_raft();
@@ -171,6 +175,11 @@ impl StorageRaft {
         self.raft_active.raft_loop()
     }
 
+    /// Metrics of the raft loop.
+    pub fn raft_metrics(&self) -> SharedRaftMetrics {
+        self.raft_active.metrics()
+    }
+
     /// Signal to the raft loop to complete
     pub async fn close_raft_loop(&mut self) {
         self.raft_active.close_raft_loop().await
@@ -227,7 +236,13 @@ impl StorageRaft {
             None
         } else {
             warn!("apply_snapshot called self.consensused updated");
-            self.consensused = deserialize(&consensused_ser).unwrap();
+            self.consensused = match deserialize(&consensused_ser) {
+                Ok(consensused) => consensused,
+                Err(e) => {
+                    error!("apply_snapshot: invalid snapshot ignored {:?}", e);
+                    return None;
+                }
+            };
             self.set_ignore_dedeup_b_num_less_than_current();
             Some(CommittedItem::Snapshot)
         }
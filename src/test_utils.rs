@@ -2,14 +2,16 @@
 //! to send a receive requests & responses, and generally to test the behavior and
 //! correctness of the mempool, miner, & storage modules.
 
+use crate::clock::Clock;
+use crate::comms_handler::network_sim::NetworkSim;
 use crate::comms_handler::{test_tls_certificates, Node, TcpTlsConfig, TcpTlsListner};
 use crate::configurations::{
     DbMode, ExtraNodeParams, MempoolNodeConfig, MinerNodeConfig, NodeSpec, PreLaunchNodeConfig,
-    PreLaunchNodeType, StorageNodeConfig, TlsSpec, UserAutoGenTxSetup, UserNodeConfig, UtxoSetSpec,
-    WalletTxSpec,
+    PreLaunchNodeType, StorageNodeConfig, TlsSpec, TxOutSpec, UserAutoGenTxSetup, UserNodeConfig,
+    UtxoSetSpec, WalletTxSpec,
 };
 use crate::constants::{DB_PATH, DB_PATH_TEST, WALLET_PATH};
-use crate::interfaces::{InitialIssuance, Response};
+use crate::interfaces::{InitialIssuance, Response, ResponseReason};
 use crate::mempool::MempoolNode;
 use crate::mempool_raft::MinerWhitelist;
 use crate::miner::MinerNode;
@@ -24,10 +26,11 @@ use crate::user::{
     make_rb_payment_send_tx_and_request, UserNode,
 };
 use crate::utils::{
-    concat_maps, decode_pub_key, decode_secret_key, get_test_common_unicorn,
-    loop_connnect_to_peers_async, loop_wait_connnect_to_peers_async, make_utxo_set_from_seed,
-    LocalEventSender, ResponseResult, StringError,
+    concat_maps, create_valid_transaction_with_ins_outs, decode_pub_key, decode_secret_key,
+    get_test_common_unicorn, loop_connnect_to_peers_async, loop_wait_connnect_to_peers_async,
+    make_utxo_set_from_seed, LocalEventSender, ResponseResult, StringError,
 };
+use crate::wallet::{KeyGen, WalletDb};
 use futures::future::join_all;
 use std::collections::{BTreeMap, BTreeSet};
 use std::future::Future;
@@ -40,6 +43,7 @@ use tracing::error_span;
 use tracing::info;
 use tracing_futures::Instrument;
 use tw_chain::crypto::sign_ed25519 as sign;
+use tw_chain::crypto::sign_ed25519::{PublicKey, SecretKey};
 use tw_chain::primitives::asset::{Asset, TokenAmount};
 use tw_chain::primitives::transaction::{OutPoint, Transaction, TxIn, TxOut};
 use tw_chain::script::lang::Script;
@@ -68,6 +72,8 @@ pub struct Network {
     dead_nodes: BTreeSet<String>,
     /// Extra params to use for node construction
     extra_params: BTreeMap<String, ExtraNodeParams>,
+    /// Clock used by the nodes timers
+    clock: Clock,
 }
 
 /// Represents a virtual network configuration.
@@ -101,6 +107,8 @@ pub struct NetworkConfig {
     pub peer_limit: usize,
     pub address_aggregation_limit: Option<usize>,
     pub initial_issuances: Vec<InitialIssuance>,
+    /// Seed of the user and miner nodes key-pairs, random if unset
+    pub key_gen_seed: Option<u64>,
 }
 
 /// Node info to create node
@@ -142,6 +150,7 @@ impl TestTlsSpec {
             untrusted_names: Some(self.pem_certificates_with_ca.keys().cloned().collect()),
             pem_certificate_override: None,
             pem_pkcs8_private_key_override: None,
+            pin_raft_peer_certificates: false,
         }
     }
 }
@@ -162,7 +171,18 @@ impl Network {
     ///
     /// * `config` - Holds the values to instanciate a Network object
     pub async fn create_from_config(config: &NetworkConfig) -> Self {
+        Self::create_from_config_with_clock(config, Clock::System).await
+    }
+
+    ///Creates a Network instance using a config object, with nodes timers using the clock
+    ///
+    /// ###Arguments
+    ///
+    /// * `config` - Holds the values to instanciate a Network object
+    /// * `clock`  - Clock for the nodes timers, i.e a manual clock advanced by the test
+    pub async fn create_from_config_with_clock(config: &NetworkConfig, clock: Clock) -> Self {
         let mut value = Self::create_stopped_from_config(config);
+        value.clock = clock;
         value.re_spawn_dead_nodes().await;
         value
     }
@@ -185,9 +205,20 @@ impl Network {
             raft_loop_handles: Default::default(),
             dead_nodes: dead,
             extra_params: Default::default(),
+            clock: Default::default(),
         }
     }
 
+    /// Clock used by the nodes timers
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Set the clock used by nodes spawned from now on
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
     /// Kill all nodes.
     pub async fn close_raft_loops_and_drop(mut self) -> BTreeMap<String, ExtraNodeParams> {
         close_raft_loops(&self.arc_nodes, &mut self.raft_loop_handles).await;
@@ -251,7 +282,13 @@ impl Network {
         // Re-spawn specified nodes
         let mut arc_nodes = BTreeMap::new();
         for name in names {
-            let extra = self.extra_params.remove(name).unwrap_or_default();
+            let mut extra = self.extra_params.remove(name).unwrap_or_default();
+            extra.clock = self.clock.clone();
+            if let (Some(seed), KeyGen::Random) = (self.config.key_gen_seed, &extra.key_gen) {
+                let node_info = &self.instance_info.node_infos[name];
+                let node_type = format!("{:?}", node_info.node_type);
+                extra.key_gen = KeyGen::seeded(seed, &node_type, node_info.index);
+            }
             let arc_node = init_arc_node(name, &self.config, &self.instance_info, extra).await;
             arc_nodes.insert(name.clone(), arc_node);
             self.dead_nodes.remove(name);
@@ -364,7 +401,7 @@ impl Network {
             .iter()
             .map(|n| self.instance_info.node_infos[n].node_spec)
             .collect();
-        connect_all_nodes(&self.arc_nodes, &dead_addr).await;
+        connect_all_nodes(&self.arc_nodes, &dead_addr, &self.clock).await;
     }
 
     ///Returns a mutable reference to the miner node with the matching name
@@ -416,6 +453,18 @@ impl Network {
         }
     }
 
+    ///Apply simulated network conditions to all frames sent by the current nodes
+    ///
+    /// ### Arguments
+    ///
+    /// * `sim` - Simulated network shared by the nodes, or None to remove it.
+    pub async fn set_network_sim(&self, sim: Option<NetworkSim>) {
+        for node in self.arc_nodes.values() {
+            let (node_conn, _, _) = connect_info_peers(node).await;
+            node_conn.set_network_sim(sim.clone()).await;
+        }
+    }
+
     ///Get the requested node local event tx
     ///
     /// ### Arguments
@@ -521,6 +570,16 @@ impl NetworkConfig {
         self.nodes.get_mut(&node_type).unwrap()
     }
 
+    pub fn with_mempool_seed_utxo(mut self, seed: UtxoSetSpec) -> Self {
+        self.mempool_seed_utxo = seed;
+        self
+    }
+
+    pub fn with_key_gen_seed(mut self, seed: u64) -> Self {
+        self.key_gen_seed = Some(seed);
+        self
+    }
+
     pub fn with_raft(mut self, use_raft: bool) -> Self {
         self.mempool_raft = use_raft;
         self.storage_raft = use_raft;
@@ -944,7 +1003,12 @@ pub async fn disconnect_all_nodes(arc_nodes: &BTreeMap<String, ArcNode>) {
 ///
 /// * `arc_nodes`   - Nodes to use.
 /// * `dead`        - Dead nodes to ignore.
-pub async fn connect_all_nodes(arc_nodes: &BTreeMap<String, ArcNode>, dead: &BTreeSet<SocketAddr>) {
+/// * `clock`       - Clock used for the delay between connection attempts.
+pub async fn connect_all_nodes(
+    arc_nodes: &BTreeMap<String, ArcNode>,
+    dead: &BTreeSet<SocketAddr>,
+    clock: &Clock,
+) {
     // Need to connect first so Raft messages can be sent.
     info!("Start connect to peers");
     for (name, node) in arc_nodes {
@@ -952,7 +1016,7 @@ pub async fn connect_all_nodes(arc_nodes: &BTreeMap<String, ArcNode>, dead: &BTr
         let (node_conn, mut addrs, _) = connect_info_peers(node).await;
         addrs.retain(|a| !dead.contains(a));
 
-        loop_connnect_to_peers_async(node_conn, addrs, None, local_event_tx).await;
+        loop_connnect_to_peers_async(node_conn, addrs, None, local_event_tx, clock).await;
         info!(?name, "Peer connect complete");
     }
 
@@ -962,7 +1026,7 @@ pub async fn connect_all_nodes(arc_nodes: &BTreeMap<String, ArcNode>, dead: &BTr
         let (node_conn, _, mut expected_connected_addrs) = connect_info_peers(node).await;
         expected_connected_addrs.retain(|a| !dead.contains(a));
 
-        loop_wait_connnect_to_peers_async(node_conn, expected_connected_addrs).await;
+        loop_wait_connnect_to_peers_async(node_conn, expected_connected_addrs, None).await;
     }
     info!("Peers connect complete: all connected");
 }
@@ -1056,10 +1120,20 @@ async fn init_miner(
         routes_pow: config.routes_pow.clone(),
         backup_block_modulo: Default::default(),
         backup_restore: config.backup_restore,
+        backup_signing_key: None,
+        identity_file: None,
+        db_threads: None,
+        db_compaction_interval: None,
         static_miner_address: config.static_miner_address.clone(),
         mining_api_key: config.mining_api_key.clone(),
         peer_limit: config.peer_limit,
+        network_id: None,
+        peer_ip_filter: None,
+        event_queue_capacity: None,
+        local_event_capacity: None,
+        peer_wait_timeout: None,
         address_aggregation_limit: config.address_aggregation_limit,
+        miner_wallet_kdf: None,
     };
     let info_str = format!("{} -> {}", name, node_info.node_spec);
     info!("New Miner {}", info_str);
@@ -1109,12 +1183,25 @@ async fn init_storage(
         storage_raft,
         storage_api_port: 3001,
         storage_api_use_tls: true,
+        storage_api_client_auth: None,
         storage_raft_tick_timeout: 200 / config.test_duration_divider,
         storage_catchup_duration: 2000 / config.test_duration_divider,
         routes_pow: Default::default(),
         backup_block_modulo: config.backup_block_modulo,
         backup_restore: config.backup_restore,
+        backup_signing_key: None,
+        identity_file: None,
+        db_threads: None,
+        db_compaction_interval: None,
         peer_limit: config.peer_limit,
+        network_id: None,
+        peer_ip_filter: None,
+        event_queue_capacity: None,
+        local_event_capacity: None,
+        peer_wait_timeout: None,
+        storage_event_bus: None,
+        storage_checkpoints: None,
+        storage_max_block_time_drift: None,
     };
     let info = format!("{} -> {}", name, node_info.node_spec);
     info!("New Storage {}", info);
@@ -1174,22 +1261,42 @@ async fn init_mempool(
         mempool_raft_tick_timeout: 200 / config.test_duration_divider,
         mempool_mining_event_timeout: 500 / config.test_duration_divider,
         mempool_transaction_timeout: 100 / config.test_duration_divider,
+        mempool_local_tx_flush_len: None,
+        mempool_local_tx_flush_timeout: None,
+        max_output_data_size: None,
+        max_tx_data_size: None,
+        mempool_reward_schedule: None,
+        db_compaction_interval: None,
         mempool_seed_utxo: config.mempool_seed_utxo.clone(),
         mempool_genesis_tx_in: config.mempool_genesis_tx_in.clone(),
+        mempool_seed_utxo_file: None,
+        mempool_genesis_allocation: None,
         mempool_partition_full_size: config.mempool_partition_full_size,
         mempool_minimum_miner_pool_len: config.mempool_minimum_miner_pool_len,
         jurisdiction: "US".to_string(),
         sanction_list: Vec::new(),
         mempool_api_port: 3002,
         mempool_api_use_tls: true,
+        mempool_api_client_auth: None,
         routes_pow: Default::default(),
         backup_block_modulo: config.backup_block_modulo,
         utxo_re_align_block_modulo: config.utxo_re_align_block_modulo,
         backup_restore: config.backup_restore,
+        backup_signing_key: None,
+        identity_file: None,
         enable_trigger_messages_pipeline_reset: config.enable_pipeline_reset,
         mempool_miner_whitelist: config.mempool_miner_whitelist.clone(),
         peer_limit: config.peer_limit,
+        network_id: None,
+        peer_ip_filter: None,
+        event_queue_capacity: None,
+        local_event_capacity: None,
+        peer_wait_timeout: None,
         initial_issuances: config.initial_issuances.clone(),
+        mempool_event_bus: None,
+        mempool_screening: None,
+        mempool_raft_standby: None,
+        mempool_raft_failover_timeout: None,
     };
     let info = format!("{} -> {}", name, node_info.node_spec);
     info!("New Mempool {}", info);
@@ -1238,13 +1345,36 @@ async fn init_user(
             .collect(),
         user_api_port: 3000,
         user_api_use_tls: true,
+        user_api_client_auth: None,
         user_wallet_seeds,
         passphrase: config.passphrase.clone(),
         user_auto_donate: config.user_auto_donate,
         user_test_auto_gen_setup: config.user_test_auto_gen_setup.clone(),
         routes_pow: Default::default(),
         backup_block_modulo: Default::default(),
+        backup_signing_key: None,
+        identity_file: None,
+        db_threads: None,
+        db_compaction_interval: None,
         peer_limit: config.peer_limit,
+        network_id: None,
+        peer_ip_filter: None,
+        event_queue_capacity: None,
+        local_event_capacity: None,
+        peer_wait_timeout: None,
+        user_exchange: None,
+        user_trade_timeout: None,
+        user_remote_signer: None,
+        user_hot_wallet_keys: None,
+        user_event_bus: None,
+        user_wallet_kdf: None,
+        user_light_client: None,
+        user_payment_expiry: None,
+        user_wallet_sync: None,
+        user_coin_selection: None,
+        user_reuse_change_address: None,
+        user_dust_consolidation: None,
+        user_wallets: None,
     };
 
     let info = format!("{} -> {}", name, node_info.node_spec);
@@ -1342,10 +1472,10 @@ pub fn remove_all_node_dbs_in_info(info: &NetworkInstanceInfo) {
 }
 
 /// Future timeout to use for handle_next_event
-fn test_timeout(timeout: Duration) -> impl Future<Output = &'static str> + Unpin {
+fn test_timeout(timeout: Duration) -> impl Future<Output = ResponseReason> + Unpin {
     Box::pin(async move {
         tokio::time::sleep(timeout).await;
-        "Test timeout elapsed"
+        ResponseReason::TestTimeoutElapsed
     })
 }
 
@@ -1355,7 +1485,7 @@ fn check_timeout<E>(
 ) -> Result<Option<Result<Response, E>>, String> {
     if let Some(Ok(Response {
         success: true,
-        reason: "Test timeout elapsed",
+        reason: ResponseReason::TestTimeoutElapsed,
     })) = response
     {
         Err("Test timeout elapsed".to_owned())
@@ -1560,3 +1690,191 @@ pub fn generate_rb_transactions(
 pub fn map_items(details: Vec<(String, u64)>) -> BTreeMap<String, u64> {
     details.into_iter().collect()
 }
+
+/// Builder for signed transactions paying the same amount to each receiver
+#[derive(Clone)]
+pub struct TransactionBuilder {
+    pub_key: PublicKey,
+    secret_key: SecretKey,
+    tx_ins: Vec<(i32, String)>,
+    receivers: Vec<String>,
+    amount: TokenAmount,
+    address_version: Option<u64>,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        let (pub_key, secret_key) = sign::gen_keypair();
+        Self {
+            pub_key,
+            secret_key,
+            tx_ins: Vec::new(),
+            receivers: Vec::new(),
+            amount: TokenAmount(1),
+            address_version: None,
+        }
+    }
+}
+
+impl TransactionBuilder {
+    /// Create a builder signing with a new random keypair
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sign the inputs with the given keypair
+    pub fn with_keypair(mut self, pub_key: PublicKey, secret_key: SecretKey) -> Self {
+        self.pub_key = pub_key;
+        self.secret_key = secret_key;
+        self
+    }
+
+    /// Spend output `n` of the given transaction
+    pub fn spending(mut self, n: i32, t_hash: &str) -> Self {
+        self.tx_ins.push((n, t_hash.to_owned()));
+        self
+    }
+
+    /// Pay the amount to the given address
+    pub fn paying(mut self, address: &str) -> Self {
+        self.receivers.push(address.to_owned());
+        self
+    }
+
+    /// Amount paid to each receiver
+    pub fn with_amount(mut self, amount: TokenAmount) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    /// Address version used to validate the inputs
+    pub fn with_address_version(mut self, address_version: Option<u64>) -> Self {
+        self.address_version = address_version;
+        self
+    }
+
+    /// Public key signing the inputs
+    pub fn pub_key(&self) -> &PublicKey {
+        &self.pub_key
+    }
+
+    /// Build the signed transaction and its hash
+    pub fn build(&self) -> (String, Transaction) {
+        let tx_ins: Vec<_> = self.tx_ins.iter().map(|(n, h)| (*n, h.as_str())).collect();
+        let receivers: Vec<_> = self.receivers.iter().map(|r| r.as_str()).collect();
+        create_valid_transaction_with_ins_outs(
+            &tx_ins,
+            &receivers,
+            &self.pub_key,
+            &self.secret_key,
+            self.amount,
+            self.address_version,
+        )
+    }
+}
+
+/// Builder for the UTXO set seeded in mempool nodes
+#[derive(Default)]
+pub struct SeedUtxoBuilder {
+    seed: UtxoSetSpec,
+}
+
+impl SeedUtxoBuilder {
+    /// Create an empty seed
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add `count` outputs of the same amount to the seed transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `t_hash`     - Hash of the seed transaction.
+    /// * `public_key` - Hex encoded public key owning the outputs.
+    /// * `amount`     - Amount of each output.
+    /// * `count`      - Number of outputs.
+    pub fn with_outputs(
+        mut self,
+        t_hash: &str,
+        public_key: &str,
+        amount: TokenAmount,
+        count: usize,
+    ) -> Self {
+        let tx_outs = self.seed.entry(t_hash.to_owned()).or_default();
+        tx_outs.extend((0..count).map(|_| TxOutSpec {
+            public_key: public_key.to_owned(),
+            amount,
+            locktime: 0,
+        }));
+        self
+    }
+
+    /// Add `count` outputs of the same amount owned by the given key
+    pub fn with_key_outputs(
+        self,
+        t_hash: &str,
+        public_key: &PublicKey,
+        amount: TokenAmount,
+        count: usize,
+    ) -> Self {
+        self.with_outputs(t_hash, &hex::encode(public_key.as_ref()), amount, count)
+    }
+
+    /// Build the seed to use as `mempool_seed_utxo`
+    pub fn build(self) -> UtxoSetSpec {
+        self.seed
+    }
+}
+
+/// Builder for in memory wallets holding payments to one of their addresses
+#[derive(Default)]
+pub struct WalletBuilder {
+    passphrase: Option<String>,
+    payments: Vec<(OutPoint, Asset)>,
+}
+
+impl WalletBuilder {
+    /// Create a builder for an empty wallet
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Encrypt the wallet keys with the given passphrase
+    pub fn with_passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = Some(passphrase.to_owned());
+        self
+    }
+
+    /// Add `count` payments of the same amount, from distinct transactions
+    pub fn with_outpoints(mut self, count: usize, amount: TokenAmount) -> Self {
+        let start = self.payments.len();
+        self.payments.extend((start..start + count).map(|i| {
+            let out_p = OutPoint::new(format!("{i:06}"), 0);
+            (out_p, Asset::Token(amount))
+        }));
+        self
+    }
+
+    /// Add a payment for the given outpoint
+    pub fn with_payment(mut self, out_p: OutPoint, asset: Asset) -> Self {
+        self.payments.push((out_p, asset));
+        self
+    }
+
+    /// Build the wallet, returning it with the address receiving the payments
+    pub async fn build(self) -> (WalletDb, String) {
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, self.passphrase, None).unwrap();
+        let (address, _) = wallet.generate_payment_address().await.unwrap();
+        let payments = self
+            .payments
+            .into_iter()
+            .map(|(out_p, asset)| (out_p, asset, address.clone(), 0))
+            .collect();
+
+        wallet
+            .save_usable_payments_to_wallet(payments, 0)
+            .await
+            .unwrap();
+        (wallet, address)
+    }
+}
@@ -7,19 +7,22 @@ use crate::constants::{NETWORK_VERSION, SANC_LIST_TEST};
 use crate::interfaces::{
     BlockStoredInfo, BlockchainItem, BlockchainItemMeta, BlockchainItemType, CommonBlockInfo,
     DruidPool, MempoolApi, MempoolRequest, MinedBlock, MinedBlockExtraInfo, Response,
-    StorageRequest, StoredSerializingBlock, UserApiRequest, UserRequest, UtxoFetchType, UtxoSet,
-    WinningPoWInfo,
+    ResponseReason, StorageRequest, StoredSerializingBlock, UserApiRequest, UserRequest,
+    UtxoFetchType, UtxoSet, WinningPoWInfo,
 };
 use crate::mempool::MempoolNode;
 use crate::mempool_raft::MinerWhitelist;
 use crate::miner::MinerNode;
+use crate::replay_guard::ReplayTagger;
 use crate::storage::{all_ordered_stored_block_tx_hashes, StorageNode};
 use crate::storage_raft::CompleteBlock;
 use crate::test_utils::{
     generate_rb_transactions, get_test_tls_spec, map_items, node_join_all_checked,
     remove_all_node_dbs, Network, NetworkConfig, NodeType, RbReceiverData, RbSenderData,
+    SeedUtxoBuilder,
 };
 use crate::tracked_utxo::TrackedUtxoBalance;
+use crate::trade::TradeState;
 use crate::transactor::Transactor;
 use crate::user::UserNode;
 use crate::utils::{
@@ -79,6 +82,7 @@ const DEFAULT_SEED_AMOUNT: TokenAmount = TokenAmount(3);
 const BLOCK_RECEIVED: &str = "Block received to be added";
 const BLOCK_STORED: &str = "Block complete stored";
 const BLOCK_RECEIVED_AND_STORED: [&str; 2] = [BLOCK_RECEIVED, BLOCK_STORED];
+const BLOCK_TXS_INVALID: &str = "Block received not added. Transactions invalid";
 
 const SOME_PUB_KEYS: [&str; 3] = [
     COMMON_PUB_KEY,
@@ -246,6 +250,7 @@ async fn full_flow_single_miner_single_raft_with_aggregation_tx_check() {
                 .await
                 .get_wallet_db()
                 .get_known_addresses()
+                .unwrap()
                 .len();
             handle_aggregation_tx = addrs % (address_aggregation_limit - 1) == 0;
         }
@@ -277,6 +282,7 @@ async fn full_flow_single_miner_single_raft_with_aggregation_tx_check() {
         .await
         .get_wallet_db()
         .get_fund_store()
+        .unwrap()
         .transactions()
         .len();
 
@@ -839,6 +845,65 @@ async fn add_transactions_raft_15_nodes() {
     add_transactions(complete_network_config_with_n_mempool_raft(10640, 15)).await;
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn create_first_block_restart_resume_round_no_raft() {
+    test_step_start();
+
+    //
+    // Arrange
+    //
+    let network_config = complete_network_config(11750);
+    let mut network = Network::create_from_config(&network_config).await;
+    let mempool = vec!["mempool1".to_owned()];
+    create_first_block_act(&mut network).await;
+    let expected = mempool_current_mining_block(&mut network, "mempool1").await;
+
+    //
+    // Act
+    //
+    network.close_loops_and_drop_named(&mempool).await;
+    network.re_spawn_nodes_named(&mempool).await;
+    let actual = mempool_current_mining_block(&mut network, "mempool1").await;
+
+    //
+    // Assert
+    //
+    assert!(expected.is_some());
+    assert_eq!(format!("{:?}", actual), format!("{:?}", expected));
+
+    test_step_complete(network).await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn create_first_block_restart_upgrade_no_raft() {
+    test_step_start();
+
+    //
+    // Arrange
+    //
+    let network_config = complete_network_config(11760);
+    let mut network = Network::create_from_config(&network_config).await;
+    let mempool = vec!["mempool1".to_owned()];
+    create_first_block_act(&mut network).await;
+    let before = mempool_current_mining_block(&mut network, "mempool1").await;
+
+    //
+    // Act
+    //
+    network.close_loops_and_drop_named(&mempool).await;
+    network.upgrade_closed_nodes().await;
+    network.re_spawn_nodes_named(&mempool).await;
+    let actual = mempool_current_mining_block(&mut network, "mempool1").await;
+
+    //
+    // Assert
+    //
+    assert!(before.is_some());
+    assert!(actual.is_none());
+
+    test_step_complete(network).await;
+}
+
 async fn add_transactions(network_config: NetworkConfig) {
     add_transactions_common(network_config, &[]).await;
 }
@@ -1240,13 +1305,19 @@ async fn proof_of_work_block_act(
 
                     {
                         let miner_node = network.miner(miner).unwrap();
-                        miner_node.lock().await.update_running_total().await;
+                        miner_node
+                            .lock()
+                            .await
+                            .update_running_total()
+                            .await
+                            .unwrap();
 
                         let running_total = miner_node
                             .lock()
                             .await
                             .get_wallet_db()
                             .get_fund_store()
+                            .unwrap()
                             .running_total()
                             .clone();
 
@@ -1509,6 +1580,45 @@ async fn send_block_to_storage_common(network_config: NetworkConfig, cfg_num: Cf
     test_step_complete(network).await;
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn send_block_to_storage_invalid_signature() {
+    test_step_start();
+
+    //
+    // Arrange
+    //
+    let network_config = complete_network_config(10360);
+    let mut network = Network::create_from_config(&network_config).await;
+    let mempool_nodes = &network_config.nodes[&NodeType::Mempool];
+    let storage_nodes = &network_config.nodes[&NodeType::Storage];
+
+    let transactions = invalid_signature_transactions();
+    let (_, block_info0) = complete_first_block(&network.collect_initial_uxto_txs()).await;
+    let (_, block_info1) = complete_block(1, Some("0"), &transactions).await;
+
+    create_first_block_act(&mut network).await;
+    mempool_all_skip_mining(&mut network, mempool_nodes, &block_info0).await;
+    send_block_to_storage_act(&mut network, CfgNum::All).await;
+
+    let initial_db_count =
+        storage_all_get_stored_key_values_count(&mut network, storage_nodes).await;
+
+    //
+    // Act
+    //
+    storage_inject_send_block_to_storage(&mut network, "mempool1", "storage1", &block_info1).await;
+    storage_handle_event_failure(&mut network, "storage1", BLOCK_TXS_INVALID).await;
+
+    //
+    // Assert
+    //
+    let actual_db_count =
+        storage_all_get_stored_key_values_count(&mut network, storage_nodes).await;
+    assert_eq!(actual_db_count, initial_db_count);
+
+    test_step_complete(network).await;
+}
+
 async fn send_block_to_storage_act(network: &mut Network, cfg_num: CfgNum) {
     let active_nodes = network.all_active_nodes().clone();
     let storage_nodes = &active_nodes[&NodeType::Storage];
@@ -1863,7 +1973,7 @@ async fn reject_payment_txs() {
     let mempool_nodes = &network_config.nodes[&NodeType::Mempool];
 
     let valid_txs = valid_transactions(true);
-    let invalid_txs = vec![
+    let invalid_txs = [
         // New keys not matching utxo_set
         valid_transactions_with(false, DEFAULT_SEED_AMOUNT, false),
         // Too much output amount for given inputs
@@ -2005,12 +2115,14 @@ async fn proof_of_work_reject() {
     //
     // Act
     //
+    let mut tagger = ReplayTagger::default();
     {
         // From node not in partition (user1 is not a miner).
         let request = MempoolRequest::SendPoW {
             block_num,
             nonce: Default::default(),
             coinbase: Default::default(),
+            replay_tag: tagger.next_tag(),
         };
         mempool_inject_next_event(&mut network, "user1", mempool, request).await;
         mempool_handle_error(&mut network, mempool, &["Not block currently mined"]).await;
@@ -2021,6 +2133,7 @@ async fn proof_of_work_reject() {
             block_num: block_num + 1,
             nonce: Default::default(),
             coinbase: Default::default(),
+            replay_tag: tagger.next_tag(),
         };
         mempool_inject_next_event(&mut network, miner, mempool, request).await;
         mempool_handle_error(&mut network, mempool, &["Not block currently mined"]).await;
@@ -2031,9 +2144,14 @@ async fn proof_of_work_reject() {
             block_num,
             nonce: Default::default(),
             coinbase: Default::default(),
+            replay_tag: tagger.next_tag(),
         };
-        mempool_inject_next_event(&mut network, miner, mempool, request).await;
+        mempool_inject_next_event(&mut network, miner, mempool, request.clone()).await;
         mempool_handle_error(&mut network, mempool, &["Coinbase transaction invalid"]).await;
+
+        // Same message sent again
+        mempool_inject_next_event(&mut network, miner, mempool, request).await;
+        mempool_handle_error(&mut network, mempool, &["Replayed message"]).await;
     }
 
     //
@@ -2907,6 +3025,10 @@ pub async fn make_item_based_payment_raft_1_node() {
 
     let wallet_assets_after_actual =
         user_get_wallet_asset_totals_for_tx(&mut network, "user1", "user2").await;
+    let trade_states = (
+        user_get_trade_states(&mut network, "user1").await,
+        user_get_trade_states(&mut network, "user2").await,
+    );
 
     let committed_tx_druid_pool: Vec<Transaction> =
         mempool_all_committed_tx_druid_pool(&mut network, mempool_nodes)
@@ -2964,8 +3086,14 @@ pub async fn make_item_based_payment_raft_1_node() {
     //Assert committed DDE transactions contain valid DDE participation count
     assert!(actual_participants.iter().all(|v| *v == 2));
 
+    //Assert the trade is completed on both user nodes
+    assert_eq!(
+        trade_states,
+        (vec![TradeState::Completed], vec![TradeState::Completed])
+    );
+
     //Assert committed DDE transactions contain valid DDE asset values
-    let expected_assets = vec![
+    let expected_assets = [
         vec![Asset::token_u64(3)],
         vec![Asset::item(
             1,
@@ -3291,8 +3419,8 @@ async fn reject_item_based_payment() {
     };
 
     let rb_txs = generate_rb_transactions(rb_sender_data, rb_receiver_data);
-    let rb_send_txs = vec![rb_txs[0].1.clone(), rb_txs[0].1.clone()];
-    let mut rb_recv_txs = vec![rb_txs[1].1.clone(), rb_txs[1].1.clone()];
+    let rb_send_txs = [rb_txs[0].1.clone(), rb_txs[0].1.clone()];
+    let mut rb_recv_txs = [rb_txs[1].1.clone(), rb_txs[1].1.clone()];
 
     // Invalid participant count
     rb_recv_txs[0].druid_info.as_mut().unwrap().participants = 3;
@@ -3491,7 +3619,35 @@ async fn mempool_re_align_utxo_set_1_node() {
     assert!(all_outpoints_present());
 }
 
-async fn mempool_remove_entry_from_pk_cache<'a>(network: &mut Network, mempool: &str, entry: &str) {
+#[tokio::test(flavor = "current_thread")]
+async fn seeded_key_gen_same_addresses_across_runs() {
+    test_step_start();
+
+    //
+    // Arrange
+    //
+    let network_config = complete_network_config(11700).with_key_gen_seed(7);
+
+    //
+    // Act
+    //
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let mut network = Network::create_from_config(&network_config).await;
+        let user_address = user_generate_payment_address(&mut network, "user1").await;
+        let miner_addresses = node_get_wallet_info(&mut network, "miner1").await.1;
+        runs.push((user_address, miner_addresses));
+        network.close_raft_loops_and_drop().await;
+    }
+
+    //
+    // Assert
+    //
+    assert_eq!(runs[0], runs[1]);
+    assert!(!runs[0].1.contains(&runs[0].0));
+}
+
+async fn mempool_remove_entry_from_pk_cache(network: &mut Network, mempool: &str, entry: &str) {
     let mut c = network.mempool(mempool).unwrap().lock().await;
     c.remove_pk_cache_entry(entry);
 }
@@ -3657,7 +3813,7 @@ async fn node_all_handle_event(network: &mut Network, node_group: &[String], rea
     node_all_handle_different_event(network, node_group, &all_raisons).await
 }
 
-async fn node_all_handle_different_event<'a>(
+async fn node_all_handle_different_event(
     network: &mut Network,
     node_group: &[String],
     all_raisons: &BTreeMap<String, Vec<String>>,
@@ -3722,14 +3878,14 @@ async fn node_get_wallet_info(
         _ => panic!("node not found"),
     };
 
-    let addresses = wallet.get_known_addresses();
+    let addresses = wallet.get_known_addresses().unwrap();
 
-    let fund = wallet.get_fund_store();
+    let fund = wallet.get_fund_store().unwrap();
     let total = fund.running_total().clone();
 
     let mut txs_to_address_and_ammount = BTreeMap::new();
     for (out_p, asset) in fund.into_transactions().into_iter() {
-        let addr = wallet.get_transaction_address(&out_p);
+        let addr = wallet.get_transaction_address(&out_p).unwrap();
         txs_to_address_and_ammount.insert(out_p, (addr, asset));
     }
     (total, addresses, txs_to_address_and_ammount)
@@ -3855,7 +4011,7 @@ async fn mempool_all_handle_error(
     }
 }
 
-async fn mempool_handle_event_for_node<E: Future<Output = &'static str> + Unpin>(
+async fn mempool_handle_event_for_node<E: Future<Output = ResponseReason> + Unpin>(
     c: &mut MempoolNode,
     success_val: bool,
     reason_val: &[&str],
@@ -3864,7 +4020,7 @@ async fn mempool_handle_event_for_node<E: Future<Output = &'static str> + Unpin>
     let addr = c.local_address();
     match c.handle_next_event(exit).await {
         Some(Ok(Response { success, reason }))
-            if success == success_val && reason_val.contains(&reason) =>
+            if success == success_val && reason_val.contains(&reason.as_str()) =>
         {
             info!("Mempool handle_next_event {} success ({})", reason, addr);
         }
@@ -3960,7 +4116,7 @@ async fn mempool_current_block_transactions(
 
 async fn mempool_current_mining_block(network: &mut Network, mempool: &str) -> Option<Block> {
     let c = network.mempool(mempool).unwrap().lock().await;
-    c.get_mining_block().clone()
+    c.get_mining_block().as_deref().cloned()
 }
 
 async fn mempool_committed_current_block_num(network: &mut Network, mempool: &str) -> Option<u64> {
@@ -4014,7 +4170,8 @@ pub async fn mempool_committed_tx_pool(
     mempool: &str,
 ) -> BTreeMap<String, Transaction> {
     let c = network.mempool(mempool).unwrap().lock().await;
-    c.get_committed_tx_pool().clone()
+    let tx_pool = c.get_committed_tx_pool().iter();
+    tx_pool.map(|(k, v)| (k.clone(), v.clone())).collect()
 }
 
 async fn mempool_all_committed_tx_druid_pool(
@@ -4206,10 +4363,10 @@ async fn storage_inject_send_block_to_storage(
     storage: &str,
     block_info: &CompleteBlock,
 ) {
-    let mined_block = Some(MinedBlock {
+    let mined_block = Some(Arc::new(MinedBlock {
         common: block_info.common.clone(),
         extra_info: block_info.extra_info.clone(),
-    });
+    }));
     let request = StorageRequest::SendBlock { mined_block };
     storage_inject_next_event(network, mempool, storage, request).await;
 }
@@ -4357,6 +4514,11 @@ async fn storage_all_send_stored_block(network: &mut Network, storage_group: &[S
     }
 }
 
+async fn storage_handle_event_failure(network: &mut Network, storage: &str, reason_str: &str) {
+    let mut s = network.storage(storage).unwrap().lock().await;
+    storage_handle_event_for_node(&mut s, false, reason_str, &mut test_timeout()).await;
+}
+
 async fn storage_handle_event(network: &mut Network, storage: &str, reason_str: &str) {
     let mut s = network.storage(storage).unwrap().lock().await;
     storage_handle_event_for_node(&mut s, true, reason_str, &mut test_timeout()).await;
@@ -4372,7 +4534,7 @@ async fn storage_all_handle_event(
     }
 }
 
-async fn storage_handle_event_for_node<E: Future<Output = &'static str> + Unpin>(
+async fn storage_handle_event_for_node<E: Future<Output = ResponseReason> + Unpin>(
     s: &mut StorageNode,
     success_val: bool,
     reason_val: &str,
@@ -4381,7 +4543,7 @@ async fn storage_handle_event_for_node<E: Future<Output = &'static str> + Unpin>
     let addr = s.local_address();
     match s.handle_next_event(exit).await {
         Some(Ok(Response { success, reason }))
-            if success == success_val && reason == reason_val =>
+            if success == success_val && reason.as_str() == reason_val =>
         {
             info!(
                 "Storage handle_next_event {} success ({})",
@@ -4440,7 +4602,7 @@ async fn user_handle_error(network: &mut Network, user: &str, reason_val: &str)
     user_handle_event_for_node(&mut u, false, reason_val, &mut test_timeout()).await;
 }
 
-async fn user_handle_event_for_node<E: Future<Output = &'static str> + Unpin>(
+async fn user_handle_event_for_node<E: Future<Output = ResponseReason> + Unpin>(
     u: &mut UserNode,
     success_val: bool,
     reason_val: &str,
@@ -4449,7 +4611,7 @@ async fn user_handle_event_for_node<E: Future<Output = &'static str> + Unpin>(
     let addr = u.local_address();
     match u.handle_next_event(exit).await {
         Some(Ok(Response { success, reason }))
-            if success == success_val && reason == reason_val =>
+            if success == success_val && reason.as_str() == reason_val =>
         {
             info!("User handle_next_event {} success ({})", reason_val, addr);
         }
@@ -4519,7 +4681,7 @@ async fn users_filter_locked_coinbase(network: &mut Network, users: &[&str], b_n
 
 async fn user_filter_locked_coinbase(network: &mut Network, user: &str, b_num: u64) {
     let mut u = network.user(user).unwrap().lock().await;
-    u.filter_locked_coinbase(b_num).await;
+    u.filter_locked_coinbase(b_num).await.unwrap();
 }
 
 async fn user_send_address_request(
@@ -4573,17 +4735,38 @@ async fn user_get_received_utxo_set_keys(network: &mut Network, user: &str) -> V
 
 async fn user_update_running_total(network: &mut Network, user: &str) {
     let mut u = network.user(user).unwrap().lock().await;
-    u.update_running_total().await;
+    u.update_running_total().await.unwrap();
 }
 
 async fn user_get_tokens_held(network: &mut Network, user: &str) -> TokenAmount {
     let u = network.user(user).unwrap().lock().await;
-    u.get_wallet_db().get_fund_store().running_total().tokens
+    u.get_wallet_db()
+        .get_fund_store()
+        .unwrap()
+        .running_total()
+        .tokens
+}
+
+async fn user_get_trade_states(network: &mut Network, user: &str) -> Vec<TradeState> {
+    let u = network.user(user).unwrap().lock().await;
+    let trades = u.get_trades().trades.values();
+    trades.map(|trade| trade.state).collect()
 }
 
 async fn user_get_all_known_addresses(network: &mut Network, user: &str) -> Vec<String> {
     let u = network.user(user).unwrap().lock().await;
-    u.get_wallet_db().get_known_addresses()
+    u.get_wallet_db().get_known_addresses().unwrap()
+}
+
+async fn user_generate_payment_address(network: &mut Network, user: &str) -> String {
+    let u = network.user(user).unwrap().lock().await;
+    let (address, _) = u
+        .get_wallet_db()
+        .clone()
+        .generate_payment_address()
+        .await
+        .unwrap();
+    address
 }
 
 async fn user_trigger_update_wallet_from_utxo_set(
@@ -4716,7 +4899,7 @@ async fn miner_all_handle_event(network: &mut Network, miner_group: &[String], r
     }
 }
 
-async fn miner_handle_event_for_node<E: Future<Output = &'static str> + Unpin>(
+async fn miner_handle_event_for_node<E: Future<Output = ResponseReason> + Unpin>(
     m: &mut MinerNode,
     success_val: bool,
     reason_val: &str,
@@ -4725,7 +4908,7 @@ async fn miner_handle_event_for_node<E: Future<Output = &'static str> + Unpin>(
     let addr = m.local_address();
     match m.handle_next_event(exit).await {
         Some(Ok(Response { success, reason }))
-            if success == success_val && reason == reason_val =>
+            if success == success_val && reason.as_str() == reason_val =>
         {
             info!("Miner handle_next_event {} success ({})", reason_val, addr);
         }
@@ -4803,6 +4986,24 @@ fn valid_transactions(fixed: bool) -> BTreeMap<String, Transaction> {
     valid_transactions_with(fixed, DEFAULT_SEED_AMOUNT, true)
 }
 
+/// Valid transactions with their inputs signed over the wrong data
+fn invalid_signature_transactions() -> BTreeMap<String, Transaction> {
+    let sk = SecretKey::from_slice(&hex::decode(COMMON_SEC_KEY).unwrap()).unwrap();
+    let signature = sign::sign_detached(b"wrong data", &sk);
+
+    let mut transactions = BTreeMap::new();
+    for mut tx in valid_transactions(true).into_values() {
+        let stacks = tx.inputs.iter_mut().map(|i| &mut i.script_signature.stack);
+        for entry in stacks.flatten() {
+            if let StackEntry::Signature(_) = entry {
+                *entry = StackEntry::Signature(signature);
+            }
+        }
+        transactions.insert(construct_tx_hash(&tx), tx);
+    }
+    transactions
+}
+
 fn valid_transactions_with(
     fixed: bool,
     amount: TokenAmount,
@@ -4835,12 +5036,12 @@ fn valid_transactions_with(
 }
 
 fn make_mempool_seed_utxo(seed: &[(i32, &str)], amount: TokenAmount) -> UtxoSetSpec {
-    let seed: Vec<_> = seed
-        .iter()
-        .copied()
-        .map(|(n, v)| (v, (0..n).map(|_| (COMMON_PUB_KEY, amount)).collect()))
-        .collect();
-    make_mempool_seed_utxo_with_info(&seed)
+    let builder = SeedUtxoBuilder::new();
+    seed.iter()
+        .fold(builder, |b, (n, v)| {
+            b.with_outputs(v, COMMON_PUB_KEY, amount, *n as usize)
+        })
+        .build()
 }
 
 fn make_mempool_seed_utxo_with_info(seed: &[(&str, Vec<(&str, TokenAmount)>)]) -> UtxoSetSpec {
@@ -4882,25 +5083,25 @@ fn block_and_partition_evt_in_miner_pow(
 fn panic_on_timeout<E>(response: &Result<Response, E>, tag: &str) {
     if let Ok(Response {
         success: true,
-        reason: "Test timeout elapsed",
+        reason: ResponseReason::TestTimeoutElapsed,
     }) = response
     {
         panic!("Test timeout elapsed - {}", tag);
     }
 }
 
-fn test_timeout() -> impl Future<Output = &'static str> + Unpin {
+fn test_timeout() -> impl Future<Output = ResponseReason> + Unpin {
     Box::pin(async move {
         time::sleep(TIMEOUT_TEST_WAIT_DURATION).await;
-        "Test timeout elapsed"
+        ResponseReason::TestTimeoutElapsed
     })
 }
 
-fn test_timeout_barrier(barrier: &'_ Barrier) -> impl Future<Output = &'static str> + Unpin + '_ {
+fn test_timeout_barrier(barrier: &'_ Barrier) -> impl Future<Output = ResponseReason> + Unpin + '_ {
     Box::pin(async move {
         tokio::select! {
             r = test_timeout() => r,
-            _ = barrier.wait() => "Barrier complete",
+            _ = barrier.wait() => ResponseReason::BarrierComplete,
         }
     })
 }
@@ -5121,6 +5322,7 @@ fn basic_network_config(initial_port: u16) -> NetworkConfig {
         peer_limit: 1000,
         address_aggregation_limit: Some(5),
         initial_issuances: Default::default(),
+        key_gen_seed: None,
     }
 }
 
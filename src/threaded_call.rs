@@ -33,7 +33,7 @@ impl<T: ?Sized> fmt::Debug for ThreadedCallChannel<T> {
     }
 }
 
-pub async fn make_threaded_call<'a, T: ?Sized, R: Send + Sized + 'static>(
+pub async fn make_threaded_call<T: ?Sized, R: Send + Sized + 'static>(
     tx: &mut ThreadedCallSender<T>,
     f: impl FnOnce(&mut T) -> R + Send + Sized + 'static,
     tag: &str,
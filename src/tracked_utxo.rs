@@ -122,7 +122,7 @@ impl TrackedUtxoSet {
                 for op in ops {
                     debug!("OP: {:?}", op);
                     // Ignore `OutPoint` values already present
-                    if known_op.get(op).is_some() {
+                    if known_op.contains(op) {
                         continue;
                     }
 
@@ -147,8 +147,8 @@ impl TrackedUtxoSet {
     /// Get all `script_public_key` values from the current UTXO set
     pub fn get_all_addresses(&self) -> Vec<String> {
         self.base
-            .iter()
-            .filter_map(|(_, tx_out)| tx_out.script_public_key.clone())
+            .values()
+            .filter_map(|tx_out| tx_out.script_public_key.clone())
             .collect::<Vec<String>>()
     }
 }
@@ -0,0 +1,331 @@
+//! Two-party trades between user nodes.
+//!
+//! A trade exchanges an asset of the offering node for an item asset of the
+//! accepting node, in two transactions bound by a DRUID: the mempool node only
+//! commits both halves together. The offer carries the offerer half of the
+//! DRUID, which identifies the trade on both nodes. Each node records its side
+//! of the trade in its wallet, so trades survive restarts, and abandons the
+//! trades not completed within the trade timeout.
+
+use crate::interfaces::RbPaymentData;
+use crate::wallet::{Result, WalletDb};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tw_chain::primitives::asset::Asset;
+
+/// Key for the trades of the node
+pub const TRADES_KEY: &str = "TradesKey";
+
+/// Seconds before a trade not completed is abandoned, when not configured
+pub const DEFAULT_TRADE_TIMEOUT: u64 = 600;
+
+/// Interval between checks for abandoned trades
+pub const TRADE_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Side of the node in a trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradeRole {
+    /// Node sending the offer
+    Offerer,
+    /// Node accepting the offer
+    Acceptor,
+}
+
+/// State of a trade on one node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradeState {
+    /// Offer sent, waiting for the peer acceptance
+    Offered,
+    /// Both halves constructed, own half not submitted yet
+    Accepted,
+    /// Own half submitted to the mempool node
+    Submitted,
+    /// Asset traded for received from the peer
+    Completed,
+    /// Offer rejected by the peer
+    Rejected,
+    /// Not completed within the trade timeout
+    Abandoned,
+}
+
+impl TradeState {
+    /// Whether the trade cannot change state anymore
+    pub fn is_final(self) -> bool {
+        matches!(self, Self::Completed | Self::Rejected)
+    }
+}
+
+/// Side of a trade recorded by a node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub role: TradeRole,
+    pub peer: SocketAddr,
+    pub state: TradeState,
+    /// Asset sent by this node
+    pub send_asset: Asset,
+    /// Address receiving the asset traded for
+    pub receive_address: String,
+    /// DRUID binding both halves, once accepted
+    pub druid: Option<String>,
+    /// Offer kept by the offerer until the peer answers
+    pub offer: Option<RbPaymentData>,
+    /// Time the trade started, in seconds since the epoch
+    pub created_at: i64,
+}
+
+/// Trades of a node by id, the offerer half of the DRUID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Trades {
+    pub trades: BTreeMap<String, TradeRecord>,
+}
+
+impl Trades {
+    /// Record an offer sent to a peer
+    ///
+    /// ### Arguments
+    ///
+    /// * `id`              - Offerer half of the DRUID.
+    /// * `peer`            - Peer the offer is sent to.
+    /// * `offer`           - Offer data needed to construct the offerer half.
+    /// * `receive_address` - Address receiving the item asset.
+    /// * `now`             - Current time, in seconds since the epoch.
+    pub fn offer(
+        &mut self,
+        id: String,
+        peer: SocketAddr,
+        offer: RbPaymentData,
+        receive_address: String,
+        now: i64,
+    ) {
+        let record = TradeRecord {
+            role: TradeRole::Offerer,
+            peer,
+            state: TradeState::Offered,
+            send_asset: offer.sender_asset.clone(),
+            receive_address,
+            druid: None,
+            offer: Some(offer),
+            created_at: now,
+        };
+        self.trades.insert(id, record);
+    }
+
+    /// Record an offer accepted from a peer
+    ///
+    /// ### Arguments
+    ///
+    /// * `id`              - Offerer half of the DRUID.
+    /// * `peer`            - Peer the offer is received from.
+    /// * `send_asset`      - Item asset sent by this node.
+    /// * `receive_address` - Address receiving the offered asset.
+    /// * `druid`           - DRUID binding both halves.
+    /// * `now`             - Current time, in seconds since the epoch.
+    pub fn accept(
+        &mut self,
+        id: String,
+        peer: SocketAddr,
+        send_asset: Asset,
+        receive_address: String,
+        druid: String,
+        now: i64,
+    ) {
+        let record = TradeRecord {
+            role: TradeRole::Acceptor,
+            peer,
+            state: TradeState::Accepted,
+            send_asset,
+            receive_address,
+            druid: Some(druid),
+            offer: None,
+            created_at: now,
+        };
+        self.trades.insert(id, record);
+    }
+
+    /// Record the peer answer to an offer, returning the offer data if it was expected
+    ///
+    /// ### Arguments
+    ///
+    /// * `id`    - Offerer half of the DRUID.
+    /// * `peer`  - Peer answering the offer.
+    /// * `druid` - DRUID binding both halves, None if the offer is rejected.
+    pub fn answer(
+        &mut self,
+        id: &str,
+        peer: SocketAddr,
+        druid: Option<String>,
+    ) -> Option<RbPaymentData> {
+        let record = self.trades.get_mut(id)?;
+        if record.peer != peer || record.state != TradeState::Offered {
+            return None;
+        }
+
+        record.state = match druid {
+            Some(_) => TradeState::Accepted,
+            None => TradeState::Rejected,
+        };
+        record.druid = druid;
+        record.offer.take()
+    }
+
+    /// Record the submission of the half of a trade, returning whether it is known
+    ///
+    /// ### Arguments
+    ///
+    /// * `druid` - DRUID of the submitted half.
+    pub fn submit(&mut self, druid: &str) -> bool {
+        let record = self
+            .trades
+            .values_mut()
+            .find(|r| r.state == TradeState::Accepted && r.druid.as_deref() == Some(druid));
+        match record {
+            Some(record) => {
+                record.state = TradeState::Submitted;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Complete the accepted trades whose asset is received, returning their ids
+    ///
+    /// Abandoned trades are completed too, as the mempool node may still have
+    /// committed them.
+    ///
+    /// ### Arguments
+    ///
+    /// * `addresses` - Addresses receiving payments.
+    pub fn complete(&mut self, addresses: &BTreeSet<&str>) -> Vec<String> {
+        let mut completed = Vec::new();
+        for (id, record) in &mut self.trades {
+            let accepted = matches!(
+                record.state,
+                TradeState::Accepted | TradeState::Submitted | TradeState::Abandoned
+            ) && record.druid.is_some();
+            if accepted && addresses.contains(record.receive_address.as_str()) {
+                record.state = TradeState::Completed;
+                completed.push(id.clone());
+            }
+        }
+        completed
+    }
+
+    /// Abandon the trades not completed within the timeout, returning their ids
+    ///
+    /// ### Arguments
+    ///
+    /// * `now`     - Current time, in seconds since the epoch.
+    /// * `timeout` - Seconds given to a trade to complete.
+    pub fn expire(&mut self, now: i64, timeout: u64) -> Vec<String> {
+        let timeout = i64::try_from(timeout).unwrap_or(i64::MAX);
+        let mut abandoned = Vec::new();
+        for (id, record) in &mut self.trades {
+            let active = !record.state.is_final() && record.state != TradeState::Abandoned;
+            if active && now.saturating_sub(record.created_at) >= timeout {
+                record.state = TradeState::Abandoned;
+                record.offer = None;
+                abandoned.push(id.clone());
+            }
+        }
+        abandoned
+    }
+}
+
+/// Load the trades of the node
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the trades.
+pub async fn load_trades(wallet_db: &WalletDb) -> Result<Trades> {
+    let trades = wallet_db.get_db_value(TRADES_KEY).await?;
+    Ok(trades
+        .and_then(|v| bincode::deserialize(&v).ok())
+        .unwrap_or_default())
+}
+
+/// Save the trades of the node
+///
+/// ### Arguments
+///
+/// * `wallet_db` - Wallet holding the trades.
+/// * `trades`    - Trades of the node.
+pub async fn save_trades(wallet_db: &WalletDb, trades: &Trades) -> Result<()> {
+    let trades = bincode::serialize(trades).unwrap();
+    wallet_db.set_db_value(TRADES_KEY, trades).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::asset::TokenAmount;
+
+    fn offer_data() -> RbPaymentData {
+        RbPaymentData {
+            sender_asset: Asset::Token(TokenAmount(10)),
+            sender_half_druid: "half_1".to_owned(),
+            tx_ins: Vec::new(),
+            tx_outs: Vec::new(),
+        }
+    }
+
+    #[test]
+    /// Checks an offer goes through acceptance, submission and completion
+    fn trade_offer_completes() {
+        //Arrange
+        let peer: SocketAddr = "127.0.0.1:12700".parse().unwrap();
+        let other_peer: SocketAddr = "127.0.0.1:12701".parse().unwrap();
+        let mut trades = Trades::default();
+        trades.offer(
+            "half_1".to_owned(),
+            peer,
+            offer_data(),
+            "addr".to_owned(),
+            0,
+        );
+
+        //Act
+        let unexpected = trades.answer("half_1", other_peer, Some("half_1half_2".to_owned()));
+        let answered = trades.answer("half_1", peer, Some("half_1half_2".to_owned()));
+        let submitted = trades.submit("half_1half_2");
+        let completed = trades.complete(&vec!["addr"].into_iter().collect());
+
+        //Assert
+        assert!(unexpected.is_none());
+        assert!(answered.is_some());
+        assert!(submitted);
+        assert_eq!(completed, vec!["half_1".to_owned()]);
+        assert_eq!(trades.trades["half_1"].state, TradeState::Completed);
+    }
+
+    #[test]
+    /// Checks only the trades older than the timeout and not completed are abandoned
+    fn trade_expire_abandons_stale_trades() {
+        //Arrange
+        let peer: SocketAddr = "127.0.0.1:12700".parse().unwrap();
+        let asset = Asset::item(1, Some("genesis".to_owned()), None);
+        let mut trades = Trades::default();
+        trades.offer("old".to_owned(), peer, offer_data(), "a1".to_owned(), 0);
+        trades.offer(
+            "rejected".to_owned(),
+            peer,
+            offer_data(),
+            "a2".to_owned(),
+            0,
+        );
+        trades.answer("rejected", peer, None);
+        let (druid, a3) = ("recentdruid".to_owned(), "a3".to_owned());
+        trades.accept("recent".to_owned(), peer, asset, a3, druid, 50);
+
+        //Act
+        let abandoned = trades.expire(100, 60);
+
+        //Assert
+        assert_eq!(abandoned, vec!["old".to_owned()]);
+        assert_eq!(trades.trades["old"].state, TradeState::Abandoned);
+        assert_eq!(trades.trades["rejected"].state, TradeState::Rejected);
+        assert_eq!(trades.trades["recent"].state, TradeState::Accepted);
+    }
+}
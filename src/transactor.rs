@@ -43,5 +43,5 @@ pub trait Transactor {
     fn receive_utxo_set(&mut self, utxo_set: UtxoSet) -> Response;
 
     /// Updates the local running total with the latest received UTXO set
-    async fn update_running_total(&mut self);
+    async fn update_running_total(&mut self) -> Result<(), Self::Error>;
 }
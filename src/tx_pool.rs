@@ -0,0 +1,332 @@
+use crate::utils::is_locktime_expired;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use tw_chain::primitives::transaction::{OutPoint, Transaction};
+use tw_chain::utils::transaction_utils::get_inputs_previous_out_point;
+
+/// Committed transactions waiting to be added to a block
+///
+/// Transactions are kept in block selection order, currently their arrival
+/// order, and the outpoints they spend are indexed. A transaction spending
+/// an outpoint already spent in the pool is rejected on arrival, so a block
+/// is assembled by a single scan checking transactions against the UTXO set.
+///
+/// A transaction may be given an expiry, a block number or a timestamp like
+/// an output locktime: once expired it is evicted and never added to a block.
+#[derive(Default, Clone, Debug)]
+pub struct TxPool {
+    /// Transactions with their selection key
+    txs: BTreeMap<String, (u64, Transaction)>,
+    /// Transaction hashes in selection order
+    order: BTreeMap<u64, String>,
+    /// Transaction spending each outpoint
+    spent: BTreeMap<OutPoint, String>,
+    /// Expiry of the transactions given one
+    expiries: BTreeMap<String, u64>,
+    /// Selection key of the next transaction
+    next_key: u64,
+}
+
+impl TxPool {
+    /// Number of transactions in the pool
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    /// Whether the pool holds no transaction
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Whether the pool holds the given transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash` - Hash of the transaction
+    pub fn contains_key(&self, hash: &str) -> bool {
+        self.txs.contains_key(hash)
+    }
+
+    /// Transactions in block selection order
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Transaction)> {
+        self.order
+            .values()
+            .filter_map(move |hash| self.txs.get_key_value(hash))
+            .map(|(hash, (_, tx))| (hash, tx))
+    }
+
+    /// Expiry of a transaction, None if it does not expire
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash` - Hash of the transaction
+    pub fn expiry(&self, hash: &str) -> Option<u64> {
+        self.expiries.get(hash).copied()
+    }
+
+    /// Add a transaction, unless it is pooled or spends a pooled outpoint
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash` - Hash of the transaction
+    /// * `tx`   - Transaction to add
+    pub fn insert(&mut self, hash: String, tx: Transaction) -> bool {
+        self.insert_with_expiry(hash, tx, None)
+    }
+
+    /// Add a transaction expiring at the given block number or timestamp
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash`   - Hash of the transaction
+    /// * `tx`     - Transaction to add
+    /// * `expiry` - Expiry of the transaction, None if it does not expire
+    pub fn insert_with_expiry(
+        &mut self,
+        hash: String,
+        tx: Transaction,
+        expiry: Option<u64>,
+    ) -> bool {
+        let inputs: Vec<_> = get_inputs_previous_out_point(Some(&tx).into_iter()).collect();
+        if self.txs.contains_key(&hash) || inputs.iter().any(|op| self.spent.contains_key(op)) {
+            return false;
+        }
+
+        for out_p in inputs {
+            self.spent.insert(out_p.clone(), hash.clone());
+        }
+        if let Some(expiry) = expiry {
+            self.expiries.insert(hash.clone(), expiry);
+        }
+        let key = self.next_key;
+        self.next_key += 1;
+        self.order.insert(key, hash.clone());
+        self.txs.insert(hash, (key, tx));
+        true
+    }
+
+    /// Add transactions in the given order, returning the hashes of the rejected ones
+    ///
+    /// ### Arguments
+    ///
+    /// * `txs` - Transactions to add
+    pub fn append(&mut self, txs: impl IntoIterator<Item = (String, Transaction)>) -> Vec<String> {
+        let mut rejected = Vec::new();
+        for (hash, tx) in txs {
+            if !self.insert(hash.clone(), tx) {
+                rejected.push(hash);
+            }
+        }
+        rejected
+    }
+
+    /// Add expiring transactions in the given order, returning the hashes of the rejected ones
+    ///
+    /// ### Arguments
+    ///
+    /// * `txs` - Transactions to add, with their expiry
+    pub fn append_with_expiry(
+        &mut self,
+        txs: impl IntoIterator<Item = (String, (Transaction, u64))>,
+    ) -> Vec<String> {
+        let mut rejected = Vec::new();
+        for (hash, (tx, expiry)) in txs {
+            if !self.insert_with_expiry(hash.clone(), tx, Some(expiry)) {
+                rejected.push(hash);
+            }
+        }
+        rejected
+    }
+
+    /// Remove a transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash` - Hash of the transaction
+    pub fn remove(&mut self, hash: &str) -> Option<Transaction> {
+        let (key, tx) = self.txs.remove(hash)?;
+        self.order.remove(&key);
+        self.expiries.remove(hash);
+        for out_p in get_inputs_previous_out_point(Some(&tx).into_iter()) {
+            self.spent.remove(out_p);
+        }
+        Some(tx)
+    }
+
+    /// Take the first transactions in selection order that can be added to a block
+    ///
+    /// Scanned transactions spending an outpoint missing from the UTXO set are dropped.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_len`    - Maximum number of transactions to take
+    /// * `is_unspent` - Whether an outpoint is in the UTXO set
+    pub fn take_block_txs(
+        &mut self,
+        max_len: usize,
+        is_unspent: impl Fn(&OutPoint) -> bool,
+    ) -> BTreeMap<String, Transaction> {
+        let mut scanned = Vec::new();
+        let mut valid_len = 0;
+        for (hash, tx) in self.iter() {
+            if valid_len == max_len {
+                break;
+            }
+            let valid = get_inputs_previous_out_point(Some(tx).into_iter()).all(&is_unspent);
+            valid_len += valid as usize;
+            scanned.push((hash.clone(), valid));
+        }
+
+        let mut block_txs = BTreeMap::new();
+        for (hash, valid) in scanned {
+            if let Some(tx) = self.remove(&hash).filter(|_| valid) {
+                block_txs.insert(hash, tx);
+            }
+        }
+        block_txs
+    }
+
+    /// Remove the transactions expired for a block, releasing the outpoints they spend
+    ///
+    /// ### Arguments
+    ///
+    /// * `b_num`     - Number of the block
+    /// * `timestamp` - Timestamp of the block
+    pub fn evict_expired(&mut self, b_num: u64, timestamp: i64) -> Vec<String> {
+        let expired: Vec<_> = self
+            .expiries
+            .iter()
+            .filter(|(_, expiry)| is_locktime_expired(**expiry, b_num, timestamp))
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in &expired {
+            self.remove(hash);
+        }
+        expired
+    }
+}
+
+impl Serialize for TxPool {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        // Map the ordered hashes so the sequence length is known up front
+        let txs = self.order.values().map(|hash| {
+            let (_, tx) = &self.txs[hash];
+            (hash, tx, self.expiry(hash))
+        });
+        s.collect_seq(txs)
+    }
+}
+
+impl<'a> Deserialize<'a> for TxPool {
+    fn deserialize<D: Deserializer<'a>>(deserializer: D) -> Result<Self, D::Error> {
+        let txs: Vec<(String, Transaction, Option<u64>)> = Deserialize::deserialize(deserializer)?;
+        let mut pool = TxPool::default();
+        for (hash, tx, expiry) in txs {
+            pool.insert_with_expiry(hash, tx, expiry);
+        }
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::LOCKTIME_TIMESTAMP_THRESHOLD;
+    use tw_chain::primitives::transaction::TxIn;
+    use tw_chain::script::lang::Script;
+
+    fn spending_tx(out_points: &[(&str, i32)]) -> Transaction {
+        let mut tx = Transaction::new();
+        tx.inputs = out_points
+            .iter()
+            .map(|(t_hash, n)| {
+                TxIn::new_from_input(OutPoint::new(t_hash.to_string(), *n), Script::new())
+            })
+            .collect();
+        tx
+    }
+
+    #[test]
+    /// Checks conflicting transactions are rejected and blocks are taken in arrival order
+    fn tx_pool_selects_in_arrival_order_without_conflicts() {
+        //Arrange
+        let mut pool = TxPool::default();
+        let txs = vec![
+            ("g_tx".to_owned(), spending_tx(&[("a", 0)])),
+            ("c_tx".to_owned(), spending_tx(&[("a", 0), ("b", 0)])),
+            ("e_tx".to_owned(), spending_tx(&[("spent", 0)])),
+            ("a_tx".to_owned(), spending_tx(&[("b", 0)])),
+            ("f_tx".to_owned(), spending_tx(&[("c", 0)])),
+        ];
+
+        //Act
+        let rejected = pool.append(txs);
+        let block_txs = pool.take_block_txs(2, |op| op.t_hash != "spent");
+        let remaining: Vec<_> = pool.iter().map(|(hash, _)| hash.clone()).collect();
+        let reused = pool.insert("d_tx".to_owned(), spending_tx(&[("a", 0)]));
+
+        //Assert
+        assert_eq!(rejected, vec!["c_tx".to_owned()]);
+        let block_hashes: Vec<_> = block_txs.keys().cloned().collect();
+        assert_eq!(block_hashes, vec!["a_tx".to_owned(), "g_tx".to_owned()]);
+        assert_eq!(remaining, vec!["f_tx".to_owned()]);
+        assert!(reused);
+    }
+
+    #[test]
+    /// Checks the pool keeps its selection order through serialization
+    fn tx_pool_serialization_keeps_order() {
+        //Arrange
+        let mut pool = TxPool::default();
+        pool.append(vec![
+            ("b_tx".to_owned(), spending_tx(&[("b", 0)])),
+            ("a_tx".to_owned(), spending_tx(&[("a", 0)])),
+        ]);
+        pool.insert_with_expiry("c_tx".to_owned(), spending_tx(&[("c", 0)]), Some(9));
+
+        //Act
+        let pool: TxPool = bincode::deserialize(&bincode::serialize(&pool).unwrap()).unwrap();
+
+        //Assert
+        let hashes: Vec<_> = pool.iter().map(|(hash, _)| hash.clone()).collect();
+        assert_eq!(
+            hashes,
+            vec!["b_tx".to_owned(), "a_tx".to_owned(), "c_tx".to_owned()]
+        );
+        assert_eq!(pool.expiry("c_tx"), Some(9));
+        assert_eq!(pool.expiry("a_tx"), None);
+    }
+
+    #[test]
+    /// Checks expired transactions are evicted and the outpoints they spend released
+    fn tx_pool_evicts_expired_transactions() {
+        //Arrange
+        let mut pool = TxPool::default();
+        let timestamp_expiry = LOCKTIME_TIMESTAMP_THRESHOLD + 100;
+        pool.insert("a_tx".to_owned(), spending_tx(&[("a", 0)]));
+        pool.append_with_expiry(vec![
+            ("b_tx".to_owned(), (spending_tx(&[("b", 0)]), 5)),
+            ("c_tx".to_owned(), (spending_tx(&[("c", 0)]), 6)),
+            (
+                "d_tx".to_owned(),
+                (spending_tx(&[("d", 0)]), timestamp_expiry),
+            ),
+        ]);
+
+        //Act
+        let expired_by_height = pool.evict_expired(5, 0);
+        let expired_by_time = pool.evict_expired(5, timestamp_expiry as i64);
+        let respent = pool.insert("e_tx".to_owned(), spending_tx(&[("b", 0)]));
+        let block_txs = pool.take_block_txs(10, |_| true);
+
+        //Assert
+        assert_eq!(expired_by_height, vec!["b_tx".to_owned()]);
+        assert_eq!(expired_by_time, vec!["d_tx".to_owned()]);
+        assert!(respent);
+        let block_hashes: Vec<_> = block_txs.keys().cloned().collect();
+        assert_eq!(
+            block_hashes,
+            vec!["a_tx".to_owned(), "c_tx".to_owned(), "e_tx".to_owned()]
+        );
+    }
+}
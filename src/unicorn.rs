@@ -257,7 +257,6 @@ mod unicorn_tests {
             let s: String = rand::thread_rng()
                 .sample_iter(&Alphanumeric)
                 .take(1024)
-                .map(char::from)
                 .collect();
             let i = Integer::from_str_radix(&hex::encode(s), 16).unwrap();
             uni.set_seed(i);
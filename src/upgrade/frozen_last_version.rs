@@ -265,6 +265,68 @@ pub mod mempool_raft {
     }
 }
 
+/// Mempool snapshot as written by the current DB version before the indexed
+/// transaction pool: the DB version did not change, so the same version upgrade reads it.
+pub mod same_version_mempool_raft {
+    use crate::block_pipeline::{MiningPipelineStatus, Participants};
+    use crate::interfaces::{InitialIssuance, WinningPoWInfo};
+    use crate::mempool_raft::{
+        AccumulatingBlockStoredInfo, CoordinatedCommand, MinerWhitelist, SpecialHandling,
+    };
+    use crate::raft_util::RaftContextKey;
+    use crate::tracked_utxo::TrackedUtxoSet;
+    use crate::unicorn::{UnicornFixedParam, UnicornInfo};
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::net::SocketAddr;
+    use tw_chain::primitives::asset::TokenAmount;
+    use tw_chain::primitives::block::Block;
+    use tw_chain::primitives::transaction::Transaction;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct MempoolConsensused {
+        pub unanimous_majority: usize,
+        pub sufficient_majority: usize,
+        pub partition_full_size: usize,
+        pub tx_pool: BTreeMap<String, Transaction>,
+        pub tx_druid_pool: Vec<BTreeMap<String, Transaction>>,
+        pub tx_current_block_previous_hash: Option<String>,
+        pub initial_utxo_txs: Option<BTreeMap<String, Transaction>>,
+        pub utxo_set: TrackedUtxoSet,
+        pub current_block_stored_info:
+            BTreeMap<Vec<u8>, (AccumulatingBlockStoredInfo, BTreeSet<u64>)>,
+        pub current_raft_coordinated_cmd_stored_info: BTreeMap<CoordinatedCommand, BTreeSet<u64>>,
+        pub last_committed_raft_idx_and_term: (u64, u64),
+        pub current_issuance: TokenAmount,
+        pub block_pipeline: MiningPipelineInfo,
+        pub last_mining_transaction_hashes: Vec<String>,
+        pub special_handling: Option<SpecialHandling>,
+        pub miner_whitelist: MinerWhitelist,
+        pub timestamp: i64,
+        pub init_issuances: Vec<InitialIssuance>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct MiningPipelineInfo {
+        pub participants_intake: BTreeMap<u64, Participants>,
+        pub participants_mining: BTreeMap<u64, Participants>,
+        pub empty_participants: Participants,
+        pub last_winning_hashes: BTreeSet<String>,
+        pub all_winning_pow: Vec<(SocketAddr, WinningPoWInfo)>,
+        pub unicorn_info: UnicornInfo,
+        pub winning_pow: Option<(SocketAddr, WinningPoWInfo)>,
+        pub mining_pipeline_status: MiningPipelineStatus,
+        pub current_phase_timeout_peer_ids: BTreeSet<u64>,
+        pub current_phase_reset_pipeline_peer_ids: BTreeSet<u64>,
+        pub unicorn_fixed_param: UnicornFixedParam,
+        pub current_block_num: Option<u64>,
+        pub current_block: Option<Block>,
+        pub current_block_tx: BTreeMap<String, Transaction>,
+        pub current_reward: TokenAmount,
+        pub proposed_keys: BTreeSet<RaftContextKey>,
+    }
+}
+
 pub mod unicorn {
     use super::*;
     use crate::utils::rug_integer;
@@ -726,6 +788,26 @@ pub mod convert {
         }
     }
 
+    pub fn convert_same_version_mempool_consensused_to_import(
+        old: old::same_version_mempool_raft::MempoolConsensused,
+        special_handling: Option<mempool_raft::SpecialHandling>,
+    ) -> mempool_raft::MempoolConsensusedImport {
+        mempool_raft::MempoolConsensusedImport {
+            unanimous_majority: old.unanimous_majority,
+            sufficient_majority: old.sufficient_majority,
+            partition_full_size: old.partition_full_size,
+            unicorn_fixed_param: old.block_pipeline.unicorn_fixed_param,
+            tx_current_block_num: old.block_pipeline.current_block_num,
+            current_block: old.block_pipeline.current_block,
+            utxo_set: old.utxo_set.into_utxoset(),
+            last_committed_raft_idx_and_term: old.last_committed_raft_idx_and_term,
+            current_issuance: old.current_issuance,
+            miner_whitelist: old.miner_whitelist,
+            init_issuances: old.init_issuances,
+            special_handling,
+        }
+    }
+
     pub fn convert_unicorn_fixed_param(old: old::unicorn::UnicornFixedParam) -> UnicornFixedParam {
         UnicornFixedParam {
             modulus: old.modulus,
@@ -6,6 +6,8 @@ mod frozen_last_version;
 #[cfg(test)]
 mod tests;
 #[cfg(test)]
+mod tests_golden;
+#[cfg(test)]
 #[rustfmt::skip]
 mod tests_last_version_db;
 
@@ -19,9 +21,10 @@ use crate::db_utils::{
     SimpleDbWriteBatch, DB_COL_DEFAULT,
 };
 use crate::miner::LAST_COINBASE_KEY;
+use crate::partition_rounds::PARTITION_ROUND_PREFIX;
 use crate::utils::StringError;
 use crate::{mempool, mempool_raft, raft_store, storage, storage_raft, user, wallet};
-use bincode::{deserialize, serialize};
+use bincode::{deserialize, serialize, Options};
 use frozen_last_version as old;
 use std::error::Error;
 use std::fmt;
@@ -197,7 +200,7 @@ pub fn upgrade_mempool_db_batch<'a>(
     batch.put_cf(mempool::DB_COL_INTERNAL, mempool::RAFT_KEY_RUN, key_run()?);
 
     let column = mempool::DB_COL_INTERNAL;
-    for (key, value) in db.iter_cf_clone(column) {
+    for (key, value) in db.iter_cf_clone(column)? {
         if key == old::mempool::REQUEST_LIST_KEY.as_bytes()
             || key == old::mempool::USER_NOTIFY_LIST_KEY.as_bytes()
         {
@@ -213,7 +216,7 @@ pub fn upgrade_mempool_db_batch<'a>(
     }
 
     let column = mempool::DB_COL_LOCAL_TXS;
-    for (key, _) in db.iter_cf_clone(column) {
+    for (key, _) in db.iter_cf_clone(column)? {
         batch.delete_cf(column, &key);
     }
 
@@ -248,30 +251,34 @@ pub fn upgrade_same_version_mempool_db(mut dbs: ExtraNodeParams) -> Result<Extra
     let (mut batch, mut raft_batch) = (db.batch_writer(), raft_db.batch_writer());
 
     let column = mempool::DB_COL_INTERNAL;
-    for (key, value) in db.iter_cf_clone(column) {
+    for (key, value) in db.iter_cf_clone(column)? {
         if key == mempool::REQUEST_LIST_KEY.as_bytes()
             || key == mempool::USER_NOTIFY_LIST_KEY.as_bytes()
             || key == mempool::POW_RANDOM_NUM_KEY.as_bytes()
             || key == mempool::POW_PREV_RANDOM_NUM_KEY.as_bytes()
+            || key == mempool::ROUND_STATE_KEY.as_bytes()
         {
             batch.delete_cf(column, &key);
         } else if key == mempool::RAFT_KEY_RUN.as_bytes() {
             // Keep modified
+        } else if key.starts_with(PARTITION_ROUND_PREFIX.as_bytes()) {
+            // Keep unmodified
         } else {
             return Err(key_value_error("Unexpected key", &key, &value));
         }
     }
 
     let column = mempool::DB_COL_LOCAL_TXS;
-    for (key, _) in db.iter_cf_clone(column) {
+    for (key, _) in db.iter_cf_clone(column)? {
         batch.delete_cf(column, &key);
     }
 
     clean_same_raft_db(raft_db, &mut raft_batch, |k, v| {
-        let mut consensus = mempool_raft::MempoolConsensused::into_import(
-            tracked_deserialize("MempoolConsensused", k, &v)?,
+        let mut consensus = same_version_mempool_consensused_to_import(
+            k,
+            &v,
             Some(mempool_raft::SpecialHandling::FirstUpgradeBlock),
-        );
+        )?;
         // Version 0.3.0 coordinated shutdown should never have a block in snapshoot
         consensus.current_block = None;
         Ok(serialize(&mempool_raft::MempoolConsensused::from_import(
@@ -285,6 +292,27 @@ pub fn upgrade_same_version_mempool_db(mut dbs: ExtraNodeParams) -> Result<Extra
     Ok(dbs)
 }
 
+/// Read a same version mempool snapshot, written either with the current layout
+/// or with the layout preceding the indexed transaction pool
+fn same_version_mempool_consensused_to_import(
+    key: &[u8],
+    value: &[u8],
+    special_handling: Option<mempool_raft::SpecialHandling>,
+) -> Result<mempool_raft::MempoolConsensusedImport> {
+    // Trailing bytes mean the value was written with the other layout
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes();
+    if let Ok(consensus) = options.deserialize::<mempool_raft::MempoolConsensused>(value) {
+        return Ok(consensus.into_import(special_handling));
+    }
+
+    Ok(old::convert_same_version_mempool_consensused_to_import(
+        tracked_deserialize("MempoolConsensused", key, value)?,
+        special_handling,
+    ))
+}
+
 /// Upgrade DB: New column are added at begining of upgrade and old one removed at the end.
 pub fn get_upgrade_storage_db(
     db_mode: DbMode,
@@ -297,6 +325,7 @@ pub fn get_upgrade_storage_db(
     let raft_db = new_db_with_version(db_mode, raft_spec, version, old_dbs.raft_db, None)?;
 
     db.upgrade_create_missing_cf(storage::DB_COL_BC_NOW)?;
+    db.upgrade_create_missing_cf(storage::DB_COL_BC_STATS)?;
     Ok(ExtraNodeParams {
         db: Some(db),
         raft_db: Some(raft_db),
@@ -340,7 +369,7 @@ pub fn upgrade_storage_db_batch<'a>(
     batch.put_cf(storage::DB_COL_INTERNAL, storage::RAFT_KEY_RUN, key_run()?);
 
     let column = storage::DB_COL_INTERNAL;
-    for (key, value) in db.iter_cf_clone(column) {
+    for (key, value) in db.iter_cf_clone(column)? {
         if key == storage::RAFT_KEY_RUN.as_bytes()
             || key == storage::LAST_CONTIGUOUS_BLOCK_KEY.as_bytes()
         {
@@ -351,7 +380,7 @@ pub fn upgrade_storage_db_batch<'a>(
     }
 
     let column = old::storage::DB_COL_BC_V0_6_0;
-    for (key, value) in db.iter_cf_clone(column) {
+    for (key, value) in db.iter_cf_clone(column)? {
         if is_transaction_key(&key) {
             let _: old::tw_chain::Transaction =
                 tracked_deserialize("Tx deserialize", &key, &value)?;
@@ -389,7 +418,7 @@ pub fn upgrade_same_version_storage_db(mut dbs: ExtraNodeParams) -> Result<Extra
     let (batch, mut raft_batch) = (db.batch_writer(), raft_db.batch_writer());
 
     let column = storage::DB_COL_INTERNAL;
-    for (key, value) in db.iter_cf_clone(column) {
+    for (key, value) in db.iter_cf_clone(column)? {
         if key == storage::RAFT_KEY_RUN.as_bytes()
             || key == storage::LAST_CONTIGUOUS_BLOCK_KEY.as_bytes()
         {
@@ -425,7 +454,7 @@ fn clean_raft_db(
     raft_batch: &mut SimpleDbWriteBatch,
     mut convert: impl FnMut(&[u8], Vec<u8>) -> Result<Vec<u8>>,
 ) -> Result<()> {
-    for (key, value) in raft_db.iter_cf_clone(DB_COL_DEFAULT) {
+    for (key, value) in raft_db.iter_cf_clone(DB_COL_DEFAULT)? {
         if key == DB_VERSION_KEY.as_bytes() {
             // Keep as is
         } else if key == old::raft_store::SNAPSHOT_DATA_KEY.as_bytes() {
@@ -449,7 +478,7 @@ fn clean_same_raft_db(
     raft_batch: &mut SimpleDbWriteBatch,
     convert: impl Fn(&[u8], Vec<u8>) -> Result<Vec<u8>>,
 ) -> Result<()> {
-    for (key, value) in raft_db.iter_cf_clone(DB_COL_DEFAULT) {
+    for (key, value) in raft_db.iter_cf_clone(DB_COL_DEFAULT)? {
         raft_batch.delete_cf(DB_COL_DEFAULT, &key);
 
         if key == raft_store::SNAPSHOT_META_KEY.as_bytes() || key == DB_VERSION_KEY.as_bytes() {
@@ -457,12 +486,27 @@ fn clean_same_raft_db(
         } else if key == raft_store::SNAPSHOT_DATA_KEY.as_bytes() {
             raft_batch.put_cf(DB_COL_DEFAULT, &key, &convert(&key, value)?);
         } else if !(key.starts_with(raft_store::ENTRY_KEY.as_bytes())
+            || key.starts_with(raft_store::SNAPSHOT_CHUNK_KEY.as_bytes())
+            || key == raft_store::SNAPSHOT_CHUNKS_KEY.as_bytes()
             || key == raft_store::HARDSTATE_KEY.as_bytes()
             || key == raft_store::LAST_ENTRY_KEY.as_bytes())
         {
             return Err(key_value_error("Unexpected raft key", &key, &value));
         }
     }
+
+    // Chunked snapshot data is stored back as a single value
+    let meta = raft_db.get_cf(DB_COL_DEFAULT, raft_store::SNAPSHOT_META_KEY)?;
+    let chunks = raft_db.get_cf(DB_COL_DEFAULT, raft_store::SNAPSHOT_CHUNKS_KEY)?;
+    if let (Some(meta), Some(_)) = (meta, chunks) {
+        let meta: raft_store::SnapMetadata = deserialize(&meta)?;
+        let data = raft_store::get_persistent_snapshot_data(raft_db, meta.index)
+            .map_err(|e| StringError(format!("Snapshot data unavailable: {e}")))?;
+        if let Some(data) = data {
+            let key = raft_store::SNAPSHOT_DATA_KEY.as_bytes();
+            raft_batch.put_cf(DB_COL_DEFAULT, key, &convert(key, data)?);
+        }
+    }
     Ok(())
 }
 
@@ -498,9 +542,10 @@ pub fn upgrade_wallet_db_batch<'a>(
     batch.put_cf(DB_COL_DEFAULT, DB_VERSION_KEY, NETWORK_VERSION_SERIALIZED);
 
     let passphrase = upgrade_cfg.passphrase.as_bytes();
-    let masterkey = wallet::get_or_save_master_key_store(db, &mut batch, passphrase)?;
+    let kdf_params = wallet::Argon2Params::default();
+    let masterkey = wallet::get_or_save_master_key_store(db, &mut batch, passphrase, kdf_params)?;
 
-    for (key, value) in db.iter_cf_clone(DB_COL_DEFAULT) {
+    for (key, value) in db.iter_cf_clone(DB_COL_DEFAULT)? {
         if key == DB_VERSION_KEY.as_bytes() {
             // Keep as is
         } else if key == old::wallet::TX_GENERATOR_KEY.as_bytes() {
@@ -541,7 +586,7 @@ pub fn upgrade_wallet_db_batch<'a>(
                 tracked_deserialize("Tx Store deserialize", &key, &value)?;
         } else if is_wallet_address_store_key(&key) {
             // Keep as is
-            let value = wallet::decrypt_store(value, &masterkey);
+            let value = wallet::decrypt_store(value, &masterkey)?;
             let _: old::wallet::AddressStore =
                 tracked_deserialize("Addr Store deserialize", &key, &value)?;
         } else {
@@ -598,13 +643,14 @@ pub fn get_db_to_dump_no_checks(
 }
 
 /// Dump the database as string
-pub fn dump_db(db: &'_ SimpleDb) -> impl Iterator<Item = String> + '_ {
-    db.iter_all_cf_clone()
+pub fn dump_db(db: &'_ SimpleDb) -> Result<impl Iterator<Item = String> + '_> {
+    Ok(db
+        .iter_all_cf_clone()?
         .into_iter()
         .flat_map(|(c, it)| it.map(move |(k, v)| (c.clone(), k, v)))
         .map(|(c, k, v)| (c, to_u8_array_literal(&k), v))
         .map(|(c, k, v)| (c, k, to_u8_array_literal(&v)))
-        .map(|(c, k, v)| format!("\"{c}\", b\"{k}\", b\"{v}\""))
+        .map(|(c, k, v)| format!("\"{c}\", b\"{k}\", b\"{v}\"")))
 }
 
 /// Convert to a valid array literal displaying ASCII nicely
@@ -9,7 +9,9 @@ use crate::constants::{LAST_BLOCK_HASH_KEY, NETWORK_VERSION_SERIALIZED};
 use crate::db_utils::{
     new_db, new_db_with_version, SimpleDb, SimpleDbError, SimpleDbSpec, DB_COL_DEFAULT,
 };
-use crate::interfaces::{BlockStoredInfo, BlockchainItem, BlockchainItemMeta, Response};
+use crate::interfaces::{
+    BlockStoredInfo, BlockchainItem, BlockchainItemMeta, Response, ResponseReason,
+};
 use crate::test_utils::{
     get_test_tls_spec, node_join_all_checked, remove_all_node_dbs, Network, NetworkConfig,
     NetworkNodeInfo, NodeType,
@@ -489,7 +491,7 @@ async fn upgrade_restart_network_common(
                 node,
                 count,
                 last_bnum,
-                dump_db(&db).collect::<Vec<String>>().join("\n")
+                dump_db(&db).unwrap().collect::<Vec<String>>().join("\n")
             );
         }
 
@@ -726,6 +728,7 @@ fn complete_network_config(initial_port: u16) -> NetworkConfig {
         peer_limit: 1000,
         address_aggregation_limit: Some(5),
         initial_issuances: Default::default(),
+        key_gen_seed: None,
     }
     .with_groups(1, 1)
 }
@@ -820,9 +823,7 @@ fn in_memory(dbs: ExtraNodeParams) -> ExtraNodeParams {
         db: dbs.db.and_then(|v| v.in_memory()),
         raft_db: dbs.raft_db.and_then(|v| v.in_memory()),
         wallet_db: dbs.wallet_db.and_then(|v| v.in_memory()),
-        shared_wallet_db: None,
-        custom_wallet_spec: None,
-        disable_tcp_listener: false,
+        ..Default::default()
     }
 }
 
@@ -831,9 +832,7 @@ fn filter_dbs(dbs: ExtraNodeParams, filter_dbs: &ExtraNodeParamsFilter) -> Extra
         db: dbs.db.filter(|_| filter_dbs.db),
         raft_db: dbs.raft_db.filter(|_| filter_dbs.raft_db),
         wallet_db: dbs.wallet_db.filter(|_| filter_dbs.wallet_db),
-        shared_wallet_db: None,
-        custom_wallet_spec: None,
-        disable_tcp_listener: false,
+        ..Default::default()
     }
 }
 
@@ -842,16 +841,14 @@ fn cloned_in_memory(dbs: &ExtraNodeParams) -> ExtraNodeParams {
         db: dbs.db.as_ref().and_then(|v| v.cloned_in_memory()),
         raft_db: dbs.raft_db.as_ref().and_then(|v| v.cloned_in_memory()),
         wallet_db: dbs.wallet_db.as_ref().and_then(|v| v.cloned_in_memory()),
-        shared_wallet_db: None,
-        custom_wallet_spec: None,
-        disable_tcp_listener: false,
+        ..Default::default()
     }
 }
 
-fn test_timeout() -> impl Future<Output = &'static str> + Unpin {
+fn test_timeout() -> impl Future<Output = ResponseReason> + Unpin {
     Box::pin(async move {
         tokio::time::sleep(TIMEOUT_TEST_WAIT_DURATION).await;
-        "Test timeout elapsed"
+        ResponseReason::TestTimeoutElapsed
     })
 }
 
@@ -876,13 +873,13 @@ async fn raft_node_handle_event(network: &mut Network, node: &str, reason_val: &
     if let Some(n) = network.mempool(node) {
         let mut n = n.lock().await;
         match n.handle_next_event(&mut test_timeout()).await {
-            Some(Ok(Response { success, reason })) if success && reason == reason_val => {}
+            Some(Ok(Response { success, reason })) if success && reason.as_str() == reason_val => {}
             other => panic!("Unexpected result: {:?} (expected:{})", other, reason_val),
         }
     } else if let Some(n) = network.storage(node) {
         let mut n = n.lock().await;
         match n.handle_next_event(&mut test_timeout()).await {
-            Some(Ok(Response { success, reason })) if success && reason == reason_val => {}
+            Some(Ok(Response { success, reason })) if success && reason.as_str() == reason_val => {}
             other => panic!("Unexpected result: {:?} (expected:{})", other, reason_val),
         }
     }
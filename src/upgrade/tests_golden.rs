@@ -0,0 +1,284 @@
+//! Golden-file tests for the on-disk formats.
+//!
+//! Each test serializes a representative value the way it is stored in the
+//! databases and compares the bytes against the hex dump checked in under
+//! `src/upgrade/golden`. A failure means the stored layout changed and existing
+//! databases need an upgrade step. Once that is handled, regenerate the dumps
+//! with `UPDATE_GOLDEN=1 cargo test golden`.
+
+use super::frozen_last_version::same_version_mempool_raft;
+use super::same_version_mempool_consensused_to_import;
+use crate::interfaces::{InitialIssuance, StoredSerializingBlock};
+use crate::mempool_raft::{MempoolConsensused, MempoolRaftItem};
+use crate::raft_store::SnapMetadata;
+use crate::raft_util::RaftContextKey;
+use crate::reward_schedule::RewardSchedule;
+use crate::tx_pool::TxPool;
+use crate::wallet::{AddressStore, FundStore, TransactionStore};
+use bincode::{deserialize, serialize};
+use protobuf::Message;
+use raft::prelude::{Entry, HardState};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tw_chain::crypto::sign_ed25519::{PublicKey, SecretKey};
+use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::primitives::block::{Block, BlockHeader};
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxIn, TxOut};
+
+const UPDATE_GOLDEN_ENV: &str = "UPDATE_GOLDEN";
+const GOLDEN_TIMESTAMP: i64 = 1_700_000_000;
+const GOLDEN_TX_HASH: &str = "g48dda5bbe9171a6656206ec56c595c5";
+const GOLDEN_OTHER_TX_HASH: &str = "g7e2ad0c3d8c8e1d93e6b4dc8e0de6a1";
+const COMMON_PUB_KEY: &str = "5371832122a8e804fa3520ec6861c3fa554a7f6fb617e6f0768452090207e07c";
+const COMMON_SEC_KEY: &str = "3053020101300506032b6570042204200186bc08f16428d2059227082b93e439ff50f8c162f24b9594b132f2cc15fca4a1230321005371832122a8e804fa3520ec6861c3fa554a7f6fb617e6f0768452090207e07c";
+
+#[test]
+fn golden_fund_store() {
+    let mut fund_store = FundStore::default();
+    fund_store.store_tx(OutPoint::new("000001".to_owned(), 0), token(10));
+    fund_store.store_tx(OutPoint::new("000002".to_owned(), 1), token(25));
+    fund_store.spend_tx(&OutPoint::new("000001".to_owned(), 0));
+
+    let stored: FundStore = assert_golden_bincode("fund_store", &fund_store);
+    assert_eq!(stored.running_total().tokens, TokenAmount(25));
+}
+
+#[test]
+fn golden_address_store() {
+    let address_store = AddressStore {
+        public_key: PublicKey::from_slice(&hex::decode(COMMON_PUB_KEY).unwrap()).unwrap(),
+        secret_key: SecretKey::from_slice(&hex::decode(COMMON_SEC_KEY).unwrap()).unwrap(),
+        address_version: None,
+    };
+
+    let stored: AddressStore = assert_golden_bincode("address_store", &address_store);
+    assert_eq!(stored.public_key, address_store.public_key);
+}
+
+#[test]
+fn golden_transaction_store() {
+    let transaction_store = TransactionStore {
+        key_address: "a0b08e623c6800bb27dddb5d6f5956582e8bc0a5a8fd3e0df7d0dafdc6f8d5ab".to_owned(),
+    };
+
+    let stored: TransactionStore = assert_golden_bincode("transaction_store", &transaction_store);
+    assert_eq!(stored.key_address, transaction_store.key_address);
+}
+
+#[test]
+fn golden_stored_block() {
+    let block = StoredSerializingBlock {
+        block: Block {
+            header: BlockHeader {
+                version: 2,
+                bits: 1,
+                nonce_and_mining_tx_hash: (vec![1, 2, 3, 4], GOLDEN_TX_HASH.to_owned()),
+                b_num: 1,
+                timestamp: GOLDEN_TIMESTAMP,
+                difficulty: Vec::new(),
+                seed_value: b"seed".to_vec(),
+                previous_hash: Some(format!("b{:0>64}", "a")),
+                txs_merkle_root_and_hash: ("merkle_root".to_owned(), "block_hash".to_owned()),
+            },
+            transactions: vec![GOLDEN_TX_HASH.to_owned()],
+        },
+    };
+
+    let stored: StoredSerializingBlock = assert_golden_bincode("stored_block", &block);
+    assert_eq!(stored.block.header.timestamp, GOLDEN_TIMESTAMP);
+    assert_eq!(stored.block.transactions, block.block.transactions);
+}
+
+#[test]
+fn golden_raft_entry() {
+    let data = serialize(&MempoolRaftItem::Timestamp(GOLDEN_TIMESTAMP)).unwrap();
+    let context = serialize(&RaftContextKey {
+        proposer_id: 1,
+        proposer_run: 2,
+        proposal_id: 3,
+    })
+    .unwrap();
+
+    let mut entry = Entry::new();
+    entry.set_term(4);
+    entry.set_index(101);
+    entry.set_data(data);
+    entry.set_context(context);
+
+    let bytes = entry.write_to_bytes().unwrap();
+    assert_golden("raft_entry", &bytes);
+
+    let stored: Entry = protobuf::Message::parse_from_bytes(&bytes).unwrap();
+    let item: MempoolRaftItem = deserialize(stored.get_data()).unwrap();
+    assert_eq!(item, MempoolRaftItem::Timestamp(GOLDEN_TIMESTAMP));
+}
+
+#[test]
+fn golden_raft_hard_state() {
+    let mut hard_state = HardState::new();
+    hard_state.set_term(4);
+    hard_state.set_vote(1);
+    hard_state.set_commit(101);
+
+    let bytes = hard_state.write_to_bytes().unwrap();
+    assert_golden("raft_hard_state", &bytes);
+}
+
+#[test]
+fn golden_raft_snapshot_meta() {
+    let meta = SnapMetadata {
+        index: 100,
+        term: 4,
+    };
+
+    let stored: SnapMetadata = assert_golden_bincode("raft_snapshot_meta", &meta);
+    assert_eq!(stored, meta);
+}
+
+#[test]
+fn golden_mempool_consensused() {
+    let mut tx_pool = TxPool::default();
+    tx_pool.insert_with_expiry(GOLDEN_TX_HASH.to_owned(), spending_tx("000001"), Some(10));
+    tx_pool.insert_with_expiry(GOLDEN_OTHER_TX_HASH.to_owned(), spending_tx("000002"), None);
+
+    let mut consensused = MempoolConsensused::default()
+        .with_peers_len(3)
+        .with_partition_full_size(2)
+        .with_initial_issuances(vec![initial_issuance()])
+        .with_round_state(tx_pool, vec![GOLDEN_TIMESTAMP - 60, GOLDEN_TIMESTAMP]);
+    consensused.apply_committed_timestamp(GOLDEN_TIMESTAMP);
+    consensused.apply_committed_reward_schedule(RewardSchedule::Halving {
+        initial_reward: 100,
+        halving_interval: 10,
+    });
+
+    let stored: MempoolConsensused = assert_golden_bincode("mempool_consensused", &consensused);
+    assert_eq!(stored.median_time_past(), Some(GOLDEN_TIMESTAMP));
+
+    let bytes = serialize(&consensused).unwrap();
+    let import = same_version_mempool_consensused_to_import(b"key", &bytes, None).unwrap();
+    assert_eq!(import.partition_full_size, 2);
+    assert_eq!(import.init_issuances, vec![initial_issuance()]);
+}
+
+#[test]
+fn golden_mempool_consensused_before_tx_pool() {
+    let consensused = same_version_mempool_raft::MempoolConsensused {
+        unanimous_majority: 3,
+        sufficient_majority: 2,
+        partition_full_size: 2,
+        tx_pool: Some((GOLDEN_TX_HASH.to_owned(), spending_tx("000001")))
+            .into_iter()
+            .collect(),
+        tx_druid_pool: Vec::new(),
+        tx_current_block_previous_hash: None,
+        initial_utxo_txs: None,
+        utxo_set: Default::default(),
+        current_block_stored_info: Default::default(),
+        current_raft_coordinated_cmd_stored_info: Default::default(),
+        last_committed_raft_idx_and_term: (101, 4),
+        current_issuance: TokenAmount(25),
+        block_pipeline: same_version_mempool_raft::MiningPipelineInfo {
+            participants_intake: Default::default(),
+            participants_mining: Default::default(),
+            empty_participants: Default::default(),
+            last_winning_hashes: Default::default(),
+            all_winning_pow: Vec::new(),
+            unicorn_info: Default::default(),
+            winning_pow: None,
+            mining_pipeline_status: Default::default(),
+            current_phase_timeout_peer_ids: Default::default(),
+            current_phase_reset_pipeline_peer_ids: Default::default(),
+            unicorn_fixed_param: Default::default(),
+            current_block_num: Some(1),
+            current_block: None,
+            current_block_tx: Default::default(),
+            current_reward: TokenAmount(0),
+            proposed_keys: Default::default(),
+        },
+        last_mining_transaction_hashes: Vec::new(),
+        special_handling: None,
+        miner_whitelist: Default::default(),
+        timestamp: GOLDEN_TIMESTAMP,
+        init_issuances: vec![initial_issuance()],
+    };
+
+    let bytes = serialize(&consensused).unwrap();
+    assert_golden("mempool_consensused_before_tx_pool", &bytes);
+
+    let import = same_version_mempool_consensused_to_import(b"key", &bytes, None).unwrap();
+    assert_eq!(import.last_committed_raft_idx_and_term, (101, 4));
+    assert_eq!(import.current_issuance, TokenAmount(25));
+    assert_eq!(import.tx_current_block_num, Some(1));
+    assert_eq!(import.init_issuances, vec![initial_issuance()]);
+}
+
+//
+// Test helpers
+//
+
+fn token(amount: u64) -> Asset {
+    Asset::Token(TokenAmount(amount))
+}
+
+fn spending_tx(t_hash: &str) -> Transaction {
+    Transaction {
+        inputs: vec![TxIn {
+            previous_out: Some(OutPoint::new(t_hash.to_owned(), 0)),
+            ..TxIn::new()
+        }],
+        outputs: vec![TxOut::new_token_amount(
+            "a0b08e623c6800bb27dddb5d6f5956582e8bc0a5a8fd3e0df7d0dafdc6f8d5ab".to_owned(),
+            TokenAmount(10),
+            None,
+        )],
+        ..Transaction::new()
+    }
+}
+
+fn initial_issuance() -> InitialIssuance {
+    InitialIssuance::new(TokenAmount(5), "issuance_address".to_owned(), 2)
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "src", "upgrade", "golden"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{name}.hex"))
+}
+
+/// Check the bincode bytes of the value match the golden dump,
+/// and that the dump deserializes back to the same bytes.
+fn assert_golden_bincode<T: Serialize + DeserializeOwned>(name: &str, value: &T) -> T {
+    let bytes = serialize(value).unwrap();
+    assert_golden(name, &bytes);
+
+    let stored: T = deserialize(&bytes).unwrap();
+    assert_eq!(
+        serialize(&stored).unwrap(),
+        bytes,
+        "{name} did not round trip"
+    );
+    stored
+}
+
+/// Check the bytes match the golden dump, or rewrite it if `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, bytes: &[u8]) {
+    let path = golden_path(name);
+    let actual = hex::encode(bytes);
+
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        fs::write(&path, format!("{actual}\n")).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Missing golden file {:?}: {}", path, e));
+    assert_eq!(
+        actual,
+        expected.trim(),
+        "On-disk layout of {name} changed: add an upgrade step, then rerun with {UPDATE_GOLDEN_ENV}=1"
+    );
+}
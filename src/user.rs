@@ -1,42 +1,79 @@
+use crate::api::client_auth::ApiClientAuth;
 use crate::comms_handler::{CommsError, Event, Node, TcpTlsConfig};
-use crate::configurations::{ExtraNodeParams, TlsPrivateInfo, UserAutoGenTxSetup, UserNodeConfig};
+use crate::configurations::{
+    ExtraNodeParams, ResponseHook, TlsPrivateInfo, UserAutoGenTxSetup, UserExchangeConfig,
+    UserNodeConfig,
+};
+use crate::db_executor::{configure_db_pool, DbPool};
+use crate::db_utils::{self, CompactionSchedule};
+use crate::dust_consolidation::DustConsolidation;
+use crate::event_bus::{publish_event, EventPublisher, NodeEvent};
+use crate::event_hooks::EventHooks;
+use crate::exchange::{
+    load_deposit_accounts, load_deposit_tracking, post_webhook, save_deposit_tracking,
+    sweep_batches, DepositTracking,
+};
 use crate::interfaces::{
-    MempoolRequest, NodeType, RbPaymentData, RbPaymentRequestData, RbPaymentResponseData, Response,
-    UserApiRequest, UserRequest, UtxoFetchType, UtxoSet,
+    BlockchainItem, MempoolRequest, NodeType, RbPaymentData, RbPaymentRequestData,
+    RbPaymentResponseData, Response, ResponseReason, StorageRequest, UserApiRequest, UserRequest,
+    UtxoFetchType, UtxoSet,
 };
+use crate::light_client::{LightClient, Payment};
+use crate::node_identity::load_node_identity;
+use crate::output_data::{save_anchored_data, DataLimits};
+use crate::replay_guard::{ReplayTag, ReplayTagger, ReplayWindows};
+use crate::snapshot_manifest::{set_snapshot_identity, set_snapshot_signer};
 use crate::threaded_call::{ThreadedCallChannel, ThreadedCallSender};
+use crate::trade::{
+    load_trades, save_trades, Trades, DEFAULT_TRADE_TIMEOUT, TRADE_EXPIRY_CHECK_INTERVAL,
+};
 use crate::transaction_gen::{PendingMap, TransactionGen};
 use crate::transactor::Transactor;
 use crate::utils::{
-    create_socket_addr, generate_half_druid, get_payments_for_wallet_from_utxo, to_api_keys,
-    to_route_pow_infos, try_send_to_ui, ApiKeys, LocalEvent, LocalEventChannel, LocalEventSender,
-    ResponseResult, RoutesPoWInfo,
+    create_socket_addr, generate_half_druid, get_payments_for_wallet_from_utxo, get_timestamp_now,
+    is_locktime_expired, to_api_keys, to_route_pow_infos, try_send_to_ui, ApiKeys, LocalEvent,
+    LocalEventChannel, LocalEventSender, ResponseResult, RoutesPoWInfo,
+};
+use crate::wallet::{
+    with_signing_trigger, AddressStore, Argon2Params, FundStore, HotKeys, RemoteSigner,
+    SealedWalletSync, TxStatus, WalletDb, WalletDbError, WalletManager, WalletSyncKey,
+    WalletSyncMessage, DB_SPEC as WALLET_DB_SPEC,
 };
-use crate::wallet::{AddressStore, WalletDb, WalletDbError};
 use crate::Rs2JsMsg;
 use async_trait::async_trait;
-use bincode::deserialize;
+use bincode::{deserialize, serialize};
 use bytes::Bytes;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use tw_chain::primitives::asset::{Asset, TokenAmount};
 use tw_chain::primitives::block::Block;
 use tw_chain::primitives::druid::{DdeValues, DruidExpectation};
-use tw_chain::primitives::transaction::{GenesisTxHashSpec, Transaction, TxIn, TxOut};
+use tw_chain::primitives::transaction::{GenesisTxHashSpec, OutPoint, Transaction, TxIn, TxOut};
 use tw_chain::utils::transaction_utils::{
     construct_item_create_tx, construct_rb_payments_send_tx, construct_rb_receive_payment_tx,
-    construct_tx_core, construct_tx_ins_address, ReceiverInfo,
+    construct_tx_core, construct_tx_hash, construct_tx_ins_address, get_inputs_previous_out_point,
+    ReceiverInfo,
 };
 
+use std::sync::Arc;
+use std::time::Duration;
 use std::{collections::BTreeMap, error::Error, fmt, future::Future, net::SocketAddr};
 use tokio::sync::mpsc;
 use tokio::task;
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
 use tracing::{debug, error, error_span, info, info_span, trace, warn};
 use tracing_futures::Instrument;
+use wallet_core::{PaymentAddress, WalletCoreError};
 
 /// Key for last pow coinbase produced
 pub const TX_GENERATOR_KEY: &str = "TxGeneratorKey";
 
+/// Key for the block headers verified in light client mode
+pub const LIGHT_CLIENT_HEADERS_KEY: &str = "LightClientHeadersKey";
+
+/// Key for the payments sent with an expiry and not yet settled
+pub const EXPIRING_PAYMENTS_KEY: &str = "ExpiringPaymentsKey";
+
 /// Result wrapper for user errors
 pub type Result<T> = std::result::Result<T, UserError>;
 
@@ -47,6 +84,7 @@ pub enum UserError {
     AsyncTask(task::JoinError),
     Serialization(bincode::Error),
     WalletError(WalletDbError),
+    MissingState(&'static str),
 }
 
 impl fmt::Display for UserError {
@@ -57,6 +95,7 @@ impl fmt::Display for UserError {
             Self::AsyncTask(err) => write!(f, "Async task error: {err}"),
             Self::Serialization(err) => write!(f, "Serialization error: {err}"),
             Self::WalletError(err) => write!(f, "Wallet error: {err}"),
+            Self::MissingState(err) => write!(f, "Missing state: {err}"),
         }
     }
 }
@@ -69,6 +108,7 @@ impl Error for UserError {
             Self::Serialization(ref e) => Some(e),
             Self::AsyncTask(ref e) => Some(e),
             Self::WalletError(ref e) => Some(e),
+            Self::MissingState(_) => None,
         }
     }
 }
@@ -113,6 +153,17 @@ pub struct PendingPayment {
     locktime: Option<u64>,
 }
 
+/// Payment sent with an expiry, reverted if it expires before being added to a block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpiringPayment {
+    /// Block number the payment expires at
+    expiry: u64,
+    /// Outputs of the wallet spent by the payment
+    inputs: Vec<OutPoint>,
+    /// Outputs paid back to the wallet by the payment
+    change: Vec<OutPoint>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AutoDonate {
     Disabled,
@@ -124,20 +175,37 @@ pub enum AutoDonate {
 pub struct UserNode {
     node: Node,
     wallet_db: WalletDb,
+    wallets: WalletManager,
     local_events: LocalEventChannel,
+    peer_wait_timeout: Option<Duration>,
+    db_compaction: CompactionSchedule,
     threaded_calls: ThreadedCallChannel<UserNode>,
     ui_feedback_tx: Option<mpsc::Sender<Rs2JsMsg>>,
     mempool_addr: SocketAddr,
     api_info: (SocketAddr, Option<TlsPrivateInfo>, ApiKeys, RoutesPoWInfo),
     trading_peer: Option<SocketAddr>,
     next_payment: Option<(Option<SocketAddr>, Transaction)>,
+    payment_expiry: Option<u64>,
+    expiring_payments: BTreeMap<String, ExpiringPayment>,
     last_block_notified: Block,
     test_auto_gen_tx: Option<AutoGenTx>,
     received_utxo_set: Option<UtxoSet>,
+    requested_utxo_set: Option<UtxoFetchType>,
     pending_payments: (BTreeMap<SocketAddr, PendingPayment>, AutoDonate),
-    next_rb_payment_response: Option<(SocketAddr, Option<RbPaymentResponseData>)>,
-    next_rb_payment_data: Option<RbPaymentData>,
+    next_rb_payment_response: Option<(SocketAddr, String, Option<RbPaymentResponseData>)>,
     next_rb_payment: Option<(Option<SocketAddr>, Transaction)>,
+    trades: Trades,
+    trade_timeout: u64,
+    trade_expiry: Interval,
+    dust_consolidation: DustConsolidation,
+    exchange: Option<UserExchangeConfig>,
+    light_client: Option<LightClient>,
+    wallet_sync: Option<(SocketAddr, WalletSyncKey)>,
+    event_publisher: Option<EventPublisher>,
+    event_hooks: EventHooks,
+    replay_tagger: ReplayTagger,
+    replay_windows: ReplayWindows,
+    response_hook: Option<ResponseHook>,
 }
 
 impl UserNode {
@@ -147,7 +215,10 @@ impl UserNode {
     ///
     /// * `config` - UserNodeConfig object containing UserNode parameters.
     /// * `extra`  - additional parameter for construction
-    pub async fn new(config: UserNodeConfig, mut extra: ExtraNodeParams) -> Result<UserNode> {
+    pub async fn new(mut config: UserNodeConfig, mut extra: ExtraNodeParams) -> Result<UserNode> {
+        if let Some(tls_config) = extra.tls_config.take() {
+            config.tls_config = tls_config;
+        }
         let addr = config.user_address;
         let raw_mempool_addr = config
             .mempool_nodes
@@ -158,11 +229,49 @@ impl UserNode {
             .map_err(|_| UserError::ConfigError("Invalid mempool address"))?;
 
         let tls_addr = create_socket_addr(&addr).await.unwrap();
-        let tcp_tls_config = TcpTlsConfig::from_tls_spec(tls_addr, &config.tls_config)?;
+        let mut tcp_tls_config = TcpTlsConfig::from_tls_spec(
+            extra.bind_address.unwrap_or(tls_addr),
+            &config.tls_config,
+        )?;
+        if let Some(listener) = extra.listener.take() {
+            tcp_tls_config = tcp_tls_config.with_listener(listener).await;
+        }
+        if let Some(spec) = &config.peer_ip_filter {
+            tcp_tls_config.set_peer_ip_filter(spec)?;
+        }
+        if let Some(capacity) = config.event_queue_capacity {
+            tcp_tls_config.set_event_queue_capacity(capacity);
+        }
+        if let Some(key_path) = &config.backup_signing_key {
+            set_snapshot_signer(key_path)
+                .map_err(|_| UserError::ConfigError("Invalid backup signing key"))?;
+        }
+        let identity = load_node_identity(
+            config.identity_file.as_deref(),
+            config.user_db_mode,
+            &WALLET_DB_SPEC,
+        )
+        .map_err(|_| UserError::ConfigError("Invalid node identity file"))?;
+        set_snapshot_identity(&identity);
+        if let Some(threads) = config.db_threads {
+            configure_db_pool(DbPool::Wallet, threads);
+        }
         let api_addr = SocketAddr::new(tls_addr.ip(), config.user_api_port);
-        let api_tls_info = config
-            .user_api_use_tls
-            .then(|| tcp_tls_config.clone_private_info());
+        let api_tls_info = match (config.user_api_use_tls, &config.user_api_client_auth) {
+            (true, client_auth) => {
+                let mut info = tcp_tls_config.clone_private_info();
+                if let Some(spec) = client_auth {
+                    info.client_auth = Some(ApiClientAuth::from_spec(&info, spec)?);
+                }
+                Some(info)
+            }
+            (false, None) => None,
+            (false, Some(_)) => {
+                return Err(UserError::ConfigError(
+                    "API client authentication needs API TLS",
+                ))
+            }
+        };
         let api_keys = to_api_keys(config.api_keys.clone());
         let api_pow_info = to_route_pow_infos(config.routes_pow.clone());
         let disable_tcp_listener = extra.disable_tcp_listener;
@@ -174,17 +283,75 @@ impl UserNode {
             false,
         )
         .await?;
-
+        node.set_event_hooks(extra.event_hooks.clone());
+        node.set_identity(identity);
+        node.set_network_id(config.network_id.unwrap_or_default());
+
+        let hot_keys = match config.user_hot_wallet_keys.clone() {
+            Some(_) if config.user_remote_signer.is_some() => {
+                return Err(UserError::ConfigError(
+                    "Hot wallet keys cannot be used with a remote signer",
+                ));
+            }
+            Some(source) => {
+                info!("Reading hot wallet keys from {source:?}");
+                let hot_keys = tokio::task::spawn_blocking(move || HotKeys::load(&source))
+                    .await
+                    .map_err(|_| UserError::ConfigError("Failed to read hot wallet keys"))??;
+                Some(Arc::new(hot_keys))
+            }
+            None => None,
+        };
+        let db_mode = config.user_db_mode;
+        let kdf_params = config
+            .user_wallet_kdf
+            .unwrap_or_else(|| Argon2Params::for_db_mode(db_mode));
         let wallet_db = match extra.shared_wallet_db {
             Some(shared_db) => shared_db,
-            None => WalletDb::new(
+            None => WalletDb::new_with_hot_keys(
                 config.user_db_mode,
                 extra.wallet_db.take(),
-                config.passphrase,
-                extra.custom_wallet_spec,
-            )?,
+                config.passphrase.clone(),
+                extra.custom_wallet_spec.clone(),
+                kdf_params,
+                hot_keys,
+            )?
+            .with_key_gen(extra.key_gen),
+        };
+        let wallet_db = match &config.user_remote_signer {
+            Some(signer_config) => {
+                let signer = RemoteSigner::new(signer_config)
+                    .map_err(|_| UserError::ConfigError("Invalid remote signer"))?;
+                wallet_db.with_signer(Arc::new(signer))
+            }
+            None => wallet_db,
         };
-        let wallet_db = wallet_db.with_seed(config.user_wallet_seeds).await;
+        let wallet_db = wallet_db
+            .with_coin_selection(config.user_coin_selection.unwrap_or_default())
+            .with_change_address_reuse(config.user_reuse_change_address.unwrap_or_default());
+        let wallet_db = wallet_db.with_seed(config.user_wallet_seeds).await?;
+        wallet_db.check_network_id(node.network_id())?;
+        let wallets = WalletManager::new(wallet_db.clone());
+        let node_passphrase = &config.passphrase;
+        for named in config.user_wallets.iter().flatten() {
+            let passphrase = named.passphrase.clone().or_else(|| node_passphrase.clone());
+            wallets
+                .open(
+                    &named.name,
+                    config.user_db_mode,
+                    passphrase,
+                    extra.custom_wallet_spec.as_ref(),
+                    kdf_params,
+                )?
+                .check_network_id(node.network_id())?;
+            info!("Opened wallet {}", named.name);
+        }
+        let trades = load_trades(&wallet_db).await?;
+        let mut trade_expiry = interval_at(
+            Instant::now() + TRADE_EXPIRY_CHECK_INTERVAL,
+            TRADE_EXPIRY_CHECK_INTERVAL,
+        );
+        trade_expiry.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
         let pending_payments = match config.user_auto_donate {
             0 => (Default::default(), AutoDonate::Disabled),
@@ -192,24 +359,77 @@ impl UserNode {
         };
 
         let test_auto_gen_tx = make_transaction_gen(config.user_test_auto_gen_setup);
+        let light_client = match &config.user_light_client {
+            Some(light) => {
+                let storage_addr = create_socket_addr(&light.storage_node.address)
+                    .await
+                    .map_err(|_| UserError::ConfigError("Invalid light client storage address"))?;
+                let headers = match wallet_db.get_db_value(LIGHT_CLIENT_HEADERS_KEY).await? {
+                    Some(headers) => deserialize(&headers)?,
+                    None => Default::default(),
+                };
+                Some(LightClient::new(storage_addr, headers))
+            }
+            None => None,
+        };
+        let wallet_sync = match &config.user_wallet_sync {
+            Some(sync) => {
+                let peer_addr = create_socket_addr(&sync.peer.address)
+                    .await
+                    .map_err(|_| UserError::ConfigError("Invalid wallet sync peer address"))?;
+                let secret = hex::decode(&sync.secret)
+                    .map_err(|_| UserError::ConfigError("Invalid wallet sync secret"))?;
+                Some((peer_addr, WalletSyncKey::new(&secret)))
+            }
+            None => None,
+        };
+        let expiring_payments = match wallet_db.get_db_value(EXPIRING_PAYMENTS_KEY).await? {
+            Some(payments) => deserialize(&payments)?,
+            None => Default::default(),
+        };
+        let event_publisher = match &config.user_event_bus {
+            Some(bus) => Some(
+                EventPublisher::start(bus)
+                    .await
+                    .map_err(|_| UserError::ConfigError("Invalid event bus"))?,
+            ),
+            None => None,
+        };
 
         Ok(UserNode {
             node,
             wallet_db,
-            local_events: Default::default(),
+            wallets,
+            local_events: LocalEventChannel::new(config.local_event_capacity),
+            peer_wait_timeout: config.peer_wait_timeout.map(Duration::from_millis),
+            db_compaction: CompactionSchedule::new(config.db_compaction_interval),
             threaded_calls: Default::default(),
             ui_feedback_tx: Default::default(),
             mempool_addr,
             api_info: (api_addr, api_tls_info, api_keys, api_pow_info),
             trading_peer: None,
             next_payment: None,
+            payment_expiry: config.user_payment_expiry,
+            expiring_payments,
             last_block_notified: Default::default(),
             test_auto_gen_tx,
             received_utxo_set: None,
+            requested_utxo_set: None,
             pending_payments,
             next_rb_payment_response: None,
-            next_rb_payment_data: None,
             next_rb_payment: None,
+            trades,
+            trade_timeout: config.user_trade_timeout.unwrap_or(DEFAULT_TRADE_TIMEOUT),
+            trade_expiry,
+            dust_consolidation: DustConsolidation::new(config.user_dust_consolidation.as_ref()),
+            exchange: config.user_exchange,
+            light_client,
+            wallet_sync,
+            event_publisher,
+            event_hooks: extra.event_hooks,
+            replay_tagger: Default::default(),
+            replay_windows: Default::default(),
+            response_hook: extra.response_hook,
         })
     }
 
@@ -269,7 +489,9 @@ impl UserNode {
     /// Connect info for peers on the network.
     pub fn connect_info_peers(&self) -> (Node, Vec<SocketAddr>, Vec<SocketAddr>) {
         let mempool = Some(self.mempool_addr);
-        let to_connect = mempool.iter();
+        let storage = self.light_client.as_ref().map(|l| l.storage_addr());
+        let sync_peer = self.wallet_sync.as_ref().map(|(peer, _)| *peer);
+        let to_connect = mempool.iter().chain(storage.iter()).chain(sync_peer.iter());
         let expect_connect = mempool.iter();
         (
             self.node.clone(),
@@ -278,11 +500,16 @@ impl UserNode {
         )
     }
 
+    /// Read every entry of the wallet database, returning the number of entries
+    pub fn scan_dbs(&self) -> db_utils::Result<usize> {
+        self.wallet_db.scan_persistent_store()
+    }
+
     /// Info needed to run the API point.
     pub fn api_inputs(
         &self,
     ) -> (
-        WalletDb,
+        WalletManager,
         Node,
         SocketAddr,
         Option<TlsPrivateInfo>,
@@ -291,7 +518,7 @@ impl UserNode {
     ) {
         let (api_addr, api_tls_info, api_keys, routes_pow_info) = self.api_info.clone();
         (
-            self.wallet_db.clone(),
+            self.wallets.clone(),
             self.node.clone(),
             api_addr,
             api_tls_info,
@@ -305,6 +532,7 @@ impl UserNode {
         let wallet_db = self.wallet_db.take_closed_persistent_store().await;
         ExtraNodeParams {
             wallet_db: wallet_db.in_memory(),
+            key_gen: self.wallet_db.key_gen().clone(),
             ..Default::default()
         }
     }
@@ -324,14 +552,17 @@ impl UserNode {
         response: Result<Response>,
     ) -> ResponseResult {
         debug!("Response: {:?}", response);
+        if let (Some(hook), Ok(resp)) = (&self.response_hook, &response) {
+            hook.call(resp);
+        }
 
         if let Ok(resp) = &response {
             let ui_message = match resp.success {
                 true => Rs2JsMsg::Info {
-                    info: resp.reason.to_owned(),
+                    info: resp.reason.to_string(),
                 },
                 false => Rs2JsMsg::Error {
-                    error: resp.reason.to_owned(),
+                    error: resp.reason.to_string(),
                 },
             };
             try_send_to_ui(self.ui_feedback_tx.as_ref(), ui_message).await;
@@ -340,15 +571,15 @@ impl UserNode {
         match response {
             Ok(Response {
                 success: true,
-                reason: "Sent startup requests on reconnection",
+                reason: ResponseReason::SentStartupRequests,
             }) => debug!("Sent startup requests on reconnection"),
             Ok(Response {
                 success: false,
-                reason: "Failed to send startup requests on reconnection",
+                reason: ResponseReason::FailedToSendStartupRequests,
             }) => error!("Failed to send startup requests on reconnection"),
             Ok(Response {
                 success: true,
-                reason: "Shutdown",
+                reason: ResponseReason::Shutdown,
             }) => {
                 warn!("Shutdown now");
                 try_send_to_ui(self.ui_feedback_tx.as_ref(), Rs2JsMsg::Exit).await;
@@ -356,123 +587,184 @@ impl UserNode {
             }
             Ok(Response {
                 success: true,
-                reason: "Donation Requested",
+                reason: ResponseReason::DonationRequested,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Request Payment Address",
+                reason: ResponseReason::RequestPaymentAddress,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Payment transaction received",
+                reason: ResponseReason::PaymentTransactionReceived,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Item asset create transaction ready",
+                reason: ResponseReason::ItemAssetCreateTransactionReady,
             }) => {
-                self.send_next_payment_to_destinations(self.mempool_address())
+                if let Err(e) = self
+                    .send_next_payment_to_destinations(self.mempool_address())
                     .await
-                    .unwrap();
+                {
+                    error!("Next payment not sent {:?}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Received item-based payment request",
+                reason: ResponseReason::ReceivedItemBasedPaymentRequest,
             }) => {
-                self.send_rb_payment_response().await.unwrap();
-                self.send_next_rb_transaction_to_destinations(self.mempool_address())
+                if let Err(e) = self.send_rb_payment_response().await {
+                    error!("Item-based payment response not sent {:?}", e);
+                }
+                if let Err(e) = self
+                    .send_next_rb_transaction_to_destinations(self.mempool_address())
                     .await
-                    .unwrap();
+                {
+                    error!("Item-based payment transaction not sent {:?}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Received item-based payment response",
+                reason: ResponseReason::ReceivedItemBasedPaymentResponse,
             }) => {
-                self.send_next_rb_transaction_to_destinations(self.mempool_address())
+                if let Err(e) = self
+                    .send_next_rb_transaction_to_destinations(self.mempool_address())
                     .await
-                    .unwrap();
+                {
+                    error!("Item-based payment transaction not sent {:?}", e);
+                }
             }
+            Ok(Response {
+                success: false,
+                reason: ResponseReason::ItemBasedPaymentRejected,
+            }) => {}
+            Ok(Response {
+                success: true,
+                reason: ResponseReason::TradeOfferSent,
+            }) => {}
+            Ok(Response {
+                success: false,
+                reason: ResponseReason::FailedToSendTradeOffer,
+            }) => {}
             Ok(Response {
                 success: true,
-                reason: "New address ready to be sent",
+                reason: ResponseReason::NewAddressReadyToBeSent,
             }) => {
                 debug!("Sending new payment address");
-                self.send_address_to_trading_peer().await.unwrap();
+                if let Err(e) = self.send_address_to_trading_peer().await {
+                    error!("Payment address not sent {:?}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "New address generated",
+                reason: ResponseReason::NewAddressGenerated,
             }) => {
                 debug!("New address generated");
             }
             Ok(Response {
                 success: true,
-                reason: "Addresses deleted",
+                reason: ResponseReason::AddressesDeleted,
             }) => {
                 debug!("Addresses deleted");
             }
             Ok(Response {
                 success: true,
-                reason: "Next payment transaction ready",
+                reason: ResponseReason::DustConsolidationStarted,
+            }) => {}
+            Ok(Response {
+                success: true,
+                reason: ResponseReason::DustConsolidationStopped,
+            }) => {}
+            Ok(Response {
+                success: true,
+                reason: ResponseReason::NextPaymentTransactionReady,
             }) => {
-                self.send_next_payment_to_destinations(self.mempool_address())
+                if let Err(e) = self
+                    .send_next_payment_to_destinations(self.mempool_address())
                     .await
-                    .unwrap();
+                {
+                    error!("Next payment not sent {:?}", e);
+                }
             }
             Ok(Response {
                 success: false,
-                reason: "Insufficient funds for payment",
+                reason: ResponseReason::InsufficientFundsForPayment,
+            }) => {}
+            Ok(Response {
+                success: false,
+                reason: ResponseReason::InvalidPaymentAddress,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Ignore unexpected transaction",
+                reason: ResponseReason::PaymentNotConstructed,
+            }) => {}
+            Ok(Response {
+                success: false,
+                reason: ResponseReason::IgnoreUnexpectedTransaction,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Block mining notified",
+                reason: ResponseReason::BlockMiningNotified,
             }) => {
                 self.process_mining_notified().await;
+                if let Err(e) = self.request_exchange_deposits().await {
+                    error!("Exchange deposits not requested {}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Request UTXO set",
+                reason: ResponseReason::RequestUtxoSet,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Received UTXO set",
+                reason: ResponseReason::ReceivedUtxoSet,
             }) => {
-                self.update_running_total().await;
+                if let Err(e) = self.update_running_total().await {
+                    error!("Running total not updated {}", e);
+                }
             }
             Ok(Response {
                 success: true,
-                reason: "Node is connected",
+                reason: ResponseReason::BlockchainItemReceived,
+            }) => {}
+            Ok(Response {
+                success: true,
+                reason: ResponseReason::PaymentInclusionVerified,
+            }) => {}
+            Ok(Response {
+                success: false,
+                reason: ResponseReason::PaymentInclusionRejected,
+            }) => {}
+            Ok(Response {
+                success: true,
+                reason: ResponseReason::NodeIsConnected,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             }) => {}
             Ok(Response {
                 success: true, // Not always an error
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Connected to mempool",
+                reason: ResponseReason::ConnectedToMempool,
             }) => {}
             Ok(Response {
                 success: true,
-                reason: "Disconnected from mempool",
+                reason: ResponseReason::DisconnectedFromMempool,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Failed to connect to mempool",
+                reason: ResponseReason::FailedToConnectToMempool,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Failed to disconnect from mempool",
+                reason: ResponseReason::FailedToDisconnectFromMempool,
             }) => {}
             Ok(Response {
                 success: false,
-                reason: "Already disconnected from mempool",
+                reason: ResponseReason::AlreadyDisconnectedFromMempool,
             }) => {}
             Ok(Response {
                 success: true,
@@ -496,7 +788,7 @@ impl UserNode {
 
     /// Listens for new events from peers and handles them.
     /// The future returned from this function should be executed in the runtime. It will block execution.
-    pub async fn handle_next_event<E: Future<Output = &'static str> + Unpin>(
+    pub async fn handle_next_event<E: Future<Output = ResponseReason> + Unpin>(
         &mut self,
         exit: &mut E,
     ) -> Option<Result<Response>> {
@@ -511,6 +803,20 @@ impl UserNode {
                         return res;
                     }
                 }
+                _ = self.db_compaction.tick() => {
+                    trace!("handle_next_event compact db");
+                    self.wallet_db.compact_db().await;
+                }
+                _ = self.trade_expiry.tick() => {
+                    trace!("handle_next_event expire trades");
+                    self.abandon_expired_trades().await;
+                }
+                _ = self.dust_consolidation.tick() => {
+                    trace!("handle_next_event consolidate dust");
+                    if let Some(res) = self.consolidate_dust().await {
+                        return Some(Ok(res));
+                    }
+                }
                 Some(event) = self.local_events.rx.recv() => {
                     if let Some(res) = self.handle_local_event(event).await {
                         return Some(Ok(res));
@@ -528,9 +834,9 @@ impl UserNode {
     }
 
     /// Send initial requests:
-    /// - block notification request (if active)
+    /// - block notification request (if auto generating transactions or in exchange mode)
     pub async fn send_startup_requests(&mut self) -> Result<()> {
-        if self.is_test_auto_gen_tx_active() {
+        if self.is_test_auto_gen_tx_active() || self.exchange.is_some() {
             info!("Send startup requests: block notification");
             return self.send_block_notification_request().await;
         }
@@ -547,6 +853,11 @@ impl UserNode {
         &self.local_events.tx
     }
 
+    /// Time to wait for peers to connect before starting, unlimited if None
+    pub fn peer_wait_timeout(&self) -> Option<Duration> {
+        self.peer_wait_timeout
+    }
+
     /// UI feedback channel.
     pub fn ui_feedback_tx(&self) -> Option<mpsc::Sender<Rs2JsMsg>> {
         self.ui_feedback_tx.clone()
@@ -574,12 +885,12 @@ impl UserNode {
                     error!("Failed to send startup requests on reconnect: {}", err);
                     return Some(Response {
                         success: false,
-                        reason: "Failed to send startup requests on reconnection",
+                        reason: ResponseReason::FailedToSendStartupRequests,
                     });
                 }
                 Some(Response {
                     success: true,
-                    reason: "Sent startup requests on reconnection",
+                    reason: ResponseReason::SentStartupRequests,
                 })
             }
             LocalEvent::CoordinatedShutdown(_) => None,
@@ -620,7 +931,10 @@ impl UserNode {
         })?;
 
         let req_span = error_span!("request", ?req);
-        let response = self.handle_request(peer, req).instrument(req_span).await;
+        let trigger = request_signing_trigger(peer, &req);
+        let response = with_signing_trigger(trigger, self.handle_request(peer, req))
+            .instrument(req_span)
+            .await;
         trace!(?response, ?peer, "response");
 
         Ok(response)
@@ -639,7 +953,10 @@ impl UserNode {
         match req {
             UserApi(req) => self.handle_api_request(peer, req).await,
             SendUtxoSet { utxo_set } => Some(self.receive_utxo_set(utxo_set)),
-            SendAddressRequest => Some(self.receive_payment_address_request(peer)),
+            SendAddressRequest { replay_tag } => match self.check_replay(peer, replay_tag) {
+                Err(response) => Some(response),
+                Ok(()) => Some(self.receive_payment_address_request(peer)),
+            },
             SendPaymentTransaction { transaction } => {
                 Some(self.receive_payment_transaction(transaction).await)
             }
@@ -654,12 +971,15 @@ impl UserNode {
                     .await,
             ),
             SendRbPaymentResponse {
+                trade_id,
                 rb_payment_response,
             } => Some(
-                self.receive_rb_payment_response(peer, rb_payment_response)
+                self.receive_rb_payment_response(peer, trade_id, rb_payment_response)
                     .await,
             ),
             BlockMining { block } => Some(self.notified_block_mining(peer, block).await),
+            SendBlockchainItem { key, item } => self.receive_blockchain_item(peer, key, item).await,
+            SendWalletSync { sealed } => self.receive_wallet_sync(peer, sealed).await,
             Closing => self.receive_closing(peer),
         }
     }
@@ -699,10 +1019,13 @@ impl UserNode {
                 address,
                 amount,
                 locktime,
-            } => Some(
-                self.make_payment_transactions(None, address, amount, locktime)
-                    .await,
-            ),
+            } => Some(match self.check_payment_addresses(&address, None) {
+                Ok((address, _)) => {
+                    self.make_payment_transactions(None, address, amount, locktime)
+                        .await
+                }
+                Err(response) => response,
+            }),
             SendCreateItemRequest {
                 item_amount,
                 genesis_hash_spec,
@@ -717,14 +1040,19 @@ impl UserNode {
                 excess_address,
                 locktime,
             } => Some(
-                self.make_payment_transactions_provided_excess(
-                    None,
-                    address,
-                    amount,
-                    Some(excess_address),
-                    locktime,
-                )
-                .await,
+                match self.check_payment_addresses(&address, Some(&excess_address)) {
+                    Ok((address, excess_address)) => {
+                        self.make_payment_transactions_provided_excess(
+                            None,
+                            address,
+                            amount,
+                            excess_address,
+                            locktime,
+                        )
+                        .await
+                    }
+                    Err(response) => response,
+                },
             ),
             GenerateNewAddress => Some(self.generate_new_address().await),
             GetConnectionStatus => Some(self.receive_connection_status_request().await),
@@ -744,6 +1072,32 @@ impl UserNode {
                 )
                 .await,
             ),
+            MakeTradeOffer {
+                trade_peer,
+                send_asset,
+                genesis_hash,
+            } => Some(
+                self.make_trade_offer(trade_peer, send_asset, genesis_hash)
+                    .await,
+            ),
+            SetDustConsolidation { active } => Some(self.set_dust_consolidation(active)),
+        }
+    }
+
+    /// Start or stop the consolidation of the dust outputs of the wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `active` - Whether to start the consolidation.
+    pub fn set_dust_consolidation(&mut self, active: bool) -> Response {
+        self.dust_consolidation.set_active(active);
+        let reason = match active {
+            true => ResponseReason::DustConsolidationStarted,
+            false => ResponseReason::DustConsolidationStopped,
+        };
+        Response {
+            success: true,
+            reason,
         }
     }
 
@@ -753,13 +1107,17 @@ impl UserNode {
         _peer: SocketAddr,
         addresses: BTreeSet<String>,
     ) -> Response {
-        self.wallet_db
+        if let Err(e) = self
+            .wallet_db
             .destroy_spent_transactions_and_keys(Some(addresses))
-            .await;
+            .await
+        {
+            return wallet_error_response(e);
+        }
 
         Response {
             success: true,
-            reason: "Addresses deleted",
+            reason: ResponseReason::AddressesDeleted,
         }
     }
 
@@ -770,7 +1128,7 @@ impl UserNode {
         if join_handles.is_empty() {
             return Response {
                 success: false,
-                reason: "Already disconnected from mempool",
+                reason: ResponseReason::AlreadyDisconnectedFromMempool,
             };
         }
         for join_handle in join_handles {
@@ -778,7 +1136,7 @@ impl UserNode {
                 error!("Failed to disconnect from mempool: {}", err);
                 return Response {
                     success: false,
-                    reason: "Failed to disconnect from mempool",
+                    reason: ResponseReason::FailedToDisconnectFromMempool,
                 };
             }
         }
@@ -791,7 +1149,7 @@ impl UserNode {
         .await;
         Response {
             success: true,
-            reason: "Disconnected from mempool",
+            reason: ResponseReason::DisconnectedFromMempool,
         }
     }
 
@@ -802,14 +1160,14 @@ impl UserNode {
             error!("Failed to connect to mempool: {e:?}");
             return Response {
                 success: false,
-                reason: "Failed to connect to mempool",
+                reason: ResponseReason::FailedToConnectToMempool,
             };
         }
         if let Err(e) = self.send_block_notification_request().await {
             error!("Failed to send startup requests to mempool: {e:?}");
             return Response {
                 success: false,
-                reason: "Failed to send startup requests on reconnection",
+                reason: ResponseReason::FailedToSendStartupRequests,
             };
         }
         try_send_to_ui(
@@ -821,7 +1179,7 @@ impl UserNode {
         .await;
         Response {
             success: true,
-            reason: "Connected to mempool",
+            reason: ResponseReason::ConnectedToMempool,
         }
     }
 
@@ -837,7 +1195,7 @@ impl UserNode {
             .await;
             return Response {
                 success: true,
-                reason: "Node is disconnected",
+                reason: ResponseReason::NodeIsDisconnected,
             };
         }
         try_send_to_ui(
@@ -849,7 +1207,7 @@ impl UserNode {
         .await;
         Response {
             success: true,
-            reason: "Node is connected",
+            reason: ResponseReason::NodeIsConnected,
         }
     }
 
@@ -865,7 +1223,7 @@ impl UserNode {
 
         Some(Response {
             success: true,
-            reason: "Shutdown",
+            reason: ResponseReason::Shutdown,
         })
     }
     pub fn get_next_payment_transaction(&self) -> Option<(Option<SocketAddr>, Transaction)> {
@@ -887,16 +1245,58 @@ impl UserNode {
         &mut self,
         mempool_peer: SocketAddr,
     ) -> Result<()> {
-        let (peer, tx) = self.next_payment.take().unwrap();
-
-        self.send_transactions_to_mempool(mempool_peer, vec![tx.clone()])
-            .await?;
+        let (peer, tx) = self
+            .next_payment
+            .take()
+            .ok_or(UserError::MissingState("No next payment"))?;
 
         let b_num = self.last_block_notified.header.b_num;
+        let expiry = self.payment_expiry.map(|blocks| b_num + blocks + 1);
+        let sent = match expiry {
+            Some(expiry) => {
+                let transactions = vec![tx.clone()];
+                self.node
+                    .send(
+                        mempool_peer,
+                        MempoolRequest::SendExpiringTransactions {
+                            transactions,
+                            expiry,
+                        },
+                    )
+                    .await
+                    .map_err(UserError::from)
+            }
+            None => {
+                self.send_transactions_to_mempool(mempool_peer, vec![tx.clone()])
+                    .await
+            }
+        };
+        if let Err(e) = sent {
+            warn!("Payment not sent to the mempool node, its inputs can be spent again");
+            let inputs = get_inputs_previous_out_point(Some(&tx).into_iter())
+                .cloned()
+                .collect();
+            self.wallet_db.revert_payment(inputs, Vec::new()).await?;
+            return Err(e);
+        }
 
-        self.wallet_db
+        let change = self
+            .wallet_db
             .store_payment_transaction(tx.clone(), b_num)
-            .await;
+            .await?;
+
+        if let Some(expiry) = expiry {
+            let payment = ExpiringPayment {
+                expiry,
+                inputs: get_inputs_previous_out_point(Some(&tx).into_iter())
+                    .cloned()
+                    .collect(),
+                change: change.into_iter().map(|(out_p, ..)| out_p).collect(),
+            };
+            self.expiring_payments
+                .insert(construct_tx_hash(&tx), payment);
+            self.save_expiring_payments().await?;
+        }
 
         if let Some(peer) = peer {
             self.send_payment_to_receiver(peer, tx).await?;
@@ -915,11 +1315,14 @@ impl UserNode {
         &mut self,
         mempool_peer: SocketAddr,
     ) -> Result<()> {
-        let (peer, transaction) = self.next_rb_payment.take().unwrap();
+        let (peer, transaction) = self
+            .next_rb_payment
+            .take()
+            .ok_or(UserError::MissingState("No next item-based payment"))?;
         let b_num = self.last_block_notified.header.b_num;
         self.wallet_db
             .store_payment_transaction(transaction.clone(), b_num)
-            .await;
+            .await?;
         let _peer_span =
             info_span!("sending item-based transaction to mempool node for processing");
         let transactions = vec![transaction.clone()];
@@ -930,6 +1333,11 @@ impl UserNode {
             )
             .await?;
 
+        let druid = transaction.druid_info.as_ref().map(|d| d.druid.as_str());
+        if druid.is_some_and(|druid| self.trades.submit(druid)) {
+            save_trades(&self.wallet_db, &self.trades).await?;
+        }
+
         if let Some(peer) = peer {
             self.send_payment_to_receiver(peer, transaction).await?;
         }
@@ -947,13 +1355,14 @@ impl UserNode {
         address_list: UtxoFetchType,
     ) -> Option<Response> {
         let mempool_addr = self.mempool_address();
+        self.requested_utxo_set = Some(address_list.clone());
         self.send_request_utxo_set(address_list, mempool_addr, NodeType::User)
             .await
             .ok()?;
 
         Some(Response {
             success: true,
-            reason: "Request UTXO set",
+            reason: ResponseReason::RequestUtxoSet,
         })
     }
 
@@ -972,7 +1381,7 @@ impl UserNode {
             .ok()?;
         Some(Response {
             success: true,
-            reason: "Donation Requested",
+            reason: ResponseReason::DonationRequested,
         })
     }
 
@@ -994,7 +1403,7 @@ impl UserNode {
             .ok()?;
         Some(Response {
             success: true,
-            reason: "Request Payment Address",
+            reason: ResponseReason::RequestPaymentAddress,
         })
     }
 
@@ -1005,13 +1414,24 @@ impl UserNode {
     /// * `transaction` - Transaction to receive and save to wallet
     pub async fn receive_payment_transaction(&mut self, transaction: Transaction) -> Response {
         let b_num = self.last_block_notified.header.b_num;
-        self.wallet_db
+        let held = match self.held_for_event_bus() {
+            Ok(held) => held,
+            Err(e) => return wallet_error_response(e),
+        };
+        let payments = match self
+            .wallet_db
             .store_payment_transaction(transaction, b_num)
-            .await;
+            .await
+        {
+            Ok(payments) => payments,
+            Err(e) => return wallet_error_response(e),
+        };
+        self.publish_payments_received(held, &payments);
+        self.complete_trades(&payments).await;
 
         Response {
             success: true,
-            reason: "Payment transaction received",
+            reason: ResponseReason::PaymentTransactionReceived,
         }
     }
 
@@ -1035,17 +1455,42 @@ impl UserNode {
             _ => {
                 return Some(Response {
                     success: false,
-                    reason: "Ignore unexpected transaction",
+                    reason: ResponseReason::IgnoreUnexpectedTransaction,
                 })
             }
         };
 
+        let address = match self.check_payment_addresses(&address, None) {
+            Ok((address, _)) => address,
+            Err(response) => return Some(response),
+        };
         Some(
             self.make_payment_transactions(Some(peer), address, amount, locktime)
                 .await,
         )
     }
 
+    /// Check the addresses of a requested payment can be paid to on the network
+    /// of the node, returning the addresses paid to on chain
+    ///
+    /// ### Arguments
+    ///
+    /// * `address`        - Address to assign the payment transaction to
+    /// * `excess_address` - Address to assign the excess to
+    fn check_payment_addresses(
+        &self,
+        address: &str,
+        excess_address: Option<&str>,
+    ) -> std::result::Result<(String, Option<String>), Response> {
+        let network = self.node.network_id().address_byte();
+        let check = |address: &str| {
+            PaymentAddress::validate_for_network(address, network)
+                .map(PaymentAddress::into_string)
+                .map_err(|e| payment_error_response(WalletCoreError::InvalidAddress(e).into()))
+        };
+        Ok((check(address)?, excess_address.map(check).transpose()?))
+    }
+
     /// Process specified payment with a provided excess address,
     /// updating wallet and next_payment
     ///
@@ -1066,24 +1511,20 @@ impl UserNode {
     ) -> Response {
         let tx_out = TxOut::new_token_amount(address, amount, locktime);
         let asset_required = Asset::Token(amount);
-        let (tx_ins, tx_outs) = if let Ok(value) = self
+        let (tx_ins, tx_outs) = match self
             .wallet_db
             .fetch_tx_ins_and_tx_outs_provided_excess(asset_required, vec![tx_out], excess_address)
             .await
         {
-            value
-        } else {
-            return Response {
-                success: false,
-                reason: "Insufficient funds for payment",
-            };
+            Ok(value) => value,
+            Err(e) => return payment_error_response(e),
         };
         let payment_tx = construct_tx_core(tx_ins, tx_outs, None);
         self.next_payment = Some((peer, payment_tx));
 
         Response {
             success: true,
-            reason: "Next payment transaction ready",
+            reason: ResponseReason::NextPaymentTransactionReady,
         }
     }
 
@@ -1103,24 +1544,20 @@ impl UserNode {
         input_addresses: BTreeSet<String>,
         excess_address: Option<String>,
     ) -> Response {
-        let (tx_ins, tx_outs) = if let Ok(value) = self
+        let (tx_ins, tx_outs) = match self
             .wallet_db
             .fetch_tx_ins_and_tx_outs_merge_input_addrs(input_addresses, excess_address)
             .await
         {
-            value
-        } else {
-            return Response {
-                success: false,
-                reason: "Insufficient funds for payment",
-            };
+            Ok(value) => value,
+            Err(e) => return payment_error_response(e),
         };
         let payment_tx = construct_tx_core(tx_ins, tx_outs, None);
         self.next_payment = Some((None, payment_tx));
 
         Response {
             success: true,
-            reason: "Next payment transaction ready",
+            reason: ResponseReason::NextPaymentTransactionReady,
         }
     }
 
@@ -1184,7 +1621,12 @@ impl UserNode {
             .insert(peer, PendingPayment { amount, locktime });
 
         self.node
-            .send(peer, UserRequest::SendAddressRequest)
+            .send(
+                peer,
+                UserRequest::SendAddressRequest {
+                    replay_tag: self.replay_tagger.next_tag(),
+                },
+            )
             .await?;
 
         Ok(())
@@ -1206,8 +1648,11 @@ impl UserNode {
 
     /// Sends a payment address from a request
     pub async fn send_address_to_trading_peer(&mut self) -> Result<()> {
-        let peer = self.trading_peer.take().unwrap();
-        let (address, _) = self.wallet_db.generate_payment_address().await;
+        let peer = self
+            .trading_peer
+            .take()
+            .ok_or(UserError::MissingState("No trading peer"))?;
+        let (address, _) = self.wallet_db.generate_payment_address().await?;
         debug!("Address to send: {:?}", address);
 
         self.node
@@ -1232,8 +1677,54 @@ impl UserNode {
     /// ### Arguments
     ///
     /// * `b_num` - Block number to filter
-    pub async fn filter_locked_coinbase(&mut self, b_num: u64) {
-        self.wallet_db.filter_locked_coinbase(b_num).await;
+    pub async fn filter_locked_coinbase(&mut self, b_num: u64) -> Result<()> {
+        Ok(self.wallet_db.filter_locked_coinbase(b_num).await?)
+    }
+
+    /// Forget the expiring payments added to a block, and revert those expired
+    ///
+    /// A payment expired for a block can no longer be added to it or any later block.
+    ///
+    /// ### Arguments
+    ///
+    /// * `block` - Block that is being mined
+    async fn settle_expiring_payments(&mut self, block: &Block) -> Result<()> {
+        let included = |hash: &String| block.transactions.contains(hash);
+        let (b_num, timestamp) = (block.header.b_num, block.header.timestamp);
+        let settled: Vec<_> = self
+            .expiring_payments
+            .iter()
+            .filter(|(hash, payment)| {
+                included(hash) || is_locktime_expired(payment.expiry, b_num, timestamp)
+            })
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        if settled.is_empty() {
+            return Ok(());
+        }
+
+        for hash in settled {
+            let payment = self.expiring_payments.remove(&hash);
+            if let Some(payment) = payment.filter(|_| !included(&hash)) {
+                info!("Payment {} expired, its inputs can be spent again", hash);
+                self.wallet_db
+                    .revert_payment(payment.inputs, payment.change)
+                    .await?;
+                self.wallet_db
+                    .set_tx_history_status(vec![hash], TxStatus::Reverted)
+                    .await?;
+            }
+        }
+        self.save_expiring_payments().await
+    }
+
+    /// Save the expiring payments not yet settled
+    async fn save_expiring_payments(&self) -> Result<()> {
+        let payments = serialize(&self.expiring_payments).unwrap();
+        self.wallet_db
+            .set_db_value(EXPIRING_PAYMENTS_KEY, payments)
+            .await?;
+        Ok(())
     }
 
     /// Received a mined block notification: allow to update pending transactions
@@ -1242,12 +1733,23 @@ impl UserNode {
     ///
     /// * `peer` -  SocketAdress of the peer notifying.
     /// * `block` - Block that is being mined and will be stored.
-    pub async fn notified_block_mining(&mut self, peer: SocketAddr, block: Block) -> Response {
+    pub async fn notified_block_mining(&mut self, peer: SocketAddr, block: Arc<Block>) -> Response {
         if peer == self.mempool_addr {
-            self.wallet_db
-                .filter_locked_coinbase(block.header.b_num)
-                .await;
-            self.last_block_notified = block;
+            if let Err(e) = self.filter_locked_coinbase(block.header.b_num).await {
+                error!("Failed to filter locked coinbase {:?}", e);
+            }
+            if let Err(e) = self.settle_expiring_payments(&block).await {
+                error!("Failed to settle expiring payments {:?}", e);
+            }
+            if let Err(e) = self
+                .wallet_db
+                .set_tx_history_status(block.transactions.clone(), TxStatus::Confirmed)
+                .await
+            {
+                error!("Failed to confirm transaction history {:?}", e);
+            }
+            self.send_wallet_sync_summary().await;
+            self.last_block_notified = Arc::unwrap_or_clone(block);
             // Send the block to the UI for realtime feedback
             try_send_to_ui(
                 self.ui_feedback_tx.as_ref(),
@@ -1258,12 +1760,12 @@ impl UserNode {
             .await;
             Response {
                 success: true,
-                reason: "Block mining notified",
+                reason: ResponseReason::BlockMiningNotified,
             }
         } else {
             Response {
                 success: false,
-                reason: "Invalid block mining notifier",
+                reason: ResponseReason::InvalidBlockMiningNotifier,
             }
         }
     }
@@ -1278,8 +1780,10 @@ impl UserNode {
 
         // Load any data after restart
         if !auto_gen.tx_generator.is_up_to_date_with_snapshot() {
-            if let Some(v) = self.wallet_db.get_db_value(TX_GENERATOR_KEY).await {
-                auto_gen.tx_generator.apply_snapshot_state(&v);
+            match self.wallet_db.get_db_value(TX_GENERATOR_KEY).await {
+                Ok(Some(v)) => auto_gen.tx_generator.apply_snapshot_state(&v),
+                Ok(None) => (),
+                Err(e) => error!("Transaction generator state not loaded {}", e),
             }
         }
 
@@ -1325,11 +1829,385 @@ impl UserNode {
         }
 
         let auto_gen = self.test_auto_gen_tx.as_mut().unwrap();
-        self.wallet_db
+        if let Err(e) = self
+            .wallet_db
             .set_db_value(TX_GENERATOR_KEY, auto_gen.tx_generator.snapshot_state())
+            .await
+        {
+            error!("Transaction generator state not saved {}", e);
+        }
+    }
+
+    /// Save payments to the wallet, making them spendable
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments to the wallet addresses.
+    async fn save_payments(&mut self, payments: Vec<Payment>) -> Result<()> {
+        let b_num = self.last_block_notified.header.b_num;
+        let held = self.held_for_event_bus()?;
+        let payments = self
+            .wallet_db
+            .save_usable_payments_to_wallet(payments, b_num)
+            .await?;
+        self.publish_payments_received(held, &payments);
+        self.complete_trades(&payments).await;
+        self.process_exchange_deposits(&payments).await
+    }
+
+    /// Hold the payments to outputs not held by the wallet until their transaction
+    /// is proven included, in light client mode, returning the payments to save
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments to the wallet addresses.
+    async fn hold_unproven_payments(&mut self, payments: Vec<Payment>) -> Result<Vec<Payment>> {
+        let light_client = match &mut self.light_client {
+            Some(light_client) => light_client,
+            None => return Ok(payments),
+        };
+
+        let fund_store = self.wallet_db.get_fund_store()?;
+        let (held, new): (Vec<_>, Vec<_>) = payments
+            .into_iter()
+            .partition(|(out_point, ..)| fund_store.transactions().contains_key(out_point));
+        let (proven, keys) = light_client.hold_payments(new);
+        let storage_addr = light_client.storage_addr();
+        self.fetch_from_storage(storage_addr, keys).await;
+        Ok(held.into_iter().chain(proven).collect())
+    }
+
+    /// Process an item of an inclusion proof received from storage, in light client mode
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer` - Storage node sending the item.
+    /// * `key`  - Key the item was fetched with.
+    /// * `item` - Item received, empty if not found.
+    async fn receive_blockchain_item(
+        &mut self,
+        peer: SocketAddr,
+        key: String,
+        item: BlockchainItem,
+    ) -> Option<Response> {
+        let light_client = match &mut self.light_client {
+            Some(light_client) if light_client.storage_addr() == peer => light_client,
+            _ => return None,
+        };
+
+        let headers_len = light_client.headers().len();
+        let update = light_client.receive_item(&key, item);
+        let proven = light_client.take_proven_payments(&update);
+        if light_client.headers().len() != headers_len {
+            let headers = serialize(light_client.headers()).unwrap();
+            if let Err(e) = self
+                .wallet_db
+                .set_db_value(LIGHT_CLIENT_HEADERS_KEY, headers)
+                .await
+            {
+                return Some(wallet_error_response(e));
+            }
+        }
+        for (tx_hash, b_num) in &update.verified {
+            info!("Payment {} proven included in block {}", tx_hash, b_num);
+        }
+        for tx_hash in &update.rejected {
+            warn!("Payment {} not proven included in a block", tx_hash);
+        }
+        self.fetch_from_storage(peer, update.to_fetch).await;
+        if !proven.is_empty() {
+            if let Err(e) = self.save_payments(proven).await {
+                return Some(wallet_error_response(e));
+            }
+        }
+
+        let (success, reason) = if !update.rejected.is_empty() {
+            (false, ResponseReason::PaymentInclusionRejected)
+        } else if !update.verified.is_empty() {
+            (true, ResponseReason::PaymentInclusionVerified)
+        } else {
+            (true, ResponseReason::BlockchainItemReceived)
+        };
+        Some(Response { success, reason })
+    }
+
+    /// Send the summary of the wallet to the sync peer, if wallet sync is enabled
+    async fn send_wallet_sync_summary(&mut self) {
+        let summary = match self.wallet_db.sync_summary() {
+            Ok(summary) => summary,
+            Err(e) => return error!("Wallet sync summary not loaded {}", e),
+        };
+        self.send_wallet_sync(WalletSyncMessage::Summary(summary))
             .await;
     }
 
+    /// Seal and send a message to the sync peer, if wallet sync is enabled
+    ///
+    /// ### Arguments
+    ///
+    /// * `message` - Message to send.
+    async fn send_wallet_sync(&mut self, message: WalletSyncMessage) {
+        let (peer_addr, key) = match &self.wallet_sync {
+            Some(wallet_sync) => wallet_sync,
+            None => return,
+        };
+        let sealed = match key.seal(&message) {
+            Ok(sealed) => sealed,
+            Err(e) => return error!("Wallet sync not sealed {}", e),
+        };
+        let peer_addr = *peer_addr;
+        if let Err(e) = self
+            .node
+            .send(peer_addr, UserRequest::SendWalletSync { sealed })
+            .await
+        {
+            error!("Wallet sync not sent {:?}", e);
+        }
+    }
+
+    /// Process a message sealed by the sync peer
+    ///
+    /// A summary is answered with the changes the peer lacks and the summary of
+    /// this wallet, and an update with the changes the peer lacks if it asks for them.
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer`   - Peer sending the message.
+    /// * `sealed` - Message received.
+    async fn receive_wallet_sync(
+        &mut self,
+        peer: SocketAddr,
+        sealed: SealedWalletSync,
+    ) -> Option<Response> {
+        let key = match &self.wallet_sync {
+            Some((peer_addr, key)) if *peer_addr == peer => key,
+            _ => return None,
+        };
+        let message = match key.open(sealed) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Wallet sync from {} rejected: {}", peer, e);
+                return Some(Response {
+                    success: false,
+                    reason: ResponseReason::WalletSyncRejected,
+                });
+            }
+        };
+
+        // A summary opens an exchange and is answered with the summary of this
+        // wallet, so the peer sends back the changes this wallet lacks
+        let (reply_to, ask_back) = match message {
+            WalletSyncMessage::Summary(summary) => (Some(summary), true),
+            WalletSyncMessage::Update { update, summary } => {
+                if !update.is_empty() {
+                    if let Err(e) = self.wallet_db.apply_sync_update(update).await {
+                        error!("Wallet sync not applied {}", e);
+                        return Some(Response {
+                            success: false,
+                            reason: ResponseReason::WalletSyncRejected,
+                        });
+                    }
+                }
+                (summary, false)
+            }
+        };
+        if let Some(summary) = reply_to {
+            let update = match self.wallet_db.sync_update_for(&summary) {
+                Ok(update) => update,
+                Err(e) => return Some(wallet_error_response(e)),
+            };
+            if ask_back || !update.is_empty() {
+                let summary = match ask_back.then(|| self.wallet_db.sync_summary()).transpose() {
+                    Ok(summary) => summary,
+                    Err(e) => return Some(wallet_error_response(e)),
+                };
+                self.send_wallet_sync(WalletSyncMessage::Update { update, summary })
+                    .await;
+            }
+        }
+        Some(Response {
+            success: true,
+            reason: ResponseReason::WalletSyncApplied,
+        })
+    }
+
+    /// Request blockchain items from a storage node
+    ///
+    /// ### Arguments
+    ///
+    /// * `storage_addr` - Storage node to fetch from.
+    /// * `keys`         - Keys of the items to fetch.
+    async fn fetch_from_storage(&mut self, storage_addr: SocketAddr, keys: Vec<String>) {
+        for key in keys {
+            let request = StorageRequest::GetBlockchainItem { key };
+            if let Err(e) = self.node.send(storage_addr, request).await {
+                error!("Blockchain item not requested {:?}", e);
+            }
+        }
+    }
+
+    /// Request the UTXO set of the deposit addresses, in exchange mode
+    async fn request_exchange_deposits(&mut self) -> Result<()> {
+        if self.exchange.is_none() {
+            return Ok(());
+        }
+
+        let accounts = load_deposit_accounts(&self.wallet_db).await?;
+        if !accounts.is_empty() {
+            let address_list = UtxoFetchType::AnyOf(accounts.into_keys().collect());
+            self.request_utxo_set_for_wallet_update(address_list).await;
+        }
+        Ok(())
+    }
+
+    /// Outputs held by the wallet before saving payments, if the node has an event bus
+    /// or event hooks
+    fn held_for_event_bus(&self) -> Result<Option<FundStore>> {
+        let publishing = self.event_publisher.is_some() || !self.event_hooks.is_empty();
+        Ok(publishing
+            .then(|| self.wallet_db.get_fund_store())
+            .transpose()?)
+    }
+
+    /// Publish the payments to outputs not previously held by the wallet, if the node
+    /// has an event bus or event hooks
+    ///
+    /// ### Arguments
+    ///
+    /// * `held`     - Outputs held by the wallet before saving the payments.
+    /// * `payments` - Payments saved to the wallet.
+    fn publish_payments_received(
+        &self,
+        held: Option<FundStore>,
+        payments: &[(OutPoint, Asset, String, u64)],
+    ) {
+        let held = match held {
+            Some(held) => held,
+            None => return,
+        };
+
+        let b_num = self.last_block_notified.header.b_num;
+        for (out_point, amount, address, _) in payments {
+            if held.transactions().contains_key(out_point)
+                || held.spent_transactions().contains_key(out_point)
+            {
+                continue;
+            }
+            publish_event(&self.event_publisher, &self.event_hooks, || {
+                NodeEvent::PaymentReceived {
+                    address: address.clone(),
+                    out_point: out_point.clone(),
+                    amount: amount.clone(),
+                    b_num,
+                }
+            });
+        }
+    }
+
+    /// Notify the deposits confirmed among received payments and sweep them when due,
+    /// in exchange mode
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments saved to the wallet.
+    async fn process_exchange_deposits(
+        &mut self,
+        payments: &[(OutPoint, Asset, String, u64)],
+    ) -> Result<()> {
+        let config = match &self.exchange {
+            Some(config) => config.clone(),
+            None => return Ok(()),
+        };
+
+        let b_num = self.last_block_notified.header.b_num;
+        let mut tracking = load_deposit_tracking(&self.wallet_db).await?;
+        let accounts = load_deposit_accounts(&self.wallet_db).await?;
+        let deposits = tracking.confirm_deposits(&accounts, payments, b_num);
+
+        for deposit in deposits {
+            info!("Deposit confirmed: {:?}", deposit);
+            if let Some(url) = config.webhook_url.clone() {
+                let mut body = serde_json::json!(deposit);
+                body["event"] = serde_json::json!("deposit_confirmed");
+                tokio::spawn(async move {
+                    if let Err(e) = post_webhook(&url, &body).await {
+                        warn!("Deposit webhook failed: {}", e);
+                    }
+                });
+            }
+        }
+
+        if tracking.is_sweep_due(&config, b_num) {
+            self.sweep_exchange_deposits(&config, &mut tracking, b_num)
+                .await?;
+        }
+        Ok(save_deposit_tracking(&self.wallet_db, &tracking).await?)
+    }
+
+    /// Sweep the deposit addresses above the threshold into the cold address
+    ///
+    /// ### Arguments
+    ///
+    /// * `config`   - Exchange configuration.
+    /// * `tracking` - Deposits confirmed and swept.
+    /// * `b_num`    - Current block number.
+    async fn sweep_exchange_deposits(
+        &mut self,
+        config: &UserExchangeConfig,
+        tracking: &mut DepositTracking,
+        b_num: u64,
+    ) -> Result<()> {
+        tracking.start_sweep(config, b_num);
+
+        let accounts = load_deposit_accounts(&self.wallet_db).await?;
+        let fund_store = self.wallet_db.get_fund_store()?;
+        let mut outputs = Vec::new();
+        for (out_p, asset) in fund_store.transactions() {
+            let amount = match asset {
+                Asset::Token(amount) if !tracking.sweeping.contains_key(out_p) => *amount,
+                _ => continue,
+            };
+            let address = self.wallet_db.get_transaction_address(out_p)?;
+            if accounts.contains_key(&address) {
+                outputs.push((out_p.clone(), address, amount));
+            }
+        }
+
+        let mut sweeps = Vec::new();
+        for batch in sweep_batches(config, outputs) {
+            let out_points: Vec<_> = batch.iter().map(|(out_p, _)| out_p.clone()).collect();
+            let trigger = "internal:sweep_exchange_deposits".to_owned();
+            let fetched = self
+                .wallet_db
+                .fetch_tx_ins_and_tx_outs_from_supplied_txs(batch);
+            let (tx_ins, total) = match with_signing_trigger(trigger, fetched).await {
+                Ok(value) => value,
+                Err(e) => {
+                    error!("Deposit sweep failed: {:?}", e);
+                    continue;
+                }
+            };
+
+            let tx_out = TxOut::new_asset(config.cold_address.clone(), total, None);
+            sweeps.push(construct_tx_core(tx_ins, vec![tx_out], None));
+            tracking
+                .sweeping
+                .extend(out_points.into_iter().map(|out_p| (out_p, b_num)));
+        }
+
+        if sweeps.is_empty() {
+            return Ok(());
+        }
+        info!("Sweeping deposits in {} transactions", sweeps.len());
+        if let Err(e) = self
+            .send_transactions_to_mempool(self.mempool_address(), sweeps)
+            .await
+        {
+            error!("Deposit sweep not sent: {:?}", e);
+        }
+        Ok(())
+    }
+
     /// Get pending autogenerated transactions
     pub fn pending_test_auto_gen_txs(&self) -> Option<&PendingMap> {
         self.test_auto_gen_tx
@@ -1342,6 +2220,11 @@ impl UserNode {
         &self.wallet_db
     }
 
+    /// Get the trades of the node
+    pub fn get_trades(&self) -> &Trades {
+        &self.trades
+    }
+
     /// Get the last block notified to us
     pub fn get_last_block_notified(&self) -> &Block {
         &self.last_block_notified
@@ -1349,20 +2232,44 @@ impl UserNode {
 
     /// Generate a new payment address
     pub async fn generate_new_address(&mut self) -> Response {
-        let _ = self.wallet_db.generate_payment_address().await;
+        if let Err(e) = self.wallet_db.generate_payment_address().await {
+            return wallet_error_response(e);
+        }
         Response {
             success: true,
-            reason: "New address generated",
+            reason: ResponseReason::NewAddressGenerated,
         }
     }
 
     #[cfg(test)]
     /// Generate a new payment address
     pub async fn generate_static_address_for_miner(&mut self) -> String {
-        let (addr, _) = self.wallet_db.generate_payment_address().await;
+        let (addr, _) = self.wallet_db.generate_payment_address().await.unwrap();
         addr
     }
 
+    /// Checks a tagged message from a peer is neither expired nor replayed
+    ///
+    /// ### Arguments
+    ///
+    /// * `peer` - Peer the message was received from
+    /// * `tag`  - Replay tag of the message
+    fn check_replay(
+        &mut self,
+        peer: SocketAddr,
+        tag: ReplayTag,
+    ) -> std::result::Result<(), Response> {
+        self.replay_windows
+            .check(peer, &tag, get_timestamp_now())
+            .map_err(|e| {
+                warn!("Rejected message from {:?}: {}", peer, e);
+                Response {
+                    success: false,
+                    reason: ResponseReason::MessageRejected(e),
+                }
+            })
+    }
+
     /// Receives a request for a new payment address to be produced
     ///
     /// ### Arguments
@@ -1374,7 +2281,7 @@ impl UserNode {
 
         Response {
             success: true,
-            reason: "New address ready to be sent",
+            reason: ResponseReason::NewAddressReadyToBeSent,
         }
     }
 
@@ -1396,7 +2303,7 @@ impl UserNode {
         sender_asset: Asset,
         genesis_hash: Option<String>, /* genesis_hash of Item asset to receive */
     ) -> Result<()> {
-        let (sender_address, _) = self.wallet_db.generate_payment_address().await;
+        let (sender_address, _) = self.wallet_db.generate_payment_address().await?;
         let sender_half_druid = generate_half_druid();
 
         let (tx_ins, tx_outs) = self
@@ -1407,12 +2314,20 @@ impl UserNode {
         let (rb_payment_data, rb_payment_request_data) = make_rb_payment_send_tx_and_request(
             sender_asset,
             (tx_ins, tx_outs),
-            sender_half_druid,
-            sender_address,
+            sender_half_druid.clone(),
+            sender_address.clone(),
             genesis_hash,
         );
 
-        self.next_rb_payment_data = Some(rb_payment_data);
+        let now = get_timestamp_now();
+        self.trades.offer(
+            sender_half_druid,
+            peer,
+            rb_payment_data,
+            sender_address,
+            now,
+        );
+        save_trades(&self.wallet_db, &self.trades).await?;
         self.node
             .send(
                 peer,
@@ -1426,11 +2341,15 @@ impl UserNode {
 
     /// Sends a response to a new item-based payment request
     pub async fn send_rb_payment_response(&mut self) -> Result<()> {
-        let (peer, rb_payment_response) = self.next_rb_payment_response.take().unwrap();
+        let (peer, trade_id, rb_payment_response) = self
+            .next_rb_payment_response
+            .take()
+            .ok_or(UserError::MissingState("No item-based payment response"))?;
         self.node
             .send(
                 peer,
                 UserRequest::SendRbPaymentResponse {
+                    trade_id,
                     rb_payment_response,
                 },
             )
@@ -1455,7 +2374,10 @@ impl UserNode {
         rb_payment_request_data: RbPaymentRequestData,
     ) -> Response {
         let receiver_half_druid = generate_half_druid();
-        let (receiver_address, _) = self.wallet_db.generate_payment_address().await;
+        let (receiver_address, _) = match self.wallet_db.generate_payment_address().await {
+            Ok(address) => address,
+            Err(e) => return wallet_error_response(e),
+        };
         let asset_required = Asset::item(
             1,
             rb_payment_request_data.sender_drs_tx_expectation.clone(),
@@ -1466,28 +2388,44 @@ impl UserNode {
             .fetch_tx_ins_and_tx_outs(asset_required, Vec::new())
             .await;
 
-        let (tx_ins, tx_outs) = if let Ok(value) = tx_ins_and_outs {
-            value
-        } else {
-            return Response {
-                success: false,
-                reason: "Insufficient funds for payment",
-            };
+        let (tx_ins, tx_outs) = match tx_ins_and_outs {
+            Ok(value) => value,
+            Err(e) => return payment_error_response(e),
         };
 
+        let trade_id = rb_payment_request_data.sender_half_druid.clone();
+        let druid = trade_id.clone() + &receiver_half_druid;
+        let send_asset = Asset::item(
+            1,
+            rb_payment_request_data.sender_drs_tx_expectation.clone(),
+            None,
+        );
         let (rb_receive_tx, rb_payment_response) = make_rb_payment_item_tx_and_response(
             rb_payment_request_data,
             (tx_ins, tx_outs),
             receiver_half_druid,
+            receiver_address.clone(),
+        );
+
+        let now = get_timestamp_now();
+        self.trades.accept(
+            trade_id.clone(),
+            peer,
+            send_asset,
             receiver_address,
+            druid,
+            now,
         );
+        if let Err(e) = save_trades(&self.wallet_db, &self.trades).await {
+            return wallet_error_response(e);
+        }
 
         self.next_rb_payment = Some((Some(peer), rb_receive_tx));
-        self.next_rb_payment_response = Some((peer, Some(rb_payment_response)));
+        self.next_rb_payment_response = Some((peer, trade_id, Some(rb_payment_response)));
 
         Response {
             success: true,
-            reason: "Received item-based payment request",
+            reason: ResponseReason::ReceivedItemBasedPaymentRequest,
         }
     }
 
@@ -1502,21 +2440,165 @@ impl UserNode {
     /// ### Arguments
     ///
     /// * `peer`                    - Peer who sent the response
+    /// * `trade_id`                - Sender half druid identifying the trade
     /// * `rb_payment_response`     - (receiver address, half druid value, hash value of Vec<TxIn>)
     async fn receive_rb_payment_response(
         &mut self,
         peer: SocketAddr,
+        trade_id: String,
         rb_payment_response: Option<RbPaymentResponseData>,
     ) -> Response {
-        let rb_payment_data = self.next_rb_payment_data.take().unwrap();
-        //TODO: Handle `None` value upon item-based payment rejection
-        if let Some(rb_payment_response) = rb_payment_response {
-            let rb_send_tx = make_rb_payment_send_transaction(rb_payment_response, rb_payment_data);
-            self.next_rb_payment = Some((Some(peer), rb_send_tx));
+        let druid = rb_payment_response
+            .as_ref()
+            .map(|r| trade_id.clone() + &r.receiver_half_druid);
+        let rb_payment_data = match self.trades.answer(&trade_id, peer, druid) {
+            Some(rb_payment_data) => rb_payment_data,
+            None => {
+                return Response {
+                    success: false,
+                    reason: ResponseReason::UnexpectedItemBasedPaymentResponse,
+                }
+            }
+        };
+        if let Err(e) = save_trades(&self.wallet_db, &self.trades).await {
+            return wallet_error_response(e);
         }
-        Response {
+
+        match rb_payment_response {
+            Some(rb_payment_response) => {
+                let rb_send_tx =
+                    make_rb_payment_send_transaction(rb_payment_response, rb_payment_data);
+                self.next_rb_payment = Some((Some(peer), rb_send_tx));
+                Response {
+                    success: true,
+                    reason: ResponseReason::ReceivedItemBasedPaymentResponse,
+                }
+            }
+            None => {
+                self.refresh_wallet_after_trade().await;
+                Response {
+                    success: false,
+                    reason: ResponseReason::ItemBasedPaymentRejected,
+                }
+            }
+        }
+    }
+
+    /// Offer a peer to trade an asset for one of its item assets
+    ///
+    /// ### Arguments
+    ///
+    /// * `trade_peer`   - Peer holding the item asset
+    /// * `send_asset`   - The asset to be sent
+    /// * `genesis_hash` - Genesis hash of the item asset to receive
+    pub async fn make_trade_offer(
+        &mut self,
+        trade_peer: SocketAddr,
+        send_asset: Asset,
+        genesis_hash: Option<String>,
+    ) -> Response {
+        match self
+            .send_rb_payment_request(trade_peer, send_asset, genesis_hash)
+            .await
+        {
+            Ok(()) => Response {
+                success: true,
+                reason: ResponseReason::TradeOfferSent,
+            },
+            Err(UserError::WalletError(e @ WalletDbError::InsufficientFundsError)) => {
+                payment_error_response(e)
+            }
+            Err(e) => {
+                error!("Trade offer not sent: {:?}", e);
+                Response {
+                    success: false,
+                    reason: ResponseReason::FailedToSendTradeOffer,
+                }
+            }
+        }
+    }
+
+    /// Complete the accepted trades whose asset is among the received payments
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments saved to the wallet.
+    async fn complete_trades(&mut self, payments: &[(OutPoint, Asset, String, u64)]) {
+        let addresses = payments.iter().map(|(_, _, a, _)| a.as_str()).collect();
+        let completed = self.trades.complete(&addresses);
+        if !completed.is_empty() {
+            info!("Trades completed: {:?}", completed);
+            if let Err(e) = save_trades(&self.wallet_db, &self.trades).await {
+                warn!("Failed to save completed trades {:?}", e);
+            }
+        }
+    }
+
+    /// Abandon the trades not completed within the trade timeout
+    async fn abandon_expired_trades(&mut self) {
+        let abandoned = self.trades.expire(get_timestamp_now(), self.trade_timeout);
+        if !abandoned.is_empty() {
+            warn!("Trades abandoned: {:?}", abandoned);
+            if let Err(e) = save_trades(&self.wallet_db, &self.trades).await {
+                warn!("Failed to save abandoned trades {:?}", e);
+            }
+            self.refresh_wallet_after_trade().await;
+        }
+    }
+
+    /// Consolidate the dust outputs of the wallet into a single output, as the next
+    /// payment, if the mempool is quiet and there is enough dust
+    async fn consolidate_dust(&mut self) -> Option<Response> {
+        let block_txs = self.last_block_notified.transactions.len();
+        if self.next_payment.is_some() || !self.dust_consolidation.is_quiet(block_txs) {
+            trace!("Dust consolidation skipped while busy");
+            return None;
+        }
+
+        let outputs = match self.wallet_db.get_confirmed_outputs() {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                error!("Dust consolidation failed: {:?}", e);
+                return None;
+            }
+        };
+        let locked_coinbase = self.wallet_db.get_locked_coinbase().await;
+        let dust = self
+            .dust_consolidation
+            .select_dust(&outputs, &locked_coinbase)?;
+
+        let dust_len = dust.len();
+        let trigger = "internal:consolidate_dust".to_owned();
+        let fetched = self.wallet_db.fetch_tx_ins_and_tx_outs_consolidate(dust);
+        let (tx_ins, tx_outs) = match with_signing_trigger(trigger, fetched).await {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Dust consolidation failed: {:?}", e);
+                return None;
+            }
+        };
+
+        info!("Consolidating {} dust outputs", dust_len);
+        self.next_payment = Some((None, construct_tx_core(tx_ins, tx_outs, None)));
+        Some(Response {
             success: true,
-            reason: "Received item-based payment response",
+            reason: ResponseReason::NextPaymentTransactionReady,
+        })
+    }
+
+    /// Request the wallet UTXO set, so inputs of trades not committed are usable again
+    async fn refresh_wallet_after_trade(&mut self) {
+        let addresses = match self.wallet_db.get_known_addresses() {
+            Ok(addresses) => addresses,
+            Err(e) => return warn!("Wallet not refreshed after trade: {}", e),
+        };
+        let address_list = UtxoFetchType::AnyOf(addresses);
+        if self
+            .request_utxo_set_for_wallet_update(address_list)
+            .await
+            .is_none()
+        {
+            warn!("Wallet not refreshed after trade");
         }
     }
 
@@ -1527,11 +2609,22 @@ impl UserNode {
         genesis_hash_spec: GenesisTxHashSpec,
         metadata: Option<String>,
     ) -> Response {
+        let data_limits = DataLimits::default();
+        if metadata.as_ref().map_or(0, String::len) > data_limits.max_output_size {
+            return Response {
+                reason: ResponseReason::OutputDataTooLarge,
+                success: false,
+            };
+        }
+
         let AddressStore {
             public_key,
             secret_key,
             address_version: _,
-        } = self.wallet_db.generate_payment_address().await.1;
+        } = match self.wallet_db.generate_payment_address().await {
+            Ok((_, store)) => store,
+            Err(e) => return wallet_error_response(e),
+        };
 
         let block_num = self.last_block_notified.header.b_num;
         let item_asset_tx = construct_item_create_tx(
@@ -1543,11 +2636,15 @@ impl UserNode {
             None,
             metadata,
         );
+        let tx_hash = construct_tx_hash(&item_asset_tx);
+        if let Err(e) = save_anchored_data(&self.wallet_db, tx_hash, &item_asset_tx).await {
+            return wallet_error_response(e);
+        }
 
         self.next_payment = Some((None, item_asset_tx));
 
         Response {
-            reason: "Item asset create transaction ready",
+            reason: ResponseReason::ItemAssetCreateTransactionReady,
             success: true,
         }
     }
@@ -1608,18 +2705,20 @@ impl Transactor for UserNode {
 
         Response {
             success: true,
-            reason: "Received UTXO set",
+            reason: ResponseReason::ReceivedUtxoSet,
         }
     }
-    async fn update_running_total(&mut self) {
+    async fn update_running_total(&mut self) -> Result<()> {
         let utxo_set = self.received_utxo_set.take();
         let payments = get_payments_for_wallet_from_utxo(utxo_set.into_iter().flatten());
-
-        let b_num = self.last_block_notified.header.b_num;
-        self.wallet_db
-            .save_usable_payments_to_wallet(payments, b_num)
-            .await
-            .unwrap();
+        if let Some(fetched) = self.requested_utxo_set.take() {
+            let unspent = payments.iter().map(|(out_p, ..)| out_p.clone()).collect();
+            self.wallet_db
+                .refresh_watch_only_outputs(fetched, unspent)
+                .await?;
+        }
+        let payments = self.hold_unproven_payments(payments).await?;
+        self.save_payments(payments).await
     }
 }
 
@@ -1750,6 +2849,55 @@ pub fn make_rb_payment_send_transaction(
     construct_rb_payments_send_tx(tx_ins, tx_outs, None, receiver, 0, druid_values)
 }
 
+/// Response to a request the wallet could not be updated for
+///
+/// ### Arguments
+///
+/// * `e` - Error updating the wallet.
+fn wallet_error_response(e: impl fmt::Display) -> Response {
+    error!("Wallet update failed: {}", e);
+    Response {
+        success: false,
+        reason: ResponseReason::WalletUpdateFailed,
+    }
+}
+
+/// Response to a payment the wallet could not fund
+///
+/// ### Arguments
+///
+/// * `e` - Error fetching the inputs of the payment.
+fn payment_error_response(e: WalletDbError) -> Response {
+    let reason = match e {
+        WalletDbError::InsufficientFundsError => ResponseReason::InsufficientFundsForPayment,
+        WalletDbError::WalletCore(WalletCoreError::InvalidAddress(e)) => {
+            warn!("Payment to invalid address: {}", e);
+            ResponseReason::InvalidPaymentAddress
+        }
+        e => {
+            error!("Payment not constructed: {}", e);
+            ResponseReason::PaymentNotConstructed
+        }
+    };
+    Response {
+        success: false,
+        reason,
+    }
+}
+
+/// Trigger recorded in the signing audit log for the inputs signed handling a request
+///
+/// ### Arguments
+///
+/// * `peer` - Peer sending the request.
+/// * `req`  - Request to execute
+fn request_signing_trigger(peer: SocketAddr, req: &UserRequest) -> String {
+    match req {
+        UserRequest::UserApi(_) => format!("api:{req:?}"),
+        _ => format!("peer:{peer}:{req:?}"),
+    }
+}
+
 fn make_transaction_gen(setup: UserAutoGenTxSetup) -> Option<AutoGenTx> {
     if !setup.user_initial_transactions.is_empty() {
         Some(AutoGenTx {
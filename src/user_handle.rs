@@ -0,0 +1,163 @@
+//! Handle on a user node running in the background.
+//!
+//! Starts a user node with its connection loops and event loop on the current
+//! runtime, without the HTTP API, so it can be embedded in another program and
+//! driven through the handle.
+
+use crate::comms_handler::Node;
+use crate::configurations::{ExtraNodeParams, UserNodeConfig};
+use crate::interfaces::{ResponseReason, UserApiRequest, UserRequest, UtxoFetchType};
+use crate::user::{Result, UserNode};
+use crate::utils::{
+    loop_wait_connnect_to_peers_async, loops_re_connect_disconnect, shutdown_connections,
+    LocalEvent, LocalEventSender, ResponseResult,
+};
+use crate::wallet::WalletDb;
+use std::net::SocketAddr;
+use tokio::task::JoinHandle;
+use tracing::warn;
+use tw_chain::primitives::asset::TokenAmount;
+
+/// A user node running in the background
+pub struct UserNodeHandle {
+    node_conn: Node,
+    wallet_db: WalletDb,
+    local_event_tx: LocalEventSender,
+    main_loop: JoinHandle<()>,
+}
+
+impl UserNodeHandle {
+    /// Start a user node on the current runtime
+    ///
+    /// The node connects to its mempool node in the background.
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - User node configuration.
+    /// * `extra`  - Extra parameters, such as a shared wallet or in-memory databases.
+    pub async fn start(config: UserNodeConfig, extra: ExtraNodeParams) -> Result<Self> {
+        let mut node = UserNode::new(config, extra).await?;
+        let (node_conn, addrs_to_connect, expected_connected_addrs) = node.connect_info_peers();
+        let local_event_tx = node.local_event_tx().clone();
+        let peer_wait_timeout = node.peer_wait_timeout();
+        let wallet_db = node.get_wallet_db().clone();
+
+        let ((re_connect, stop_re_connect_tx), (disconnect, stop_disconnect_tx)) =
+            loops_re_connect_disconnect(
+                node_conn.clone(),
+                addrs_to_connect,
+                local_event_tx.clone(),
+            );
+        let re_connect = tokio::spawn(re_connect);
+        let disconnect = tokio::spawn(disconnect);
+
+        let main_loop = tokio::spawn({
+            let mut node_conn = node_conn.clone();
+            async move {
+                if !loop_wait_connnect_to_peers_async(
+                    node_conn.clone(),
+                    expected_connected_addrs,
+                    peer_wait_timeout,
+                )
+                .await
+                {
+                    warn!("Not all peers connected after {peer_wait_timeout:?}, starting anyway");
+                }
+                if let Err(e) = node.send_startup_requests().await {
+                    warn!("User node startup requests failed: {e:?}");
+                }
+
+                let mut exit = std::future::pending();
+                while let Some(response) = node.handle_next_event(&mut exit).await {
+                    if node.handle_next_event_response(response).await == ResponseResult::Exit {
+                        break;
+                    }
+                }
+
+                let _ = stop_re_connect_tx.send(());
+                let _ = stop_disconnect_tx.send(());
+                let _ = tokio::join!(re_connect, disconnect);
+                shutdown_connections(&mut node_conn).await;
+            }
+        });
+
+        Ok(Self {
+            node_conn,
+            wallet_db,
+            local_event_tx,
+            main_loop,
+        })
+    }
+
+    /// Address the node listens on
+    pub fn address(&self) -> SocketAddr {
+        self.node_conn.local_address()
+    }
+
+    /// Wallet of the node
+    pub fn wallet_db(&self) -> &WalletDb {
+        &self.wallet_db
+    }
+
+    /// Connection to the node peers
+    pub fn node_conn(&self) -> &Node {
+        &self.node_conn
+    }
+
+    /// Whether the node is connected to its mempool node
+    pub async fn is_connected(&self) -> bool {
+        !self.node_conn.get_peer_list().await.is_empty()
+    }
+
+    /// Queue a request to the node event loop, as the HTTP API does
+    ///
+    /// ### Arguments
+    ///
+    /// * `request` - Request to process.
+    pub fn request(&self, request: UserApiRequest) -> Result<()> {
+        let local_address = self.node_conn.local_address();
+        let request = UserRequest::UserApi(request);
+        Ok(self.node_conn.inject_next_event(local_address, request)?)
+    }
+
+    /// Queue a payment to an address
+    ///
+    /// ### Arguments
+    ///
+    /// * `address`  - Address to pay.
+    /// * `amount`   - Amount of tokens to pay.
+    /// * `locktime` - Block number or Unix timestamp before which the payment cannot be spent.
+    pub fn make_payment(
+        &self,
+        address: String,
+        amount: TokenAmount,
+        locktime: Option<u64>,
+    ) -> Result<()> {
+        self.request(UserApiRequest::MakePayment {
+            address,
+            amount,
+            locktime,
+        })
+    }
+
+    /// Queue an update of the wallet from the UTXO set of the mempool node
+    pub fn update_wallet_from_utxo_set(&self) -> Result<()> {
+        self.request(UserApiRequest::UpdateWalletFromUtxoSet {
+            address_list: UtxoFetchType::All,
+        })
+    }
+
+    /// Stop the node and wait for its loops to complete
+    pub async fn shutdown(self) {
+        let mut tx = self.local_event_tx;
+        if let Err(e) = tx
+            .send(LocalEvent::Exit(ResponseReason::Shutdown), "shutdown")
+            .await
+        {
+            warn!("User node already stopped: {e:?}");
+        }
+        if let Err(e) = self.main_loop.await {
+            warn!("User node main loop failed: {e:?}");
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use crate::clock::Clock;
 use crate::comms_handler::Node;
 use crate::configurations::{UnicornFixedInfo, UtxoSetSpec, WalletTxSpec};
 use crate::constants::{
-    BLOCK_PREPEND, COINBASE_MATURITY, D_DISPLAY_PLACES_U64, MINING_DIFFICULTY, NETWORK_VERSION,
-    REWARD_ISSUANCE_VAL, REWARD_SMOOTHING_VAL,
+    BLOCK_PREPEND, COINBASE_MATURITY, D_DISPLAY_PLACES_U64, LOCKTIME_TIMESTAMP_THRESHOLD,
+    MINING_DIFFICULTY, NETWORK_VERSION, REWARD_ISSUANCE_VAL, REWARD_SMOOTHING_VAL,
 };
+use crate::denomination::checked_add;
 use crate::interfaces::{
-    BlockchainItem, BlockchainItemMeta, DruidDroplet, PowInfo, ProofOfWork, StoredSerializingBlock,
+    BlockchainItem, BlockchainItemMeta, DruidDroplet, PowInfo, ProofOfWork, ResponseReason,
+    StoredSerializingBlock,
 };
 use crate::wallet::WalletDb;
 use crate::Rs2JsMsg;
@@ -14,6 +17,7 @@ use chrono::Utc;
 use futures::future::join_all;
 use rand::{self, Rng};
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
@@ -49,13 +53,17 @@ pub type RoutesPoWInfo = Arc<Mutex<BTreeMap<String, usize>>>;
 pub type ApiKeys = Arc<Mutex<BTreeMap<String, Vec<String>>>>;
 pub type LocalEventSender = MpscTracingSender<LocalEvent>;
 pub type LocalEventReceiver = mpsc::Receiver<LocalEvent>;
+pub type LogFilterReload = Box<dyn Fn(&str) -> Result<(), StringError> + Send + Sync>;
+
+/// Reload function for the installed tracing filter, if enabled
+static LOG_FILTER_RELOAD: Mutex<Option<(LogFilterReload, String)>> = Mutex::new(None);
 
 /// Local command event to nodes
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LocalEvent {
     CoordinatedShutdown(u64),
     ReconnectionComplete,
-    Exit(&'static str),
+    Exit(ResponseReason),
     Ignore,
 }
 
@@ -115,19 +123,14 @@ impl<T> MpscTracingSender<T> {
 }
 
 /// A running tasks or end result
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum RunningTaskOrResult<T> {
+    #[default]
     None,
     Running(task::JoinHandle<T>),
     Completed(Result<T, task::JoinError>),
 }
 
-impl<T> Default for RunningTaskOrResult<T> {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 impl<T> RunningTaskOrResult<T> {
     /// Wait for the handle to complete or wait forever
     pub async fn wait(&mut self) {
@@ -153,16 +156,31 @@ impl<T> RunningTaskOrResult<T> {
     }
 }
 
+/// Number of local events waiting for the event loop when not configured
+pub const DEFAULT_LOCAL_EVENT_CAPACITY: usize = 10;
+
 /// Channel for low volume local events
 pub struct LocalEventChannel {
     pub tx: LocalEventSender,
     pub rx: LocalEventReceiver,
 }
 
+impl LocalEventChannel {
+    /// Create a channel holding the given number of waiting events
+    ///
+    /// ### Arguments
+    ///
+    /// * `capacity` - Number of waiting events, DEFAULT_LOCAL_EVENT_CAPACITY if None
+    pub fn new(capacity: Option<usize>) -> Self {
+        let capacity = capacity.unwrap_or(DEFAULT_LOCAL_EVENT_CAPACITY).max(1);
+        let (tx, rx) = mpsc::channel(capacity);
+        Self { tx: tx.into(), rx }
+    }
+}
+
 impl Default for LocalEventChannel {
     fn default() -> Self {
-        let (tx, rx) = mpsc::channel(10);
-        Self { tx: tx.into(), rx }
+        Self::new(None)
     }
 }
 
@@ -283,6 +301,39 @@ pub fn tracing_log_try_init() -> Result<(), Box<dyn Error + Send + Sync + 'stati
     builder.try_init()
 }
 
+/// Enable runtime changes of the tracing filter directives
+///
+/// ### Arguments
+///
+/// * `reload`     - Function applying new filter directives to the installed subscriber.
+/// * `directives` - Filter directives currently in use.
+pub fn set_log_filter_reload(reload: LogFilterReload, directives: String) {
+    *LOG_FILTER_RELOAD.lock().unwrap() = Some((reload, directives));
+}
+
+/// Change the tracing filter directives at runtime (i.e "info,aiblock_network::comms_handler=debug")
+///
+/// ### Arguments
+///
+/// * `directives` - New filter directives to apply.
+pub fn reload_log_filter(directives: &str) -> Result<(), StringError> {
+    let mut log_filter = LOG_FILTER_RELOAD.lock().unwrap();
+    let (reload, current) = log_filter
+        .as_mut()
+        .ok_or_else(|| StringError("Log filter reloading is not enabled".to_owned()))?;
+
+    reload(directives)?;
+    info!("Log filter changed from {:?} to {:?}", current, directives);
+    *current = directives.to_owned();
+    Ok(())
+}
+
+/// Tracing filter directives currently in use, if reloading is enabled
+pub fn current_log_filter() -> Option<String> {
+    let log_filter = LOG_FILTER_RELOAD.lock().unwrap();
+    log_filter.as_ref().map(|(_, current)| current.clone())
+}
+
 /// Attempts to connect to all peers
 ///
 /// ### Arguments
@@ -290,11 +341,13 @@ pub fn tracing_log_try_init() -> Result<(), Box<dyn Error + Send + Sync + 'stati
 /// * `node`     - Node attempting to connect to peers.
 /// * `peers`    - Vec of socket addresses of peers
 /// * `close_rx` - Receiver for close event or None to finish when all connected
+/// * `clock`    - Clock used for the delay between connection attempts
 pub async fn loop_connnect_to_peers_async(
     mut node: Node,
     peers: Vec<SocketAddr>,
     mut close_rx: Option<oneshot::Receiver<()>>,
     mut local_events_tx: LocalEventSender,
+    clock: &Clock,
 ) {
     let mut is_initial_conn = true;
 
@@ -322,7 +375,7 @@ pub async fn loop_connnect_to_peers_async(
             is_initial_conn = false;
         }
 
-        let delay_retry = tokio::time::sleep(Duration::from_millis(500));
+        let delay_retry = clock.sleep(Duration::from_millis(500));
         if let Some(close_rx) = &mut close_rx {
             tokio::select! {
                 _ = delay_retry => (),
@@ -337,15 +390,30 @@ pub async fn loop_connnect_to_peers_async(
     }
 }
 
-/// check connected to all peers
+/// check connected to all peers, returning false if the timeout expired first
 ///
 /// ### Arguments
 ///
 /// * `node`     - Node attempting to connect to peers.
 /// * `peers`    - Vec of socket addresses of peers
-pub async fn loop_wait_connnect_to_peers_async(node: Node, peers: Vec<SocketAddr>) {
-    while !node.unconnected_peers(&peers).await.is_empty() {
-        tokio::time::sleep(Duration::from_millis(10)).await;
+/// * `timeout`  - Time to wait for the peers, unlimited if None
+pub async fn loop_wait_connnect_to_peers_async(
+    node: Node,
+    peers: Vec<SocketAddr>,
+    timeout: Option<Duration>,
+) -> bool {
+    let wait = async {
+        while !node.unconnected_peers(&peers).await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, wait).await.is_ok(),
+        None => {
+            wait.await;
+            true
+        }
     }
 }
 
@@ -386,8 +454,8 @@ pub fn get_sanction_addresses(path: String, jurisdiction: &str) -> Vec<String> {
 pub async fn create_and_save_fake_to_wallet(
     wallet_db: &mut WalletDb,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let (final_address, address_keys) = wallet_db.generate_payment_address().await;
-    let (receiver_addr, _) = wallet_db.generate_payment_address().await;
+    let (final_address, address_keys) = wallet_db.generate_payment_address().await?;
+    let (receiver_addr, _) = wallet_db.generate_payment_address().await?;
 
     let (t_hash, _payment_tx) = create_valid_transaction(
         "00000",
@@ -829,6 +897,51 @@ pub fn make_utxo_set_from_seed(
         .collect()
 }
 
+/// Load the seed UTXO set from a JSON file, in the format of `mempool_seed_utxo`
+///
+/// ### Arguments
+///
+/// * `path` - Path to the seed UTXO file
+pub fn load_seed_utxo_file(path: &str) -> Result<UtxoSetSpec, StringError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| StringError(format!("Cannot read seed UTXO file {path}: {e}")))?;
+    serde_json::from_str(&content)
+        .map_err(|e| StringError(format!("Invalid seed UTXO file {path}: {e}")))
+}
+
+/// Check the seed UTXO set outputs are spendable and add up to the genesis allocation
+///
+/// ### Arguments
+///
+/// * `seed`               - Seed UTXO set
+/// * `genesis_allocation` - Expected total of the seed amounts, not checked if None
+pub fn validate_seed_utxo(
+    seed: &UtxoSetSpec,
+    genesis_allocation: Option<TokenAmount>,
+) -> Result<(), StringError> {
+    let mut total = TokenAmount(0);
+    for (tx_hash, tx_out) in seed {
+        for out in tx_out {
+            if decode_pub_key(&out.public_key).is_err() {
+                return Err(StringError(format!(
+                    "Seed transaction {tx_hash} has an invalid public key {}",
+                    out.public_key
+                )));
+            }
+            total = checked_add(total, out.amount)
+                .ok_or_else(|| StringError("Seed UTXO amounts overflow".to_owned()))?;
+        }
+    }
+
+    match genesis_allocation {
+        Some(allocation) if allocation != total => Err(StringError(format!(
+            "Seed UTXO amounts add up to {} instead of the genesis allocation {}",
+            total.0, allocation.0
+        ))),
+        _ => Ok(()),
+    }
+}
+
 /// Generate wallet transactions from seed info
 ///
 /// ### Arguments
@@ -949,6 +1062,7 @@ pub fn loops_re_connect_disconnect(
                     addrs_to_connect,
                     Some(stop_re_connect_rx),
                     local_events_tx,
+                    &Clock::System,
                 )
                 .await;
                 info!("Reconnect complete");
@@ -1069,7 +1183,7 @@ async fn shutdown_on_path(
             );
 
             let event = if is_now {
-                LocalEvent::Exit("Shutdown")
+                LocalEvent::Exit(ResponseReason::Shutdown)
             } else {
                 LocalEvent::CoordinatedShutdown(block_num)
             };
@@ -1209,7 +1323,6 @@ pub fn construct_coinbase_tx(b_num: u64, amount: TokenAmount, address: String) -
         value: Asset::Token(amount),
         script_public_key: Some(address),
         locktime: b_num + COINBASE_MATURITY,
-        ..Default::default()
     };
 
     construct_tx_core(vec![tx_in], vec![tx_out], None)
@@ -1242,6 +1355,24 @@ pub fn get_test_common_unicorn() -> UnicornFixedInfo {
     }
 }
 
+/// Whether an output with the given locktime can be spent
+///
+/// Locktimes below `LOCKTIME_TIMESTAMP_THRESHOLD` are block numbers, others
+/// are Unix timestamps in seconds.
+///
+/// ### Arguments
+///
+/// * `locktime`  - Locktime of the output
+/// * `b_num`     - Current block number
+/// * `timestamp` - Current timestamp, in seconds since the epoch
+pub fn is_locktime_expired(locktime: u64, b_num: u64, timestamp: i64) -> bool {
+    if locktime < LOCKTIME_TIMESTAMP_THRESHOLD {
+        b_num >= locktime
+    } else {
+        u64::try_from(timestamp).is_ok_and(|t| t >= locktime)
+    }
+}
+
 /// Get the current timestamp as a string
 pub fn get_timestamp_now() -> i64 {
     let now = Utc::now();
@@ -1285,6 +1416,7 @@ pub mod rug_integer {
 #[cfg(test)]
 mod util_tests {
     use super::*;
+    use crate::configurations::TxOutSpec;
     use std::net::Ipv4Addr;
 
     #[tokio::test]
@@ -1319,4 +1451,48 @@ mod util_tests {
             SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 12300)
         );
     }
+
+    #[test]
+    /// Tests the seed UTXO set is checked against the genesis allocation
+    fn test_validate_seed_utxo() {
+        let public_key = "5371832122a8e804fa3520ec6861c3fa554a7f6fb617e6f0768452090207e07c";
+        let out = |amount| TxOutSpec {
+            public_key: public_key.to_owned(),
+            amount: TokenAmount(amount),
+            locktime: 0,
+        };
+        let seed: UtxoSetSpec = vec![
+            ("000000".to_owned(), vec![out(10), out(20)]),
+            ("000001".to_owned(), vec![out(5)]),
+        ]
+        .into_iter()
+        .collect();
+        let mut invalid_key = seed.clone();
+        invalid_key.insert(
+            "000002".to_owned(),
+            vec![TxOutSpec {
+                public_key: "00".to_owned(),
+                ..out(0)
+            }],
+        );
+
+        assert!(validate_seed_utxo(&seed, None).is_ok());
+        assert!(validate_seed_utxo(&seed, Some(TokenAmount(35))).is_ok());
+        assert!(validate_seed_utxo(&seed, Some(TokenAmount(36))).is_err());
+        assert!(validate_seed_utxo(&invalid_key, None).is_err());
+    }
+
+    #[test]
+    /// Tests locktimes are compared to the block number or the timestamp
+    fn test_is_locktime_expired() {
+        let time_lock = LOCKTIME_TIMESTAMP_THRESHOLD + 1000;
+        let time_lock_ts = time_lock as i64;
+
+        assert!(is_locktime_expired(0, 0, 0));
+        assert!(is_locktime_expired(10, 10, 0));
+        assert!(!is_locktime_expired(10, 9, time_lock_ts));
+        assert!(is_locktime_expired(time_lock, 0, time_lock_ts));
+        assert!(!is_locktime_expired(time_lock, u64::MAX, time_lock_ts - 1));
+        assert!(!is_locktime_expired(time_lock, 0, -1));
+    }
 }
@@ -0,0 +1,162 @@
+//! Append-only audit log of the signing operations of a wallet.
+//!
+//! Each input signed by the wallet is recorded with the address whose key signed
+//! it, the outpoint it spends, the time, and what triggered the signing: the API
+//! call, the peer request or the internal flow. Entries are stored in the wallet
+//! database under increasing sequence numbers, in the same write as the spending
+//! of the inputs, and are never changed or deleted.
+//!
+//! The trigger is set for the duration of a task with `with_signing_trigger`, so
+//! the flows signing inputs do not need to pass it down.
+
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use crate::wallet::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Key of the sequence number of the next audit entry
+pub const SIGNING_AUDIT_NEXT_KEY: &str = "SigningAuditNext";
+
+/// Prefix of the keys of the audit entries
+pub const SIGNING_AUDIT_PREFIX: &str = "SigningAudit_";
+
+/// Trigger of the signing done outside of any API call or peer request
+pub const INTERNAL_TRIGGER: &str = "internal";
+
+tokio::task_local! {
+    static SIGNING_TRIGGER: String;
+}
+
+/// Record of an input signed by the wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningAuditEntry {
+    pub seq: u64,
+    /// Signing time, in seconds since the UNIX epoch
+    pub timestamp: i64,
+    /// Address whose key signed the input
+    pub address: String,
+    pub out_point: OutPoint,
+    /// API call, peer request or internal flow the signing was done for
+    pub trigger: String,
+}
+
+/// Run a future with the trigger recorded for the inputs it signs
+///
+/// ### Arguments
+///
+/// * `trigger` - API call, peer request or internal flow, e.g. `api:MakePayment`.
+/// * `f`       - Future to run.
+pub async fn with_signing_trigger<F: Future>(trigger: String, f: F) -> F::Output {
+    SIGNING_TRIGGER.scope(trigger, f).await
+}
+
+/// Trigger of the signing done by the current task
+pub fn current_signing_trigger() -> String {
+    SIGNING_TRIGGER
+        .try_with(|trigger| trigger.clone())
+        .unwrap_or_else(|_| INTERNAL_TRIGGER.to_owned())
+}
+
+/// Append the entries of signed inputs to the audit log
+///
+/// ### Arguments
+///
+/// * `db`        - Wallet database, to get the next sequence number from.
+/// * `batch`     - Write batch to add the entries to.
+/// * `tx_used`   - Outpoints signed, with the address whose key signed them.
+/// * `trigger`   - API call, peer request or internal flow the signing was done for.
+/// * `timestamp` - Signing time, in seconds since the UNIX epoch.
+pub fn append_signing_audit(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    tx_used: &[(OutPoint, String)],
+    trigger: &str,
+    timestamp: i64,
+) -> Result<()> {
+    let mut seq: u64 = match db.get_cf(DB_COL_DEFAULT, SIGNING_AUDIT_NEXT_KEY)? {
+        Some(next) => deserialize(&next)?,
+        None => 0,
+    };
+
+    for (out_point, address) in tx_used {
+        let entry = SigningAuditEntry {
+            seq,
+            timestamp,
+            address: address.clone(),
+            out_point: out_point.clone(),
+            trigger: trigger.to_owned(),
+        };
+        batch.put_cf(DB_COL_DEFAULT, audit_key(seq), serialize(&entry).unwrap());
+        seq += 1;
+    }
+    batch.put_cf(
+        DB_COL_DEFAULT,
+        SIGNING_AUDIT_NEXT_KEY,
+        serialize(&seq).unwrap(),
+    );
+    Ok(())
+}
+
+/// Get the audit log entries, oldest first
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_signing_audit_log(db: &SimpleDb) -> Result<Vec<SigningAuditEntry>> {
+    let mut entries: Vec<SigningAuditEntry> = db
+        .iter_cf_clone(DB_COL_DEFAULT)?
+        .filter(|(key, _)| key.starts_with(SIGNING_AUDIT_PREFIX.as_bytes()))
+        .map(|(_, value)| deserialize(&value))
+        .collect::<bincode::Result<_>>()?;
+    entries.sort_by_key(|entry| entry.seq);
+    Ok(entries)
+}
+
+/// Key of the audit entry with the given sequence number
+fn audit_key(seq: u64) -> String {
+    format!("{SIGNING_AUDIT_PREFIX}{seq:020}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks entries are appended in order, with the trigger of the signing task
+    async fn signing_audit_appended_with_trigger() {
+        //Arrange
+        let mut db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let used = |n: i32| (OutPoint::new("tx_hash".to_owned(), n), format!("addr{n}"));
+        let api_trigger = with_signing_trigger("api:MakePayment".to_owned(), async {
+            current_signing_trigger()
+        });
+
+        //Act
+        let signed = vec![
+            (vec![used(0), used(1)], api_trigger.await),
+            (vec![used(2)], current_signing_trigger()),
+        ];
+        for (tx_used, trigger) in signed {
+            let mut batch = db.batch_writer();
+            append_signing_audit(&db, &mut batch, &tx_used, &trigger, 1_700_000_000).unwrap();
+            db.write(batch.done()).unwrap();
+        }
+
+        //Assert
+        let actual: Vec<_> = get_signing_audit_log(&db)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.seq, e.address, e.out_point.n, e.trigger))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (0, "addr0".to_owned(), 0, "api:MakePayment".to_owned()),
+                (1, "addr1".to_owned(), 1, "api:MakePayment".to_owned()),
+                (2, "addr2".to_owned(), 2, INTERNAL_TRIGGER.to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Versioned wallet backup format, shared with other wallet implementations.
+//!
+//! A backup is a JSON document holding metadata in clear and the key records
+//! in an encrypted container:
+//!
+//! ```json
+//! {
+//!   "format": "aiblock-wallet-backup",
+//!   "version": 1,
+//!   "metadata": { "created_at": "2024-01-01T00:00:00+00:00", "generator": "aiblock_network 1.1.0", "key_count": 1 },
+//!   "encryption": {
+//!     "kdf": "PBKDF2-HMAC-SHA256", "iterations": 100000, "salt": "<16 bytes hex>",
+//!     "cipher": "ChaCha20-Poly1305", "nonce": "<12 bytes hex>"
+//!   },
+//!   "ciphertext": "<hex>"
+//! }
+//! ```
+//!
+//! The 32 byte encryption key is derived from the UTF-8 passphrase with the
+//! given KDF, salt and iterations. The ciphertext is the JSON payload sealed
+//! with the cipher, key and nonce, with no associated data and the 16 byte tag
+//! appended. The payload lists the key records:
+//!
+//! ```json
+//! { "keys": [{ "address": "<hex>", "key_type": "ed25519", "public_key": "<hex>", "seed": "<hex>", "address_version": null }] }
+//! ```
+//!
+//! where `seed` is the 32 byte Ed25519 private key seed. On restore, the public
+//! key and the address are derived again from the seed and must match the record.
+//! Readers must reject documents of an unknown format, version, KDF or cipher.
+//!
+//! Version 2 backs up the whole wallet: its payload also lists the outputs paid
+//! to the addresses, spent or not, so the funds are restored without waiting
+//! for the UTXO set:
+//!
+//! ```json
+//! { "keys": [...], "outputs": [{ "out_point": { "t_hash": "<hex>", "n": 0 }, "asset": { "Token": 5 }, "address": "<hex>", "spent": false }] }
+//! ```
+//!
+//! Backups of the key-pairs only keep version 1.
+
+use super::key_import::{decode_keypair, KeyFormat, PKCS8_SEED_RANGE};
+use super::{AddressStore, Result, WalletDbError};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fs;
+use std::io::Write;
+use std::num::NonZeroU32;
+use std::path::Path;
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::OutPoint;
+use tw_chain::utils::transaction_utils::construct_address_for;
+
+/// Format name of wallet backups
+pub const BACKUP_FORMAT: &str = "aiblock-wallet-backup";
+
+/// Current version of the backup format, backing up the whole wallet
+pub const BACKUP_VERSION: u32 = 2;
+
+/// Version of the backups of the key-pairs only
+pub const BACKUP_KEYS_VERSION: u32 = 1;
+
+/// Key derivation function of version 1
+pub const BACKUP_KDF: &str = "PBKDF2-HMAC-SHA256";
+
+/// Cipher of version 1
+pub const BACKUP_CIPHER: &str = "ChaCha20-Poly1305";
+
+/// Key type of version 1 key records
+pub const BACKUP_KEY_TYPE: &str = "ed25519";
+
+/// KDF iterations used for new backups
+pub const BACKUP_KDF_ITERATIONS: u32 = 100_000;
+
+/// Length of the KDF salt of new backups
+const SALT_LEN: usize = 16;
+
+/// Length of the encryption key
+const KEY_LEN: usize = 32;
+
+/// Wallet backup document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletBackup {
+    pub format: String,
+    pub version: u32,
+    pub metadata: BackupMetadata,
+    pub encryption: BackupEncryption,
+    pub ciphertext: String,
+}
+
+/// Information on a backup, readable without the passphrase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupMetadata {
+    pub created_at: String,
+    pub generator: String,
+    pub key_count: usize,
+}
+
+/// Parameters of the encrypted container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEncryption {
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt: String,
+    pub cipher: String,
+    pub nonce: String,
+}
+
+/// Encrypted content of a backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BackupPayload {
+    keys: Vec<BackupKeyRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outputs: Vec<BackupOutputRecord>,
+}
+
+/// Output paid to an address of the wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupOutputRecord {
+    pub out_point: OutPoint,
+    pub asset: Asset,
+    pub address: String,
+    pub spent: bool,
+}
+
+/// Key-pair of an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupKeyRecord {
+    pub address: String,
+    pub key_type: String,
+    pub public_key: String,
+    pub seed: String,
+    pub address_version: Option<u64>,
+}
+
+impl BackupKeyRecord {
+    /// Record for the key-pair of an address held by the wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address of the key-pair.
+    /// * `store`   - Key-pair held by the wallet.
+    pub fn from_address_store(address: String, store: &AddressStore) -> Result<Self> {
+        let seed = store
+            .secret_key
+            .as_ref()
+            .get(PKCS8_SEED_RANGE)
+            .ok_or(WalletDbError::BackupError("Unexpected secret key format"))?;
+        Ok(Self {
+            address,
+            key_type: BACKUP_KEY_TYPE.to_owned(),
+            public_key: hex::encode(store.public_key.as_ref()),
+            seed: hex::encode(seed),
+            address_version: store.address_version,
+        })
+    }
+
+    /// Key-pair to hold in the wallet, checked against the record
+    pub fn into_address_store(self) -> Result<(String, AddressStore)> {
+        if self.key_type != BACKUP_KEY_TYPE {
+            return Err(WalletDbError::BackupError("Unsupported key type"));
+        }
+        let (public_key, secret_key) = decode_keypair(KeyFormat::Hex, &self.seed)?;
+        if hex::encode(public_key.as_ref()) != self.public_key.to_lowercase() {
+            return Err(WalletDbError::BackupError("Public key does not match seed"));
+        }
+        let address = construct_address_for(&public_key, self.address_version);
+        if address != self.address {
+            return Err(WalletDbError::AddressMismatchError(address));
+        }
+
+        let store = AddressStore {
+            public_key,
+            secret_key,
+            address_version: self.address_version,
+        };
+        Ok((self.address, store))
+    }
+}
+
+impl WalletBackup {
+    /// Encrypt key records into a new backup
+    ///
+    /// ### Arguments
+    ///
+    /// * `keys`       - Key records to back up.
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub fn seal(keys: Vec<BackupKeyRecord>, passphrase: &str) -> Result<Self> {
+        let payload = BackupPayload {
+            keys,
+            outputs: Vec::new(),
+        };
+        Self::seal_payload(BACKUP_KEYS_VERSION, payload, passphrase)
+    }
+
+    /// Encrypt key records and the outputs of their addresses into a new backup
+    ///
+    /// ### Arguments
+    ///
+    /// * `keys`       - Key records to back up.
+    /// * `outputs`    - Outputs paid to the addresses of the keys.
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub fn seal_wallet(
+        keys: Vec<BackupKeyRecord>,
+        outputs: Vec<BackupOutputRecord>,
+        passphrase: &str,
+    ) -> Result<Self> {
+        let payload = BackupPayload { keys, outputs };
+        Self::seal_payload(BACKUP_VERSION, payload, passphrase)
+    }
+
+    /// Encrypt a payload into a new backup of the given version
+    fn seal_payload(version: u32, payload: BackupPayload, passphrase: &str) -> Result<Self> {
+        let rng = SystemRandom::new();
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut salt)
+            .and_then(|_| rng.fill(&mut nonce))
+            .map_err(|_| WalletDbError::BackupError("Random generation failed"))?;
+
+        let metadata = BackupMetadata {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            generator: format!("aiblock_network {}", env!("CARGO_PKG_VERSION")),
+            key_count: payload.keys.len(),
+        };
+        let encryption = BackupEncryption {
+            kdf: BACKUP_KDF.to_owned(),
+            iterations: BACKUP_KDF_ITERATIONS,
+            salt: hex::encode(salt),
+            cipher: BACKUP_CIPHER.to_owned(),
+            nonce: hex::encode(nonce),
+        };
+
+        let mut content = serde_json::to_vec(&payload)
+            .map_err(|_| WalletDbError::BackupError("Payload serialization failed"))?;
+        cipher_key(&encryption, passphrase)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut content,
+            )
+            .map_err(|_| WalletDbError::BackupError("Encryption failed"))?;
+
+        Ok(Self {
+            format: BACKUP_FORMAT.to_owned(),
+            version,
+            metadata,
+            encryption,
+            ciphertext: hex::encode(content),
+        })
+    }
+
+    /// Decrypt the key records of a backup
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub fn open(&self, passphrase: &str) -> Result<Vec<BackupKeyRecord>> {
+        Ok(self.open_payload(passphrase)?.keys)
+    }
+
+    /// Decrypt the key records of a backup and the outputs of their addresses
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub fn open_wallet(
+        &self,
+        passphrase: &str,
+    ) -> Result<(Vec<BackupKeyRecord>, Vec<BackupOutputRecord>)> {
+        let payload = self.open_payload(passphrase)?;
+        Ok((payload.keys, payload.outputs))
+    }
+
+    /// Write the backup to a new file, only readable by its owner
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Backup file to create.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|_| WalletDbError::BackupError("Backup serialization failed"))?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)?.write_all(&json)?;
+        Ok(())
+    }
+
+    /// Read a backup from a file
+    ///
+    /// ### Arguments
+    ///
+    /// * `path` - Backup file.
+    pub fn load(path: &Path) -> Result<Self> {
+        serde_json::from_slice(&fs::read(path)?)
+            .map_err(|_| WalletDbError::BackupError("Invalid backup document"))
+    }
+
+    /// Decrypt the payload of a backup of a supported version
+    fn open_payload(&self, passphrase: &str) -> Result<BackupPayload> {
+        let supported = self.version == BACKUP_VERSION || self.version == BACKUP_KEYS_VERSION;
+        if self.format != BACKUP_FORMAT || !supported {
+            return Err(WalletDbError::BackupError("Unsupported backup format"));
+        }
+        let nonce: [u8; NONCE_LEN] = hex::decode(&self.encryption.nonce)?
+            .try_into()
+            .map_err(|_| WalletDbError::BackupError("Invalid nonce"))?;
+
+        let mut content = hex::decode(&self.ciphertext)?;
+        let content = cipher_key(&self.encryption, passphrase)?
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut content,
+            )
+            .map_err(|_| WalletDbError::PassphraseError)?;
+        serde_json::from_slice(content).map_err(|_| WalletDbError::BackupError("Invalid payload"))
+    }
+}
+
+/// Cipher key derived from the passphrase
+///
+/// ### Arguments
+///
+/// * `encryption`  - Parameters of the encrypted container.
+/// * `passphrase`  - Passphrase protecting the backup.
+fn cipher_key(encryption: &BackupEncryption, passphrase: &str) -> Result<LessSafeKey> {
+    if encryption.kdf != BACKUP_KDF || encryption.cipher != BACKUP_CIPHER {
+        return Err(WalletDbError::BackupError("Unsupported backup encryption"));
+    }
+    let iterations = NonZeroU32::new(encryption.iterations)
+        .ok_or(WalletDbError::BackupError("Invalid KDF iterations"))?;
+
+    let salt = hex::decode(&encryption.salt)?;
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        &salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+        .map_err(|_| WalletDbError::BackupError("Invalid key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> BackupKeyRecord {
+        let seed = "d4ee72dbf913584ad5b6d8f1f769f8ad3afe7c28cbf1d4fbe097a88f44755842";
+        let (public_key, secret_key) = decode_keypair(KeyFormat::Hex, seed).unwrap();
+        let address = construct_address_for(&public_key, None);
+        let store = AddressStore {
+            public_key,
+            secret_key,
+            address_version: None,
+        };
+        BackupKeyRecord::from_address_store(address, &store).unwrap()
+    }
+
+    #[test]
+    /// Checks key records are restored from a backup with its passphrase only
+    fn seal_open_backup() {
+        let backup = WalletBackup::seal(vec![record()], "passphrase").unwrap();
+        let json = serde_json::to_string(&backup).unwrap();
+        let backup: WalletBackup = serde_json::from_str(&json).unwrap();
+
+        let opened = backup.open("passphrase").unwrap();
+        let wrong_passphrase = backup.open("other");
+
+        assert_eq!(backup.metadata.key_count, 1);
+        assert_eq!(opened, vec![record()]);
+        assert!(matches!(
+            wrong_passphrase,
+            Err(WalletDbError::PassphraseError)
+        ));
+        assert!(opened[0].clone().into_address_store().is_ok());
+    }
+
+    #[test]
+    /// Checks the outputs of a wallet backup are restored, and key backups keep version 1
+    fn seal_open_wallet_backup() {
+        let output = BackupOutputRecord {
+            out_point: OutPoint::new("tx_hash".to_owned(), 1),
+            asset: Asset::token_u64(5),
+            address: record().address,
+            spent: true,
+        };
+        let keys_backup = WalletBackup::seal(vec![record()], "passphrase").unwrap();
+        let backup = WalletBackup::seal_wallet(vec![record()], vec![output.clone()], "passphrase");
+        let backup = backup.unwrap();
+
+        let (keys, outputs) = backup.open_wallet("passphrase").unwrap();
+        let (_, no_outputs) = keys_backup.open_wallet("passphrase").unwrap();
+
+        assert_eq!((keys_backup.version, backup.version), (1, 2));
+        assert_eq!(keys, vec![record()]);
+        assert_eq!(outputs, vec![output]);
+        assert!(no_outputs.is_empty());
+    }
+
+    #[test]
+    /// Checks records not matching their seed, and unknown versions, are rejected
+    fn open_backup_invalid() {
+        let mismatched_address = BackupKeyRecord {
+            address: "other".to_owned(),
+            ..record()
+        };
+        let mut future_backup = WalletBackup::seal(vec![record()], "passphrase").unwrap();
+        future_backup.version = BACKUP_VERSION + 1;
+
+        let restored = mismatched_address.into_address_store();
+        let opened = future_backup.open("passphrase");
+
+        assert!(matches!(
+            restored,
+            Err(WalletDbError::AddressMismatchError(_))
+        ));
+        assert!(matches!(opened, Err(WalletDbError::BackupError(_))));
+    }
+}
@@ -0,0 +1,143 @@
+//! Changes to the stores of a wallet applied in a single database write.
+//!
+//! The key-pairs, the transaction stores and the fund store of a wallet are
+//! separate entries of its database. Saved one after the other, a stop of the
+//! node in between leaves the wallet inconsistent, e.g. with an output in the
+//! fund store whose transaction store, and so its address, is missing. The
+//! changes collected in a `WalletBatch` are instead added to one write batch,
+//! so they are all written or none of them.
+
+use super::{
+    get_known_key_address, labels, save_address_store_to_wallet, save_public_address_store,
+    save_transaction_to_wallet, set_known_key_address, AddressStore, FundStoreUpdate, HotKeys,
+    Result, TransactionStore,
+};
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch};
+use crate::utils::get_timestamp_now;
+use tw_chain::crypto::secretbox_chacha20_poly1305 as secretbox;
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Changes to the address, transaction and fund stores of a wallet
+#[derive(Debug, Clone, Default)]
+pub struct WalletBatch {
+    /// Key-pairs of the addresses to save
+    addresses: Vec<(String, AddressStore)>,
+    /// Addresses of the outputs to save
+    transactions: Vec<(OutPoint, TransactionStore)>,
+    /// Outputs to add to the fund store
+    outputs: Vec<(OutPoint, Asset)>,
+    /// Outputs of the fund store spent
+    spent: Vec<OutPoint>,
+}
+
+impl WalletBatch {
+    /// Save an address and its key-pair
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to save to wallet.
+    /// * `keys`    - Address-related keys to save.
+    pub fn save_address(&mut self, address: String, keys: AddressStore) {
+        self.addresses.push((address, keys));
+    }
+
+    /// Save the address an output is paid to, without adding it to the funds
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p`       - Outpoint of the output.
+    /// * `key_address` - Address the output is paid to.
+    pub fn save_transaction(&mut self, out_p: OutPoint, key_address: String) {
+        self.transactions
+            .push((out_p, TransactionStore { key_address }));
+    }
+
+    /// Save an output paid to the wallet, adding it to the funds
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p`       - Outpoint of the output.
+    /// * `asset`       - Asset held by the output.
+    /// * `key_address` - Address the output is paid to.
+    pub fn save_payment(&mut self, out_p: OutPoint, asset: Asset, key_address: String) {
+        self.save_transaction(out_p.clone(), key_address);
+        self.outputs.push((out_p, asset));
+    }
+
+    /// Spend an unspent output of the funds
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p` - Outpoint of the output.
+    pub fn spend(&mut self, out_p: OutPoint) {
+        self.spent.push(out_p);
+    }
+
+    /// Whether the batch changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+            && self.transactions.is_empty()
+            && self.outputs.is_empty()
+            && self.spent.is_empty()
+    }
+}
+
+/// Add the changes of a wallet batch to a write batch
+///
+/// ### Arguments
+///
+/// * `db`             - Wallet database.
+/// * `batch`          - Write batch of the wallet database.
+/// * `changes`        - Changes to the wallet stores.
+/// * `encryption_key` - Key the address stores are encrypted with.
+/// * `hot_keys`       - Secret keys held in memory, for hot wallet mode.
+pub fn write_wallet_batch(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    changes: WalletBatch,
+    encryption_key: &secretbox::Key,
+    hot_keys: Option<&HotKeys>,
+) -> Result<()> {
+    let WalletBatch {
+        addresses,
+        transactions,
+        outputs,
+        spent,
+    } = changes;
+
+    if !addresses.is_empty() {
+        let mut address_list = get_known_key_address(db)?;
+        let timestamp = get_timestamp_now();
+        for (address, keys) in addresses {
+            // Save to disk, and the secret key to memory only for hot wallets
+            match hot_keys {
+                Some(hot_keys) => {
+                    let (public, secret_key) = keys.split();
+                    hot_keys.insert(&public.public_key, secret_key);
+                    save_public_address_store(batch, &address, &public, encryption_key);
+                }
+                None => save_address_store_to_wallet(batch, &address, keys, encryption_key),
+            }
+            labels::record_address_created(db, batch, &address, timestamp)?;
+            address_list.insert(address);
+        }
+        set_known_key_address(batch, address_list);
+    }
+
+    for (out_p, store) in &transactions {
+        save_transaction_to_wallet(batch, out_p, store);
+    }
+
+    if !outputs.is_empty() || !spent.is_empty() {
+        let mut fund_store = FundStoreUpdate::new(db)?;
+        for (out_p, asset) in outputs {
+            fund_store.store_tx(out_p, asset)?;
+        }
+        for out_p in &spent {
+            fund_store.spend_tx(out_p)?;
+        }
+        fund_store.write(batch);
+    }
+    Ok(())
+}
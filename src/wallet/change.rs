@@ -0,0 +1,99 @@
+//! Change addresses of the payments sent by a wallet.
+//!
+//! The excess of the inputs of a payment is paid back to the wallet at a new
+//! address generated for the payment, so the change cannot be linked to the
+//! addresses spent by looking at the outputs. Each change address is linked to
+//! the transaction it was generated for when the transaction is stored.
+//!
+//! Test scenarios needing stable addresses can pay the change back to the
+//! address of the first input spent instead.
+
+use super::Result;
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use std::collections::BTreeMap;
+
+/// Prefix of the keys of the transactions of the change addresses
+pub const CHANGE_ADDRESS_PREFIX: &str = "ChangeAddress_";
+
+/// Hash of the transaction a change address was generated for, if any
+///
+/// ### Arguments
+///
+/// * `db`      - Wallet database.
+/// * `address` - Wallet address.
+pub fn get_change_tx(db: &SimpleDb, address: &str) -> Result<Option<String>> {
+    let tx_hash = db.get_cf(DB_COL_DEFAULT, change_key(address))?;
+    Ok(tx_hash.and_then(|tx_hash| String::from_utf8(tx_hash).ok()))
+}
+
+/// All the change addresses, with the hash of the transaction each was generated for
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_change_addresses(db: &SimpleDb) -> Result<BTreeMap<String, String>> {
+    Ok(db
+        .iter_cf_clone(DB_COL_DEFAULT)?
+        .filter_map(|(key, value)| {
+            let address = key.strip_prefix(CHANGE_ADDRESS_PREFIX.as_bytes())?;
+            let address = String::from_utf8(address.to_vec()).ok()?;
+            Some((address, String::from_utf8(value).ok()?))
+        })
+        .collect())
+}
+
+/// Link a change address to the transaction paying to it, unless already linked
+///
+/// Returns false if the address was already linked, e.g. when reused.
+///
+/// ### Arguments
+///
+/// * `db`      - Wallet database.
+/// * `batch`   - Write batch of the wallet database.
+/// * `address` - Change address.
+/// * `tx_hash` - Hash of the transaction paying the change.
+pub fn link_change_address(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    address: &str,
+    tx_hash: &str,
+) -> Result<bool> {
+    if get_change_tx(db, address)?.is_some() {
+        return Ok(false);
+    }
+    batch.put_cf(DB_COL_DEFAULT, change_key(address), tx_hash.as_bytes());
+    Ok(true)
+}
+
+/// Key of the transaction of a change address
+fn change_key(address: &str) -> String {
+    format!("{CHANGE_ADDRESS_PREFIX}{address}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks a change address stays linked to the first transaction paying to it
+    fn change_address_linked_once() {
+        //Arrange
+        let mut db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let mut batch = db.batch_writer();
+        let linked = link_change_address(&db, &mut batch, "a", "tx_1").unwrap();
+        db.write(batch.done()).unwrap();
+
+        //Act
+        let mut batch = db.batch_writer();
+        let relinked = link_change_address(&db, &mut batch, "a", "tx_2").unwrap();
+        link_change_address(&db, &mut batch, "b", "tx_2").unwrap();
+        db.write(batch.done()).unwrap();
+
+        //Assert
+        assert!(linked);
+        assert!(!relinked);
+        assert_eq!(get_change_tx(&db, "a").unwrap().as_deref(), Some("tx_1"));
+        assert_eq!(get_change_tx(&db, "c").unwrap(), None);
+        assert_eq!(get_change_addresses(&db).unwrap().len(), 2);
+    }
+}
@@ -1,9 +1,42 @@
-use crate::wallet::LockedCoinbase;
+//! Funds of a wallet, and their storage in the wallet database.
+//!
+//! Each output is stored under its own key, with the order it was received in,
+//! next to a running total of the unspent outputs. Recording a payment or
+//! spending an output only writes the keys of the outputs involved and the
+//! running total, whatever the size of the wallet. The `FundStore` is assembled
+//! from these keys when the whole set of outputs is needed.
+//!
+//! The running total does not tell the outputs added to a block from those still
+//! pending. `FundBalances` splits the funds into confirmed, pending incoming and
+//! locked outgoing balances, the latter including the outputs spent by payments
+//! sent until their transaction is added to a block or reverted.
+
+use crate::constants::FUND_KEY;
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use crate::wallet::reservation::PendingSpends;
+use crate::wallet::{LockedCoinbase, Result, WalletDbError};
+use bincode::{deserialize, serialize};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use tw_chain::primitives::asset::{Asset, AssetValues};
 use tw_chain::primitives::transaction::OutPoint;
 
+/// Key of the running total of the unspent outputs
+pub const FUND_TOTAL_KEY: &str = "FundTotal";
+
+/// Key of the sequence number of the next stored output
+pub const FUND_NEXT_SEQ_KEY: &str = "FundNextSeq";
+
+/// Prefix of the keys of the unspent outputs
+pub const FUND_UNSPENT_PREFIX: &str = "FundUnspent_";
+
+/// Prefix of the keys of the spent outputs
+pub const FUND_SPENT_PREFIX: &str = "FundSpent_";
+
+/// Key of the outputs spent by the payments sent not yet added to a block
+pub const FUND_LOCKED_OUTGOING_KEY: &str = "FundLockedOutgoing";
+
 /// A reference to fund stores, where `transactions` contains the hash
 /// of the transaction and its holding `AssetValue`
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +102,27 @@ impl FundStore {
         out_points_locked_count
     }
 
+    /// Filters out the outputs reserved for other payments, updating the running total.
+    ///
+    /// Returns the number of outputs filtered out
+    ///
+    /// # Arguments
+    /// * `pending_spends` - Outputs reserved for payments not yet sent
+    pub fn filter_reserved(&mut self, pending_spends: &PendingSpends) -> usize {
+        let reserved: Vec<_> = self
+            .transactions
+            .keys()
+            .filter(|out_p| pending_spends.is_reserved(out_p))
+            .cloned()
+            .collect();
+        for out_p in &reserved {
+            if let Some(asset_reserved) = self.transactions.remove(out_p) {
+                self.running_total.update_sub(&asset_reserved);
+            }
+        }
+        reserved.len()
+    }
+
     /// Returns a page (or nearest page) with tranasactions
     pub fn transaction_pages(&self, page: usize) -> &BTreeMap<OutPoint, Asset> {
         if let Some(page_ref) = self.transaction_pages.get(page) {
@@ -151,3 +205,450 @@ impl FundStore {
         }
     }
 }
+
+/// Balances of the funds of a wallet, by the state of their transactions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FundBalances {
+    /// Unspent outputs of transactions added to a block, not reserved for a payment
+    pub confirmed: AssetValues,
+    /// Unspent outputs of transactions not yet added to a block
+    pub pending_incoming: AssetValues,
+    /// Outputs reserved for, or spent by, payments not yet added to a block
+    pub locked_outgoing: AssetValues,
+}
+
+impl FundBalances {
+    /// Split the funds of a wallet by the state of their transactions
+    ///
+    /// ### Arguments
+    ///
+    /// * `fund_store`      - Funds of the wallet.
+    /// * `pending_txs`     - Hashes of the transactions not yet added to a block.
+    /// * `pending_spends`  - Outputs reserved for payments not yet sent.
+    /// * `locked_outgoing` - Outputs spent by each payment sent not yet added to a block.
+    pub fn new(
+        fund_store: &FundStore,
+        pending_txs: &BTreeSet<String>,
+        pending_spends: &PendingSpends,
+        locked_outgoing: &BTreeMap<String, Vec<Asset>>,
+    ) -> Self {
+        let mut balances = Self::default();
+        for (out_p, asset) in fund_store.transactions() {
+            if pending_spends.is_reserved(out_p) {
+                balances.locked_outgoing.update_add(asset);
+            } else if pending_txs.contains(&out_p.t_hash) {
+                balances.pending_incoming.update_add(asset);
+            } else {
+                balances.confirmed.update_add(asset);
+            }
+        }
+        for asset in locked_outgoing.values().flatten() {
+            balances.locked_outgoing.update_add(asset);
+        }
+        balances
+    }
+}
+
+/// Output stored under its own key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FundEntry {
+    /// Order the output was received in
+    seq: u64,
+    out_point: OutPoint,
+    asset: Asset,
+}
+
+/// Changes to the funds of a wallet database, written with a batch
+///
+/// Only the outputs changed and the running total are read and written.
+pub struct FundStoreUpdate<'a> {
+    db: &'a SimpleDb,
+    running_total: AssetValues,
+    next_seq: u64,
+    /// Entries changed by the update, `None` if deleted
+    changed: BTreeMap<String, Option<FundEntry>>,
+}
+
+impl<'a> FundStoreUpdate<'a> {
+    /// Start an update of the funds stored in a database
+    ///
+    /// ### Arguments
+    ///
+    /// * `db` - Wallet database.
+    pub fn new(db: &'a SimpleDb) -> Result<Self> {
+        Ok(Self {
+            db,
+            running_total: get_value(db, FUND_TOTAL_KEY)?.unwrap_or_default(),
+            next_seq: get_value(db, FUND_NEXT_SEQ_KEY)?.unwrap_or_default(),
+            changed: Default::default(),
+        })
+    }
+
+    /// Store an unspent output, adding it to the running total
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p`  - Outpoint of the output.
+    /// * `amount` - Asset held by the output.
+    pub fn store_tx(&mut self, out_p: OutPoint, amount: Asset) -> Result<()> {
+        let asset = amount.clone().with_fixed_hash(&out_p);
+        let key = unspent_key(&out_p);
+        if let Some(old) = self.get(&key)? {
+            if old.asset != amount {
+                return Err(WalletDbError::FundStoreError(
+                    "Output already stored with another asset",
+                ));
+            }
+            return Ok(());
+        }
+
+        self.running_total.update_add(&asset);
+        let entry = FundEntry {
+            seq: self.next_seq,
+            out_point: out_p,
+            asset,
+        };
+        self.next_seq += 1;
+        self.changed.insert(key, Some(entry));
+        Ok(())
+    }
+
+    /// Move an unspent output to the spent outputs, removing it from the running total
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p` - Outpoint of the output.
+    pub fn spend_tx(&mut self, out_p: &OutPoint) -> Result<()> {
+        let key = unspent_key(out_p);
+        if let Some(entry) = self.get(&key)? {
+            let spent_key = spent_key(out_p);
+            if self.get(&spent_key)?.is_some() {
+                return Err(WalletDbError::FundStoreError("Output already spent"));
+            }
+            self.running_total.update_sub(&entry.asset);
+            self.changed.insert(key, None);
+            self.changed.insert(spent_key, Some(entry));
+        }
+        Ok(())
+    }
+
+    /// Move a spent output back to the unspent outputs, adding it to the running total
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p` - Outpoint of the output.
+    pub fn unspend_tx(&mut self, out_p: &OutPoint) -> Result<()> {
+        let key = spent_key(out_p);
+        if let Some(entry) = self.get(&key)? {
+            self.running_total.update_add(&entry.asset);
+            self.changed.insert(key, None);
+            self.changed.insert(unspent_key(out_p), Some(entry));
+        }
+        Ok(())
+    }
+
+    /// Delete a spent output
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p` - Outpoint of the output.
+    pub fn remove_spent_tx(&mut self, out_p: &OutPoint) {
+        self.changed.insert(spent_key(out_p), None);
+    }
+
+    /// Add the changes to a write batch
+    ///
+    /// ### Arguments
+    ///
+    /// * `batch` - Write batch of the wallet database.
+    pub fn write(self, batch: &mut SimpleDbWriteBatch) {
+        for (key, entry) in self.changed {
+            match entry {
+                Some(entry) => batch.put_cf(DB_COL_DEFAULT, key, serialize(&entry).unwrap()),
+                None => batch.delete_cf(DB_COL_DEFAULT, key),
+            }
+        }
+        batch.put_cf(
+            DB_COL_DEFAULT,
+            FUND_TOTAL_KEY,
+            serialize(&self.running_total).unwrap(),
+        );
+        batch.put_cf(
+            DB_COL_DEFAULT,
+            FUND_NEXT_SEQ_KEY,
+            serialize(&self.next_seq).unwrap(),
+        );
+    }
+
+    fn get(&self, key: &str) -> Result<Option<FundEntry>> {
+        match self.changed.get(key) {
+            Some(entry) => Ok(entry.clone()),
+            None => get_value(self.db, key),
+        }
+    }
+}
+
+/// Assemble the fund store from the outputs stored in a database
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn load_fund_store(db: &SimpleDb) -> Result<FundStore> {
+    let running_total: Option<AssetValues> = get_value(db, FUND_TOTAL_KEY)?;
+    let unspent = load_entries(db, FUND_UNSPENT_PREFIX)?;
+    let spent = load_entries(db, FUND_SPENT_PREFIX)?;
+    if running_total.is_none() && unspent.is_empty() && spent.is_empty() {
+        return Ok(FundStore::default());
+    }
+
+    let pages = unspent
+        .chunks(ENTRIES_PER_PAGE)
+        .map(|page| {
+            page.iter()
+                .map(|e| (e.out_point.clone(), e.asset.clone()))
+                .collect()
+        })
+        .collect();
+    Ok(FundStore::new(
+        running_total.unwrap_or_default(),
+        unspent
+            .into_iter()
+            .map(|e| (e.out_point, e.asset))
+            .collect(),
+        pages,
+        spent.into_iter().map(|e| (e.out_point, e.asset)).collect(),
+    ))
+}
+
+/// Replace all the outputs stored in a database with the ones of a fund store
+///
+/// The unspent outputs keep the order of the fund store pages.
+///
+/// ### Arguments
+///
+/// * `db`         - Wallet database.
+/// * `batch`      - Write batch of the wallet database.
+/// * `fund_store` - Outputs to store.
+pub fn replace_fund_store(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    fund_store: FundStore,
+) -> Result<()> {
+    for (key, _) in db.iter_cf_clone(DB_COL_DEFAULT)? {
+        if key.starts_with(FUND_UNSPENT_PREFIX.as_bytes())
+            || key.starts_with(FUND_SPENT_PREFIX.as_bytes())
+        {
+            batch.delete_cf(DB_COL_DEFAULT, key);
+        }
+    }
+
+    let paged = fund_store.transaction_pages.iter().flatten();
+    let mut unspent: Vec<_> = paged
+        .chain(&fund_store.transactions)
+        .filter(|(out_p, _)| fund_store.transactions.contains_key(out_p))
+        .map(|(out_p, asset)| (unspent_key(out_p), out_p, asset))
+        .collect();
+    let mut stored = BTreeSet::new();
+    unspent.retain(|(key, _, _)| stored.insert(key.clone()));
+    let spent = fund_store
+        .spent_transactions
+        .iter()
+        .map(|(out_p, asset)| (spent_key(out_p), out_p, asset));
+
+    let mut seq = 0;
+    for (key, out_p, asset) in unspent.into_iter().chain(spent) {
+        let entry = FundEntry {
+            seq,
+            out_point: out_p.clone(),
+            asset: asset.clone(),
+        };
+        batch.put_cf(DB_COL_DEFAULT, key, serialize(&entry).unwrap());
+        seq += 1;
+    }
+    batch.put_cf(
+        DB_COL_DEFAULT,
+        FUND_TOTAL_KEY,
+        serialize(&fund_store.running_total).unwrap(),
+    );
+    batch.put_cf(DB_COL_DEFAULT, FUND_NEXT_SEQ_KEY, serialize(&seq).unwrap());
+    Ok(())
+}
+
+/// Move the outputs of a fund store saved as a single value to their own keys
+///
+/// Wallets written by previous versions keep all their outputs under `FUND_KEY`.
+///
+/// ### Arguments
+///
+/// * `db`    - Wallet database.
+/// * `batch` - Write batch of the wallet database.
+pub fn migrate_fund_store_value(db: &SimpleDb, batch: &mut SimpleDbWriteBatch) -> Result<()> {
+    let fund_store: Option<FundStore> = get_value(db, FUND_KEY)?;
+    if let Some(fund_store) = fund_store {
+        replace_fund_store(db, batch, fund_store)?;
+        batch.delete_cf(DB_COL_DEFAULT, FUND_KEY);
+    }
+    Ok(())
+}
+
+/// Outputs spent by each payment sent not yet added to a block, by transaction hash
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_locked_outgoing(db: &SimpleDb) -> Result<BTreeMap<String, Vec<Asset>>> {
+    Ok(get_value(db, FUND_LOCKED_OUTGOING_KEY)?.unwrap_or_default())
+}
+
+/// Lock the spent outputs of a payment sent until its transaction is added to a block
+///
+/// Returns the number of outputs locked, only spent outputs of the wallet are.
+///
+/// ### Arguments
+///
+/// * `db`      - Wallet database.
+/// * `batch`   - Write batch of the wallet database.
+/// * `tx_hash` - Hash of the transaction of the payment.
+/// * `inputs`  - Outputs spent by the transaction.
+pub fn lock_outgoing<'a>(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    tx_hash: &str,
+    inputs: impl IntoIterator<Item = &'a OutPoint>,
+) -> Result<usize> {
+    let mut assets = Vec::new();
+    for out_p in inputs {
+        if let Some(entry) = get_value::<FundEntry>(db, &spent_key(out_p))? {
+            assets.push(entry.asset);
+        }
+    }
+    if assets.is_empty() {
+        return Ok(0);
+    }
+
+    let locked_len = assets.len();
+    let mut locked = get_locked_outgoing(db)?;
+    locked.insert(tx_hash.to_owned(), assets);
+    batch.put_cf(
+        DB_COL_DEFAULT,
+        FUND_LOCKED_OUTGOING_KEY,
+        serialize(&locked).unwrap(),
+    );
+    Ok(locked_len)
+}
+
+/// Release the outputs locked by payments added to a block or reverted
+///
+/// Returns the number of payments released.
+///
+/// ### Arguments
+///
+/// * `db`        - Wallet database.
+/// * `batch`     - Write batch of the wallet database.
+/// * `tx_hashes` - Hashes of the transactions of the payments.
+pub fn release_outgoing(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    tx_hashes: &[String],
+) -> Result<usize> {
+    let mut locked = get_locked_outgoing(db)?;
+    let released = tx_hashes
+        .iter()
+        .filter(|tx_hash| locked.remove(*tx_hash).is_some())
+        .count();
+    if released > 0 {
+        batch.put_cf(
+            DB_COL_DEFAULT,
+            FUND_LOCKED_OUTGOING_KEY,
+            serialize(&locked).unwrap(),
+        );
+    }
+    Ok(released)
+}
+
+/// Key of an unspent output
+fn unspent_key(out_p: &OutPoint) -> String {
+    format!("{FUND_UNSPENT_PREFIX}{}-{}", out_p.t_hash, out_p.n)
+}
+
+/// Key of a spent output
+fn spent_key(out_p: &OutPoint) -> String {
+    format!("{FUND_SPENT_PREFIX}{}-{}", out_p.t_hash, out_p.n)
+}
+
+/// Stored entries with the given key prefix, in the order they were received in
+fn load_entries(db: &SimpleDb, prefix: &str) -> Result<Vec<FundEntry>> {
+    let mut entries = db
+        .iter_cf_clone(DB_COL_DEFAULT)?
+        .filter(|(key, _)| key.starts_with(prefix.as_bytes()))
+        .map(|(_, value)| deserialize(&value))
+        .collect::<bincode::Result<Vec<FundEntry>>>()?;
+    entries.sort_by_key(|entry| entry.seq);
+    Ok(entries)
+}
+
+/// Value stored at the given key
+fn get_value<T: DeserializeOwned>(db: &SimpleDb, key: &str) -> Result<Option<T>> {
+    match db.get_cf(DB_COL_DEFAULT, key)? {
+        Some(value) => Ok(Some(deserialize(&value)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the funds are split by the state of their transactions as payments are sent
+    fn fund_balances_follow_payments() {
+        //Arrange
+        let mut db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let out_p = |t_hash: &str, n| OutPoint::new(t_hash.to_owned(), n);
+        let mut batch = db.batch_writer();
+        let mut update = FundStoreUpdate::new(&db).unwrap();
+        update
+            .store_tx(out_p("block_tx", 0), Asset::token_u64(5))
+            .unwrap();
+        update
+            .store_tx(out_p("block_tx", 1), Asset::token_u64(7))
+            .unwrap();
+        update
+            .store_tx(out_p("block_tx", 2), Asset::token_u64(3))
+            .unwrap();
+        update
+            .store_tx(out_p("pending_tx", 0), Asset::token_u64(2))
+            .unwrap();
+        update.spend_tx(&out_p("block_tx", 1)).unwrap();
+        update.write(&mut batch);
+        db.write(batch.done()).unwrap();
+        let pending_txs = vec!["pending_tx".to_owned()].into_iter().collect();
+        let mut pending_spends = PendingSpends::default();
+        pending_spends.reserve(vec![out_p("block_tx", 2)], 100);
+
+        //Act
+        let mut batch = db.batch_writer();
+        let inputs = [out_p("block_tx", 1), out_p("other_tx", 0)];
+        let locked = lock_outgoing(&db, &mut batch, "sent_tx", &inputs).unwrap();
+        db.write(batch.done()).unwrap();
+        let fund_store = load_fund_store(&db).unwrap();
+        let locked_outgoing = get_locked_outgoing(&db).unwrap();
+        let sent = FundBalances::new(&fund_store, &pending_txs, &pending_spends, &locked_outgoing);
+
+        let mut batch = db.batch_writer();
+        let hashes = ["sent_tx".to_owned(), "unknown_tx".to_owned()];
+        let released = release_outgoing(&db, &mut batch, &hashes).unwrap();
+        db.write(batch.done()).unwrap();
+        let locked_outgoing = get_locked_outgoing(&db).unwrap();
+        let confirmed =
+            FundBalances::new(&fund_store, &pending_txs, &pending_spends, &locked_outgoing);
+
+        //Assert
+        assert_eq!((locked, released), (1, 1));
+        assert_eq!(sent.confirmed, AssetValues::token_u64(5));
+        assert_eq!(sent.pending_incoming, AssetValues::token_u64(2));
+        assert_eq!(sent.locked_outgoing, AssetValues::token_u64(10));
+        assert_eq!(confirmed.locked_outgoing, AssetValues::token_u64(3));
+        assert!(locked_outgoing.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+//! Deterministic derivation of the key-pairs of wallet addresses.
+//!
+//! A wallet holds a random master seed, encrypted with its master key, and
+//! derives the key-pair of each new address from it with SLIP-0010 for Ed25519,
+//! the Ed25519 variant of BIP32 where every derivation step is hardened. The
+//! address `i` uses the path `m/0'/i'`. The seed alone is then enough to derive
+//! the addresses of a lost wallet again, from index 0 up.
+//!
+//! To be written down, the seed is encoded as a mnemonic phrase with the BIP39
+//! English word list, 24 words for a 32 byte seed. The phrase encodes the seed
+//! itself, it is not stretched into another seed as BIP39 wallets do.
+//!
+//! When restoring, the addresses of a seed can be scanned against the addresses
+//! of the UTXO set: derivation stops once `gap_limit` addresses in a row hold no
+//! output, as BIP44 wallets do, so a wallet which left more unused addresses in
+//! a row than the gap limit has its later addresses missed.
+
+use super::key_gen::keypair_from_seed;
+use super::{decrypt_store, encrypt_store, Result, WalletDbError};
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use bincode::{deserialize, serialize};
+use bip39::Mnemonic;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use tw_chain::crypto::secretbox_chacha20_poly1305 as secretbox;
+use tw_chain::crypto::sign_ed25519::{PublicKey, SecretKey};
+use tw_chain::utils::transaction_utils::construct_address_for;
+
+/// Storage key of the encrypted master seed
+pub const HD_SEED_KEY: &str = "HdSeed";
+
+/// Storage key of the index of the next address to derive
+pub const HD_NEXT_INDEX_KEY: &str = "HdNextIndex";
+
+/// Length of new master seeds
+pub const HD_SEED_LEN: usize = 32;
+
+/// Number of addresses derived again when restoring from a seed, if not given
+pub const DEFAULT_HD_RESTORE_COUNT: u32 = 20;
+
+/// Number of unused addresses in a row ending the scan of a seed, if not given
+pub const DEFAULT_HD_GAP_LIMIT: u32 = 20;
+
+/// Key of the SLIP-0010 master key derivation for Ed25519
+const SLIP10_ED25519_KEY: &[u8] = b"ed25519 seed";
+
+/// Flag of the hardened derivation indices
+const HARDENED: u32 = 0x8000_0000;
+
+/// Path of the addresses, before the address index
+const ADDRESS_PATH: &[u32] = &[0];
+
+/// Key-pair of the address of the given index
+///
+/// ### Arguments
+///
+/// * `seed`  - Master seed of the wallet.
+/// * `index` - Index of the address.
+pub fn derive_address_keypair(seed: &[u8], index: u32) -> (PublicKey, SecretKey) {
+    let path: Vec<u32> = ADDRESS_PATH.iter().copied().chain(Some(index)).collect();
+    keypair_from_seed(&derive_private_key(seed, &path))
+}
+
+/// Number of addresses to derive again so that every used address of a seed is restored
+///
+/// Addresses are derived from index 0 until `gap_limit` addresses in a row are
+/// unused, those unused addresses being counted too.
+///
+/// ### Arguments
+///
+/// * `seed`      - Master seed of the wallet.
+/// * `gap_limit` - Number of unused addresses in a row ending the scan.
+/// * `is_used`   - Whether an address holds outputs in the UTXO set.
+pub fn gap_limit_restore_count(seed: &[u8], gap_limit: u32, is_used: impl Fn(&str) -> bool) -> u32 {
+    let (mut index, mut unused) = (0, 0);
+    while unused < gap_limit {
+        let (public_key, _) = derive_address_keypair(seed, index);
+        if is_used(&construct_address_for(&public_key, None)) {
+            unused = 0;
+        } else {
+            unused += 1;
+        }
+        index += 1;
+    }
+    index
+}
+
+/// Private key seed at a path of hardened indices, as derived by SLIP-0010 for Ed25519
+///
+/// ### Arguments
+///
+/// * `seed` - Master seed of the wallet.
+/// * `path` - Indices of the path, hardened whether or not their flag is set.
+pub fn derive_private_key(seed: &[u8], path: &[u32]) -> [u8; 32] {
+    let (mut key, mut chain_code) = split_hmac(SLIP10_ED25519_KEY, &[seed]);
+    for index in path {
+        let index = (index | HARDENED).to_be_bytes();
+        let (child_key, child_chain_code) = split_hmac(&chain_code, &[&[0], &key, &index]);
+        key = child_key;
+        chain_code = child_chain_code;
+    }
+    key
+}
+
+/// Key and chain code from the HMAC-SHA512 of the data
+fn split_hmac(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut ctx = hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA512, key));
+    for part in data {
+        ctx.update(part);
+    }
+    let tag = ctx.sign();
+    let (mut left, mut right) = ([0u8; 32], [0u8; 32]);
+    left.copy_from_slice(&tag.as_ref()[..32]);
+    right.copy_from_slice(&tag.as_ref()[32..]);
+    (left, right)
+}
+
+/// Mnemonic phrase encoding a master seed
+///
+/// ### Arguments
+///
+/// * `seed` - Master seed of 16 to 32 bytes, a multiple of 4.
+pub fn seed_to_mnemonic(seed: &[u8]) -> Result<String> {
+    Mnemonic::from_entropy(seed)
+        .map(|mnemonic| mnemonic.to_string())
+        .map_err(|_| WalletDbError::HdWalletError("Seed cannot be encoded as a mnemonic"))
+}
+
+/// Master seed encoded by a mnemonic phrase
+///
+/// ### Arguments
+///
+/// * `phrase` - Words of the mnemonic, separated by spaces.
+pub fn mnemonic_to_seed(phrase: &str) -> Result<Vec<u8>> {
+    Mnemonic::parse(phrase)
+        .map(|mnemonic| mnemonic.to_entropy())
+        .map_err(|_| WalletDbError::HdWalletError("Invalid mnemonic"))
+}
+
+/// Generate a new master seed
+pub fn generate_hd_seed() -> Result<Vec<u8>> {
+    let mut seed = vec![0u8; HD_SEED_LEN];
+    SystemRandom::new()
+        .fill(&mut seed)
+        .map_err(|_| WalletDbError::HdWalletError("Random generation failed"))?;
+    Ok(seed)
+}
+
+/// Master seed of the wallet, if it has one
+///
+/// ### Arguments
+///
+/// * `db`             - Wallet database.
+/// * `encryption_key` - Master key of the wallet.
+pub fn get_hd_seed(db: &SimpleDb, encryption_key: &secretbox::Key) -> Result<Option<Vec<u8>>> {
+    match db.get_cf(DB_COL_DEFAULT, HD_SEED_KEY)? {
+        Some(store) => Ok(Some(decrypt_store(store, encryption_key)?)),
+        None => Ok(None),
+    }
+}
+
+/// Save the master seed of the wallet, encrypted with its master key
+///
+/// ### Arguments
+///
+/// * `batch`          - Write batch of the wallet database.
+/// * `seed`           - Master seed.
+/// * `encryption_key` - Master key of the wallet.
+pub fn save_hd_seed(batch: &mut SimpleDbWriteBatch, seed: &[u8], encryption_key: &secretbox::Key) {
+    let store = encrypt_store(seed.to_vec(), encryption_key);
+    batch.put_cf(DB_COL_DEFAULT, HD_SEED_KEY, &store);
+}
+
+/// Index of the next address to derive
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_hd_next_index(db: &SimpleDb) -> Result<u32> {
+    match db.get_cf(DB_COL_DEFAULT, HD_NEXT_INDEX_KEY)? {
+        Some(index) => Ok(deserialize(&index)?),
+        None => Ok(0),
+    }
+}
+
+/// Set the index of the next address to derive
+///
+/// ### Arguments
+///
+/// * `batch` - Write batch of the wallet database.
+/// * `index` - Index of the next address.
+pub fn set_hd_next_index(batch: &mut SimpleDbWriteBatch, index: u32) {
+    batch.put_cf(
+        DB_COL_DEFAULT,
+        HD_NEXT_INDEX_KEY,
+        serialize(&index).unwrap(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the derivation matches the SLIP-0010 Ed25519 test vector
+    fn derive_private_key_slip10_vector() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+
+        assert_eq!(
+            hex::encode(derive_private_key(&seed, &[])),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            hex::encode(derive_private_key(&seed, &[0])),
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+        );
+        assert_eq!(
+            derive_private_key(&seed, &[0]),
+            derive_private_key(&seed, &[HARDENED])
+        );
+    }
+
+    #[test]
+    /// Checks seeds are encoded with the BIP39 word list and decoded back
+    fn mnemonic_encodes_seed() {
+        let seed = [7u8; HD_SEED_LEN];
+
+        let phrase = seed_to_mnemonic(&seed).unwrap();
+        let zero_phrase = seed_to_mnemonic(&[0u8; 16]).unwrap();
+
+        assert_eq!(phrase.split(' ').count(), 24);
+        assert_eq!(mnemonic_to_seed(&phrase).unwrap(), seed.to_vec());
+        assert_eq!(zero_phrase, format!("{}about", "abandon ".repeat(11)));
+        assert!(mnemonic_to_seed(&zero_phrase.replace("about", "abandon")).is_err());
+    }
+
+    #[test]
+    /// Checks each address index derives its own key-pair, the same for the same seed
+    fn derive_address_keypair_per_index() {
+        let seed = [7u8; HD_SEED_LEN];
+
+        let first = derive_address_keypair(&seed, 0);
+        let again = derive_address_keypair(&seed, 0);
+        let second = derive_address_keypair(&seed, 1);
+
+        assert_eq!(first.0, again.0);
+        assert_eq!(first.1.as_ref(), again.1.as_ref());
+        assert_ne!(first.0, second.0);
+    }
+
+    #[test]
+    /// Checks the scan goes on past used addresses until the gap limit is reached
+    fn gap_limit_restore_count_scans_used_addresses() {
+        let seed = [7u8; HD_SEED_LEN];
+        let address = |index| construct_address_for(&derive_address_keypair(&seed, index).0, None);
+        let used = [address(0), address(3), address(5), address(9)];
+
+        let count = gap_limit_restore_count(&seed, 3, |a| used.iter().any(|u| u == a));
+        let unused_count = gap_limit_restore_count(&seed, 3, |_| false);
+
+        assert_eq!(count, 9);
+        assert_eq!(unused_count, 3);
+    }
+}
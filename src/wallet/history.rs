@@ -0,0 +1,339 @@
+//! Journal of the payments sent and received by a wallet.
+//!
+//! Each payment is recorded once per transaction, with its direction, the assets
+//! paid, the address of the other party when known, and its status. Entries are
+//! keyed by block number then sequence number, so they are read back in the
+//! order the payments were made, and are indexed by transaction hash so their
+//! status can be updated once the transaction is added to a block or reverted.
+//!
+//! Outgoing payments are recorded when the wallet stores the transaction it
+//! sends, and the change they pay back to the wallet is not recorded again as
+//! incoming. Incoming payments are recorded as they are saved to the wallet,
+//! pending when the payer sends the transaction before it is added to a block.
+
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use crate::wallet::Result;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tw_chain::primitives::asset::AssetValues;
+
+/// Key of the sequence number of the next history entry
+pub const TX_HISTORY_NEXT_KEY: &str = "TxHistoryNext";
+
+/// Key of the hashes of the transactions whose entry is pending
+pub const TX_HISTORY_PENDING_KEY: &str = "TxHistoryPending";
+
+/// Prefix of the keys of the history entries
+pub const TX_HISTORY_PREFIX: &str = "TxHistory_";
+
+/// Prefix of the keys of the history entries by transaction hash
+pub const TX_HISTORY_INDEX_PREFIX: &str = "TxHistoryIndex_";
+
+/// Number of entries of a history page, if not given
+pub const DEFAULT_TX_HISTORY_PAGE_SIZE: usize = 25;
+
+/// Maximum number of entries of a history page
+pub const MAX_TX_HISTORY_PAGE_SIZE: usize = 100;
+
+/// Direction of a payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// Status of a payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxStatus {
+    /// Sent or received, not yet added to a block
+    Pending,
+    /// Added to a block
+    Confirmed,
+    /// Never to be added to a block, its inputs given back
+    Reverted,
+}
+
+/// Payment sent or received by the wallet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxHistoryEntry {
+    pub seq: u64,
+    /// Number of the last block when recorded
+    pub b_num: u64,
+    /// Recording time, in seconds since the UNIX epoch
+    pub timestamp: i64,
+    pub tx_hash: String,
+    pub direction: TxDirection,
+    /// Assets received, or paid to other wallets
+    pub amount: AssetValues,
+    /// First address paid by an outgoing payment, unknown for incoming ones
+    pub counterparty: Option<String>,
+    pub status: TxStatus,
+    /// Whether the payment is to watch-only addresses of the wallet
+    pub watch_only: bool,
+}
+
+impl TxHistoryEntry {
+    /// Entry of a payment, numbered and dated when appended
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hash`      - Hash of the payment transaction.
+    /// * `direction`    - Sent or received.
+    /// * `amount`       - Assets received, or paid to other wallets.
+    /// * `counterparty` - Address of the other party, if known.
+    /// * `status`       - Status of the payment.
+    pub fn new(
+        tx_hash: String,
+        direction: TxDirection,
+        amount: AssetValues,
+        counterparty: Option<String>,
+        status: TxStatus,
+    ) -> Self {
+        Self {
+            seq: 0,
+            b_num: 0,
+            timestamp: 0,
+            tx_hash,
+            direction,
+            amount,
+            counterparty,
+            status,
+            watch_only: false,
+        }
+    }
+
+    /// Mark the entry as a payment to watch-only addresses
+    pub fn watched(mut self) -> Self {
+        self.watch_only = true;
+        self
+    }
+}
+
+/// Page of the history, newest entries first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxHistoryPage {
+    pub entries: Vec<TxHistoryEntry>,
+    pub page: usize,
+    pub page_size: usize,
+    /// Number of entries of the whole history
+    pub total: usize,
+}
+
+/// Append the entries of payments not recorded yet to the history
+///
+/// Returns the number of entries appended.
+///
+/// ### Arguments
+///
+/// * `db`        - Wallet database, to get the next sequence number from.
+/// * `batch`     - Write batch to add the entries to.
+/// * `entries`   - Entries of the payments.
+/// * `b_num`     - Number of the last block.
+/// * `timestamp` - Recording time, in seconds since the UNIX epoch.
+pub fn append_tx_history(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    entries: Vec<TxHistoryEntry>,
+    b_num: u64,
+    timestamp: i64,
+) -> Result<usize> {
+    let mut seq: u64 = get_value(db, TX_HISTORY_NEXT_KEY)?.unwrap_or_default();
+    let mut pending: BTreeSet<String> = get_value(db, TX_HISTORY_PENDING_KEY)?.unwrap_or_default();
+    let mut recorded = BTreeSet::new();
+
+    for mut entry in entries {
+        let index_key = index_key(&entry.tx_hash);
+        if db.get_cf(DB_COL_DEFAULT, &index_key)?.is_some()
+            || !recorded.insert(entry.tx_hash.clone())
+        {
+            continue;
+        }
+
+        entry.seq = seq;
+        entry.b_num = b_num;
+        entry.timestamp = timestamp;
+        if entry.status == TxStatus::Pending {
+            pending.insert(entry.tx_hash.clone());
+        }
+        let key = history_key(b_num, seq);
+        batch.put_cf(DB_COL_DEFAULT, &key, serialize(&entry).unwrap());
+        batch.put_cf(DB_COL_DEFAULT, &index_key, key);
+        seq += 1;
+    }
+
+    if !recorded.is_empty() {
+        batch.put_cf(
+            DB_COL_DEFAULT,
+            TX_HISTORY_NEXT_KEY,
+            serialize(&seq).unwrap(),
+        );
+        let pending = serialize(&pending).unwrap();
+        batch.put_cf(DB_COL_DEFAULT, TX_HISTORY_PENDING_KEY, pending);
+    }
+    Ok(recorded.len())
+}
+
+/// Set the status of the pending entries of the given transactions
+///
+/// Returns the number of entries updated.
+///
+/// ### Arguments
+///
+/// * `db`        - Wallet database.
+/// * `batch`     - Write batch to add the updated entries to.
+/// * `tx_hashes` - Hashes of the transactions, only those of pending entries are updated.
+/// * `status`    - New status of the entries.
+pub fn set_tx_history_status(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    tx_hashes: &[String],
+    status: TxStatus,
+) -> Result<usize> {
+    let mut pending: BTreeSet<String> = get_value(db, TX_HISTORY_PENDING_KEY)?.unwrap_or_default();
+    let updated: Vec<_> = tx_hashes
+        .iter()
+        .filter(|tx_hash| pending.remove(*tx_hash))
+        .collect();
+
+    for tx_hash in &updated {
+        let key = match db.get_cf(DB_COL_DEFAULT, index_key(tx_hash))? {
+            Some(key) => key,
+            None => continue,
+        };
+        if let Some(mut entry) = get_value::<TxHistoryEntry>(db, &key)? {
+            entry.status = status;
+            batch.put_cf(DB_COL_DEFAULT, key, serialize(&entry).unwrap());
+        }
+    }
+
+    if !updated.is_empty() {
+        let pending = serialize(&pending).unwrap();
+        batch.put_cf(DB_COL_DEFAULT, TX_HISTORY_PENDING_KEY, pending);
+    }
+    Ok(updated.len())
+}
+
+/// Hashes of the transactions whose entries are pending
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_pending_tx_hashes(db: &SimpleDb) -> Result<BTreeSet<String>> {
+    Ok(get_value(db, TX_HISTORY_PENDING_KEY)?.unwrap_or_default())
+}
+
+/// Get a page of the history, newest entries first
+///
+/// ### Arguments
+///
+/// * `db`        - Wallet database.
+/// * `page`      - Index of the page, from 0.
+/// * `page_size` - Number of entries of a page, capped to `MAX_TX_HISTORY_PAGE_SIZE`.
+pub fn get_tx_history(db: &SimpleDb, page: usize, page_size: usize) -> Result<TxHistoryPage> {
+    let page_size = page_size.clamp(1, MAX_TX_HISTORY_PAGE_SIZE);
+    let mut keys: Vec<_> = db
+        .iter_cf_clone(DB_COL_DEFAULT)?
+        .filter(|(key, _)| key.starts_with(TX_HISTORY_PREFIX.as_bytes()))
+        .collect();
+    keys.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+
+    let entries = keys
+        .iter()
+        .skip(page.saturating_mul(page_size))
+        .take(page_size)
+        .map(|(_, value)| deserialize(value))
+        .collect::<bincode::Result<_>>()?;
+    Ok(TxHistoryPage {
+        entries,
+        page,
+        page_size,
+        total: keys.len(),
+    })
+}
+
+/// Deserialized value stored at a key, if any
+fn get_value<T: serde::de::DeserializeOwned>(
+    db: &SimpleDb,
+    key: impl AsRef<[u8]>,
+) -> Result<Option<T>> {
+    match db.get_cf(DB_COL_DEFAULT, key)? {
+        Some(value) => Ok(Some(deserialize(&value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Key of the history entry of the given block and sequence numbers
+fn history_key(b_num: u64, seq: u64) -> String {
+    format!("{TX_HISTORY_PREFIX}{b_num:016x}_{seq:016x}")
+}
+
+/// Key of the history entry key of a transaction
+fn index_key(tx_hash: &str) -> String {
+    format!("{TX_HISTORY_INDEX_PREFIX}{tx_hash}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::primitives::asset::Asset;
+
+    #[test]
+    /// Checks entries are recorded once per transaction and read back newest first
+    fn tx_history_paged_newest_first() {
+        //Arrange
+        let mut db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let entry = |hash: &str, status| {
+            let mut amount = AssetValues::default();
+            amount.update_add(&Asset::token_u64(5));
+            TxHistoryEntry::new(hash.to_owned(), TxDirection::Incoming, amount, None, status)
+        };
+        let appended = vec![
+            (vec![entry("a", TxStatus::Confirmed)], 1),
+            (
+                vec![entry("b", TxStatus::Pending), entry("b", TxStatus::Pending)],
+                2,
+            ),
+            (
+                vec![entry("a", TxStatus::Pending), entry("c", TxStatus::Pending)],
+                3,
+            ),
+        ];
+
+        //Act
+        let mut counts = Vec::new();
+        for (entries, b_num) in appended {
+            let mut batch = db.batch_writer();
+            counts.push(append_tx_history(&db, &mut batch, entries, b_num, 0).unwrap());
+            db.write(batch.done()).unwrap();
+        }
+        let mut batch = db.batch_writer();
+        let hashes = ["a".to_owned(), "b".to_owned()];
+        let updated = set_tx_history_status(&db, &mut batch, &hashes, TxStatus::Confirmed).unwrap();
+        db.write(batch.done()).unwrap();
+        let first = get_tx_history(&db, 0, 2).unwrap();
+        let second = get_tx_history(&db, 1, 2).unwrap();
+
+        //Assert
+        let summary = |page: &TxHistoryPage| -> Vec<_> {
+            let entries = page.entries.iter();
+            entries
+                .map(|e| (e.tx_hash.clone(), e.b_num, e.status))
+                .collect()
+        };
+        assert_eq!(counts, vec![1, 1, 1]);
+        assert_eq!(updated, 1);
+        assert_eq!(
+            summary(&first),
+            vec![
+                ("c".to_owned(), 3, TxStatus::Pending),
+                ("b".to_owned(), 2, TxStatus::Confirmed)
+            ]
+        );
+        assert_eq!(
+            summary(&second),
+            vec![("a".to_owned(), 1, TxStatus::Confirmed)]
+        );
+        assert_eq!(first.total, 3);
+    }
+}
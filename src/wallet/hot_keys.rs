@@ -0,0 +1,159 @@
+//! Secret keys of a hot wallet, held in memory only.
+//!
+//! In hot wallet mode the wallet database stores the public half of the key-pair
+//! of each address, so a copy of the disk cannot leak spending keys. The secret
+//! keys are fed in at startup, from stdin or from the first connection to a Unix
+//! socket, one per line as a hex encoded seed optionally followed by the public
+//! key. Blank lines and lines starting with `#` are ignored.
+//!
+//! The secret keys of the addresses generated while running are held in memory
+//! too, so they are lost on restart unless exported with `export_backup`.
+
+use super::key_import::{decode_keypair, KeyFormat};
+use super::Result;
+use crate::configurations::HotKeySource;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, BufRead};
+use std::sync::Mutex;
+use tw_chain::crypto::sign_ed25519::{PublicKey, SecretKey};
+
+/// Secret keys held in memory, by hex encoded public key
+#[derive(Default)]
+pub struct HotKeys {
+    keys: Mutex<BTreeMap<String, SecretKey>>,
+}
+
+impl fmt::Debug for HotKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HotKeys")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl HotKeys {
+    /// Read the secret keys from their configured source
+    ///
+    /// Blocks until the whole input is read.
+    ///
+    /// ### Arguments
+    ///
+    /// * `source` - Stdin, or Unix socket to accept one connection on.
+    pub fn load(source: &HotKeySource) -> Result<Self> {
+        match source {
+            HotKeySource::Stdin => Self::from_reader(io::stdin().lock()),
+            HotKeySource::Socket(path) => load_from_socket(path),
+        }
+    }
+
+    /// Read secret keys, one per line
+    ///
+    /// ### Arguments
+    ///
+    /// * `reader` - Input holding the secret keys.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let hot_keys = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (public_key, secret_key) = decode_keypair(KeyFormat::Hex, line)?;
+            hot_keys.insert(&public_key, secret_key);
+        }
+        Ok(hot_keys)
+    }
+
+    /// Hold the secret key of a public key
+    ///
+    /// ### Arguments
+    ///
+    /// * `public_key` - Public key of the key-pair.
+    /// * `secret_key` - Secret key of the key-pair.
+    pub fn insert(&self, public_key: &PublicKey, secret_key: SecretKey) {
+        let mut keys = self.keys.lock().unwrap();
+        keys.insert(hex::encode(public_key.as_ref()), secret_key);
+    }
+
+    /// Secret key of a public key, if held
+    ///
+    /// ### Arguments
+    ///
+    /// * `public_key` - Public key of the key-pair.
+    pub fn secret_key(&self, public_key: &PublicKey) -> Option<SecretKey> {
+        let keys = self.keys.lock().unwrap();
+        keys.get(&hex::encode(public_key.as_ref())).cloned()
+    }
+
+    /// Drop the secret keys of the public keys not listed
+    ///
+    /// ### Arguments
+    ///
+    /// * `public_keys` - Hex encoded public keys to keep the secret key of.
+    pub fn retain(&self, public_keys: &BTreeSet<String>) {
+        let mut keys = self.keys.lock().unwrap();
+        keys.retain(|public_key, _| public_keys.contains(public_key));
+    }
+
+    /// Number of secret keys held
+    pub fn len(&self) -> usize {
+        self.keys.lock().unwrap().len()
+    }
+
+    /// Whether no secret key is held
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Read the secret keys from the first connection to a Unix socket
+///
+/// ### Arguments
+///
+/// * `path` - Path to create the socket at.
+#[cfg(unix)]
+fn load_from_socket(path: &str) -> Result<HotKeys> {
+    use std::io::BufReader;
+    use std::os::unix::net::UnixListener;
+
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    let accepted = listener.accept();
+    let _ = std::fs::remove_file(path);
+
+    let (stream, _) = accepted?;
+    HotKeys::from_reader(BufReader::new(stream))
+}
+
+#[cfg(not(unix))]
+fn load_from_socket(_path: &str) -> Result<HotKeys> {
+    Err(super::WalletDbError::HotWalletError(
+        "Unix sockets are not available on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::WalletDbError;
+
+    #[test]
+    /// Checks keys are read one per line, and invalid lines are rejected
+    fn hot_keys_from_reader() {
+        let seed = "d4ee72dbf913584ad5b6d8f1f769f8ad3afe7c28cbf1d4fbe097a88f44755842";
+        let (public_key, secret_key) = decode_keypair(KeyFormat::Hex, seed).unwrap();
+        let input = format!("# payment keys\n\n{seed}\n");
+
+        let hot_keys = HotKeys::from_reader(input.as_bytes()).unwrap();
+        let invalid = HotKeys::from_reader("not a key\n".as_bytes());
+
+        assert_eq!(hot_keys.len(), 1);
+        assert_eq!(
+            hot_keys.secret_key(&public_key).unwrap().as_ref(),
+            secret_key.as_ref()
+        );
+        assert!(matches!(invalid, Err(WalletDbError::HexError(_))));
+    }
+}
@@ -0,0 +1,125 @@
+//! Generator of the key-pairs of new wallet addresses.
+//!
+//! Wallets use random key-pairs by default. Tests and development networks can
+//! use a generator seeded per node, so the addresses and signatures of a run
+//! are the same every time and block dumps can be reproduced.
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::sync::{Arc, Mutex};
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey};
+
+/// PKCS#8 v2 document prefix for an Ed25519 private key, followed by the seed
+const PKCS8_SEED_PREFIX: &[u8] = &[
+    0x30, 0x53, 0x02, 0x01, 0x01, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// PKCS#8 v2 document separator between the seed and the public key
+const PKCS8_PUBLIC_KEY_PREFIX: &[u8] = &[0xa1, 0x23, 0x03, 0x21, 0x00];
+
+/// Source of the key-pairs of new wallet addresses
+#[derive(Debug, Clone, Default)]
+pub enum KeyGen {
+    /// Random key-pairs
+    #[default]
+    Random,
+    /// Key-pairs derived from a seeded generator, shared by its clones
+    Seeded(Arc<Mutex<StdRng>>),
+}
+
+impl KeyGen {
+    /// Create a generator giving the same key-pairs for the same seed and node
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed`       - Seed shared by the nodes of a network.
+    /// * `node_type`  - Type of the node generating keys.
+    /// * `node_index` - Index of the node among the nodes of its type.
+    pub fn seeded(seed: u64, node_type: &str, node_index: usize) -> Self {
+        let mut rng_seed = [0u8; 32];
+        rng_seed[..8].copy_from_slice(&seed.to_le_bytes());
+        rng_seed[8..16].copy_from_slice(&(node_index as u64).to_le_bytes());
+        for (byte, type_byte) in rng_seed[16..].iter_mut().zip(node_type.bytes()) {
+            *byte = type_byte;
+        }
+        Self::Seeded(Arc::new(Mutex::new(StdRng::from_seed(rng_seed))))
+    }
+
+    /// Generate the next key-pair
+    pub fn gen_keypair(&self) -> (PublicKey, SecretKey) {
+        match self {
+            Self::Random => sign::gen_keypair(),
+            Self::Seeded(rng) => {
+                let mut seed = [0u8; 32];
+                rng.lock().unwrap().fill_bytes(&mut seed);
+                keypair_from_seed(&seed)
+            }
+        }
+    }
+}
+
+/// Ed25519 key-pair for the seed, with the secret key in the PKCS#8 format of `sign::gen_keypair`
+///
+/// ### Arguments
+///
+/// * `seed` - Private key seed.
+pub(crate) fn keypair_from_seed(seed: &[u8; 32]) -> (PublicKey, SecretKey) {
+    let key_pair = Ed25519KeyPair::from_seed_unchecked(seed).unwrap();
+    let public_key = key_pair.public_key().as_ref();
+
+    let pkcs8 = [PKCS8_SEED_PREFIX, seed, PKCS8_PUBLIC_KEY_PREFIX, public_key].concat();
+    (
+        PublicKey::from_slice(public_key).unwrap(),
+        SecretKey::from_slice(&pkcs8).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ED25519};
+
+    #[test]
+    /// Checks seeded generators give the same valid key-pairs for the same seed and node only
+    fn seeded_keypairs_deterministic_for_node() {
+        //
+        // Arrange
+        //
+        let key_gens = [
+            KeyGen::seeded(7, "user", 0),
+            KeyGen::seeded(7, "user", 0),
+            KeyGen::seeded(7, "user", 1),
+            KeyGen::seeded(7, "miner", 0),
+            KeyGen::seeded(8, "user", 0),
+        ];
+
+        //
+        // Act
+        //
+        let keys: Vec<Vec<_>> = key_gens
+            .iter()
+            .map(|k| (0..3).map(|_| k.gen_keypair()).collect())
+            .collect();
+        let (pk, sk) = &keys[0][0];
+        let signature = sign::sign_detached(b"message", sk);
+        let verified = UnparsedPublicKey::new(&ED25519, pk.as_ref())
+            .verify(b"message", signature.as_ref())
+            .is_ok();
+
+        //
+        // Assert
+        //
+        let public_keys: Vec<Vec<_>> = keys
+            .iter()
+            .map(|ks| ks.iter().map(|(pk, _)| *pk).collect())
+            .collect();
+        assert_eq!(public_keys[0], public_keys[1]);
+        assert_ne!(public_keys[0][0], public_keys[0][1]);
+        assert!(public_keys[2..]
+            .iter()
+            .all(|pks| pks[0] != public_keys[0][0]));
+        assert_eq!(sk.as_ref().len(), 85);
+        assert!(verified);
+    }
+}
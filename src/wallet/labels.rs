@@ -0,0 +1,156 @@
+//! Labels and creation times of the wallet addresses.
+//!
+//! The key-pairs and transaction stores keep their on-disk format, so the
+//! metadata of an address is stored apart, under a key of its own. The creation
+//! time is recorded when the address is first saved to the wallet, and the label
+//! is set by the user to tag the address, e.g. "salary" or "donations".
+
+use super::{Result, WalletDbError};
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Prefix of the keys of the address metadata
+pub const ADDRESS_METADATA_PREFIX: &str = "AddressMetadata_";
+
+/// Maximum length of an address label, in bytes
+pub const MAX_ADDRESS_LABEL_LEN: usize = 64;
+
+/// Metadata of a wallet address
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressMetadata {
+    /// Label given by the user
+    pub label: Option<String>,
+    /// Time the address was saved to the wallet, in seconds since the UNIX epoch
+    pub created: Option<i64>,
+}
+
+/// Check a label can be given to an address
+///
+/// ### Arguments
+///
+/// * `label` - Label to give.
+pub fn check_address_label(label: &str) -> Result<()> {
+    if label.is_empty() || label.len() > MAX_ADDRESS_LABEL_LEN {
+        return Err(WalletDbError::LabelError(
+            "Label must be 1 to 64 bytes long",
+        ));
+    }
+    Ok(())
+}
+
+/// Metadata of an address, empty if none was recorded
+///
+/// ### Arguments
+///
+/// * `db`      - Wallet database.
+/// * `address` - Wallet address.
+pub fn get_address_metadata(db: &SimpleDb, address: &str) -> Result<AddressMetadata> {
+    match db.get_cf(DB_COL_DEFAULT, metadata_key(address))? {
+        Some(metadata) => Ok(deserialize(&metadata)?),
+        None => Ok(Default::default()),
+    }
+}
+
+/// Metadata of all the addresses that have some
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_all_address_metadata(db: &SimpleDb) -> Result<BTreeMap<String, AddressMetadata>> {
+    db.iter_cf_clone(DB_COL_DEFAULT)?
+        .filter_map(|(key, value)| {
+            let address = key.strip_prefix(ADDRESS_METADATA_PREFIX.as_bytes())?;
+            let address = String::from_utf8(address.to_vec()).ok()?;
+            Some(deserialize(&value).map(|metadata| (address, metadata)))
+        })
+        .collect::<bincode::Result<_>>()
+        .map_err(Into::into)
+}
+
+/// Save the metadata of an address
+///
+/// ### Arguments
+///
+/// * `batch`    - Write batch of the wallet database.
+/// * `address`  - Wallet address.
+/// * `metadata` - Metadata of the address.
+pub fn save_address_metadata(
+    batch: &mut SimpleDbWriteBatch,
+    address: &str,
+    metadata: &AddressMetadata,
+) {
+    let metadata = serialize(metadata).unwrap();
+    batch.put_cf(DB_COL_DEFAULT, metadata_key(address), metadata);
+}
+
+/// Record the creation time of an address, unless already recorded
+///
+/// ### Arguments
+///
+/// * `db`        - Wallet database.
+/// * `batch`     - Write batch of the wallet database.
+/// * `address`   - Wallet address.
+/// * `timestamp` - Time the address is saved, in seconds since the UNIX epoch.
+pub fn record_address_created(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    address: &str,
+    timestamp: i64,
+) -> Result<()> {
+    let mut metadata = get_address_metadata(db, address)?;
+    if metadata.created.is_none() {
+        metadata.created = Some(timestamp);
+        save_address_metadata(batch, address, &metadata);
+    }
+    Ok(())
+}
+
+/// Delete the metadata of an address
+///
+/// ### Arguments
+///
+/// * `batch`   - Write batch of the wallet database.
+/// * `address` - Wallet address.
+pub fn delete_address_metadata(batch: &mut SimpleDbWriteBatch, address: &str) {
+    batch.delete_cf(DB_COL_DEFAULT, metadata_key(address));
+}
+
+/// Key of the metadata of an address
+fn metadata_key(address: &str) -> String {
+    format!("{ADDRESS_METADATA_PREFIX}{address}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the creation time is kept once recorded, along with the label
+    fn address_metadata_keeps_creation_time() {
+        //Arrange
+        let mut db = SimpleDb::new_in_memory(&[], None).unwrap();
+        let mut batch = db.batch_writer();
+        record_address_created(&db, &mut batch, "a", 10).unwrap();
+        record_address_created(&db, &mut batch, "b", 10).unwrap();
+        db.write(batch.done()).unwrap();
+
+        //Act
+        let mut batch = db.batch_writer();
+        let mut metadata = get_address_metadata(&db, "a").unwrap();
+        metadata.label = Some("salary".to_owned());
+        save_address_metadata(&mut batch, "a", &metadata);
+        record_address_created(&db, &mut batch, "a", 20).unwrap();
+        delete_address_metadata(&mut batch, "b");
+        db.write(batch.done()).unwrap();
+
+        //Assert
+        let all = get_all_address_metadata(&db).unwrap();
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(all["a"].created, Some(10));
+        assert_eq!(all["a"].label.as_deref(), Some("salary"));
+        assert!(check_address_label(&"x".repeat(MAX_ADDRESS_LABEL_LEN + 1)).is_err());
+        assert!(check_address_label("").is_err());
+    }
+}
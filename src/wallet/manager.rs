@@ -0,0 +1,172 @@
+//! Named wallets opened by a user node along with its own wallet.
+//!
+//! The node wallet funds the payments and trades of the node, and is stored at
+//! the wallet path of the node. Each named wallet, e.g. "mining" or "savings",
+//! is a separate database next to it, with its name appended to the database
+//! suffix, and is encrypted with its own passphrase. The user API selects a
+//! wallet by name, the node wallet being selected when no name is given.
+
+use super::{Argon2Params, Result, WalletDb, WalletDbError};
+use crate::configurations::DbMode;
+use crate::constants::WALLET_PATH;
+use crate::db_utils::CustomDbSpec;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// Name selecting the node wallet
+pub const DEFAULT_WALLET_NAME: &str = "default";
+
+/// Maximum length of a wallet name, in bytes
+pub const MAX_WALLET_NAME_LEN: usize = 32;
+
+/// Node wallet and the named wallets opened along with it
+#[derive(Debug, Clone)]
+pub struct WalletManager {
+    default: WalletDb,
+    named: Arc<RwLock<BTreeMap<String, WalletDb>>>,
+}
+
+impl From<WalletDb> for WalletManager {
+    fn from(default: WalletDb) -> Self {
+        Self::new(default)
+    }
+}
+
+impl WalletManager {
+    /// Manager of the node wallet, with no named wallet opened
+    ///
+    /// ### Arguments
+    ///
+    /// * `default` - Node wallet.
+    pub fn new(default: WalletDb) -> Self {
+        Self {
+            default,
+            named: Default::default(),
+        }
+    }
+
+    /// Open or create a named wallet, and add it to the wallets selectable by name
+    ///
+    /// ### Arguments
+    ///
+    /// * `name`           - Name of the wallet.
+    /// * `db_mode`        - Mode of the wallet database.
+    /// * `passphrase`     - Passphrase of the wallet.
+    /// * `custom_db_spec` - Custom database specification of the node wallet.
+    /// * `kdf_params`     - Argon2id costs of the passphrase key derivation.
+    pub fn open(
+        &self,
+        name: &str,
+        db_mode: DbMode,
+        passphrase: Option<String>,
+        custom_db_spec: Option<&CustomDbSpec>,
+        kdf_params: Argon2Params,
+    ) -> Result<WalletDb> {
+        check_wallet_name(name)?;
+        if self.get(Some(name)).is_some() {
+            return Err(WalletDbError::WalletNameError("Wallet already opened"));
+        }
+
+        let spec = named_wallet_spec(name, custom_db_spec);
+        let wallet = WalletDb::new_with_kdf(db_mode, None, passphrase, Some(spec), kdf_params)?;
+        let mut named = self.named.write().unwrap();
+        named.insert(name.to_owned(), wallet.clone());
+        Ok(wallet)
+    }
+
+    /// Wallet selected by name, the node wallet if no name is given
+    ///
+    /// ### Arguments
+    ///
+    /// * `name` - Name of the wallet.
+    pub fn get(&self, name: Option<&str>) -> Option<WalletDb> {
+        match name {
+            None => Some(self.default.clone()),
+            Some(name) if is_node_wallet_name(name) => Some(self.default.clone()),
+            Some(name) => self.named.read().unwrap().get(name).cloned(),
+        }
+    }
+
+    /// Names of the wallets, the node wallet first
+    pub fn names(&self) -> Vec<String> {
+        let named = self.named.read().unwrap();
+        std::iter::once(DEFAULT_WALLET_NAME.to_owned())
+            .chain(named.keys().cloned())
+            .collect()
+    }
+}
+
+/// Whether a name selects the node wallet
+///
+/// ### Arguments
+///
+/// * `name` - Name of the wallet.
+pub fn is_node_wallet_name(name: &str) -> bool {
+    name.is_empty() || name == DEFAULT_WALLET_NAME
+}
+
+/// Check a name can be given to a wallet
+///
+/// ### Arguments
+///
+/// * `name` - Name of the wallet.
+pub fn check_wallet_name(name: &str) -> Result<()> {
+    if is_node_wallet_name(name) {
+        return Err(WalletDbError::WalletNameError("Name of the node wallet"));
+    }
+    let valid_char = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-';
+    if name.len() > MAX_WALLET_NAME_LEN || !name.chars().all(valid_char) {
+        return Err(WalletDbError::WalletNameError(
+            "Name must be 1 to 32 lowercase letters, digits, '_' or '-'",
+        ));
+    }
+    Ok(())
+}
+
+/// Database specification of a named wallet, next to the node wallet
+///
+/// ### Arguments
+///
+/// * `name`           - Name of the wallet.
+/// * `custom_db_spec` - Custom database specification of the node wallet.
+pub fn named_wallet_spec(name: &str, custom_db_spec: Option<&CustomDbSpec>) -> CustomDbSpec {
+    match custom_db_spec {
+        Some(spec) => CustomDbSpec {
+            db_path: spec.db_path.clone(),
+            suffix: format!("{}_{name}", spec.suffix),
+        },
+        None => CustomDbSpec {
+            db_path: WALLET_PATH.to_owned(),
+            suffix: format!("_{name}"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks named wallets are selected by name, apart from the node wallet
+    fn named_wallets_selected_by_name() {
+        //Arrange
+        let kdf_params = Argon2Params::for_db_mode(DbMode::InMemory);
+        let default = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let wallets = WalletManager::new(default);
+
+        //Act
+        let mining = wallets.open("mining", DbMode::InMemory, None, None, kdf_params);
+        let reopened = wallets.open("mining", DbMode::InMemory, None, None, kdf_params);
+        let invalid = wallets.open("Savings!", DbMode::InMemory, None, None, kdf_params);
+
+        //Assert
+        assert!(mining.is_ok());
+        assert!(matches!(reopened, Err(WalletDbError::WalletNameError(_))));
+        assert!(matches!(invalid, Err(WalletDbError::WalletNameError(_))));
+        assert_eq!(wallets.names(), vec!["default", "mining"]);
+        assert!(wallets.get(Some("mining")).is_some());
+        assert!(wallets.get(Some("savings")).is_none());
+        assert!(wallets.get(Some(DEFAULT_WALLET_NAME)).is_some());
+        assert_eq!(named_wallet_spec("mining", None).suffix, "_mining");
+    }
+}
@@ -1,31 +1,74 @@
 use crate::configurations::{DbMode, WalletTxSpec};
-use crate::constants::{FUND_KEY, KNOWN_ADDRESS_KEY, WALLET_PATH};
+use crate::constants::{KNOWN_ADDRESS_KEY, WALLET_PATH, WALLET_READ_CACHE_ENTRIES};
+use crate::db_executor::{spawn_db, DbPool};
 use crate::db_utils::{
     self, CustomDbSpec, SimpleDb, SimpleDbError, SimpleDbSpec, SimpleDbWriteBatch, DB_COL_DEFAULT,
 };
-use crate::utils::{get_payments_for_wallet, make_wallet_tx_info};
+use crate::interfaces::{NetworkId, UtxoFetchType};
+use crate::spend_script::{condition_signable_data, construct_condition_tx_in};
+use crate::utils::{
+    get_payments_for_wallet, get_timestamp_now, is_locktime_expired, make_wallet_tx_info,
+};
 use crate::Rs2JsMsg;
+use argon2::{Algorithm, Argon2, Version};
 use bincode::{deserialize, serialize};
 use hex::FromHexError;
+use rand::RngCore;
+use reservation::{get_pending_spends, set_pending_spends, RESERVATION_TTL};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
-use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 use std::{error, fmt, io};
 use tokio::sync::Mutex as TokioMutex;
 use tokio::task;
 use tracing::warn;
 use tw_chain::crypto::pbkdf2 as pwhash;
 use tw_chain::crypto::secretbox_chacha20_poly1305 as secretbox;
-use tw_chain::crypto::sign_ed25519 as sign;
-use tw_chain::crypto::sign_ed25519::{PublicKey, SecretKey};
-use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey, Signature};
+use tw_chain::primitives::asset::{Asset, AssetValues};
 use tw_chain::primitives::transaction::{OutPoint, Transaction, TxConstructor, TxIn, TxOut};
 use tw_chain::utils::transaction_utils::{
     construct_address_for, construct_payment_tx_ins, construct_tx_hash,
-    construct_tx_in_signable_hash,
 };
+use wallet_core::{
+    AddressError, AddressKeys, CoinSelection, PaymentAddress, WalletCoreError, WalletStore,
+};
+pub mod audit;
+pub mod backup;
+pub mod batch;
+pub mod change;
 pub mod fund_store;
-pub use fund_store::FundStore;
+pub mod hd;
+pub mod history;
+pub mod hot_keys;
+pub mod key_gen;
+pub mod key_import;
+pub mod labels;
+pub mod manager;
+pub mod multisig;
+pub mod reservation;
+pub mod signer;
+pub mod store_cipher;
+pub mod sync;
+pub mod watch_only;
+pub use audit::{with_signing_trigger, SigningAuditEntry};
+pub use backup::{BackupMetadata, WalletBackup};
+pub use batch::WalletBatch;
+pub use fund_store::{FundBalances, FundStore, FundStoreUpdate};
+pub use history::{TxDirection, TxHistoryEntry, TxHistoryPage, TxStatus};
+pub use hot_keys::HotKeys;
+pub use key_gen::KeyGen;
+pub use key_import::KeyFormat;
+pub use labels::AddressMetadata;
+pub use manager::WalletManager;
+pub use multisig::MultisigAddress;
+pub use reservation::PendingSpends;
+pub use signer::{RemoteSigner, Signer, SignerError};
+pub use sync::{
+    SealedWalletSync, WalletSyncKey, WalletSyncMessage, WalletSyncSummary, WalletSyncUpdate,
+};
+pub use watch_only::WatchOnlyStore;
 
 /// Key for locked coinbase transactions
 pub const LOCKED_COINBASE_KEY: &str = "LockedCoinbaseKey";
@@ -33,6 +76,15 @@ pub const LOCKED_COINBASE_KEY: &str = "LockedCoinbaseKey";
 /// Storage key for a &[u8] of the word 'MasterKeyStore'
 pub const MASTER_KEY_STORE_KEY: &str = "MasterKeyStore";
 
+/// Storage key for the passphrase key derivation of the master key store
+pub const MASTER_KEY_KDF_KEY: &str = "MasterKeyKdf";
+
+/// Storage key marking wallets storing the public half of their key-pairs only
+pub const HOT_WALLET_KEY: &str = "HotWalletMode";
+
+/// Length of the Argon2id salt of new master key stores
+const ARGON2_SALT_LEN: usize = 16;
+
 pub const DB_SPEC: SimpleDbSpec = SimpleDbSpec {
     db_path: WALLET_PATH,
     suffix: "",
@@ -60,6 +112,20 @@ pub enum WalletDbError {
     InsufficientFundsError,
     MasterKeyRetrievalError,
     MasterKeyMissingError,
+    DecryptionError,
+    KeyImportError(&'static str),
+    AddressMismatchError(String),
+    BackupError(&'static str),
+    KeyDerivationError(String),
+    HotWalletError(&'static str),
+    HdWalletError(&'static str),
+    MissingEntryError(String),
+    SyncError(&'static str),
+    LabelError(&'static str),
+    MultisigError(&'static str),
+    WalletNameError(&'static str),
+    FundStoreError(&'static str),
+    WalletCore(WalletCoreError),
 }
 
 impl fmt::Display for WalletDbError {
@@ -74,6 +140,22 @@ impl fmt::Display for WalletDbError {
             Self::InsufficientFundsError => write!(f, "InsufficientFundsError"),
             Self::MasterKeyRetrievalError => write!(f, "MasterKeyRetrievalError"),
             Self::MasterKeyMissingError => write!(f, "MasterKeyMissingError"),
+            Self::DecryptionError => write!(f, "DecryptionError"),
+            Self::KeyImportError(err) => write!(f, "Key Import Error: {err}"),
+            Self::AddressMismatchError(addr) => {
+                write!(f, "Key-pair derives a different address: {addr}")
+            }
+            Self::BackupError(err) => write!(f, "Backup Error: {err}"),
+            Self::KeyDerivationError(err) => write!(f, "Key Derivation Error: {err}"),
+            Self::HotWalletError(err) => write!(f, "Hot Wallet Error: {err}"),
+            Self::HdWalletError(err) => write!(f, "HD Wallet Error: {err}"),
+            Self::MissingEntryError(entry) => write!(f, "Missing Wallet Entry: {entry}"),
+            Self::SyncError(err) => write!(f, "Wallet Sync Error: {err}"),
+            Self::LabelError(err) => write!(f, "Address Label Error: {err}"),
+            Self::MultisigError(err) => write!(f, "Multisig Error: {err}"),
+            Self::WalletNameError(err) => write!(f, "Wallet Name Error: {err}"),
+            Self::FundStoreError(err) => write!(f, "Fund Store Error: {err}"),
+            Self::WalletCore(err) => write!(f, "Wallet Core Error: {err}"),
         }
     }
 }
@@ -90,6 +172,20 @@ impl error::Error for WalletDbError {
             Self::InsufficientFundsError => None,
             Self::MasterKeyRetrievalError => None,
             Self::MasterKeyMissingError => None,
+            Self::DecryptionError => None,
+            Self::KeyImportError(_) => None,
+            Self::AddressMismatchError(_) => None,
+            Self::BackupError(_) => None,
+            Self::KeyDerivationError(_) => None,
+            Self::HotWalletError(_) => None,
+            Self::HdWalletError(_) => None,
+            Self::MissingEntryError(_) => None,
+            Self::SyncError(_) => None,
+            Self::LabelError(_) => None,
+            Self::MultisigError(_) => None,
+            Self::WalletNameError(_) => None,
+            Self::FundStoreError(_) => None,
+            Self::WalletCore(_) => None,
         }
     }
 }
@@ -124,6 +220,15 @@ impl From<FromHexError> for WalletDbError {
     }
 }
 
+impl From<WalletCoreError> for WalletDbError {
+    fn from(other: WalletCoreError) -> Self {
+        match other {
+            WalletCoreError::InsufficientFunds => Self::InsufficientFundsError,
+            other => Self::WalletCore(other),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddressStore {
     pub public_key: PublicKey,
@@ -131,6 +236,13 @@ pub struct AddressStore {
     pub address_version: Option<u64>,
 }
 
+/// Public half of an `AddressStore`, all hot wallets store on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicAddressStore {
+    pub public_key: PublicKey,
+    pub address_version: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddressStoreHex {
     pub public_key: String,
@@ -149,6 +261,29 @@ impl From<AddressStore> for AddressStoreHex {
 }
 
 impl AddressStore {
+    /// Key-pair from its public and secret halves
+    ///
+    /// ### Arguments
+    ///
+    /// * `public`     - Public key and address version.
+    /// * `secret_key` - Secret key.
+    pub fn from_parts(public: PublicAddressStore, secret_key: SecretKey) -> Self {
+        Self {
+            public_key: public.public_key,
+            secret_key,
+            address_version: public.address_version,
+        }
+    }
+
+    /// Split into the public and secret halves
+    pub fn split(self) -> (PublicAddressStore, SecretKey) {
+        let public = PublicAddressStore {
+            public_key: self.public_key,
+            address_version: self.address_version,
+        };
+        (public, self.secret_key)
+    }
+
     pub fn try_from_hex_store(hex_store: AddressStoreHex) -> Result<Self> {
         let public_key = hex::decode(hex_store.public_key)?;
         let secret_key = hex::decode(hex_store.secret_key)?;
@@ -162,6 +297,16 @@ impl AddressStore {
     }
 }
 
+impl From<AddressStore> for AddressKeys {
+    fn from(other: AddressStore) -> Self {
+        Self {
+            public_key: other.public_key,
+            secret_key: other.secret_key,
+            address_version: other.address_version,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionStore {
     pub key_address: String,
@@ -174,14 +319,163 @@ pub struct MasterKeyStore {
     pub enc_master_key: Vec<u8>,
 }
 
+/// Argon2id costs of the passphrase key derivation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    /// Memory used, in KiB
+    pub memory_kib: u32,
+    /// Number of passes over the memory
+    pub iterations: u32,
+    /// Number of lanes
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl Argon2Params {
+    /// Default costs for a wallet database
+    ///
+    /// Test and in-memory wallets use the minimum costs, so tests creating
+    /// many wallets stay fast.
+    ///
+    /// ### Arguments
+    ///
+    /// * `db_mode` - Mode of the wallet database.
+    pub fn for_db_mode(db_mode: DbMode) -> Self {
+        match db_mode {
+            DbMode::Live => Self::default(),
+            DbMode::Test(_) | DbMode::InMemory => Self {
+                memory_kib: 8,
+                iterations: 1,
+                parallelism: 1,
+            },
+        }
+    }
+}
+
+/// Key derivation of the passphrase for the master key store
+///
+/// Stored alongside the master key store, wallets without it use PBKDF2.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MasterKeyKdf {
+    /// PBKDF2 with the salt of the master key store
+    Pbkdf2,
+    /// Argon2id with its own salt and costs
+    Argon2id { salt: Vec<u8>, params: Argon2Params },
+}
+
+impl MasterKeyKdf {
+    /// Argon2id derivation with a new random salt
+    ///
+    /// ### Arguments
+    ///
+    /// * `params` - Argon2id costs.
+    pub fn new_argon2id(params: Argon2Params) -> Self {
+        let mut salt = vec![0; ARGON2_SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self::Argon2id { salt, params }
+    }
+}
+
+/// Wallet opened without its passphrase, whose stores cannot be read until unlocked
+#[derive(Debug)]
+pub struct LockedWalletDb {
+    db: SimpleDb,
+    db_mode: DbMode,
+}
+
+impl LockedWalletDb {
+    /// Unlock the wallet with its passphrase, creating its master key on first use
+    ///
+    /// The stores of the wallet are decrypted with its master key, and those
+    /// written before they were encrypted are encrypted in place.
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Wallet passphrase.
+    /// * `kdf_params` - Argon2id costs of the passphrase key derivation.
+    /// * `hot_keys`   - Secret keys held in memory, for hot wallet mode.
+    pub fn unlock(
+        self,
+        passphrase: Option<String>,
+        kdf_params: Argon2Params,
+        hot_keys: Option<Arc<HotKeys>>,
+    ) -> Result<WalletDb> {
+        let Self { mut db, db_mode } = self;
+
+        let passphrase = passphrase.as_deref().unwrap_or("").as_bytes();
+        if passphrase.is_empty() && matches!(db_mode, DbMode::Live) {
+            warn!("Wallet opened without a passphrase, its stores are encrypted with an empty one");
+        }
+        let masterkey = {
+            let mut batch = db.batch_writer();
+            let masterkey = get_or_save_master_key_store(&db, &mut batch, passphrase, kdf_params)?;
+            let batch = batch.done();
+            db.write(batch)?;
+            masterkey
+        };
+        store_cipher::unlock_store(&mut db, &masterkey)?;
+
+        let mut batch = db.batch_writer();
+        fund_store::migrate_fund_store_value(&db, &mut batch)?;
+
+        let is_hot_wallet = db.get_cf(DB_COL_DEFAULT, HOT_WALLET_KEY)?.is_some();
+        match &hot_keys {
+            Some(hot_keys) if !is_hot_wallet => {
+                move_secret_keys_to_memory(&db, &mut batch, &masterkey, hot_keys)?;
+                batch.put_cf(DB_COL_DEFAULT, HOT_WALLET_KEY, [1u8]);
+            }
+            None if is_hot_wallet => {
+                return Err(WalletDbError::HotWalletError(
+                    "Wallet stores public keys only",
+                ));
+            }
+            _ => (),
+        }
+
+        let batch = batch.done();
+        db.write(batch)?;
+        db.set_read_cache(WALLET_READ_CACHE_ENTRIES);
+        Ok(WalletDb {
+            db: Arc::new(RwLock::new(db)),
+            locked_coinbase: Arc::new(TokioMutex::new(None)),
+            encryption_key: masterkey,
+            ui_feedback_tx: None,
+            last_generated_address: None,
+            last_locked_coinbase_filter_b_num: None,
+            key_gen: Default::default(),
+            coin_selection: Default::default(),
+            reuse_change_address: false,
+            signer: None,
+            hot_keys,
+            kdf_params,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WalletDb {
-    db: Arc<Mutex<SimpleDb>>,
+    /// Queries share the lock, input selection reserving its outputs and writes are exclusive
+    db: Arc<RwLock<SimpleDb>>,
     encryption_key: secretbox::Key,
     ui_feedback_tx: Option<tokio::sync::mpsc::Sender<Rs2JsMsg>>,
     locked_coinbase: LockedCoinbaseWithMutex,
     last_generated_address: Option<String>,
     last_locked_coinbase_filter_b_num: Option<u64>,
+    key_gen: KeyGen,
+    coin_selection: CoinSelection,
+    reuse_change_address: bool,
+    signer: Option<Arc<dyn Signer>>,
+    hot_keys: Option<Arc<HotKeys>>,
+    kdf_params: Argon2Params,
 }
 
 impl WalletDb {
@@ -191,22 +485,133 @@ impl WalletDb {
         passphrase: Option<String>,
         custom_db_spec: Option<CustomDbSpec>,
     ) -> Result<Self> {
+        let kdf_params = Argon2Params::for_db_mode(db_mode);
+        Self::new_with_kdf(db_mode, db, passphrase, custom_db_spec, kdf_params)
+    }
+
+    /// Open or create a wallet, using the given Argon2id costs for new passphrases
+    ///
+    /// Existing wallets keep the key derivation stored with them until their
+    /// passphrase is changed.
+    ///
+    /// ### Arguments
+    ///
+    /// * `db_mode`        - Mode of the wallet database.
+    /// * `db`             - Existing database to use.
+    /// * `passphrase`     - Wallet passphrase.
+    /// * `custom_db_spec` - Custom database specification.
+    /// * `kdf_params`     - Argon2id costs of the passphrase key derivation.
+    pub fn new_with_kdf(
+        db_mode: DbMode,
+        db: Option<SimpleDb>,
+        passphrase: Option<String>,
+        custom_db_spec: Option<CustomDbSpec>,
+        kdf_params: Argon2Params,
+    ) -> Result<Self> {
+        Self::new_with_hot_keys(db_mode, db, passphrase, custom_db_spec, kdf_params, None)
+    }
+
+    /// Open or create a wallet, holding its secret keys in memory only if hot keys are given
+    ///
+    /// The secret keys stored by a wallet opened in hot wallet mode for the first
+    /// time are moved to the hot keys, and only the public keys are kept on disk.
+    /// Wallets opened in hot wallet mode once cannot be opened without it.
+    ///
+    /// ### Arguments
+    ///
+    /// * `db_mode`        - Mode of the wallet database.
+    /// * `db`             - Existing database to use.
+    /// * `passphrase`     - Wallet passphrase.
+    /// * `custom_db_spec` - Custom database specification.
+    /// * `kdf_params`     - Argon2id costs of the passphrase key derivation.
+    /// * `hot_keys`       - Secret keys held in memory, for hot wallet mode.
+    pub fn new_with_hot_keys(
+        db_mode: DbMode,
+        db: Option<SimpleDb>,
+        passphrase: Option<String>,
+        custom_db_spec: Option<CustomDbSpec>,
+        kdf_params: Argon2Params,
+        hot_keys: Option<Arc<HotKeys>>,
+    ) -> Result<Self> {
+        Self::open_locked(db_mode, db, custom_db_spec).unlock(passphrase, kdf_params, hot_keys)
+    }
+
+    /// Open or create a wallet, locked until unlocked with its passphrase
+    ///
+    /// ### Arguments
+    ///
+    /// * `db_mode`        - Mode of the wallet database.
+    /// * `db`             - Existing database to use.
+    /// * `custom_db_spec` - Custom database specification.
+    pub fn open_locked(
+        db_mode: DbMode,
+        db: Option<SimpleDb>,
+        custom_db_spec: Option<CustomDbSpec>,
+    ) -> LockedWalletDb {
         let mut db = db_utils::new_db(db_mode, &DB_SPEC, db, custom_db_spec);
-        let mut batch = db.batch_writer();
+        store_cipher::lock_store(&mut db);
+        LockedWalletDb { db, db_mode }
+    }
+    /// Check the wallet belongs to the network, recording it on first use
+    ///
+    /// ### Arguments
+    ///
+    /// * `network_id` - Network of the node opening the wallet.
+    pub fn check_network_id(&self, network_id: NetworkId) -> Result<()> {
+        let mut db = self.db.write().unwrap();
+        Ok(db_utils::check_network_id(&mut db, network_id)?)
+    }
 
-        let passphrase = passphrase.as_deref().unwrap_or("").as_bytes();
-        let masterkey = get_or_save_master_key_store(&db, &mut batch, passphrase)?;
+    /// Network recorded in the wallet, the main network if no node recorded one
+    pub fn get_network_id(&self) -> Result<NetworkId> {
+        let db = self.db.read().unwrap();
+        Ok(db_utils::get_network_id(&db)?.unwrap_or(NetworkId::Mainnet))
+    }
 
-        let batch = batch.done();
-        db.write(batch).unwrap();
-        Ok(Self {
-            db: Arc::new(Mutex::new(db)),
-            locked_coinbase: Arc::new(TokioMutex::new(None)),
-            encryption_key: masterkey,
-            ui_feedback_tx: None,
-            last_generated_address: None,
-            last_locked_coinbase_filter_b_num: None,
-        })
+    /// Set the generator of the key-pairs of new addresses
+    ///
+    /// ### Arguments
+    ///
+    /// * `key_gen` - Random or seeded key-pair generator.
+    pub fn with_key_gen(mut self, key_gen: KeyGen) -> Self {
+        self.key_gen = key_gen;
+        self
+    }
+
+    /// Generator of the key-pairs of new addresses
+    pub fn key_gen(&self) -> &KeyGen {
+        &self.key_gen
+    }
+
+    /// Set the selection of the outputs spent by payments
+    ///
+    /// ### Arguments
+    ///
+    /// * `coin_selection` - Coin selection policy and dust consolidation.
+    pub fn with_coin_selection(mut self, coin_selection: CoinSelection) -> Self {
+        self.coin_selection = coin_selection;
+        self
+    }
+
+    /// Set whether the change of payments is paid back to a spent address
+    ///
+    /// ### Arguments
+    ///
+    /// * `reuse` - Whether to reuse the address of the first input spent, for test
+    ///   scenarios, instead of generating a new change address.
+    pub fn with_change_address_reuse(mut self, reuse: bool) -> Self {
+        self.reuse_change_address = reuse;
+        self
+    }
+
+    /// Set the signer of inputs, instead of the stored secret keys
+    ///
+    /// ### Arguments
+    ///
+    /// * `signer` - Signer holding the secret keys of the addresses.
+    pub fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
     }
 
     /// Set the UI feedback channel
@@ -279,15 +684,18 @@ impl WalletDb {
     /// * `passphrase` - Current wallet passphrase
     pub async fn get_master_key_store(&self, passphrase: String) -> Result<secretbox::Key> {
         let db = self.db.clone();
-        task::spawn_blocking(move || {
-            let db = db.lock().unwrap();
+        spawn_db(DbPool::Wallet, move || {
+            let db = db.read().unwrap();
             get_master_key_store(&db, passphrase.as_bytes())
         })
-        .await?
+        .await
     }
 
     /// Re-encrypt the master key with a new passphrase
     ///
+    /// The new passphrase is always derived with Argon2id, upgrading wallets
+    /// still using PBKDF2.
+    ///
     /// ### Arguments
     ///
     /// * `new_passphrase`      - Passphrase for master key store.
@@ -297,224 +705,931 @@ impl WalletDb {
         new_passphrase: String,
     ) -> Result<()> {
         let db = self.db.clone();
-        task::spawn_blocking(move || {
-            let mut db = db.lock().unwrap();
+        let kdf_params = self.kdf_params;
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
             let mut batch = db.batch_writer();
             let master_key = get_master_key_store(&db, old_passphrase.as_bytes())?;
-            let salt = pwhash::gen_salt();
-            let nonce = secretbox::gen_nonce();
-            let pass_key = make_key(new_passphrase.as_bytes(), salt);
-            let enc_master_key =
-                secretbox::seal(master_key.as_ref().to_vec(), &nonce, &pass_key).unwrap();
-            let store = serialize(&MasterKeyStore {
-                salt,
-                nonce,
-                enc_master_key,
-            })
-            .unwrap();
-            batch.put_cf(DB_COL_DEFAULT, MASTER_KEY_STORE_KEY, &store);
+            save_master_key_store(
+                &mut batch,
+                &master_key,
+                new_passphrase.as_bytes(),
+                kdf_params,
+            )?;
             let batch = batch.done();
-            db.write(batch).unwrap();
+            db.write(batch)?;
             Ok(())
         })
-        .await?
+        .await
     }
 
-    pub async fn with_seed(mut self, seeds: Vec<WalletTxSpec>) -> Self {
+    pub async fn with_seed(mut self, seeds: Vec<WalletTxSpec>) -> Result<Self> {
         {
-            let fund_store = self.get_fund_store();
-            let addresses = self.get_known_addresses();
+            let fund_store = self.get_fund_store()?;
+            let addresses = self.get_known_addresses()?;
             if !fund_store.transactions().is_empty()
                 || !fund_store.spent_transactions().is_empty()
                 || !addresses.is_empty()
             {
-                return self;
+                return Ok(self);
             }
         }
 
         for seed in seeds {
             let (tx_out_p, pk, sk, amount, v) = make_wallet_tx_info(&seed);
-            let (address, _) = self.store_payment_address(pk, sk, v).await;
+            let (address, _) = self.store_payment_address(pk, sk, v).await?;
             let payments = vec![(tx_out_p, Asset::Token(amount), address, 0)];
-            self.save_usable_payments_to_wallet(payments, 0)
-                .await
-                .unwrap();
+            self.save_usable_payments_to_wallet(payments, 0).await?;
         }
-        self
+        Ok(self)
     }
 
     /// Extract persistent storage of a closed raft
     pub async fn take_closed_persistent_store(&mut self) -> SimpleDb {
-        self.db.lock().unwrap().take()
+        self.db.write().unwrap().take()
+    }
+
+    /// Read every entry of the persistent storage, returning the number of entries
+    pub fn scan_persistent_store(&self) -> db_utils::Result<usize> {
+        self.db.read().unwrap().scan()
     }
 
     /// Backup persistent storage
     pub async fn backup_persistent_store(&mut self) -> Result<()> {
-        self.db.lock().unwrap().file_backup()?;
+        self.db.read().unwrap().file_backup()?;
         Ok(())
     }
 
     /// Generates a new payment address, saving the related keys to the wallet
+    ///
+    /// Random key-pairs are derived from the wallet master seed, except for hot
+    /// wallets which do not store one.
     /// TODO: Add static address capability for frequent payments
-    pub async fn generate_payment_address(&mut self) -> (String, AddressStore) {
-        let (public_key, secret_key) = sign::gen_keypair();
+    pub async fn generate_payment_address(&mut self) -> Result<(String, AddressStore)> {
+        let (public_key, secret_key) = match (&self.key_gen, &self.hot_keys) {
+            (KeyGen::Random, None) => self.next_hd_keypair().await?,
+            _ => self.key_gen.gen_keypair(),
+        };
         self.store_payment_address(public_key, secret_key, None)
             .await
     }
 
-    /// Store a new payment address, saving the related keys to the wallet
-    pub async fn store_payment_address(
-        &mut self,
-        public_key: PublicKey,
-        secret_key: SecretKey,
-        address_version: Option<u64>,
-    ) -> (String, AddressStore) {
-        let final_address = construct_address_for(&public_key, address_version);
-        let address_keys = AddressStore {
-            public_key,
-            secret_key,
-            address_version,
-        };
-
-        let save_result = self
-            .save_address_to_wallet(final_address.clone(), address_keys.clone())
-            .await;
-        if save_result.is_err() {
-            panic!("Error writing address to wallet");
-        }
-
-        self.last_generated_address = Some(final_address.clone());
-
-        (final_address, address_keys)
+    /// Generates a new payment address, encoded as a checked address of the wallet network
+    ///
+    /// Checked addresses are given out to payers, so a typo or an address of
+    /// another network is refused when paid to. Outputs still hold the address
+    /// they decode to.
+    pub async fn generate_checked_payment_address(&mut self) -> Result<String> {
+        let (address, _) = self.generate_payment_address().await?;
+        self.encode_checked_address(&address)
     }
 
-    /// Saves an address and its ancestor keys to the wallet
+    /// Encode an address of the current version as a checked address of the wallet network
     ///
     /// ### Arguments
     ///
-    /// * `address` - Address to save to wallet
-    /// * `keys`    - Address-related keys to save
-    pub async fn save_address_to_wallet(&self, address: String, keys: AddressStore) -> Result<()> {
-        let db = self.db.clone();
-        let encryption_key = self.encryption_key.clone();
-        Ok(task::spawn_blocking(move || {
-            // Wallet DB handling
-            let mut db = db.lock().unwrap();
-            let mut batch = db.batch_writer();
-
-            let mut address_list = get_known_key_address(&db);
-            address_list.insert(address.clone());
-
-            // Save to disk
-            save_address_store_to_wallet(&mut batch, &address, keys, &encryption_key);
-            set_known_key_address(&mut batch, address_list);
-            let batch = batch.done();
-            db.write(batch).unwrap();
+    /// * `address` - Address of the current version.
+    pub fn encode_checked_address(&self, address: &str) -> Result<String> {
+        let network = self.get_network_id()?.address_byte();
+        wallet_core::encode_checked_address(address, network).ok_or_else(|| {
+            let error = AddressError::InvalidLength(address.len());
+            WalletCoreError::InvalidAddress(error).into()
         })
-        .await?)
     }
 
-    /// Saves an AddressStore to wallet in a directly encrypted state
+    /// Check an address can be paid to from the wallet, returning the address paid to on chain
+    ///
+    /// Checked addresses are decoded, and refused if of another network than the wallet.
     ///
     /// ### Arguments
     ///
-    /// * `address` - Address to save to wallet
-    /// * `keys`    - Address-related keys in an encrypted state
-    pub async fn save_encrypted_address_to_wallet(
-        &self,
-        address: String,
-        keys: Vec<u8>,
-    ) -> Result<()> {
+    /// * `address` - Address paid to.
+    pub fn decode_payment_address(&self, address: &str) -> Result<String> {
+        let network = self.get_network_id()?.address_byte();
+        PaymentAddress::validate_for_network(address, network)
+            .map(PaymentAddress::into_string)
+            .map_err(|e| WalletCoreError::InvalidAddress(e).into())
+    }
+
+    /// Derive the key-pair of the next address from the master seed, creating the seed if needed
+    async fn next_hd_keypair(&self) -> Result<(PublicKey, SecretKey)> {
         let db = self.db.clone();
-        Ok(task::spawn_blocking(move || {
-            let mut db = db.lock().unwrap();
+        let encryption_key = self.encryption_key.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
             let mut batch = db.batch_writer();
 
-            let mut address_list = get_known_key_address(&db);
-            address_list.insert(address.clone());
-
-            batch.put_cf(DB_COL_DEFAULT, address, keys);
-            set_known_key_address(&mut batch, address_list);
+            let seed = match hd::get_hd_seed(&db, &encryption_key)? {
+                Some(seed) => seed,
+                None => {
+                    let seed = hd::generate_hd_seed()?;
+                    hd::save_hd_seed(&mut batch, &seed, &encryption_key);
+                    seed
+                }
+            };
+            let index = hd::get_hd_next_index(&db)?;
+            hd::set_hd_next_index(&mut batch, index + 1);
 
             let batch = batch.done();
-            db.write(batch).unwrap();
+            db.write(batch)?;
+            Ok(hd::derive_address_keypair(&seed, index))
         })
-        .await?)
+        .await
     }
 
-    /// Saves an address and the associated transaction with it to the wallet
+    /// Master seed the addresses of the wallet are derived from, hex encoded
     ///
     /// ### Arguments
     ///
-    /// * `out_p`        - Transaction hash/index
-    /// * `key_address`  - Transaction Address
-    pub async fn save_transaction_to_wallet(
-        &self,
-        out_p: OutPoint,
-        key_address: String,
-    ) -> Result<()> {
-        let db = self.db.clone();
-        Ok(task::spawn_blocking(move || {
-            let mut db = db.lock().unwrap();
-            let mut batch = db.batch_writer();
-
-            let store = TransactionStore { key_address };
-            save_transaction_to_wallet(&mut batch, &out_p, &store);
+    /// * `passphrase` - Current wallet passphrase.
+    pub async fn export_hd_seed(&self, passphrase: String) -> Result<String> {
+        Ok(hex::encode(self.get_hd_seed(passphrase).await?))
+    }
 
-            let batch = batch.done();
-            db.write(batch).unwrap();
-        })
-        .await?)
+    /// Mnemonic phrase of the master seed the addresses of the wallet are derived from
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Current wallet passphrase.
+    pub async fn export_mnemonic(&self, passphrase: String) -> Result<String> {
+        hd::seed_to_mnemonic(&self.get_hd_seed(passphrase).await?)
     }
 
-    /// Saves a received payment to the local wallet
+    /// Master seed of the wallet, once the passphrase is checked
     ///
     /// ### Arguments
     ///
-    /// * `payments` - Payments OutPoint, amount and receiver key address
-    /// * `b_num`    - Current block number
-    pub async fn save_usable_payments_to_wallet(
-        &mut self,
-        payments: Vec<(OutPoint, Asset, String, u64)>,
-        current_b_num: u64,
-    ) -> Result<Vec<(OutPoint, Asset, String, u64)>> {
-        let db = self.db.clone();
-        let locked_coinbase = self.get_locked_coinbase().await.unwrap_or_default();
-        let (result, locked_db) = task::spawn_blocking(move || {
-            let mut db = db.lock().unwrap();
-            let mut batch = db.batch_writer();
-            let mut fund_store = get_fund_store(&db);
-            let mut locked_coinbase = locked_coinbase.clone();
-            let addresses = get_known_key_address(&db);
+    /// * `passphrase` - Current wallet passphrase.
+    async fn get_hd_seed(&self, passphrase: String) -> Result<Vec<u8>> {
+        self.test_passphrase(passphrase).await?;
+        hd::get_hd_seed(&self.db.read().unwrap(), &self.encryption_key)?
+            .ok_or(WalletDbError::HdWalletError("Wallet has no seed"))
+    }
 
-            let usable_payments: Vec<_> = payments
+    /// Open or create a wallet using the master seed of a mnemonic phrase, saving
+    /// the first addresses derived from it
+    ///
+    /// ### Arguments
+    ///
+    /// * `db_mode`        - Mode of the wallet database.
+    /// * `db`             - Existing database to use.
+    /// * `passphrase`     - Wallet passphrase.
+    /// * `custom_db_spec` - Custom database specification.
+    /// * `mnemonic`       - Mnemonic phrase of the master seed.
+    pub async fn from_mnemonic(
+        db_mode: DbMode,
+        db: Option<SimpleDb>,
+        passphrase: Option<String>,
+        custom_db_spec: Option<CustomDbSpec>,
+        mnemonic: &str,
+    ) -> Result<Self> {
+        let seed = hd::mnemonic_to_seed(mnemonic)?;
+        let mut wallet = Self::new(db_mode, db, passphrase, custom_db_spec)?;
+        wallet
+            .restore_from_seed(&seed, hd::DEFAULT_HD_RESTORE_COUNT, false)
+            .await?;
+        Ok(wallet)
+    }
+
+    /// Use a master seed for the wallet, saving the first addresses derived from it
+    ///
+    /// The addresses derived are returned, so their funds can be found again
+    /// from the UTXO set, and new addresses are derived after them. A wallet
+    /// with another seed is refused unless `overwrite` is set, its addresses
+    /// then being kept but no longer derived. The addresses and the seed are
+    /// written together.
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed`      - Master seed to restore.
+    /// * `count`     - Number of addresses to derive, from index 0.
+    /// * `overwrite` - Whether to replace another seed of the wallet.
+    pub async fn restore_from_seed(
+        &mut self,
+        seed: &[u8],
+        count: u32,
+        overwrite: bool,
+    ) -> Result<Vec<String>> {
+        if self.hot_keys.is_some() {
+            return Err(WalletDbError::HotWalletError(
+                "Hot wallets do not store a seed",
+            ));
+        }
+        if seed.len() < 16 || seed.len() > 64 {
+            return Err(WalletDbError::HdWalletError("Seed must be 16 to 64 bytes"));
+        }
+
+        let db = self.db.clone();
+        let encryption_key = self.encryption_key.clone();
+        let seed = seed.to_vec();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let next_index = match hd::get_hd_seed(&db, &encryption_key)? {
+                Some(current) if current == seed => hd::get_hd_next_index(&db)?.max(count),
+                Some(_) if !overwrite => {
+                    return Err(WalletDbError::HdWalletError("Wallet has another seed"));
+                }
+                _ => count,
+            };
+
+            let known_addresses = get_known_key_address(&db)?;
+            let mut changes = WalletBatch::default();
+            let mut addresses = Vec::with_capacity(count as usize);
+            for index in 0..count {
+                let (public_key, secret_key) = hd::derive_address_keypair(&seed, index);
+                let address = construct_address_for(&public_key, None);
+                if !known_addresses.contains(&address) {
+                    let store = AddressStore {
+                        public_key,
+                        secret_key,
+                        address_version: None,
+                    };
+                    changes.save_address(address.clone(), store);
+                }
+                addresses.push(address);
+            }
+
+            let mut batch = db.batch_writer();
+            batch::write_wallet_batch(&db, &mut batch, changes, &encryption_key, None)?;
+            hd::save_hd_seed(&mut batch, &seed, &encryption_key);
+            hd::set_hd_next_index(&mut batch, next_index);
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(addresses)
+        })
+        .await
+    }
+
+    /// Store a new payment address, saving the related keys to the wallet
+    pub async fn store_payment_address(
+        &mut self,
+        public_key: PublicKey,
+        secret_key: SecretKey,
+        address_version: Option<u64>,
+    ) -> Result<(String, AddressStore)> {
+        let final_address = construct_address_for(&public_key, address_version);
+        let address_keys = AddressStore {
+            public_key,
+            secret_key,
+            address_version,
+        };
+
+        self.save_address_to_wallet(final_address.clone(), address_keys.clone())
+            .await?;
+
+        self.last_generated_address = Some(final_address.clone());
+
+        Ok((final_address, address_keys))
+    }
+
+    /// Saves an address and its ancestor keys to the wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to save to wallet
+    /// * `keys`    - Address-related keys to save
+    pub async fn save_address_to_wallet(&self, address: String, keys: AddressStore) -> Result<()> {
+        let mut changes = WalletBatch::default();
+        changes.save_address(address, keys);
+        self.batch_update(changes).await
+    }
+
+    /// Apply changes to the address, transaction and fund stores in a single write
+    ///
+    /// None of the changes are written if any of them fails.
+    ///
+    /// ### Arguments
+    ///
+    /// * `changes` - Changes to the wallet stores.
+    pub async fn batch_update(&self, changes: WalletBatch) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let db = self.db.clone();
+        let encryption_key = self.encryption_key.clone();
+        let hot_keys = self.hot_keys.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            let hot_keys = hot_keys.as_deref();
+            batch::write_wallet_batch(&db, &mut batch, changes, &encryption_key, hot_keys)?;
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Import a key-pair exported by other tooling, returning its address
+    ///
+    /// ### Arguments
+    ///
+    /// * `format`           - Format of the secret key.
+    /// * `secret_key`       - Encoded secret key.
+    /// * `address_version`  - Version of the address derived from the public key.
+    /// * `expected_address` - Address the key-pair must derive, if known.
+    pub async fn import_keypair(
+        &self,
+        format: KeyFormat,
+        secret_key: &str,
+        address_version: Option<u64>,
+        expected_address: Option<&str>,
+    ) -> Result<String> {
+        let (public_key, secret_key) = key_import::decode_keypair(format, secret_key)?;
+        let address = construct_address_for(&public_key, address_version);
+        if expected_address.is_some_and(|expected| expected != address) {
+            return Err(WalletDbError::AddressMismatchError(address));
+        }
+
+        let keys = AddressStore {
+            public_key,
+            secret_key,
+            address_version,
+        };
+        self.save_address_to_wallet(address.clone(), keys).await?;
+        Ok(address)
+    }
+
+    /// Set or clear the label of a wallet address
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Wallet address.
+    /// * `label`   - Label to give, or None to clear it.
+    pub async fn set_address_label(&self, address: String, label: Option<String>) -> Result<()> {
+        if let Some(label) = &label {
+            labels::check_address_label(label)?;
+        }
+
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            if !get_known_key_address(&db)?.contains(&address) {
+                return Err(WalletDbError::MissingEntryError(address));
+            }
+
+            let mut metadata = labels::get_address_metadata(&db, &address)?;
+            metadata.label = label;
+            let mut batch = db.batch_writer();
+            labels::save_address_metadata(&mut batch, &address, &metadata);
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Create an m-of-n multisig address with co-signers, signing with a new
+    /// key-pair of the wallet, returning the multisig address
+    ///
+    /// ### Arguments
+    ///
+    /// * `threshold`  - Number of signatures needed to spend, m.
+    /// * `co_signers` - Public keys of the other signers.
+    pub async fn create_multisig_address(
+        &mut self,
+        threshold: usize,
+        co_signers: Vec<PublicKey>,
+    ) -> Result<String> {
+        multisig::check_multisig_shape(threshold, co_signers.len() + 1)?;
+        let (own_address, keys) = self.generate_payment_address().await?;
+        let mut signers = co_signers;
+        signers.push(keys.public_key);
+        let multisig = MultisigAddress::new(threshold, signers, own_address)?;
+        let address = multisig.address();
+
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            multisig::save_multisig_address(&mut batch, &multisig);
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(address)
+        })
+        .await
+    }
+
+    /// Sign the spending of an output paid to a multisig address with the key of
+    /// the wallet, recording it in the audit log, returning the partial signature
+    ///
+    /// ### Arguments
+    ///
+    /// * `multisig_address` - Multisig address the output is paid to.
+    /// * `previous_out`     - Output spent.
+    /// * `outputs`          - Outputs of the spending transaction.
+    pub async fn create_partial_signature(
+        &self,
+        multisig_address: String,
+        previous_out: OutPoint,
+        outputs: Vec<TxOut>,
+    ) -> Result<(PublicKey, Signature)> {
+        let db = self.db.clone();
+        let encryption_key = self.encryption_key.clone();
+        let signer = self.signer.clone();
+        let hot_keys = self.hot_keys.clone();
+        let trigger = audit::current_signing_trigger();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let multisig = multisig::get_multisig_address(&db, &multisig_address)?
+                .ok_or(WalletDbError::MissingEntryError(multisig_address))?;
+            let own_address = multisig.own_address;
+
+            let store = DbWalletStore::new(
+                &db,
+                &encryption_key,
+                FundStore::default(),
+                signer.as_deref(),
+                hot_keys.as_deref(),
+            );
+            let keys = store
+                .address_keys(&own_address)
+                .ok_or_else(|| WalletDbError::MissingEntryError(own_address.clone()))?;
+            let signable_data = condition_signable_data(&previous_out, &outputs);
+            let signature = store.sign_digest(&own_address, &keys, signable_data.as_bytes())?;
+
+            let mut batch = db.batch_writer();
+            let tx_used = [(previous_out, own_address)];
+            let timestamp = get_timestamp_now();
+            audit::append_signing_audit(&db, &mut batch, &tx_used, &trigger, timestamp)?;
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok((keys.public_key, signature))
+        })
+        .await
+    }
+
+    /// Combine the partial signatures of the signers of a multisig address into
+    /// the input spending an output paid to it
+    ///
+    /// ### Arguments
+    ///
+    /// * `multisig_address` - Multisig address the output is paid to.
+    /// * `previous_out`     - Output spent.
+    /// * `outputs`          - Outputs of the spending transaction.
+    /// * `partials`         - Partial signatures collected from the signers.
+    pub fn combine_signatures(
+        &self,
+        multisig_address: &str,
+        previous_out: OutPoint,
+        outputs: &[TxOut],
+        partials: Vec<(PublicKey, Signature)>,
+    ) -> Result<TxIn> {
+        let multisig = self
+            .get_multisig_address(multisig_address)?
+            .ok_or_else(|| WalletDbError::MissingEntryError(multisig_address.to_owned()))?;
+        let signable_data = condition_signable_data(&previous_out, outputs);
+        let witness = multisig.combine_signatures(&signable_data, partials)?;
+        Ok(construct_condition_tx_in(
+            previous_out,
+            &multisig.condition(),
+            &witness,
+        ))
+    }
+
+    /// Watch an address without its keys, returning false if already watched
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to watch.
+    pub async fn import_watch_only_address(&self, address: String) -> Result<bool> {
+        self.save_watch_only_address(address, None).await
+    }
+
+    /// Watch the address of a public key without its secret key, returning the address
+    ///
+    /// ### Arguments
+    ///
+    /// * `public_key`      - Public key of the address.
+    /// * `address_version` - Version of the address derived from the public key.
+    pub async fn import_watch_only_public_key(
+        &self,
+        public_key: PublicKey,
+        address_version: Option<u64>,
+    ) -> Result<String> {
+        let address = construct_address_for(&public_key, address_version);
+        self.save_watch_only_address(address.clone(), Some(public_key))
+            .await?;
+        Ok(address)
+    }
+
+    /// Save a watch-only address, returning false if already watched
+    ///
+    /// ### Arguments
+    ///
+    /// * `address`    - Address to watch.
+    /// * `public_key` - Public key of the address, if known.
+    async fn save_watch_only_address(
+        &self,
+        address: String,
+        public_key: Option<PublicKey>,
+    ) -> Result<bool> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            watch_only::check_watch_only_address(&address, &get_known_key_address(&db)?)?;
+
+            let mut watch_only = watch_only::get_watch_only_store(&db)?;
+            let added = watch_only.add_address(address, public_key);
+            let mut batch = db.batch_writer();
+            watch_only::set_watch_only_store(&mut batch, &watch_only);
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(added)
+        })
+        .await
+    }
+
+    /// Export the key-pairs of the wallet addresses as an encrypted backup
+    ///
+    /// ### Arguments
+    ///
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub fn export_key_backup(&self, passphrase: &str) -> Result<WalletBackup> {
+        WalletBackup::seal(self.backup_key_records()?, passphrase)
+    }
+
+    /// Write an encrypted backup of the key-pairs and outputs of the wallet to a new file
+    ///
+    /// ### Arguments
+    ///
+    /// * `path`       - Backup file to create, never overwritten.
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub fn export_backup(&self, path: &Path, passphrase: &str) -> Result<BackupMetadata> {
+        if passphrase.is_empty() {
+            return Err(WalletDbError::BackupError("Backup passphrase is blank"));
+        }
+
+        let outputs = {
+            let db = self.db.read().unwrap();
+            let fund_store = get_fund_store(&db)?;
+            let unspent = fund_store.transactions().iter().map(|o| (o, false));
+            let spent = fund_store.spent_transactions().iter().map(|o| (o, true));
+            unspent
+                .chain(spent)
+                .filter_map(|((out_point, asset), spent)| {
+                    let store = get_transaction_store(&db, out_point).ok()?;
+                    Some(backup::BackupOutputRecord {
+                        out_point: out_point.clone(),
+                        asset: asset.clone(),
+                        address: store.key_address,
+                        spent,
+                    })
+                })
+                .collect()
+        };
+        let backup = WalletBackup::seal_wallet(self.backup_key_records()?, outputs, passphrase)?;
+        backup.save(path)?;
+        Ok(backup.metadata)
+    }
+
+    /// Restore the key-pairs and outputs of a backup file, returning the addresses restored
+    ///
+    /// The backup is checked, and its content saved in a single write, before any
+    /// change is made. Outputs known to the wallet are kept, and spent outputs are
+    /// never made spendable again.
+    ///
+    /// ### Arguments
+    ///
+    /// * `path`       - Backup file.
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub async fn import_backup(&self, path: &Path, passphrase: &str) -> Result<Vec<String>> {
+        let (keys, outputs) = WalletBackup::load(path)?.open_wallet(passphrase)?;
+        let addresses = keys
+            .into_iter()
+            .map(|record| record.into_address_store())
+            .collect::<Result<Vec<_>>>()?;
+
+        let restored = addresses.iter().map(|(a, _)| a.clone()).collect();
+        let spent = outputs.iter().filter(|o| o.spent);
+        let update = WalletSyncUpdate {
+            spent: spent.map(|o| o.out_point.clone()).collect(),
+            outputs: outputs
                 .into_iter()
-                .filter(|(_, _, a, _)| addresses.contains(a))
-                .collect();
+                .map(|o| (o.out_point, o.asset, o.address))
+                .collect(),
+            addresses,
+        };
+        self.apply_sync_update(update).await?;
+        Ok(restored)
+    }
+
+    /// Backup records of the key-pairs of the wallet addresses
+    fn backup_key_records(&self) -> Result<Vec<backup::BackupKeyRecord>> {
+        self.get_known_addresses()?
+            .into_iter()
+            .map(|address| {
+                let store = self.get_address_store(&address)?;
+                backup::BackupKeyRecord::from_address_store(address, &store)
+            })
+            .collect()
+    }
+
+    /// Import the key-pairs of an encrypted backup, returning their addresses
+    ///
+    /// All records are checked before any key-pair is saved.
+    ///
+    /// ### Arguments
+    ///
+    /// * `backup`     - Backup to import.
+    /// * `passphrase` - Passphrase protecting the backup.
+    pub async fn import_key_backup(
+        &self,
+        backup: &WalletBackup,
+        passphrase: &str,
+    ) -> Result<Vec<String>> {
+        let stores = backup
+            .open(passphrase)?
+            .into_iter()
+            .map(|record| record.into_address_store())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut addresses = Vec::with_capacity(stores.len());
+        for (address, store) in stores {
+            self.save_address_to_wallet(address.clone(), store).await?;
+            addresses.push(address);
+        }
+        Ok(addresses)
+    }
+
+    /// Summary of the addresses and outputs of the wallet, for a sync peer
+    pub fn sync_summary(&self) -> Result<WalletSyncSummary> {
+        let fund_store = self.get_fund_store()?;
+        Ok(WalletSyncSummary {
+            addresses: get_known_key_address(&self.db.read().unwrap())?,
+            unspent: fund_store.transactions().keys().cloned().collect(),
+            spent: fund_store.spent_transactions().keys().cloned().collect(),
+        })
+    }
+
+    /// Changes of the wallet a sync peer lacks
+    ///
+    /// Key-pairs whose secret key is not held are not sent.
+    ///
+    /// ### Arguments
+    ///
+    /// * `summary` - Summary of the peer wallet.
+    pub fn sync_update_for(&self, summary: &WalletSyncSummary) -> Result<WalletSyncUpdate> {
+        let db = self.db.read().unwrap();
+        let mut addresses = Vec::new();
+        for address in get_known_key_address(&db)? {
+            if summary.addresses.contains(&address) {
+                continue;
+            }
+            let hot_keys = self.hot_keys.as_deref();
+            if let Some(store) = load_address_store(&db, &address, &self.encryption_key, hot_keys)?
+            {
+                addresses.push((address, store));
+            }
+        }
+
+        let known =
+            |out_p: &OutPoint| summary.unspent.contains(out_p) || summary.spent.contains(out_p);
+        let fund_store = get_fund_store(&db)?;
+        let outputs = fund_store
+            .transactions()
+            .iter()
+            .chain(fund_store.spent_transactions())
+            .filter(|(out_p, _)| !known(out_p))
+            .map(|(out_p, asset)| {
+                let address = get_transaction_store(&db, out_p)?.key_address;
+                Ok((out_p.clone(), asset.clone(), address))
+            })
+            .collect::<Result<_>>()?;
+        let spent = fund_store
+            .spent_transactions()
+            .keys()
+            .filter(|out_p| !summary.spent.contains(out_p))
+            .cloned()
+            .collect();
+
+        Ok(WalletSyncUpdate {
+            addresses,
+            outputs,
+            spent,
+        })
+    }
+
+    /// Apply the changes sent by a sync peer
+    ///
+    /// Known addresses and outputs are left unchanged, and spent outputs are
+    /// never made spendable again.
+    ///
+    /// ### Arguments
+    ///
+    /// * `update` - Changes sent by the peer.
+    pub async fn apply_sync_update(&self, update: WalletSyncUpdate) -> Result<()> {
+        let db = self.db.clone();
+        let encryption_key = self.encryption_key.clone();
+        let hot_keys = self.hot_keys.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            let known_addresses = get_known_key_address(&db)?;
+            let fund_store = get_fund_store(&db)?;
+            let mut changes = WalletBatch::default();
+
+            for (address, store) in update.addresses {
+                if !known_addresses.contains(&address) {
+                    changes.save_address(address, store);
+                }
+            }
+            for (out_p, asset, key_address) in update.outputs {
+                if fund_store.transactions().contains_key(&out_p)
+                    || fund_store.spent_transactions().contains_key(&out_p)
+                {
+                    continue;
+                }
+                changes.save_payment(out_p, asset, key_address);
+            }
+            for out_p in update.spent {
+                if !fund_store.spent_transactions().contains_key(&out_p) {
+                    changes.spend(out_p);
+                }
+            }
+
+            let hot_keys = hot_keys.as_deref();
+            batch::write_wallet_batch(&db, &mut batch, changes, &encryption_key, hot_keys)?;
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Saves an AddressStore to wallet in a directly encrypted state
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to save to wallet
+    /// * `keys`    - Address-related keys in an encrypted state
+    pub async fn save_encrypted_address_to_wallet(
+        &self,
+        address: String,
+        keys: Vec<u8>,
+    ) -> Result<()> {
+        if self.hot_keys.is_some() {
+            let keys = deserialize(&decrypt_store(keys, &self.encryption_key)?)?;
+            return self.save_address_to_wallet(address, keys).await;
+        }
+
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+
+            let mut address_list = get_known_key_address(&db)?;
+            address_list.insert(address.clone());
+
+            labels::record_address_created(&db, &mut batch, &address, get_timestamp_now())?;
+            batch.put_cf(DB_COL_DEFAULT, address, keys);
+            set_known_key_address(&mut batch, address_list);
+
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Saves an address and the associated transaction with it to the wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p`        - Transaction hash/index
+    /// * `key_address`  - Transaction Address
+    pub async fn save_transaction_to_wallet(
+        &self,
+        out_p: OutPoint,
+        key_address: String,
+    ) -> Result<()> {
+        let mut changes = WalletBatch::default();
+        changes.save_transaction(out_p, key_address);
+        self.batch_update(changes).await
+    }
+
+    /// Saves a received payment to the local wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments OutPoint, amount and receiver key address
+    /// * `b_num`    - Current block number
+    pub async fn save_usable_payments_to_wallet(
+        &mut self,
+        payments: Vec<(OutPoint, Asset, String, u64)>,
+        current_b_num: u64,
+    ) -> Result<Vec<(OutPoint, Asset, String, u64)>> {
+        self.save_payments_with_history(payments, current_b_num, TxStatus::Confirmed)
+            .await
+    }
+
+    /// Saves a received payment to the local wallet, recording it in the history
+    /// with the given status if its transaction is not recorded yet
+    ///
+    /// ### Arguments
+    ///
+    /// * `payments` - Payments OutPoint, amount and receiver key address
+    /// * `b_num`    - Current block number
+    /// * `status`   - Status of the payments recorded
+    async fn save_payments_with_history(
+        &mut self,
+        payments: Vec<(OutPoint, Asset, String, u64)>,
+        current_b_num: u64,
+        status: TxStatus,
+    ) -> Result<Vec<(OutPoint, Asset, String, u64)>> {
+        let db = self.db.clone();
+        let locked_coinbase = self.get_locked_coinbase().await.unwrap_or_default();
+        let timestamp = get_timestamp_now();
+        let (result, locked_db) = spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            let mut fund_store = FundStoreUpdate::new(&db)?;
+            let mut locked_coinbase = locked_coinbase.clone();
+            let addresses = get_known_key_address(&db)?;
+            let mut watch_only = watch_only::get_watch_only_store(&db)?;
+
+            let (usable_payments, watched_payments): (Vec<_>, Vec<_>) = payments
+                .into_iter()
+                .filter(|(_, _, a, _)| addresses.contains(a) || watch_only.contains(a))
+                .partition(|(_, _, a, _)| addresses.contains(a));
 
             for (out_p, asset, key_address, locktime) in &usable_payments {
                 let key_address = key_address.clone();
                 let store = TransactionStore { key_address };
                 let asset_to_store = asset.clone().with_fixed_hash(out_p);
-                fund_store.store_tx(out_p.clone(), asset_to_store);
-                save_transaction_to_wallet(&mut batch, out_p, &store);
-                if *locktime > current_b_num {
-                    locked_coinbase.insert(out_p.t_hash.clone(), *locktime);
+                fund_store.store_tx(out_p.clone(), asset_to_store)?;
+                save_transaction_to_wallet(&mut batch, out_p, &store);
+                if !is_locktime_expired(*locktime, current_b_num, timestamp) {
+                    locked_coinbase.insert(out_p.t_hash.clone(), *locktime);
+                }
+            }
+
+            let mut received: BTreeMap<&String, AssetValues> = BTreeMap::new();
+            for (out_p, asset, _, _) in &usable_payments {
+                received.entry(&out_p.t_hash).or_default().update_add(asset);
+            }
+            let mut watched: BTreeMap<&String, AssetValues> = BTreeMap::new();
+            let mut watch_only_changed = false;
+            for (out_p, asset, address, _) in &watched_payments {
+                let asset_to_store = asset.clone().with_fixed_hash(out_p);
+                if watch_only.store_output(out_p.clone(), asset_to_store, address.clone()) {
+                    watch_only_changed = true;
+                    watched.entry(&out_p.t_hash).or_default().update_add(asset);
                 }
             }
+            if watch_only_changed {
+                watch_only::set_watch_only_store(&mut batch, &watch_only);
+            }
 
-            set_fund_store(&mut batch, fund_store);
+            let entry = |(tx_hash, amount): (&String, AssetValues)| {
+                TxHistoryEntry::new(tx_hash.clone(), TxDirection::Incoming, amount, None, status)
+            };
+            let entries = received.into_iter().map(entry);
+            let entries = entries
+                .chain(watched.into_iter().map(|e| entry(e).watched()))
+                .collect();
+            history::append_tx_history(&db, &mut batch, entries, current_b_num, timestamp)?;
+
+            fund_store.write(&mut batch);
 
             let batch = batch.done();
-            db.write(batch).unwrap();
-            (usable_payments, locked_coinbase)
+            db.write(batch)?;
+            Ok::<_, WalletDbError>((usable_payments, locked_coinbase))
         })
         .await?;
-        let locked_coinbase = self.store_locked_coinbase(Some(locked_db)).await;
+        let locked_coinbase = self.store_locked_coinbase(Some(locked_db)).await?;
         self.set_locked_coinbase(locked_coinbase).await;
         Ok(result)
     }
 
+    /// Drop the watch-only outputs spent since the last update of the running total
+    ///
+    /// ### Arguments
+    ///
+    /// * `fetched` - Addresses whose unspent outputs were fetched.
+    /// * `unspent` - Unspent outputs of the fetched addresses.
+    pub async fn refresh_watch_only_outputs(
+        &self,
+        fetched: UtxoFetchType,
+        unspent: BTreeSet<OutPoint>,
+    ) -> Result<usize> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut watch_only = watch_only::get_watch_only_store(&db)?;
+            let dropped = watch_only.retain_unspent(&fetched, &unspent);
+            if dropped > 0 {
+                let mut batch = db.batch_writer();
+                watch_only::set_watch_only_store(&mut batch, &watch_only);
+                let batch = batch.done();
+                db.write(batch)?;
+            }
+            Ok(dropped)
+        })
+        .await
+    }
+
     /// Get `Vec<TxIn>` and `Vec<TxOut>` values for a transaction
     ///
     /// ### Arguments
@@ -544,16 +1659,15 @@ impl WalletDb {
     ) -> Result<(Vec<TxIn>, Vec<TxOut>)> {
         let (tx_cons, asset, tx_used) = self
             .fetch_inputs_for_payment_from_supplied_input_addrs(input_addresses)
-            .await
-            .unwrap();
+            .await?;
 
         let excess_addr = match excess_address {
             Some(excess_addr) => excess_addr,
-            None => self.generate_payment_address().await.0,
+            None => self.change_address(&tx_used).await?,
         };
 
         let tx_outs: Vec<TxOut> = vec![TxOut::new_asset(excess_addr, asset, None)];
-        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await;
+        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await?;
 
         Ok((tx_ins, tx_outs))
     }
@@ -577,16 +1691,51 @@ impl WalletDb {
         if let Some(excess) = total_amount.get_excess(&asset_required) {
             let excess_address = match excess_address {
                 Some(address) => address,
-                None => self.generate_payment_address().await.0,
+                None => self.change_address(&tx_used).await?,
             };
             tx_outs.push(TxOut::new_asset(excess_address, excess, None));
         }
 
-        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await;
+        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await?;
+
+        Ok((tx_ins, tx_outs))
+    }
+
+    /// Get `Vec<TxIn>` and `Vec<TxOut>` values for a transaction that consolidates
+    /// the given outputs into a single output paid to a new address of the wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `txs` - Outpoints and Assets of the outputs to consolidate
+    pub async fn fetch_tx_ins_and_tx_outs_consolidate(
+        &mut self,
+        txs: Vec<(OutPoint, Asset)>,
+    ) -> Result<(Vec<TxIn>, Vec<TxOut>)> {
+        let (tx_cons, total_amount, tx_used) =
+            self.fetch_inputs_for_payment_from_supplied_txs(txs).await?;
+
+        let address = self.change_address(&tx_used).await?;
+        let tx_outs = vec![TxOut::new_asset(address, total_amount, None)];
+        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await?;
 
         Ok((tx_ins, tx_outs))
     }
 
+    /// Address the change of a payment is paid back to
+    ///
+    /// A new address unless the change address reuse is set, in which case the
+    /// address of the first input spent.
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_used` - Outputs spent by the payment, with their address
+    async fn change_address(&mut self, tx_used: &[(OutPoint, String)]) -> Result<String> {
+        match tx_used.first() {
+            Some((_, address)) if self.reuse_change_address => Ok(address.clone()),
+            _ => Ok(self.generate_payment_address().await?.0),
+        }
+    }
+
     /// Get `Vec<TxIn>` and `Vec<TxOut>` values for a transaction
     ///
     /// ### Arguments
@@ -597,39 +1746,141 @@ impl WalletDb {
         &mut self,
         txs: Vec<(OutPoint, Asset)>,
     ) -> Result<(Vec<TxIn>, Asset)> {
-        let (tx_cons, total_amount, tx_used) = self
-            .fetch_inputs_for_payment_from_supplied_txs(txs)
-            .await
-            .unwrap();
+        let (tx_cons, total_amount, tx_used) =
+            self.fetch_inputs_for_payment_from_supplied_txs(txs).await?;
 
         tracing::trace!("Total amount collected by store {total_amount:?}");
 
-        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await;
+        let tx_ins = self.consume_inputs_for_payment(tx_cons, tx_used).await?;
 
         Ok((tx_ins, total_amount))
     }
 
-    /// Store payment transaction
+    /// Store payment transaction, returning the payments saved to the wallet
     ///
     /// ### Arguments
     ///
     /// * `transaction` - Transaction to be received and saved to wallet
     /// * `b_num` - Block number
-    pub async fn store_payment_transaction(&mut self, transaction: Transaction, b_num: u64) {
+    pub async fn store_payment_transaction(
+        &mut self,
+        transaction: Transaction,
+        b_num: u64,
+    ) -> Result<Vec<(OutPoint, Asset, String, u64)>> {
         let hash = construct_tx_hash(&transaction);
         let payments = get_payments_for_wallet(Some((&hash, &transaction)).into_iter());
+        self.record_outgoing_payment(hash, transaction, b_num)
+            .await?;
         let our_payments = self
-            .save_usable_payments_to_wallet(payments, b_num)
-            .await
-            .unwrap();
+            .save_payments_with_history(payments, b_num, TxStatus::Pending)
+            .await?;
         tracing::debug!("store_payment_transactions: {:?}", our_payments);
+        Ok(our_payments)
+    }
+
+    /// Record a transaction in the history as an outgoing payment if it spends
+    /// outputs of the wallet, with the assets it pays to other wallets, lock the
+    /// outputs it spends until it is added to a block, and link the new addresses
+    /// of the wallet it pays the change to with the transaction
+    ///
+    /// ### Arguments
+    ///
+    /// * `hash`        - Hash of the transaction
+    /// * `transaction` - Transaction sent
+    /// * `b_num`       - Block number
+    async fn record_outgoing_payment(
+        &self,
+        hash: String,
+        transaction: Transaction,
+        b_num: u64,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let inputs: Vec<_> = transaction
+                .inputs
+                .iter()
+                .filter_map(|tx_in| tx_in.previous_out.as_ref())
+                .collect();
+            let spent: BTreeSet<_> = inputs
+                .iter()
+                .filter_map(|out_p| get_transaction_store(&db, out_p).ok())
+                .map(|store| store.key_address)
+                .collect();
+            if spent.is_empty() {
+                return Ok(());
+            }
+
+            let addresses = get_known_key_address(&db)?;
+            let mut batch = db.batch_writer();
+            fund_store::lock_outgoing(&db, &mut batch, &hash, inputs)?;
+            let mut amount = AssetValues::default();
+            let mut counterparty = None;
+            for tx_out in &transaction.outputs {
+                let address = match tx_out.script_public_key.as_ref() {
+                    Some(address) => address,
+                    None => continue,
+                };
+                if !addresses.contains(address) {
+                    amount.update_add(&tx_out.value);
+                    counterparty = counterparty.or_else(|| Some(address.clone()));
+                } else if !spent.contains(address) {
+                    change::link_change_address(&db, &mut batch, address, &hash)?;
+                }
+            }
+
+            let entry = TxHistoryEntry::new(
+                hash,
+                TxDirection::Outgoing,
+                amount,
+                counterparty,
+                TxStatus::Pending,
+            );
+            let timestamp = get_timestamp_now();
+            history::append_tx_history(&db, &mut batch, vec![entry], b_num, timestamp)?;
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Set the status of the pending history entries of the given transactions,
+    /// releasing the outputs locked by those sent
+    ///
+    /// ### Arguments
+    ///
+    /// * `tx_hashes` - Hashes of the transactions
+    /// * `status`    - New status of their entries
+    pub async fn set_tx_history_status(
+        &self,
+        tx_hashes: Vec<String>,
+        status: TxStatus,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            let updated = history::set_tx_history_status(&db, &mut batch, &tx_hashes, status)?;
+            let released = fund_store::release_outgoing(&db, &mut batch, &tx_hashes)?;
+            if updated > 0 || released > 0 {
+                let batch = batch.done();
+                db.write(batch)?;
+            }
+            Ok(())
+        })
+        .await
     }
 
     /// Fetches valid TxIns based on the wallet's running total and available unspent
     /// transactions, and total value
     ///
+    /// The outputs are selected with the coin selection of the wallet, among
+    /// those not reserved for other payments, and are reserved in turn until
+    /// `consume_inputs_for_payment` commits or `release_reserved_inputs` rolls
+    /// back the reservation.
+    ///
     /// TODO: Replace errors here with Error enum types that the Result can return
-    /// TODO: Possibly sort addresses found ascending, so that smaller amounts are consumed
     ///
     /// ### Arguments
     ///
@@ -641,11 +1892,37 @@ impl WalletDb {
         let db = self.db.clone();
         let encryption_key = self.encryption_key.clone();
         let locked_coinbase = self.get_locked_coinbase().await;
-        task::spawn_blocking(move || {
-            let db = db.lock().unwrap();
-            fetch_inputs_for_payment_from_db(&db, asset_required, &encryption_key, &locked_coinbase)
+        let coin_selection = self.coin_selection;
+        let signer = self.signer.clone();
+        let hot_keys = self.hot_keys.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let now = chrono::Utc::now().timestamp();
+            let mut pending_spends = get_pending_spends(&db)?;
+            if pending_spends.prune_expired(now) > 0 {
+                warn!("Expired reservations of payment inputs released");
+            }
+
+            let inputs = fetch_inputs_for_payment_from_db(
+                &db,
+                asset_required,
+                &coin_selection,
+                &pending_spends,
+                &encryption_key,
+                &locked_coinbase,
+                signer.as_deref(),
+                hot_keys.as_deref(),
+            )?;
+
+            let reserved = inputs.2.iter().map(|(out_p, _)| out_p.clone());
+            pending_spends.reserve(reserved, now + RESERVATION_TTL);
+            let mut batch = db.batch_writer();
+            set_pending_spends(&mut batch, &pending_spends);
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(inputs)
         })
-        .await?
+        .await
     }
 
     /// Fetches valid TxIns based on the supplied transactions
@@ -662,16 +1939,20 @@ impl WalletDb {
         let db = self.db.clone();
         let locked_coinbase = self.get_locked_coinbase().await;
         let encryption_key = self.encryption_key.clone();
-        task::spawn_blocking(move || {
-            let db = db.lock().unwrap();
+        let signer = self.signer.clone();
+        let hot_keys = self.hot_keys.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let db = db.read().unwrap();
             fetch_inputs_for_payment_from_supplied_input_addrs_db(
                 &db,
                 addresses,
                 &encryption_key,
                 &locked_coinbase,
+                signer.as_deref(),
+                hot_keys.as_deref(),
             )
         })
-        .await?
+        .await
     }
 
     /// Fetches valid TxIns based on the supplied transactions
@@ -687,15 +1968,26 @@ impl WalletDb {
     ) -> Result<(Vec<TxConstructor>, Asset, Vec<(OutPoint, String)>)> {
         let db = self.db.clone();
         let encryption_key = self.encryption_key.clone();
-        task::spawn_blocking(move || {
-            let db = db.lock().unwrap();
-            fetch_inputs_from_supplied_txs_for_payment_from_db(&db, txs, &encryption_key)
+        let signer = self.signer.clone();
+        let hot_keys = self.hot_keys.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let db = db.read().unwrap();
+            fetch_inputs_from_supplied_txs_for_payment_from_db(
+                &db,
+                txs,
+                &encryption_key,
+                signer.as_deref(),
+                hot_keys.as_deref(),
+            )
         })
-        .await?
+        .await
     }
 
     /// Consume given used transaction and produce TxIns
     ///
+    /// The signed inputs are recorded in the audit log, with the signing trigger
+    /// of the current task, and their reservation is committed.
+    ///
     /// ### Arguments
     ///
     /// * `tx_cons`         - TxIn TxConstructors
@@ -704,24 +1996,84 @@ impl WalletDb {
         &mut self,
         tx_cons: Vec<TxConstructor>,
         tx_used: Vec<(OutPoint, String)>,
-    ) -> Vec<TxIn> {
+    ) -> Result<Vec<TxIn>> {
         let db = self.db.clone();
-        task::spawn_blocking(move || {
-            let mut db = db.lock().unwrap();
+        let trigger = audit::current_signing_trigger();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
             let mut batch = db.batch_writer();
-            let mut fund_store = get_fund_store(&db);
+            let mut fund_store = FundStoreUpdate::new(&db)?;
+            let mut pending_spends = get_pending_spends(&db)?;
 
-            for (out_p, _) in tx_used {
-                fund_store.spend_tx(&out_p);
+            let timestamp = chrono::Utc::now().timestamp();
+            audit::append_signing_audit(&db, &mut batch, &tx_used, &trigger, timestamp)?;
+            for (out_p, _) in &tx_used {
+                fund_store.spend_tx(out_p)?;
+            }
+            if pending_spends.release(tx_used.iter().map(|(out_p, _)| out_p)) > 0 {
+                set_pending_spends(&mut batch, &pending_spends);
             }
-            set_fund_store(&mut batch, fund_store);
+            fund_store.write(&mut batch);
             let batch = batch.done();
-            db.write(batch).unwrap();
+            db.write(batch)?;
 
-            construct_payment_tx_ins(tx_cons)
+            Ok(construct_payment_tx_ins(tx_cons))
+        })
+        .await
+    }
+
+    /// Roll back the reservation of inputs fetched for a payment abandoned before it is sent
+    ///
+    /// ### Arguments
+    ///
+    /// * `inputs` - Outputs fetched for the payment, selectable again
+    pub async fn release_reserved_inputs(&self, inputs: Vec<OutPoint>) -> Result<()> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut pending_spends = get_pending_spends(&db)?;
+            if pending_spends.release(&inputs) > 0 {
+                let mut batch = db.batch_writer();
+                set_pending_spends(&mut batch, &pending_spends);
+                let batch = batch.done();
+                db.write(batch)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Revert a payment that will never be added to a block
+    ///
+    /// ### Arguments
+    ///
+    /// * `inputs` - Outputs spent by the payment, spendable again
+    /// * `change` - Outputs paid back to the wallet by the payment, removed
+    pub async fn revert_payment(
+        &mut self,
+        inputs: Vec<OutPoint>,
+        change: Vec<OutPoint>,
+    ) -> Result<()> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            let mut fund_store = FundStoreUpdate::new(&db)?;
+
+            for out_p in &inputs {
+                fund_store.unspend_tx(out_p)?;
+            }
+            for out_p in &change {
+                fund_store.spend_tx(out_p)?;
+                fund_store.remove_spent_tx(out_p);
+                delete_transaction_store(&mut batch, out_p);
+            }
+            fund_store.write(&mut batch);
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
         })
         .await
-        .unwrap()
     }
 
     /// Destroy the used transactions with keys purging them from the wallet
@@ -729,58 +2081,113 @@ impl WalletDb {
     pub async fn destroy_spent_transactions_and_keys(
         &mut self,
         addresses: Option<BTreeSet<String>>,
-    ) -> (BTreeSet<String>, BTreeMap<OutPoint, Asset>) {
+    ) -> Result<(BTreeSet<String>, BTreeMap<OutPoint, Asset>)> {
         let db = self.db.clone();
-        task::spawn_blocking(move || {
-            let mut db = db.lock().unwrap();
-            destroy_spent_transactions_and_keys(&mut db, addresses)
+        let encryption_key = self.encryption_key.clone();
+        let hot_keys = self.hot_keys.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let destroyed = destroy_spent_transactions_and_keys(&mut db, addresses)?;
+            if let Some(hot_keys) = hot_keys {
+                let public_keys = get_known_key_address(&db)?
+                    .iter()
+                    .map(|address| {
+                        let public = get_public_address_store(&db, address, &encryption_key)?;
+                        Ok(hex::encode(public.public_key.as_ref()))
+                    })
+                    .collect::<Result<_>>()?;
+                hot_keys.retain(&public_keys);
+            }
+            Ok(destroyed)
         })
         .await
-        .unwrap()
     }
 
     /// Get a the serialized value stored at given key
-    pub async fn get_db_value(&self, key: &'static str) -> Option<Vec<u8>> {
+    pub async fn get_db_value(&self, key: &'static str) -> Result<Option<Vec<u8>>> {
         let db = self.db.clone();
-        task::spawn_blocking(move || db.lock().unwrap().get_cf(DB_COL_DEFAULT, key).unwrap())
-            .await
-            .unwrap()
+        spawn_db(DbPool::Wallet, move || {
+            Ok(db.read().unwrap().get_cf(DB_COL_DEFAULT, key)?)
+        })
+        .await
+    }
+
+    /// Compact the wallet database
+    pub async fn compact_db(&self) {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || db.read().unwrap().compact()).await
     }
 
     /// Set a the serialized value stored at given key
-    pub async fn set_db_value(&self, key: &'static str, value: Vec<u8>) {
+    pub async fn set_db_value(&self, key: &'static str, value: Vec<u8>) -> Result<()> {
         let db = self.db.clone();
-        task::spawn_blocking(move || {
-            db.lock()
-                .unwrap()
-                .put_cf(DB_COL_DEFAULT, key, &value)
-                .unwrap()
+        spawn_db(DbPool::Wallet, move || {
+            Ok(db.write().unwrap().put_cf(DB_COL_DEFAULT, key, &value)?)
         })
         .await
-        .unwrap()
     }
 
     /// Delete value stored at given key
-    pub async fn delete_db_value(&self, key: &'static str) {
+    pub async fn delete_db_value(&self, key: &'static str) -> Result<()> {
         let db = self.db.clone();
-        task::spawn_blocking(move || db.lock().unwrap().delete_cf(DB_COL_DEFAULT, key).unwrap())
-            .await
-            .unwrap()
+        spawn_db(DbPool::Wallet, move || {
+            Ok(db.write().unwrap().delete_cf(DB_COL_DEFAULT, key)?)
+        })
+        .await
     }
 
-    /// Get the wallet fund store
-    pub fn get_fund_store(&self) -> FundStore {
-        get_fund_store(&self.db.lock().unwrap())
+    /// Replace the outputs of the wallet with the ones of a fund store
+    ///
+    /// ### Arguments
+    ///
+    /// * `fund_store` - Outputs to store.
+    pub async fn set_fund_store(&self, fund_store: FundStore) -> Result<()> {
+        let db = self.db.clone();
+        spawn_db(DbPool::Wallet, move || {
+            let mut db = db.write().unwrap();
+            let mut batch = db.batch_writer();
+            fund_store::replace_fund_store(&db, &mut batch, fund_store)?;
+            let batch = batch.done();
+            db.write(batch)?;
+            Ok(())
+        })
+        .await
     }
 
-    /// Get the wallet fund store with errors
-    pub fn get_fund_store_err(&self) -> Result<FundStore> {
-        get_fund_store_err(&self.db.lock().unwrap())
+    /// Get the wallet fund store
+    pub fn get_fund_store(&self) -> Result<FundStore> {
+        get_fund_store(&self.db.read().unwrap())
+    }
+
+    /// Get the balances of the wallet funds, by the state of their transactions
+    pub fn get_fund_balances(&self) -> Result<FundBalances> {
+        let db = self.db.read().unwrap();
+        let mut pending_spends = get_pending_spends(&db)?;
+        pending_spends.prune_expired(get_timestamp_now());
+        Ok(FundBalances::new(
+            &get_fund_store(&db)?,
+            &history::get_pending_tx_hashes(&db)?,
+            &pending_spends,
+            &fund_store::get_locked_outgoing(&db)?,
+        ))
+    }
+
+    /// Get the unspent outputs of transactions added to a block, not reserved for a payment
+    pub fn get_confirmed_outputs(&self) -> Result<BTreeMap<OutPoint, Asset>> {
+        let db = self.db.read().unwrap();
+        let mut pending_spends = get_pending_spends(&db)?;
+        pending_spends.prune_expired(get_timestamp_now());
+        let pending_txs = history::get_pending_tx_hashes(&db)?;
+        let mut outputs = get_fund_store(&db)?.into_transactions();
+        outputs.retain(|out_p, _| {
+            !pending_spends.is_reserved(out_p) && !pending_txs.contains(&out_p.t_hash)
+        });
+        Ok(outputs)
     }
 
     /// Get the wallet address
-    pub fn get_transaction_store(&self, out_p: &OutPoint) -> TransactionStore {
-        get_transaction_store(&self.db.lock().unwrap(), out_p)
+    pub fn get_transaction_store(&self, out_p: &OutPoint) -> Result<TransactionStore> {
+        get_transaction_store(&self.db.read().unwrap(), out_p)
     }
 
     /// Gets the address store based on a provided key
@@ -788,8 +2195,32 @@ impl WalletDb {
     /// ### Arguments
     ///
     ///  * `key_addr` - Key to get the address store for
-    pub fn get_address_store(&self, key_addr: &str) -> AddressStore {
-        get_address_store(&self.db.lock().unwrap(), key_addr, &self.encryption_key)
+    pub fn get_address_store(&self, key_addr: &str) -> Result<AddressStore> {
+        let db = self.db.read().unwrap();
+        let hot_keys = self.hot_keys.as_deref();
+        load_address_store(&db, key_addr, &self.encryption_key, hot_keys)?.ok_or(
+            WalletDbError::HotWalletError("Secret key not held for address"),
+        )
+    }
+
+    /// Gets the public half of the address store based on a provided key
+    ///
+    /// ### Arguments
+    ///
+    ///  * `key_addr` - Key to get the address store for
+    pub fn get_public_address_store(&self, key_addr: &str) -> Result<PublicAddressStore> {
+        let db = self.db.read().unwrap();
+        match self.hot_keys {
+            Some(_) => get_public_address_store(&db, key_addr, &self.encryption_key),
+            None => Ok(get_address_store(&db, key_addr, &self.encryption_key)?
+                .split()
+                .0),
+        }
+    }
+
+    /// Whether the wallet holds its secret keys in memory only
+    pub fn is_hot_wallet(&self) -> bool {
+        self.hot_keys.is_some()
     }
 
     /// Gets the address store based on a provided key, but returns
@@ -798,26 +2229,86 @@ impl WalletDb {
     /// ### Arguments
     ///
     ///  * `key_addr` - Key to get the address store for
-    pub fn get_address_store_encrypted(&self, key_addr: &str) -> Vec<u8> {
-        get_address_store_encrypted(&self.db.lock().unwrap(), key_addr)
+    pub fn get_address_store_encrypted(&self, key_addr: &str) -> Result<Vec<u8>> {
+        if self.hot_keys.is_some() {
+            let store = serialize(&self.get_address_store(key_addr)?)?;
+            return Ok(encrypt_store(store, &self.encryption_key));
+        }
+        get_address_store_encrypted(&self.db.read().unwrap(), key_addr)
     }
 
     /// Get the wallet addresses
-    pub fn get_known_addresses(&self) -> Vec<String> {
-        get_known_key_address(&self.db.lock().unwrap())
-            .into_iter()
-            .collect()
+    pub fn get_known_addresses(&self) -> Result<Vec<String>> {
+        let addresses = get_known_key_address(&self.db.read().unwrap())?;
+        Ok(addresses.into_iter().collect())
+    }
+
+    /// Get the audit log of the inputs signed by the wallet, oldest first
+    pub fn get_signing_audit_log(&self) -> Result<Vec<SigningAuditEntry>> {
+        audit::get_signing_audit_log(&self.db.read().unwrap())
+    }
+
+    /// Get a page of the payments sent and received, newest first
+    ///
+    /// ### Arguments
+    ///
+    /// * `page`      - Index of the page, from 0
+    /// * `page_size` - Number of payments of a page
+    pub fn get_tx_history(&self, page: usize, page_size: usize) -> Result<TxHistoryPage> {
+        history::get_tx_history(&self.db.read().unwrap(), page, page_size)
+    }
+
+    /// Get the change addresses, with the hash of the transaction each was generated for
+    pub fn get_change_addresses(&self) -> Result<BTreeMap<String, String>> {
+        change::get_change_addresses(&self.db.read().unwrap())
+    }
+
+    /// Get the metadata of the wallet addresses that have some
+    pub fn get_address_metadata(&self) -> Result<BTreeMap<String, AddressMetadata>> {
+        labels::get_all_address_metadata(&self.db.read().unwrap())
+    }
+
+    /// Get the wallet addresses given a label
+    ///
+    /// ### Arguments
+    ///
+    /// * `label` - Label of the addresses
+    pub fn get_addresses_by_label(&self, label: &str) -> Result<Vec<String>> {
+        let addresses = self.get_address_metadata()?.into_iter();
+        Ok(addresses
+            .filter(|(_, metadata)| metadata.label.as_deref() == Some(label))
+            .map(|(address, _)| address)
+            .collect())
+    }
+
+    /// Get a multisig address of the wallet, with the public keys of its signers
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Multisig address
+    pub fn get_multisig_address(&self, address: &str) -> Result<Option<MultisigAddress>> {
+        multisig::get_multisig_address(&self.db.read().unwrap(), address)
+    }
+
+    /// Get the multisig addresses of the wallet
+    pub fn get_multisig_addresses(&self) -> Result<BTreeMap<String, MultisigAddress>> {
+        multisig::get_multisig_addresses(&self.db.read().unwrap())
+    }
+
+    /// Get the watch-only addresses and their unspent outputs
+    pub fn get_watch_only_store(&self) -> Result<WatchOnlyStore> {
+        watch_only::get_watch_only_store(&self.db.read().unwrap())
     }
 
     /// Get the wallet transaction address
-    pub fn get_transaction_address(&self, out_p: &OutPoint) -> String {
-        self.get_transaction_store(out_p).key_address
+    pub fn get_transaction_address(&self, out_p: &OutPoint) -> Result<String> {
+        Ok(self.get_transaction_store(out_p)?.key_address)
     }
 
     /// Load locked coinbase from wallet
     pub async fn load_locked_coinbase(&mut self) -> Result<()> {
         let mut cb = self.locked_coinbase.lock().await;
-        let serialized_value = self.get_db_value(LOCKED_COINBASE_KEY).await;
+        let serialized_value = self.get_db_value(LOCKED_COINBASE_KEY).await?;
         if let Some(serialized_value) = serialized_value {
             let deserialize_old = deserialize::<Vec<(String, u64)>>(&serialized_value);
             let deserialize_new = deserialize::<BTreeMap<String, u64>>(&serialized_value);
@@ -834,7 +2325,7 @@ impl WalletDb {
         }
         let locked_coinbase = self
             .get_db_value(LOCKED_COINBASE_KEY)
-            .await
+            .await?
             .map(|v| deserialize(&v))
             .transpose()?;
         *cb = locked_coinbase;
@@ -845,60 +2336,44 @@ impl WalletDb {
     pub async fn store_locked_coinbase(
         &mut self,
         locked_coinbase: LockedCoinbase,
-    ) -> LockedCoinbase {
+    ) -> Result<LockedCoinbase> {
         if let Some(cb) = &locked_coinbase {
             let ser_cb = serialize(cb).unwrap();
-            self.set_db_value(LOCKED_COINBASE_KEY, ser_cb).await;
+            self.set_db_value(LOCKED_COINBASE_KEY, ser_cb).await?;
         } else {
-            self.delete_db_value(LOCKED_COINBASE_KEY).await;
+            self.delete_db_value(LOCKED_COINBASE_KEY).await?;
         }
-        locked_coinbase
+        Ok(locked_coinbase)
     }
 
     /// Filter locked coinbase after receiving new block to mine
-    pub async fn filter_locked_coinbase(&mut self, b_num: u64) {
+    pub async fn filter_locked_coinbase(&mut self, b_num: u64) -> Result<()> {
         if b_num <= self.last_locked_coinbase_filter_b_num.unwrap_or_default() {
-            return; // We're not ready to filter again
+            return Ok(()); // We're not ready to filter again
         }
-        // Remove all fields where current_block_num >= locktime
+        // Remove all fields whose locktime expired
         self.last_locked_coinbase_filter_b_num = Some(b_num);
+        let timestamp = get_timestamp_now();
         let mut locked_coinbase = self.get_locked_coinbase().await;
         if let Some(l_coinbase) = locked_coinbase.as_mut() {
-            l_coinbase.retain(|_, locktime| b_num < *locktime)
+            l_coinbase.retain(|_, locktime| !is_locktime_expired(*locktime, b_num, timestamp))
         };
-        let value = self.store_locked_coinbase(locked_coinbase).await;
+        let value = self.store_locked_coinbase(locked_coinbase).await?;
         self.set_locked_coinbase(value).await;
+        Ok(())
     }
 }
 
 /// Get the wallet fund store
-pub fn get_fund_store(db: &SimpleDb) -> FundStore {
-    match get_fund_store_err(db) {
-        Ok(v) => v,
-        Err(e) => panic!("Failed to access the wallet database with error: {:?}", e),
-    }
-}
-
-/// Get the wallet fund store
-pub fn get_fund_store_err(db: &SimpleDb) -> Result<FundStore> {
-    match db.get_cf(DB_COL_DEFAULT, FUND_KEY) {
-        Ok(Some(list)) => Ok(deserialize(&list).unwrap()),
-        Ok(None) => Ok(FundStore::default()),
-        Err(e) => Err(e.into()),
-    }
-}
-
-/// Set the wallet fund store
-pub fn set_fund_store(db: &mut SimpleDbWriteBatch, fund_store: FundStore) {
-    db.put_cf(DB_COL_DEFAULT, FUND_KEY, &serialize(&fund_store).unwrap())
+pub fn get_fund_store(db: &SimpleDb) -> Result<FundStore> {
+    fund_store::load_fund_store(db)
 }
 
 /// Get the wallet known address
-pub fn get_known_key_address(db: &SimpleDb) -> BTreeSet<String> {
-    match db.get_cf(DB_COL_DEFAULT, KNOWN_ADDRESS_KEY) {
-        Ok(Some(list)) => deserialize(&list).unwrap(),
-        Ok(None) => Default::default(),
-        Err(e) => panic!("Error accessing wallet: {:?}", e),
+pub fn get_known_key_address(db: &SimpleDb) -> Result<BTreeSet<String>> {
+    match db.get_cf(DB_COL_DEFAULT, KNOWN_ADDRESS_KEY)? {
+        Some(list) => Ok(deserialize(&list)?),
+        None => Ok(Default::default()),
     }
 }
 
@@ -907,17 +2382,14 @@ pub fn set_known_key_address(db: &mut SimpleDbWriteBatch, address_store: BTreeSe
     db.put_cf(
         DB_COL_DEFAULT,
         KNOWN_ADDRESS_KEY,
-        &serialize(&address_store).unwrap(),
+        serialize(&address_store).unwrap(),
     );
 }
 
 /// Gets the wallet AddressStore in an encrypted state for external storage
-pub fn get_address_store_encrypted(db: &SimpleDb, key_addr: &str) -> Vec<u8> {
-    match db.get_cf(DB_COL_DEFAULT, key_addr) {
-        Ok(Some(store)) => store,
-        Ok(None) => panic!("Key address not present in wallet: {}", key_addr),
-        Err(e) => panic!("Error accessing wallet: {:?}", e),
-    }
+pub fn get_address_store_encrypted(db: &SimpleDb, key_addr: &str) -> Result<Vec<u8>> {
+    db.get_cf(DB_COL_DEFAULT, key_addr)?
+        .ok_or_else(|| WalletDbError::MissingEntryError(key_addr.to_owned()))
 }
 
 /// Get the wallet AddressStore
@@ -925,14 +2397,47 @@ pub fn get_address_store(
     db: &SimpleDb,
     key_addr: &str,
     encryption_key: &secretbox::Key,
-) -> AddressStore {
-    match db.get_cf(DB_COL_DEFAULT, key_addr) {
-        Ok(Some(store)) => {
-            let decrypted = decrypt_store(store, encryption_key);
-            deserialize(&decrypted).unwrap()
+) -> Result<AddressStore> {
+    match db.get_cf(DB_COL_DEFAULT, key_addr)? {
+        Some(store) => {
+            let decrypted = decrypt_store(store, encryption_key)?;
+            Ok(deserialize(&decrypted)?)
+        }
+        None => Err(WalletDbError::MissingEntryError(key_addr.to_owned())),
+    }
+}
+
+/// Get the public half of the wallet AddressStore, as stored by hot wallets
+pub fn get_public_address_store(
+    db: &SimpleDb,
+    key_addr: &str,
+    encryption_key: &secretbox::Key,
+) -> Result<PublicAddressStore> {
+    match db.get_cf(DB_COL_DEFAULT, key_addr)? {
+        Some(store) => {
+            let decrypted = decrypt_store(store, encryption_key)?;
+            Ok(deserialize(&decrypted)?)
+        }
+        None => Err(WalletDbError::MissingEntryError(key_addr.to_owned())),
+    }
+}
+
+/// Get the wallet AddressStore, with the secret key held in memory for hot wallets
+///
+/// Returns None if the secret key of a hot wallet address is not held.
+pub fn load_address_store(
+    db: &SimpleDb,
+    key_addr: &str,
+    encryption_key: &secretbox::Key,
+    hot_keys: Option<&HotKeys>,
+) -> Result<Option<AddressStore>> {
+    match hot_keys {
+        Some(hot_keys) => {
+            let public = get_public_address_store(db, key_addr, encryption_key)?;
+            let secret_key = hot_keys.secret_key(&public.public_key);
+            Ok(secret_key.map(|secret_key| AddressStore::from_parts(public, secret_key)))
         }
-        Ok(None) => panic!("Key address not present in wallet: {}", key_addr),
-        Err(e) => panic!("Error accessing wallet: {:?}", e),
+        None => Ok(Some(get_address_store(db, key_addr, encryption_key)?)),
     }
 }
 
@@ -953,12 +2458,45 @@ pub fn save_address_store_to_wallet(
     db.put_cf(DB_COL_DEFAULT, key_addr, &input);
 }
 
+/// Save the public half of an AddressStore, as stored by hot wallets
+pub fn save_public_address_store(
+    db: &mut SimpleDbWriteBatch,
+    key_addr: &str,
+    store: &PublicAddressStore,
+    encryption_key: &secretbox::Key,
+) {
+    let store = serialize(store).unwrap();
+    let input = encrypt_store(store, encryption_key);
+    db.put_cf(DB_COL_DEFAULT, key_addr, &input);
+}
+
+/// Move the secret keys of the stored AddressStores to memory, keeping their public half only
+///
+/// ### Arguments
+///
+/// * `db`             - Wallet database storing full key-pairs.
+/// * `batch`          - Batch to write the public halves to.
+/// * `encryption_key` - Key the address stores are encrypted with.
+/// * `hot_keys`       - Secret keys held in memory.
+fn move_secret_keys_to_memory(
+    db: &SimpleDb,
+    batch: &mut SimpleDbWriteBatch,
+    encryption_key: &secretbox::Key,
+    hot_keys: &HotKeys,
+) -> Result<()> {
+    for address in get_known_key_address(db)? {
+        let (public, secret_key) = get_address_store(db, &address, encryption_key)?.split();
+        hot_keys.insert(&public.public_key, secret_key);
+        save_public_address_store(batch, &address, &public, encryption_key);
+    }
+    Ok(())
+}
+
 /// Get the wallet transaction store
-pub fn get_transaction_store(db: &SimpleDb, out_p: &OutPoint) -> TransactionStore {
-    match db.get_cf(DB_COL_DEFAULT, serialize(&out_p).unwrap()) {
-        Ok(Some(store)) => deserialize(&store).unwrap(),
-        Ok(None) => panic!("Transaction not present in wallet: {:?}", out_p),
-        Err(e) => panic!("Error accessing wallet: {:?}", e),
+pub fn get_transaction_store(db: &SimpleDb, out_p: &OutPoint) -> Result<TransactionStore> {
+    match db.get_cf(DB_COL_DEFAULT, serialize(&out_p)?)? {
+        Some(store) => Ok(deserialize(&store)?),
+        None => Err(WalletDbError::MissingEntryError(format!("{out_p:?}"))),
     }
 }
 
@@ -983,11 +2521,31 @@ pub fn save_transaction_to_wallet(
 pub fn set_new_master_key_store(
     batch: &mut SimpleDbWriteBatch,
     passphrase: &[u8],
-) -> secretbox::Key {
-    let salt = pwhash::gen_salt();
+    kdf_params: Argon2Params,
+) -> Result<secretbox::Key> {
     let master_key = secretbox::gen_key();
+    save_master_key_store(batch, &master_key, passphrase, kdf_params)?;
+    Ok(master_key)
+}
+
+/// Encrypt the master key with a passphrase derived with Argon2id
+///
+/// ### Arguments
+///
+/// * `batch`      - Batch to write the master key store to.
+/// * `master_key` - Master key to encrypt.
+/// * `passphrase` - Passphrase for master key store.
+/// * `kdf_params` - Argon2id costs of the passphrase key derivation.
+fn save_master_key_store(
+    batch: &mut SimpleDbWriteBatch,
+    master_key: &secretbox::Key,
+    passphrase: &[u8],
+    kdf_params: Argon2Params,
+) -> Result<()> {
+    let salt = pwhash::gen_salt();
     let nonce = secretbox::gen_nonce();
-    let pass_key = make_key(passphrase, salt);
+    let kdf = MasterKeyKdf::new_argon2id(kdf_params);
+    let pass_key = make_pass_key(passphrase, salt, &kdf)?;
     let enc_master_key = secretbox::seal(master_key.as_ref().to_vec(), &nonce, &pass_key).unwrap();
     let store = serialize(&MasterKeyStore {
         salt,
@@ -996,7 +2554,16 @@ pub fn set_new_master_key_store(
     })
     .unwrap();
     batch.put_cf(DB_COL_DEFAULT, MASTER_KEY_STORE_KEY, &store);
-    master_key
+    batch.put_cf(DB_COL_DEFAULT, MASTER_KEY_KDF_KEY, serialize(&kdf).unwrap());
+    Ok(())
+}
+
+/// Get the passphrase key derivation of the master key store
+pub fn get_master_key_kdf(db: &SimpleDb) -> Result<MasterKeyKdf> {
+    match db.get_cf(DB_COL_DEFAULT, MASTER_KEY_KDF_KEY)? {
+        Some(kdf) => Ok(deserialize(&kdf)?),
+        None => Ok(MasterKeyKdf::Pbkdf2),
+    }
 }
 
 /// Get master store key with given passphrase
@@ -1004,8 +2571,9 @@ pub fn get_master_key_store(db: &SimpleDb, passphrase: &[u8]) -> Result<secretbo
     let store = db.get_cf(DB_COL_DEFAULT, MASTER_KEY_STORE_KEY)?;
     let store = store.ok_or(WalletDbError::MasterKeyMissingError)?;
     let store: MasterKeyStore = deserialize(&store)?;
+    let kdf = get_master_key_kdf(db)?;
 
-    let pass_key = make_key(passphrase, store.salt);
+    let pass_key = make_pass_key(passphrase, store.salt, &kdf)?;
     let master_key = secretbox::open(store.clone().enc_master_key, &store.nonce, &pass_key)
         .ok_or(WalletDbError::PassphraseError)?;
     let key =
@@ -1021,11 +2589,12 @@ pub fn get_or_save_master_key_store(
     db: &SimpleDb,
     batch: &mut SimpleDbWriteBatch,
     passphrase: &[u8],
+    kdf_params: Argon2Params,
 ) -> Result<secretbox::Key> {
     match get_master_key_store(db, passphrase) {
         Ok(key) => Ok(key),
         Err(WalletDbError::MasterKeyMissingError) => {
-            Ok(set_new_master_key_store(batch, passphrase))
+            set_new_master_key_store(batch, passphrase, kdf_params)
         }
         Err(e) => Err(e),
     }
@@ -1043,14 +2612,46 @@ pub fn make_key(passphrase: &[u8], salt: pwhash::Salt) -> secretbox::Key {
     secretbox::Key::from_slice(&kb).unwrap()
 }
 
+/// Creates the key encrypting the master key, with the stored key derivation
+///
+/// ### Arguments
+///
+/// * `passphrase` - Passphrase for master key store.
+/// * `salt`       - Salt of the master key store, used by PBKDF2.
+/// * `kdf`        - Key derivation of the master key store.
+pub fn make_pass_key(
+    passphrase: &[u8],
+    salt: pwhash::Salt,
+    kdf: &MasterKeyKdf,
+) -> Result<secretbox::Key> {
+    let (salt, params) = match kdf {
+        MasterKeyKdf::Pbkdf2 => return Ok(make_key(passphrase, salt)),
+        MasterKeyKdf::Argon2id { salt, params } => (salt, params),
+    };
+    let kdf_error = |e: argon2::Error| WalletDbError::KeyDerivationError(e.to_string());
+    let params = argon2::Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(secretbox::KEY_LEN),
+    )
+    .map_err(kdf_error)?;
+
+    let mut kb = [0; secretbox::KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut kb)
+        .map_err(kdf_error)?;
+    Ok(secretbox::Key::from_slice(&kb).unwrap())
+}
+
 /// Decrypt a Store value
-pub fn decrypt_store(store: Vec<u8>, encryption_key: &secretbox::Key) -> Vec<u8> {
+pub fn decrypt_store(store: Vec<u8>, encryption_key: &secretbox::Key) -> Result<Vec<u8>> {
+    if store.len() < secretbox::NONCE_LEN {
+        return Err(WalletDbError::DecryptionError);
+    }
     let (nonce, output) = store.split_at(secretbox::NONCE_LEN);
     let nonce = secretbox::Nonce::from_slice(nonce).unwrap();
-    match secretbox::open(output.to_vec(), &nonce, encryption_key) {
-        Some(decrypted) => decrypted,
-        _ => panic!("Error accessing wallet"),
-    }
+    secretbox::open(output.to_vec(), &nonce, encryption_key).ok_or(WalletDbError::DecryptionError)
 }
 
 /// Encrypt a Store value
@@ -1064,37 +2665,32 @@ pub fn encrypt_store(store: Vec<u8>, encryption_key: &secretbox::Key) -> Vec<u8>
 /// Make TxConstructors from stored TxOut
 /// Also return the used info for db cleanup
 #[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
 pub fn fetch_inputs_for_payment_from_db(
     db: &SimpleDb,
     asset_required: Asset,
+    coin_selection: &CoinSelection,
+    pending_spends: &PendingSpends,
     encryption_key: &secretbox::Key,
     locked_coinbase: &LockedCoinbase,
+    signer: Option<&dyn Signer>,
+    hot_keys: Option<&HotKeys>,
 ) -> Result<(Vec<TxConstructor>, Asset, Vec<(OutPoint, String)>)> {
-    let mut tx_cons = Vec::new();
-    let mut tx_used = Vec::new();
-    let mut fund_store = get_fund_store(db);
+    let mut fund_store = get_fund_store(db)?;
     // We need to filter here, because we are fetching inputs for a transaction
     if let Some(count) = fund_store.filter_locked_coinbase(locked_coinbase) {
         warn!("{count} locked coinbase transaction filtered out");
     }
-    let mut amount_made = Asset::default_of_type(&asset_required);
+    fund_store.filter_reserved(pending_spends);
 
     if !fund_store.running_total().has_enough(&asset_required) {
         return Err(WalletDbError::InsufficientFundsError);
     }
 
-    for (out_p, amount) in fund_store.into_transactions() {
-        if amount_made.add_assign(&amount) {
-            let (cons, used) = tx_constructor_from_prev_out(db, out_p, encryption_key);
-            tx_cons.push(cons);
-            tx_used.push(used);
-        }
-        if let Some(true) = amount_made.is_greater_or_equal_to(&asset_required) {
-            break;
-        }
-    }
-
-    Ok((tx_cons, amount_made, tx_used))
+    let seed = rand::thread_rng().next_u64();
+    let store = DbWalletStore::new(db, encryption_key, fund_store, signer, hot_keys);
+    wallet_core::fetch_inputs_for_payment_with(&store, &asset_required, coin_selection, seed)
+        .map_err(WalletDbError::from)
 }
 
 /// Make TxConstructors from stored TxOut
@@ -1105,10 +2701,12 @@ pub fn fetch_inputs_for_payment_from_supplied_input_addrs_db(
     addresses: BTreeSet<String>,
     encryption_key: &secretbox::Key,
     locked_coinbase: &LockedCoinbase,
+    signer: Option<&dyn Signer>,
+    hot_keys: Option<&HotKeys>,
 ) -> Result<(Vec<TxConstructor>, Asset, Vec<(OutPoint, String)>)> {
     // Only use addresses that actually contain assets
-    let addresses_to_use = retrieve_non_empty_addresses(addresses, db);
-    let mut fund_store = get_fund_store(db);
+    let addresses_to_use = retrieve_non_empty_addresses(addresses, db)?;
+    let mut fund_store = get_fund_store(db)?;
     // We need to filter here because we are fetching inputs for a transaction
     if let Some(count) = fund_store.filter_locked_coinbase(locked_coinbase) {
         warn!("{count} locked coinbase transaction filtered out");
@@ -1117,13 +2715,20 @@ pub fn fetch_inputs_for_payment_from_supplied_input_addrs_db(
     let mut txs_to_use = Vec::new();
 
     for addr in addresses_to_use {
-        fund_store_txs
-            .iter()
-            .filter(|(out_p, _)| addr == get_transaction_store(db, out_p).key_address)
-            .for_each(|(op, asset)| txs_to_use.push((op.clone(), asset.clone())));
+        for (out_p, asset) in &fund_store_txs {
+            if addr == get_transaction_store(db, out_p)?.key_address {
+                txs_to_use.push((out_p.clone(), asset.clone()));
+            }
+        }
     }
 
-    fetch_inputs_from_supplied_txs_for_payment_from_db(db, txs_to_use, encryption_key)
+    fetch_inputs_from_supplied_txs_for_payment_from_db(
+        db,
+        txs_to_use,
+        encryption_key,
+        signer,
+        hot_keys,
+    )
 }
 
 /// Make TxConstructors from stored TxOut
@@ -1133,20 +2738,12 @@ pub fn fetch_inputs_from_supplied_txs_for_payment_from_db(
     db: &SimpleDb,
     addresses: Vec<(OutPoint, Asset)>,
     encryption_key: &secretbox::Key,
+    signer: Option<&dyn Signer>,
+    hot_keys: Option<&HotKeys>,
 ) -> Result<(Vec<TxConstructor>, Asset, Vec<(OutPoint, String)>)> {
-    let mut tx_cons = Vec::new();
-    let mut tx_used = Vec::new();
-    let mut amount_made = Asset::Token(TokenAmount(0)); // TODO: Allow any asset type here
-
-    for (out_p, amount) in addresses {
-        if amount_made.add_assign(&amount) {
-            let (cons, used) = tx_constructor_from_prev_out(db, out_p, encryption_key);
-            tx_cons.push(cons);
-            tx_used.push(used);
-        }
-    }
-
-    Ok((tx_cons, amount_made, tx_used))
+    // TODO: Allow any asset type here
+    let store = DbWalletStore::new(db, encryption_key, FundStore::default(), signer, hot_keys);
+    wallet_core::fetch_inputs_from_supplied_outputs(&store, addresses).map_err(WalletDbError::from)
 }
 
 /// Destroy the used transactions with keys purging them from the wallet
@@ -1154,11 +2751,12 @@ pub fn fetch_inputs_from_supplied_txs_for_payment_from_db(
 pub fn destroy_spent_transactions_and_keys(
     db: &mut SimpleDb,
     addresses: Option<BTreeSet<String>>,
-) -> (BTreeSet<String>, BTreeMap<OutPoint, Asset>) {
-    let empty_addr = retrieve_empty_addresses(addresses.unwrap_or_default(), db);
+) -> Result<(BTreeSet<String>, BTreeMap<OutPoint, Asset>)> {
+    let empty_addr = retrieve_empty_addresses(addresses.unwrap_or_default(), db)?;
     let mut batch = db.batch_writer();
-    let mut fund_store = get_fund_store(db);
-    let mut address_store = get_known_key_address(db);
+    let mut fund_store = get_fund_store(db)?;
+    let mut fund_store_update = FundStoreUpdate::new(db)?;
+    let mut address_store = get_known_key_address(db)?;
 
     //
     // Gather data for update
@@ -1169,162 +2767,932 @@ pub fn destroy_spent_transactions_and_keys(
     let remove_key_addresses: BTreeSet<_> = {
         let fund_store_txs = fund_store.transactions();
 
-        let unspent_key_addresses: BTreeSet<_> = fund_store_txs
-            .keys()
-            .map(|out_p| get_transaction_store(db, out_p).key_address)
+        let unspent_key_addresses = transaction_key_addresses(db, fund_store_txs.keys())?;
+
+        let mut spent_addrs = transaction_key_addresses(db, spent_txs.keys())?;
+        spent_addrs.retain(|addr| !unspent_key_addresses.contains(addr));
+
+        spent_addrs.extend(empty_addr);
+        spent_addrs
+    };
+
+    for keys_address in &remove_key_addresses {
+        address_store.remove(keys_address);
+    }
+
+    //
+    // Update database
+    //
+    for out_p in spent_txs.keys() {
+        fund_store_update.remove_spent_tx(out_p);
+    }
+    fund_store_update.write(&mut batch);
+    set_known_key_address(&mut batch, address_store);
+    for keys_address in &remove_key_addresses {
+        delete_address_store(&mut batch, keys_address);
+        labels::delete_address_metadata(&mut batch, keys_address);
+    }
+    for out_p in spent_txs.keys() {
+        delete_transaction_store(&mut batch, out_p);
+    }
+
+    let batch = batch.done();
+    db.write(batch)?;
+
+    Ok((remove_key_addresses, spent_txs))
+}
+
+/// Addresses the transactions of some outputs were paid to
+///
+/// ### Arguments
+///
+/// * `db`     - Wallet database.
+/// * `out_ps` - Outputs stored in the wallet.
+fn transaction_key_addresses<'a>(
+    db: &SimpleDb,
+    out_ps: impl Iterator<Item = &'a OutPoint>,
+) -> Result<BTreeSet<String>> {
+    out_ps
+        .map(|out_p| Ok(get_transaction_store(db, out_p)?.key_address))
+        .collect()
+}
+
+/// Retrieve addresses from a subset that may or may not contain assets
+///
+/// ## Arguments
+/// * `addresses` - The subset of addresses to filter
+/// * `db` - The database to use
+/// * `look_for_empty` - If true, return addresses that DO NOT contain assets
+pub fn retrieve_empty_or_non_empty_addresses(
+    mut addresses: BTreeSet<String>,
+    db: &SimpleDb,
+    look_for_empty: bool,
+) -> Result<BTreeSet<String>> {
+    let fund_store = get_fund_store(db)?;
+    let unspent_key_addresses = transaction_key_addresses(db, fund_store.transactions().keys())?;
+
+    if look_for_empty {
+        addresses.retain(|addr| !unspent_key_addresses.contains(addr));
+    } else {
+        addresses.retain(|addr| unspent_key_addresses.contains(addr));
+    }
+    Ok(addresses)
+}
+
+/// Retrieve addresses from a subset that DO NOT contain assets from the wallet
+pub fn retrieve_empty_addresses(
+    addresses: BTreeSet<String>,
+    db: &SimpleDb,
+) -> Result<BTreeSet<String>> {
+    retrieve_empty_or_non_empty_addresses(addresses, db, true)
+}
+
+/// Retrieve addresses from a subset that contain assets from the wallet
+pub fn retrieve_non_empty_addresses(
+    addresses: BTreeSet<String>,
+    db: &SimpleDb,
+) -> Result<BTreeSet<String>> {
+    retrieve_empty_or_non_empty_addresses(addresses, db, false)
+}
+
+/// Make TxConstructor from stored TxOut
+/// Also return the used info for db cleanup
+pub fn tx_constructor_from_prev_out(
+    db: &SimpleDb,
+    out_p: OutPoint,
+    encryption_key: &secretbox::Key,
+) -> Result<(TxConstructor, (OutPoint, String))> {
+    let key_address = get_transaction_store(db, &out_p)?.key_address;
+    let needed_store = get_address_store(db, &key_address, encryption_key)?;
+    let tx_const = wallet_core::sign_input(out_p.clone(), &needed_store.into());
+
+    Ok((tx_const, (out_p, key_address)))
+}
+
+/// Wallet database content seen by the wallet core
+struct DbWalletStore<'a> {
+    db: &'a SimpleDb,
+    encryption_key: &'a secretbox::Key,
+    fund_store: FundStore,
+    signer: Option<&'a dyn Signer>,
+    hot_keys: Option<&'a HotKeys>,
+}
+
+impl<'a> DbWalletStore<'a> {
+    /// Create a store spending the outputs of the given fund store
+    ///
+    /// ### Arguments
+    ///
+    /// * `db`             - Wallet database holding the keys.
+    /// * `encryption_key` - Key the address stores are encrypted with.
+    /// * `fund_store`     - Outputs that can be spent.
+    /// * `signer`         - Signer of the inputs, the stored secret keys if None.
+    /// * `hot_keys`       - Secret keys held in memory, for hot wallets.
+    fn new(
+        db: &'a SimpleDb,
+        encryption_key: &'a secretbox::Key,
+        fund_store: FundStore,
+        signer: Option<&'a dyn Signer>,
+        hot_keys: Option<&'a HotKeys>,
+    ) -> Self {
+        Self {
+            db,
+            encryption_key,
+            fund_store,
+            signer,
+            hot_keys,
+        }
+    }
+}
+
+impl WalletStore for DbWalletStore<'_> {
+    fn spendable_outputs(&self) -> BTreeMap<OutPoint, Asset> {
+        self.fund_store.transactions().clone()
+    }
+
+    fn output_address(&self, out_p: &OutPoint) -> Option<String> {
+        match get_transaction_store(self.db, out_p) {
+            Ok(store) => Some(store.key_address),
+            Err(e) => {
+                warn!("Output address not loaded: {}", e);
+                None
+            }
+        }
+    }
+
+    fn address_keys(&self, address: &str) -> Option<AddressKeys> {
+        match load_address_store(self.db, address, self.encryption_key, self.hot_keys) {
+            Ok(store) => store.map(Into::into),
+            Err(e) => {
+                warn!("Address keys not loaded: {}", e);
+                None
+            }
+        }
+    }
+
+    fn sign_digest(
+        &self,
+        address: &str,
+        keys: &AddressKeys,
+        digest: &[u8],
+    ) -> wallet_core::Result<Signature> {
+        match self.signer {
+            Some(signer) => signer
+                .sign(address, &keys.public_key, digest)
+                .map_err(|e| WalletCoreError::SigningFailed(e.to_string())),
+            None => Ok(sign::sign_detached(digest, &keys.secret_key)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fund_store::ENTRIES_PER_PAGE;
+    use super::*;
+    use crate::test_utils::WalletBuilder;
+    use proptest::prelude::*;
+    use tw_chain::primitives::asset::TokenAmount;
+    use tw_chain::utils::transaction_utils::{construct_address, construct_tx_core};
+
+    #[test]
+    /// Creating a valid payment address
+    fn should_construct_address_valid() {
+        let pk = PublicKey::from_slice(&[
+            196, 234, 50, 92, 76, 102, 62, 4, 231, 81, 211, 133, 33, 164, 134, 52, 44, 68, 174, 18,
+            14, 59, 108, 187, 150, 190, 169, 229, 215, 130, 78, 78,
+        ])
+        .unwrap();
+        let addr = construct_address(&pk);
+
+        assert_eq!(
+            addr,
+            "fc0aa2394edb0d2df918325d4682c21eacecf173265db7c04623d2a921f3876c".to_string(),
+        );
+    }
+
+    #[test]
+    /// Creating a payment address of 25 bytes
+    fn should_construct_address_valid_length() {
+        let pk = PublicKey::from_slice(&[
+            196, 234, 50, 92, 76, 102, 62, 4, 231, 81, 211, 133, 33, 164, 134, 52, 44, 68, 174, 18,
+            14, 59, 108, 187, 150, 190, 169, 229, 215, 130, 78, 78,
+        ])
+        .unwrap();
+        let addr = construct_address(&pk);
+
+        assert_eq!(addr.len(), 64);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    #[should_panic(expected = "called `Result::unwrap()` on an `Err` value: PassphraseError")]
+    async fn incorrect_password() {
+        //Arrange
+        let db = WalletDb::new(
+            DbMode::InMemory,
+            None,
+            Some(String::from("Test Passphrase1")),
+            None,
+        )
+        .unwrap()
+        .take_closed_persistent_store()
+        .await;
+
+        //Act/Panic - Wrong Passphrase
+        let _db = WalletDb::new(
+            DbMode::InMemory,
+            Some(db),
+            Some("Test Passphrase 2".to_owned()),
+            None,
+        )
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks the stores of a wallet are encrypted on disk, and read once unlocked
+    async fn locked_wallet_stores_encrypted() {
+        //Arrange
+        let kdf_params = Argon2Params::for_db_mode(DbMode::InMemory);
+        let passphrase = Some("Test Passphrase".to_owned());
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, passphrase.clone(), None).unwrap();
+        let (address, _) = wallet.generate_payment_address().await.unwrap();
+        let db = wallet.take_closed_persistent_store().await;
+
+        //Act
+        let locked = WalletDb::open_locked(DbMode::InMemory, Some(db), None);
+        let stored = locked.db.get_cf(DB_COL_DEFAULT, KNOWN_ADDRESS_KEY).unwrap();
+        let unlocked = locked.unlock(passphrase, kdf_params, None).unwrap();
+
+        //Assert
+        let stored = stored.unwrap();
+        assert!(!stored
+            .windows(address.len())
+            .any(|window| window == address.as_bytes()));
+        assert!(unlocked.get_known_addresses().unwrap().contains(&address));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a wallet cannot be opened by a node of another network
+    async fn wallet_network_id_checked() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let first_check = wallet.check_network_id(NetworkId::Testnet);
+        let db = wallet.take_closed_persistent_store().await;
+
+        //Act
+        let wallet = WalletDb::new(DbMode::InMemory, Some(db), None, None).unwrap();
+        let same_network = wallet.check_network_id(NetworkId::Testnet);
+        let other_network = wallet.check_network_id(NetworkId::Mainnet);
+
+        //Assert
+        assert!(first_check.is_ok());
+        assert!(same_network.is_ok());
+        assert!(matches!(other_network, Err(WalletDbError::Database(_))));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks cached transaction stores are invalidated when written
+    async fn cached_transaction_store_invalidated_on_write() {
+        //Arrange
+        let wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let out_p = OutPoint::new("tx_hash".to_owned(), 0);
+        let store = |key_address: &str| TransactionStore {
+            key_address: key_address.to_owned(),
+        };
+        let write_store = |key_address: &str| {
+            let mut db = wallet.db.write().unwrap();
+            let mut batch = db.batch_writer();
+            save_transaction_to_wallet(&mut batch, &out_p, &store(key_address));
+            let batch = batch.done();
+            db.write(batch).unwrap();
+        };
+
+        //Act
+        write_store("address_1");
+        let first = wallet.get_transaction_store(&out_p).unwrap();
+        let cached = wallet.get_transaction_store(&out_p).unwrap();
+        write_store("address_2");
+        let overwritten = wallet.get_transaction_store(&out_p).unwrap();
+
+        //Assert
+        assert_eq!(first.key_address, "address_1");
+        assert_eq!(cached.key_address, "address_1");
+        assert_eq!(overwritten.key_address, "address_2");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks new wallets derive their passphrase with Argon2id and the stored costs
+    async fn new_wallet_argon2id_passphrase() {
+        //Arrange
+        let params = Argon2Params {
+            memory_kib: 16,
+            iterations: 2,
+            parallelism: 1,
+        };
+        let passphrase = Some("Test Passphrase".to_owned());
+        let mut wallet =
+            WalletDb::new_with_kdf(DbMode::InMemory, None, passphrase.clone(), None, params)
+                .unwrap();
+        let master_key = wallet.encryption_key.clone();
+        let db = wallet.take_closed_persistent_store().await;
+
+        //Act
+        let kdf = get_master_key_kdf(&db).unwrap();
+        let reopened = WalletDb::new(DbMode::InMemory, Some(db), passphrase, None).unwrap();
+
+        //Assert
+        assert!(matches!(kdf, MasterKeyKdf::Argon2id { params: p, .. } if p == params));
+        assert_eq!(reopened.encryption_key.as_ref(), master_key.as_ref());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks payments the wallet cannot fund return errors instead of panicking
+    async fn fetch_inputs_for_payment_errors() {
+        //Arrange
+        let wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let out_p = OutPoint::new("tx_hash".to_owned(), 0);
+        {
+            let mut db = wallet.db.write().unwrap();
+            let mut batch = db.batch_writer();
+            let mut fund_store = FundStoreUpdate::new(&db).unwrap();
+            fund_store
+                .store_tx(out_p.clone(), Asset::token_u64(5))
+                .unwrap();
+            fund_store.write(&mut batch);
+            let batch = batch.done();
+            db.write(batch).unwrap();
+        }
+
+        //Act
+        let insufficient = wallet.fetch_inputs_for_payment(Asset::token_u64(6)).await;
+        let unknown_output = wallet.fetch_inputs_for_payment(Asset::token_u64(5)).await;
+        let missing_store = get_transaction_store(&wallet.db.read().unwrap(), &out_p);
+
+        //Assert
+        assert!(matches!(
+            insufficient,
+            Err(WalletDbError::InsufficientFundsError)
+        ));
+        assert!(matches!(
+            unknown_output,
+            Err(WalletDbError::WalletCore(WalletCoreError::OutputAddressMissing(o))) if o == out_p
+        ));
+        assert!(matches!(
+            missing_store,
+            Err(WalletDbError::MissingEntryError(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks outputs fetched for a payment are not fetched again until released
+    async fn fetch_inputs_for_payment_reserves_outputs() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (key_addr, _) = wallet.generate_payment_address().await.unwrap();
+        let payments = (0..2)
+            .map(|n| {
+                let out_p = OutPoint::new("funds".to_owned(), n);
+                (out_p, Asset::token_u64(5), key_addr.clone(), 0)
+            })
             .collect();
+        wallet
+            .save_usable_payments_to_wallet(payments, 0)
+            .await
+            .unwrap();
+        let fetch = || wallet.fetch_inputs_for_payment(Asset::token_u64(4));
+
+        //Act
+        let (_, _, first) = fetch().await.unwrap();
+        let (second_cons, _, second) = fetch().await.unwrap();
+        let all_reserved = fetch().await;
+        wallet
+            .release_reserved_inputs(vec![first[0].0.clone()])
+            .await
+            .unwrap();
+        let (_, _, released) = fetch().await.unwrap();
+        wallet
+            .consume_inputs_for_payment(second_cons, second.clone())
+            .await
+            .unwrap();
+        let pending_spends = get_pending_spends(&wallet.db.read().unwrap()).unwrap();
+
+        //Assert
+        assert_ne!(first, second);
+        assert!(matches!(
+            all_reserved,
+            Err(WalletDbError::InsufficientFundsError)
+        ));
+        assert_eq!(released, first);
+        assert!(!pending_spends.is_reserved(&second[0].0));
+        assert!(pending_spends.is_reserved(&first[0].0));
+        assert_eq!(wallet.get_fund_store().unwrap().running_total().tokens.0, 5);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a partial signature of the wallet combines with a co-signer's into a valid spend
+    async fn multisig_partial_signatures_spend_output() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let co_signers: Vec<_> = (0..2).map(|_| sign::gen_keypair()).collect();
+        let public_keys = co_signers.iter().map(|(pk, _)| *pk).collect();
+        let address = wallet
+            .create_multisig_address(2, public_keys)
+            .await
+            .unwrap();
+        let out_p = OutPoint::new("multisig".to_owned(), 0);
+        let tx_out = TxOut::new_token_amount(address.clone(), TokenAmount(10), None);
+        let outputs = vec![TxOut::new_token_amount(
+            "receiver".to_owned(),
+            TokenAmount(10),
+            None,
+        )];
+
+        //Act
+        let own = wallet
+            .create_partial_signature(address.clone(), out_p.clone(), outputs.clone())
+            .await
+            .unwrap();
+        let signable_data = condition_signable_data(&out_p, &outputs);
+        let (co_pk, co_sk) = &co_signers[1];
+        let co_signed = (*co_pk, sign::sign_detached(signable_data.as_bytes(), co_sk));
+        let partials = vec![own, co_signed];
+        let combine =
+            |partials| wallet.combine_signatures(&address, out_p.clone(), &outputs, partials);
+        let alone = combine(partials[..1].to_vec());
+        let tx_in = combine(partials).unwrap();
+
+        //Assert
+        let mut tx = Transaction::new();
+        tx.inputs = vec![tx_in];
+        tx.outputs = outputs;
+        let utxo = |o: &OutPoint| Some(&tx_out).filter(|_| *o == out_p);
+        let multisig = wallet.get_multisig_address(&address).unwrap().unwrap();
+        assert!(matches!(alone, Err(WalletDbError::MultisigError(_))));
+        assert!(crate::spend_script::condition_tx_is_valid(&tx, 0, 0, utxo));
+        assert_eq!(multisig.keys.len(), 3);
+        assert_eq!(wallet.get_signing_audit_log().unwrap().len(), 1);
+        assert!(wallet.create_multisig_address(4, Vec::new()).await.is_err());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks addresses are found by their label, given to wallet addresses only
+    async fn set_address_label_tags_addresses() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (salary, _) = wallet.generate_payment_address().await.unwrap();
+        let (donations, _) = wallet.generate_payment_address().await.unwrap();
+        let label = |l: &str| Some(l.to_owned());
+
+        //Act
+        wallet
+            .set_address_label(salary.clone(), label("salary"))
+            .await
+            .unwrap();
+        wallet
+            .set_address_label(donations.clone(), label("salary"))
+            .await
+            .unwrap();
+        wallet
+            .set_address_label(donations.clone(), label("donations"))
+            .await
+            .unwrap();
+        let unknown = wallet
+            .set_address_label("unknown".to_owned(), label("salary"))
+            .await;
+
+        //Assert
+        let metadata = wallet.get_address_metadata().unwrap();
+        assert_eq!(
+            wallet.get_addresses_by_label("salary").unwrap(),
+            vec![salary.clone()]
+        );
+        assert_eq!(
+            wallet.get_addresses_by_label("donations").unwrap(),
+            vec![donations]
+        );
+        assert!(matches!(unknown, Err(WalletDbError::MissingEntryError(_))));
+        assert!(metadata[&salary].created.is_some());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks payments to watch-only addresses are tracked but never fetched as inputs
+    async fn watch_only_payments_not_fetched() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (key_addr, _) = wallet.generate_payment_address().await.unwrap();
+        let (public_key, _) = sign::gen_keypair();
+        let watched = wallet
+            .import_watch_only_public_key(public_key, None)
+            .await
+            .unwrap();
+        let payment = |n, address: &String| {
+            let out_p = OutPoint::new("funds".to_owned(), n);
+            (out_p, Asset::token_u64(5), address.clone(), 0)
+        };
+        let payments = vec![payment(0, &watched), payment(1, &watched)];
 
-        let mut spent_addrs: BTreeSet<_> = spent_txs
-            .keys()
-            .map(|out_p| get_transaction_store(db, out_p).key_address)
-            .filter(|addr| !unspent_key_addresses.contains(addr))
+        //Act
+        let saved = wallet.save_usable_payments_to_wallet(payments, 0).await;
+        let key_import = wallet.import_watch_only_address(key_addr).await;
+        let no_funds = wallet.fetch_inputs_for_payment(Asset::token_u64(4)).await;
+        let unspent = vec![OutPoint::new("funds".to_owned(), 1)]
+            .into_iter()
             .collect();
+        let dropped = wallet
+            .refresh_watch_only_outputs(UtxoFetchType::All, unspent)
+            .await
+            .unwrap();
 
-        spent_addrs.extend(empty_addr);
-        spent_addrs
-    };
-
-    for keys_address in &remove_key_addresses {
-        address_store.remove(keys_address);
+        //Assert
+        let watch_only = wallet.get_watch_only_store().unwrap();
+        let history = wallet.get_tx_history(0, 10).unwrap();
+        assert_eq!(saved.unwrap(), Vec::new());
+        assert!(matches!(key_import, Err(WalletDbError::KeyImportError(_))));
+        assert!(matches!(
+            no_funds,
+            Err(WalletDbError::InsufficientFundsError)
+        ));
+        assert_eq!(dropped, 1);
+        assert_eq!(
+            watch_only.addresses().get(&watched),
+            Some(&Some(public_key))
+        );
+        assert_eq!(watch_only.running_total().tokens.0, 5);
+        assert_eq!(wallet.get_fund_store().unwrap().running_total().tokens.0, 0);
+        assert!(history.entries[0].watch_only);
     }
 
-    //
-    // Update database
-    //
-    set_fund_store(&mut batch, fund_store);
-    set_known_key_address(&mut batch, address_store);
-    for keys_address in &remove_key_addresses {
-        delete_address_store(&mut batch, keys_address);
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a wallet restored from a backup file holds the same addresses and funds
+    async fn backup_file_restores_wallet() {
+        //Arrange
+        let mut wallet = wallet_with_payments(&[5, 7]).await;
+        let (tx_cons, _, tx_used) = wallet
+            .fetch_inputs_for_payment(Asset::token_u64(4))
+            .await
+            .unwrap();
+        wallet
+            .consume_inputs_for_payment(tx_cons, tx_used)
+            .await
+            .unwrap();
+        let restored = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let path = std::env::temp_dir().join(format!("wallet_backup_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        //Act
+        let metadata = wallet.export_backup(&path, "backup").unwrap();
+        let overwrite = wallet.export_backup(&path, "backup");
+        let wrong_passphrase = restored.import_backup(&path, "other").await;
+        let addresses = restored.import_backup(&path, "backup").await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        //Assert
+        assert_eq!(metadata.key_count, 1);
+        assert!(matches!(overwrite, Err(WalletDbError::IO(_))));
+        assert!(matches!(
+            wrong_passphrase,
+            Err(WalletDbError::PassphraseError)
+        ));
+        assert_eq!(addresses, wallet.get_known_addresses().unwrap());
+        assert_eq!(
+            restored.sync_summary().unwrap(),
+            wallet.sync_summary().unwrap()
+        );
+        assert_eq!(
+            restored.get_fund_store().unwrap().running_total().tokens.0,
+            7
+        );
     }
-    for out_p in spent_txs.keys() {
-        delete_transaction_store(&mut batch, out_p);
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks the changes of a batch to the address, transaction and fund stores are all written
+    async fn batch_update_writes_stores_together() {
+        //Arrange
+        let wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (public_key, secret_key) = sign::gen_keypair();
+        let address = construct_address_for(&public_key, None);
+        let keys = AddressStore {
+            public_key,
+            secret_key,
+            address_version: None,
+        };
+        let out_points: Vec<_> = (0..2)
+            .map(|n| OutPoint::new("tx_hash".to_owned(), n))
+            .collect();
+        let mut changes = WalletBatch::default();
+        changes.save_address(address.clone(), keys);
+        for out_p in &out_points {
+            changes.save_payment(out_p.clone(), Asset::token_u64(5), address.clone());
+        }
+        changes.spend(out_points[0].clone());
+
+        //Act
+        let empty = wallet.batch_update(WalletBatch::default()).await;
+        wallet.batch_update(changes).await.unwrap();
+
+        //Assert
+        let fund_store = wallet.get_fund_store().unwrap();
+        assert!(empty.is_ok());
+        assert_eq!(wallet.get_known_addresses().unwrap(), vec![address.clone()]);
+        assert_eq!(
+            wallet.get_transaction_address(&out_points[1]).unwrap(),
+            address
+        );
+        assert_eq!(fund_store.running_total().tokens.0, 5);
+        assert!(fund_store.spent_transactions().contains_key(&out_points[0]));
+        assert!(wallet.get_address_metadata().unwrap()[&address]
+            .created
+            .is_some());
     }
 
-    let batch = batch.done();
-    db.write(batch).unwrap();
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks the addresses of a wallet are derived again from its seed in another wallet
+    async fn restore_from_seed_recovers_addresses() {
+        //Arrange
+        let passphrase = "Test Passphrase".to_owned();
+        let mut wallet =
+            WalletDb::new(DbMode::InMemory, None, Some(passphrase.clone()), None).unwrap();
+        let mut restored = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let mut generated = Vec::new();
+        for _ in 0..3 {
+            generated.push(wallet.generate_payment_address().await.unwrap().0);
+        }
 
-    (remove_key_addresses, spent_txs)
-}
+        //Act
+        let seed = wallet.export_hd_seed(passphrase.clone()).await.unwrap();
+        let seed = hex::decode(seed).unwrap();
+        let addresses = restored.restore_from_seed(&seed, 5, false).await.unwrap();
+        let other_seed = [7u8; 32];
+        let refused = restored.restore_from_seed(&other_seed, 2, false).await;
+        let known_after_refused = restored.get_known_addresses().unwrap().len();
+        let mnemonic = wallet.export_mnemonic(passphrase).await.unwrap();
+        let from_mnemonic = WalletDb::from_mnemonic(DbMode::InMemory, None, None, None, &mnemonic)
+            .await
+            .unwrap();
+        let (next_address, _) = restored.generate_payment_address().await.unwrap();
+        let (wallet_next, _) = wallet.generate_payment_address().await.unwrap();
+
+        //Assert
+        assert_eq!(addresses[..3], generated[..]);
+        assert!(matches!(refused, Err(WalletDbError::HdWalletError(_))));
+        assert_eq!(known_after_refused, 5);
+        assert_eq!(restored.get_known_addresses().unwrap().len(), 6);
+        assert!(!addresses.contains(&next_address));
+        assert_eq!(addresses[3], wallet_next);
+        assert_eq!(from_mnemonic.get_known_addresses().unwrap().len(), 20);
+        assert!(generated
+            .iter()
+            .all(|a| from_mnemonic.get_known_addresses().unwrap().contains(a)));
+    }
 
-/// Retrieve addresses from a subset that may or may not contain assets
-///
-/// ## Arguments
-/// * `addresses` - The subset of addresses to filter
-/// * `db` - The database to use
-/// * `look_for_empty` - If true, return addresses that DO NOT contain assets
-pub fn retrieve_empty_or_non_empty_addresses(
-    mut addresses: BTreeSet<String>,
-    db: &SimpleDb,
-    look_for_empty: bool,
-) -> BTreeSet<String> {
-    let fund_store = get_fund_store(db);
-    let fund_store_txs = fund_store.transactions();
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks a wallet synced with the update it lacks holds the same funds, and
+    /// spent outputs stay spent when an older update is applied again
+    async fn sync_update_converges_wallets() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let peer = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (address, _) = wallet.generate_payment_address().await.unwrap();
+        let out_points: Vec<_> = (0..2)
+            .map(|n| OutPoint::new("tx_hash".to_owned(), n))
+            .collect();
+        let outputs: Vec<_> = out_points
+            .iter()
+            .map(|out_p| (out_p.clone(), Asset::token_u64(5), address.clone()))
+            .collect();
+        let received = WalletSyncUpdate {
+            outputs,
+            ..Default::default()
+        };
+        wallet.apply_sync_update(received.clone()).await.unwrap();
+        let spent = WalletSyncUpdate {
+            spent: vec![out_points[0].clone()],
+            ..Default::default()
+        };
+        wallet.apply_sync_update(spent).await.unwrap();
 
-    let unspent_key_addresses: BTreeSet<_> = fund_store_txs
-        .keys()
-        .map(|out_p| get_transaction_store(db, out_p).key_address)
-        .collect();
+        //Act
+        let update = wallet
+            .sync_update_for(&peer.sync_summary().unwrap())
+            .unwrap();
+        peer.apply_sync_update(update).await.unwrap();
+        peer.apply_sync_update(received).await.unwrap();
+        let update_back = wallet
+            .sync_update_for(&peer.sync_summary().unwrap())
+            .unwrap();
 
-    if look_for_empty {
-        addresses.retain(|addr| !unspent_key_addresses.contains(addr));
-    } else {
-        addresses.retain(|addr| unspent_key_addresses.contains(addr));
+        //Assert
+        assert_eq!(peer.sync_summary().unwrap(), wallet.sync_summary().unwrap());
+        assert_eq!(peer.get_fund_store().unwrap().running_total().tokens.0, 5);
+        assert_eq!(
+            peer.get_transaction_address(&out_points[1]).unwrap(),
+            address
+        );
+        assert!(update_back.is_empty());
     }
-    addresses
-}
 
-/// Retrieve addresses from a subset that DO NOT contain assets from the wallet
-pub fn retrieve_empty_addresses(addresses: BTreeSet<String>, db: &SimpleDb) -> BTreeSet<String> {
-    retrieve_empty_or_non_empty_addresses(addresses, db, true)
-}
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks fund stores saved as a single value move to their own keys, and are updated in place
+    async fn legacy_fund_store_value_migrated() {
+        //Arrange
+        let mut db = db_utils::new_db(DbMode::InMemory, &DB_SPEC, None, None);
+        let out_points: Vec<_> = (0..3)
+            .map(|n| OutPoint::new("tx_hash".to_owned(), n))
+            .collect();
+        let mut legacy = FundStore::default();
+        for out_p in &out_points {
+            legacy.store_tx(out_p.clone(), Asset::token_u64(5));
+        }
+        legacy.spend_tx(&out_points[0]);
+        let mut batch = db.batch_writer();
+        batch.put_cf(
+            DB_COL_DEFAULT,
+            crate::constants::FUND_KEY,
+            serialize(&legacy).unwrap(),
+        );
+        let batch = batch.done();
+        db.write(batch).unwrap();
 
-/// Retrieve addresses from a subset that contain assets from the wallet
-pub fn retrieve_non_empty_addresses(
-    addresses: BTreeSet<String>,
-    db: &SimpleDb,
-) -> BTreeSet<String> {
-    retrieve_empty_or_non_empty_addresses(addresses, db, false)
-}
+        //Act
+        let mut wallet = WalletDb::new(DbMode::InMemory, Some(db), None, None).unwrap();
+        let migrated = wallet.get_fund_store().unwrap();
+        {
+            let mut db = wallet.db.write().unwrap();
+            let mut update = FundStoreUpdate::new(&db).unwrap();
+            let new_out_p = OutPoint::new("tx_hash_2".to_owned(), 0);
+            update.spend_tx(&out_points[1]).unwrap();
+            update.store_tx(new_out_p, Asset::token_u64(7)).unwrap();
+            let mut batch = db.batch_writer();
+            update.write(&mut batch);
+            let batch = batch.done();
+            db.write(batch).unwrap();
+        }
+        let updated = wallet.get_fund_store().unwrap();
+        let db = wallet.take_closed_persistent_store().await;
+
+        //Assert
+        assert_eq!(migrated.running_total().tokens.0, 10);
+        assert_eq!(migrated.transactions().len(), 2);
+        assert_eq!(migrated.spent_transactions().len(), 1);
+        assert_eq!(updated.running_total().tokens.0, 12);
+        assert_eq!(
+            updated.transactions().keys().collect::<Vec<_>>(),
+            vec![&out_points[2], &OutPoint::new("tx_hash_2".to_owned(), 0)]
+        );
+        assert_eq!(updated.spent_transactions().len(), 2);
+        assert_eq!(
+            db.get_cf(DB_COL_DEFAULT, crate::constants::FUND_KEY)
+                .unwrap(),
+            None
+        );
+    }
 
-/// Make TxConstructor from stored TxOut
-/// Also return the used info for db cleanup
-pub fn tx_constructor_from_prev_out(
-    db: &SimpleDb,
-    out_p: OutPoint,
-    encryption_key: &secretbox::Key,
-) -> (TxConstructor, (OutPoint, String)) {
-    let key_address = get_transaction_store(db, &out_p).key_address;
-    let needed_store = get_address_store(db, &key_address, encryption_key);
-
-    let hash_to_sign = construct_tx_in_signable_hash(&out_p);
-    let signature = sign::sign_detached(hash_to_sign.as_bytes(), &needed_store.secret_key);
-
-    let tx_const = TxConstructor {
-        previous_out: out_p.clone(),
-        signatures: vec![signature],
-        pub_keys: vec![needed_store.public_key],
-        address_version: needed_store.address_version,
-    };
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks wallets using PBKDF2 still open, and are upgraded on passphrase change
+    async fn legacy_pbkdf2_passphrase_upgrade() {
+        //Arrange
+        let mut db = db_utils::new_db(DbMode::InMemory, &DB_SPEC, None, None);
+        let master_key = secretbox::gen_key();
+        let salt = pwhash::gen_salt();
+        let nonce = secretbox::gen_nonce();
+        let pass_key = make_key(b"old", salt);
+        let store = MasterKeyStore {
+            salt,
+            nonce,
+            enc_master_key: secretbox::seal(master_key.as_ref().to_vec(), &nonce, &pass_key)
+                .unwrap(),
+        };
+        let mut batch = db.batch_writer();
+        batch.put_cf(
+            DB_COL_DEFAULT,
+            MASTER_KEY_STORE_KEY,
+            serialize(&store).unwrap(),
+        );
+        let batch = batch.done();
+        db.write(batch).unwrap();
 
-    (tx_const, (out_p, key_address))
-}
+        //Act
+        let mut wallet =
+            WalletDb::new(DbMode::InMemory, Some(db), Some("old".to_owned()), None).unwrap();
+        let opened_key = wallet.encryption_key.clone();
+        wallet
+            .change_wallet_passphrase("old".to_owned(), "new".to_owned())
+            .await
+            .unwrap();
+        let db = wallet.take_closed_persistent_store().await;
+        let kdf = get_master_key_kdf(&db).unwrap();
+        let reopened = WalletDb::new(DbMode::InMemory, Some(db), Some("new".to_owned()), None);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tw_chain::utils::transaction_utils::construct_address;
+        //Assert
+        assert_eq!(opened_key.as_ref(), master_key.as_ref());
+        assert!(matches!(kdf, MasterKeyKdf::Argon2id { .. }));
+        assert_eq!(
+            reopened.unwrap().encryption_key.as_ref(),
+            master_key.as_ref()
+        );
+    }
 
-    #[test]
-    /// Creating a valid payment address
-    fn should_construct_address_valid() {
-        let pk = PublicKey::from_slice(&[
-            196, 234, 50, 92, 76, 102, 62, 4, 231, 81, 211, 133, 33, 164, 134, 52, 44, 68, 174, 18,
-            14, 59, 108, 187, 150, 190, 169, 229, 215, 130, 78, 78,
-        ])
-        .unwrap();
-        let addr = construct_address(&pk);
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks imported key-pairs are saved only if they derive the expected address
+    async fn import_keypair_expected_address() {
+        //Arrange
+        let wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let seed = "d4ee72dbf913584ad5b6d8f1f769f8ad3afe7c28cbf1d4fbe097a88f44755842";
+        let (public_key, _) = key_import::decode_keypair(KeyFormat::Hex, seed).unwrap();
+        let address = construct_address_for(&public_key, None);
+
+        //Act
+        let mismatched = wallet
+            .import_keypair(KeyFormat::Hex, seed, None, Some("other"))
+            .await;
+        let known_after_mismatch = wallet.get_known_addresses().unwrap();
+        let imported = wallet
+            .import_keypair(KeyFormat::Hex, seed, None, Some(&address))
+            .await
+            .unwrap();
 
+        //Assert
+        assert!(matches!(
+            mismatched,
+            Err(WalletDbError::AddressMismatchError(a)) if a == address
+        ));
+        assert!(known_after_mismatch.is_empty());
+        assert_eq!(imported, address);
+        assert_eq!(wallet.get_known_addresses().unwrap(), vec![address.clone()]);
         assert_eq!(
-            addr,
-            "fc0aa2394edb0d2df918325d4682c21eacecf173265db7c04623d2a921f3876c".to_string(),
+            wallet
+                .get_address_store(&address)
+                .unwrap()
+                .public_key
+                .as_ref(),
+            public_key.as_ref()
         );
     }
 
-    #[test]
-    /// Creating a payment address of 25 bytes
-    fn should_construct_address_valid_length() {
-        let pk = PublicKey::from_slice(&[
-            196, 234, 50, 92, 76, 102, 62, 4, 231, 81, 211, 133, 33, 164, 134, 52, 44, 68, 174, 18,
-            14, 59, 108, 187, 150, 190, 169, 229, 215, 130, 78, 78,
-        ])
-        .unwrap();
-        let addr = construct_address(&pk);
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks the key-pairs of a wallet are restored from its backup into another wallet
+    async fn key_backup_round_trip() {
+        //Arrange
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let restored = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (address, keys) = wallet.generate_payment_address().await.unwrap();
+
+        //Act
+        let backup = wallet.export_key_backup("passphrase").unwrap();
+        let imported = restored
+            .import_key_backup(&backup, "passphrase")
+            .await
+            .unwrap();
 
-        assert_eq!(addr.len(), 64);
+        //Assert
+        assert_eq!(imported, vec![address.clone()]);
+        assert_eq!(
+            restored
+                .get_address_store(&address)
+                .unwrap()
+                .secret_key
+                .as_ref(),
+            keys.secret_key.as_ref()
+        );
     }
 
     #[tokio::test(flavor = "current_thread")]
-    #[should_panic(expected = "called `Result::unwrap()` on an `Err` value: PassphraseError")]
-    async fn incorrect_password() {
+    /// Checks hot wallets keep their secret keys in memory, and store public keys only
+    async fn hot_wallet_public_keys_on_disk() {
         //Arrange
-        let db = WalletDb::new(
+        let params = Argon2Params::for_db_mode(DbMode::InMemory);
+        let mut wallet = WalletDb::new(DbMode::InMemory, None, None, None).unwrap();
+        let (address, keys) = wallet.generate_payment_address().await.unwrap();
+        let db = wallet.take_closed_persistent_store().await;
+        let hot_keys = Arc::new(HotKeys::default());
+
+        //Act
+        let mut hot_wallet = WalletDb::new_with_hot_keys(
             DbMode::InMemory,
+            Some(db),
             None,
-            Some(String::from("Test Passphrase1")),
             None,
+            params,
+            Some(hot_keys.clone()),
         )
-        .unwrap()
-        .take_closed_persistent_store()
-        .await;
-
-        //Act/Panic - Wrong Passphrase
-        let _db = WalletDb::new(
-            DbMode::InMemory,
-            Some(db),
-            Some("Test Passphrase 2".to_owned()),
-            None,
+        .unwrap();
+        let (new_address, new_keys) = hot_wallet.generate_payment_address().await.unwrap();
+        let restored_store = hot_wallet.get_address_store(&address).unwrap();
+        let db = hot_wallet.take_closed_persistent_store().await;
+        let disk_store = decrypt_store(
+            db.get_cf(DB_COL_DEFAULT, &new_address).unwrap().unwrap(),
+            &hot_wallet.encryption_key,
         )
         .unwrap();
+        let cold_reopen = WalletDb::new(DbMode::InMemory, Some(db), None, None);
+
+        //Assert
+        assert_eq!(hot_keys.len(), 2);
+        assert_eq!(restored_store.secret_key.as_ref(), keys.secret_key.as_ref());
+        assert!(deserialize::<PublicAddressStore>(&disk_store).is_ok());
+        assert!(!disk_store
+            .windows(new_keys.secret_key.as_ref().len())
+            .any(|w| w == new_keys.secret_key.as_ref()));
+        assert!(matches!(cold_reopen, Err(WalletDbError::HotWalletError(_))));
     }
 
     #[tokio::test(flavor = "current_thread")]
@@ -1360,15 +3728,15 @@ mod tests {
         .unwrap();
 
         // Unlinked keys and transactions
-        let (_key_addr_unused, _) = wallet.generate_payment_address().await;
+        let (_key_addr_unused, _) = wallet.generate_payment_address().await.unwrap();
         wallet
             .save_transaction_to_wallet(out_p_non_pay, key_addr_non_pay)
             .await
             .unwrap();
 
         // Store payments
-        let (key_addr1, _) = wallet.generate_payment_address().await;
-        let (key_addr2, _) = wallet.generate_payment_address().await;
+        let (key_addr1, _) = wallet.generate_payment_address().await.unwrap();
+        let (key_addr2, _) = wallet.generate_payment_address().await.unwrap();
         let stored_usable = wallet
             .save_usable_payments_to_wallet(
                 vec![
@@ -1385,11 +3753,16 @@ mod tests {
         // Pay out
         let (tx_cons, fetched_amount, tx_used) =
             wallet.fetch_inputs_for_payment(amount_out).await.unwrap();
-        let tx_ins = wallet.consume_inputs_for_payment(tx_cons, tx_used).await;
+        let tx_ins = wallet
+            .consume_inputs_for_payment(tx_cons, tx_used)
+            .await
+            .unwrap();
 
         // clean up db
-        let (destroyed_keys, destroyed_txs) =
-            wallet.destroy_spent_transactions_and_keys(None).await;
+        let (destroyed_keys, destroyed_txs) = wallet
+            .destroy_spent_transactions_and_keys(None)
+            .await
+            .unwrap();
 
         //
         // Assert
@@ -1410,4 +3783,250 @@ mod tests {
                 .collect();
         assert_eq!(destroyed_txs, expected_destroyedkeys);
     }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks reverting a payment gives back its inputs and drops its change
+    async fn revert_payment_restores_inputs() {
+        //Arrange
+        let mut wallet = WalletDb::new(
+            DbMode::InMemory,
+            None,
+            Some("Test Passphrase".to_owned()),
+            None,
+        )
+        .unwrap();
+        let (key_addr, _) = wallet.generate_payment_address().await.unwrap();
+        let funds_out_p = OutPoint::new("funds".to_owned(), 0);
+        let change_out_p = OutPoint::new("payment".to_owned(), 1);
+        let payment = |out_p: &OutPoint, amount| {
+            (out_p.clone(), Asset::token_u64(amount), key_addr.clone(), 0)
+        };
+        wallet
+            .save_usable_payments_to_wallet(vec![payment(&funds_out_p, 5)], 0)
+            .await
+            .unwrap();
+        let (tx_cons, _, tx_used) = wallet
+            .fetch_inputs_for_payment(Asset::token_u64(4))
+            .await
+            .unwrap();
+        wallet
+            .consume_inputs_for_payment(tx_cons, tx_used)
+            .await
+            .unwrap();
+        wallet
+            .save_usable_payments_to_wallet(vec![payment(&change_out_p, 1)], 0)
+            .await
+            .unwrap();
+        let paid_total = wallet.get_fund_store().unwrap().running_total().tokens.0;
+
+        //Act
+        wallet
+            .revert_payment(vec![funds_out_p.clone()], vec![change_out_p.clone()])
+            .await
+            .unwrap();
+        let fund_store = wallet.get_fund_store().unwrap();
+
+        //Assert
+        assert_eq!(paid_total, 1);
+        assert_eq!(fund_store.running_total().tokens.0, 5);
+        assert!(fund_store.transactions().contains_key(&funds_out_p));
+        assert!(!fund_store.transactions().contains_key(&change_out_p));
+        assert!(fund_store.spent_transactions().is_empty());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    /// Checks the change is paid to a new address linked to its payment, or to the
+    /// spent address when reused
+    async fn change_address_new_or_reused() {
+        //Arrange
+        let mut wallet = wallet_with_payments(&[5, 5]).await;
+        let spent_address = wallet.get_known_addresses().unwrap()[0].clone();
+        let payment = || {
+            vec![TxOut::new_token_amount(
+                "recipient".to_owned(),
+                TokenAmount(3),
+                None,
+            )]
+        };
+
+        //Act
+        let (tx_ins, tx_outs) = wallet
+            .fetch_tx_ins_and_tx_outs(Asset::token_u64(3), payment())
+            .await
+            .unwrap();
+        let change_address = tx_outs[1].script_public_key.clone().unwrap();
+        let transaction = construct_tx_core(tx_ins, tx_outs, None);
+        wallet
+            .store_payment_transaction(transaction.clone(), 0)
+            .await
+            .unwrap();
+        let mut wallet = wallet.with_change_address_reuse(true);
+        let (_, reused_outs) = wallet
+            .fetch_tx_ins_and_tx_outs(Asset::token_u64(3), payment())
+            .await
+            .unwrap();
+
+        //Assert
+        let change_addresses = wallet.get_change_addresses().unwrap();
+        assert_ne!(change_address, spent_address);
+        assert_eq!(
+            change_addresses.get(&change_address),
+            Some(&construct_tx_hash(&transaction))
+        );
+        assert_eq!(change_addresses.len(), 1);
+        assert_eq!(reused_outs[1].script_public_key, Some(spent_address));
+    }
+
+    /// Sum of the tokens held by the given assets
+    fn tokens_sum<'a>(assets: impl Iterator<Item = &'a Asset>) -> u64 {
+        assets.map(|a| a.token_amount().0).sum()
+    }
+
+    /// Wallet with one payment address holding the given token amounts
+    async fn wallet_with_payments(amounts: &[u64]) -> WalletDb {
+        let builder = amounts
+            .iter()
+            .fold(WalletBuilder::new(), |builder, amount| {
+                builder.with_outpoints(1, TokenAmount(*amount))
+            });
+        builder.build().await.0
+    }
+
+    /// Run the future to completion on a new runtime
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        /// Checks the running total and pages always match the unspent outpoints
+        fn fund_store_running_total_matches_outpoints(
+            amounts in prop::collection::vec(1..1_000_000u64, 1..60),
+            ops in prop::collection::vec((0..60usize, any::<bool>()), 0..200),
+        ) {
+            let mut fund_store = FundStore::default();
+            let mut received = BTreeMap::new();
+            let mut spent = BTreeSet::new();
+
+            for (index, receive) in ops {
+                let index = index % amounts.len();
+                let out_p = OutPoint::new(format!("{index:04}"), 0);
+                if receive && !spent.contains(&out_p) {
+                    fund_store.store_tx(out_p.clone(), Asset::token_u64(amounts[index]));
+                    received.insert(out_p, amounts[index]);
+                } else if !receive && received.contains_key(&out_p) {
+                    fund_store.spend_tx(&out_p);
+                    spent.insert(out_p);
+                }
+
+                let unspent_total = tokens_sum(fund_store.transactions().values());
+                let spent_total = tokens_sum(fund_store.spent_transactions().values());
+                let paged: BTreeMap<_, _> = (0..fund_store.transaction_pages_len())
+                    .flat_map(|page| fund_store.transaction_pages(page).clone())
+                    .collect();
+
+                prop_assert_eq!(fund_store.running_total().tokens.0, unspent_total);
+                prop_assert_eq!(
+                    unspent_total + spent_total,
+                    received.values().sum::<u64>()
+                );
+                prop_assert_eq!(&paged, fund_store.transactions());
+                prop_assert!((0..fund_store.transaction_pages_len())
+                    .all(|page| fund_store.transaction_pages(page).len() <= ENTRIES_PER_PAGE));
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        /// Checks selected inputs cover the requested amount, without selecting needless inputs
+        fn fetch_inputs_cover_requested_amount(
+            amounts in prop::collection::vec(1..10_000u64, 1..30),
+            requested_ratio in 0.0..1.2f64,
+        ) {
+            let total: u64 = amounts.iter().sum();
+            let requested = ((total as f64 * requested_ratio) as u64).max(1);
+
+            let (result, fund_store) = block_on(async {
+                let wallet = wallet_with_payments(&amounts).await;
+                let result = wallet.fetch_inputs_for_payment(Asset::token_u64(requested)).await;
+                (result, wallet.get_fund_store().unwrap())
+            });
+
+            if requested > total {
+                prop_assert!(matches!(
+                    result,
+                    Err(WalletDbError::InsufficientFundsError)
+                ));
+            } else {
+                let (tx_cons, fetched, tx_used) = result.unwrap();
+                let used: Vec<_> = tx_used
+                    .iter()
+                    .map(|(out_p, _)| fund_store.transactions()[out_p].token_amount().0)
+                    .collect();
+                let used_set: BTreeSet<_> = tx_used.iter().map(|(out_p, _)| out_p).collect();
+
+                prop_assert_eq!(tx_cons.len(), tx_used.len());
+                prop_assert_eq!(used_set.len(), tx_used.len());
+                prop_assert_eq!(fetched.token_amount().0, used.iter().sum::<u64>());
+                prop_assert!(fetched.token_amount().0 >= requested);
+                prop_assert!(fetched.token_amount().0 - used.last().unwrap() < requested);
+            }
+        }
+
+        #[test]
+        /// Checks a sequence of payments never creates nor loses tokens
+        fn payments_with_change_conserve_tokens(
+            amounts in prop::collection::vec(1..10_000u64, 1..30),
+            payments in prop::collection::vec(1..20_000u64, 1..10),
+        ) {
+            let (steps, final_total) = block_on(async {
+                let mut wallet = wallet_with_payments(&amounts).await;
+                let mut steps = Vec::new();
+                for payment in payments {
+                    let before = wallet.get_fund_store().unwrap().running_total().tokens.0;
+                    let tx_out =
+                        TxOut::new_token_amount("payee".to_owned(), TokenAmount(payment), None);
+                    let result = wallet
+                        .fetch_tx_ins_and_tx_outs_provided_excess(
+                            Asset::token_u64(payment),
+                            vec![tx_out],
+                            Some("excess".to_owned()),
+                        )
+                        .await;
+                    let after = wallet.get_fund_store().unwrap().running_total().tokens.0;
+                    steps.push((payment, before, after, result.ok()));
+                }
+                (steps, tokens_sum(wallet.get_fund_store().unwrap().transactions().values()))
+            });
+
+            let mut spent_total = 0;
+            for (payment, before, after, result) in &steps {
+                match result {
+                    Some((tx_ins, tx_outs)) => {
+                        let outputs = tokens_sum(tx_outs.iter().map(|tx_out| &tx_out.value));
+                        let change = tokens_sum(tx_outs.iter().skip(1).map(|tx_out| &tx_out.value));
+                        prop_assert!(!tx_ins.is_empty());
+                        prop_assert_eq!(tx_outs[0].value.token_amount().0, *payment);
+                        prop_assert_eq!(outputs, before - after);
+                        prop_assert_eq!(change, before - after - payment);
+                        spent_total += before - after;
+                    }
+                    None => {
+                        prop_assert!(payment > before);
+                        prop_assert_eq!(before, after);
+                    }
+                }
+            }
+            prop_assert_eq!(final_total + spent_total, amounts.iter().sum::<u64>());
+        }
+    }
 }
@@ -0,0 +1,197 @@
+//! Multisig addresses shared by a wallet with co-signers.
+//!
+//! An m-of-n multisig address is the address of a `SpendCondition::Multisig`
+//! over the public keys of its n signers, sorted so every co-signer derives the
+//! same address whatever the order the keys are exchanged in. The wallet holds
+//! one of the key-pairs, and stores the public keys of its co-signers along with
+//! the threshold.
+//!
+//! To spend an output paid to the address, each signer creates a partial
+//! signature over the output spent and the outputs of the spending transaction.
+//! Once at least m partial signatures are collected, they are combined into the
+//! witness of the input spending the output.
+
+use super::{Result, WalletDbError};
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use crate::spend_script::{condition_address, SpendCondition, SpendWitness, MAX_MULTISIG_KEYS};
+use bincode::{deserialize, serialize};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tw_chain::crypto::sign_ed25519::{PublicKey, Signature};
+
+/// Prefix of the keys of the multisig addresses
+pub const MULTISIG_PREFIX: &str = "Multisig_";
+
+/// Multisig address the wallet holds one of the keys of
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigAddress {
+    /// Number of signatures needed to spend
+    pub threshold: usize,
+    /// Public keys of all the signers, sorted
+    pub keys: Vec<PublicKey>,
+    /// Wallet address whose key-pair is one of the signers
+    pub own_address: String,
+}
+
+impl MultisigAddress {
+    /// Multisig address of the given signers
+    ///
+    /// ### Arguments
+    ///
+    /// * `threshold`   - Number of signatures needed to spend.
+    /// * `keys`        - Public keys of all the signers, in any order.
+    /// * `own_address` - Wallet address whose key-pair is one of the signers.
+    pub fn new(threshold: usize, mut keys: Vec<PublicKey>, own_address: String) -> Result<Self> {
+        keys.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        keys.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        check_multisig_shape(threshold, keys.len())?;
+        Ok(Self {
+            threshold,
+            keys,
+            own_address,
+        })
+    }
+
+    /// Condition the outputs paid to the address are locked to
+    pub fn condition(&self) -> SpendCondition {
+        SpendCondition::Multisig {
+            threshold: self.threshold,
+            keys: self.keys.clone(),
+        }
+    }
+
+    /// Address the co-signers are paid to
+    pub fn address(&self) -> String {
+        condition_address(&self.condition())
+    }
+
+    /// Witness of the partial signatures of distinct signers that verify
+    ///
+    /// ### Arguments
+    ///
+    /// * `signable_data` - Data signed for the spending input.
+    /// * `partials`      - Partial signatures collected from the signers.
+    pub fn combine_signatures(
+        &self,
+        signable_data: &str,
+        partials: Vec<(PublicKey, Signature)>,
+    ) -> Result<SpendWitness> {
+        let signers: BTreeSet<_> = self.keys.iter().map(|k| k.as_ref()).collect();
+        let mut signed = BTreeSet::new();
+        let mut witness = SpendWitness::default();
+        for (public_key, signature) in partials {
+            let verified = UnparsedPublicKey::new(&ED25519, public_key.as_ref())
+                .verify(signable_data.as_bytes(), signature.as_ref())
+                .is_ok();
+            if verified
+                && signers.contains(public_key.as_ref())
+                && signed.insert(public_key.as_ref().to_vec())
+            {
+                witness.signatures.push((public_key, signature));
+            }
+        }
+
+        if witness.signatures.len() < self.threshold {
+            return Err(WalletDbError::MultisigError("Not enough valid signatures"));
+        }
+        witness.signatures.truncate(self.threshold);
+        Ok(witness)
+    }
+}
+
+/// Check an m-of-n multisig can be spent and is within the condition limits
+///
+/// ### Arguments
+///
+/// * `threshold` - Number of signatures needed to spend, m.
+/// * `signers`   - Number of distinct signers, n.
+pub fn check_multisig_shape(threshold: usize, signers: usize) -> Result<()> {
+    if threshold == 0 || threshold > signers || signers > MAX_MULTISIG_KEYS {
+        return Err(WalletDbError::MultisigError(
+            "Threshold must be 1 to the number of signers, of at most 16",
+        ));
+    }
+    Ok(())
+}
+
+/// Multisig address stored at an address, if any
+///
+/// ### Arguments
+///
+/// * `db`      - Wallet database.
+/// * `address` - Multisig address.
+pub fn get_multisig_address(db: &SimpleDb, address: &str) -> Result<Option<MultisigAddress>> {
+    match db.get_cf(DB_COL_DEFAULT, multisig_key(address))? {
+        Some(multisig) => Ok(Some(deserialize(&multisig)?)),
+        None => Ok(None),
+    }
+}
+
+/// All the multisig addresses of the wallet
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_multisig_addresses(db: &SimpleDb) -> Result<BTreeMap<String, MultisigAddress>> {
+    db.iter_cf_clone(DB_COL_DEFAULT)?
+        .filter_map(|(key, value)| {
+            let address = key.strip_prefix(MULTISIG_PREFIX.as_bytes())?;
+            let address = String::from_utf8(address.to_vec()).ok()?;
+            Some(deserialize(&value).map(|multisig| (address, multisig)))
+        })
+        .collect::<bincode::Result<_>>()
+        .map_err(Into::into)
+}
+
+/// Save a multisig address
+///
+/// ### Arguments
+///
+/// * `batch`    - Write batch of the wallet database.
+/// * `multisig` - Multisig address, stored at its address.
+pub fn save_multisig_address(batch: &mut SimpleDbWriteBatch, multisig: &MultisigAddress) {
+    let key = multisig_key(&multisig.address());
+    batch.put_cf(DB_COL_DEFAULT, key, serialize(multisig).unwrap());
+}
+
+/// Key of a multisig address
+fn multisig_key(address: &str) -> String {
+    format!("{MULTISIG_PREFIX}{address}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tw_chain::crypto::sign_ed25519 as sign;
+
+    #[test]
+    /// Checks the address ignores the key order and signatures combine up to the threshold
+    fn multisig_combines_threshold_signatures() {
+        //Arrange
+        let keys: Vec<_> = (0..3).map(|_| sign::gen_keypair()).collect();
+        let public_keys: Vec<_> = keys.iter().map(|(pk, _)| *pk).collect();
+        let reversed = public_keys.iter().rev().copied().collect();
+        let multisig = MultisigAddress::new(2, public_keys.clone(), "own".to_owned()).unwrap();
+        let signable_data = "signable";
+        let partial = |n: usize| {
+            let (public_key, secret_key) = &keys[n];
+            let signature = sign::sign_detached(signable_data.as_bytes(), secret_key);
+            (*public_key, signature)
+        };
+        let (stranger, stranger_sk) = sign::gen_keypair();
+        let forged = (stranger, sign::sign_detached(b"signable", &stranger_sk));
+
+        //Act
+        let same = MultisigAddress::new(2, reversed, "own".to_owned()).unwrap();
+        let too_few =
+            multisig.combine_signatures(signable_data, vec![partial(0), partial(0), forged]);
+        let combined = multisig.combine_signatures(signable_data, vec![partial(0), partial(2)]);
+
+        //Assert
+        assert_eq!(same.address(), multisig.address());
+        assert!(matches!(too_few, Err(WalletDbError::MultisigError(_))));
+        assert_eq!(combined.unwrap().signatures.len(), 2);
+        assert!(MultisigAddress::new(4, public_keys, "own".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Reservation of the outputs selected for payments not yet sent.
+//!
+//! Selecting the inputs of a payment and spending them are two steps, so two
+//! payments constructed at the same time, e.g. from concurrent API calls, could
+//! otherwise select the same outputs. The outputs selected for a payment are
+//! reserved in the same write as the selection, and are not selected again
+//! until the reservation is:
+//!
+//! * committed, when the payment is sent and its inputs are spent,
+//! * rolled back, when the payment is abandoned before it is sent.
+//!
+//! A payment rejected once sent is reverted with its inputs given back to the
+//! wallet instead. Reservations neither committed nor rolled back, e.g. when
+//! the node stops in between, expire.
+
+use super::Result;
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Storage key of the outputs reserved for payments
+pub const PENDING_SPENDS_KEY: &str = "PendingSpends";
+
+/// Seconds before a reservation neither committed nor rolled back expires
+pub const RESERVATION_TTL: i64 = 300;
+
+/// Outputs reserved for payments, with the timestamp their reservation expires at
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSpends {
+    expiries: BTreeMap<OutPoint, i64>,
+}
+
+impl PendingSpends {
+    /// Reserve outputs until the given timestamp
+    ///
+    /// ### Arguments
+    ///
+    /// * `outputs` - Outputs selected for a payment.
+    /// * `expiry`  - Timestamp the reservation expires at.
+    pub fn reserve(&mut self, outputs: impl IntoIterator<Item = OutPoint>, expiry: i64) {
+        self.expiries
+            .extend(outputs.into_iter().map(|out_p| (out_p, expiry)));
+    }
+
+    /// Release the reservation of outputs, committed or rolled back
+    ///
+    /// ### Arguments
+    ///
+    /// * `outputs` - Outputs of the payment.
+    pub fn release<'a>(&mut self, outputs: impl IntoIterator<Item = &'a OutPoint>) -> usize {
+        let before = self.expiries.len();
+        for out_p in outputs {
+            self.expiries.remove(out_p);
+        }
+        before - self.expiries.len()
+    }
+
+    /// Drop the reservations expired at the given time, returning their number
+    ///
+    /// ### Arguments
+    ///
+    /// * `now` - Current timestamp.
+    pub fn prune_expired(&mut self, now: i64) -> usize {
+        let before = self.expiries.len();
+        self.expiries.retain(|_, expiry| *expiry > now);
+        before - self.expiries.len()
+    }
+
+    /// Whether an output is reserved
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p` - Output of the wallet.
+    pub fn is_reserved(&self, out_p: &OutPoint) -> bool {
+        self.expiries.contains_key(out_p)
+    }
+
+    /// Number of outputs reserved
+    pub fn len(&self) -> usize {
+        self.expiries.len()
+    }
+
+    /// Whether no output is reserved
+    pub fn is_empty(&self) -> bool {
+        self.expiries.is_empty()
+    }
+}
+
+/// Outputs reserved for payments
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_pending_spends(db: &SimpleDb) -> Result<PendingSpends> {
+    match db.get_cf(DB_COL_DEFAULT, PENDING_SPENDS_KEY)? {
+        Some(pending_spends) => Ok(deserialize(&pending_spends)?),
+        None => Ok(Default::default()),
+    }
+}
+
+/// Save the outputs reserved for payments
+///
+/// ### Arguments
+///
+/// * `batch`          - Write batch of the wallet database.
+/// * `pending_spends` - Outputs reserved.
+pub fn set_pending_spends(batch: &mut SimpleDbWriteBatch, pending_spends: &PendingSpends) {
+    let pending_spends = serialize(pending_spends).unwrap();
+    batch.put_cf(DB_COL_DEFAULT, PENDING_SPENDS_KEY, pending_spends);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks reservations are released or expire
+    fn pending_spends_release_and_expire() {
+        //Arrange
+        let out_p = |n| OutPoint::new("tx_hash".to_owned(), n);
+        let mut pending_spends = PendingSpends::default();
+        pending_spends.reserve(vec![out_p(0), out_p(1)], 100);
+        pending_spends.reserve(vec![out_p(2)], 200);
+
+        //Act
+        let released = pending_spends.release(&[out_p(1), out_p(3)]);
+        let expired = pending_spends.prune_expired(100);
+
+        //Assert
+        assert_eq!((released, expired), (1, 1));
+        assert!(!pending_spends.is_reserved(&out_p(0)));
+        assert!(pending_spends.is_reserved(&out_p(2)));
+        assert_eq!(pending_spends.len(), 1);
+    }
+}
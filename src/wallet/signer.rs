@@ -0,0 +1,355 @@
+//! Signing of wallet inputs.
+//!
+//! Wallets sign inputs with the secret keys they store by default. A `Signer`
+//! set on the wallet signs instead, so the secret keys can be held elsewhere.
+//! `RemoteSigner` forwards the digests to a signing service, such as a front
+//! end to an HSM, over TCP.
+//!
+//! Each request is a JSON line `{"key_id", "digest", "nonce", "mac"}` and each
+//! response a JSON line `{"signature", "mac"}` or `{"error"}`. The key id is the
+//! address of the key, binary values are hex encoded, and both MACs are
+//! HMAC-SHA256 with the shared authentication key: over `nonce:key_id:digest`
+//! for the request and over `nonce:signature` for the response.
+
+use crate::configurations::RemoteSignerConfig;
+use ring::hmac;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{error, fmt};
+use tracing::warn;
+use tw_chain::crypto::sign_ed25519::{PublicKey, Signature};
+
+/// Maximum length of a signing service response line
+const MAX_RESPONSE_LEN: u64 = 4096;
+
+/// Result wrapper for signer errors
+pub type Result<T> = std::result::Result<T, SignerError>;
+
+/// Errors that occur while signing
+#[derive(Debug)]
+pub enum SignerError {
+    IO(io::Error),
+    Serialization(serde_json::Error),
+    Config(String),
+    Rejected(String),
+    InvalidResponse(&'static str),
+    Unavailable(String),
+}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "I/O Error: {err}"),
+            Self::Serialization(err) => write!(f, "Serialization Error: {err}"),
+            Self::Config(err) => write!(f, "Config Error: {err}"),
+            Self::Rejected(err) => write!(f, "Signing rejected: {err}"),
+            Self::InvalidResponse(err) => write!(f, "Invalid signing response: {err}"),
+            Self::Unavailable(err) => write!(f, "No signing service available: {err}"),
+        }
+    }
+}
+
+impl error::Error for SignerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::IO(ref e) => Some(e),
+            Self::Serialization(ref e) => Some(e),
+            Self::Config(_) => None,
+            Self::Rejected(_) => None,
+            Self::InvalidResponse(_) => None,
+            Self::Unavailable(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SignerError {
+    fn from(other: io::Error) -> Self {
+        Self::IO(other)
+    }
+}
+
+impl From<serde_json::Error> for SignerError {
+    fn from(other: serde_json::Error) -> Self {
+        Self::Serialization(other)
+    }
+}
+
+/// Signer of input digests for keys held outside the wallet
+pub trait Signer: fmt::Debug + Send + Sync {
+    /// Sign a digest with the key of an address
+    ///
+    /// ### Arguments
+    ///
+    /// * `key_id`     - Address of the key.
+    /// * `public_key` - Public key of the address, to check the signature.
+    /// * `digest`     - Digest to sign.
+    fn sign(&self, key_id: &str, public_key: &PublicKey, digest: &[u8]) -> Result<Signature>;
+}
+
+/// Request sent to the signing service
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignRequest {
+    pub key_id: String,
+    pub digest: String,
+    pub nonce: String,
+    pub mac: String,
+}
+
+/// Response of the signing service
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SignResponse {
+    pub signature: Option<String>,
+    pub mac: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Signer forwarding digests to a signing service, failing over between its instances
+pub struct RemoteSigner {
+    endpoints: Vec<String>,
+    auth_key: hmac::Key,
+    timeout: Duration,
+    preferred: AtomicUsize,
+}
+
+impl fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("endpoints", &self.endpoints)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RemoteSigner {
+    /// Create a signer for the configured signing service
+    ///
+    /// ### Arguments
+    ///
+    /// * `config` - Signing service endpoints, authentication key and timeout.
+    pub fn new(config: &RemoteSignerConfig) -> Result<Self> {
+        if config.endpoints.is_empty() {
+            return Err(SignerError::Config("No signing endpoints".to_owned()));
+        }
+        let auth_key = hex::decode(&config.auth_key)
+            .map_err(|e| SignerError::Config(format!("Invalid auth key: {e}")))?;
+
+        Ok(Self {
+            endpoints: config.endpoints.clone(),
+            auth_key: hmac::Key::new(hmac::HMAC_SHA256, &auth_key),
+            timeout: Duration::from_millis(config.timeout_ms),
+            preferred: AtomicUsize::new(0),
+        })
+    }
+
+    /// Request a signature from one instance of the signing service
+    ///
+    /// ### Arguments
+    ///
+    /// * `endpoint` - Address of the instance.
+    /// * `key_id`   - Address of the key.
+    /// * `digest`   - Digest to sign.
+    fn sign_at(&self, endpoint: &str, key_id: &str, digest: &[u8]) -> Result<Signature> {
+        let addr = endpoint
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| SignerError::Config(format!("Invalid endpoint: {endpoint}")))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        let digest = hex::encode(digest);
+        let mac = hmac::sign(
+            &self.auth_key,
+            request_mac_data(&nonce, key_id, &digest).as_bytes(),
+        );
+        let request = SignRequest {
+            key_id: key_id.to_owned(),
+            digest,
+            nonce: nonce.clone(),
+            mac: hex::encode(mac.as_ref()),
+        };
+        let mut request = serde_json::to_vec(&request)?;
+        request.push(b'\n');
+        stream.write_all(&request)?;
+
+        let mut line = String::new();
+        BufReader::new(stream.take(MAX_RESPONSE_LEN)).read_line(&mut line)?;
+        let response: SignResponse = serde_json::from_str(&line)?;
+        if let Some(error) = response.error {
+            return Err(SignerError::Rejected(error));
+        }
+
+        let (signature, mac) = match (response.signature, response.mac) {
+            (Some(signature), Some(mac)) => (signature, mac),
+            _ => return Err(SignerError::InvalidResponse("Missing signature")),
+        };
+        let mac = hex::decode(mac).map_err(|_| SignerError::InvalidResponse("Invalid MAC"))?;
+        hmac::verify(
+            &self.auth_key,
+            response_mac_data(&nonce, &signature).as_bytes(),
+            &mac,
+        )
+        .map_err(|_| SignerError::InvalidResponse("Unauthenticated response"))?;
+
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|s| Signature::from_slice(&s))
+            .ok_or(SignerError::InvalidResponse("Invalid signature"))?;
+        Ok(signature)
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn sign(&self, key_id: &str, public_key: &PublicKey, digest: &[u8]) -> Result<Signature> {
+        let count = self.endpoints.len();
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let mut errors = Vec::new();
+
+        for idx in (0..count).map(|i| (preferred + i) % count) {
+            let endpoint = &self.endpoints[idx];
+            let result = self
+                .sign_at(endpoint, key_id, digest)
+                .and_then(|signature| match verify(public_key, digest, &signature) {
+                    true => Ok(signature),
+                    false => Err(SignerError::InvalidResponse("Signature does not verify")),
+                });
+
+            match result {
+                Ok(signature) => {
+                    self.preferred.store(idx, Ordering::Relaxed);
+                    return Ok(signature);
+                }
+                Err(SignerError::Rejected(error)) => return Err(SignerError::Rejected(error)),
+                Err(e) => {
+                    warn!("Signing service {} failed: {}", endpoint, e);
+                    errors.push(format!("{endpoint}: {e}"));
+                }
+            }
+        }
+
+        Err(SignerError::Unavailable(errors.join(", ")))
+    }
+}
+
+/// Data authenticated by the MAC of a request
+pub fn request_mac_data(nonce: &str, key_id: &str, digest: &str) -> String {
+    format!("{nonce}:{key_id}:{digest}")
+}
+
+/// Data authenticated by the MAC of a response
+pub fn response_mac_data(nonce: &str, signature: &str) -> String {
+    format!("{nonce}:{signature}")
+}
+
+/// Check a signature of a digest
+fn verify(public_key: &PublicKey, digest: &[u8], signature: &Signature) -> bool {
+    let public_key = UnparsedPublicKey::new(&ED25519, public_key.as_ref());
+    public_key.verify(digest, signature.as_ref()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use tw_chain::crypto::sign_ed25519::{self as sign, SecretKey};
+
+    const AUTH_KEY: &str = "00112233445566778899aabbccddeeff";
+
+    /// Serve one signing request with the given key, authenticating with `auth_key`
+    fn spawn_signing_service(secret_key: SecretKey, auth_key: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let auth_key = hmac::Key::new(hmac::HMAC_SHA256, &hex::decode(auth_key).unwrap());
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let request: SignRequest = serde_json::from_str(&line).unwrap();
+
+            let data = request_mac_data(&request.nonce, &request.key_id, &request.digest);
+            let mac = hex::decode(&request.mac).unwrap();
+            let response = match hmac::verify(&auth_key, data.as_bytes(), &mac) {
+                Ok(()) => {
+                    let digest = hex::decode(&request.digest).unwrap();
+                    let signature = hex::encode(sign::sign_detached(&digest, &secret_key));
+                    let data = response_mac_data(&request.nonce, &signature);
+                    let mac = hmac::sign(&auth_key, data.as_bytes());
+                    SignResponse {
+                        signature: Some(signature),
+                        mac: Some(hex::encode(mac.as_ref())),
+                        error: None,
+                    }
+                }
+                Err(_) => SignResponse {
+                    error: Some("Unauthenticated request".to_owned()),
+                    ..Default::default()
+                },
+            };
+
+            let mut response = serde_json::to_vec(&response).unwrap();
+            response.push(b'\n');
+            (&stream).write_all(&response).unwrap();
+        });
+        endpoint
+    }
+
+    /// Address of a closed port
+    fn closed_endpoint() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    fn signer(endpoints: Vec<String>) -> RemoteSigner {
+        RemoteSigner::new(&RemoteSignerConfig {
+            endpoints,
+            auth_key: AUTH_KEY.to_owned(),
+            timeout_ms: 1000,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    /// Checks signatures are requested from the next instance when one fails
+    fn remote_sign_failover() {
+        let (public_key, secret_key) = sign::gen_keypair();
+        let service = spawn_signing_service(secret_key, AUTH_KEY);
+        let signer = signer(vec![closed_endpoint(), service]);
+
+        let signature = signer.sign("address", &public_key, b"digest").unwrap();
+
+        assert!(verify(&public_key, b"digest", &signature));
+        assert_eq!(signer.preferred.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    /// Checks requests with another authentication key are rejected
+    fn remote_sign_unauthenticated() {
+        let (public_key, secret_key) = sign::gen_keypair();
+        let service = spawn_signing_service(secret_key, "ffeeddccbbaa99887766554433221100");
+        let signer = signer(vec![service]);
+
+        let result = signer.sign("address", &public_key, b"digest");
+
+        assert!(matches!(result, Err(SignerError::Rejected(_))));
+    }
+
+    #[test]
+    /// Checks signatures made with another key are not accepted
+    fn remote_sign_wrong_key() {
+        let (public_key, _) = sign::gen_keypair();
+        let (_, other_secret_key) = sign::gen_keypair();
+        let service = spawn_signing_service(other_secret_key, AUTH_KEY);
+        let signer = signer(vec![service]);
+
+        let result = signer.sign("address", &public_key, b"digest");
+
+        assert!(matches!(result, Err(SignerError::Unavailable(_))));
+    }
+}
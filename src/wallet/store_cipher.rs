@@ -0,0 +1,135 @@
+//! Encryption of the whole wallet database.
+//!
+//! Every value of the wallet database, e.g. the fund store, transaction
+//! stores, history or labels, is sealed with the wallet master key. Only the
+//! entries read before the passphrase is checked stay in clear: the database
+//! version and network, and the master key store with its key derivation.
+//! The keys of the entries are not encrypted. Wallets written before their
+//! values were encrypted are encrypted in place when first unlocked.
+
+use super::{encrypt_store, Result, MASTER_KEY_KDF_KEY, MASTER_KEY_STORE_KEY};
+use crate::constants::{DB_NETWORK_ID_KEY, DB_VERSION_KEY};
+use crate::db_utils::{SimpleDb, ValueCipher, DB_COL_DEFAULT};
+use std::sync::Arc;
+use tw_chain::crypto::secretbox_chacha20_poly1305 as secretbox;
+
+/// Key marking the values of the wallet database as encrypted
+pub const STORE_ENCRYPTED_KEY: &str = "StoreEncrypted";
+
+/// Keys of the entries kept in clear, read to unlock the wallet
+const CLEAR_KEYS: &[&str] = &[
+    DB_VERSION_KEY,
+    DB_NETWORK_ID_KEY,
+    MASTER_KEY_STORE_KEY,
+    MASTER_KEY_KDF_KEY,
+    STORE_ENCRYPTED_KEY,
+];
+
+/// Cipher of the wallet database values, sealing them with the master key
+pub struct StoreCipher {
+    master_key: secretbox::Key,
+}
+
+impl ValueCipher for StoreCipher {
+    fn encrypt(&self, key: &[u8], value: &[u8]) -> Vec<u8> {
+        if is_clear_key(key) {
+            return value.to_vec();
+        }
+        encrypt_store(value.to_vec(), &self.master_key)
+    }
+
+    fn decrypt(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        if is_clear_key(key) {
+            return Some(value.to_vec());
+        }
+        if value.len() < secretbox::NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = value.split_at(secretbox::NONCE_LEN);
+        let nonce = secretbox::Nonce::from_slice(nonce)?;
+        secretbox::open(sealed.to_vec(), &nonce, &self.master_key)
+    }
+}
+
+/// Whether the entry at the given key is kept in clear
+fn is_clear_key(key: &[u8]) -> bool {
+    CLEAR_KEYS.iter().any(|clear| clear.as_bytes() == key)
+}
+
+/// Stop decrypting the values of the wallet database
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn lock_store(db: &mut SimpleDb) {
+    db.set_value_cipher(None);
+}
+
+/// Decrypt the values of the wallet database with its master key,
+/// encrypting them first if they are still in clear
+///
+/// ### Arguments
+///
+/// * `db`         - Wallet database, locked.
+/// * `master_key` - Master key of the wallet.
+pub fn unlock_store(db: &mut SimpleDb, master_key: &secretbox::Key) -> Result<()> {
+    let in_clear = db.get_cf(DB_COL_DEFAULT, STORE_ENCRYPTED_KEY)?.is_none();
+    let clear_entries: Vec<_> = if in_clear {
+        db.iter_cf_clone(DB_COL_DEFAULT)?.collect()
+    } else {
+        Vec::new()
+    };
+
+    let cipher = StoreCipher {
+        master_key: master_key.clone(),
+    };
+    db.set_value_cipher(Some(Arc::new(cipher)));
+
+    if in_clear {
+        let mut batch = db.batch_writer();
+        for (key, value) in clear_entries {
+            batch.put_cf(DB_COL_DEFAULT, key, value);
+        }
+        batch.put_cf(DB_COL_DEFAULT, STORE_ENCRYPTED_KEY, [1u8]);
+        let batch = batch.done();
+        db.write(batch)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks values in clear are encrypted when unlocked, apart from the ones read to unlock
+    fn unlock_encrypts_values_in_clear() {
+        //Arrange
+        let mut db = SimpleDb::new_in_memory(&[], None).unwrap();
+        db.put_cf(DB_COL_DEFAULT, "AddressMetadata_a", b"salary")
+            .unwrap();
+        db.put_cf(DB_COL_DEFAULT, MASTER_KEY_KDF_KEY, b"kdf")
+            .unwrap();
+        let master_key = secretbox::gen_key();
+
+        //Act
+        unlock_store(&mut db, &master_key).unwrap();
+        let unlocked_value = db.get_cf(DB_COL_DEFAULT, "AddressMetadata_a").unwrap();
+        db.put_cf(DB_COL_DEFAULT, "FundStore", b"funds").unwrap();
+        lock_store(&mut db);
+        let locked_value = db.get_cf(DB_COL_DEFAULT, "AddressMetadata_a").unwrap();
+        let locked_new_value = db.get_cf(DB_COL_DEFAULT, "FundStore").unwrap();
+        let locked_kdf = db.get_cf(DB_COL_DEFAULT, MASTER_KEY_KDF_KEY).unwrap();
+        unlock_store(&mut db, &secretbox::gen_key()).unwrap();
+        let wrong_key_value = db.get_cf(DB_COL_DEFAULT, "AddressMetadata_a");
+        let wrong_key_iter = db.iter_cf_clone(DB_COL_DEFAULT);
+
+        //Assert
+        assert_eq!(unlocked_value.as_deref(), Some(&b"salary"[..]));
+        assert_ne!(locked_value.as_deref(), Some(&b"salary"[..]));
+        assert_ne!(locked_new_value.as_deref(), Some(&b"funds"[..]));
+        assert_eq!(locked_kdf.as_deref(), Some(&b"kdf"[..]));
+        assert!(wrong_key_value.is_err());
+        assert!(wrong_key_iter.is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Encrypted synchronisation of the wallets of two user nodes of the same owner.
+//!
+//! Both nodes are configured with the address of the other and a shared sync
+//! secret. The messages are exchanged over the comms layer, sealed with
+//! ChaCha20-Poly1305 under a key derived from the secret, so the transport only
+//! sees ciphertext:
+//!
+//! 1. A node sends the summary of its wallet (addresses, unspent and spent
+//!    outputs) on each block notified.
+//! 2. The peer replies with the update the summary lacks, along with its own
+//!    summary.
+//! 3. The node applies the update and replies with the update the peer lacks.
+//!
+//! Updates only ever add addresses and outputs, or spend outputs, so applying
+//! one again, or out of order, leaves the wallet unchanged: a spent output is
+//! never made spendable again by an update.
+
+use super::{AddressStore, Result, WalletDbError};
+use bincode::{deserialize, serialize};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fmt;
+use tw_chain::primitives::asset::Asset;
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Context the sync key is derived from the sync secret with
+const SYNC_KEY_CONTEXT: &[u8] = b"aiblock-wallet-sync";
+
+/// Message exchanged between the wallets, before sealing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WalletSyncMessage {
+    /// State of the sender wallet
+    Summary(WalletSyncSummary),
+    /// Changes the receiver wallet lacks, with the state of the sender wallet
+    /// when it expects the changes it lacks in return
+    Update {
+        update: WalletSyncUpdate,
+        summary: Option<WalletSyncSummary>,
+    },
+}
+
+/// Addresses and outputs held by a wallet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletSyncSummary {
+    pub addresses: BTreeSet<String>,
+    pub unspent: BTreeSet<OutPoint>,
+    pub spent: BTreeSet<OutPoint>,
+}
+
+/// Changes to apply to a wallet
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WalletSyncUpdate {
+    /// Key-pairs of the new addresses
+    pub addresses: Vec<(String, AddressStore)>,
+    /// New outputs, with their asset and address
+    pub outputs: Vec<(OutPoint, Asset, String)>,
+    /// Outputs spent
+    pub spent: Vec<OutPoint>,
+}
+
+impl WalletSyncUpdate {
+    /// Whether the update changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.outputs.is_empty() && self.spent.is_empty()
+    }
+}
+
+/// Sealed message, as sent over the comms layer
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedWalletSync {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl fmt::Debug for SealedWalletSync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SealedWalletSync({} bytes)", self.ciphertext.len())
+    }
+}
+
+/// Key sealing the messages of the wallets sharing a sync secret
+#[derive(Debug)]
+pub struct WalletSyncKey {
+    key: LessSafeKey,
+}
+
+impl WalletSyncKey {
+    /// Derive the key of a sync secret
+    ///
+    /// ### Arguments
+    ///
+    /// * `secret` - Sync secret shared by the wallets.
+    pub fn new(secret: &[u8]) -> Self {
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, secret), SYNC_KEY_CONTEXT);
+        let key = UnboundKey::new(&CHACHA20_POLY1305, tag.as_ref()).unwrap();
+        Self {
+            key: LessSafeKey::new(key),
+        }
+    }
+
+    /// Seal a message for the peer wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `message` - Message to seal.
+    pub fn seal(&self, message: &WalletSyncMessage) -> Result<SealedWalletSync> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| WalletDbError::SyncError("Random generation failed"))?;
+
+        let mut ciphertext = serialize(message)?;
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut ciphertext,
+            )
+            .map_err(|_| WalletDbError::SyncError("Encryption failed"))?;
+        Ok(SealedWalletSync {
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    /// Open a message sealed by the peer wallet
+    ///
+    /// ### Arguments
+    ///
+    /// * `sealed` - Message received.
+    pub fn open(&self, sealed: SealedWalletSync) -> Result<WalletSyncMessage> {
+        let nonce: [u8; NONCE_LEN] = sealed
+            .nonce
+            .try_into()
+            .map_err(|_| WalletDbError::SyncError("Invalid nonce"))?;
+
+        let mut content = sealed.ciphertext;
+        let content = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut content,
+            )
+            .map_err(|_| WalletDbError::SyncError("Message not sealed with the sync secret"))?;
+        Ok(deserialize(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks messages only open with the secret they were sealed with
+    fn sealed_message_opens_with_same_secret() {
+        //Arrange
+        let summary = WalletSyncSummary {
+            addresses: vec!["address".to_owned()].into_iter().collect(),
+            unspent: vec![OutPoint::new("tx_hash".to_owned(), 0)]
+                .into_iter()
+                .collect(),
+            spent: BTreeSet::new(),
+        };
+        let key = WalletSyncKey::new(b"sync secret");
+
+        //Act
+        let sealed = key
+            .seal(&WalletSyncMessage::Summary(summary.clone()))
+            .unwrap();
+        let opened = key.open(sealed.clone());
+        let other = WalletSyncKey::new(b"other secret").open(sealed.clone());
+        let mut tampered = sealed;
+        tampered.ciphertext[0] ^= 1;
+        let tampered = key.open(tampered);
+
+        //Assert
+        assert!(matches!(opened, Ok(WalletSyncMessage::Summary(s)) if s == summary));
+        assert!(matches!(other, Err(WalletDbError::SyncError(_))));
+        assert!(matches!(tampered, Err(WalletDbError::SyncError(_))));
+    }
+}
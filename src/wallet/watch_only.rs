@@ -0,0 +1,196 @@
+//! Addresses watched by a wallet without their secret keys.
+//!
+//! Addresses are imported as watch-only either directly or from their public
+//! key. The payments to them are saved like those to the wallet addresses and
+//! recorded in the transaction history, but their outputs are kept in the
+//! `WatchOnlyStore`, apart from the fund store, so they are never selected as
+//! payment inputs. Their outputs are replaced by the unspent ones each time the
+//! running total of the wallet is updated from a UTXO set covering them, which
+//! drops the outputs spent by their owner.
+
+use super::{Result, WalletDbError};
+use crate::db_utils::{SimpleDb, SimpleDbWriteBatch, DB_COL_DEFAULT};
+use crate::interfaces::UtxoFetchType;
+use bincode::{deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tw_chain::crypto::sign_ed25519::PublicKey;
+use tw_chain::primitives::asset::{Asset, AssetValues};
+use tw_chain::primitives::transaction::OutPoint;
+
+/// Storage key of the watch-only addresses and their outputs
+pub const WATCH_ONLY_KEY: &str = "WatchOnlyStore";
+
+/// Watch-only addresses with their unspent outputs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchOnlyStore {
+    /// Public key of each address, if it was imported from it
+    addresses: BTreeMap<String, Option<PublicKey>>,
+    /// Outputs paid to the addresses, with their asset and address
+    outputs: BTreeMap<OutPoint, (Asset, String)>,
+    running_total: AssetValues,
+}
+
+impl WatchOnlyStore {
+    /// Watch an address, returning false if it is already watched
+    ///
+    /// ### Arguments
+    ///
+    /// * `address`    - Address to watch.
+    /// * `public_key` - Public key of the address, if known.
+    pub fn add_address(&mut self, address: String, public_key: Option<PublicKey>) -> bool {
+        match self.addresses.get_mut(&address) {
+            Some(known) => {
+                if known.is_none() {
+                    *known = public_key;
+                }
+                false
+            }
+            None => {
+                self.addresses.insert(address, public_key);
+                true
+            }
+        }
+    }
+
+    /// Whether an address is watched
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address to check.
+    pub fn contains(&self, address: &str) -> bool {
+        self.addresses.contains_key(address)
+    }
+
+    pub fn addresses(&self) -> &BTreeMap<String, Option<PublicKey>> {
+        &self.addresses
+    }
+
+    pub fn outputs(&self) -> &BTreeMap<OutPoint, (Asset, String)> {
+        &self.outputs
+    }
+
+    pub fn running_total(&self) -> &AssetValues {
+        &self.running_total
+    }
+
+    /// Save an output paid to a watched address, returning false if already saved
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p`   - Output paid to the address.
+    /// * `asset`   - Asset of the output.
+    /// * `address` - Watched address.
+    pub fn store_output(&mut self, out_p: OutPoint, asset: Asset, address: String) -> bool {
+        if self.outputs.contains_key(&out_p) {
+            return false;
+        }
+        self.running_total.update_add(&asset);
+        self.outputs.insert(out_p, (asset, address));
+        true
+    }
+
+    /// Drop the outputs of the fetched addresses missing from their unspent outputs
+    ///
+    /// Returns the number of outputs dropped.
+    ///
+    /// ### Arguments
+    ///
+    /// * `fetched` - Addresses whose unspent outputs were fetched.
+    /// * `unspent` - Unspent outputs of the fetched addresses.
+    pub fn retain_unspent(
+        &mut self,
+        fetched: &UtxoFetchType,
+        unspent: &BTreeSet<OutPoint>,
+    ) -> usize {
+        let covered = |address: &String| match fetched {
+            UtxoFetchType::All => true,
+            UtxoFetchType::AnyOf(addresses) => addresses.contains(address),
+        };
+        let spent: Vec<_> = self
+            .outputs
+            .iter()
+            .filter(|(out_p, (_, address))| covered(address) && !unspent.contains(*out_p))
+            .map(|(out_p, _)| out_p.clone())
+            .collect();
+
+        for out_p in &spent {
+            if let Some((asset, _)) = self.outputs.remove(out_p) {
+                self.running_total.update_sub(&asset);
+            }
+        }
+        spent.len()
+    }
+}
+
+/// Check an address can be watched by a wallet holding the given key addresses
+///
+/// ### Arguments
+///
+/// * `address`       - Address to watch.
+/// * `key_addresses` - Addresses the wallet holds the keys of.
+pub fn check_watch_only_address(address: &str, key_addresses: &BTreeSet<String>) -> Result<()> {
+    if !wallet_core::is_valid_address(address) {
+        return Err(WalletDbError::KeyImportError("Invalid watch-only address"));
+    }
+    if key_addresses.contains(address) {
+        return Err(WalletDbError::KeyImportError(
+            "Address already held with its keys",
+        ));
+    }
+    Ok(())
+}
+
+/// Watch-only addresses and outputs of the wallet
+///
+/// ### Arguments
+///
+/// * `db` - Wallet database.
+pub fn get_watch_only_store(db: &SimpleDb) -> Result<WatchOnlyStore> {
+    match db.get_cf(DB_COL_DEFAULT, WATCH_ONLY_KEY)? {
+        Some(store) => Ok(deserialize(&store)?),
+        None => Ok(Default::default()),
+    }
+}
+
+/// Save the watch-only addresses and outputs of the wallet
+///
+/// ### Arguments
+///
+/// * `batch` - Write batch of the wallet database.
+/// * `store` - Watch-only addresses and outputs.
+pub fn set_watch_only_store(batch: &mut SimpleDbWriteBatch, store: &WatchOnlyStore) {
+    batch.put_cf(DB_COL_DEFAULT, WATCH_ONLY_KEY, serialize(store).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks the outputs of the fetched addresses are replaced by the unspent ones
+    fn watch_only_outputs_retain_unspent() {
+        //Arrange
+        let mut store = WatchOnlyStore::default();
+        let out_p = |n| OutPoint::new("tx_hash".to_owned(), n);
+        store.add_address("a".to_owned(), None);
+        store.add_address("b".to_owned(), None);
+        for (n, address) in [(0, "a"), (1, "a"), (2, "b")].iter() {
+            store.store_output(out_p(*n), Asset::token_u64(5), address.to_string());
+        }
+        let stored_again = store.store_output(out_p(0), Asset::token_u64(5), "a".to_owned());
+
+        //Act
+        let fetched = UtxoFetchType::AnyOf(vec!["a".to_owned()]);
+        let unspent = vec![out_p(1)].into_iter().collect();
+        let dropped = store.retain_unspent(&fetched, &unspent);
+
+        //Assert
+        let outputs: Vec<_> = store.outputs().keys().map(|o| o.n).collect();
+        assert!(!stored_again);
+        assert_eq!(dropped, 1);
+        assert_eq!(outputs, vec![1, 2]);
+        assert_eq!(store.running_total().tokens.0, 10);
+        assert!(!store.add_address("a".to_owned(), None));
+    }
+}
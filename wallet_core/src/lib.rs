@@ -0,0 +1,1088 @@
+//! # Wallet core
+//!
+//! Address construction, input signing and input selection shared by the node
+//! wallet and by browser and mobile wallets.
+//!
+//! The crate only relies on `alloc` and on the chain primitives, and gets the
+//! wallet content from an injected `WalletStore`, so it can be compiled to
+//! `wasm32` and used over any storage. With the `parallel` feature, the
+//! inputs of a payment are signed across the rayon thread pool, which needs
+//! the stores to be `Sync`, and with the `serde` feature the coin selection
+//! can be read from configurations.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::fmt;
+use core::fmt::Write;
+use ripemd::{Digest, Ripemd160};
+use tw_chain::constants::NETWORK_VERSION;
+use tw_chain::crypto::sha3_256;
+use tw_chain::crypto::sign_ed25519::{self as sign, PublicKey, SecretKey, Signature};
+use tw_chain::primitives::asset::{Asset, TokenAmount};
+use tw_chain::primitives::transaction::{OutPoint, Transaction, TxConstructor, TxOut};
+use tw_chain::utils::transaction_utils::{
+    construct_address_for, construct_payment_tx_ins, construct_tx_core,
+    construct_tx_in_signable_hash,
+};
+
+/// Length of a hex encoded address of the current version
+pub const ADDRESS_LEN: usize = 64;
+
+/// Length of a hex encoded address of the legacy address versions
+pub const LEGACY_ADDRESS_LEN: usize = 32;
+
+/// Length of a hex encoded checked address: version byte, network byte,
+/// address of the current version and checksum
+pub const CHECKED_ADDRESS_LEN: usize = 2 * (2 + ADDRESS_LEN / 2 + CHECKSUM_LEN);
+
+/// Length of the checksum of a checked address, in bytes
+const CHECKSUM_LEN: usize = 4;
+
+/// Maximum number of subsets tried by the branch and bound selection
+pub const BRANCH_AND_BOUND_MAX_TRIES: usize = 100_000;
+
+/// Result wrapper for wallet core errors
+pub type Result<T> = core::result::Result<T, WalletCoreError>;
+
+/// Inputs signed for a payment: constructors, total amount, and used outputs with their address
+pub type PaymentInputs = (Vec<TxConstructor>, Asset, Vec<(OutPoint, String)>);
+
+/// Inputs signed from outputs: constructors, and used outputs with their address
+pub type SignedInputs = (Vec<TxConstructor>, Vec<(OutPoint, String)>);
+
+/// Errors that occur during wallet core operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletCoreError {
+    /// The spendable outputs do not cover the required asset
+    InsufficientFunds,
+    /// The address of an output is unknown to the store
+    OutputAddressMissing(OutPoint),
+    /// The keys of an address are unknown to the store
+    AddressKeysMissing(String),
+    /// The store could not sign an input
+    SigningFailed(String),
+    /// The string is not an address that can be paid to
+    InvalidAddress(AddressError),
+}
+
+impl fmt::Display for WalletCoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InsufficientFunds => write!(f, "Insufficient funds"),
+            Self::OutputAddressMissing(out_p) => write!(f, "Output address missing: {out_p:?}"),
+            Self::AddressKeysMissing(address) => write!(f, "Address keys missing: {address}"),
+            Self::SigningFailed(reason) => write!(f, "Signing failed: {reason}"),
+            Self::InvalidAddress(err) => write!(f, "Invalid address: {err}"),
+        }
+    }
+}
+
+/// Order in which spendable outputs are selected to cover a payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CoinSelectionPolicy {
+    /// Outputs in outpoint order
+    #[default]
+    OutputOrder,
+    /// Largest outputs first, for the fewest inputs
+    LargestFirst,
+    /// Smallest outputs first, for the smallest change output
+    SmallestFirst,
+    /// Outputs adding up to the exact amount, for no change output,
+    /// largest first if there are none
+    BranchAndBound,
+    /// Outputs in random order, so payments do not reveal which outputs are held
+    Random,
+}
+
+/// Selection of the spendable outputs covering a payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct CoinSelection {
+    pub policy: CoinSelectionPolicy,
+    /// Token outputs of at most this amount are dust, none if 0
+    pub dust_threshold: u64,
+    /// Dust outputs consolidated into the change of a token payment
+    pub max_dust_inputs: usize,
+}
+
+impl CoinSelection {
+    /// Whether a spendable output is dust to consolidate
+    ///
+    /// ### Arguments
+    ///
+    /// * `asset` - Asset of the output.
+    fn is_dust(&self, asset: &Asset) -> bool {
+        matches!(asset, Asset::Token(amount) if amount.0 <= self.dust_threshold)
+    }
+}
+
+/// Keys of a wallet address
+#[derive(Debug, Clone)]
+pub struct AddressKeys {
+    pub public_key: PublicKey,
+    pub secret_key: SecretKey,
+    pub address_version: Option<u64>,
+}
+
+/// Stores shared across the signing threads with the `parallel` feature
+#[cfg(feature = "parallel")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "parallel")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+/// Stores shared across the signing threads with the `parallel` feature
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "parallel"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Content of a wallet needed to construct payments
+pub trait WalletStore: MaybeSync {
+    /// Outputs that can be spent, with their asset
+    fn spendable_outputs(&self) -> BTreeMap<OutPoint, Asset>;
+
+    /// Address an output was paid to
+    ///
+    /// ### Arguments
+    ///
+    /// * `out_p` - Output held by the wallet.
+    fn output_address(&self, out_p: &OutPoint) -> Option<String>;
+
+    /// Keys of an address
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address held by the wallet.
+    fn address_keys(&self, address: &str) -> Option<AddressKeys>;
+
+    /// Sign the digest of an input with the keys of an address
+    ///
+    /// Signs with the secret key by default. Stores keeping their secret keys
+    /// in a signing service forward the digest to it instead.
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address the input was paid to.
+    /// * `keys`    - Keys of the address.
+    /// * `digest`  - Signable hash of the input.
+    fn sign_digest(&self, address: &str, keys: &AddressKeys, digest: &[u8]) -> Result<Signature> {
+        let _ = address;
+        Ok(sign::sign_detached(digest, &keys.secret_key))
+    }
+}
+
+/// Construct the address of a public key
+///
+/// ### Arguments
+///
+/// * `public_key`      - Public key of the address.
+/// * `address_version` - Address version, None for the current version.
+pub fn construct_address(public_key: &PublicKey, address_version: Option<u64>) -> String {
+    construct_address_for(public_key, address_version)
+}
+
+/// Encode an address of the current version as a checked address
+///
+/// The checked address starts with the network version it is encoded under
+/// and the network byte, and ends with a checksum, so a typo or an address of
+/// another network is detected when it is paid to. None is returned if the
+/// address is not of the current version.
+///
+/// ### Arguments
+///
+/// * `address` - Address of the current version.
+/// * `network` - Network byte of the network the address belongs to.
+pub fn encode_checked_address(address: &str, network: u8) -> Option<String> {
+    if !is_valid_address(address) {
+        return None;
+    }
+    let mut payload = vec![NETWORK_VERSION as u8, network];
+    payload.extend(decode_hex(address));
+    payload.extend(checked_address_checksum(&payload));
+    Some(encode_hex(&payload))
+}
+
+/// Check a string has the format of an address of the current version
+///
+/// ### Arguments
+///
+/// * `address` - Address to check.
+pub fn is_valid_address(address: &str) -> bool {
+    PaymentAddress::validate(address).is_ok_and(|a| a.format() == AddressFormat::Current)
+}
+
+/// Format of an address, by the address versions it is constructed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    /// Address of the legacy address versions, still held by older outputs
+    Legacy,
+    /// Address of the current version, or of a spending condition
+    Current,
+    /// Address of the current version, encoded with its network and a checksum
+    Checked,
+}
+
+/// Reason a string is not an address that can be paid to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    /// Not the length of an address of any version
+    InvalidLength(usize),
+    /// Not a lowercase hex digit, at the given byte position
+    InvalidCharacter { position: usize, character: char },
+    /// Checked address encoded under a newer network version
+    UnsupportedVersion(u8),
+    /// Checked address of another network
+    WrongNetwork { expected: u8, found: u8 },
+    /// Checked address whose checksum does not match, e.g. after a typo
+    InvalidChecksum,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "expected {ADDRESS_LEN} hex characters, {CHECKED_ADDRESS_LEN} for a checked address or {LEGACY_ADDRESS_LEN} for a legacy address, found {len}"
+            ),
+            Self::InvalidCharacter {
+                position,
+                character,
+            } => write!(
+                f,
+                "invalid character {character:?} at position {position}, addresses are lowercase hex"
+            ),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "address of network version {version}, newer than {NETWORK_VERSION}"
+            ),
+            Self::WrongNetwork { expected, found } => write!(
+                f,
+                "address of network {found}, expected network {expected}"
+            ),
+            Self::InvalidChecksum => write!(f, "checksum mismatch, the address has a typo"),
+        }
+    }
+}
+
+/// Address checked to be one that can be paid to
+///
+/// Addresses are the hex encoded hash of a public key or spending condition.
+/// Checked addresses also hold the network version they are encoded under, a
+/// network byte and a checksum, which are checked. Addresses of the current
+/// and legacy versions have neither, so only their length and characters are
+/// checked: a typo keeping both goes undetected. Upper case digits are rejected
+/// as outputs are matched against the lower case addresses the keys construct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentAddress {
+    address: String,
+    format: AddressFormat,
+    network: Option<u8>,
+}
+
+impl PaymentAddress {
+    /// Check an address can be paid to
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address paid to.
+    pub fn validate(address: &str) -> core::result::Result<Self, AddressError> {
+        let invalid = address
+            .char_indices()
+            .find(|(_, c)| !matches!(c, '0'..='9' | 'a'..='f'));
+        if let Some((position, character)) = invalid {
+            return Err(AddressError::InvalidCharacter {
+                position,
+                character,
+            });
+        }
+
+        let format = match address.len() {
+            ADDRESS_LEN => AddressFormat::Current,
+            LEGACY_ADDRESS_LEN => AddressFormat::Legacy,
+            CHECKED_ADDRESS_LEN => return Self::decode_checked(address),
+            len => return Err(AddressError::InvalidLength(len)),
+        };
+        Ok(Self {
+            address: address.into(),
+            format,
+            network: None,
+        })
+    }
+
+    /// Check an address can be paid to on a network
+    ///
+    /// Checked addresses of another network are rejected, the other addresses
+    /// not holding their network.
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Address paid to.
+    /// * `network` - Network byte of the network paying it.
+    pub fn validate_for_network(
+        address: &str,
+        network: u8,
+    ) -> core::result::Result<Self, AddressError> {
+        let payment_address = Self::validate(address)?;
+        match payment_address.network {
+            Some(found) if found != network => Err(AddressError::WrongNetwork {
+                expected: network,
+                found,
+            }),
+            _ => Ok(payment_address),
+        }
+    }
+
+    /// Decode a checked address, of lowercase hex digits
+    ///
+    /// ### Arguments
+    ///
+    /// * `address` - Checked address.
+    fn decode_checked(address: &str) -> core::result::Result<Self, AddressError> {
+        let bytes = decode_hex(address);
+        let (payload, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if payload[0] > NETWORK_VERSION as u8 {
+            return Err(AddressError::UnsupportedVersion(payload[0]));
+        }
+        if checked_address_checksum(payload) != checksum {
+            return Err(AddressError::InvalidChecksum);
+        }
+        Ok(Self {
+            address: encode_hex(&payload[2..]),
+            format: AddressFormat::Checked,
+            network: Some(payload[1]),
+        })
+    }
+
+    /// Address paid to on chain, decoded for a checked address
+    pub fn as_str(&self) -> &str {
+        &self.address
+    }
+
+    pub fn format(&self) -> AddressFormat {
+        self.format
+    }
+
+    /// Network byte of a checked address
+    pub fn network(&self) -> Option<u8> {
+        self.network
+    }
+
+    pub fn into_string(self) -> String {
+        self.address
+    }
+}
+
+/// Checksum of the version byte, network byte and address of a checked address
+///
+/// The payload is hashed with SHA3-256 then RIPEMD160, the first bytes of the
+/// RIPEMD160 hash being the checksum.
+///
+/// ### Arguments
+///
+/// * `payload` - Checked address without its checksum.
+fn checked_address_checksum(payload: &[u8]) -> Vec<u8> {
+    let hash = Ripemd160::digest(sha3_256::digest(payload));
+    hash[..CHECKSUM_LEN].to_vec()
+}
+
+/// Decode a string of lowercase hex digits, of even length
+///
+/// ### Arguments
+///
+/// * `hex` - String to decode.
+fn decode_hex(hex: &str) -> Vec<u8> {
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => c - b'0',
+        _ => c - b'a' + 10,
+    };
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| (digit(pair[0]) << 4) | digit(pair[1]))
+        .collect()
+}
+
+/// Encode bytes as a string of lowercase hex digits
+///
+/// ### Arguments
+///
+/// * `bytes` - Bytes to encode.
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// Sign an output to spend it as a transaction input
+///
+/// ### Arguments
+///
+/// * `out_p` - Output to spend.
+/// * `keys`  - Keys of the address the output was paid to.
+pub fn sign_input(out_p: OutPoint, keys: &AddressKeys) -> TxConstructor {
+    let hash_to_sign = construct_tx_in_signable_hash(&out_p);
+    let signature = sign::sign_detached(hash_to_sign.as_bytes(), &keys.secret_key);
+    input_constructor(out_p, signature, keys)
+}
+
+/// Construct the transaction input spending an output with its signature
+///
+/// ### Arguments
+///
+/// * `out_p`     - Output to spend.
+/// * `signature` - Signature of the signable hash of the output.
+/// * `keys`      - Keys of the address the output was paid to.
+pub fn input_constructor(
+    out_p: OutPoint,
+    signature: Signature,
+    keys: &AddressKeys,
+) -> TxConstructor {
+    TxConstructor {
+        previous_out: out_p,
+        signatures: vec![signature],
+        pub_keys: vec![keys.public_key],
+        address_version: keys.address_version,
+    }
+}
+
+/// Select outputs covering the required asset, in output order
+///
+/// ### Arguments
+///
+/// * `outputs`        - Outputs that can be spent, with their asset.
+/// * `asset_required` - Asset to cover.
+pub fn select_inputs(
+    outputs: BTreeMap<OutPoint, Asset>,
+    asset_required: &Asset,
+) -> Result<(Vec<OutPoint>, Asset)> {
+    select_inputs_in_order(outputs, asset_required)
+}
+
+/// Select outputs covering the required asset, with the given coin selection
+///
+/// Once the asset is covered, up to `max_dust_inputs` dust outputs not selected
+/// yet are added to a token payment, so their tokens are merged into the change.
+///
+/// ### Arguments
+///
+/// * `outputs`        - Outputs that can be spent, with their asset.
+/// * `asset_required` - Asset to cover.
+/// * `selection`      - Coin selection policy and dust consolidation.
+/// * `seed`           - Seed of the order of the `Random` policy.
+pub fn select_inputs_with(
+    outputs: BTreeMap<OutPoint, Asset>,
+    asset_required: &Asset,
+    selection: &CoinSelection,
+    seed: u64,
+) -> Result<(Vec<OutPoint>, Asset)> {
+    let mut ordered: Vec<(OutPoint, Asset)> = outputs.into_iter().collect();
+    let exact = match selection.policy {
+        CoinSelectionPolicy::OutputOrder => None,
+        CoinSelectionPolicy::LargestFirst => {
+            ordered.sort_by_key(|(_, asset)| Reverse(asset.token_amount().0));
+            None
+        }
+        CoinSelectionPolicy::SmallestFirst => {
+            ordered.sort_by_key(|(_, asset)| asset.token_amount().0);
+            None
+        }
+        CoinSelectionPolicy::BranchAndBound => {
+            ordered.sort_by_key(|(_, asset)| Reverse(asset.token_amount().0));
+            match asset_required {
+                Asset::Token(amount) => branch_and_bound(&ordered, amount.0),
+                _ => None,
+            }
+        }
+        CoinSelectionPolicy::Random => {
+            shuffle(&mut ordered, seed);
+            None
+        }
+    };
+
+    let (mut selected, mut amount_made) = match exact {
+        Some(selected) => (selected, asset_required.clone()),
+        None => select_inputs_in_order(ordered.iter().cloned(), asset_required)?,
+    };
+
+    if matches!(asset_required, Asset::Token(_)) && selection.dust_threshold > 0 {
+        let taken: BTreeSet<OutPoint> = selected.iter().cloned().collect();
+        let dust = ordered
+            .into_iter()
+            .filter(|(out_p, asset)| selection.is_dust(asset) && !taken.contains(out_p))
+            .take(selection.max_dust_inputs);
+        for (out_p, asset) in dust {
+            if amount_made.add_assign(&asset) {
+                selected.push(out_p);
+            }
+        }
+    }
+
+    Ok((selected, amount_made))
+}
+
+/// Select outputs covering the required asset, in the order given
+///
+/// ### Arguments
+///
+/// * `outputs`        - Outputs that can be spent, with their asset.
+/// * `asset_required` - Asset to cover.
+fn select_inputs_in_order(
+    outputs: impl IntoIterator<Item = (OutPoint, Asset)>,
+    asset_required: &Asset,
+) -> Result<(Vec<OutPoint>, Asset)> {
+    let mut selected = Vec::new();
+    let mut amount_made = Asset::default_of_type(asset_required);
+
+    for (out_p, amount) in outputs {
+        if amount_made.add_assign(&amount) {
+            selected.push(out_p);
+        }
+        if let Some(true) = amount_made.is_greater_or_equal_to(asset_required) {
+            break;
+        }
+    }
+
+    match amount_made.is_greater_or_equal_to(asset_required) {
+        Some(true) => Ok((selected, amount_made)),
+        _ => Err(WalletCoreError::InsufficientFunds),
+    }
+}
+
+/// Outputs adding up to exactly the target amount, if found within the tries
+///
+/// ### Arguments
+///
+/// * `outputs` - Outputs that can be spent, largest first.
+/// * `target`  - Token amount to add up to.
+fn branch_and_bound(outputs: &[(OutPoint, Asset)], target: u64) -> Option<Vec<OutPoint>> {
+    let amounts: Vec<u64> = outputs
+        .iter()
+        .map(|(_, asset)| match asset {
+            Asset::Token(amount) => amount.0,
+            _ => 0,
+        })
+        .collect();
+
+    let mut remaining = vec![0u64; amounts.len() + 1];
+    for index in (0..amounts.len()).rev() {
+        remaining[index] = remaining[index + 1].saturating_add(amounts[index]);
+    }
+
+    let mut chosen = Vec::new();
+    let mut tries = 0;
+    if target > 0 && search_exact(&amounts, &remaining, target, 0, &mut chosen, &mut tries) {
+        Some(chosen.into_iter().map(|i| outputs[i].0.clone()).collect())
+    } else {
+        None
+    }
+}
+
+/// Depth first search of the amounts from `index` adding up to the target
+///
+/// Each amount is first included then excluded, and branches whose remaining
+/// amounts cannot reach the target are cut.
+///
+/// ### Arguments
+///
+/// * `amounts`   - Amounts of the outputs.
+/// * `remaining` - Sum of the amounts from each index on.
+/// * `target`    - Amount left to add up to.
+/// * `index`     - Index of the next amount to include or exclude.
+/// * `chosen`    - Indices of the amounts included.
+/// * `tries`     - Number of branches tried.
+fn search_exact(
+    amounts: &[u64],
+    remaining: &[u64],
+    target: u64,
+    index: usize,
+    chosen: &mut Vec<usize>,
+    tries: &mut usize,
+) -> bool {
+    if target == 0 {
+        return true;
+    }
+    *tries += 1;
+    if index == amounts.len() || remaining[index] < target || *tries > BRANCH_AND_BOUND_MAX_TRIES {
+        return false;
+    }
+
+    let amount = amounts[index];
+    if amount > 0 && amount <= target {
+        chosen.push(index);
+        if search_exact(
+            amounts,
+            remaining,
+            target - amount,
+            index + 1,
+            chosen,
+            tries,
+        ) {
+            return true;
+        }
+        chosen.pop();
+    }
+    search_exact(amounts, remaining, target, index + 1, chosen, tries)
+}
+
+/// Shuffle the outputs with a xorshift generator of the given seed
+///
+/// ### Arguments
+///
+/// * `outputs` - Outputs to shuffle.
+/// * `seed`    - Seed of the generator.
+fn shuffle<T>(outputs: &mut [T], seed: u64) {
+    let mut state = seed | 1;
+    for index in (1..outputs.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        outputs.swap(index, (state % (index as u64 + 1)) as usize);
+    }
+}
+
+/// Sign the given outputs of the store as inputs
+///
+/// The keys of the outputs are independent, so with the `parallel` feature
+/// the outputs are signed across the rayon thread pool, in a single pass.
+///
+/// ### Arguments
+///
+/// * `store`   - Wallet holding the outputs and their keys.
+/// * `outputs` - Outputs to sign.
+pub fn sign_inputs<S: WalletStore>(store: &S, outputs: Vec<OutPoint>) -> Result<SignedInputs> {
+    #[cfg(feature = "parallel")]
+    let signed: Vec<_> = {
+        use rayon::prelude::*;
+        outputs
+            .into_par_iter()
+            .map(|out_p| sign_output(store, out_p))
+            .collect::<Result<_>>()?
+    };
+    #[cfg(not(feature = "parallel"))]
+    let signed: Vec<_> = outputs
+        .into_iter()
+        .map(|out_p| sign_output(store, out_p))
+        .collect::<Result<_>>()?;
+
+    Ok(signed.into_iter().unzip())
+}
+
+/// Sign one output of the store as an input
+///
+/// ### Arguments
+///
+/// * `store` - Wallet holding the output and its keys.
+/// * `out_p` - Output to sign.
+fn sign_output<S: WalletStore>(
+    store: &S,
+    out_p: OutPoint,
+) -> Result<(TxConstructor, (OutPoint, String))> {
+    let address = store
+        .output_address(&out_p)
+        .ok_or_else(|| WalletCoreError::OutputAddressMissing(out_p.clone()))?;
+    let keys = store
+        .address_keys(&address)
+        .ok_or_else(|| WalletCoreError::AddressKeysMissing(address.clone()))?;
+
+    let hash_to_sign = construct_tx_in_signable_hash(&out_p);
+    let signature = store.sign_digest(&address, &keys, hash_to_sign.as_bytes())?;
+
+    Ok((
+        input_constructor(out_p.clone(), signature, &keys),
+        (out_p, address),
+    ))
+}
+
+/// Select and sign spendable outputs of the store covering the required asset
+///
+/// ### Arguments
+///
+/// * `store`          - Wallet holding the outputs and their keys.
+/// * `asset_required` - Asset to cover.
+pub fn fetch_inputs_for_payment<S: WalletStore>(
+    store: &S,
+    asset_required: &Asset,
+) -> Result<PaymentInputs> {
+    fetch_inputs_for_payment_with(store, asset_required, &CoinSelection::default(), 0)
+}
+
+/// Select, with the given coin selection, and sign spendable outputs of the store covering the required asset
+///
+/// ### Arguments
+///
+/// * `store`          - Wallet holding the outputs and their keys.
+/// * `asset_required` - Asset to cover.
+/// * `selection`      - Coin selection policy and dust consolidation.
+/// * `seed`           - Seed of the order of the `Random` policy.
+pub fn fetch_inputs_for_payment_with<S: WalletStore>(
+    store: &S,
+    asset_required: &Asset,
+    selection: &CoinSelection,
+    seed: u64,
+) -> Result<PaymentInputs> {
+    let outputs = store.spendable_outputs();
+    let (outputs, amount_made) = select_inputs_with(outputs, asset_required, selection, seed)?;
+    let (tx_cons, tx_used) = sign_inputs(store, outputs)?;
+    Ok((tx_cons, amount_made, tx_used))
+}
+
+/// Sign the supplied token outputs of the store, whatever their total
+///
+/// ### Arguments
+///
+/// * `store`   - Wallet holding the outputs and their keys.
+/// * `outputs` - Outputs to spend, with their asset.
+pub fn fetch_inputs_from_supplied_outputs<S: WalletStore>(
+    store: &S,
+    outputs: Vec<(OutPoint, Asset)>,
+) -> Result<PaymentInputs> {
+    let mut selected = Vec::new();
+    let mut amount_made = Asset::Token(TokenAmount(0));
+
+    for (out_p, amount) in outputs {
+        if amount_made.add_assign(&amount) {
+            selected.push(out_p);
+        }
+    }
+
+    let (tx_cons, tx_used) = sign_inputs(store, selected)?;
+    Ok((tx_cons, amount_made, tx_used))
+}
+
+/// Construct a signed payment from the spendable outputs of the store
+///
+/// Returns the transaction and the outputs it spends, with their address.
+///
+/// ### Arguments
+///
+/// * `store`          - Wallet holding the outputs and their keys.
+/// * `tx_outs`        - Outputs of the payment.
+/// * `asset_required` - Asset paid by the outputs.
+/// * `excess_address` - Address receiving the change.
+pub fn construct_payment<S: WalletStore>(
+    store: &S,
+    mut tx_outs: Vec<TxOut>,
+    asset_required: &Asset,
+    excess_address: String,
+) -> Result<(Transaction, Vec<(OutPoint, String)>)> {
+    let (tx_cons, amount_made, tx_used) = fetch_inputs_for_payment(store, asset_required)?;
+
+    if let Some(excess) = amount_made.get_excess(asset_required) {
+        tx_outs.push(TxOut::new_asset(excess_address, excess, None));
+    }
+
+    let tx_ins = construct_payment_tx_ins(tx_cons);
+    Ok((construct_tx_core(tx_ins, tx_outs, None), tx_used))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+
+    /// Wallet content held in memory
+    #[derive(Default)]
+    struct MemoryStore {
+        outputs: BTreeMap<OutPoint, (Asset, String)>,
+        keys: BTreeMap<String, AddressKeys>,
+    }
+
+    impl MemoryStore {
+        fn with_outputs(amounts: &[u64]) -> Self {
+            let mut store = Self::default();
+            for (index, amount) in amounts.iter().enumerate() {
+                let (public_key, secret_key) = sign::gen_keypair();
+                let address = construct_address(&public_key, None);
+                let out_p = OutPoint::new("000000".to_owned(), index as i32);
+                let asset = Asset::Token(TokenAmount(*amount));
+
+                store.outputs.insert(out_p, (asset, address.clone()));
+                store.keys.insert(
+                    address,
+                    AddressKeys {
+                        public_key,
+                        secret_key,
+                        address_version: None,
+                    },
+                );
+            }
+            store
+        }
+    }
+
+    impl WalletStore for MemoryStore {
+        fn spendable_outputs(&self) -> BTreeMap<OutPoint, Asset> {
+            let outputs = self.outputs.iter();
+            outputs.map(|(o, (a, _))| (o.clone(), a.clone())).collect()
+        }
+
+        fn output_address(&self, out_p: &OutPoint) -> Option<String> {
+            self.outputs.get(out_p).map(|(_, address)| address.clone())
+        }
+
+        fn address_keys(&self, address: &str) -> Option<AddressKeys> {
+            self.keys.get(address).cloned()
+        }
+    }
+
+    #[test]
+    /// Checks each output is signed with its own keys, in output order
+    fn sign_inputs_keeps_output_order() {
+        let store = MemoryStore::with_outputs(&[1; 64]);
+        let outputs: Vec<_> = store.outputs.keys().cloned().collect();
+
+        let (tx_cons, tx_used) = sign_inputs(&store, outputs.clone()).unwrap();
+
+        let used: Vec<_> = tx_used.iter().map(|(out_p, _)| out_p.clone()).collect();
+        assert_eq!(used, outputs);
+        for (tx_con, (out_p, address)) in tx_cons.iter().zip(&tx_used) {
+            let keys = store.address_keys(address).unwrap();
+            let hash = construct_tx_in_signable_hash(out_p);
+            assert_eq!(&tx_con.previous_out, out_p);
+            assert_eq!(tx_con.pub_keys, vec![keys.public_key]);
+            assert!(sign::verify_detached(
+                &tx_con.signatures[0],
+                hash.as_bytes(),
+                &keys.public_key
+            ));
+        }
+    }
+
+    #[test]
+    /// Checks inputs are selected in order until the required amount is covered
+    fn select_inputs_covers_required() {
+        let outputs = MemoryStore::with_outputs(&[3, 4, 5]).spendable_outputs();
+
+        let selected = select_inputs(outputs.clone(), &Asset::token_u64(6));
+        let insufficient = select_inputs(outputs, &Asset::token_u64(13));
+
+        let (selected, amount_made) = selected.unwrap();
+        assert_eq!(selected.len(), 2);
+        assert_eq!(amount_made, Asset::token_u64(7));
+        assert_eq!(insufficient, Err(WalletCoreError::InsufficientFunds));
+    }
+
+    #[test]
+    /// Checks each coin selection policy picks its own outputs
+    fn select_inputs_with_policies() {
+        let outputs = MemoryStore::with_outputs(&[4, 9, 1, 5, 2]).spendable_outputs();
+        let select = |policy| {
+            let selection = CoinSelection {
+                policy,
+                ..Default::default()
+            };
+            let (selected, amount_made) =
+                select_inputs_with(outputs.clone(), &Asset::token_u64(7), &selection, 3).unwrap();
+            let mut indices: Vec<_> = selected.iter().map(|o| o.n).collect();
+            indices.sort_unstable();
+            (indices, amount_made)
+        };
+
+        let (random, random_made) = select(CoinSelectionPolicy::Random);
+
+        assert_eq!(
+            select(CoinSelectionPolicy::OutputOrder),
+            (vec![0, 1], Asset::token_u64(13))
+        );
+        assert_eq!(
+            select(CoinSelectionPolicy::LargestFirst),
+            (vec![1], Asset::token_u64(9))
+        );
+        assert_eq!(
+            select(CoinSelectionPolicy::SmallestFirst),
+            (vec![0, 2, 4], Asset::token_u64(7))
+        );
+        assert_eq!(
+            select(CoinSelectionPolicy::BranchAndBound),
+            (vec![3, 4], Asset::token_u64(7))
+        );
+        assert_eq!(
+            random_made.is_greater_or_equal_to(&Asset::token_u64(7)),
+            Some(true)
+        );
+        assert!(!random.is_empty());
+    }
+
+    #[test]
+    /// Checks dust outputs are added to the selected outputs, up to the maximum
+    fn select_inputs_with_consolidates_dust() {
+        let outputs = MemoryStore::with_outputs(&[10, 1, 2, 1, 50]).spendable_outputs();
+        let selection = CoinSelection {
+            policy: CoinSelectionPolicy::LargestFirst,
+            dust_threshold: 1,
+            max_dust_inputs: 1,
+        };
+
+        let (selected, amount_made) =
+            select_inputs_with(outputs.clone(), &Asset::token_u64(20), &selection, 0).unwrap();
+        let insufficient = select_inputs_with(outputs, &Asset::token_u64(65), &selection, 0);
+
+        let indices: Vec<_> = selected.iter().map(|o| o.n).collect();
+        assert_eq!(indices, vec![4, 1]);
+        assert_eq!(amount_made, Asset::token_u64(51));
+        assert_eq!(insufficient, Err(WalletCoreError::InsufficientFunds));
+    }
+
+    #[test]
+    /// Checks a payment spends enough signed inputs and returns the change
+    fn construct_payment_with_change() {
+        let store = MemoryStore::with_outputs(&[3, 4, 5]);
+        let tx_out = TxOut::new_token_amount("payee".to_owned(), TokenAmount(6), None);
+
+        let (tx, used) = construct_payment(
+            &store,
+            vec![tx_out],
+            &Asset::token_u64(6),
+            "change".to_owned(),
+        )
+        .unwrap();
+
+        let outs: Vec<_> = tx.outputs.iter().map(|o| o.value.clone()).collect();
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(used.len(), 2);
+        assert_eq!(outs, vec![Asset::token_u64(6), Asset::token_u64(1)]);
+        assert_eq!(tx.outputs[1].script_public_key.as_deref(), Some("change"));
+    }
+
+    #[test]
+    /// Checks constructed addresses are valid, and other strings are not
+    fn address_validity() {
+        let (public_key, _) = sign::gen_keypair();
+        let address = construct_address(&public_key, None);
+
+        assert!(is_valid_address(&address));
+        assert!(!is_valid_address(&address[1..]));
+        assert!(!is_valid_address(&address.replace(&address[..1], "g")));
+    }
+
+    #[test]
+    /// Checks addresses of the legacy and current versions can be paid to, and typos are reported
+    /// unless they keep the length and hex digits, these addresses having no checksum
+    fn payment_address_validation() {
+        let (public_key, _) = sign::gen_keypair();
+        let current = construct_address(&public_key, None);
+        let legacy = &current[..LEGACY_ADDRESS_LEN];
+        let digit = if &current[..1] == "0" { "1" } else { "0" };
+        let undetected_typo = format!("{digit}{}", &current[1..]);
+        let format = |a: &str| PaymentAddress::validate(a).map(|a| a.format());
+
+        assert_eq!(format(&current), Ok(AddressFormat::Current));
+        assert_eq!(format(legacy), Ok(AddressFormat::Legacy));
+        assert!(!is_valid_address(legacy));
+        assert_eq!(
+            format(&current[1..]),
+            Err(AddressError::InvalidLength(ADDRESS_LEN - 1))
+        );
+        assert_eq!(
+            format(&format!("{}o", &current[1..])),
+            Err(AddressError::InvalidCharacter {
+                position: ADDRESS_LEN - 1,
+                character: 'o'
+            })
+        );
+        assert!(format(&current.to_uppercase()).is_err());
+        assert_eq!(format(&undetected_typo), Ok(AddressFormat::Current));
+    }
+
+    #[test]
+    /// Checks checked addresses decode to the address paid to, and typos and other networks are reported
+    fn checked_address_validation() {
+        let (public_key, _) = sign::gen_keypair();
+        let current = construct_address(&public_key, None);
+        let checked = encode_checked_address(&current, 1).unwrap();
+        let typo_at = CHECKED_ADDRESS_LEN / 2;
+        let typo = if &checked[typo_at..typo_at + 1] == "0" {
+            "1"
+        } else {
+            "0"
+        };
+        let typo = format!("{}{typo}{}", &checked[..typo_at], &checked[typo_at + 1..]);
+        let newer = format!("{:02x}{}", NETWORK_VERSION + 1, &checked[2..]);
+
+        let decoded = PaymentAddress::validate_for_network(&checked, 1).unwrap();
+
+        assert_eq!(checked.len(), CHECKED_ADDRESS_LEN);
+        assert_eq!(decoded.as_str(), current);
+        assert_eq!(decoded.format(), AddressFormat::Checked);
+        assert_eq!(decoded.network(), Some(1));
+        assert_eq!(
+            PaymentAddress::validate_for_network(&checked, 0),
+            Err(AddressError::WrongNetwork {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            PaymentAddress::validate(&typo),
+            Err(AddressError::InvalidChecksum)
+        );
+        assert_eq!(
+            PaymentAddress::validate(&newer),
+            Err(AddressError::UnsupportedVersion(NETWORK_VERSION as u8 + 1))
+        );
+        assert!(PaymentAddress::validate_for_network(&current, 0).is_ok());
+        assert!(encode_checked_address(&current[..LEGACY_ADDRESS_LEN], 1).is_none());
+        assert_eq!(encode_hex(&checked_address_checksum(&[0; 34])), "bbf53896");
+    }
+
+    /// Store whose signing service is unavailable
+    struct FailingSignerStore(MemoryStore);
+
+    impl WalletStore for FailingSignerStore {
+        fn spendable_outputs(&self) -> BTreeMap<OutPoint, Asset> {
+            self.0.spendable_outputs()
+        }
+
+        fn output_address(&self, out_p: &OutPoint) -> Option<String> {
+            self.0.output_address(out_p)
+        }
+
+        fn address_keys(&self, address: &str) -> Option<AddressKeys> {
+            self.0.address_keys(address)
+        }
+
+        fn sign_digest(&self, _: &str, _: &AddressKeys, _: &[u8]) -> Result<Signature> {
+            Err(WalletCoreError::SigningFailed("unavailable".to_owned()))
+        }
+    }
+
+    #[test]
+    /// Checks signing failures of the store are reported
+    fn sign_inputs_signing_failed() {
+        let store = FailingSignerStore(MemoryStore::with_outputs(&[3]));
+
+        let result = fetch_inputs_for_payment(&store, &Asset::token_u64(1));
+
+        assert!(matches!(result, Err(WalletCoreError::SigningFailed(_))));
+    }
+
+    #[test]
+    /// Checks selected inputs with unknown keys are reported
+    fn sign_inputs_missing_keys() {
+        let mut store = MemoryStore::with_outputs(&[3]);
+        store.keys.clear();
+
+        let result = fetch_inputs_for_payment(&store, &Asset::token_u64(1));
+
+        assert!(matches!(
+            result,
+            Err(WalletCoreError::AddressKeysMissing(_))
+        ));
+    }
+}